@@ -0,0 +1,408 @@
+use std::{
+    sync::{Mutex, RwLock},
+    time::{Duration, Instant},
+};
+
+use anyhow::Result;
+use uuid::Uuid;
+
+use crate::{
+    broker_lifecycle::BrokerLifecycleManager,
+    config::BrokerConfig,
+    config_registry::{ConfigResolver, LogConfig},
+    controller::{self, BrokerHeartbeatManager},
+    log_manager::{LogManager, TopicPartition},
+    metadata::{random_uuid, MetaProperties, MetadataImage},
+    protocol::{
+        cluster_metadata::{MetadataRecord, PartitionChangeRecord},
+        fetch::SnapshotId,
+        primitive::Serializable,
+    },
+    quota::{self, QuotaManager, QuotaType, ReplicationQuotaType},
+    raft::RaftManager,
+    remote_storage::RemoteLogManager,
+    replica_manager::ReplicaManager,
+    security::{
+        acl::{AclOperation, ResourceType},
+        authorizer::{AclAuthorizer, Action, Authorizer},
+    },
+    server::ConnectionContext,
+    share_group::ShareGroupCoordinator,
+    share_partition::SharePartitionManager,
+};
+
+/// Progress of the image through the committed metadata log.
+struct AppliedMetadata {
+    /// Offset up to which the log is applied
+    offset: i64,
+    /// Epoch of the last batch applied
+    epoch: i32,
+    /// Timestamp of the last batch applied
+    last_timestamp: i64,
+    /// Size of the record values applied since the last snapshot
+    bytes_since_snapshot: u64,
+    last_snapshot: Instant,
+}
+
+/// State shared by every connection of the broker.
+pub struct Broker {
+    pub config: BrokerConfig,
+    pub cluster_id: String,
+    pub metadata: RwLock<MetadataImage>,
+    pub raft: RaftManager,
+    pub heartbeats: BrokerHeartbeatManager,
+    /// How far the committed metadata log is applied to the image
+    applied: Mutex<AppliedMetadata>,
+    /// Held while writing, so that records are built from the offset they land at
+    metadata_writer: Mutex<()>,
+    /// Identifies this run of the node in its broker registration
+    pub incarnation_id: Uuid,
+    pub lifecycle: BrokerLifecycleManager,
+    pub logs: Mutex<LogManager>,
+    pub replicas: ReplicaManager,
+    /// `None` unless tiered storage is enabled
+    pub remote_logs: Option<RemoteLogManager>,
+    /// Membership of the share groups this broker coordinates
+    pub share_groups: ShareGroupCoordinator,
+    /// Delivery state of the share partitions this broker leads
+    pub share_partitions: SharePartitionManager,
+    /// `None` when no authorizer is configured, in which case every action is allowed
+    authorizer: Option<Box<dyn Authorizer>>,
+    quotas: QuotaManager,
+}
+
+impl Broker {
+    pub fn new(config: BrokerConfig) -> Result<Self> {
+        let meta_properties =
+            MetaProperties::load_or_format(&config.metadata_log_dir, config.node_id, None)?;
+        let raft = RaftManager::open(&config, &meta_properties.cluster_id)?;
+        let logs = LogManager::open(&config, &meta_properties.cluster_id)?;
+        let authorizer = config
+            .authorizer
+            .as_ref()
+            .map(|config| Box::new(AclAuthorizer::new(config)) as Box<dyn Authorizer>);
+        let quotas = QuotaManager::new(config.quota.clone());
+        let remote_logs = config.remote_log.as_ref().map(RemoteLogManager::new);
+        let share_groups = ShareGroupCoordinator::new(&config.share_group);
+        let share_partitions = SharePartitionManager::open(&config)?;
+        let broker = Broker {
+            config,
+            cluster_id: meta_properties.cluster_id,
+            metadata: RwLock::new(MetadataImage::default()),
+            raft,
+            heartbeats: BrokerHeartbeatManager::default(),
+            applied: Mutex::new(AppliedMetadata {
+                offset: 0,
+                epoch: 0,
+                last_timestamp: -1,
+                bytes_since_snapshot: 0,
+                last_snapshot: Instant::now(),
+            }),
+            metadata_writer: Mutex::new(()),
+            incarnation_id: random_uuid(),
+            lifecycle: BrokerLifecycleManager::default(),
+            logs: Mutex::new(logs),
+            replicas: ReplicaManager::default(),
+            remote_logs,
+            share_groups,
+            share_partitions,
+            authorizer,
+            quotas,
+        };
+        // A single voter has committed its whole log, and registers before serving
+        broker.apply_committed_metadata()?;
+        broker.lifecycle.start_up(&broker)?;
+        Ok(broker)
+    }
+
+    /// Persist the records to the metadata log, then make them visible in the image.
+    pub fn commit_metadata(&self, records: Vec<MetadataRecord>) -> Result<()> {
+        if records.is_empty() {
+            return Ok(());
+        }
+        self.append_metadata(|_| records)
+    }
+
+    /// Create the local replicas the metadata assigns to this broker and record the log
+    /// directory of any replica that moved.
+    pub fn sync_replicas(&self) -> Result<()> {
+        self.append_metadata(|_| {
+            let image = self.metadata.read().unwrap();
+            self.logs.lock().unwrap().sync_replicas(&image)
+        })
+    }
+
+    /// Replace the ISR of a partition, unless it changed since `partition_epoch`. Another
+    /// change got there first in that case, and the caller works from the new state. An
+    /// ISR holding every replica a reassignment adds completes the reassignment.
+    pub fn alter_isr(
+        &self,
+        topic_id: Uuid,
+        partition: i32,
+        partition_epoch: i32,
+        isr: Vec<i32>,
+    ) -> Result<()> {
+        let mut completed = None;
+        self.append_metadata(|_| {
+            let image = self.metadata.read().unwrap();
+            let current = image
+                .partitions
+                .get(&topic_id)
+                .and_then(|partitions| partitions.get(&partition));
+            let Some(current) = current.filter(|p| p.partition_epoch == partition_epoch) else {
+                return vec![];
+            };
+            // The new ISR may be what an ongoing reassignment waits for
+            if let Some(record) =
+                controller::complete_reassignment(&image, topic_id, partition, current, &isr)
+            {
+                let topic = image.topic_name(topic_id).unwrap_or_default();
+                let replicas = record.replicas.clone().unwrap_or_default();
+                completed = Some(format!("{topic}-{partition} to {replicas:?}"));
+                return vec![MetadataRecord::PartitionChange(record)];
+            }
+            let mut record = PartitionChangeRecord::new(topic_id, partition);
+            record.isr = Some(isr);
+            vec![MetadataRecord::PartitionChange(record)]
+        })?;
+        if let Some(reassignment) = completed {
+            eprintln!("Completed the reassignment of {reassignment}");
+        }
+        Ok(())
+    }
+
+    /// Append the records built from the offset they will be written at, wait for the
+    /// quorum to commit them, then make them visible in the image.
+    pub fn append_metadata(&self, build: impl FnOnce(i64) -> Vec<MetadataRecord>) -> Result<()> {
+        let _writer = self.metadata_writer.lock().unwrap();
+        let mut records = build(self.raft.end_offset());
+        // Replicas the records bring to this broker get their directory recorded in turn
+        while !records.is_empty() {
+            let end_offset = self.raft.append(&records)?;
+            self.raft.wait_for_commit(end_offset)?;
+            records = self.apply_committed_metadata()?;
+        }
+        Ok(())
+    }
+
+    /// Offset up to which the image reflects the metadata log.
+    pub fn applied_offset(&self) -> i64 {
+        self.applied.lock().unwrap().offset
+    }
+
+    /// Apply the records committed since the last call to the image, then create the
+    /// local replicas it assigns to this broker. Returns the records assigning a log
+    /// directory to replicas that got one.
+    fn apply_committed_metadata(&self) -> Result<Vec<MetadataRecord>> {
+        // Holding the applied lock while applying keeps the image in log order
+        let mut applied = self.applied.lock().unwrap();
+        let batches = loop {
+            // The log no longer holds what a snapshot ahead of the image covers
+            if let Some(snapshot) = self.raft.latest_snapshot() {
+                if snapshot.end_offset > applied.offset {
+                    self.load_snapshot(&mut applied, snapshot)?;
+                }
+            }
+            let batches = self.raft.committed_batches(applied.offset)?;
+            // A snapshot installed meanwhile moved the log start past the image
+            if batches
+                .first()
+                .is_some_and(|batch| batch.base_offset > applied.offset)
+            {
+                continue;
+            }
+            break batches;
+        };
+        let Some(last) = batches.last() else {
+            return Ok(vec![]);
+        };
+        let next_offset = last.last_offset() + 1;
+        let mut image = self.metadata.write().unwrap();
+        applied.epoch = last.partition_leader_epoch;
+        applied.last_timestamp = last.max_timestamp;
+        for batch in batches.into_iter().filter(|batch| !batch.is_control()) {
+            for record in batch.records {
+                if batch.base_offset + (record.offset_delta as i64) < applied.offset {
+                    continue;
+                }
+                let Some(value) = record.value else { continue };
+                applied.bytes_since_snapshot += value.len() as u64;
+                match MetadataRecord::deserialize(&value) {
+                    Ok((record, _)) => image.apply(record),
+                    Err(e) => eprintln!("Skipping undecodable metadata record: {e}"),
+                }
+            }
+        }
+        applied.offset = next_offset;
+        image.offset = next_offset;
+        drop(image);
+        self.maybe_snapshot(&mut applied);
+        let image = self.metadata.read().unwrap();
+        Ok(self.logs.lock().unwrap().sync_replicas(&image))
+    }
+
+    /// Replace the image with the content of a snapshot.
+    fn load_snapshot(&self, applied: &mut AppliedMetadata, snapshot: SnapshotId) -> Result<()> {
+        let records = self.raft.read_snapshot(snapshot)?;
+        let mut image = MetadataImage::default();
+        for record in records {
+            image.apply(record);
+        }
+        image.offset = snapshot.end_offset;
+        *self.metadata.write().unwrap() = image;
+        applied.offset = snapshot.end_offset;
+        applied.epoch = snapshot.epoch;
+        applied.bytes_since_snapshot = 0;
+        applied.last_snapshot = Instant::now();
+        Ok(())
+    }
+
+    /// Snapshot the image once enough records were applied since the last snapshot,
+    /// or once some were and the snapshot interval passed.
+    fn maybe_snapshot(&self, applied: &mut AppliedMetadata) {
+        let config = &self.config.metadata_log;
+        let interval = Duration::from_millis(config.max_snapshot_interval_ms);
+        let due = applied.bytes_since_snapshot >= config.max_bytes_between_snapshots
+            || (config.max_snapshot_interval_ms > 0 && applied.last_snapshot.elapsed() >= interval);
+        if applied.bytes_since_snapshot == 0 || !due {
+            return;
+        }
+        let snapshot_id = SnapshotId {
+            end_offset: applied.offset,
+            epoch: applied.epoch,
+        };
+        let records = self.metadata.read().unwrap().records();
+        match self
+            .raft
+            .create_snapshot(snapshot_id, &records, applied.last_timestamp)
+        {
+            Ok(()) => {
+                applied.bytes_since_snapshot = 0;
+                applied.last_snapshot = Instant::now();
+            }
+            Err(e) => eprintln!("Failed to snapshot the metadata log: {e:#}"),
+        }
+    }
+
+    /// Bring the image up to the committed metadata log. The active controller records
+    /// the directories of new local replicas unless a write is under way, in which case
+    /// the writer does; other nodes leave them unassigned.
+    pub fn publish_metadata(&self) {
+        let records = match self.apply_committed_metadata() {
+            Ok(records) => records,
+            Err(e) => {
+                eprintln!("Failed to apply the metadata log: {e:#}");
+                return;
+            }
+        };
+        if !records.is_empty() {
+            if let Ok(_writer) = self.metadata_writer.try_lock() {
+                if self.raft.is_leader() && self.raft.high_watermark() == self.raft.end_offset() {
+                    if let Err(e) = self.raft.append(&records) {
+                        eprintln!("Failed to record replica directories: {e:#}");
+                    }
+                }
+            }
+        }
+    }
+
+    /// Effective log configs of the topic, reflecting the latest dynamic configs.
+    pub fn log_config(&self, topic: &str) -> LogConfig {
+        let metadata = self.metadata.read().unwrap();
+        ConfigResolver::new(&metadata, &self.config).log_config(topic)
+    }
+
+    /// Remote log manager tiering the partitions of the topic, `None` unless the topic
+    /// has `remote.storage.enable` and tiered storage is enabled.
+    pub fn remote_log_manager(&self, topic: &str) -> Option<&RemoteLogManager> {
+        let remote_logs = self.remote_logs.as_ref()?;
+        self.log_config(topic)
+            .remote_storage_enable
+            .then_some(remote_logs)
+    }
+
+    pub fn has_authorizer(&self) -> bool {
+        self.authorizer.is_some()
+    }
+
+    /// Whether the connection's principal may perform `operation` on the resource.
+    pub fn authorize(
+        &self,
+        context: &ConnectionContext,
+        operation: AclOperation,
+        resource_type: ResourceType,
+        resource_name: &str,
+    ) -> bool {
+        let Some(authorizer) = self.authorizer.as_ref() else {
+            return true;
+        };
+        let host = context.peer_addr.ip().to_string();
+        let action = Action {
+            principal: &context.principal,
+            host: &host,
+            operation,
+            resource_type,
+            resource_name,
+        };
+        authorizer.authorize(&self.metadata.read().unwrap(), &action)
+    }
+
+    /// Bitfield of the operations the connection may perform on the resource, with bit
+    /// `n` set for the operation of code `n`.
+    pub fn authorized_operations(
+        &self,
+        context: &ConnectionContext,
+        resource_type: ResourceType,
+        resource_name: &str,
+    ) -> i32 {
+        resource_type.authorized_operations(|operation| {
+            self.authorize(context, operation, resource_type, resource_name)
+        })
+    }
+
+    /// Record the connection's usage against its quota and return how long it must be
+    /// throttled for, in milliseconds.
+    pub fn record_quota(
+        &self,
+        context: &ConnectionContext,
+        client_id: &str,
+        quota_type: QuotaType,
+        value: f64,
+    ) -> i32 {
+        self.quotas.record(
+            &self.metadata.read().unwrap(),
+            quota_type,
+            &context.principal.name,
+            client_id,
+            value,
+        )
+    }
+
+    /// Whether the replica of this broker is listed as throttled for `quota_type` by
+    /// the configs of its topic.
+    pub fn is_throttled_replica(
+        &self,
+        quota_type: ReplicationQuotaType,
+        replica: &TopicPartition,
+    ) -> bool {
+        let config = self.log_config(&replica.topic);
+        let throttled = match quota_type {
+            ReplicationQuotaType::Leader => &config.leader_replication_throttled_replicas,
+            ReplicationQuotaType::Follower => &config.follower_replication_throttled_replicas,
+        };
+        quota::is_throttled_replica(throttled, replica.partition, self.config.node_id)
+    }
+
+    /// Whether throttled replicas went over the rate configured for `quota_type`.
+    pub fn is_replication_quota_exceeded(&self, quota_type: ReplicationQuotaType) -> bool {
+        let rate = {
+            let metadata = self.metadata.read().unwrap();
+            ConfigResolver::new(&metadata, &self.config).broker_config(quota_type.rate_config())
+        };
+        self.quotas.is_replication_quota_exceeded(quota_type, rate)
+    }
+
+    pub fn record_replication(&self, quota_type: ReplicationQuotaType, bytes: usize) {
+        self.quotas.record_replication(quota_type, bytes);
+    }
+}
@@ -0,0 +1,248 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use anyhow::{bail, Context, Result};
+
+use crate::{
+    broker::Broker,
+    controller::{self, BrokerHeartbeatState},
+    metadata,
+    network_client::NetworkClient,
+    protocol::{
+        broker_heartbeat::{BrokerHeartbeatRequest, BrokerHeartbeatResponse},
+        broker_registration::{BrokerRegistrationRequest, BrokerRegistrationResponse},
+        cluster_metadata::BrokerEndpoint,
+        error,
+    },
+};
+
+const BROKER_REGISTRATION_API_KEY: i16 = 62;
+const BROKER_HEARTBEAT_API_KEY: i16 = 63;
+
+/// The registration of this broker with the active controller, kept alive by
+/// heartbeats. The broker registers again whenever the controller no longer knows the
+/// registration.
+#[derive(Default)]
+pub struct BrokerLifecycleManager {
+    state: Mutex<LifecycleState>,
+    /// Set once a controlled shutdown took over the heartbeats
+    shutting_down: AtomicBool,
+}
+
+#[derive(Default)]
+struct LifecycleState {
+    /// Epoch of the current registration, `None` until registered
+    broker_epoch: Option<i64>,
+    fenced: bool,
+}
+
+impl BrokerLifecycleManager {
+    /// Register with the local controller and get unfenced before serving, when this
+    /// node leads the metadata quorum on its own.
+    pub fn start_up(&self, broker: &Broker) -> Result<()> {
+        if !broker.config.quorum.broker || !broker.raft.is_leader() {
+            return Ok(());
+        }
+        self.tick(broker, false)?;
+        self.tick(broker, false)?;
+        Ok(())
+    }
+
+    /// Move the leaderships of this broker to other replicas before it exits, giving up
+    /// once the session would have expired anyway.
+    pub fn controlled_shutdown(&self, broker: &Broker) {
+        self.shutting_down.store(true, Ordering::SeqCst);
+        if self.state.lock().unwrap().broker_epoch.is_none() {
+            return;
+        }
+        let deadline =
+            Instant::now() + Duration::from_millis(broker.config.quorum.broker_session_timeout_ms);
+        let backoff = Duration::from_millis(100);
+        loop {
+            match self.tick(broker, true) {
+                Ok(Some(state)) if state.should_shut_down => {
+                    eprintln!(
+                        "Controlled shutdown of broker {} done",
+                        broker.config.node_id
+                    );
+                    return;
+                }
+                Ok(_) => {}
+                Err(e) => eprintln!("Failed to shut down broker in a controlled way: {e:#}"),
+            }
+            if Instant::now() >= deadline {
+                eprintln!("Giving up on the controlled shutdown");
+                return;
+            }
+            thread::sleep(backoff);
+        }
+    }
+
+    /// Register if not registered, or heartbeat otherwise. Returns the state of the
+    /// registration after a heartbeat.
+    fn tick(&self, broker: &Broker, want_shut_down: bool) -> Result<Option<BrokerHeartbeatState>> {
+        let mut state = self.state.lock().unwrap();
+        let node_id = broker.config.node_id;
+        let Some(broker_epoch) = state.broker_epoch else {
+            let request = registration_request(broker);
+            match register(broker, &request)? {
+                Ok(broker_epoch) => {
+                    eprintln!("Registered broker {node_id} with epoch {broker_epoch}");
+                    state.broker_epoch = Some(broker_epoch);
+                    state.fenced = true;
+                }
+                Err(error_code) => bail!("registration refused with error code {error_code}"),
+            }
+            return Ok(None);
+        };
+
+        let request = BrokerHeartbeatRequest {
+            version: BrokerHeartbeatRequest::LATEST_VERSION,
+            broker_id: node_id,
+            broker_epoch,
+            current_metadata_offset: broker.applied_offset() - 1,
+            want_fence: false,
+            want_shut_down,
+            offline_log_dirs: broker
+                .logs
+                .lock()
+                .unwrap()
+                .dirs()
+                .iter()
+                .filter(|dir| !dir.is_online())
+                .map(|dir| dir.id)
+                .collect(),
+        };
+        match heartbeat(broker, &request)? {
+            Ok(heartbeat) => {
+                if heartbeat.is_fenced != state.fenced {
+                    let change = if heartbeat.is_fenced {
+                        "fenced"
+                    } else {
+                        "unfenced"
+                    };
+                    eprintln!("Broker {node_id} is now {change}");
+                    state.fenced = heartbeat.is_fenced;
+                }
+                Ok(Some(heartbeat))
+            }
+            // The registration was replaced, removed or lost with a snapshot
+            Err(error::STALE_BROKER_EPOCH | error::BROKER_ID_NOT_REGISTERED) => {
+                eprintln!("Registration of broker {node_id} is gone, registering again");
+                state.broker_epoch = None;
+                Ok(None)
+            }
+            Err(error_code) => bail!("heartbeat refused with error code {error_code}"),
+        }
+    }
+}
+
+/// Start registering this broker with the active controller and heartbeating to it.
+pub fn start(broker: &Arc<Broker>) {
+    if !broker.config.quorum.broker {
+        return;
+    }
+    let broker = Arc::clone(broker);
+    thread::spawn(move || {
+        let interval = Duration::from_millis(broker.config.quorum.broker_heartbeat_interval_ms);
+        loop {
+            if broker.lifecycle.shutting_down.load(Ordering::SeqCst) {
+                return;
+            }
+            if let Err(e) = broker.lifecycle.tick(&broker, false) {
+                eprintln!("Failed to heartbeat to the controller: {e:#}");
+            }
+            thread::sleep(interval);
+        }
+    });
+}
+
+fn registration_request(broker: &Broker) -> BrokerRegistrationRequest {
+    let listeners = broker
+        .config
+        .advertised_listeners
+        .iter()
+        .filter_map(|endpoint| {
+            let protocol = broker.config.security_protocol(&endpoint.name)?;
+            Some(BrokerEndpoint {
+                name: endpoint.name.clone(),
+                host: endpoint.host.clone(),
+                port: endpoint.port,
+                security_protocol: protocol.id(),
+            })
+        })
+        .collect();
+    BrokerRegistrationRequest {
+        version: BrokerRegistrationRequest::LATEST_VERSION,
+        broker_id: broker.config.node_id,
+        cluster_id: broker.cluster_id.clone(),
+        incarnation_id: broker.incarnation_id,
+        listeners,
+        features: metadata::supported_features(),
+        rack: broker.config.rack.clone(),
+        is_migrating_zk_broker: false,
+        log_dirs: broker.logs.lock().unwrap().online_dir_ids(),
+        previous_broker_epoch: -1,
+    }
+}
+
+/// Send the registration to the active controller, which may be this node. Returns the
+/// epoch of the registration, or the error code the controller answered.
+fn register(broker: &Broker, request: &BrokerRegistrationRequest) -> Result<Result<i64, i16>> {
+    if broker.raft.is_leader() {
+        return Ok(controller::register_broker(broker, request));
+    }
+    let response: BrokerRegistrationResponse = connect_to_controller(broker)?.send(
+        BROKER_REGISTRATION_API_KEY,
+        request.version,
+        request,
+    )?;
+    Ok(match response.error_code {
+        error::NONE => Ok(response.broker_epoch),
+        error_code => Err(error_code),
+    })
+}
+
+/// Send the heartbeat to the active controller, which may be this node.
+fn heartbeat(
+    broker: &Broker,
+    request: &BrokerHeartbeatRequest,
+) -> Result<Result<BrokerHeartbeatState, i16>> {
+    if broker.raft.is_leader() {
+        return Ok(controller::process_heartbeat(broker, request));
+    }
+    let response: BrokerHeartbeatResponse =
+        connect_to_controller(broker)?.send(BROKER_HEARTBEAT_API_KEY, request.version, request)?;
+    Ok(match response.error_code {
+        error::NONE => Ok(BrokerHeartbeatState {
+            is_caught_up: response.is_caught_up,
+            is_fenced: response.is_fenced,
+            should_shut_down: response.should_shut_down,
+        }),
+        error_code => Err(error_code),
+    })
+}
+
+/// Connect to the quorum listener of the active controller.
+fn connect_to_controller(broker: &Broker) -> Result<NetworkClient> {
+    let controller = broker.raft.leader().context("no active controller")?;
+    let voter = broker
+        .config
+        .quorum
+        .voter(controller)
+        .with_context(|| format!("controller {controller} is not a voter"))?;
+    // A heartbeat answered after the session expired is of no use
+    let timeout = Duration::from_millis(broker.config.quorum.broker_session_timeout_ms);
+    NetworkClient::connect(
+        &voter.host,
+        voter.port,
+        timeout,
+        format!("broker-{}", broker.config.node_id),
+    )
+}
@@ -0,0 +1,1580 @@
+use std::{
+    collections::HashMap,
+    fmt, fs,
+    path::{Path, PathBuf},
+};
+
+use thiserror::Error;
+
+use crate::{
+    config_registry::{self, ConfigResourceType},
+    security::KafkaPrincipal,
+};
+
+/// Default location used by the codecrafters harness for the KRaft logs.
+pub const DEFAULT_LOG_DIR: &str = "/tmp/kraft-combined-logs";
+/// Name of the directory holding the cluster metadata partition.
+pub const METADATA_PARTITION_DIR: &str = "__cluster_metadata-0";
+/// Listener bound when `listeners` is unset.
+pub const DEFAULT_LISTENERS: &str = "PLAINTEXT://127.0.0.1:9092";
+/// Security protocol of each listener name when `listener.security.protocol.map` is unset.
+pub const DEFAULT_PROTOCOL_MAP: &str =
+    "PLAINTEXT:PLAINTEXT,SSL:SSL,SASL_PLAINTEXT:SASL_PLAINTEXT,SASL_SSL:SASL_SSL";
+/// Prefix of the remote storage manager settings when
+/// `remote.log.storage.manager.impl.prefix` is unset.
+pub const REMOTE_STORAGE_MANAGER_PREFIX: &str = "rsm.config.";
+
+#[derive(Debug, Error)]
+pub enum ConfigError {
+    #[error("failed to read config file {path}: {source}")]
+    Io {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    #[error("{path}:{line}: expected `key=value`, found {content:?}")]
+    MalformedLine {
+        path: PathBuf,
+        line: usize,
+        content: String,
+    },
+    #[error("invalid value {value:?} for `{key}`: {reason}")]
+    InvalidValue {
+        key: String,
+        value: String,
+        reason: String,
+    },
+    #[error("unknown command-line argument {0:?}")]
+    UnknownArgument(String),
+    #[error("missing value for command-line argument {0:?}")]
+    MissingArgument(String),
+}
+
+impl ConfigError {
+    fn invalid(key: &str, value: &str, reason: impl Into<String>) -> Self {
+        ConfigError::InvalidValue {
+            key: key.to_string(),
+            value: value.to_string(),
+            reason: reason.into(),
+        }
+    }
+}
+
+/// Raw `key=value` pairs read from a Java style properties file.
+#[derive(Debug, Default, Clone)]
+pub struct Properties(pub HashMap<String, String>);
+
+impl Properties {
+    pub fn load(path: &Path) -> Result<Self, ConfigError> {
+        let content = fs::read_to_string(path).map_err(|source| ConfigError::Io {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        Self::parse(path, &content)
+    }
+
+    pub fn parse(path: &Path, content: &str) -> Result<Self, ConfigError> {
+        let mut properties = HashMap::new();
+        for (index, line) in content.lines().enumerate() {
+            let line = line.trim();
+            // Blank lines and comments are skipped
+            if line.is_empty() || line.starts_with('#') || line.starts_with('!') {
+                continue;
+            }
+            let Some((key, value)) = line.split_once(['=', ':']) else {
+                return Err(ConfigError::MalformedLine {
+                    path: path.to_path_buf(),
+                    line: index + 1,
+                    content: line.to_string(),
+                });
+            };
+            properties.insert(key.trim().to_string(), value.trim().to_string());
+        }
+        Ok(Properties(properties))
+    }
+
+    pub fn set(&mut self, key: &str, value: &str) {
+        self.0.insert(key.to_string(), value.to_string());
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key).map(String::as_str)
+    }
+
+    /// Look up `listener.name.<listener>.<key>` first and fall back to the plain `key`,
+    /// returning the key that was actually found along with its value.
+    pub fn get_for_listener(&self, listener_name: &str, key: &str) -> Option<(String, &str)> {
+        let prefixed = format!("{}{key}", listener_prefix(listener_name));
+        match self.get(&prefixed) {
+            Some(value) => Some((prefixed, value)),
+            None => self.get(key).map(|value| (key.to_string(), value)),
+        }
+    }
+}
+
+fn listener_prefix(listener_name: &str) -> String {
+    format!("listener.name.{}.", listener_name.to_ascii_lowercase())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SecurityProtocol {
+    Plaintext,
+    Ssl,
+    SaslPlaintext,
+    SaslSsl,
+}
+
+impl SecurityProtocol {
+    pub fn parse(key: &str, value: &str) -> Result<Self, ConfigError> {
+        match value.to_ascii_uppercase().as_str() {
+            "PLAINTEXT" => Ok(SecurityProtocol::Plaintext),
+            "SSL" => Ok(SecurityProtocol::Ssl),
+            "SASL_PLAINTEXT" => Ok(SecurityProtocol::SaslPlaintext),
+            "SASL_SSL" => Ok(SecurityProtocol::SaslSsl),
+            _ => Err(ConfigError::invalid(
+                key,
+                value,
+                "unknown security protocol",
+            )),
+        }
+    }
+
+    /// Id of the protocol in broker registrations.
+    pub fn id(&self) -> i16 {
+        match self {
+            SecurityProtocol::Plaintext => 0,
+            SecurityProtocol::Ssl => 1,
+            SecurityProtocol::SaslPlaintext => 2,
+            SecurityProtocol::SaslSsl => 3,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            SecurityProtocol::Plaintext => "PLAINTEXT",
+            SecurityProtocol::Ssl => "SSL",
+            SecurityProtocol::SaslPlaintext => "SASL_PLAINTEXT",
+            SecurityProtocol::SaslSsl => "SASL_SSL",
+        }
+    }
+}
+
+impl fmt::Display for SecurityProtocol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Parse `listener.security.protocol.map`, e.g. `INTERNAL:PLAINTEXT,EXTERNAL:SSL`.
+fn parse_protocol_map(
+    key: &str,
+    value: &str,
+) -> Result<HashMap<String, SecurityProtocol>, ConfigError> {
+    let mut map = HashMap::new();
+    for entry in value.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+        let Some((name, protocol)) = entry.split_once(':') else {
+            return Err(ConfigError::invalid(key, value, "expected `NAME:PROTOCOL`"));
+        };
+        let name = name.trim().to_ascii_uppercase();
+        if map
+            .insert(name.clone(), SecurityProtocol::parse(key, protocol.trim())?)
+            .is_some()
+        {
+            return Err(ConfigError::invalid(
+                key,
+                value,
+                format!("listener {name} is mapped twice"),
+            ));
+        }
+    }
+    Ok(map)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SslClientAuth {
+    None,
+    Requested,
+    Required,
+}
+
+/// TLS settings of a single SSL listener, read from `listener.name.<name>.ssl.*`
+/// with a fallback to the broker-wide `ssl.*` keys.
+#[derive(Debug, Clone)]
+pub struct SslConfig {
+    /// PEM file holding the certificate chain and the private key.
+    pub keystore_location: PathBuf,
+    /// PEM file holding the CA certificates trusted for client authentication.
+    pub truststore_location: Option<PathBuf>,
+    pub client_auth: SslClientAuth,
+}
+
+impl SslConfig {
+    fn from_properties(properties: &Properties, listener_name: &str) -> Result<Self, ConfigError> {
+        let prefix = listener_prefix(listener_name);
+        let lookup = |key: &str| properties.get_for_listener(listener_name, key);
+
+        if let Some((key, value)) = lookup("ssl.keystore.type") {
+            if !value.eq_ignore_ascii_case("PEM") {
+                return Err(ConfigError::invalid(
+                    &key,
+                    value,
+                    "only PEM keystores are supported",
+                ));
+            }
+        }
+        if let Some((key, value)) = lookup("ssl.truststore.type") {
+            if !value.eq_ignore_ascii_case("PEM") {
+                return Err(ConfigError::invalid(
+                    &key,
+                    value,
+                    "only PEM truststores are supported",
+                ));
+            }
+        }
+
+        let keystore_location = match lookup("ssl.keystore.location") {
+            Some((key, value)) => parse_paths(&key, value)?.remove(0),
+            None => {
+                return Err(ConfigError::invalid(
+                    &format!("{prefix}ssl.keystore.location"),
+                    "",
+                    format!("required by SSL listener {listener_name}"),
+                ))
+            }
+        };
+        let truststore_location = match lookup("ssl.truststore.location") {
+            Some((key, value)) => Some(parse_paths(&key, value)?.remove(0)),
+            None => None,
+        };
+        let client_auth = match lookup("ssl.client.auth") {
+            Some((key, value)) => match value.to_ascii_lowercase().as_str() {
+                "none" => SslClientAuth::None,
+                "requested" => SslClientAuth::Requested,
+                "required" => SslClientAuth::Required,
+                _ => {
+                    return Err(ConfigError::invalid(
+                        &key,
+                        value,
+                        "expected one of none, requested, required",
+                    ))
+                }
+            },
+            None => SslClientAuth::None,
+        };
+        if client_auth != SslClientAuth::None && truststore_location.is_none() {
+            return Err(ConfigError::invalid(
+                &format!("{prefix}ssl.truststore.location"),
+                "",
+                "required when ssl.client.auth is enabled",
+            ));
+        }
+
+        Ok(SslConfig {
+            keystore_location,
+            truststore_location,
+            client_auth,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SaslMechanism {
+    Plain,
+    ScramSha256,
+    ScramSha512,
+}
+
+impl SaslMechanism {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "PLAIN" => Some(SaslMechanism::Plain),
+            "SCRAM-SHA-256" => Some(SaslMechanism::ScramSha256),
+            "SCRAM-SHA-512" => Some(SaslMechanism::ScramSha512),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            SaslMechanism::Plain => "PLAIN",
+            SaslMechanism::ScramSha256 => "SCRAM-SHA-256",
+            SaslMechanism::ScramSha512 => "SCRAM-SHA-512",
+        }
+    }
+}
+
+/// SASL settings of a single SASL_PLAINTEXT or SASL_SSL listener.
+#[derive(Debug, Clone)]
+pub struct SaslConfig {
+    pub enabled_mechanisms: Vec<SaslMechanism>,
+    /// Users accepted by the PLAIN mechanism, taken from the `user_<name>="<password>"`
+    /// options of the PLAIN JAAS config.
+    pub plain_users: HashMap<String, String>,
+    /// Maximum session lifetime before the client must re-authenticate; 0 disables it.
+    pub max_reauth_ms: i64,
+}
+
+impl SaslConfig {
+    fn from_properties(properties: &Properties, listener_name: &str) -> Result<Self, ConfigError> {
+        let (key, value) = properties
+            .get_for_listener(listener_name, "sasl.enabled.mechanisms")
+            .unwrap_or(("sasl.enabled.mechanisms".to_string(), "PLAIN"));
+        let mut enabled_mechanisms = vec![];
+        for name in value.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+            match SaslMechanism::from_name(name) {
+                Some(mechanism) if !enabled_mechanisms.contains(&mechanism) => {
+                    enabled_mechanisms.push(mechanism)
+                }
+                Some(_) => {}
+                None => {
+                    return Err(ConfigError::invalid(
+                        &key,
+                        value,
+                        format!("unsupported SASL mechanism {name}"),
+                    ))
+                }
+            }
+        }
+        if enabled_mechanisms.is_empty() {
+            return Err(ConfigError::invalid(
+                &key,
+                value,
+                format!("SASL listener {listener_name} needs at least one mechanism"),
+            ));
+        }
+
+        let mut plain_users = HashMap::new();
+        if enabled_mechanisms.contains(&SaslMechanism::Plain) {
+            let jaas = properties
+                .get_for_listener(listener_name, "plain.sasl.jaas.config")
+                .or_else(|| properties.get_for_listener(listener_name, "sasl.jaas.config"));
+            let Some((key, value)) = jaas else {
+                return Err(ConfigError::invalid(
+                    &format!("{}plain.sasl.jaas.config", listener_prefix(listener_name)),
+                    "",
+                    "required when the PLAIN mechanism is enabled",
+                ));
+            };
+            for (option, password) in parse_jaas_options(&key, value)? {
+                if let Some(user) = option.strip_prefix("user_") {
+                    plain_users.insert(user.to_string(), password);
+                }
+            }
+        }
+
+        let max_reauth_ms =
+            match properties.get_for_listener(listener_name, "connections.max.reauth.ms") {
+                Some((key, value)) => match value.parse::<i64>() {
+                    Ok(ms) if ms >= 0 => ms,
+                    _ => {
+                        return Err(ConfigError::invalid(
+                            &key,
+                            value,
+                            "expected a non-negative integer",
+                        ))
+                    }
+                },
+                None => 0,
+            };
+
+        Ok(SaslConfig {
+            enabled_mechanisms,
+            plain_users,
+            max_reauth_ms,
+        })
+    }
+}
+
+/// Extract the `name="value"` options of a JAAS login module entry such as
+/// `org.apache.kafka.common.security.plain.PlainLoginModule required user_alice="secret";`.
+fn parse_jaas_options(key: &str, value: &str) -> Result<Vec<(String, String)>, ConfigError> {
+    let body = value.trim().trim_end_matches(';');
+    let mut words = body.splitn(3, char::is_whitespace);
+    let (Some(_module), Some(flag)) = (words.next(), words.next()) else {
+        return Err(ConfigError::invalid(
+            key,
+            value,
+            "expected `<LoginModule> <flag> <options>;`",
+        ));
+    };
+    if !matches!(flag, "required" | "requisite" | "sufficient" | "optional") {
+        return Err(ConfigError::invalid(
+            key,
+            value,
+            format!("invalid control flag {flag}"),
+        ));
+    }
+
+    let mut options = vec![];
+    let mut rest = words.next().unwrap_or_default().trim();
+    while !rest.is_empty() {
+        let Some((name, after)) = rest.split_once('=') else {
+            return Err(ConfigError::invalid(
+                key,
+                value,
+                "expected `name=\"value\"` options",
+            ));
+        };
+        let after = after.trim_start();
+        let (option, remaining) = match after.strip_prefix('"') {
+            Some(quoted) => match quoted.split_once('"') {
+                Some((option, remaining)) => (option, remaining),
+                None => return Err(ConfigError::invalid(key, value, "unterminated quote")),
+            },
+            None => after.split_once(char::is_whitespace).unwrap_or((after, "")),
+        };
+        options.push((name.trim().to_string(), option.to_string()));
+        rest = remaining.trim();
+    }
+    Ok(options)
+}
+
+/// Settings of the built-in ACL authorizer.
+#[derive(Debug, Clone, Default)]
+pub struct AuthorizerConfig {
+    /// Principals allowed to perform every operation regardless of ACLs.
+    pub super_users: Vec<KafkaPrincipal>,
+    /// Whether resources without any ACL are open to everyone.
+    pub allow_everyone_if_no_acl_found: bool,
+}
+
+impl AuthorizerConfig {
+    /// Class name selecting the built-in authorizer, as used by Apache Kafka in KRaft mode.
+    pub const STANDARD_AUTHORIZER: &'static str =
+        "org.apache.kafka.metadata.authorizer.StandardAuthorizer";
+
+    /// `None` when `authorizer.class.name` is unset, in which case nothing is denied.
+    fn from_properties(properties: &Properties) -> Result<Option<Self>, ConfigError> {
+        match properties.get("authorizer.class.name").map(str::trim) {
+            None | Some("") => return Ok(None),
+            Some(Self::STANDARD_AUTHORIZER) => {}
+            Some(value) => {
+                return Err(ConfigError::invalid(
+                    "authorizer.class.name",
+                    value,
+                    format!("only {} is supported", Self::STANDARD_AUTHORIZER),
+                ))
+            }
+        }
+
+        let mut super_users = vec![];
+        if let Some(value) = properties.get("super.users") {
+            for entry in value.split(';').map(str::trim).filter(|s| !s.is_empty()) {
+                let Some(principal) = KafkaPrincipal::parse(entry) else {
+                    return Err(ConfigError::invalid(
+                        "super.users",
+                        value,
+                        format!("{entry} is not a `Type:name` principal"),
+                    ));
+                };
+                super_users.push(principal);
+            }
+        }
+
+        let allow_everyone_if_no_acl_found = match properties.get("allow.everyone.if.no.acl.found")
+        {
+            Some(value) => value.trim().parse::<bool>().map_err(|_| {
+                ConfigError::invalid(
+                    "allow.everyone.if.no.acl.found",
+                    value,
+                    "expected true or false",
+                )
+            })?,
+            None => false,
+        };
+
+        Ok(Some(AuthorizerConfig {
+            super_users,
+            allow_everyone_if_no_acl_found,
+        }))
+    }
+}
+
+/// Sliding window client quotas are measured over.
+#[derive(Debug, Clone)]
+pub struct QuotaConfig {
+    pub window_num: u32,
+    pub window_size_ms: u64,
+}
+
+impl QuotaConfig {
+    /// Age past which a sample no longer counts, that of the whole window.
+    pub fn max_age_ms(&self) -> u64 {
+        self.window_size_ms.saturating_mul(self.window_num as u64)
+    }
+
+    fn from_properties(properties: &Properties) -> Result<Self, ConfigError> {
+        let positive = |key: &str, default: u64| match properties.get(key) {
+            Some(value) => match value.trim().parse::<u64>() {
+                Ok(n) if n > 0 => Ok(n),
+                _ => Err(ConfigError::invalid(
+                    key,
+                    value,
+                    "expected a positive integer",
+                )),
+            },
+            None => Ok(default),
+        };
+        let too_large = |key: &str| {
+            ConfigError::invalid(
+                key,
+                properties.get(key).unwrap_or_default(),
+                "value is too large",
+            )
+        };
+        let window_num = u32::try_from(positive("quota.window.num", 11)?)
+            .map_err(|_| too_large("quota.window.num"))?;
+        let window_size_ms = positive("quota.window.size.seconds", 1)?
+            .checked_mul(1000)
+            .ok_or_else(|| too_large("quota.window.size.seconds"))?;
+        Ok(QuotaConfig {
+            window_num,
+            window_size_ms,
+        })
+    }
+}
+
+/// How the leader of a partition picks the replica a consumer should fetch from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReplicaSelector {
+    /// Consumers always fetch from the leader
+    Leader,
+    /// Consumers fetch from an in-sync replica in their own rack when there is one
+    RackAware,
+}
+
+impl ReplicaSelector {
+    pub const LEADER_SELECTOR: &'static str = "org.apache.kafka.common.replica.LeaderSelector";
+    pub const RACK_AWARE_SELECTOR: &'static str =
+        "org.apache.kafka.common.replica.RackAwareReplicaSelector";
+
+    /// Consumers fetch from the leader when `replica.selector.class` is unset.
+    fn from_properties(properties: &Properties) -> Result<Self, ConfigError> {
+        match properties.get("replica.selector.class").map(str::trim) {
+            None | Some("") | Some(Self::LEADER_SELECTOR) => Ok(ReplicaSelector::Leader),
+            Some(Self::RACK_AWARE_SELECTOR) => Ok(ReplicaSelector::RackAware),
+            Some(value) => Err(ConfigError::invalid(
+                "replica.selector.class",
+                value,
+                format!(
+                    "only {} and {} are supported",
+                    Self::LEADER_SELECTOR,
+                    Self::RACK_AWARE_SELECTOR
+                ),
+            )),
+        }
+    }
+}
+
+/// How followers replicate the partitions they host from the leaders.
+#[derive(Debug, Clone)]
+pub struct ReplicationConfig {
+    /// Listener followers reach leaders through
+    pub inter_broker_listener_name: String,
+    /// Time a follower may go without catching up before it drops out of the ISR
+    pub lag_time_max_ms: u64,
+    /// How long a follower's fetch waits at the leader for new records
+    pub fetch_wait_max_ms: u64,
+    /// Pause before a follower retries a leader it failed to fetch from
+    pub fetch_backoff_ms: u64,
+    pub high_watermark_checkpoint_interval_ms: u64,
+    /// Replica consumers are sent to fetch from
+    pub replica_selector: ReplicaSelector,
+}
+
+impl ReplicationConfig {
+    fn from_properties(
+        properties: &Properties,
+        listeners: &[Endpoint],
+    ) -> Result<Self, ConfigError> {
+        let non_negative = |key: &str, default: u64| match properties.get(key) {
+            Some(value) => value
+                .trim()
+                .parse::<u64>()
+                .map_err(|_| ConfigError::invalid(key, value, "expected a non-negative integer")),
+            None => Ok(default),
+        };
+        // Like Apache Kafka, brokers talk to each other over the first listener by default
+        let inter_broker_listener_name = match properties.get("inter.broker.listener.name") {
+            Some(value) => {
+                let name = value.trim();
+                if !listeners.iter().any(|l| l.name == name) {
+                    return Err(ConfigError::invalid(
+                        "inter.broker.listener.name",
+                        value,
+                        format!("{name} is not declared in `listeners`"),
+                    ));
+                }
+                name.to_string()
+            }
+            None => listeners[0].name.clone(),
+        };
+        Ok(ReplicationConfig {
+            inter_broker_listener_name,
+            lag_time_max_ms: non_negative("replica.lag.time.max.ms", 30_000)?,
+            fetch_wait_max_ms: non_negative("replica.fetch.wait.max.ms", 500)?,
+            fetch_backoff_ms: non_negative("replica.fetch.backoff.ms", 1000)?,
+            high_watermark_checkpoint_interval_ms: non_negative(
+                "replica.high.watermark.checkpoint.interval.ms",
+                5000,
+            )?,
+            replica_selector: ReplicaSelector::from_properties(properties)?,
+        })
+    }
+}
+
+/// Tiered storage, copying the closed segments of topics with `remote.storage.enable`
+/// to a directory standing for an object store.
+///
+/// As in KIP-405, the settings of the remote storage manager are those whose key starts
+/// with `remote.log.storage.manager.impl.prefix`, `rsm.config.` by default: the
+/// directory is `<prefix>dir`.
+#[derive(Debug, Clone)]
+pub struct RemoteLogConfig {
+    /// Root of the remote storage, which every broker of the cluster shares
+    pub storage_dir: PathBuf,
+    /// Time between two rounds of copying segments to remote storage and expiring them
+    pub task_interval_ms: u64,
+}
+
+impl RemoteLogConfig {
+    /// `None` unless `remote.log.storage.system.enable` is set.
+    fn from_properties(properties: &Properties) -> Result<Option<Self>, ConfigError> {
+        let enabled = match properties.get("remote.log.storage.system.enable") {
+            Some(value) => value.trim().parse::<bool>().map_err(|_| {
+                ConfigError::invalid(
+                    "remote.log.storage.system.enable",
+                    value,
+                    "expected true or false",
+                )
+            })?,
+            None => false,
+        };
+        if !enabled {
+            return Ok(None);
+        }
+        let prefix = properties
+            .get("remote.log.storage.manager.impl.prefix")
+            .map_or(REMOTE_STORAGE_MANAGER_PREFIX, |prefix| prefix.trim());
+        let key = format!("{prefix}dir");
+        let storage_dir = match properties.get(&key) {
+            Some(value) => parse_paths(&key, value)?
+                .into_iter()
+                .next()
+                .unwrap_or_default(),
+            None => {
+                return Err(ConfigError::invalid(
+                    &key,
+                    "",
+                    "a directory is required when remote log storage is enabled",
+                ))
+            }
+        };
+        let task_interval_ms = match properties.get("remote.log.manager.task.interval.ms") {
+            Some(value) => value.trim().parse::<u64>().map_err(|_| {
+                ConfigError::invalid(
+                    "remote.log.manager.task.interval.ms",
+                    value,
+                    "expected a non-negative integer",
+                )
+            })?,
+            None => 30_000,
+        };
+        Ok(Some(RemoteLogConfig {
+            storage_dir,
+            task_interval_ms,
+        }))
+    }
+}
+
+/// Share groups, whose members consume the partitions they subscribe to together and
+/// acknowledge records one by one.
+#[derive(Debug, Clone)]
+pub struct ShareGroupConfig {
+    pub heartbeat_interval_ms: u64,
+    /// Time a member may go without a heartbeat before it leaves the group
+    pub session_timeout_ms: u64,
+    pub max_size: usize,
+    /// Time a member holds the records it acquired before they become available again
+    pub record_lock_duration_ms: u64,
+    /// Deliveries after which a record is archived instead of made available again
+    pub delivery_count_limit: i16,
+    /// Largest number of records between the start and the end of a share partition
+    pub partition_max_record_locks: i64,
+    /// Share partition states written to the state log between two snapshots of it
+    pub updates_per_snapshot: usize,
+}
+
+impl ShareGroupConfig {
+    fn from_properties(properties: &Properties) -> Result<Self, ConfigError> {
+        let non_negative = |key: &str, default: u64| match properties.get(key) {
+            Some(value) => value
+                .trim()
+                .parse::<u64>()
+                .map_err(|_| ConfigError::invalid(key, value, "expected a non-negative integer")),
+            None => Ok(default),
+        };
+        let key = "group.share.delivery.count.limit";
+        let delivery_count_limit = i16::try_from(non_negative(key, 5)?).map_err(|_| {
+            ConfigError::invalid(
+                key,
+                properties.get(key).unwrap_or_default(),
+                "expected an integer of at most 32767",
+            )
+        })?;
+        Ok(ShareGroupConfig {
+            heartbeat_interval_ms: non_negative("group.share.heartbeat.interval.ms", 5000)?,
+            session_timeout_ms: non_negative("group.share.session.timeout.ms", 45_000)?,
+            max_size: non_negative("group.share.max.size", 200)? as usize,
+            record_lock_duration_ms: non_negative("group.share.record.lock.duration.ms", 30_000)?,
+            delivery_count_limit,
+            partition_max_record_locks: non_negative(
+                "group.share.partition.max.record.locks",
+                2000,
+            )? as i64,
+            updates_per_snapshot: non_negative(
+                "share.coordinator.snapshot.update.records.per.snapshot",
+                500,
+            )? as usize,
+        })
+    }
+}
+
+/// Segments and snapshots of the `__cluster_metadata` log.
+#[derive(Debug, Clone)]
+pub struct MetadataLogConfig {
+    pub segment_bytes: u64,
+    /// Bytes of committed records after which the image is written to a new snapshot
+    pub max_bytes_between_snapshots: u64,
+    /// Longest time between snapshots while records keep being committed, 0 to disable
+    pub max_snapshot_interval_ms: u64,
+}
+
+impl MetadataLogConfig {
+    fn from_properties(properties: &Properties) -> Result<Self, ConfigError> {
+        let at_least = |key: &str, min: u64, default: u64| match properties.get(key) {
+            Some(value) => match value.trim().parse::<u64>() {
+                Ok(n) if n >= min => Ok(n),
+                _ => Err(ConfigError::invalid(
+                    key,
+                    value,
+                    format!("expected an integer of at least {min}"),
+                )),
+            },
+            None => Ok(default),
+        };
+        Ok(MetadataLogConfig {
+            segment_bytes: at_least("metadata.log.segment.bytes", 12, 1024 * 1024 * 1024)?,
+            max_bytes_between_snapshots: at_least(
+                "metadata.log.max.record.bytes.between.snapshots",
+                1,
+                20 * 1024 * 1024,
+            )?,
+            max_snapshot_interval_ms: at_least(
+                "metadata.log.max.snapshot.interval.ms",
+                0,
+                60 * 60 * 1000,
+            )?,
+        })
+    }
+}
+
+/// A controller voting in the metadata quorum, in the form `id@host:port`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QuorumVoter {
+    pub id: i32,
+    pub host: String,
+    pub port: u16,
+}
+
+impl QuorumVoter {
+    fn parse(key: &str, value: &str) -> Result<Self, ConfigError> {
+        let Some((id, address)) = value.split_once('@') else {
+            return Err(ConfigError::invalid(key, value, "expected `id@host:port`"));
+        };
+        let id = id
+            .parse::<i32>()
+            .ok()
+            .filter(|&id| id >= 0)
+            .ok_or_else(|| ConfigError::invalid(key, value, "invalid node id"))?;
+        let Some((host, port)) = address.rsplit_once(':') else {
+            return Err(ConfigError::invalid(key, value, "missing port"));
+        };
+        let port = port
+            .parse::<u16>()
+            .map_err(|e| ConfigError::invalid(key, value, format!("invalid port: {e}")))?;
+        let host = host.trim_start_matches('[').trim_end_matches(']');
+        Ok(QuorumVoter {
+            id,
+            host: host.to_string(),
+            port,
+        })
+    }
+}
+
+/// Part the node takes in the KRaft quorum replicating the metadata log.
+#[derive(Debug, Clone)]
+pub struct QuorumConfig {
+    /// Whether the node registers as a broker hosting partitions
+    pub broker: bool,
+    /// Whether the node votes for the active controller
+    pub controller: bool,
+    pub voters: Vec<QuorumVoter>,
+    pub election_timeout_ms: u64,
+    pub election_backoff_max_ms: u64,
+    /// Time a follower may go without fetching from the leader before it stands for
+    /// election
+    pub fetch_timeout_ms: u64,
+    /// Time a registered broker may go without heartbeating before the controller
+    /// fences it
+    pub broker_session_timeout_ms: u64,
+    pub broker_heartbeat_interval_ms: u64,
+}
+
+impl QuorumConfig {
+    fn from_properties(
+        properties: &Properties,
+        node_id: i32,
+        listeners: &[Endpoint],
+        protocols: &HashMap<String, SecurityProtocol>,
+    ) -> Result<Self, ConfigError> {
+        let roles = properties
+            .get("process.roles")
+            .unwrap_or("broker,controller");
+        let has_role = |role: &str| {
+            roles
+                .split(',')
+                .any(|r| r.trim().eq_ignore_ascii_case(role))
+        };
+        let (broker, controller) = (has_role("broker"), has_role("controller"));
+        if !broker && !controller {
+            return Err(ConfigError::invalid(
+                "process.roles",
+                roles,
+                "at least one role is required",
+            ));
+        }
+
+        // Without voters a controller forms a quorum of its own, as a single node did
+        let voters = match properties.get("controller.quorum.voters") {
+            Some(value) => {
+                let voters = value
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|s| !s.is_empty())
+                    .map(|s| QuorumVoter::parse("controller.quorum.voters", s))
+                    .collect::<Result<Vec<_>, _>>()?;
+                if let Some(voter) = voters
+                    .iter()
+                    .enumerate()
+                    .find_map(|(i, voter)| voters[..i].iter().find(|v| v.id == voter.id))
+                {
+                    return Err(ConfigError::invalid(
+                        "controller.quorum.voters",
+                        value,
+                        format!("node {} is listed more than once", voter.id),
+                    ));
+                }
+                voters
+            }
+            None if controller => vec![QuorumVoter {
+                id: node_id,
+                host: listeners[0].host.clone(),
+                port: listeners[0].port,
+            }],
+            None => vec![],
+        };
+        let voters_value = properties
+            .get("controller.quorum.voters")
+            .unwrap_or_default();
+        match voters.iter().find(|voter| voter.id == node_id) {
+            Some(_) if !controller => {
+                return Err(ConfigError::invalid(
+                    "controller.quorum.voters",
+                    voters_value,
+                    format!("node {node_id} votes without the controller role"),
+                ))
+            }
+            None if controller => {
+                return Err(ConfigError::invalid(
+                    "controller.quorum.voters",
+                    voters_value,
+                    format!("controller {node_id} is not a voter"),
+                ))
+            }
+            None if voters.is_empty() => {
+                return Err(ConfigError::invalid(
+                    "controller.quorum.voters",
+                    voters_value,
+                    "brokers without the controller role need voters to fetch metadata from",
+                ))
+            }
+            // Other voters reach this one without TLS or SASL
+            Some(voter)
+                if !listeners.iter().any(|l| {
+                    l.port == voter.port
+                        && protocols.get(&l.name) == Some(&SecurityProtocol::Plaintext)
+                }) =>
+            {
+                return Err(ConfigError::invalid(
+                    "controller.quorum.voters",
+                    voters_value,
+                    format!(
+                        "no PLAINTEXT listener on port {} of node {node_id}",
+                        voter.port
+                    ),
+                ))
+            }
+            _ => {}
+        }
+
+        let positive = |key: &str, default: u64| match properties.get(key) {
+            Some(value) => match value.trim().parse::<u64>() {
+                Ok(n) if n > 0 => Ok(n),
+                _ => Err(ConfigError::invalid(
+                    key,
+                    value,
+                    "expected a positive integer",
+                )),
+            },
+            None => Ok(default),
+        };
+        Ok(QuorumConfig {
+            broker,
+            controller,
+            voters,
+            election_timeout_ms: positive("controller.quorum.election.timeout.ms", 1000)?,
+            election_backoff_max_ms: positive("controller.quorum.election.backoff.max.ms", 1000)?,
+            fetch_timeout_ms: positive("controller.quorum.fetch.timeout.ms", 2000)?,
+            broker_session_timeout_ms: positive("broker.session.timeout.ms", 9000)?,
+            broker_heartbeat_interval_ms: positive("broker.heartbeat.interval.ms", 2000)?,
+        })
+    }
+
+    pub fn voter(&self, id: i32) -> Option<&QuorumVoter> {
+        self.voters.iter().find(|voter| voter.id == id)
+    }
+}
+
+/// A listener endpoint in the form `NAME://host:port`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Endpoint {
+    pub name: String,
+    pub host: String,
+    pub port: u16,
+}
+
+impl Endpoint {
+    pub fn parse(key: &str, value: &str) -> Result<Self, ConfigError> {
+        let Some((name, address)) = value.split_once("://") else {
+            return Err(ConfigError::invalid(
+                key,
+                value,
+                "expected `NAME://host:port`",
+            ));
+        };
+        if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+            return Err(ConfigError::invalid(key, value, "invalid listener name"));
+        }
+        let Some((host, port)) = address.rsplit_once(':') else {
+            return Err(ConfigError::invalid(key, value, "missing port"));
+        };
+        let port = port
+            .parse::<u16>()
+            .map_err(|e| ConfigError::invalid(key, value, format!("invalid port: {e}")))?;
+        // IPv6 literals are written in brackets
+        let host = host.trim_start_matches('[').trim_end_matches(']');
+        Ok(Endpoint {
+            name: name.to_ascii_uppercase(),
+            host: host.to_string(),
+            port,
+        })
+    }
+
+    /// Address the listener socket should bind to; an empty host binds every interface.
+    pub fn bind_address(&self) -> String {
+        match self.host.as_str() {
+            "" => format!("0.0.0.0:{}", self.port),
+            host if host.contains(':') => format!("[{host}]:{}", self.port),
+            host => format!("{host}:{}", self.port),
+        }
+    }
+}
+
+impl fmt::Display for Endpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}://{}:{}", self.name, self.host, self.port)
+    }
+}
+
+fn parse_endpoints(key: &str, value: &str) -> Result<Vec<Endpoint>, ConfigError> {
+    let endpoints = value
+        .split(',')
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(|s| Endpoint::parse(key, s))
+        .collect::<Result<Vec<_>, _>>()?;
+    for (i, endpoint) in endpoints.iter().enumerate() {
+        if endpoints[..i].iter().any(|e| e.name == endpoint.name) {
+            return Err(ConfigError::invalid(
+                key,
+                value,
+                format!("duplicate listener name {}", endpoint.name),
+            ));
+        }
+    }
+    Ok(endpoints)
+}
+
+fn parse_paths(key: &str, value: &str) -> Result<Vec<PathBuf>, ConfigError> {
+    let paths: Vec<PathBuf> = value
+        .split(',')
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(PathBuf::from)
+        .collect();
+    if paths.is_empty() {
+        return Err(ConfigError::invalid(
+            key,
+            value,
+            "at least one path is required",
+        ));
+    }
+    if let Some(path) = paths.iter().find(|p| !p.is_absolute()) {
+        return Err(ConfigError::invalid(
+            key,
+            value,
+            format!("{} is not an absolute path", path.display()),
+        ));
+    }
+    if let Some(path) = paths
+        .iter()
+        .enumerate()
+        .find_map(|(i, path)| paths[..i].contains(path).then_some(path))
+    {
+        return Err(ConfigError::invalid(
+            key,
+            value,
+            format!("{} is listed more than once", path.display()),
+        ));
+    }
+    Ok(paths)
+}
+
+#[derive(Debug, Clone)]
+pub struct BrokerConfig {
+    pub node_id: i32,
+    pub rack: Option<String>,
+    pub listeners: Vec<Endpoint>,
+    pub advertised_listeners: Vec<Endpoint>,
+    pub listener_security_protocol_map: HashMap<String, SecurityProtocol>,
+    /// TLS settings keyed by listener name, for listeners using SSL or SASL_SSL.
+    pub ssl: HashMap<String, SslConfig>,
+    /// SASL settings keyed by listener name, for listeners using SASL_PLAINTEXT or SASL_SSL.
+    pub sasl: HashMap<String, SaslConfig>,
+    pub authorizer: Option<AuthorizerConfig>,
+    pub quota: QuotaConfig,
+    pub replication: ReplicationConfig,
+    pub quorum: QuorumConfig,
+    pub log_dirs: Vec<PathBuf>,
+    pub metadata_log_dir: PathBuf,
+    pub metadata_log: MetadataLogConfig,
+    /// Time between two looks for segments past their retention
+    pub log_retention_check_interval_ms: u64,
+    /// Largest request frame a connection may send
+    pub socket_request_max_bytes: usize,
+    /// `None` when tiered storage is disabled
+    pub remote_log: Option<RemoteLogConfig>,
+    pub share_group: ShareGroupConfig,
+    /// Properties the config was built from, reported as static broker configs.
+    pub properties: Properties,
+}
+
+impl Default for BrokerConfig {
+    fn default() -> Self {
+        BrokerConfig::from_properties(&Properties::default())
+            .expect("default broker config is valid")
+    }
+}
+
+impl BrokerConfig {
+    /// Build the config from the process arguments: an optional properties file
+    /// followed by any number of overrides.
+    pub fn from_args<I: IntoIterator<Item = String>>(args: I) -> Result<Self, ConfigError> {
+        let mut args = args.into_iter();
+        let mut properties = Properties::default();
+        let mut overrides = vec![];
+
+        while let Some(arg) = args.next() {
+            let (flag, inline_value) = match arg.split_once('=') {
+                Some((flag, value)) if flag.starts_with("--") => (flag, Some(value.to_string())),
+                _ => (arg.as_str(), None),
+            };
+            let key = match flag {
+                "--override" => None,
+                "--node-id" => Some("node.id"),
+                "--listeners" => Some("listeners"),
+                "--advertised-listeners" => Some("advertised.listeners"),
+                "--log-dirs" => Some("log.dirs"),
+                "--metadata-log-dir" => Some("metadata.log.dir"),
+                flag if flag.starts_with('-') => {
+                    return Err(ConfigError::UnknownArgument(flag.to_string()))
+                }
+                path => {
+                    properties = Properties::load(Path::new(path))?;
+                    continue;
+                }
+            };
+            let value = match inline_value {
+                Some(value) => value,
+                None => args
+                    .next()
+                    .ok_or_else(|| ConfigError::MissingArgument(flag.to_string()))?,
+            };
+            match key {
+                Some(key) => overrides.push((key.to_string(), value)),
+                None => {
+                    let Some((key, value)) = value.split_once('=') else {
+                        return Err(ConfigError::invalid(
+                            "--override",
+                            &value,
+                            "expected `key=value`",
+                        ));
+                    };
+                    overrides.push((key.trim().to_string(), value.trim().to_string()));
+                }
+            }
+        }
+
+        // Command-line overrides always win over the properties file
+        for (key, value) in overrides {
+            properties.set(&key, &value);
+        }
+        Self::from_properties(&properties)
+    }
+
+    pub fn from_properties(properties: &Properties) -> Result<Self, ConfigError> {
+        // Settings also exposed through DescribeConfigs follow the registry's rules
+        for (key, value) in &properties.0 {
+            if let Some(def) = config_registry::find(ConfigResourceType::Broker, key) {
+                def.validate(value)
+                    .map_err(|reason| ConfigError::invalid(key, value, reason))?;
+            }
+        }
+
+        let node_id = match properties.get("node.id") {
+            Some(value) => {
+                let id = value
+                    .parse::<i32>()
+                    .map_err(|e| ConfigError::invalid("node.id", value, e.to_string()))?;
+                if id < 0 {
+                    return Err(ConfigError::invalid(
+                        "node.id",
+                        value,
+                        "must be non-negative",
+                    ));
+                }
+                id
+            }
+            None => 1,
+        };
+
+        let rack = properties
+            .get("broker.rack")
+            .map(str::trim)
+            .filter(|rack| !rack.is_empty())
+            .map(str::to_string);
+
+        let listeners = match properties.get("listeners") {
+            Some(value) => parse_endpoints("listeners", value)?,
+            None => parse_endpoints("listeners", DEFAULT_LISTENERS)?,
+        };
+        for (i, endpoint) in listeners.iter().enumerate() {
+            if listeners[..i].iter().any(|l| l.port == endpoint.port) {
+                return Err(ConfigError::invalid(
+                    "listeners",
+                    properties.get("listeners").unwrap_or_default(),
+                    format!("port {} is used by more than one listener", endpoint.port),
+                ));
+            }
+        }
+        if listeners.is_empty() {
+            return Err(ConfigError::invalid(
+                "listeners",
+                properties.get("listeners").unwrap_or_default(),
+                "at least one listener is required",
+            ));
+        }
+
+        // Advertised listeners default to the bound listeners
+        let advertised_listeners = match properties.get("advertised.listeners") {
+            Some(value) => {
+                let advertised = parse_endpoints("advertised.listeners", value)?;
+                if let Some(endpoint) = advertised
+                    .iter()
+                    .find(|a| !listeners.iter().any(|l| l.name == a.name))
+                {
+                    return Err(ConfigError::invalid(
+                        "advertised.listeners",
+                        value,
+                        format!("{} is not declared in `listeners`", endpoint.name),
+                    ));
+                }
+                advertised
+            }
+            None => listeners.clone(),
+        };
+
+        // Every listener name must resolve to a security protocol
+        let listener_security_protocol_map = match properties.get("listener.security.protocol.map")
+        {
+            Some(value) => parse_protocol_map("listener.security.protocol.map", value)?,
+            None => parse_protocol_map("listener.security.protocol.map", DEFAULT_PROTOCOL_MAP)?,
+        };
+        if let Some(endpoint) = listeners
+            .iter()
+            .find(|l| !listener_security_protocol_map.contains_key(&l.name))
+        {
+            return Err(ConfigError::invalid(
+                "listener.security.protocol.map",
+                properties
+                    .get("listener.security.protocol.map")
+                    .unwrap_or_default(),
+                format!(
+                    "no security protocol defined for listener {}",
+                    endpoint.name
+                ),
+            ));
+        }
+
+        let mut ssl = HashMap::new();
+        let mut sasl = HashMap::new();
+        for endpoint in &listeners {
+            let protocol = listener_security_protocol_map[&endpoint.name];
+            if matches!(protocol, SecurityProtocol::Ssl | SecurityProtocol::SaslSsl) {
+                let ssl_config = SslConfig::from_properties(properties, &endpoint.name)?;
+                ssl.insert(endpoint.name.clone(), ssl_config);
+            }
+            if matches!(
+                protocol,
+                SecurityProtocol::SaslPlaintext | SecurityProtocol::SaslSsl
+            ) {
+                let sasl_config = SaslConfig::from_properties(properties, &endpoint.name)?;
+                sasl.insert(endpoint.name.clone(), sasl_config);
+            }
+        }
+
+        let authorizer = AuthorizerConfig::from_properties(properties)?;
+        let quota = QuotaConfig::from_properties(properties)?;
+        let replication = ReplicationConfig::from_properties(properties, &listeners)?;
+        let quorum = QuorumConfig::from_properties(
+            properties,
+            node_id,
+            &listeners,
+            &listener_security_protocol_map,
+        )?;
+
+        let log_dirs = match properties.get("log.dirs").or(properties.get("log.dir")) {
+            Some(value) => parse_paths("log.dirs", value)?,
+            None => vec![PathBuf::from(DEFAULT_LOG_DIR)],
+        };
+
+        // The metadata log lives in the first log directory unless told otherwise
+        let metadata_log_dir = match properties.get("metadata.log.dir") {
+            Some(value) => parse_paths("metadata.log.dir", value)?
+                .into_iter()
+                .next()
+                .unwrap_or_default(),
+            None => log_dirs[0].clone(),
+        };
+        let metadata_log = MetadataLogConfig::from_properties(properties)?;
+        let log_retention_check_interval_ms =
+            match properties.get("log.retention.check.interval.ms") {
+                Some(value) => value.trim().parse::<u64>().map_err(|_| {
+                    ConfigError::invalid(
+                        "log.retention.check.interval.ms",
+                        value,
+                        "expected a non-negative integer",
+                    )
+                })?,
+                None => 300_000,
+            };
+        let socket_request_max_bytes = match properties.get("socket.request.max.bytes") {
+            Some(value) => value
+                .trim()
+                .parse::<i32>()
+                .ok()
+                .filter(|&max| max > 0)
+                .ok_or_else(|| {
+                    ConfigError::invalid(
+                        "socket.request.max.bytes",
+                        value,
+                        "expected a positive integer",
+                    )
+                })? as usize,
+            None => 104_857_600,
+        };
+        let remote_log = RemoteLogConfig::from_properties(properties)?;
+        let share_group = ShareGroupConfig::from_properties(properties)?;
+
+        Ok(BrokerConfig {
+            node_id,
+            rack,
+            listeners,
+            advertised_listeners,
+            listener_security_protocol_map,
+            ssl,
+            sasl,
+            authorizer,
+            quota,
+            replication,
+            quorum,
+            log_dirs,
+            metadata_log_dir,
+            metadata_log,
+            log_retention_check_interval_ms,
+            socket_request_max_bytes,
+            remote_log,
+            share_group,
+            properties: properties.clone(),
+        })
+    }
+
+    pub fn security_protocol(&self, listener_name: &str) -> Option<SecurityProtocol> {
+        self.listener_security_protocol_map
+            .get(listener_name)
+            .copied()
+    }
+
+    /// Endpoint clients connected through `listener_name` should be told to use.
+    pub fn advertised_endpoint(&self, listener_name: &str) -> Option<&Endpoint> {
+        self.advertised_listeners
+            .iter()
+            .find(|e| e.name == listener_name)
+    }
+
+    /// Directory of the `__cluster_metadata` partition log.
+    pub fn metadata_partition_dir(&self) -> PathBuf {
+        self.metadata_log_dir.join(METADATA_PARTITION_DIR)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn properties(pairs: &[(&str, &str)]) -> Properties {
+        let mut properties = Properties::default();
+        for (key, value) in pairs {
+            properties.set(key, value);
+        }
+        properties
+    }
+
+    fn args(args: &[&str]) -> Result<BrokerConfig, ConfigError> {
+        BrokerConfig::from_args(args.iter().map(|arg| arg.to_string()))
+    }
+
+    /// Key of the invalid value the config was rejected for.
+    fn invalid_key(result: Result<BrokerConfig, ConfigError>) -> String {
+        match result {
+            Err(ConfigError::InvalidValue { key, .. }) => key,
+            other => panic!("expected an invalid value, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn parses_properties_files() {
+        let content = "\
+# comment
+! another comment
+
+node.id = 3
+listeners:PLAINTEXT://localhost:9093
+log.dirs=/tmp/a=b
+";
+        let properties = Properties::parse(Path::new("server.properties"), content).unwrap();
+        assert_eq!(properties.0.len(), 3);
+        assert_eq!(properties.get("node.id"), Some("3"));
+        assert_eq!(
+            properties.get("listeners"),
+            Some("PLAINTEXT://localhost:9093")
+        );
+        assert_eq!(properties.get("log.dirs"), Some("/tmp/a=b"));
+
+        let Err(ConfigError::MalformedLine { line, content, .. }) =
+            Properties::parse(Path::new("server.properties"), "node.id=1\nlisteners\n")
+        else {
+            panic!("expected a malformed line");
+        };
+        assert_eq!((line, content.as_str()), (2, "listeners"));
+    }
+
+    #[test]
+    fn looks_up_listener_settings_before_broker_wide_ones() {
+        let properties = properties(&[
+            ("ssl.client.auth", "none"),
+            ("listener.name.external.ssl.client.auth", "required"),
+        ]);
+        assert_eq!(
+            properties.get_for_listener("EXTERNAL", "ssl.client.auth"),
+            Some((
+                "listener.name.external.ssl.client.auth".to_string(),
+                "required"
+            ))
+        );
+        assert_eq!(
+            properties.get_for_listener("INTERNAL", "ssl.client.auth"),
+            Some(("ssl.client.auth".to_string(), "none"))
+        );
+    }
+
+    #[test]
+    fn command_line_flags_override_the_properties_file() {
+        let path = std::env::temp_dir().join(format!("config-{}.properties", std::process::id()));
+        fs::write(
+            &path,
+            "node.id=1\nlisteners=PLAINTEXT://127.0.0.1:9092\nlog.dirs=/tmp/file-logs\n",
+        )
+        .unwrap();
+        let config = args(&[
+            path.to_str().unwrap(),
+            "--node-id",
+            "4",
+            "--listeners=PLAINTEXT://127.0.0.1:9094",
+            "--override",
+            "broker.rack=rack-a",
+            "--override=log.dirs=/tmp/override-logs",
+        ])
+        .unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(config.node_id, 4);
+        assert_eq!(config.listeners[0].port, 9094);
+        assert_eq!(config.quorum.voters[0].port, 9094);
+        assert_eq!(config.rack.as_deref(), Some("rack-a"));
+        assert_eq!(config.log_dirs, vec![PathBuf::from("/tmp/override-logs")]);
+    }
+
+    #[test]
+    fn rejects_bad_command_lines() {
+        assert!(matches!(
+            args(&["--no-such-flag", "1"]),
+            Err(ConfigError::UnknownArgument(flag)) if flag == "--no-such-flag"
+        ));
+        assert!(matches!(
+            args(&["--node-id"]),
+            Err(ConfigError::MissingArgument(flag)) if flag == "--node-id"
+        ));
+        assert_eq!(invalid_key(args(&["--override", "node.id"])), "--override");
+        assert!(matches!(
+            args(&["/no/such/server.properties"]),
+            Err(ConfigError::Io { .. })
+        ));
+    }
+
+    #[test]
+    fn defaults_to_a_single_plaintext_listener() {
+        let config = BrokerConfig::default();
+        assert_eq!(config.node_id, 1);
+        assert_eq!(
+            config.listeners,
+            vec![Endpoint {
+                name: "PLAINTEXT".to_string(),
+                host: "127.0.0.1".to_string(),
+                port: 9092,
+            }]
+        );
+        assert_eq!(config.advertised_listeners, config.listeners);
+        assert_eq!(config.log_dirs, vec![PathBuf::from(DEFAULT_LOG_DIR)]);
+        assert_eq!(config.share_group.delivery_count_limit, 5);
+    }
+
+    #[test]
+    fn rejects_bad_values() {
+        let cases: &[(&str, &str)] = &[
+            ("node.id", "-1"),
+            ("node.id", "one"),
+            ("listeners", "127.0.0.1:9092"),
+            ("listeners", "PLAINTEXT://127.0.0.1:port"),
+            ("advertised.listeners", "OTHER://127.0.0.1:9092"),
+            ("log.dirs", "relative/logs"),
+            ("socket.request.max.bytes", "0"),
+            ("replica.selector.class", "org.example.Selector"),
+            ("group.share.delivery.count.limit", "1"),
+            ("group.share.delivery.count.limit", "11"),
+            ("group.share.delivery.count.limit", "40000"),
+            ("group.share.delivery.count.limit", "99999999999"),
+        ];
+        for &(key, value) in cases {
+            let result = BrokerConfig::from_properties(&properties(&[(key, value)]));
+            assert_eq!(invalid_key(result), key, "{key}={value}");
+        }
+
+        let duplicate_port = properties(&[(
+            "listeners",
+            "PLAINTEXT://127.0.0.1:9092,OTHER://127.0.0.1:9092",
+        )]);
+        assert_eq!(
+            invalid_key(BrokerConfig::from_properties(&duplicate_port)),
+            "listeners"
+        );
+    }
+
+    #[test]
+    fn reads_the_quota_window() {
+        let window = properties(&[
+            ("quota.window.num", "3"),
+            ("quota.window.size.seconds", "2147483647"),
+        ]);
+        let quota = BrokerConfig::from_properties(&window).unwrap().quota;
+        assert_eq!(quota.window_num, 3);
+        assert_eq!(quota.window_size_ms, 2_147_483_647_000);
+        assert_eq!(quota.max_age_ms(), 6_442_450_941_000);
+
+        for key in ["quota.window.num", "quota.window.size.seconds"] {
+            for value in ["0", "-1", "4294967296", "99999999999999999999"] {
+                let result = BrokerConfig::from_properties(&properties(&[(key, value)]));
+                assert_eq!(invalid_key(result), key, "{key}={value}");
+            }
+        }
+    }
+
+    #[test]
+    fn reads_the_remote_storage_directory_under_the_manager_prefix() {
+        let enabled = ("remote.log.storage.system.enable", "true");
+        let config = |pairs: &[(&str, &str)]| {
+            BrokerConfig::from_properties(&properties(&[&[enabled], pairs].concat()))
+        };
+        let remote_log = config(&[("rsm.config.dir", "/tmp/remote")])
+            .unwrap()
+            .remote_log
+            .unwrap();
+        assert_eq!(remote_log.storage_dir, PathBuf::from("/tmp/remote"));
+
+        let prefixed = config(&[
+            ("remote.log.storage.manager.impl.prefix", "tiered."),
+            ("tiered.dir", "/tmp/tiered"),
+        ]);
+        assert_eq!(
+            prefixed.unwrap().remote_log.unwrap().storage_dir,
+            PathBuf::from("/tmp/tiered")
+        );
+        assert_eq!(invalid_key(config(&[])), "rsm.config.dir");
+
+        let disabled = properties(&[("rsm.config.dir", "/tmp/remote")]);
+        assert!(BrokerConfig::from_properties(&disabled)
+            .unwrap()
+            .remote_log
+            .is_none());
+    }
+
+    #[test]
+    fn accepts_delivery_count_limits_in_range() {
+        for limit in [2, 10] {
+            let properties =
+                properties(&[("group.share.delivery.count.limit", &limit.to_string())]);
+            let config = BrokerConfig::from_properties(&properties).unwrap();
+            assert_eq!(config.share_group.delivery_count_limit, limit);
+        }
+    }
+}
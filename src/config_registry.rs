@@ -0,0 +1,1294 @@
+use std::{iter, str::FromStr};
+
+use crate::{
+    config::{
+        AuthorizerConfig, BrokerConfig, Properties, ReplicaSelector, DEFAULT_LISTENERS,
+        DEFAULT_LOG_DIR, DEFAULT_PROTOCOL_MAP, REMOTE_STORAGE_MANAGER_PREFIX,
+    },
+    metadata::MetadataImage,
+};
+
+/// Kind of resource configs are attached to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum ConfigResourceType {
+    Unknown,
+    Topic,
+    Broker,
+}
+
+impl ConfigResourceType {
+    pub fn from_code(code: i8) -> Self {
+        match code {
+            2 => ConfigResourceType::Topic,
+            4 => ConfigResourceType::Broker,
+            _ => ConfigResourceType::Unknown,
+        }
+    }
+
+    pub fn code(&self) -> i8 {
+        match self {
+            ConfigResourceType::Unknown => 0,
+            ConfigResourceType::Topic => 2,
+            ConfigResourceType::Broker => 4,
+        }
+    }
+}
+
+/// A topic, or a broker named by its node id; the empty broker name stands for the
+/// cluster-wide default of every broker.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ConfigResource {
+    pub resource_type: ConfigResourceType,
+    pub name: String,
+}
+
+impl ConfigResource {
+    pub fn new(resource_type: ConfigResourceType, name: impl Into<String>) -> Self {
+        ConfigResource {
+            resource_type,
+            name: name.into(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigType {
+    Boolean,
+    String,
+    Int,
+    Long,
+    Double,
+    List,
+    Class,
+    Password,
+}
+
+impl ConfigType {
+    pub fn code(&self) -> i8 {
+        match self {
+            ConfigType::Boolean => 1,
+            ConfigType::String => 2,
+            ConfigType::Int => 3,
+            ConfigType::Long => 5,
+            ConfigType::Double => 6,
+            ConfigType::List => 7,
+            ConfigType::Class => 8,
+            ConfigType::Password => 9,
+        }
+    }
+}
+
+/// Where the value of a config comes from, most to least preferred.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigSource {
+    DynamicTopic,
+    DynamicBroker,
+    DynamicDefaultBroker,
+    Static,
+    Default,
+}
+
+impl ConfigSource {
+    pub fn code(&self) -> i8 {
+        match self {
+            ConfigSource::DynamicTopic => 1,
+            ConfigSource::DynamicBroker => 2,
+            ConfigSource::DynamicDefaultBroker => 3,
+            ConfigSource::Static => 4,
+            ConfigSource::Default => 5,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum Validator {
+    Any,
+    AtLeast(i64),
+    /// Inclusive range of allowed values
+    Between(i64, i64),
+    /// Allowed values, or allowed items for lists, compared case-insensitively
+    OneOf(&'static [&'static str]),
+}
+
+#[derive(Debug)]
+pub struct ConfigDef {
+    pub name: &'static str,
+    pub config_type: ConfigType,
+    pub default: Option<&'static str>,
+    pub validator: Validator,
+    /// Whether the config can be altered at runtime; always the case for topic configs
+    pub dynamic: bool,
+    /// Broker configs this one falls back to, in order of precedence: the whole group
+    /// for a topic config, the less preferred members of its group for a broker config
+    pub synonyms: &'static [&'static str],
+    /// Factor converting a value to milliseconds, for time configs in minutes or hours
+    pub scale: i64,
+    pub documentation: &'static str,
+}
+
+impl ConfigDef {
+    const fn new(
+        name: &'static str,
+        config_type: ConfigType,
+        default: Option<&'static str>,
+        documentation: &'static str,
+    ) -> Self {
+        ConfigDef {
+            name,
+            config_type,
+            default,
+            validator: Validator::Any,
+            dynamic: false,
+            synonyms: &[],
+            scale: 1,
+            documentation,
+        }
+    }
+
+    const fn validator(mut self, validator: Validator) -> Self {
+        self.validator = validator;
+        self
+    }
+
+    const fn dynamic(mut self) -> Self {
+        self.dynamic = true;
+        self
+    }
+
+    const fn synonyms(mut self, synonyms: &'static [&'static str]) -> Self {
+        self.synonyms = synonyms;
+        self
+    }
+
+    const fn scale(mut self, scale: i64) -> Self {
+        self.scale = scale;
+        self
+    }
+
+    pub fn is_sensitive(&self) -> bool {
+        self.config_type == ConfigType::Password
+    }
+
+    /// Reason `value` is not acceptable for the config, if any.
+    pub fn validate(&self, value: &str) -> Result<(), String> {
+        let value = value.trim();
+        match self.config_type {
+            ConfigType::Boolean => {
+                if value != "true" && value != "false" {
+                    return Err("Expected value to be either true or false".to_string());
+                }
+            }
+            ConfigType::Int => {
+                let n = value
+                    .parse::<i32>()
+                    .map_err(|_| "Not a number of type INT".to_string())?;
+                self.check(value, n as i64)?;
+            }
+            ConfigType::Long => {
+                let n = value
+                    .parse::<i64>()
+                    .map_err(|_| "Not a number of type LONG".to_string())?;
+                self.check(value, n)?;
+            }
+            ConfigType::Double => {
+                value
+                    .parse::<f64>()
+                    .map_err(|_| "Not a number of type DOUBLE".to_string())?;
+            }
+            ConfigType::List => {
+                for item in value.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+                    self.check(item, 0)?;
+                }
+            }
+            ConfigType::String | ConfigType::Class | ConfigType::Password => {
+                self.check(value, 0)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn check(&self, value: &str, number: i64) -> Result<(), String> {
+        match self.validator {
+            Validator::Any => Ok(()),
+            Validator::AtLeast(min) if number < min => Err(format!("Value must be at least {min}")),
+            Validator::AtLeast(_) => Ok(()),
+            Validator::Between(min, max) if number < min || number > max => {
+                Err(format!("Value must be between {min} and {max}"))
+            }
+            Validator::Between(..) => Ok(()),
+            Validator::OneOf(allowed) => {
+                if allowed.iter().any(|a| a.eq_ignore_ascii_case(value)) {
+                    Ok(())
+                } else {
+                    Err(format!("String must be one of: {}", allowed.join(", ")))
+                }
+            }
+        }
+    }
+
+    /// Express a value of `from`, a synonym of this config, in this config's unit.
+    fn convert(&self, from: &ConfigDef, value: &str) -> String {
+        if from.scale == self.scale {
+            return value.to_string();
+        }
+        // Negative values such as -1 mean unlimited whatever the unit
+        match value.trim().parse::<i64>() {
+            Ok(n) if n >= 0 => (n.saturating_mul(from.scale) / self.scale).to_string(),
+            _ => value.to_string(),
+        }
+    }
+}
+
+const CLEANUP_POLICIES: &[&str] = &["compact", "delete"];
+const COMPRESSION_TYPES: &[&str] = &["uncompressed", "zstd", "lz4", "snappy", "gzip", "producer"];
+const TIMESTAMP_TYPES: &[&str] = &["CreateTime", "LogAppendTime"];
+const LONG_MAX: &str = "9223372036854775807";
+
+/// Configs of the topic resource, as in Apache Kafka's `LogConfig`.
+pub static TOPIC_CONFIGS: &[ConfigDef] = &[
+    ConfigDef::new(
+        "cleanup.policy",
+        ConfigType::List,
+        Some("delete"),
+        "Retention policy of old log segments: delete, compact or both.",
+    )
+    .validator(Validator::OneOf(CLEANUP_POLICIES))
+    .dynamic()
+    .synonyms(&["log.cleanup.policy"]),
+    ConfigDef::new(
+        "compression.type",
+        ConfigType::String,
+        Some("producer"),
+        "Final compression type of the topic, producer keeping the producer's codec.",
+    )
+    .validator(Validator::OneOf(COMPRESSION_TYPES))
+    .dynamic()
+    .synonyms(&["compression.type"]),
+    ConfigDef::new(
+        "delete.retention.ms",
+        ConfigType::Long,
+        Some("86400000"),
+        "How long delete tombstones are retained in compacted topics.",
+    )
+    .validator(Validator::AtLeast(0))
+    .dynamic()
+    .synonyms(&["log.cleaner.delete.retention.ms"]),
+    ConfigDef::new(
+        "file.delete.delay.ms",
+        ConfigType::Long,
+        Some("60000"),
+        "Time to wait before deleting a file from the filesystem.",
+    )
+    .validator(Validator::AtLeast(0))
+    .dynamic()
+    .synonyms(&["log.segment.delete.delay.ms"]),
+    ConfigDef::new(
+        "flush.messages",
+        ConfigType::Long,
+        Some(LONG_MAX),
+        "Number of messages written to the log before it is forced to disk.",
+    )
+    .validator(Validator::AtLeast(1))
+    .dynamic()
+    .synonyms(&["log.flush.interval.messages"]),
+    ConfigDef::new(
+        "flush.ms",
+        ConfigType::Long,
+        Some(LONG_MAX),
+        "Time after which the log is forced to disk.",
+    )
+    .validator(Validator::AtLeast(0))
+    .dynamic()
+    .synonyms(&["log.flush.interval.ms"]),
+    ConfigDef::new(
+        "follower.replication.throttled.replicas",
+        ConfigType::List,
+        Some(""),
+        "Replicas, as partition:broker or * for all, throttled when fetching from the leader.",
+    )
+    .dynamic(),
+    ConfigDef::new(
+        "leader.replication.throttled.replicas",
+        ConfigType::List,
+        Some(""),
+        "Replicas, as partition:broker or * for all, throttled when sending to followers.",
+    )
+    .dynamic(),
+    ConfigDef::new(
+        "local.retention.bytes",
+        ConfigType::Long,
+        Some("-2"),
+        "Maximum size of the local log of a tiered partition, -2 for retention.bytes.",
+    )
+    .validator(Validator::AtLeast(-2))
+    .dynamic()
+    .synonyms(&["log.local.retention.bytes"]),
+    ConfigDef::new(
+        "local.retention.ms",
+        ConfigType::Long,
+        Some("-2"),
+        "Maximum age of a local segment of a tiered partition, -2 for retention.ms.",
+    )
+    .validator(Validator::AtLeast(-2))
+    .dynamic()
+    .synonyms(&["log.local.retention.ms"]),
+    ConfigDef::new(
+        "max.message.bytes",
+        ConfigType::Int,
+        Some("1048588"),
+        "Largest record batch size allowed in the topic.",
+    )
+    .validator(Validator::AtLeast(0))
+    .dynamic()
+    .synonyms(&["message.max.bytes"]),
+    ConfigDef::new(
+        "message.timestamp.type",
+        ConfigType::String,
+        Some("CreateTime"),
+        "Whether record timestamps are set by the producer or on append.",
+    )
+    .validator(Validator::OneOf(TIMESTAMP_TYPES))
+    .dynamic()
+    .synonyms(&["log.message.timestamp.type"]),
+    ConfigDef::new(
+        "min.insync.replicas",
+        ConfigType::Int,
+        Some("1"),
+        "Minimum number of in-sync replicas for a write with acks=all to succeed.",
+    )
+    .validator(Validator::AtLeast(1))
+    .dynamic()
+    .synonyms(&["min.insync.replicas"]),
+    ConfigDef::new(
+        "remote.storage.enable",
+        ConfigType::Boolean,
+        Some("false"),
+        "Whether closed segments of the topic are copied to remote storage.",
+    )
+    .dynamic(),
+    ConfigDef::new(
+        "retention.bytes",
+        ConfigType::Long,
+        Some("-1"),
+        "Maximum size of a partition before old segments are discarded, -1 for no limit.",
+    )
+    .dynamic()
+    .synonyms(&["log.retention.bytes"]),
+    ConfigDef::new(
+        "retention.ms",
+        ConfigType::Long,
+        Some("604800000"),
+        "Maximum age of a log segment before it is discarded, -1 for no limit.",
+    )
+    .validator(Validator::AtLeast(-1))
+    .dynamic()
+    .synonyms(&[
+        "log.retention.ms",
+        "log.retention.minutes",
+        "log.retention.hours",
+    ]),
+    ConfigDef::new(
+        "segment.bytes",
+        ConfigType::Int,
+        Some("1073741824"),
+        "Size of a log segment file.",
+    )
+    .validator(Validator::AtLeast(14))
+    .dynamic()
+    .synonyms(&["log.segment.bytes"]),
+    ConfigDef::new(
+        "segment.index.bytes",
+        ConfigType::Int,
+        Some("10485760"),
+        "Size of the index mapping offsets to file positions.",
+    )
+    .validator(Validator::AtLeast(4))
+    .dynamic()
+    .synonyms(&["log.index.size.max.bytes"]),
+    ConfigDef::new(
+        "segment.ms",
+        ConfigType::Long,
+        Some("604800000"),
+        "Time after which a log segment is rolled even if it is not full.",
+    )
+    .validator(Validator::AtLeast(1))
+    .dynamic()
+    .synonyms(&["log.roll.ms", "log.roll.hours"]),
+    ConfigDef::new(
+        "unclean.leader.election.enable",
+        ConfigType::Boolean,
+        Some("false"),
+        "Whether replicas outside the ISR may be elected leader as a last resort.",
+    )
+    .dynamic()
+    .synonyms(&["unclean.leader.election.enable"]),
+];
+
+/// Configs of the broker resource; the dynamic ones also provide topic defaults.
+pub static BROKER_CONFIGS: &[ConfigDef] = &[
+    ConfigDef::new("node.id", ConfigType::Int, Some("1"), "Id of this node.")
+        .validator(Validator::AtLeast(0)),
+    ConfigDef::new(
+        "broker.rack",
+        ConfigType::String,
+        None,
+        "Rack of the broker, used for rack-aware replica placement.",
+    ),
+    ConfigDef::new(
+        "listeners",
+        ConfigType::String,
+        Some(DEFAULT_LISTENERS),
+        "Comma-separated `NAME://host:port` endpoints the broker listens on.",
+    ),
+    ConfigDef::new(
+        "advertised.listeners",
+        ConfigType::String,
+        None,
+        "Endpoints given to clients, when different from the listeners.",
+    ),
+    ConfigDef::new(
+        "listener.security.protocol.map",
+        ConfigType::String,
+        Some(DEFAULT_PROTOCOL_MAP),
+        "Security protocol of each listener name.",
+    ),
+    ConfigDef::new(
+        "socket.request.max.bytes",
+        ConfigType::Int,
+        Some("104857600"),
+        "Largest request frame accepted, larger ones closing the connection.",
+    )
+    .validator(Validator::AtLeast(1)),
+    ConfigDef::new(
+        "log.dirs",
+        ConfigType::List,
+        Some(DEFAULT_LOG_DIR),
+        "Directories the log data is stored in.",
+    ),
+    ConfigDef::new(
+        "metadata.log.dir",
+        ConfigType::String,
+        None,
+        "Directory of the cluster metadata log, the first log directory by default.",
+    ),
+    ConfigDef::new(
+        "metadata.log.segment.bytes",
+        ConfigType::Int,
+        Some("1073741824"),
+        "Size a segment of the metadata log grows to before a new one is started.",
+    )
+    .validator(Validator::AtLeast(12)),
+    ConfigDef::new(
+        "metadata.log.max.record.bytes.between.snapshots",
+        ConfigType::Long,
+        Some("20971520"),
+        "Bytes of committed metadata records after which a new snapshot is written.",
+    )
+    .validator(Validator::AtLeast(1)),
+    ConfigDef::new(
+        "metadata.log.max.snapshot.interval.ms",
+        ConfigType::Long,
+        Some("3600000"),
+        "Longest time between snapshots while new metadata records are committed, 0 to disable.",
+    )
+    .validator(Validator::AtLeast(0)),
+    ConfigDef::new(
+        "process.roles",
+        ConfigType::List,
+        Some("broker,controller"),
+        "Roles of the node: a broker, a controller, or both in combined mode.",
+    )
+    .validator(Validator::OneOf(&["broker", "controller"])),
+    ConfigDef::new(
+        "controller.quorum.voters",
+        ConfigType::List,
+        None,
+        "Comma-separated `id@host:port` controllers voting in the metadata quorum.",
+    ),
+    ConfigDef::new(
+        "controller.quorum.election.timeout.ms",
+        ConfigType::Int,
+        Some("1000"),
+        "Time without a leader after which a voter starts an election.",
+    )
+    .validator(Validator::AtLeast(1)),
+    ConfigDef::new(
+        "controller.quorum.election.backoff.max.ms",
+        ConfigType::Int,
+        Some("1000"),
+        "Longest pause before a candidate that lost an election tries again.",
+    )
+    .validator(Validator::AtLeast(1)),
+    ConfigDef::new(
+        "controller.quorum.fetch.timeout.ms",
+        ConfigType::Int,
+        Some("2000"),
+        "Time a follower may go without hearing from the leader before it stands for election.",
+    )
+    .validator(Validator::AtLeast(1)),
+    ConfigDef::new(
+        "broker.session.timeout.ms",
+        ConfigType::Int,
+        Some("9000"),
+        "Time a broker may go without heartbeating before the controller fences it.",
+    )
+    .validator(Validator::AtLeast(1)),
+    ConfigDef::new(
+        "broker.heartbeat.interval.ms",
+        ConfigType::Int,
+        Some("2000"),
+        "Time between the heartbeats of a broker to the controller.",
+    )
+    .validator(Validator::AtLeast(1)),
+    ConfigDef::new(
+        "sasl.enabled.mechanisms",
+        ConfigType::List,
+        Some("PLAIN"),
+        "SASL mechanisms enabled on SASL listeners.",
+    )
+    .validator(Validator::OneOf(&[
+        "PLAIN",
+        "SCRAM-SHA-256",
+        "SCRAM-SHA-512",
+    ])),
+    ConfigDef::new(
+        "sasl.jaas.config",
+        ConfigType::Password,
+        None,
+        "JAAS login context parameters of SASL connections.",
+    ),
+    ConfigDef::new(
+        "connections.max.reauth.ms",
+        ConfigType::Long,
+        Some("0"),
+        "Maximum SASL session lifetime before re-authentication, 0 to disable it.",
+    )
+    .validator(Validator::AtLeast(0)),
+    ConfigDef::new(
+        "ssl.keystore.location",
+        ConfigType::String,
+        None,
+        "PEM file holding the certificate chain and the private key.",
+    ),
+    ConfigDef::new(
+        "ssl.truststore.location",
+        ConfigType::String,
+        None,
+        "PEM file holding the CA certificates trusted for client authentication.",
+    ),
+    ConfigDef::new(
+        "ssl.client.auth",
+        ConfigType::String,
+        Some("none"),
+        "Whether SSL listeners request or require client certificates.",
+    )
+    .validator(Validator::OneOf(&["none", "requested", "required"])),
+    ConfigDef::new(
+        "authorizer.class.name",
+        ConfigType::String,
+        Some(""),
+        "Authorizer used to check client requests against ACLs.",
+    )
+    .validator(Validator::OneOf(&[
+        "",
+        AuthorizerConfig::STANDARD_AUTHORIZER,
+    ])),
+    ConfigDef::new(
+        "super.users",
+        ConfigType::String,
+        None,
+        "Semicolon-separated principals allowed every operation.",
+    ),
+    ConfigDef::new(
+        "allow.everyone.if.no.acl.found",
+        ConfigType::Boolean,
+        Some("false"),
+        "Whether resources without any ACL are open to everyone.",
+    ),
+    ConfigDef::new(
+        "quota.window.num",
+        ConfigType::Int,
+        Some("11"),
+        "Number of samples client quotas are measured over.",
+    )
+    .validator(Validator::AtLeast(1)),
+    ConfigDef::new(
+        "quota.window.size.seconds",
+        ConfigType::Int,
+        Some("1"),
+        "Time span of each client quota sample.",
+    )
+    .validator(Validator::AtLeast(1)),
+    ConfigDef::new(
+        "inter.broker.listener.name",
+        ConfigType::String,
+        None,
+        "Listener followers reach partition leaders through, the first listener by default.",
+    ),
+    ConfigDef::new(
+        "replica.lag.time.max.ms",
+        ConfigType::Long,
+        Some("30000"),
+        "Time a follower may go without catching up before it is removed from the ISR.",
+    )
+    .validator(Validator::AtLeast(0)),
+    ConfigDef::new(
+        "replica.fetch.wait.max.ms",
+        ConfigType::Int,
+        Some("500"),
+        "Maximum time a follower fetch waits at the leader for new records.",
+    )
+    .validator(Validator::AtLeast(0)),
+    ConfigDef::new(
+        "replica.fetch.backoff.ms",
+        ConfigType::Int,
+        Some("1000"),
+        "Time to wait before fetching again from a leader after an error.",
+    )
+    .validator(Validator::AtLeast(0)),
+    ConfigDef::new(
+        "replica.high.watermark.checkpoint.interval.ms",
+        ConfigType::Long,
+        Some("5000"),
+        "Frequency at which high watermarks are saved to disk.",
+    )
+    .validator(Validator::AtLeast(0)),
+    ConfigDef::new(
+        "replica.selector.class",
+        ConfigType::Class,
+        None,
+        "Selector of the replica consumers fetch from, the leader by default.",
+    )
+    .validator(Validator::OneOf(&[
+        "",
+        ReplicaSelector::LEADER_SELECTOR,
+        ReplicaSelector::RACK_AWARE_SELECTOR,
+    ])),
+    ConfigDef::new(
+        "group.share.heartbeat.interval.ms",
+        ConfigType::Int,
+        Some("5000"),
+        "Time share group members are told to wait between heartbeats.",
+    )
+    .validator(Validator::AtLeast(1)),
+    ConfigDef::new(
+        "group.share.session.timeout.ms",
+        ConfigType::Int,
+        Some("45000"),
+        "Time a share group member may go without a heartbeat before it is removed.",
+    )
+    .validator(Validator::AtLeast(1)),
+    ConfigDef::new(
+        "group.share.max.size",
+        ConfigType::Int,
+        Some("200"),
+        "Largest number of members a share group may have.",
+    )
+    .validator(Validator::AtLeast(1)),
+    ConfigDef::new(
+        "group.share.record.lock.duration.ms",
+        ConfigType::Int,
+        Some("30000"),
+        "Time a share group member holds the records it acquired before they are released.",
+    )
+    .validator(Validator::AtLeast(1)),
+    ConfigDef::new(
+        "group.share.delivery.count.limit",
+        ConfigType::Int,
+        Some("5"),
+        "Number of deliveries after which a record no longer acknowledged is archived.",
+    )
+    .validator(Validator::Between(2, 10)),
+    ConfigDef::new(
+        "group.share.partition.max.record.locks",
+        ConfigType::Int,
+        Some("2000"),
+        "Largest number of records in flight in a share partition.",
+    )
+    .validator(Validator::AtLeast(1)),
+    ConfigDef::new(
+        "share.coordinator.snapshot.update.records.per.snapshot",
+        ConfigType::Int,
+        Some("500"),
+        "Share partition states written to the state log between two snapshots of it.",
+    )
+    .validator(Validator::AtLeast(0)),
+    ConfigDef::new(
+        "num.partitions",
+        ConfigType::Int,
+        Some("1"),
+        "Default number of partitions of new topics.",
+    )
+    .validator(Validator::AtLeast(1)),
+    ConfigDef::new(
+        "default.replication.factor",
+        ConfigType::Int,
+        Some("1"),
+        "Default replication factor of new topics.",
+    )
+    .validator(Validator::AtLeast(1)),
+    ConfigDef::new(
+        "leader.replication.throttled.rate",
+        ConfigType::Long,
+        Some(LONG_MAX),
+        "Bytes per second the leader sends throttled replicas at, at most.",
+    )
+    .validator(Validator::AtLeast(0))
+    .dynamic(),
+    ConfigDef::new(
+        "follower.replication.throttled.rate",
+        ConfigType::Long,
+        Some(LONG_MAX),
+        "Bytes per second a follower fetches throttled replicas at, at most.",
+    )
+    .validator(Validator::AtLeast(0))
+    .dynamic(),
+    ConfigDef::new(
+        "log.cleanup.policy",
+        ConfigType::List,
+        Some("delete"),
+        "Default retention policy of old log segments.",
+    )
+    .validator(Validator::OneOf(CLEANUP_POLICIES))
+    .dynamic(),
+    ConfigDef::new(
+        "compression.type",
+        ConfigType::String,
+        Some("producer"),
+        "Default final compression type of topics.",
+    )
+    .validator(Validator::OneOf(COMPRESSION_TYPES))
+    .dynamic(),
+    ConfigDef::new(
+        "log.cleaner.delete.retention.ms",
+        ConfigType::Long,
+        Some("86400000"),
+        "Default time delete tombstones are retained in compacted topics.",
+    )
+    .validator(Validator::AtLeast(0))
+    .dynamic(),
+    ConfigDef::new(
+        "log.segment.delete.delay.ms",
+        ConfigType::Long,
+        Some("60000"),
+        "Default time to wait before deleting a file from the filesystem.",
+    )
+    .validator(Validator::AtLeast(0))
+    .dynamic(),
+    ConfigDef::new(
+        "log.flush.interval.messages",
+        ConfigType::Long,
+        Some(LONG_MAX),
+        "Default number of messages written before a log is forced to disk.",
+    )
+    .validator(Validator::AtLeast(1))
+    .dynamic(),
+    ConfigDef::new(
+        "log.flush.interval.ms",
+        ConfigType::Long,
+        Some(LONG_MAX),
+        "Default time after which a log is forced to disk.",
+    )
+    .validator(Validator::AtLeast(0))
+    .dynamic(),
+    ConfigDef::new(
+        "message.max.bytes",
+        ConfigType::Int,
+        Some("1048588"),
+        "Default largest record batch size allowed in a topic.",
+    )
+    .validator(Validator::AtLeast(0))
+    .dynamic(),
+    ConfigDef::new(
+        "log.message.timestamp.type",
+        ConfigType::String,
+        Some("CreateTime"),
+        "Default source of record timestamps.",
+    )
+    .validator(Validator::OneOf(TIMESTAMP_TYPES))
+    .dynamic(),
+    ConfigDef::new(
+        "min.insync.replicas",
+        ConfigType::Int,
+        Some("1"),
+        "Default minimum number of in-sync replicas for acks=all writes.",
+    )
+    .validator(Validator::AtLeast(1))
+    .dynamic(),
+    ConfigDef::new(
+        "log.retention.bytes",
+        ConfigType::Long,
+        Some("-1"),
+        "Default maximum size of a partition before old segments are discarded.",
+    )
+    .dynamic(),
+    ConfigDef::new(
+        "log.retention.ms",
+        ConfigType::Long,
+        None,
+        "Default maximum age of a log segment, in milliseconds.",
+    )
+    .validator(Validator::AtLeast(-1))
+    .dynamic()
+    .synonyms(&["log.retention.minutes", "log.retention.hours"]),
+    ConfigDef::new(
+        "log.retention.minutes",
+        ConfigType::Int,
+        None,
+        "Default maximum age of a log segment, in minutes.",
+    )
+    .validator(Validator::AtLeast(-1))
+    .dynamic()
+    .synonyms(&["log.retention.hours"])
+    .scale(60_000),
+    ConfigDef::new(
+        "log.retention.hours",
+        ConfigType::Int,
+        Some("168"),
+        "Default maximum age of a log segment, in hours.",
+    )
+    .validator(Validator::AtLeast(-1))
+    .dynamic()
+    .scale(3_600_000),
+    ConfigDef::new(
+        "log.retention.check.interval.ms",
+        ConfigType::Long,
+        Some("300000"),
+        "Frequency at which segments past their retention are looked for.",
+    )
+    .validator(Validator::AtLeast(1)),
+    ConfigDef::new(
+        "log.local.retention.bytes",
+        ConfigType::Long,
+        Some("-2"),
+        "Default maximum size of the local log of a tiered partition.",
+    )
+    .validator(Validator::AtLeast(-2))
+    .dynamic(),
+    ConfigDef::new(
+        "log.local.retention.ms",
+        ConfigType::Long,
+        Some("-2"),
+        "Default maximum age of a local segment of a tiered partition.",
+    )
+    .validator(Validator::AtLeast(-2))
+    .dynamic(),
+    ConfigDef::new(
+        "remote.log.storage.system.enable",
+        ConfigType::Boolean,
+        Some("false"),
+        "Whether topics may keep closed segments in remote storage.",
+    ),
+    ConfigDef::new(
+        "remote.log.storage.manager.impl.prefix",
+        ConfigType::String,
+        Some(REMOTE_STORAGE_MANAGER_PREFIX),
+        "Prefix of the remote storage manager settings, whose directory is `<prefix>dir`.",
+    ),
+    ConfigDef::new(
+        "remote.log.manager.task.interval.ms",
+        ConfigType::Long,
+        Some("30000"),
+        "Frequency at which segments are copied to remote storage and expired there.",
+    )
+    .validator(Validator::AtLeast(1)),
+    ConfigDef::new(
+        "log.segment.bytes",
+        ConfigType::Int,
+        Some("1073741824"),
+        "Default size of a log segment file.",
+    )
+    .validator(Validator::AtLeast(14))
+    .dynamic(),
+    ConfigDef::new(
+        "log.index.size.max.bytes",
+        ConfigType::Int,
+        Some("10485760"),
+        "Default size of the offset index.",
+    )
+    .validator(Validator::AtLeast(4))
+    .dynamic(),
+    ConfigDef::new(
+        "log.roll.ms",
+        ConfigType::Long,
+        None,
+        "Default time after which a log segment is rolled, in milliseconds.",
+    )
+    .validator(Validator::AtLeast(1))
+    .dynamic()
+    .synonyms(&["log.roll.hours"]),
+    ConfigDef::new(
+        "log.roll.hours",
+        ConfigType::Int,
+        Some("168"),
+        "Default time after which a log segment is rolled, in hours.",
+    )
+    .validator(Validator::AtLeast(1))
+    .dynamic()
+    .scale(3_600_000),
+    ConfigDef::new(
+        "unclean.leader.election.enable",
+        ConfigType::Boolean,
+        Some("false"),
+        "Default for electing replicas outside the ISR as a last resort.",
+    )
+    .dynamic(),
+];
+
+/// Configs defined for resources of the given type.
+pub fn configs(resource_type: ConfigResourceType) -> &'static [ConfigDef] {
+    match resource_type {
+        ConfigResourceType::Topic => TOPIC_CONFIGS,
+        ConfigResourceType::Broker => BROKER_CONFIGS,
+        ConfigResourceType::Unknown => &[],
+    }
+}
+
+pub fn find(resource_type: ConfigResourceType, name: &str) -> Option<&'static ConfigDef> {
+    configs(resource_type).iter().find(|def| def.name == name)
+}
+
+/// One of the values a config could take, as reported in DescribeConfigs synonyms.
+#[derive(Debug, Clone)]
+pub struct ConfigSynonym {
+    pub def: &'static ConfigDef,
+    pub value: String,
+    pub source: ConfigSource,
+}
+
+#[derive(Debug, Clone)]
+pub struct ResolvedConfig {
+    pub def: &'static ConfigDef,
+    /// Effective value in the unit of `def`, `None` when unset without default
+    pub value: Option<String>,
+    pub source: ConfigSource,
+    /// Every value the config could come from, the first one being in effect
+    pub synonyms: Vec<ConfigSynonym>,
+}
+
+/// Effective configs of a topic's log. It is resolved again on every use so that
+/// dynamic changes apply without a restart.
+#[derive(Debug, Clone)]
+pub struct LogConfig {
+    pub cleanup_policy: Vec<String>,
+    pub compression_type: String,
+    pub delete_retention_ms: i64,
+    pub file_delete_delay_ms: i64,
+    pub flush_messages: i64,
+    pub flush_ms: i64,
+    /// `partition:broker` entries, or `*` for every replica
+    pub follower_replication_throttled_replicas: Vec<String>,
+    pub leader_replication_throttled_replicas: Vec<String>,
+    /// `retention.bytes` when `local.retention.bytes` is -2
+    pub local_retention_bytes: i64,
+    /// `retention.ms` when `local.retention.ms` is -2
+    pub local_retention_ms: i64,
+    pub max_message_bytes: i32,
+    pub message_timestamp_type: String,
+    pub min_insync_replicas: i32,
+    pub remote_storage_enable: bool,
+    pub retention_bytes: i64,
+    pub retention_ms: i64,
+    pub segment_bytes: i32,
+    pub segment_index_bytes: i32,
+    pub segment_ms: i64,
+    pub unclean_leader_election_enable: bool,
+}
+
+/// Resolves configs across the dynamic configs of the metadata image, the static
+/// broker properties and the registry defaults.
+pub struct ConfigResolver<'a> {
+    metadata: &'a MetadataImage,
+    static_configs: &'a Properties,
+    node_id: i32,
+}
+
+impl<'a> ConfigResolver<'a> {
+    pub fn new(metadata: &'a MetadataImage, config: &'a BrokerConfig) -> Self {
+        ConfigResolver {
+            metadata,
+            static_configs: &config.properties,
+            node_id: config.node_id,
+        }
+    }
+
+    pub fn resolve(&self, resource: &ConfigResource, def: &'static ConfigDef) -> ResolvedConfig {
+        let mut synonyms = vec![];
+        let (broker_names, broker_id): (Vec<&str>, String) = match resource.resource_type {
+            ConfigResourceType::Topic => {
+                if let Some(value) = self.metadata.config(resource, def.name) {
+                    synonyms.push(ConfigSynonym {
+                        def,
+                        value: value.to_string(),
+                        source: ConfigSource::DynamicTopic,
+                    });
+                }
+                (def.synonyms.to_vec(), self.node_id.to_string())
+            }
+            _ => (
+                iter::once(def.name)
+                    .chain(def.synonyms.iter().copied())
+                    .collect(),
+                resource.name.clone(),
+            ),
+        };
+        let broker_defs: Vec<&'static ConfigDef> = broker_names
+            .into_iter()
+            .filter_map(|name| find(ConfigResourceType::Broker, name))
+            .collect();
+
+        // Same order as Apache Kafka: by source first, then by synonym precedence
+        let per_broker = ConfigResource::new(ConfigResourceType::Broker, broker_id);
+        let default_broker = ConfigResource::new(ConfigResourceType::Broker, "");
+        for source in [
+            ConfigSource::DynamicBroker,
+            ConfigSource::DynamicDefaultBroker,
+            ConfigSource::Static,
+            ConfigSource::Default,
+        ] {
+            for &broker_def in &broker_defs {
+                let value = match source {
+                    ConfigSource::DynamicBroker if !per_broker.name.is_empty() => {
+                        self.metadata.config(&per_broker, broker_def.name)
+                    }
+                    ConfigSource::DynamicDefaultBroker => {
+                        self.metadata.config(&default_broker, broker_def.name)
+                    }
+                    ConfigSource::Static => self.static_configs.get(broker_def.name),
+                    ConfigSource::Default => broker_def.default,
+                    _ => None,
+                };
+                if let Some(value) = value {
+                    synonyms.push(ConfigSynonym {
+                        def: broker_def,
+                        value: value.to_string(),
+                        source,
+                    });
+                }
+            }
+        }
+
+        let (value, source) = match synonyms.first() {
+            Some(synonym) => (
+                Some(def.convert(synonym.def, &synonym.value)),
+                synonym.source,
+            ),
+            None => (def.default.map(str::to_string), ConfigSource::Default),
+        };
+        ResolvedConfig {
+            def,
+            value,
+            source,
+            synonyms,
+        }
+    }
+
+    /// Effective log configs of the topic.
+    pub fn log_config(&self, topic: &str) -> LogConfig {
+        let resource = ConfigResource::new(ConfigResourceType::Topic, topic);
+        let retention_bytes = self.typed(&resource, "retention.bytes");
+        let retention_ms = self.typed(&resource, "retention.ms");
+        let local = |name: &str, total: i64| match self.typed(&resource, name) {
+            -2 => total,
+            local => local,
+        };
+        LogConfig {
+            cleanup_policy: self
+                .typed::<String>(&resource, "cleanup.policy")
+                .split(',')
+                .map(|s| s.trim().to_ascii_lowercase())
+                .filter(|s| !s.is_empty())
+                .collect(),
+            compression_type: self.typed(&resource, "compression.type"),
+            delete_retention_ms: self.typed(&resource, "delete.retention.ms"),
+            file_delete_delay_ms: self.typed(&resource, "file.delete.delay.ms"),
+            flush_messages: self.typed(&resource, "flush.messages"),
+            flush_ms: self.typed(&resource, "flush.ms"),
+            follower_replication_throttled_replicas: self
+                .list(&resource, "follower.replication.throttled.replicas"),
+            leader_replication_throttled_replicas: self
+                .list(&resource, "leader.replication.throttled.replicas"),
+            local_retention_bytes: local("local.retention.bytes", retention_bytes),
+            local_retention_ms: local("local.retention.ms", retention_ms),
+            max_message_bytes: self.typed(&resource, "max.message.bytes"),
+            message_timestamp_type: self.typed(&resource, "message.timestamp.type"),
+            min_insync_replicas: self.typed(&resource, "min.insync.replicas"),
+            remote_storage_enable: self.typed(&resource, "remote.storage.enable"),
+            retention_bytes,
+            retention_ms,
+            segment_bytes: self.typed(&resource, "segment.bytes"),
+            segment_index_bytes: self.typed(&resource, "segment.index.bytes"),
+            segment_ms: self.typed(&resource, "segment.ms"),
+            unclean_leader_election_enable: self.typed(&resource, "unclean.leader.election.enable"),
+        }
+    }
+
+    /// Effective value of a config of this broker.
+    pub fn broker_config<T: FromStr>(&self, name: &str) -> T {
+        let resource = ConfigResource::new(ConfigResourceType::Broker, self.node_id.to_string());
+        self.typed(&resource, name)
+    }
+
+    fn list(&self, resource: &ConfigResource, name: &str) -> Vec<String> {
+        self.typed::<String>(resource, name)
+            .split(',')
+            .map(|s| s.trim().to_string())
+            .filter(|s| !s.is_empty())
+            .collect()
+    }
+
+    fn typed<T: FromStr>(&self, resource: &ConfigResource, name: &str) -> T {
+        let def = find(resource.resource_type, name).expect("config is registered");
+        // Values are validated before being stored, the default covers older records
+        self.resolve(resource, def)
+            .value
+            .and_then(|value| value.trim().parse().ok())
+            .or_else(|| def.default.and_then(|value| value.parse().ok()))
+            .expect("registered configs read this way have a valid default")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn broker_config(pairs: &[(&str, &str)]) -> BrokerConfig {
+        let mut properties = Properties::default();
+        properties.set("node.id", "1");
+        for (key, value) in pairs {
+            properties.set(key, value);
+        }
+        BrokerConfig::from_properties(&properties).unwrap()
+    }
+
+    fn set(image: &mut MetadataImage, resource: &ConfigResource, name: &str, value: &str) {
+        image
+            .configs
+            .entry(resource.clone())
+            .or_default()
+            .insert(name.to_string(), value.to_string());
+    }
+
+    #[test]
+    fn finds_configs_of_each_resource_type() {
+        let def = find(ConfigResourceType::Topic, "retention.ms").unwrap();
+        assert_eq!(def.config_type, ConfigType::Long);
+        assert_eq!(def.default, Some("604800000"));
+        assert!(find(ConfigResourceType::Broker, "retention.ms").is_none());
+        assert!(find(ConfigResourceType::Broker, "log.retention.ms").is_some());
+        assert!(find(ConfigResourceType::Topic, "log.retention.ms").is_none());
+        assert!(find(ConfigResourceType::Unknown, "retention.ms").is_none());
+
+        for resource_type in [ConfigResourceType::Topic, ConfigResourceType::Broker] {
+            assert_eq!(
+                ConfigResourceType::from_code(resource_type.code()),
+                resource_type
+            );
+            // Every synonym is a broker config
+            for def in configs(resource_type) {
+                for synonym in def.synonyms {
+                    assert!(
+                        find(ConfigResourceType::Broker, synonym).is_some(),
+                        "{synonym}"
+                    );
+                }
+            }
+        }
+        assert_eq!(
+            ConfigResourceType::from_code(3),
+            ConfigResourceType::Unknown
+        );
+    }
+
+    #[test]
+    fn validates_values_against_the_type_and_validator() {
+        let topic = |name| find(ConfigResourceType::Topic, name).unwrap();
+
+        assert_eq!(topic("remote.storage.enable").validate(" true "), Ok(()));
+        assert_eq!(
+            topic("remote.storage.enable").validate("yes"),
+            Err("Expected value to be either true or false".to_string())
+        );
+        assert_eq!(topic("segment.bytes").validate("1024"), Ok(()));
+        assert_eq!(
+            topic("segment.bytes").validate("2147483648"),
+            Err("Not a number of type INT".to_string())
+        );
+        assert_eq!(
+            topic("segment.bytes").validate("13"),
+            Err("Value must be at least 14".to_string())
+        );
+        assert_eq!(topic("retention.ms").validate("-1"), Ok(()));
+        assert_eq!(
+            topic("retention.ms").validate("1h"),
+            Err("Not a number of type LONG".to_string())
+        );
+        // Allowed strings and list items compare case-insensitively
+        assert_eq!(topic("compression.type").validate("GZIP"), Ok(()));
+        assert!(topic("compression.type").validate("brotli").is_err());
+        assert_eq!(topic("cleanup.policy").validate("compact, Delete"), Ok(()));
+        assert!(topic("cleanup.policy")
+            .validate("compact,forever")
+            .unwrap_err()
+            .starts_with("String must be one of: "));
+    }
+
+    #[test]
+    fn resolves_dynamic_then_static_then_default_values() {
+        let mut image = MetadataImage::default();
+        let topic = ConfigResource::new(ConfigResourceType::Topic, "topic");
+        let retention = find(ConfigResourceType::Topic, "retention.ms").unwrap();
+
+        // The default of the least preferred synonym, in the unit of the topic config
+        let config = broker_config(&[]);
+        let resolved = ConfigResolver::new(&image, &config).resolve(&topic, retention);
+        assert_eq!(resolved.value.as_deref(), Some("604800000"));
+        assert_eq!(resolved.source, ConfigSource::Default);
+        assert_eq!(resolved.synonyms[0].def.name, "log.retention.hours");
+
+        let config = broker_config(&[("log.retention.minutes", "10")]);
+        let resolved = ConfigResolver::new(&image, &config).resolve(&topic, retention);
+        assert_eq!(resolved.value.as_deref(), Some("600000"));
+        assert_eq!(resolved.source, ConfigSource::Static);
+
+        let default_broker = ConfigResource::new(ConfigResourceType::Broker, "");
+        set(&mut image, &default_broker, "log.retention.ms", "5000");
+        let resolved = ConfigResolver::new(&image, &config).resolve(&topic, retention);
+        assert_eq!(resolved.value.as_deref(), Some("5000"));
+        assert_eq!(resolved.source, ConfigSource::DynamicDefaultBroker);
+
+        // The source comes before the precedence of the synonyms
+        let this_broker = ConfigResource::new(ConfigResourceType::Broker, "1");
+        set(&mut image, &this_broker, "log.retention.hours", "1");
+        let resolved = ConfigResolver::new(&image, &config).resolve(&topic, retention);
+        assert_eq!(resolved.value.as_deref(), Some("3600000"));
+        assert_eq!(resolved.source, ConfigSource::DynamicBroker);
+
+        set(&mut image, &topic, "retention.ms", "42");
+        let resolver = ConfigResolver::new(&image, &config);
+        let resolved = resolver.resolve(&topic, retention);
+        assert_eq!(resolved.value.as_deref(), Some("42"));
+        assert_eq!(resolved.source, ConfigSource::DynamicTopic);
+        let sources: Vec<ConfigSource> = resolved.synonyms.iter().map(|s| s.source).collect();
+        assert_eq!(
+            sources,
+            [
+                ConfigSource::DynamicTopic,
+                ConfigSource::DynamicBroker,
+                ConfigSource::DynamicDefaultBroker,
+                ConfigSource::Static,
+                ConfigSource::Default,
+            ]
+        );
+        assert_eq!(resolver.log_config("topic").retention_ms, 42);
+        assert_eq!(resolver.log_config("other").retention_ms, 3_600_000);
+
+        // Another broker only sees the cluster-wide default
+        let other_broker = ConfigResource::new(ConfigResourceType::Broker, "2");
+        let log_retention = find(ConfigResourceType::Broker, "log.retention.ms").unwrap();
+        let resolved = resolver.resolve(&other_broker, log_retention);
+        assert_eq!(resolved.value.as_deref(), Some("5000"));
+        assert_eq!(resolved.source, ConfigSource::DynamicDefaultBroker);
+        assert_eq!(resolver.broker_config::<i64>("log.retention.hours"), 1);
+    }
+}
@@ -0,0 +1,1216 @@
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    sync::{Arc, Mutex},
+    thread,
+    time::{Duration, Instant},
+};
+
+use rand::Rng;
+use uuid::Uuid;
+
+use crate::{
+    broker::Broker,
+    config_registry::ConfigResolver,
+    metadata::{BrokerRegistration, MetadataImage, PartitionRegistration},
+    protocol::{
+        broker_heartbeat::BrokerHeartbeatRequest,
+        broker_registration::BrokerRegistrationRequest,
+        cluster_metadata::{
+            BrokerEpochRecord, BrokerRegistrationChangeRecord, MetadataRecord,
+            PartitionChangeRecord, PartitionRecord, RegisterBrokerRecord,
+        },
+        error,
+    },
+    raft::RaftError,
+};
+
+/// How an ElectLeaders request picks the new leader of a partition.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ElectionType {
+    /// Move the leadership back to the first replica of the assignment
+    Preferred,
+    /// Elect a replica outside the ISR when no in-sync replica is available
+    Unclean,
+}
+
+impl ElectionType {
+    pub fn from_code(code: i8) -> Option<Self> {
+        match code {
+            0 => Some(ElectionType::Preferred),
+            1 => Some(ElectionType::Unclean),
+            _ => None,
+        }
+    }
+}
+
+/// Heartbeats of the registered brokers, as seen by the active controller. Sessions
+/// start over whenever the controller changes, so that a new controller gives every
+/// broker a full session to reach it.
+#[derive(Default)]
+pub struct BrokerHeartbeatManager {
+    sessions: Mutex<Sessions>,
+}
+
+#[derive(Default)]
+struct Sessions {
+    /// Epoch of the metadata quorum the sessions were tracked in
+    epoch: i32,
+    /// When the sessions of brokers yet to heartbeat in this epoch started
+    started: Option<Instant>,
+    last_heartbeat: HashMap<i32, Instant>,
+}
+
+impl BrokerHeartbeatManager {
+    /// Record a heartbeat of `broker_id` to the controller of `epoch`.
+    pub fn heartbeat(&self, epoch: i32, broker_id: i32) {
+        let mut sessions = self.sessions.lock().unwrap();
+        sessions.start_epoch(epoch);
+        sessions.last_heartbeat.insert(broker_id, Instant::now());
+    }
+
+    /// Whether `broker_id` heartbeated to the controller of `epoch` within `timeout`.
+    fn is_alive(&self, epoch: i32, broker_id: i32, timeout: Duration) -> bool {
+        let mut sessions = self.sessions.lock().unwrap();
+        sessions.start_epoch(epoch);
+        sessions
+            .last_heartbeat
+            .get(&broker_id)
+            .is_some_and(|last| last.elapsed() <= timeout)
+    }
+
+    /// Brokers among `brokers` that went without heartbeating for `timeout`.
+    fn expired(&self, epoch: i32, brokers: &[i32], timeout: Duration) -> Vec<i32> {
+        let mut sessions = self.sessions.lock().unwrap();
+        sessions.start_epoch(epoch);
+        let now = Instant::now();
+        let started = *sessions.started.get_or_insert(now);
+        brokers
+            .iter()
+            .copied()
+            .filter(|id| {
+                let last = sessions.last_heartbeat.get(id).copied().unwrap_or(started);
+                now.duration_since(last) > timeout
+            })
+            .collect()
+    }
+}
+
+impl Sessions {
+    fn start_epoch(&mut self, epoch: i32) {
+        if self.epoch != epoch {
+            self.epoch = epoch;
+            self.started = None;
+            self.last_heartbeat.clear();
+        }
+    }
+}
+
+/// Start fencing the brokers whose session expired and electing leaders for the
+/// partitions left without one, whenever this node is the active controller.
+pub fn start(broker: &Arc<Broker>) {
+    let broker = Arc::clone(broker);
+    thread::spawn(move || {
+        let interval = Duration::from_millis(broker.config.quorum.broker_heartbeat_interval_ms);
+        loop {
+            thread::sleep(interval);
+            if !broker.raft.is_leader() {
+                continue;
+            }
+            if let Err(e) = maintain_brokers(&broker) {
+                eprintln!("Failed to update broker sessions and partition leaders: {e:#}");
+            }
+        }
+    });
+}
+
+fn maintain_brokers(broker: &Broker) -> anyhow::Result<()> {
+    let epoch = broker.raft.current_leader().leader_epoch;
+    let timeout = Duration::from_millis(broker.config.quorum.broker_session_timeout_ms);
+    broker.append_metadata(|_| {
+        let image = broker.metadata.read().unwrap();
+        let unfenced: Vec<i32> = image
+            .brokers
+            .values()
+            .filter(|registration| !registration.fenced)
+            .map(|registration| registration.id)
+            .collect();
+        let expired = broker.heartbeats.expired(epoch, &unfenced, timeout);
+        let mut records = vec![];
+        let mut changes = FencingChanges::new();
+        for broker_id in expired {
+            eprintln!("Fencing broker {broker_id}, whose session expired");
+            records.push(MetadataRecord::FenceBroker(BrokerEpochRecord {
+                broker_id,
+                broker_epoch: image.brokers[&broker_id].epoch,
+            }));
+            changes.insert(broker_id, true);
+        }
+        records.extend(partition_changes(broker, &image, &changes));
+        records
+    })
+}
+
+/// Register a broker, fenced until a heartbeat shows it caught up with the metadata log,
+/// and return the epoch of the registration. A broker may replace the registration of a
+/// previous incarnation once that one is fenced, shut down cleanly or stopped
+/// heartbeating.
+pub fn register_broker(broker: &Broker, request: &BrokerRegistrationRequest) -> Result<i64, i16> {
+    if !broker.raft.is_leader() {
+        return Err(error::NOT_CONTROLLER);
+    }
+    let epoch = broker.raft.current_leader().leader_epoch;
+    let timeout = Duration::from_millis(broker.config.quorum.broker_session_timeout_ms);
+    let broker_id = request.broker_id;
+    let mut outcome = Err(error::UNKNOWN_SERVER_ERROR);
+    broker
+        .append_metadata(|next_offset| {
+            let image = broker.metadata.read().unwrap();
+            if let Some(existing) = image.brokers.get(&broker_id) {
+                let replaceable = existing.incarnation_id == request.incarnation_id
+                    || existing.fenced
+                    || existing.in_controlled_shutdown
+                    || existing.epoch == request.previous_broker_epoch
+                    || !broker.heartbeats.is_alive(epoch, broker_id, timeout);
+                if !replaceable {
+                    outcome = Err(error::DUPLICATE_BROKER_REGISTRATION);
+                    return vec![];
+                }
+            }
+            eprintln!("Registering broker {broker_id} with epoch {next_offset}");
+            let mut records = vec![MetadataRecord::RegisterBroker(RegisterBrokerRecord {
+                broker_id,
+                is_migrating_zk_broker: request.is_migrating_zk_broker,
+                incarnation_id: request.incarnation_id,
+                // The offset of the registration identifies it, as in Apache Kafka
+                broker_epoch: next_offset,
+                end_points: request.listeners.clone(),
+                features: request.features.clone(),
+                rack: request.rack.clone(),
+                fenced: true,
+                in_controlled_shutdown: false,
+                log_dirs: request.log_dirs.clone(),
+            })];
+            // Partitions the previous incarnation leads move to other replicas meanwhile
+            let changes = FencingChanges::from([(broker_id, true)]);
+            records.extend(partition_changes(broker, &image, &changes));
+            outcome = Ok(next_offset);
+            records
+        })
+        .map_err(|e| RaftError::error_code(&e))?;
+    if outcome.is_ok() {
+        broker.heartbeats.heartbeat(epoch, broker_id);
+    }
+    outcome
+}
+
+/// State of a broker registration reported in a BrokerHeartbeat response.
+#[derive(Debug, Clone, Copy)]
+pub struct BrokerHeartbeatState {
+    pub is_caught_up: bool,
+    pub is_fenced: bool,
+    pub should_shut_down: bool,
+}
+
+/// Keep the session of a broker alive and move its registration along as it asks: a
+/// caught up broker is unfenced, and a broker shutting down is fenced once its
+/// leaderships moved to other replicas.
+pub fn process_heartbeat(
+    broker: &Broker,
+    request: &BrokerHeartbeatRequest,
+) -> Result<BrokerHeartbeatState, i16> {
+    if !broker.raft.is_leader() {
+        return Err(error::NOT_CONTROLLER);
+    }
+    let epoch = broker.raft.current_leader().leader_epoch;
+    let broker_id = request.broker_id;
+    let mut outcome = Err(error::BROKER_ID_NOT_REGISTERED);
+    broker
+        .append_metadata(|_| {
+            let image = broker.metadata.read().unwrap();
+            let Some(registration) = image.brokers.get(&broker_id) else {
+                return vec![];
+            };
+            if registration.epoch != request.broker_epoch {
+                outcome = Err(error::STALE_BROKER_EPOCH);
+                return vec![];
+            }
+            broker.heartbeats.heartbeat(epoch, broker_id);
+
+            let is_caught_up = request.current_metadata_offset >= registration.epoch;
+            let mut state = BrokerHeartbeatState {
+                is_caught_up,
+                is_fenced: registration.fenced,
+                should_shut_down: false,
+            };
+            let mut records = vec![];
+            let mut changes = FencingChanges::new();
+            if request.want_shut_down {
+                // Leaderships move in the same write, so the broker may exit once it commits
+                if !registration.in_controlled_shutdown {
+                    eprintln!("Broker {broker_id} is shutting down, moving its leaderships");
+                    let mut change =
+                        BrokerRegistrationChangeRecord::new(broker_id, registration.epoch);
+                    change.fenced = (!registration.fenced).then_some(true);
+                    change.in_controlled_shutdown = true;
+                    records.push(MetadataRecord::BrokerRegistrationChange(change));
+                    changes.insert(broker_id, true);
+                }
+                state.is_fenced = true;
+                state.should_shut_down = true;
+            } else if request.want_fence {
+                if !registration.fenced {
+                    eprintln!("Fencing broker {broker_id}, as it asked");
+                    records.push(MetadataRecord::FenceBroker(BrokerEpochRecord {
+                        broker_id,
+                        broker_epoch: registration.epoch,
+                    }));
+                    changes.insert(broker_id, true);
+                }
+                state.is_fenced = true;
+            } else if registration.fenced && is_caught_up && !registration.in_controlled_shutdown {
+                eprintln!("Unfencing broker {broker_id}, which caught up");
+                records.push(MetadataRecord::UnfenceBroker(BrokerEpochRecord {
+                    broker_id,
+                    broker_epoch: registration.epoch,
+                }));
+                changes.insert(broker_id, false);
+                state.is_fenced = false;
+            }
+            records.extend(partition_changes(broker, &image, &changes));
+            outcome = Ok(state);
+            records
+        })
+        .map_err(|e| RaftError::error_code(&e))?;
+    outcome
+}
+
+/// Remove the registration of a broker, moving the leaderships it holds to other
+/// replicas.
+pub fn unregister_broker(broker: &Broker, broker_id: i32) -> Result<(), i16> {
+    if !broker.raft.is_leader() {
+        return Err(error::NOT_CONTROLLER);
+    }
+    let mut outcome = Err(error::BROKER_ID_NOT_REGISTERED);
+    broker
+        .append_metadata(|_| {
+            let image = broker.metadata.read().unwrap();
+            let Some(registration) = image.brokers.get(&broker_id) else {
+                return vec![];
+            };
+            eprintln!("Unregistering broker {broker_id}");
+            let mut records = vec![MetadataRecord::UnregisterBroker(BrokerEpochRecord {
+                broker_id,
+                broker_epoch: registration.epoch,
+            })];
+            let changes = FencingChanges::from([(broker_id, true)]);
+            records.extend(partition_changes(broker, &image, &changes));
+            outcome = Ok(());
+            records
+        })
+        .map_err(|e| RaftError::error_code(&e))?;
+    outcome
+}
+
+/// Fencing brought about by the records under construction: brokers being fenced, shut
+/// down or unregistered map to `true`, brokers being unfenced to `false`.
+type FencingChanges = BTreeMap<i32, bool>;
+
+/// Partition changes following the fencing changes, with the unclean leader election
+/// config of each topic.
+fn partition_changes(
+    broker: &Broker,
+    image: &MetadataImage,
+    changes: &FencingChanges,
+) -> Vec<MetadataRecord> {
+    if changes.is_empty() {
+        return vec![];
+    }
+    let resolver = ConfigResolver::new(image, &broker.config);
+    offline_partition_changes(image, changes, |topic| {
+        resolver.log_config(topic).unclean_leader_election_enable
+    })
+    .into_iter()
+    .map(MetadataRecord::PartitionChange)
+    .collect()
+}
+
+/// Whether a broker may lead partitions: registered, and neither fenced nor shutting
+/// down.
+fn is_acceptable_leader(image: &MetadataImage, changes: &FencingChanges, broker_id: i32) -> bool {
+    image.brokers.contains_key(&broker_id) && !is_fenced(image, changes, broker_id)
+}
+
+/// Whether a broker is registered and fenced or shutting down, or about to be.
+fn is_fenced(image: &MetadataImage, changes: &FencingChanges, broker_id: i32) -> bool {
+    changes.get(&broker_id).copied().unwrap_or_else(|| {
+        image
+            .brokers
+            .get(&broker_id)
+            .is_some_and(|registration| registration.fenced || registration.in_controlled_shutdown)
+    })
+}
+
+/// Changes removing fenced brokers from the ISRs and electing a leader for the
+/// partitions whose leader is fenced or missing. The ISR keeps its last member, so that
+/// it still names the replica holding every committed record.
+fn offline_partition_changes(
+    image: &MetadataImage,
+    changes: &FencingChanges,
+    unclean_allowed: impl Fn(&str) -> bool,
+) -> Vec<PartitionChangeRecord> {
+    let mut records = vec![];
+    for (topic, topic_id) in &image.topics {
+        let partitions = image.partitions.get(topic_id).into_iter().flatten();
+        for (&partition, registration) in partitions {
+            let mut isr: Vec<i32> = registration
+                .isr
+                .iter()
+                .copied()
+                .filter(|&id| !is_fenced(image, changes, id))
+                .collect();
+            if isr.is_empty() {
+                isr = registration.isr.clone();
+            }
+            let has_leader = registration.leader >= 0
+                && !is_fenced(image, changes, registration.leader)
+                && isr.contains(&registration.leader);
+            let mut leader = registration.leader;
+            if !has_leader {
+                let acceptable = |id: &i32| is_acceptable_leader(image, changes, *id);
+                leader = match clean_leader(registration, &isr, acceptable) {
+                    Some(leader) => leader,
+                    None if unclean_allowed(topic) => {
+                        match registration.replicas.iter().copied().find(acceptable) {
+                            Some(unclean) => {
+                                eprintln!(
+                                    "Electing broker {unclean} leader of {topic}-{partition} \
+                                     from outside the ISR"
+                                );
+                                isr = vec![unclean];
+                                unclean
+                            }
+                            None => -1,
+                        }
+                    }
+                    None => -1,
+                };
+            }
+
+            let mut record = PartitionChangeRecord::new(*topic_id, partition);
+            if isr != registration.isr {
+                record.isr = Some(isr);
+            }
+            if leader != registration.leader {
+                record.leader = leader;
+            }
+            if record.isr.is_some() || record.leader != PartitionChangeRecord::NO_LEADER_CHANGE {
+                records.push(record);
+            }
+        }
+    }
+    records
+}
+
+/// First replica of the assignment, the preferred one first, that is in sync and may
+/// lead.
+fn clean_leader(
+    registration: &PartitionRegistration,
+    isr: &[i32],
+    acceptable: impl Fn(&i32) -> bool,
+) -> Option<i32> {
+    registration
+        .replicas
+        .iter()
+        .copied()
+        .find(|id| isr.contains(id) && acceptable(id))
+}
+
+/// Change electing a leader of the partition as an ElectLeaders request asks, or the
+/// error code explaining why there is none.
+pub fn elect_leader(
+    image: &MetadataImage,
+    topic_id: Uuid,
+    partition: i32,
+    registration: &PartitionRegistration,
+    election_type: ElectionType,
+) -> Result<PartitionChangeRecord, i16> {
+    let no_changes = FencingChanges::new();
+    let acceptable = |id: &i32| is_acceptable_leader(image, &no_changes, *id);
+    let mut record = PartitionChangeRecord::new(topic_id, partition);
+    match election_type {
+        ElectionType::Preferred => {
+            let &preferred = registration
+                .replicas
+                .first()
+                .ok_or(error::PREFERRED_LEADER_NOT_AVAILABLE)?;
+            if registration.leader == preferred {
+                return Err(error::ELECTION_NOT_NEEDED);
+            }
+            if !registration.isr.contains(&preferred) || !acceptable(&preferred) {
+                return Err(error::PREFERRED_LEADER_NOT_AVAILABLE);
+            }
+            record.leader = preferred;
+        }
+        ElectionType::Unclean => {
+            if registration.leader >= 0 && acceptable(&registration.leader) {
+                return Err(error::ELECTION_NOT_NEEDED);
+            }
+            if let Some(leader) = clean_leader(registration, &registration.isr, acceptable) {
+                record.leader = leader;
+            } else {
+                let leader = registration
+                    .replicas
+                    .iter()
+                    .copied()
+                    .find(acceptable)
+                    .ok_or(error::ELIGIBLE_LEADERS_NOT_AVAILABLE)?;
+                record.leader = leader;
+                record.isr = Some(vec![leader]);
+            }
+        }
+    }
+    Ok(record)
+}
+
+/// Change reassigning a partition to `target`, or cancelling the ongoing reassignment
+/// when `target` is `None`, and `None` when nothing changes. Replicas new to the
+/// assignment join it first and catch up as followers; the others only leave once every
+/// new replica is in sync. A reassignment with nothing to catch up completes at once.
+pub fn reassign_partition(
+    image: &MetadataImage,
+    topic_id: Uuid,
+    partition: i32,
+    registration: &PartitionRegistration,
+    target: Option<&[i32]>,
+    allow_replication_factor_change: bool,
+) -> Result<Option<PartitionChangeRecord>, i16> {
+    // Assignment from before the ongoing reassignment, if any
+    let original: Vec<i32> = registration
+        .replicas
+        .iter()
+        .copied()
+        .filter(|id| !registration.adding_replicas.contains(id))
+        .collect();
+    let target = match target {
+        None if !registration.is_reassigning() => return Err(error::NO_REASSIGNMENT_IN_PROGRESS),
+        None => original.clone(),
+        Some(target) => {
+            check_assignment(image, target)?;
+            if !allow_replication_factor_change && target.len() != original.len() {
+                return Err(error::INVALID_REPLICATION_FACTOR);
+            }
+            target.to_vec()
+        }
+    };
+
+    let adding_replicas: Vec<i32> = target
+        .iter()
+        .copied()
+        .filter(|id| !original.contains(id))
+        .collect();
+    let removing_replicas: Vec<i32> = registration
+        .replicas
+        .iter()
+        .copied()
+        .filter(|id| !target.contains(id))
+        .collect();
+    let mut replicas = target;
+    replicas.extend(&removing_replicas);
+    let reassigning = PartitionRegistration {
+        directories: registration.directories_of(&replicas),
+        replicas,
+        adding_replicas,
+        removing_replicas,
+        ..registration.clone()
+    };
+    if !reassigning.is_reassigning() {
+        if !registration.is_reassigning() && reassigning.replicas == registration.replicas {
+            return Ok(None);
+        }
+        // Only the order of the replicas changes
+        let mut record = PartitionChangeRecord::new(topic_id, partition);
+        record.replicas = Some(reassigning.replicas);
+        record.directories = Some(reassigning.directories);
+        record.adding_replicas = Some(vec![]);
+        record.removing_replicas = Some(vec![]);
+        return Ok(Some(record));
+    }
+    if let Some(record) =
+        complete_reassignment(image, topic_id, partition, &reassigning, &registration.isr)
+    {
+        return Ok(Some(record));
+    }
+    let mut record = PartitionChangeRecord::new(topic_id, partition);
+    record.replicas = Some(reassigning.replicas);
+    record.directories = Some(reassigning.directories);
+    record.adding_replicas = Some(reassigning.adding_replicas);
+    record.removing_replicas = Some(reassigning.removing_replicas);
+    Ok(Some(record))
+}
+
+/// Change completing the reassignment of a partition once `isr` holds every replica it
+/// adds and one of those it keeps. The removed replicas leave the assignment and the
+/// ISR, handing the leadership over to a kept replica if they held it.
+pub fn complete_reassignment(
+    image: &MetadataImage,
+    topic_id: Uuid,
+    partition: i32,
+    registration: &PartitionRegistration,
+    isr: &[i32],
+) -> Option<PartitionChangeRecord> {
+    if !registration.is_reassigning()
+        || !registration
+            .adding_replicas
+            .iter()
+            .all(|id| isr.contains(id))
+    {
+        return None;
+    }
+    let target: Vec<i32> = registration
+        .replicas
+        .iter()
+        .copied()
+        .filter(|id| !registration.removing_replicas.contains(id))
+        .collect();
+    let isr: Vec<i32> = isr
+        .iter()
+        .copied()
+        .filter(|id| target.contains(id))
+        .collect();
+    if isr.is_empty() {
+        return None;
+    }
+
+    let mut record = PartitionChangeRecord::new(topic_id, partition);
+    if !isr.contains(&registration.leader) {
+        let no_changes = FencingChanges::new();
+        let leader = target
+            .iter()
+            .copied()
+            .find(|id| isr.contains(id) && is_acceptable_leader(image, &no_changes, *id))
+            .unwrap_or(-1);
+        if leader != registration.leader {
+            record.leader = leader;
+        }
+    }
+    record.directories = Some(registration.directories_of(&target));
+    record.replicas = Some(target);
+    record.isr = Some(isr);
+    record.adding_replicas = Some(vec![]);
+    record.removing_replicas = Some(vec![]);
+    Some(record)
+}
+
+/// Check that replicas given by a client form an assignment: some registered brokers,
+/// each named once.
+pub fn check_assignment(image: &MetadataImage, replicas: &[i32]) -> Result<(), i16> {
+    let distinct: BTreeSet<&i32> = replicas.iter().collect();
+    if replicas.is_empty()
+        || distinct.len() != replicas.len()
+        || !replicas.iter().all(|id| image.brokers.contains_key(id))
+    {
+        return Err(error::INVALID_REPLICA_ASSIGNMENT);
+    }
+    Ok(())
+}
+
+/// Assignments of `count` new partitions numbered from `first_partition`, as Apache
+/// Kafka places them. The brokers that may lead are lined up alternating between racks,
+/// brokers without a rack sharing one, and each partition starts at the next broker in
+/// line from a random start. Its other replicas follow at a shift that changes once per
+/// round over the brokers, taking brokers from racks the partition is not in yet while
+/// there are any.
+pub fn place_replicas(
+    image: &MetadataImage,
+    first_partition: i32,
+    count: i32,
+    replication_factor: i32,
+) -> Result<Vec<Vec<i32>>, i16> {
+    let no_changes = FencingChanges::new();
+    let usable: Vec<&BrokerRegistration> = image
+        .brokers
+        .values()
+        .filter(|registration| is_acceptable_leader(image, &no_changes, registration.id))
+        .collect();
+    if replication_factor <= 0 || replication_factor as usize > usable.len() {
+        return Err(error::INVALID_REPLICATION_FACTOR);
+    }
+
+    let mut by_rack: BTreeMap<Option<&str>, Vec<i32>> = BTreeMap::new();
+    for registration in &usable {
+        by_rack
+            .entry(registration.rack.as_deref())
+            .or_default()
+            .push(registration.id);
+    }
+    let num_racks = by_rack.len();
+    let mut arranged = vec![];
+    for round in 0.. {
+        if arranged.len() == usable.len() {
+            break;
+        }
+        arranged.extend(by_rack.values().filter_map(|ids| ids.get(round)));
+    }
+    let rack_of = |id: i32| image.brokers.get(&id).and_then(|b| b.rack.as_deref());
+
+    let num_brokers = arranged.len();
+    let mut rng = rand::thread_rng();
+    let start = rng.gen_range(0..num_brokers);
+    let mut shift = rng.gen_range(0..num_brokers);
+    let mut assignments = vec![];
+    for partition in first_partition.max(0)..first_partition.max(0) + count {
+        let partition = partition as usize;
+        if partition > 0 && partition % num_brokers == 0 {
+            shift += 1;
+        }
+        let first = (partition + start) % num_brokers;
+        let mut replicas = vec![arranged[first]];
+        let mut racks = BTreeSet::from([rack_of(arranged[first])]);
+        let mut k = 0;
+        while replicas.len() < replication_factor as usize {
+            let offset = 1 + (shift * num_racks + k) % (num_brokers - 1);
+            let id = arranged[(first + offset) % num_brokers];
+            k += 1;
+            let rack = rack_of(id);
+            if (!racks.contains(&rack) || racks.len() == num_racks) && !replicas.contains(&id) {
+                racks.insert(rack);
+                replicas.push(id);
+            }
+        }
+        assignments.push(replicas);
+    }
+    Ok(assignments)
+}
+
+/// Record of a new partition assigned to `replicas`, led by the first of them that may
+/// lead. The ISR holds every replica that could join it right away.
+pub fn new_partition(
+    image: &MetadataImage,
+    topic_id: Uuid,
+    partition_id: i32,
+    replicas: Vec<i32>,
+) -> Result<PartitionRecord, i16> {
+    let no_changes = FencingChanges::new();
+    let isr: Vec<i32> = replicas
+        .iter()
+        .copied()
+        .filter(|&id| is_acceptable_leader(image, &no_changes, id))
+        .collect();
+    let &leader = isr.first().ok_or(error::INVALID_REPLICA_ASSIGNMENT)?;
+    Ok(PartitionRecord {
+        partition_id,
+        topic_id,
+        replicas,
+        isr,
+        removing_replicas: vec![],
+        adding_replicas: vec![],
+        leader,
+        leader_epoch: 0,
+        partition_epoch: 0,
+        directories: vec![],
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::PathBuf};
+
+    use super::*;
+    use crate::config::{BrokerConfig, Properties};
+
+    /// An image of unfenced brokers, numbered from 1, in the racks given.
+    fn image(racks: &[Option<&str>]) -> MetadataImage {
+        let mut image = MetadataImage::default();
+        for (i, rack) in racks.iter().enumerate() {
+            let id = i as i32 + 1;
+            image.brokers.insert(
+                id,
+                BrokerRegistration {
+                    id,
+                    epoch: 0,
+                    incarnation_id: Uuid::nil(),
+                    endpoints: vec![],
+                    rack: rack.map(str::to_string),
+                    fenced: false,
+                    in_controlled_shutdown: false,
+                    log_dirs: vec![],
+                },
+            );
+        }
+        image
+    }
+
+    fn racks_of(image: &MetadataImage, replicas: &[i32]) -> BTreeSet<Option<String>> {
+        replicas
+            .iter()
+            .map(|id| image.brokers[id].rack.clone())
+            .collect()
+    }
+
+    fn assert_distinct(replicas: &[i32]) {
+        let distinct: BTreeSet<&i32> = replicas.iter().collect();
+        assert_eq!(distinct.len(), replicas.len(), "{replicas:?}");
+    }
+
+    #[test]
+    fn spreads_replicas_over_racks() {
+        let racks = [
+            Some("a"),
+            Some("a"),
+            Some("b"),
+            Some("b"),
+            Some("c"),
+            Some("c"),
+        ];
+        let image = image(&racks);
+        // The start and shift are random, so try a few of them
+        for _ in 0..20 {
+            for replication_factor in [2, 3] {
+                let assignments = place_replicas(&image, 0, 12, replication_factor).unwrap();
+                assert_eq!(assignments.len(), 12);
+                for replicas in &assignments {
+                    assert_eq!(replicas.len(), replication_factor as usize);
+                    assert_distinct(replicas);
+                    assert_eq!(
+                        racks_of(&image, replicas).len(),
+                        replication_factor as usize,
+                        "{replicas:?}"
+                    );
+                }
+                // Every broker leads as many partitions
+                let mut leaders: BTreeMap<i32, usize> = BTreeMap::new();
+                for replicas in &assignments {
+                    *leaders.entry(replicas[0]).or_default() += 1;
+                }
+                assert!(leaders.values().all(|&count| count == 2), "{leaders:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn covers_every_rack_when_there_are_more_replicas_than_racks() {
+        let image = image(&[Some("a"), Some("a"), Some("a"), Some("b")]);
+        for _ in 0..20 {
+            for replicas in place_replicas(&image, 3, 8, 3).unwrap() {
+                assert_distinct(&replicas);
+                assert_eq!(racks_of(&image, &replicas).len(), 2, "{replicas:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn places_brokers_without_a_rack_together() {
+        let image = image(&[None, None, None]);
+        for _ in 0..20 {
+            for replicas in place_replicas(&image, 0, 6, 3).unwrap() {
+                assert_distinct(&replicas);
+            }
+        }
+    }
+
+    #[test]
+    fn leaves_out_brokers_that_may_not_lead() {
+        let mut image = image(&[Some("a"), Some("b"), Some("c"), Some("c")]);
+        image.brokers.get_mut(&1).unwrap().fenced = true;
+        image.brokers.get_mut(&4).unwrap().in_controlled_shutdown = true;
+        for _ in 0..20 {
+            for replicas in place_replicas(&image, 0, 4, 2).unwrap() {
+                assert!(
+                    replicas.iter().all(|id| [2, 3].contains(id)),
+                    "{replicas:?}"
+                );
+                assert_distinct(&replicas);
+            }
+        }
+        assert_eq!(
+            place_replicas(&image, 0, 1, 3),
+            Err(error::INVALID_REPLICATION_FACTOR)
+        );
+        assert_eq!(
+            place_replicas(&image, 0, 1, 0),
+            Err(error::INVALID_REPLICATION_FACTOR)
+        );
+    }
+
+    /// A partition of `replicas`, led by `leader`.
+    fn partition(replicas: &[i32], isr: &[i32], leader: i32) -> PartitionRegistration {
+        PartitionRegistration {
+            replicas: replicas.to_vec(),
+            isr: isr.to_vec(),
+            leader,
+            leader_epoch: 0,
+            partition_epoch: 0,
+            directories: vec![],
+            adding_replicas: vec![],
+            removing_replicas: vec![],
+        }
+    }
+
+    /// Add topic `topic` with the given partitions, numbered from 0, to the image.
+    fn add_topic(image: &mut MetadataImage, partitions: Vec<PartitionRegistration>) -> Uuid {
+        let topic_id = Uuid::from_u128(1);
+        image.topics.insert("topic".to_string(), topic_id);
+        image.partitions.insert(
+            topic_id,
+            partitions
+                .into_iter()
+                .enumerate()
+                .map(|(i, registration)| (i as i32, registration))
+                .collect(),
+        );
+        topic_id
+    }
+
+    /// A single-node cluster, whose node is the active controller and registered broker 1.
+    fn broker(name: &str) -> (PathBuf, Broker) {
+        let dir = std::env::temp_dir().join(format!("{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let mut properties = Properties::default();
+        for (key, value) in [
+            ("node.id", "1"),
+            ("process.roles", "broker,controller"),
+            ("controller.quorum.voters", "1@127.0.0.1:9093"),
+            (
+                "listeners",
+                "PLAINTEXT://127.0.0.1:9092,CONTROLLER://127.0.0.1:9093",
+            ),
+            (
+                "listener.security.protocol.map",
+                "PLAINTEXT:PLAINTEXT,CONTROLLER:PLAINTEXT",
+            ),
+            ("controller.listener.names", "CONTROLLER"),
+            ("log.dirs", &dir.join("logs").display().to_string()),
+        ] {
+            properties.set(key, value);
+        }
+        let broker = Broker::new(BrokerConfig::from_properties(&properties).unwrap()).unwrap();
+        (dir, broker)
+    }
+
+    fn registration_request(broker_id: i32) -> BrokerRegistrationRequest {
+        BrokerRegistrationRequest {
+            version: BrokerRegistrationRequest::LATEST_VERSION,
+            broker_id,
+            cluster_id: String::new(),
+            incarnation_id: Uuid::from_u128(broker_id as u128),
+            listeners: vec![],
+            features: vec![],
+            rack: None,
+            is_migrating_zk_broker: false,
+            log_dirs: vec![],
+            previous_broker_epoch: -1,
+        }
+    }
+
+    fn heartbeat_request(broker_id: i32, broker_epoch: i64, offset: i64) -> BrokerHeartbeatRequest {
+        BrokerHeartbeatRequest {
+            version: BrokerHeartbeatRequest::LATEST_VERSION,
+            broker_id,
+            broker_epoch,
+            current_metadata_offset: offset,
+            want_fence: false,
+            want_shut_down: false,
+            offline_log_dirs: vec![],
+        }
+    }
+
+    #[test]
+    fn expires_sessions_without_heartbeats() {
+        let heartbeats = BrokerHeartbeatManager::default();
+        let timeout = Duration::from_millis(50);
+        heartbeats.heartbeat(1, 1);
+        // Brokers yet to heartbeat get a full session from the first check
+        assert_eq!(heartbeats.expired(1, &[1, 2], timeout), Vec::<i32>::new());
+        assert!(heartbeats.is_alive(1, 1, timeout));
+        assert!(!heartbeats.is_alive(1, 2, timeout));
+
+        thread::sleep(timeout * 2);
+        heartbeats.heartbeat(1, 1);
+        assert_eq!(heartbeats.expired(1, &[1, 2], timeout), vec![2]);
+
+        // A new controller starts every session over
+        assert_eq!(heartbeats.expired(2, &[1, 2], timeout), Vec::<i32>::new());
+        assert!(!heartbeats.is_alive(2, 1, timeout));
+    }
+
+    #[test]
+    fn moves_partitions_off_fenced_brokers() {
+        let mut image = image(&[None, None, None]);
+        let topic_id = add_topic(
+            &mut image,
+            vec![
+                partition(&[1, 2, 3], &[1, 2, 3], 1),
+                partition(&[2, 1], &[2, 1], 2),
+                partition(&[1, 2], &[1], 1),
+            ],
+        );
+        let changes = FencingChanges::from([(1, true)]);
+
+        let records = offline_partition_changes(&image, &changes, |_| false);
+        let by_partition: BTreeMap<i32, &PartitionChangeRecord> = records
+            .iter()
+            .map(|record| (record.partition_id, record))
+            .collect();
+        assert!(records.iter().all(|record| record.topic_id == topic_id));
+        // The next replica in sync takes over
+        assert_eq!(by_partition[&0].leader, 2);
+        assert_eq!(by_partition[&0].isr, Some(vec![2, 3]));
+        // Only the ISR shrinks when the broker follows
+        assert_eq!(
+            by_partition[&1].leader,
+            PartitionChangeRecord::NO_LEADER_CHANGE
+        );
+        assert_eq!(by_partition[&1].isr, Some(vec![2]));
+        // The last member of the ISR stays in it, leaving the partition offline
+        assert_eq!(by_partition[&2].leader, -1);
+        assert_eq!(by_partition[&2].isr, None);
+
+        // Unclean elections pick a replica outside the ISR instead
+        let records = offline_partition_changes(&image, &changes, |topic| topic == "topic");
+        let unclean = records
+            .iter()
+            .find(|record| record.partition_id == 2)
+            .unwrap();
+        assert_eq!(unclean.leader, 2);
+        assert_eq!(unclean.isr, Some(vec![2]));
+    }
+
+    #[test]
+    fn elects_leaders_on_unfenced_brokers() {
+        let mut image = image(&[None, None]);
+        image.brokers.get_mut(&1).unwrap().fenced = true;
+        add_topic(
+            &mut image,
+            vec![partition(&[1, 2], &[1], -1), partition(&[2], &[2], 2)],
+        );
+        assert!(offline_partition_changes(&image, &FencingChanges::new(), |_| false).is_empty());
+
+        let changes = FencingChanges::from([(1, false)]);
+        let records = offline_partition_changes(&image, &changes, |_| false);
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].partition_id, 0);
+        assert_eq!(records[0].leader, 1);
+        assert_eq!(records[0].isr, None);
+    }
+
+    #[test]
+    fn fences_and_unfences_brokers_as_they_heartbeat() {
+        let (dir, broker) = broker("controller-heartbeats");
+        let epoch = register_broker(&broker, &registration_request(2)).unwrap();
+        assert!(broker.metadata.read().unwrap().brokers[&2].fenced);
+
+        // Fenced until caught up with its registration
+        let state = process_heartbeat(&broker, &heartbeat_request(2, epoch, epoch - 1)).unwrap();
+        assert!(!state.is_caught_up);
+        assert!(state.is_fenced);
+        let state = process_heartbeat(&broker, &heartbeat_request(2, epoch, epoch)).unwrap();
+        assert!(state.is_caught_up);
+        assert!(!state.is_fenced);
+        assert!(!broker.metadata.read().unwrap().brokers[&2].fenced);
+
+        let mut request = heartbeat_request(2, epoch, epoch);
+        request.want_fence = true;
+        let state = process_heartbeat(&broker, &request).unwrap();
+        assert!(state.is_fenced);
+        assert!(broker.metadata.read().unwrap().brokers[&2].fenced);
+
+        let mut request = heartbeat_request(2, epoch, epoch);
+        request.want_shut_down = true;
+        let state = process_heartbeat(&broker, &request).unwrap();
+        assert!(state.is_fenced);
+        assert!(state.should_shut_down);
+        let registration = broker.metadata.read().unwrap().brokers[&2].clone();
+        assert!(registration.fenced && registration.in_controlled_shutdown);
+        // Shutting down keeps a broker fenced whatever it asks
+        let state = process_heartbeat(&broker, &heartbeat_request(2, epoch, epoch)).unwrap();
+        assert!(state.is_fenced);
+
+        assert_eq!(
+            process_heartbeat(&broker, &heartbeat_request(2, epoch - 1, epoch)).unwrap_err(),
+            error::STALE_BROKER_EPOCH
+        );
+        assert_eq!(
+            process_heartbeat(&broker, &heartbeat_request(3, epoch, epoch)).unwrap_err(),
+            error::BROKER_ID_NOT_REGISTERED
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn refuses_to_replace_a_live_registration() {
+        let (dir, broker) = broker("controller-registrations");
+        let epoch = register_broker(&broker, &registration_request(2)).unwrap();
+        process_heartbeat(&broker, &heartbeat_request(2, epoch, epoch)).unwrap();
+
+        let mut request = registration_request(2);
+        request.incarnation_id = Uuid::from_u128(20);
+        assert_eq!(
+            register_broker(&broker, &request),
+            Err(error::DUPLICATE_BROKER_REGISTRATION)
+        );
+        // Unless it is the registration the broker shut down cleanly with
+        request.previous_broker_epoch = epoch;
+        let replaced = register_broker(&broker, &request).unwrap();
+        assert!(replaced > epoch);
+        assert!(broker.metadata.read().unwrap().brokers[&2].fenced);
+
+        unregister_broker(&broker, 2).unwrap();
+        assert!(!broker.metadata.read().unwrap().brokers.contains_key(&2));
+        assert_eq!(
+            unregister_broker(&broker, 2),
+            Err(error::BROKER_ID_NOT_REGISTERED)
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn elects_the_preferred_leader() {
+        let mut image = image(&[None, None, None]);
+        let topic_id = Uuid::from_u128(1);
+        let elect = |image: &MetadataImage, registration: &PartitionRegistration| {
+            elect_leader(image, topic_id, 0, registration, ElectionType::Preferred)
+        };
+
+        let record = elect(&image, &partition(&[1, 2, 3], &[1, 2, 3], 2)).unwrap();
+        assert_eq!(record.leader, 1);
+        assert_eq!(record.isr, None);
+        assert_eq!(
+            elect(&image, &partition(&[1, 2, 3], &[1, 2, 3], 1)).unwrap_err(),
+            error::ELECTION_NOT_NEEDED
+        );
+        assert_eq!(
+            elect(&image, &partition(&[1, 2, 3], &[2, 3], 2)).unwrap_err(),
+            error::PREFERRED_LEADER_NOT_AVAILABLE
+        );
+        image.brokers.get_mut(&1).unwrap().in_controlled_shutdown = true;
+        assert_eq!(
+            elect(&image, &partition(&[1, 2, 3], &[1, 2, 3], 2)).unwrap_err(),
+            error::PREFERRED_LEADER_NOT_AVAILABLE
+        );
+    }
+
+    #[test]
+    fn elects_a_leader_outside_the_isr_only_when_unclean() {
+        let mut image = image(&[None, None, None]);
+        image.brokers.get_mut(&1).unwrap().fenced = true;
+        let topic_id = Uuid::from_u128(1);
+        let elect = |image: &MetadataImage, registration: &PartitionRegistration| {
+            elect_leader(image, topic_id, 0, registration, ElectionType::Unclean)
+        };
+
+        assert_eq!(
+            elect(&image, &partition(&[1, 2, 3], &[2, 3], 2)).unwrap_err(),
+            error::ELECTION_NOT_NEEDED
+        );
+        // An acceptable replica in sync keeps the ISR as it is
+        let record = elect(&image, &partition(&[1, 2, 3], &[1, 3], -1)).unwrap();
+        assert_eq!(record.leader, 3);
+        assert_eq!(record.isr, None);
+        // Otherwise the first acceptable replica becomes the only one in sync
+        let record = elect(&image, &partition(&[1, 2, 3], &[1], 1)).unwrap();
+        assert_eq!(record.leader, 2);
+        assert_eq!(record.isr, Some(vec![2]));
+
+        image.brokers.get_mut(&2).unwrap().fenced = true;
+        assert_eq!(
+            elect(&image, &partition(&[1, 2], &[1], -1)).unwrap_err(),
+            error::ELIGIBLE_LEADERS_NOT_AVAILABLE
+        );
+        assert_eq!(ElectionType::from_code(1), Some(ElectionType::Unclean));
+        assert_eq!(ElectionType::from_code(2), None);
+    }
+
+    #[test]
+    fn reassigns_partitions_once_new_replicas_catch_up() {
+        let image = image(&[None, None, None]);
+        let topic_id = Uuid::from_u128(1);
+        let registration = partition(&[1, 2], &[1, 2], 1);
+
+        // The new replica joins first, the removed one leaves once it is in sync
+        let record = reassign_partition(&image, topic_id, 0, &registration, Some(&[2, 3]), false)
+            .unwrap()
+            .unwrap();
+        assert_eq!(record.replicas, Some(vec![2, 3, 1]));
+        assert_eq!(record.adding_replicas, Some(vec![3]));
+        assert_eq!(record.removing_replicas, Some(vec![1]));
+        assert_eq!(record.isr, None);
+        assert_eq!(record.leader, PartitionChangeRecord::NO_LEADER_CHANGE);
+
+        let reassigning = PartitionRegistration {
+            adding_replicas: vec![3],
+            removing_replicas: vec![1],
+            ..partition(&[2, 3, 1], &[1, 2], 1)
+        };
+        assert!(complete_reassignment(&image, topic_id, 0, &reassigning, &[1, 2]).is_none());
+        let record = complete_reassignment(&image, topic_id, 0, &reassigning, &[1, 2, 3]).unwrap();
+        assert_eq!(record.replicas, Some(vec![2, 3]));
+        assert_eq!(record.isr, Some(vec![2, 3]));
+        assert_eq!(record.leader, 2);
+        assert_eq!(record.adding_replicas, Some(vec![]));
+        assert_eq!(record.removing_replicas, Some(vec![]));
+
+        // Cancelling goes back to the original replicas
+        let record = reassign_partition(&image, topic_id, 0, &reassigning, None, false)
+            .unwrap()
+            .unwrap();
+        let mut replicas = record.replicas.unwrap();
+        replicas.sort();
+        assert_eq!(replicas, vec![1, 2]);
+        assert_eq!(record.adding_replicas, Some(vec![]));
+        assert_eq!(record.removing_replicas, Some(vec![]));
+    }
+
+    #[test]
+    fn completes_reassignments_with_nothing_to_catch_up() {
+        let image = image(&[None, None, None]);
+        let topic_id = Uuid::from_u128(1);
+        let registration = partition(&[1, 2], &[1, 2], 1);
+        let reassign = |target: Option<&[i32]>, allow_replication_factor_change| {
+            reassign_partition(
+                &image,
+                topic_id,
+                0,
+                &registration,
+                target,
+                allow_replication_factor_change,
+            )
+        };
+
+        assert!(reassign(Some(&[1, 2]), false).unwrap().is_none());
+        let record = reassign(Some(&[2, 1]), false).unwrap().unwrap();
+        assert_eq!(record.replicas, Some(vec![2, 1]));
+        assert_eq!(record.adding_replicas, Some(vec![]));
+        assert_eq!(record.removing_replicas, Some(vec![]));
+
+        let record = reassign(Some(&[2]), true).unwrap().unwrap();
+        assert_eq!(record.replicas, Some(vec![2]));
+        assert_eq!(record.isr, Some(vec![2]));
+        assert_eq!(record.leader, 2);
+        assert_eq!(record.adding_replicas, Some(vec![]));
+    }
+
+    #[test]
+    fn refuses_invalid_reassignments() {
+        let image = image(&[None, None, None]);
+        let topic_id = Uuid::from_u128(1);
+        let registration = partition(&[1, 2], &[1, 2], 1);
+        let reassign = |target: Option<&[i32]>| {
+            reassign_partition(&image, topic_id, 0, &registration, target, false)
+        };
+
+        assert_eq!(
+            reassign(None).unwrap_err(),
+            error::NO_REASSIGNMENT_IN_PROGRESS
+        );
+        assert_eq!(
+            reassign(Some(&[1, 2, 3])).unwrap_err(),
+            error::INVALID_REPLICATION_FACTOR
+        );
+        for target in [&[][..], &[1, 1], &[1, 4]] {
+            assert_eq!(
+                reassign(Some(target)).unwrap_err(),
+                error::INVALID_REPLICA_ASSIGNMENT
+            );
+        }
+    }
+}
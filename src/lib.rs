@@ -1 +1,18 @@
+pub mod broker;
+pub mod broker_lifecycle;
+pub mod config;
+pub mod config_registry;
+pub mod controller;
+pub mod log_manager;
+pub mod metadata;
+pub mod network_client;
 pub mod protocol;
+pub mod quota;
+pub mod raft;
+pub mod remote_storage;
+pub mod replica_fetcher;
+pub mod replica_manager;
+pub mod security;
+pub mod server;
+pub mod share_group;
+pub mod share_partition;
@@ -0,0 +1,1605 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    ffi::CString,
+    fmt::{self, Display},
+    fs::{self, File, OpenOptions},
+    io::{self, ErrorKind, Write},
+    mem::MaybeUninit,
+    ops::Range,
+    os::unix::{ffi::OsStrExt, fs::FileExt},
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{bail, Context, Result};
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::{
+    config::{BrokerConfig, METADATA_PARTITION_DIR},
+    config_registry::LogConfig,
+    metadata::{random_uuid, segment_file_name, segment_files, MetaProperties, MetadataImage},
+    protocol::{
+        cluster_metadata::{MetadataRecord, PartitionChangeRecord, RecordBatch},
+        error,
+        fetch::EpochEndOffset,
+    },
+};
+
+/// Suffix of the copy of a replica being moved into another log directory.
+const FUTURE_DIR_SUFFIX: &str = "-future";
+/// Suffix of a replica directory scheduled for deletion.
+const DELETE_DIR_SUFFIX: &str = "-delete";
+/// File at the root of a log directory recording the log start offset of its replicas.
+const LOG_START_OFFSET_CHECKPOINT: &str = "log-start-offset-checkpoint";
+/// File at the root of a log directory recording the high watermark of its replicas.
+const REPLICATION_OFFSET_CHECKPOINT: &str = "replication-offset-checkpoint";
+/// File in a replica directory recording the offset each leader epoch starts at.
+const LEADER_EPOCH_CHECKPOINT: &str = "leader-epoch-checkpoint";
+/// Offset standing for the high watermark in DeleteRecords requests.
+pub const HIGH_WATERMARK: i64 = -1;
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct TopicPartition {
+    pub topic: String,
+    pub partition: i32,
+}
+
+impl TopicPartition {
+    pub fn new(topic: impl Into<String>, partition: i32) -> Self {
+        TopicPartition {
+            topic: topic.into(),
+            partition,
+        }
+    }
+
+    /// Parse the name of a replica directory, `<topic>-<partition>`.
+    fn from_dir_name(name: &str) -> Option<Self> {
+        let (topic, partition) = name.rsplit_once('-')?;
+        if topic.is_empty() {
+            return None;
+        }
+        Some(TopicPartition::new(topic, partition.parse().ok()?))
+    }
+}
+
+impl Display for TopicPartition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}-{}", self.topic, self.partition)
+    }
+}
+
+/// Offset at which the records of a leader epoch start in a replica.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct EpochEntry {
+    epoch: i32,
+    start_offset: i64,
+}
+
+/// A segment of a local replica, with the offsets and timestamps of its batches.
+#[derive(Debug, Clone)]
+pub struct SegmentInfo {
+    pub path: PathBuf,
+    pub base_offset: i64,
+    /// Offset of its last record, one below the base offset while it is empty
+    pub end_offset: i64,
+    /// Largest timestamp of its batches, or when it was last written to if none has one
+    pub max_timestamp: i64,
+    pub size: u64,
+}
+
+/// Where a batch sits in a segment, with the offsets and timestamp it covers.
+#[derive(Debug, Clone, Copy)]
+pub struct BatchHeader {
+    pub position: usize,
+    pub size: usize,
+    pub base_offset: i64,
+    pub last_offset: i64,
+    pub max_timestamp: i64,
+    pub leader_epoch: i32,
+}
+
+/// One of the `log.dirs` of the broker.
+#[derive(Debug)]
+pub struct LogDir {
+    pub path: PathBuf,
+    /// `directory.id` of its `meta.properties`, nil when it could not be read
+    pub id: Uuid,
+    /// Why the directory was taken offline, `None` while it is online
+    pub failure: Option<String>,
+}
+
+impl LogDir {
+    pub fn is_online(&self) -> bool {
+        self.failure.is_none()
+    }
+
+    /// Capacity and free space of the file system holding the directory, in bytes.
+    pub fn disk_space(&self) -> Option<(i64, i64)> {
+        let path = CString::new(self.path.as_os_str().as_bytes()).ok()?;
+        let mut stat = MaybeUninit::<libc::statvfs>::uninit();
+        // SAFETY: `path` is NUL-terminated and `stat` is only read once statvfs filled it
+        let stat = unsafe {
+            if libc::statvfs(path.as_ptr(), stat.as_mut_ptr()) != 0 {
+                return None;
+            }
+            stat.assume_init()
+        };
+        let block_size = stat.f_frsize as i64;
+        Some((
+            stat.f_blocks as i64 * block_size,
+            stat.f_bavail as i64 * block_size,
+        ))
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum LogDirError {
+    #[error("{0} is not a log directory of this broker")]
+    NotFound(PathBuf),
+    #[error("log directory {0} is offline")]
+    Offline(PathBuf),
+    #[error("replica {0} is not hosted by this broker")]
+    ReplicaNotAvailable(TopicPartition),
+    #[error("log directory {0} is not configured on this broker")]
+    UnknownDir(Uuid),
+    #[error("no log directory is online")]
+    NoOnlineDir,
+    #[error("offset {offset} of {replica} is outside of [{start}, {end}]")]
+    OffsetOutOfRange {
+        replica: TopicPartition,
+        offset: i64,
+        start: i64,
+        end: i64,
+    },
+}
+
+impl LogDirError {
+    pub fn error_code(&self) -> i16 {
+        match self {
+            LogDirError::NotFound(_) => error::LOG_DIR_NOT_FOUND,
+            LogDirError::Offline(_) | LogDirError::UnknownDir(_) | LogDirError::NoOnlineDir => {
+                error::KAFKA_STORAGE_ERROR
+            }
+            LogDirError::ReplicaNotAvailable(_) => error::REPLICA_NOT_AVAILABLE,
+            LogDirError::OffsetOutOfRange { .. } => error::OFFSET_OUT_OF_RANGE,
+        }
+    }
+}
+
+/// Local replica logs spread over the log directories of the broker. A directory failing
+/// an I/O operation is taken offline along with its replicas, the broker carrying on with
+/// the others.
+#[derive(Debug)]
+pub struct LogManager {
+    node_id: i32,
+    dirs: Vec<LogDir>,
+    /// Index in `dirs` of the directory holding each local replica
+    replicas: BTreeMap<TopicPartition, usize>,
+    /// Log start offsets advanced past the first segment, as checkpointed
+    log_start_offsets: HashMap<TopicPartition, i64>,
+    /// Offset below which the records of each replica are on every in-sync replica
+    high_watermarks: HashMap<TopicPartition, i64>,
+    /// Offset following the last batch of each replica, read from disk on first use
+    log_end_offsets: HashMap<TopicPartition, i64>,
+    /// Leader epochs of each replica in increasing order, read from disk on first use
+    leader_epochs: HashMap<TopicPartition, Vec<EpochEntry>>,
+}
+
+/// What a log directory holds when the broker starts.
+struct LoadedDir {
+    id: Uuid,
+    replicas: Vec<TopicPartition>,
+    log_start_offsets: HashMap<TopicPartition, i64>,
+    high_watermarks: HashMap<TopicPartition, i64>,
+}
+
+impl LogManager {
+    /// Format the log directories as needed and find the replicas they hold. Directories
+    /// that cannot be read start offline.
+    pub fn open(config: &BrokerConfig, cluster_id: &str) -> Result<Self> {
+        let mut manager = LogManager {
+            node_id: config.node_id,
+            dirs: vec![],
+            replicas: BTreeMap::new(),
+            log_start_offsets: HashMap::new(),
+            high_watermarks: HashMap::new(),
+            log_end_offsets: HashMap::new(),
+            leader_epochs: HashMap::new(),
+        };
+        for path in &config.log_dirs {
+            let index = manager.dirs.len();
+            match Self::load_dir(path, config.node_id, cluster_id) {
+                Ok(LoadedDir {
+                    id,
+                    replicas,
+                    mut log_start_offsets,
+                    mut high_watermarks,
+                }) => {
+                    for replica in replicas {
+                        if let Some(offset) = log_start_offsets.remove(&replica) {
+                            manager.log_start_offsets.insert(replica.clone(), offset);
+                        }
+                        if let Some(offset) = high_watermarks.remove(&replica) {
+                            manager.high_watermarks.insert(replica.clone(), offset);
+                        }
+                        if let Some(other) = manager.replicas.insert(replica.clone(), index) {
+                            bail!(
+                                "replica {replica} is in both {} and {}",
+                                manager.dirs[other].path.display(),
+                                path.display()
+                            );
+                        }
+                    }
+                    manager.dirs.push(LogDir {
+                        path: path.clone(),
+                        id,
+                        failure: None,
+                    });
+                }
+                Err(e) => {
+                    eprintln!("Log directory {} is offline: {e:#}", path.display());
+                    manager.dirs.push(LogDir {
+                        path: path.clone(),
+                        id: Uuid::nil(),
+                        failure: Some(format!("{e:#}")),
+                    });
+                }
+            }
+        }
+        if !manager.dirs.iter().any(LogDir::is_online) {
+            bail!("every log directory is offline");
+        }
+        Ok(manager)
+    }
+
+    fn load_dir(path: &Path, node_id: i32, cluster_id: &str) -> Result<LoadedDir> {
+        let meta = MetaProperties::load_or_format(path, node_id, Some(cluster_id))?;
+        let mut replicas = vec![];
+        let entries =
+            fs::read_dir(path).with_context(|| format!("failed to list {}", path.display()))?;
+        for entry in entries {
+            let entry = entry?;
+            if !entry.file_type()?.is_dir() {
+                continue;
+            }
+            let name = entry.file_name().to_string_lossy().into_owned();
+            // A move or a deletion cut short by a restart leaves a partial copy behind
+            if name.ends_with(FUTURE_DIR_SUFFIX) || name.ends_with(DELETE_DIR_SUFFIX) {
+                fs::remove_dir_all(entry.path())
+                    .with_context(|| format!("failed to remove {}", entry.path().display()))?;
+                continue;
+            }
+            if name == METADATA_PARTITION_DIR {
+                continue;
+            }
+            if let Some(replica) = TopicPartition::from_dir_name(&name) {
+                replicas.push(replica);
+            }
+        }
+        Ok(LoadedDir {
+            id: meta.directory_id,
+            replicas,
+            log_start_offsets: read_checkpoint(&path.join(LOG_START_OFFSET_CHECKPOINT))?,
+            high_watermarks: read_checkpoint(&path.join(REPLICATION_OFFSET_CHECKPOINT))?,
+        })
+    }
+
+    pub fn dirs(&self) -> &[LogDir] {
+        &self.dirs
+    }
+
+    /// Ids of the log directories that are online.
+    pub fn online_dir_ids(&self) -> Vec<Uuid> {
+        self.dirs
+            .iter()
+            .filter(|dir| dir.is_online())
+            .map(|dir| dir.id)
+            .collect()
+    }
+
+    /// Replicas held by the log directory at `index` of [`LogManager::dirs`].
+    pub fn replicas_in(&self, index: usize) -> impl Iterator<Item = &TopicPartition> {
+        self.replicas
+            .iter()
+            .filter(move |(_, &dir)| dir == index)
+            .map(|(replica, _)| replica)
+    }
+
+    /// Create a log for every partition the metadata assigns to this broker that has
+    /// none yet, delete the logs of the partitions it assigns elsewhere, and return the
+    /// records assigning each local replica to the directory now holding it.
+    pub fn sync_replicas(&mut self, image: &MetadataImage) -> Vec<MetadataRecord> {
+        let mut records = vec![];
+        for (topic, topic_id) in &image.topics {
+            let Some(partitions) = image.partitions.get(topic_id) else {
+                continue;
+            };
+            for (&partition, registration) in partitions {
+                if !registration.replicas.contains(&self.node_id) {
+                    continue;
+                }
+                let replica = TopicPartition::new(topic.as_str(), partition);
+                let assigned = registration.directory(self.node_id);
+                let index = match self.replicas.get(&replica) {
+                    Some(&index) => index,
+                    None => match self.create_replica(replica.clone(), assigned) {
+                        Ok(index) => index,
+                        // Already reported when the directory went offline
+                        Err(LogDirError::Offline(_) | LogDirError::UnknownDir(_)) => continue,
+                        Err(e) => {
+                            eprintln!("Cannot create a log for {replica}: {e}");
+                            continue;
+                        }
+                    },
+                };
+                let dir = &self.dirs[index];
+                if dir.is_online() && dir.id != assigned {
+                    let mut directories = registration.directories.clone();
+                    directories.resize(registration.replicas.len(), Uuid::nil());
+                    for (replica, directory) in registration.replicas.iter().zip(&mut directories) {
+                        if *replica == self.node_id {
+                            *directory = dir.id;
+                        }
+                    }
+                    let mut record = PartitionChangeRecord::new(*topic_id, partition);
+                    record.directories = Some(directories);
+                    records.push(MetadataRecord::PartitionChange(record));
+                }
+            }
+        }
+
+        // Replicas a reassignment moved to other brokers are no longer needed here
+        let unassigned: Vec<TopicPartition> = self
+            .replicas
+            .keys()
+            .filter(|replica| {
+                image
+                    .partition(&replica.topic, replica.partition)
+                    .is_some_and(|(_, registration)| !registration.replicas.contains(&self.node_id))
+            })
+            .cloned()
+            .collect();
+        for replica in unassigned {
+            self.delete_replica(&replica);
+        }
+        records
+    }
+
+    /// Delete a local replica. Its directory is renamed first, so that a deletion cut
+    /// short leaves nothing that passes for a replica.
+    fn delete_replica(&mut self, replica: &TopicPartition) {
+        let Ok(index) = self.online_dir_of(replica) else {
+            return;
+        };
+        let path = &self.dirs[index].path;
+        let dir = path.join(replica.to_string());
+        let deleted = path.join(format!(
+            "{replica}.{}{DELETE_DIR_SUFFIX}",
+            random_uuid().simple()
+        ));
+        if let Err(e) = fs::rename(&dir, &deleted) {
+            self.fail_dir(index, e);
+            return;
+        }
+        self.replicas.remove(replica);
+        self.log_start_offsets.remove(replica);
+        self.high_watermarks.remove(replica);
+        self.log_end_offsets.remove(replica);
+        self.leader_epochs.remove(replica);
+        if let Err(e) = self.checkpoint(index) {
+            self.fail_dir(index, e);
+        }
+        // The replica is gone already, failing to clean up only affects its directory
+        if let Err(e) = fs::remove_dir_all(&deleted) {
+            self.fail_dir(index, e);
+        }
+        eprintln!("Deleted replica {replica}, which is no longer assigned to this broker");
+    }
+
+    /// Create an empty replica in its assigned directory, or in the online directory
+    /// holding the fewest replicas if it has none. A replica assigned to a directory that
+    /// is offline or gone is left alone rather than recreated empty.
+    fn create_replica(
+        &mut self,
+        replica: TopicPartition,
+        assigned: Uuid,
+    ) -> Result<usize, LogDirError> {
+        if !assigned.is_nil() {
+            return match self.dirs.iter().position(|dir| dir.id == assigned) {
+                Some(index) if self.dirs[index].is_online() => {
+                    self.create_replica_in(replica, index)
+                }
+                Some(index) => Err(LogDirError::Offline(self.dirs[index].path.clone())),
+                None => Err(LogDirError::UnknownDir(assigned)),
+            };
+        }
+        loop {
+            let index = (0..self.dirs.len())
+                .filter(|&index| self.dirs[index].is_online())
+                .min_by_key(|&index| self.replicas_in(index).count())
+                .ok_or(LogDirError::NoOnlineDir)?;
+            if let Ok(index) = self.create_replica_in(replica.clone(), index) {
+                return Ok(index);
+            }
+        }
+    }
+
+    fn create_replica_in(
+        &mut self,
+        replica: TopicPartition,
+        index: usize,
+    ) -> Result<usize, LogDirError> {
+        let dir = self.dirs[index].path.join(replica.to_string());
+        let created = fs::create_dir_all(&dir)
+            .and_then(|()| fs::File::create(dir.join(segment_file_name(0))).map(drop));
+        match created {
+            Ok(()) => {
+                self.replicas.insert(replica, index);
+                Ok(index)
+            }
+            Err(e) => Err(self.fail_dir(index, e)),
+        }
+    }
+
+    /// Bytes used by the segments of a local replica, taking its directory offline if
+    /// they cannot be read.
+    pub fn replica_size(&mut self, replica: &TopicPartition) -> Result<i64, LogDirError> {
+        let index = self.online_dir_of(replica)?;
+        let dir = self.dirs[index].path.join(replica.to_string());
+        let size = fs::read_dir(&dir).and_then(|entries| {
+            entries
+                .map(|entry| entry?.metadata())
+                .filter(|metadata| metadata.as_ref().map_or(true, |m| m.is_file()))
+                .map(|metadata| metadata.map(|m| m.len()))
+                .sum::<std::io::Result<u64>>()
+        });
+        match size {
+            Ok(size) => Ok(size as i64),
+            Err(e) => Err(self.fail_dir(index, e)),
+        }
+    }
+
+    /// Move a local replica into another log directory. The replica is copied next to
+    /// its destination first, so an interrupted move leaves the original in place.
+    pub fn move_replica(
+        &mut self,
+        replica: &TopicPartition,
+        destination: &Path,
+    ) -> Result<(), LogDirError> {
+        let target = self
+            .dirs
+            .iter()
+            .position(|dir| dir.path == destination)
+            .ok_or_else(|| LogDirError::NotFound(destination.to_path_buf()))?;
+        if !self.dirs[target].is_online() {
+            return Err(LogDirError::Offline(destination.to_path_buf()));
+        }
+        let source = self.online_dir_of(replica)?;
+        if source == target {
+            return Ok(());
+        }
+
+        let source_dir = self.dirs[source].path.join(replica.to_string());
+        let future_dir = self.dirs[target].path.join(format!(
+            "{replica}.{}{FUTURE_DIR_SUFFIX}",
+            random_uuid().simple()
+        ));
+        if let Err((index, e)) = copy_dir(&source_dir, &future_dir, source, target) {
+            let _ = fs::remove_dir_all(&future_dir);
+            return Err(self.fail_dir(index, e));
+        }
+        let target_dir = self.dirs[target].path.join(replica.to_string());
+        if let Err(e) = fs::rename(&future_dir, &target_dir) {
+            let _ = fs::remove_dir_all(&future_dir);
+            return Err(self.fail_dir(target, e));
+        }
+        self.replicas.insert(replica.clone(), target);
+        for index in [target, source] {
+            if let Err(e) = self.checkpoint(index) {
+                self.fail_dir(index, e);
+            }
+        }
+
+        // The replica already lives in its new directory, failing to clean up the old
+        // copy only affects the old directory
+        if let Err(e) = fs::remove_dir_all(&source_dir) {
+            if e.kind() != ErrorKind::NotFound {
+                self.fail_dir(source, e);
+            }
+        }
+        Ok(())
+    }
+
+    /// Offset the next batch appended to a local replica will start at.
+    pub fn log_end_offset(&mut self, replica: &TopicPartition) -> Result<i64, LogDirError> {
+        let index = self.online_dir_of(replica)?;
+        if let Some(&offset) = self.log_end_offsets.get(replica) {
+            return Ok(offset);
+        }
+        let segments = self.segments(index, replica)?;
+        match log_end_offset(&segments) {
+            Ok(offset) => {
+                self.log_end_offsets.insert(replica.clone(), offset);
+                Ok(offset)
+            }
+            Err(e) => Err(self.fail_dir(index, format!("{e:#}"))),
+        }
+    }
+
+    /// First offset a local replica still holds records from.
+    pub fn log_start_offset(&mut self, replica: &TopicPartition) -> Result<i64, LogDirError> {
+        let index = self.online_dir_of(replica)?;
+        let segments = self.segments(index, replica)?;
+        let first_segment = segments.first().and_then(|s| base_offset(s));
+        Ok(self
+            .log_start_offsets
+            .get(replica)
+            .copied()
+            .unwrap_or_default()
+            .max(first_segment.unwrap_or_default()))
+    }
+
+    /// Offset below which the records of a local replica are on every in-sync replica.
+    pub fn high_watermark(&self, replica: &TopicPartition) -> i64 {
+        self.high_watermarks
+            .get(replica)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Move the high watermark of a local replica, saved at the next checkpoint.
+    pub fn set_high_watermark(&mut self, replica: &TopicPartition, offset: i64) {
+        self.high_watermarks.insert(replica.clone(), offset);
+    }
+
+    /// Save the high watermarks of the replicas in every online directory.
+    pub fn checkpoint_high_watermarks(&mut self) {
+        for index in 0..self.dirs.len() {
+            if self.dirs[index].is_online() {
+                if let Err(e) = self.checkpoint(index) {
+                    self.fail_dir(index, e);
+                }
+            }
+        }
+    }
+
+    /// Append batches to a local replica as its leader. They get consecutive offsets
+    /// from the log end offset and the leader epoch, and the base offset of the first
+    /// one is returned.
+    pub fn append(
+        &mut self,
+        replica: &TopicPartition,
+        mut records: Vec<u8>,
+        leader_epoch: i32,
+        config: &LogConfig,
+    ) -> Result<i64, LogDirError> {
+        let base_offset = self.log_end_offset(replica)?;
+        self.assign_leader_epoch(replica, leader_epoch)?;
+        let mut next_offset = base_offset;
+        for batch in batch_positions(&records) {
+            let header = &mut records[batch.start..];
+            header[..8].copy_from_slice(&next_offset.to_be_bytes());
+            // Neither field is covered by the CRC, which stays valid
+            header[PARTITION_LEADER_EPOCH_POSITION..PARTITION_LEADER_EPOCH_POSITION + 4]
+                .copy_from_slice(&leader_epoch.to_be_bytes());
+            next_offset += last_offset_delta(header) as i64 + 1;
+        }
+        self.write(replica, base_offset, &records, config)?;
+        self.log_end_offsets.insert(replica.clone(), next_offset);
+        Ok(base_offset)
+    }
+
+    /// Append batches fetched from the leader of the replica, keeping their offsets.
+    /// Batches the replica already has are skipped, and the local batches overlapping
+    /// one straddling the log end offset are truncated first. Returns the new log end
+    /// offset.
+    pub fn append_as_follower(
+        &mut self,
+        replica: &TopicPartition,
+        records: &[u8],
+        config: &LogConfig,
+    ) -> Result<i64, LogDirError> {
+        let mut log_end_offset = self.log_end_offset(replica)?;
+        let mut epochs = self.epoch_entries(replica)?;
+        let mut first_offset = None;
+        let mut appended = vec![];
+        for batch in batch_positions(records) {
+            let header = &records[batch.start..];
+            let base_offset = i64::from_be_bytes(header[..8].try_into().unwrap());
+            let last_offset = base_offset + last_offset_delta(header) as i64;
+            if last_offset < log_end_offset {
+                continue;
+            }
+            if first_offset.is_none() && base_offset < log_end_offset {
+                // An empty log starting within the batch, after records were deleted on
+                // the leader, starts over at the batch instead
+                log_end_offset = if log_end_offset == self.log_start_offset(replica)? {
+                    self.truncate_fully_and_start_at(replica, base_offset, &[])?;
+                    base_offset
+                } else {
+                    self.truncate_to(replica, base_offset)?
+                };
+                epochs = self.epoch_entries(replica)?;
+                // The local log ended within an earlier batch, the next fetch goes on
+                // from where it ends now
+                if log_end_offset != base_offset {
+                    return Ok(log_end_offset);
+                }
+            }
+            add_epoch(&mut epochs, partition_leader_epoch(header), base_offset);
+            first_offset.get_or_insert(base_offset);
+            appended.extend_from_slice(&records[batch]);
+            log_end_offset = last_offset + 1;
+        }
+        if let Some(first_offset) = first_offset {
+            self.set_epoch_entries(replica, epochs)?;
+            self.write(replica, first_offset, &appended, config)?;
+            self.log_end_offsets.insert(replica.clone(), log_end_offset);
+        }
+        Ok(log_end_offset)
+    }
+
+    /// Remove the batches of a local replica from the one holding `offset` on, as a
+    /// follower whose log diverged from its leader's does. Returns the new log end
+    /// offset.
+    pub fn truncate_to(
+        &mut self,
+        replica: &TopicPartition,
+        offset: i64,
+    ) -> Result<i64, LogDirError> {
+        let index = self.online_dir_of(replica)?;
+        let segments = self.segments(index, replica)?;
+        for (position, segment) in segments.iter().enumerate().rev() {
+            // The first segment is emptied rather than deleted, so that the log has one
+            if position > 0 && base_offset(segment).is_some_and(|base| base >= offset) {
+                if let Err(e) = remove_segment(segment) {
+                    return Err(self.fail_dir(index, e));
+                }
+                continue;
+            }
+            let truncated = OpenOptions::new()
+                .write(true)
+                .read(true)
+                .open(segment)
+                .and_then(|file| {
+                    let first_removed = segment_batch_headers(&file)?
+                        .into_iter()
+                        .find(|batch| batch.last_offset >= offset);
+                    match first_removed {
+                        Some(batch) => {
+                            file.set_len(batch.position as u64)?;
+                            file.sync_data()
+                        }
+                        None => Ok(()),
+                    }
+                });
+            if let Err(e) = truncated {
+                return Err(self.fail_dir(index, e));
+            }
+            break;
+        }
+        self.log_end_offsets.remove(replica);
+        let log_end_offset = self.log_end_offset(replica)?;
+        if self.high_watermark(replica) > log_end_offset {
+            self.set_high_watermark(replica, log_end_offset);
+        }
+        let mut epochs = self.epoch_entries(replica)?;
+        epochs.retain(|entry| entry.start_offset < log_end_offset);
+        self.set_epoch_entries(replica, epochs)?;
+        Ok(log_end_offset)
+    }
+
+    /// Start `epoch` at the log end offset of a local replica this broker leads, unless
+    /// it started already. Epochs the leader wrote nothing in still tell followers
+    /// where the previous one ended.
+    pub fn assign_leader_epoch(
+        &mut self,
+        replica: &TopicPartition,
+        epoch: i32,
+    ) -> Result<(), LogDirError> {
+        let log_end_offset = self.log_end_offset(replica)?;
+        let mut epochs = self.epoch_entries(replica)?;
+        if add_epoch(&mut epochs, epoch, log_end_offset) {
+            self.set_epoch_entries(replica, epochs)?;
+        }
+        Ok(())
+    }
+
+    /// Latest leader epoch of a local replica, -1 before its first batch.
+    pub fn latest_epoch(&mut self, replica: &TopicPartition) -> Result<i32, LogDirError> {
+        Ok(self
+            .epoch_entries(replica)?
+            .last()
+            .map_or(-1, |entry| entry.epoch))
+    }
+
+    /// Largest leader epoch of a local replica not above `epoch`, with the offset the
+    /// next epoch starts at, or the log end offset for the latest one. An epoch older
+    /// than the whole log ends where the log starts; one newer than every epoch, or -1,
+    /// is unknown and answered with -1 for both.
+    pub fn end_offset_for_epoch(
+        &mut self,
+        replica: &TopicPartition,
+        epoch: i32,
+    ) -> Result<EpochEndOffset, LogDirError> {
+        const UNDEFINED: EpochEndOffset = EpochEndOffset {
+            epoch: -1,
+            end_offset: -1,
+        };
+        let epochs = self.epoch_entries(replica)?;
+        if epoch < 0 {
+            return Ok(UNDEFINED);
+        }
+        if epochs.last().is_some_and(|latest| latest.epoch == epoch) {
+            return Ok(EpochEndOffset {
+                epoch,
+                end_offset: self.log_end_offset(replica)?,
+            });
+        }
+        let Some(next) = epochs.iter().position(|entry| entry.epoch > epoch) else {
+            return Ok(UNDEFINED);
+        };
+        Ok(EpochEndOffset {
+            epoch: next
+                .checked_sub(1)
+                .map_or(epoch, |floor| epochs[floor].epoch),
+            end_offset: epochs[next].start_offset,
+        })
+    }
+
+    /// Batches of a local replica from the one holding `offset` on, until the first
+    /// one reaching `max_offset`. They add up to at most `max_bytes`, unless the first
+    /// batch alone is larger, so that a large batch never blocks the reader.
+    pub fn read(
+        &mut self,
+        replica: &TopicPartition,
+        offset: i64,
+        max_offset: i64,
+        max_bytes: usize,
+    ) -> Result<Vec<u8>, LogDirError> {
+        let start = self.log_start_offset(replica)?;
+        let end = self.log_end_offset(replica)?;
+        if !(start..=end).contains(&offset) {
+            return Err(LogDirError::OffsetOutOfRange {
+                replica: replica.clone(),
+                offset,
+                start,
+                end,
+            });
+        }
+        let index = self.online_dir_of(replica)?;
+        let segments = self.segments(index, replica)?;
+        let first = segments
+            .iter()
+            .rposition(|s| base_offset(s).is_some_and(|base| base <= offset))
+            .unwrap_or_default();
+        let mut records = vec![];
+        for segment in &segments[first..] {
+            match read_segment(segment, offset, max_offset, max_bytes, &mut records) {
+                Ok(true) => break,
+                Ok(false) => {}
+                Err(e) => return Err(self.fail_dir(index, e)),
+            }
+        }
+        Ok(records)
+    }
+
+    /// Segments of a local replica, oldest first, the last one being the active segment.
+    pub fn segment_infos(
+        &mut self,
+        replica: &TopicPartition,
+    ) -> Result<Vec<SegmentInfo>, LogDirError> {
+        let index = self.online_dir_of(replica)?;
+        let segments = self.segments(index, replica)?;
+        let mut infos = vec![];
+        for path in segments {
+            let Some(base_offset) = base_offset(&path) else {
+                continue;
+            };
+            let info = File::open(&path).and_then(|file| {
+                let metadata = file.metadata()?;
+                let batches = segment_batch_headers(&file)?;
+                let max_timestamp = match batches.iter().map(|b| b.max_timestamp).max() {
+                    Some(timestamp) if timestamp >= 0 => timestamp,
+                    _ => metadata
+                        .modified()?
+                        .duration_since(UNIX_EPOCH)
+                        .map_or(0, |since| since.as_millis() as i64),
+                };
+                Ok(SegmentInfo {
+                    end_offset: batches.last().map_or(base_offset - 1, |b| b.last_offset),
+                    max_timestamp,
+                    size: metadata.len(),
+                    base_offset,
+                    path,
+                })
+            });
+            match info {
+                Ok(info) => infos.push(info),
+                Err(e) => return Err(self.fail_dir(index, e)),
+            }
+        }
+        Ok(infos)
+    }
+
+    /// Leader epochs of a local replica with the offset each starts at, in increasing
+    /// order.
+    pub fn leader_epochs(
+        &mut self,
+        replica: &TopicPartition,
+    ) -> Result<Vec<(i32, i64)>, LogDirError> {
+        Ok(self
+            .epoch_entries(replica)?
+            .into_iter()
+            .map(|entry| (entry.epoch, entry.start_offset))
+            .collect())
+    }
+
+    /// Empty a local replica and have its log start at `offset`, with the given leader
+    /// epochs, as a follower does when the records below it are only in remote storage.
+    pub fn truncate_fully_and_start_at(
+        &mut self,
+        replica: &TopicPartition,
+        offset: i64,
+        epochs: &[(i32, i64)],
+    ) -> Result<(), LogDirError> {
+        let index = self.online_dir_of(replica)?;
+        for segment in self.segments(index, replica)? {
+            if let Err(e) = remove_segment(&segment) {
+                return Err(self.fail_dir(index, e));
+            }
+        }
+        let dir = self.dirs[index].path.join(replica.to_string());
+        if let Err(e) = fs::File::create(dir.join(segment_file_name(offset))) {
+            return Err(self.fail_dir(index, e));
+        }
+        self.log_start_offsets.insert(replica.clone(), offset);
+        self.high_watermarks.insert(replica.clone(), offset);
+        self.log_end_offsets.insert(replica.clone(), offset);
+        if let Err(e) = self.checkpoint(index) {
+            return Err(self.fail_dir(index, e));
+        }
+        let epochs = epochs
+            .iter()
+            .filter(|&&(_, start_offset)| start_offset <= offset)
+            .map(|&(epoch, start_offset)| EpochEntry {
+                epoch,
+                start_offset,
+            })
+            .collect();
+        self.set_epoch_entries(replica, epochs)
+    }
+
+    /// Advance the log start offset of a local replica to `offset`, or to its high
+    /// watermark for [`HIGH_WATERMARK`], and delete the segments wholly below it. Returns
+    /// the resulting log start offset, which never moves backwards.
+    pub fn delete_records(
+        &mut self,
+        replica: &TopicPartition,
+        offset: i64,
+    ) -> Result<i64, LogDirError> {
+        let index = self.online_dir_of(replica)?;
+        let segments = self.segments(index, replica)?;
+        let high_watermark = self.high_watermark(replica);
+        let offset = if offset == HIGH_WATERMARK {
+            high_watermark
+        } else {
+            offset
+        };
+        if !(0..=high_watermark).contains(&offset) {
+            return Err(LogDirError::OffsetOutOfRange {
+                replica: replica.clone(),
+                offset,
+                start: 0,
+                end: high_watermark,
+            });
+        }
+        let log_start_offset = self.log_start_offset(replica)?;
+        if offset <= log_start_offset {
+            return Ok(log_start_offset);
+        }
+
+        // The new start is durable before any segment goes
+        self.log_start_offsets.insert(replica.clone(), offset);
+        if let Err(e) = self.checkpoint(index) {
+            return Err(self.fail_dir(index, e));
+        }
+        // The epoch holding the new start now starts there
+        let mut epochs = self.epoch_entries(replica)?;
+        let first_kept = epochs
+            .iter()
+            .rposition(|entry| entry.start_offset <= offset)
+            .unwrap_or_default();
+        epochs.drain(..first_kept);
+        if let Some(first) = epochs.first_mut() {
+            first.start_offset = first.start_offset.max(offset);
+        }
+        self.set_epoch_entries(replica, epochs)?;
+        for pair in segments.windows(2) {
+            if base_offset(&pair[1]).is_some_and(|next| next <= offset) {
+                if let Err(e) = remove_segment(&pair[0]) {
+                    return Err(self.fail_dir(index, e));
+                }
+            }
+        }
+        Ok(offset)
+    }
+
+    /// Rewrite the checkpoints of a log directory.
+    fn checkpoint(&self, index: usize) -> io::Result<()> {
+        let path = &self.dirs[index].path;
+        for (file, offsets) in [
+            (LOG_START_OFFSET_CHECKPOINT, &self.log_start_offsets),
+            (REPLICATION_OFFSET_CHECKPOINT, &self.high_watermarks),
+        ] {
+            let entries: Vec<(&TopicPartition, i64)> = self
+                .replicas_in(index)
+                .filter_map(|replica| Some((replica, *offsets.get(replica)?)))
+                .collect();
+            write_checkpoint(&path.join(file), &entries)?;
+        }
+        Ok(())
+    }
+
+    /// Leader epochs of a local replica, from its checkpoint or, lacking one, from the
+    /// headers of its batches.
+    fn epoch_entries(&mut self, replica: &TopicPartition) -> Result<Vec<EpochEntry>, LogDirError> {
+        if let Some(epochs) = self.leader_epochs.get(replica) {
+            return Ok(epochs.clone());
+        }
+        let index = self.online_dir_of(replica)?;
+        let dir = self.dirs[index].path.join(replica.to_string());
+        let checkpoint = read_checkpoint_lines(&dir.join(LEADER_EPOCH_CHECKPOINT), |line| {
+            let (epoch, start_offset) = line.split_once(' ')?;
+            Some(EpochEntry {
+                epoch: epoch.parse().ok()?,
+                start_offset: start_offset.parse().ok()?,
+            })
+        });
+        let epochs = match checkpoint {
+            Ok(Some(epochs)) => epochs,
+            Ok(None) => {
+                let mut epochs = vec![];
+                for segment in self.segments(index, replica)? {
+                    let headers =
+                        File::open(&segment).and_then(|file| segment_batch_headers(&file));
+                    let headers = match headers {
+                        Ok(headers) => headers,
+                        Err(e) => return Err(self.fail_dir(index, e)),
+                    };
+                    for batch in headers {
+                        add_epoch(&mut epochs, batch.leader_epoch, batch.base_offset);
+                    }
+                }
+                epochs
+            }
+            Err(e) => return Err(self.fail_dir(index, format!("{e:#}"))),
+        };
+        self.leader_epochs.insert(replica.clone(), epochs.clone());
+        Ok(epochs)
+    }
+
+    /// Replace the leader epochs of a local replica, durably.
+    fn set_epoch_entries(
+        &mut self,
+        replica: &TopicPartition,
+        epochs: Vec<EpochEntry>,
+    ) -> Result<(), LogDirError> {
+        let index = self.online_dir_of(replica)?;
+        let path = self.dirs[index]
+            .path
+            .join(replica.to_string())
+            .join(LEADER_EPOCH_CHECKPOINT);
+        let lines: Vec<String> = epochs
+            .iter()
+            .map(|entry| format!("{} {}", entry.epoch, entry.start_offset))
+            .collect();
+        if let Err(e) = write_checkpoint_lines(&path, &lines) {
+            return Err(self.fail_dir(index, e));
+        }
+        self.leader_epochs.insert(replica.clone(), epochs);
+        Ok(())
+    }
+
+    /// Segments of a local replica, taking its directory offline if they cannot be
+    /// listed.
+    fn segments(
+        &mut self,
+        index: usize,
+        replica: &TopicPartition,
+    ) -> Result<Vec<PathBuf>, LogDirError> {
+        let dir = self.dirs[index].path.join(replica.to_string());
+        segment_files(&dir).map_err(|e| self.fail_dir(index, format!("{e:#}")))
+    }
+
+    /// Append raw batches starting at `base_offset` to the active segment of a local
+    /// replica. A new segment is rolled first once the active one would outgrow
+    /// `segment.bytes`, or has been around for `segment.ms`.
+    fn write(
+        &mut self,
+        replica: &TopicPartition,
+        base_offset: i64,
+        records: &[u8],
+        config: &LogConfig,
+    ) -> Result<(), LogDirError> {
+        let index = self.online_dir_of(replica)?;
+        let segments = self.segments(index, replica)?;
+        let dir = self.dirs[index].path.join(replica.to_string());
+        let active = match segments.last() {
+            Some(segment) => match should_roll(segment, records.len(), config) {
+                Ok(true) => dir.join(segment_file_name(base_offset)),
+                Ok(false) => segment.clone(),
+                Err(e) => return Err(self.fail_dir(index, e)),
+            },
+            None => dir.join(segment_file_name(0)),
+        };
+        fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&active)
+            .and_then(|mut file| file.write_all(records))
+            .map_err(|e| self.fail_dir(index, e))
+    }
+
+    fn online_dir_of(&self, replica: &TopicPartition) -> Result<usize, LogDirError> {
+        let &index = self
+            .replicas
+            .get(replica)
+            .ok_or_else(|| LogDirError::ReplicaNotAvailable(replica.clone()))?;
+        if !self.dirs[index].is_online() {
+            return Err(LogDirError::Offline(self.dirs[index].path.clone()));
+        }
+        Ok(index)
+    }
+
+    /// Take a log directory offline after an I/O failure, returning the error to
+    /// report. Its replicas stay assigned to it rather than being recreated empty
+    /// elsewhere.
+    fn fail_dir(&mut self, index: usize, e: impl Display) -> LogDirError {
+        let dir = &mut self.dirs[index];
+        if dir.is_online() {
+            eprintln!("Taking log directory {} offline: {e}", dir.path.display());
+            dir.failure = Some(e.to_string());
+        }
+        LogDirError::Offline(dir.path.clone())
+    }
+}
+
+/// Copy the files of a replica directory, reporting which of the two log directories
+/// failed.
+fn copy_dir(
+    from: &Path,
+    to: &Path,
+    from_index: usize,
+    to_index: usize,
+) -> Result<(), (usize, std::io::Error)> {
+    fs::create_dir_all(to).map_err(|e| (to_index, e))?;
+    for entry in fs::read_dir(from).map_err(|e| (from_index, e))? {
+        let entry = entry.map_err(|e| (from_index, e))?;
+        let content = fs::read(entry.path()).map_err(|e| (from_index, e))?;
+        let file = to.join(entry.file_name());
+        fs::write(&file, content)
+            .and_then(|()| fs::File::open(&file)?.sync_all())
+            .map_err(|e| (to_index, e))?;
+    }
+    Ok(())
+}
+
+/// Read a checkpoint file of `<topic> <partition> <offset>` lines.
+fn read_checkpoint(path: &Path) -> Result<HashMap<TopicPartition, i64>> {
+    let entries =
+        read_checkpoint_lines(path, |line| match line.split(' ').collect::<Vec<_>>()[..] {
+            [topic, partition, offset] => partition
+                .parse()
+                .ok()
+                .zip(offset.parse().ok())
+                .map(|(partition, offset)| (TopicPartition::new(topic, partition), offset)),
+            _ => None,
+        })?;
+    Ok(entries.unwrap_or_default().into_iter().collect())
+}
+
+/// Read the entries of a checkpoint file, preceded by the format version and their
+/// number, or `None` if there is no such file.
+pub fn read_checkpoint_lines<T>(
+    path: &Path,
+    parse: impl Fn(&str) -> Option<T>,
+) -> Result<Option<Vec<T>>> {
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e).with_context(|| format!("failed to read {}", path.display())),
+    };
+    let mut lines = content.lines();
+    if lines.next() != Some("0") {
+        bail!("{} has an unsupported version", path.display());
+    }
+    let count: usize = lines
+        .next()
+        .and_then(|count| count.parse().ok())
+        .with_context(|| format!("{} has no entry count", path.display()))?;
+    let mut entries = vec![];
+    for line in lines {
+        let Some(entry) = parse(line) else {
+            bail!("{} has a malformed entry {line:?}", path.display());
+        };
+        entries.push(entry);
+    }
+    if entries.len() != count {
+        bail!(
+            "{} lists {} entries instead of {count}",
+            path.display(),
+            entries.len()
+        );
+    }
+    Ok(Some(entries))
+}
+
+/// Base offset of a segment, from its file name.
+fn base_offset(segment: &Path) -> Option<i64> {
+    segment.file_stem()?.to_str()?.parse().ok()
+}
+
+/// Offset following the last batch of the log, read from the batch headers of its
+/// active segment.
+fn log_end_offset(segments: &[PathBuf]) -> Result<i64> {
+    let Some(active) = segments.last() else {
+        return Ok(0);
+    };
+    let headers = File::open(active)
+        .and_then(|file| segment_batch_headers(&file))
+        .with_context(|| format!("failed to read {}", active.display()))?;
+    let end_offset = headers.last().map(|batch| batch.last_offset + 1);
+    Ok(end_offset.unwrap_or_else(|| base_offset(active).unwrap_or_default()))
+}
+
+/// Whether the active segment should be rolled before `size` more bytes are appended to
+/// it. An empty segment never is.
+fn should_roll(active: &Path, size: usize, config: &LogConfig) -> io::Result<bool> {
+    let metadata = fs::metadata(active)?;
+    if metadata.len() == 0 {
+        return Ok(false);
+    }
+    if metadata.len() + size as u64 > config.segment_bytes.max(0) as u64 {
+        return Ok(true);
+    }
+    // Not every file system records when a file was created
+    let age = metadata
+        .created()
+        .ok()
+        .and_then(|created| SystemTime::now().duration_since(created).ok());
+    Ok(age.is_some_and(|age| age.as_millis() >= config.segment_ms.max(0) as u128))
+}
+
+/// Gather the batches of `content` from the one holding `offset` on into `records`, as
+/// [`LogManager::read`] does. Returns whether the read is complete, having reached
+/// `max_offset` or `max_bytes`.
+pub fn read_batches(
+    content: &[u8],
+    offset: i64,
+    max_offset: i64,
+    max_bytes: usize,
+    records: &mut Vec<u8>,
+) -> bool {
+    for batch in batch_positions(content) {
+        let header = &content[batch.start..];
+        let base_offset = i64::from_be_bytes(header[..8].try_into().unwrap());
+        let last_offset = base_offset + last_offset_delta(header) as i64;
+        if last_offset < offset {
+            continue;
+        }
+        if last_offset >= max_offset
+            || (!records.is_empty() && records.len() + batch.len() > max_bytes)
+        {
+            return true;
+        }
+        records.extend_from_slice(&content[batch]);
+    }
+    false
+}
+
+/// Append the batches of a segment to `records` as [`read_batches`] does, reading
+/// only the batch headers and the batches returned.
+fn read_segment(
+    segment: &Path,
+    offset: i64,
+    max_offset: i64,
+    max_bytes: usize,
+    records: &mut Vec<u8>,
+) -> io::Result<bool> {
+    let file = File::open(segment)?;
+    let mut selected: Option<Range<usize>> = None;
+    let mut done = false;
+    for batch in segment_batch_headers(&file)? {
+        if batch.last_offset < offset {
+            continue;
+        }
+        let size = records.len() + selected.as_ref().map_or(0, |range| range.len());
+        if batch.last_offset >= max_offset || (size > 0 && size + batch.size > max_bytes) {
+            done = true;
+            break;
+        }
+        let start = selected.map_or(batch.position, |range| range.start);
+        selected = Some(start..batch.position + batch.size);
+    }
+    if let Some(range) = selected {
+        let start = records.len();
+        records.resize(start + range.len(), 0);
+        file.read_exact_at(&mut records[start..], range.start as u64)?;
+    }
+    Ok(done)
+}
+
+/// Headers of the complete batches of a log.
+pub fn batch_headers(bytes: &[u8]) -> Vec<BatchHeader> {
+    batch_positions(bytes)
+        .into_iter()
+        .map(|batch| batch_header(&bytes[batch.start..], batch))
+        .collect()
+}
+
+/// Headers of the complete batches of a segment file, read without their records.
+fn segment_batch_headers(file: &File) -> io::Result<Vec<BatchHeader>> {
+    let len = file.metadata()?.len() as usize;
+    let mut buf = [0; MAX_TIMESTAMP_POSITION + 8];
+    let mut headers = vec![];
+    let mut position = 0;
+    while position + LAST_OFFSET_DELTA_POSITION + 4 <= len {
+        let header = &mut buf[..(len - position).min(MAX_TIMESTAMP_POSITION + 8)];
+        file.read_exact_at(header, position as u64)?;
+        let length = i32::from_be_bytes(header[8..12].try_into().unwrap());
+        let end = position + RecordBatch::LOG_OVERHEAD + length.max(0) as usize;
+        if length < 0 || end > len {
+            break;
+        }
+        headers.push(batch_header(header, position..end));
+        position = end;
+    }
+    Ok(headers)
+}
+
+/// Header of the batch at `batch`, starting `header`.
+fn batch_header(header: &[u8], batch: Range<usize>) -> BatchHeader {
+    let base_offset = i64::from_be_bytes(header[..8].try_into().unwrap());
+    let max_timestamp = header
+        .get(MAX_TIMESTAMP_POSITION..MAX_TIMESTAMP_POSITION + 8)
+        .map_or(-1, |bytes| i64::from_be_bytes(bytes.try_into().unwrap()));
+    BatchHeader {
+        position: batch.start,
+        size: batch.len(),
+        base_offset,
+        last_offset: base_offset + last_offset_delta(header) as i64,
+        max_timestamp,
+        leader_epoch: partition_leader_epoch(header),
+    }
+}
+
+/// Position of the partition leader epoch in a batch.
+const PARTITION_LEADER_EPOCH_POSITION: usize = RecordBatch::LOG_OVERHEAD;
+/// Position of the last offset delta in a batch, after the base offset, batch length,
+/// leader epoch, magic, CRC and attributes.
+const LAST_OFFSET_DELTA_POSITION: usize = RecordBatch::LOG_OVERHEAD + 11;
+/// Position of the max timestamp in a batch, after the last offset delta and the base
+/// timestamp.
+const MAX_TIMESTAMP_POSITION: usize = LAST_OFFSET_DELTA_POSITION + 12;
+
+/// Byte ranges of the complete batches of a log, a torn batch at the end not being part
+/// of it.
+fn batch_positions(bytes: &[u8]) -> Vec<Range<usize>> {
+    let mut batches = vec![];
+    let mut position = 0;
+    while let Some(header) = bytes.get(position..position + LAST_OFFSET_DELTA_POSITION + 4) {
+        let length = i32::from_be_bytes(header[8..12].try_into().unwrap());
+        let end = position + RecordBatch::LOG_OVERHEAD + length.max(0) as usize;
+        if length < 0 || end > bytes.len() {
+            break;
+        }
+        batches.push(position..end);
+        position = end;
+    }
+    batches
+}
+
+/// Partition leader epoch of the batch starting `header`.
+fn partition_leader_epoch(header: &[u8]) -> i32 {
+    i32::from_be_bytes(
+        header[PARTITION_LEADER_EPOCH_POSITION..PARTITION_LEADER_EPOCH_POSITION + 4]
+            .try_into()
+            .unwrap(),
+    )
+}
+
+/// Partition leader epochs the batches are stamped with.
+pub fn partition_leader_epochs(records: &[u8]) -> Vec<i32> {
+    batch_positions(records)
+        .into_iter()
+        .map(|batch| partition_leader_epoch(&records[batch.start..]))
+        .collect()
+}
+
+/// Record that `epoch` starts at `start_offset`, unless a later epoch is known. An
+/// epoch nothing was written in is replaced by the one starting at the same offset.
+/// Returns whether the epochs changed.
+fn add_epoch(epochs: &mut Vec<EpochEntry>, epoch: i32, start_offset: i64) -> bool {
+    if epoch < 0 || epochs.last().is_some_and(|latest| latest.epoch >= epoch) {
+        return false;
+    }
+    epochs.retain(|entry| entry.start_offset < start_offset);
+    epochs.push(EpochEntry {
+        epoch,
+        start_offset,
+    });
+    true
+}
+
+/// Last offset delta of the batch starting `header`.
+fn last_offset_delta(header: &[u8]) -> i32 {
+    i32::from_be_bytes(
+        header[LAST_OFFSET_DELTA_POSITION..LAST_OFFSET_DELTA_POSITION + 4]
+            .try_into()
+            .unwrap(),
+    )
+}
+
+/// Write a checkpoint file of `<topic> <partition> <offset>` lines.
+fn write_checkpoint(path: &Path, entries: &[(&TopicPartition, i64)]) -> io::Result<()> {
+    let lines: Vec<String> = entries
+        .iter()
+        .map(|(replica, offset)| format!("{} {} {offset}", replica.topic, replica.partition))
+        .collect();
+    write_checkpoint_lines(path, &lines)
+}
+
+/// Write the entries of a checkpoint file after the format version and their number,
+/// replacing the previous one atomically.
+pub fn write_checkpoint_lines(path: &Path, lines: &[String]) -> io::Result<()> {
+    let temp = path.with_extension("tmp");
+    let mut content = format!("0\n{}\n", lines.len());
+    for line in lines {
+        content.push_str(line);
+        content.push('\n');
+    }
+    let mut file = fs::File::create(&temp)?;
+    file.write_all(content.as_bytes())?;
+    file.sync_all()?;
+    fs::rename(&temp, path)
+}
+
+/// Delete a segment along with its index files.
+fn remove_segment(segment: &Path) -> io::Result<()> {
+    for extension in ["log", "index", "timeindex"] {
+        match fs::remove_file(segment.with_extension(extension)) {
+            Err(e) if e.kind() != ErrorKind::NotFound => return Err(e),
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        config::Properties, config_registry::ConfigResolver, metadata::PartitionRegistration,
+        protocol::primitive::Serializable,
+    };
+
+    /// Config of broker 1 with `dirs` log directories under `dir`, of which the log
+    /// segments hold one batch each.
+    fn config(dir: &Path, dirs: usize) -> BrokerConfig {
+        let log_dirs: Vec<String> = (0..dirs)
+            .map(|index| dir.join(format!("logs-{index}")).display().to_string())
+            .collect();
+        let mut properties = Properties::default();
+        properties.set("node.id", "1");
+        properties.set("log.dirs", &log_dirs.join(","));
+        properties.set("log.segment.bytes", "14");
+        BrokerConfig::from_properties(&properties).unwrap()
+    }
+
+    /// An image holding topic `topic` with `partitions` partitions on broker 1.
+    fn image(partitions: i32) -> MetadataImage {
+        let mut image = MetadataImage::default();
+        let topic_id = Uuid::from_u128(1);
+        image.topics.insert("topic".to_string(), topic_id);
+        let registrations = (0..partitions)
+            .map(|partition| {
+                let registration = PartitionRegistration {
+                    replicas: vec![1],
+                    isr: vec![1],
+                    leader: 1,
+                    leader_epoch: 0,
+                    partition_epoch: 0,
+                    directories: vec![],
+                    adding_replicas: vec![],
+                    removing_replicas: vec![],
+                };
+                (partition, registration)
+            })
+            .collect();
+        image.partitions.insert(topic_id, registrations);
+        image
+    }
+
+    /// A log manager holding the partitions of [`image`], in a fresh directory.
+    fn open(name: &str, dirs: usize, partitions: i32) -> (PathBuf, BrokerConfig, LogManager) {
+        let dir = std::env::temp_dir().join(format!("{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let config = config(&dir, dirs);
+        let mut logs = LogManager::open(&config, "cluster").unwrap();
+        logs.sync_replicas(&image(partitions));
+        (dir, config, logs)
+    }
+
+    fn append(logs: &mut LogManager, config: &BrokerConfig, epoch: i32, count: usize) {
+        let replica = TopicPartition::new("topic", 0);
+        let image = image(1);
+        let log_config = ConfigResolver::new(&image, config).log_config("topic");
+        for _ in 0..count {
+            let batch = RecordBatch::new(0, 0, 0, vec![b"value".to_vec()]);
+            logs.append(&replica, batch.serialize(), epoch, &log_config)
+                .unwrap();
+        }
+    }
+
+    fn end_offset_for_epoch(logs: &mut LogManager, epoch: i32) -> (i32, i64) {
+        let replica = TopicPartition::new("topic", 0);
+        let end = logs.end_offset_for_epoch(&replica, epoch).unwrap();
+        (end.epoch, end.end_offset)
+    }
+
+    fn epoch_checkpoint(dir: &Path) -> String {
+        fs::read_to_string(dir.join("logs-0/topic-0").join(LEADER_EPOCH_CHECKPOINT)).unwrap()
+    }
+
+    #[test]
+    fn starts_a_leader_epoch_on_the_first_append_in_it() {
+        let (dir, config, mut logs) = open("epochs-append", 1, 1);
+        let replica = TopicPartition::new("topic", 0);
+        assert_eq!(logs.latest_epoch(&replica).unwrap(), -1);
+        append(&mut logs, &config, 0, 2);
+        append(&mut logs, &config, 3, 1);
+        assert_eq!(logs.leader_epochs(&replica).unwrap(), vec![(0, 0), (3, 2)]);
+        assert_eq!(logs.latest_epoch(&replica).unwrap(), 3);
+        assert_eq!(epoch_checkpoint(&dir), "0\n2\n0 0\n3 2\n");
+
+        // An older epoch never goes after a newer one
+        logs.assign_leader_epoch(&replica, 2).unwrap();
+        assert_eq!(logs.leader_epochs(&replica).unwrap(), vec![(0, 0), (3, 2)]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn ends_each_epoch_where_the_next_starts() {
+        let (dir, config, mut logs) = open("epochs-end-offset", 1, 1);
+        append(&mut logs, &config, 1, 2);
+        append(&mut logs, &config, 3, 2);
+        // The latest epoch ends at the log end offset
+        assert_eq!(end_offset_for_epoch(&mut logs, 3), (3, 4));
+        assert_eq!(end_offset_for_epoch(&mut logs, 1), (1, 2));
+        // An epoch without records ends with the largest epoch before it
+        assert_eq!(end_offset_for_epoch(&mut logs, 2), (1, 2));
+        // One older than the log ends where the log starts
+        assert_eq!(end_offset_for_epoch(&mut logs, 0), (0, 0));
+        assert_eq!(end_offset_for_epoch(&mut logs, 4), (-1, -1));
+        assert_eq!(end_offset_for_epoch(&mut logs, -1), (-1, -1));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn truncation_drops_the_epochs_past_the_log_end() {
+        let (dir, config, mut logs) = open("epochs-truncate", 1, 1);
+        let replica = TopicPartition::new("topic", 0);
+        append(&mut logs, &config, 0, 2);
+        append(&mut logs, &config, 1, 1);
+        append(&mut logs, &config, 2, 1);
+        assert_eq!(logs.truncate_to(&replica, 2).unwrap(), 2);
+        assert_eq!(logs.leader_epochs(&replica).unwrap(), vec![(0, 0)]);
+        assert_eq!(epoch_checkpoint(&dir), "0\n1\n0 0\n");
+        assert_eq!(end_offset_for_epoch(&mut logs, 0), (0, 2));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn reloads_the_leader_epochs_after_a_restart() {
+        let (dir, config, mut logs) = open("epochs-reload", 1, 1);
+        let replica = TopicPartition::new("topic", 0);
+        append(&mut logs, &config, 0, 1);
+        append(&mut logs, &config, 2, 1);
+        drop(logs);
+
+        let mut logs = LogManager::open(&config, "cluster").unwrap();
+        assert_eq!(logs.leader_epochs(&replica).unwrap(), vec![(0, 0), (2, 1)]);
+        drop(logs);
+
+        // Lacking a checkpoint, the epochs are read from the batch headers
+        fs::remove_file(dir.join("logs-0/topic-0").join(LEADER_EPOCH_CHECKPOINT)).unwrap();
+        let mut logs = LogManager::open(&config, "cluster").unwrap();
+        assert_eq!(logs.leader_epochs(&replica).unwrap(), vec![(0, 0), (2, 1)]);
+        assert_eq!(end_offset_for_epoch(&mut logs, 2), (2, 2));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    fn dir_of(logs: &LogManager, partition: i32) -> usize {
+        logs.replicas[&TopicPartition::new("topic", partition)]
+    }
+
+    #[test]
+    fn places_new_logs_in_the_directory_holding_the_fewest_replicas() {
+        let (dir, _, mut logs) = open("placement", 2, 3);
+        let placed: Vec<usize> = (0..3).map(|partition| dir_of(&logs, partition)).collect();
+        assert_eq!(placed, vec![0, 1, 0]);
+
+        let records = logs.sync_replicas(&image(4));
+        assert_eq!(dir_of(&logs, 3), 1);
+        // Every replica reports the directory it landed in, as none was assigned one
+        let directories: Vec<Vec<Uuid>> = records
+            .iter()
+            .filter_map(|record| match record {
+                MetadataRecord::PartitionChange(change) => change.directories.clone(),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(directories.len(), 4);
+        assert!(directories.contains(&vec![logs.dirs()[1].id]));
+
+        // A replica assigned a directory goes there, whatever its load
+        let mut assigned = image(5);
+        let topic_id = Uuid::from_u128(1);
+        let registration = assigned.partitions.get_mut(&topic_id).unwrap();
+        registration.get_mut(&4).unwrap().directories = vec![logs.dirs()[0].id];
+        logs.sync_replicas(&assigned);
+        assert_eq!(dir_of(&logs, 4), 0);
+        assert_eq!(logs.replicas_in(0).count(), 3);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    fn segment_count(dir: &Path) -> usize {
+        segment_files(&dir.join("logs-0/topic-0")).unwrap().len()
+    }
+
+    #[test]
+    fn delete_records_checkpoints_the_log_start_offset_before_deleting_segments() {
+        let (dir, config, mut logs) = open("delete-records", 1, 1);
+        let replica = TopicPartition::new("topic", 0);
+        append(&mut logs, &config, 0, 3);
+        logs.set_high_watermark(&replica, 2);
+        assert!(matches!(
+            logs.delete_records(&replica, 3),
+            Err(LogDirError::OffsetOutOfRange { .. })
+        ));
+
+        assert_eq!(logs.delete_records(&replica, HIGH_WATERMARK).unwrap(), 2);
+        let checkpoint = fs::read_to_string(dir.join("logs-0").join(LOG_START_OFFSET_CHECKPOINT));
+        assert_eq!(checkpoint.unwrap(), "0\n1\ntopic 0 2\n");
+        assert_eq!(segment_count(&dir), 1);
+        assert_eq!(logs.leader_epochs(&replica).unwrap(), vec![(0, 2)]);
+        // The start never moves backwards
+        assert_eq!(logs.delete_records(&replica, 1).unwrap(), 2);
+        drop(logs);
+
+        let mut logs = LogManager::open(&config, "cluster").unwrap();
+        assert_eq!(logs.log_start_offset(&replica).unwrap(), 2);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn delete_records_keeps_the_segments_when_the_checkpoint_cannot_be_written() {
+        let (dir, config, mut logs) = open("delete-records-failure", 1, 1);
+        let replica = TopicPartition::new("topic", 0);
+        append(&mut logs, &config, 0, 3);
+        logs.set_high_watermark(&replica, 3);
+        // A directory in the way of the checkpoint makes replacing it fail
+        let checkpoint = dir.join("logs-0").join(LOG_START_OFFSET_CHECKPOINT);
+        fs::create_dir_all(checkpoint.join("blocker")).unwrap();
+
+        assert!(matches!(
+            logs.delete_records(&replica, 2),
+            Err(LogDirError::Offline(_))
+        ));
+        assert_eq!(segment_count(&dir), 3);
+        assert!(!logs.dirs()[0].is_online());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,1186 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs::{self, File, OpenOptions},
+    io::{Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+};
+
+use anyhow::{bail, Context, Result};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use uuid::Uuid;
+
+use crate::{
+    config::Properties,
+    config_registry::{ConfigResource, ConfigResourceType},
+    protocol::{
+        cluster_metadata::{
+            AccessControlEntryRecord, BrokerEndpoint, BrokerEpochRecord, BrokerFeature,
+            ClientQuotaRecord, ConfigRecord, EntityData, FeatureLevelRecord, MetadataRecord,
+            PartitionChangeRecord, PartitionRecord, RecordBatch, RegisterBrokerRecord, TopicRecord,
+            UserScramCredentialRecord,
+        },
+        fetch::{EpochEndOffset, SnapshotId},
+        primitive::Serializable,
+    },
+    quota::ClientQuotaEntity,
+    security::{
+        acl::{AclBinding, AclOperation, AclPermissionType, PatternType, ResourceType},
+        scram::{ScramCredential, ScramMechanism},
+    },
+};
+
+/// Identity of the cluster, node and directory stored in `meta.properties` at the root
+/// of a log directory.
+#[derive(Debug, Clone)]
+pub struct MetaProperties {
+    pub cluster_id: String,
+    pub node_id: i32,
+    pub directory_id: Uuid,
+}
+
+impl MetaProperties {
+    const FILE_NAME: &'static str = "meta.properties";
+
+    /// Read the identity of a formatted directory, formatting it otherwise. The directory
+    /// joins `cluster_id` when given, or a new cluster.
+    pub fn load_or_format(dir: &Path, node_id: i32, cluster_id: Option<&str>) -> Result<Self> {
+        let path = dir.join(Self::FILE_NAME);
+        if !path.exists() {
+            let meta = MetaProperties {
+                cluster_id: cluster_id
+                    .map(str::to_string)
+                    .unwrap_or_else(|| URL_SAFE_NO_PAD.encode(rand::random::<[u8; 16]>())),
+                node_id,
+                directory_id: random_uuid(),
+            };
+            fs::create_dir_all(dir)
+                .with_context(|| format!("failed to create {}", dir.display()))?;
+            meta.write(&path)?;
+            return Ok(meta);
+        }
+
+        let properties = Properties::load(&path)?;
+        let Some(stored_cluster_id) = properties.get("cluster.id") else {
+            bail!("{} has no cluster.id", path.display());
+        };
+        if cluster_id.is_some_and(|cluster_id| cluster_id != stored_cluster_id) {
+            bail!(
+                "{} belongs to cluster {stored_cluster_id}, not to cluster {}",
+                path.display(),
+                cluster_id.unwrap_or_default()
+            );
+        }
+        if let Some(stored) = properties.get("node.id") {
+            if stored.parse::<i32>().ok() != Some(node_id) {
+                bail!(
+                    "{} belongs to node {stored}, not to node {node_id}",
+                    path.display()
+                );
+            }
+        }
+        let directory_id = properties
+            .get("directory.id")
+            .and_then(|id| URL_SAFE_NO_PAD.decode(id).ok())
+            .and_then(|bytes| Uuid::from_slice(&bytes).ok());
+        let meta = MetaProperties {
+            cluster_id: stored_cluster_id.to_string(),
+            node_id,
+            directory_id: directory_id.unwrap_or_else(random_uuid),
+        };
+        // Directories formatted before they had an id get one
+        if directory_id.is_none() {
+            meta.write(&path)?;
+        }
+        Ok(meta)
+    }
+
+    fn write(&self, path: &Path) -> Result<()> {
+        let content = format!(
+            "version=1\ncluster.id={}\nnode.id={}\ndirectory.id={}\n",
+            self.cluster_id,
+            self.node_id,
+            URL_SAFE_NO_PAD.encode(self.directory_id.as_bytes())
+        );
+        fs::write(path, content).with_context(|| format!("failed to write {}", path.display()))
+    }
+}
+
+/// A random (version 4) uuid.
+pub fn random_uuid() -> Uuid {
+    uuid::Builder::from_random_bytes(rand::random()).into_uuid()
+}
+
+/// Levels of the cluster-wide features the broker can run at, as registered with the
+/// controller and reported by ApiVersions. metadata.version 7 (3.3-IV3) is the oldest
+/// KRaft version Apache Kafka still bootstraps, 21 (3.9-IV0) the newest record layout
+/// the broker reads.
+pub fn supported_features() -> Vec<BrokerFeature> {
+    vec![BrokerFeature {
+        name: "metadata.version".to_string(),
+        min_supported_version: 7,
+        max_supported_version: 21,
+    }]
+}
+
+/// A broker as registered in the metadata log.
+#[derive(Debug, Clone)]
+pub struct BrokerRegistration {
+    pub id: i32,
+    /// Offset of the registration record, changing whenever the broker registers again
+    pub epoch: i64,
+    pub incarnation_id: Uuid,
+    pub endpoints: Vec<BrokerEndpoint>,
+    pub rack: Option<String>,
+    pub fenced: bool,
+    pub in_controlled_shutdown: bool,
+    /// Online log directories of the broker
+    pub log_dirs: Vec<Uuid>,
+}
+
+/// A partition as recorded in the metadata log.
+#[derive(Debug, Clone)]
+pub struct PartitionRegistration {
+    pub replicas: Vec<i32>,
+    pub isr: Vec<i32>,
+    pub leader: i32,
+    pub leader_epoch: i32,
+    pub partition_epoch: i32,
+    /// Log directory of each replica, empty when unassigned
+    pub directories: Vec<Uuid>,
+    /// Replicas a reassignment adds, still catching up
+    pub adding_replicas: Vec<i32>,
+    /// Replicas a reassignment removes once the adding ones are in sync
+    pub removing_replicas: Vec<i32>,
+}
+
+impl PartitionRegistration {
+    /// Log directory assigned to the replica on `broker_id`, nil if unassigned.
+    pub fn directory(&self, broker_id: i32) -> Uuid {
+        self.replicas
+            .iter()
+            .position(|&replica| replica == broker_id)
+            .and_then(|index| self.directories.get(index).copied())
+            .unwrap_or_default()
+    }
+
+    /// Log directories of `replicas` in that order, nil for those without one.
+    pub fn directories_of(&self, replicas: &[i32]) -> Vec<Uuid> {
+        replicas.iter().map(|&id| self.directory(id)).collect()
+    }
+
+    /// Whether a reassignment of the partition is under way.
+    pub fn is_reassigning(&self) -> bool {
+        !self.adding_replicas.is_empty() || !self.removing_replicas.is_empty()
+    }
+}
+
+/// In-memory state of the cluster built by replaying the `__cluster_metadata` log.
+#[derive(Debug, Default)]
+pub struct MetadataImage {
+    pub brokers: BTreeMap<i32, BrokerRegistration>,
+    pub topics: HashMap<String, Uuid>,
+    /// Partitions of each topic, by topic id
+    pub partitions: HashMap<Uuid, BTreeMap<i32, PartitionRegistration>>,
+    /// Dynamic configs of topics and brokers
+    pub configs: HashMap<ConfigResource, BTreeMap<String, String>>,
+    pub scram_credentials: HashMap<(String, ScramMechanism), ScramCredential>,
+    pub acls: HashMap<Uuid, AclBinding>,
+    pub client_quotas: HashMap<ClientQuotaEntity, HashMap<String, f64>>,
+    /// Finalized level of each cluster-wide feature
+    pub features: BTreeMap<String, i16>,
+    /// Offset up to which the metadata log is applied
+    pub offset: i64,
+}
+
+impl MetadataImage {
+    pub fn apply(&mut self, record: MetadataRecord) {
+        match record {
+            MetadataRecord::RegisterBroker(record) => {
+                self.brokers.insert(
+                    record.broker_id,
+                    BrokerRegistration {
+                        id: record.broker_id,
+                        epoch: record.broker_epoch,
+                        incarnation_id: record.incarnation_id,
+                        endpoints: record.end_points,
+                        rack: record.rack,
+                        fenced: record.fenced,
+                        in_controlled_shutdown: record.in_controlled_shutdown,
+                        log_dirs: record.log_dirs,
+                    },
+                );
+            }
+            MetadataRecord::UnregisterBroker(record) => {
+                if self
+                    .brokers
+                    .get(&record.broker_id)
+                    .is_some_and(|broker| broker.epoch == record.broker_epoch)
+                {
+                    self.brokers.remove(&record.broker_id);
+                }
+            }
+            MetadataRecord::FenceBroker(record) => self.set_fenced(&record, true),
+            MetadataRecord::UnfenceBroker(record) => self.set_fenced(&record, false),
+            MetadataRecord::BrokerRegistrationChange(record) => {
+                if let Some(broker) = self.brokers.get_mut(&record.broker_id) {
+                    if broker.epoch == record.broker_epoch {
+                        if let Some(fenced) = record.fenced {
+                            broker.fenced = fenced;
+                        }
+                        if record.in_controlled_shutdown {
+                            broker.in_controlled_shutdown = true;
+                        }
+                    }
+                }
+            }
+            MetadataRecord::Topic(topic) => {
+                self.topics.insert(topic.name, topic.topic_id);
+            }
+            MetadataRecord::Partition(record) => {
+                self.partitions.entry(record.topic_id).or_default().insert(
+                    record.partition_id,
+                    PartitionRegistration {
+                        replicas: record.replicas,
+                        isr: record.isr,
+                        leader: record.leader,
+                        leader_epoch: record.leader_epoch,
+                        partition_epoch: record.partition_epoch,
+                        directories: record.directories,
+                        adding_replicas: record.adding_replicas,
+                        removing_replicas: record.removing_replicas,
+                    },
+                );
+            }
+            MetadataRecord::PartitionChange(record) => {
+                let Some(partition) = self
+                    .partitions
+                    .get_mut(&record.topic_id)
+                    .and_then(|partitions| partitions.get_mut(&record.partition_id))
+                else {
+                    return;
+                };
+                if let Some(isr) = record.isr {
+                    partition.isr = isr;
+                }
+                if record.leader != PartitionChangeRecord::NO_LEADER_CHANGE {
+                    partition.leader = record.leader;
+                    partition.leader_epoch += 1;
+                }
+                if let Some(replicas) = record.replicas {
+                    partition.replicas = replicas;
+                }
+                if let Some(directories) = record.directories {
+                    partition.directories = directories;
+                }
+                if let Some(adding_replicas) = record.adding_replicas {
+                    partition.adding_replicas = adding_replicas;
+                }
+                if let Some(removing_replicas) = record.removing_replicas {
+                    partition.removing_replicas = removing_replicas;
+                }
+                partition.partition_epoch += 1;
+            }
+            MetadataRecord::Config(record) => {
+                let resource = ConfigResource::new(
+                    ConfigResourceType::from_code(record.resource_type),
+                    record.resource_name,
+                );
+                match record.value {
+                    Some(value) => {
+                        self.configs
+                            .entry(resource)
+                            .or_default()
+                            .insert(record.name, value);
+                    }
+                    None => {
+                        if let Some(configs) = self.configs.get_mut(&resource) {
+                            configs.remove(&record.name);
+                            if configs.is_empty() {
+                                self.configs.remove(&resource);
+                            }
+                        }
+                    }
+                }
+            }
+            MetadataRecord::AccessControlEntry(record) => {
+                self.acls.insert(
+                    record.id,
+                    AclBinding {
+                        resource_type: ResourceType::from_code(record.resource_type),
+                        resource_name: record.resource_name,
+                        pattern_type: PatternType::from_code(record.pattern_type),
+                        principal: record.principal,
+                        host: record.host,
+                        operation: AclOperation::from_code(record.operation),
+                        permission_type: AclPermissionType::from_code(record.permission_type),
+                    },
+                );
+            }
+            MetadataRecord::RemoveAccessControlEntry(record) => {
+                self.acls.remove(&record.id);
+            }
+            MetadataRecord::UserScramCredential(record) => {
+                let Some(mechanism) = ScramMechanism::from_type(record.mechanism) else {
+                    return;
+                };
+                self.scram_credentials.insert(
+                    (record.name, mechanism),
+                    ScramCredential {
+                        salt: record.salt,
+                        stored_key: record.stored_key,
+                        server_key: record.server_key,
+                        iterations: record.iterations,
+                    },
+                );
+            }
+            MetadataRecord::FeatureLevel(record) => {
+                if record.feature_level == 0 {
+                    self.features.remove(&record.name);
+                } else {
+                    self.features.insert(record.name, record.feature_level);
+                }
+            }
+            MetadataRecord::ClientQuota(record) => {
+                let entity = ClientQuotaEntity(
+                    record
+                        .entity
+                        .into_iter()
+                        .map(|data| (data.entity_type, data.entity_name))
+                        .collect(),
+                );
+                if record.remove {
+                    if let Some(values) = self.client_quotas.get_mut(&entity) {
+                        values.remove(&record.key);
+                        if values.is_empty() {
+                            self.client_quotas.remove(&entity);
+                        }
+                    }
+                } else {
+                    self.client_quotas
+                        .entry(entity)
+                        .or_default()
+                        .insert(record.key, record.value);
+                }
+            }
+            MetadataRecord::RemoveUserScramCredential(record) => {
+                if let Some(mechanism) = ScramMechanism::from_type(record.mechanism) {
+                    self.scram_credentials.remove(&(record.name, mechanism));
+                }
+            }
+            MetadataRecord::Unknown { .. } => {}
+        }
+    }
+
+    /// Records that rebuild the image when applied to an empty one, as written to
+    /// snapshots. Records of types the broker does not interpret are not kept.
+    pub fn records(&self) -> Vec<MetadataRecord> {
+        let mut records = vec![];
+        for (name, &feature_level) in &self.features {
+            records.push(MetadataRecord::FeatureLevel(FeatureLevelRecord {
+                name: name.clone(),
+                feature_level,
+            }));
+        }
+        for broker in self.brokers.values() {
+            records.push(MetadataRecord::RegisterBroker(RegisterBrokerRecord {
+                broker_id: broker.id,
+                is_migrating_zk_broker: false,
+                incarnation_id: broker.incarnation_id,
+                broker_epoch: broker.epoch,
+                end_points: broker.endpoints.clone(),
+                features: vec![],
+                rack: broker.rack.clone(),
+                fenced: broker.fenced,
+                in_controlled_shutdown: broker.in_controlled_shutdown,
+                log_dirs: broker.log_dirs.clone(),
+            }));
+        }
+
+        let topics: BTreeMap<&String, &Uuid> = self.topics.iter().collect();
+        for (name, &topic_id) in topics {
+            records.push(MetadataRecord::Topic(TopicRecord {
+                name: name.clone(),
+                topic_id,
+            }));
+            let partitions = self.partitions.get(&topic_id).into_iter().flatten();
+            for (&partition_id, partition) in partitions {
+                records.push(MetadataRecord::Partition(PartitionRecord {
+                    partition_id,
+                    topic_id,
+                    replicas: partition.replicas.clone(),
+                    isr: partition.isr.clone(),
+                    removing_replicas: partition.removing_replicas.clone(),
+                    adding_replicas: partition.adding_replicas.clone(),
+                    leader: partition.leader,
+                    leader_epoch: partition.leader_epoch,
+                    partition_epoch: partition.partition_epoch,
+                    directories: partition.directories.clone(),
+                }));
+            }
+        }
+
+        let configs: BTreeMap<_, _> = self.configs.iter().collect();
+        for (resource, configs) in configs {
+            for (name, value) in configs {
+                records.push(MetadataRecord::Config(ConfigRecord {
+                    resource_type: resource.resource_type.code(),
+                    resource_name: resource.name.clone(),
+                    name: name.clone(),
+                    value: Some(value.clone()),
+                }));
+            }
+        }
+
+        for ((name, mechanism), credential) in &self.scram_credentials {
+            records.push(MetadataRecord::UserScramCredential(
+                UserScramCredentialRecord {
+                    name: name.clone(),
+                    mechanism: mechanism.type_id(),
+                    salt: credential.salt.clone(),
+                    stored_key: credential.stored_key.clone(),
+                    server_key: credential.server_key.clone(),
+                    iterations: credential.iterations,
+                },
+            ));
+        }
+
+        for (&id, acl) in &self.acls {
+            records.push(MetadataRecord::AccessControlEntry(
+                AccessControlEntryRecord {
+                    id,
+                    resource_type: acl.resource_type.code(),
+                    resource_name: acl.resource_name.clone(),
+                    pattern_type: acl.pattern_type.code(),
+                    principal: acl.principal.clone(),
+                    host: acl.host.clone(),
+                    operation: acl.operation.code(),
+                    permission_type: acl.permission_type.code(),
+                },
+            ));
+        }
+
+        for (entity, quotas) in &self.client_quotas {
+            for (key, &value) in quotas {
+                records.push(MetadataRecord::ClientQuota(ClientQuotaRecord {
+                    entity: entity
+                        .0
+                        .iter()
+                        .map(|(entity_type, entity_name)| EntityData {
+                            entity_type: entity_type.clone(),
+                            entity_name: entity_name.clone(),
+                        })
+                        .collect(),
+                    key: key.clone(),
+                    value,
+                    remove: false,
+                }));
+            }
+        }
+        records
+    }
+
+    /// Records naming an earlier registration of the broker are ignored.
+    fn set_fenced(&mut self, record: &BrokerEpochRecord, fenced: bool) {
+        if let Some(broker) = self.brokers.get_mut(&record.broker_id) {
+            if broker.epoch == record.broker_epoch {
+                broker.fenced = fenced;
+            }
+        }
+    }
+
+    /// Topic id and registration of a partition, if it exists.
+    /// Name of the topic with the given id.
+    pub fn topic_name(&self, topic_id: Uuid) -> Option<&str> {
+        self.topics
+            .iter()
+            .find(|(_, &id)| id == topic_id)
+            .map(|(name, _)| name.as_str())
+    }
+
+    pub fn partition(&self, topic: &str, partition: i32) -> Option<(Uuid, &PartitionRegistration)> {
+        let &topic_id = self.topics.get(topic)?;
+        let registration = self.partitions.get(&topic_id)?.get(&partition)?;
+        Some((topic_id, registration))
+    }
+
+    /// Dynamic value of a config of the resource, if one is set.
+    pub fn config(&self, resource: &ConfigResource, name: &str) -> Option<&str> {
+        self.configs.get(resource)?.get(name).map(String::as_str)
+    }
+
+    pub fn scram_credential(
+        &self,
+        user: &str,
+        mechanism: ScramMechanism,
+    ) -> Option<&ScramCredential> {
+        self.scram_credentials.get(&(user.to_string(), mechanism))
+    }
+}
+
+/// Where a batch of the metadata log is stored.
+#[derive(Debug, Clone, Copy)]
+struct BatchEntry {
+    base_offset: i64,
+    last_offset: i64,
+    epoch: i32,
+    /// Index of the segment holding the batch
+    segment: usize,
+    position: u64,
+    size: u64,
+}
+
+/// The `__cluster_metadata` partition replicated by the KRaft quorum, indexed by batch
+/// so that followers can be served and diverging suffixes truncated. The log starts
+/// where its latest snapshot ends once the segments before it are deleted.
+#[derive(Debug)]
+pub struct MetadataLog {
+    partition_dir: PathBuf,
+    segment_bytes: u64,
+    segments: Vec<PathBuf>,
+    batches: Vec<BatchEntry>,
+    snapshot: Option<SnapshotId>,
+}
+
+impl MetadataLog {
+    /// Open the metadata partition and index the batches of every segment, dropping a
+    /// torn write at the end of the log.
+    pub fn open(partition_dir: &Path, segment_bytes: u64) -> Result<Self> {
+        let snapshot = snapshot_ids(partition_dir)?.pop();
+        let mut segments = segment_files(partition_dir)?;
+        if segments.is_empty() {
+            let base_offset = snapshot.map_or(0, |snapshot| snapshot.end_offset);
+            segments.push(partition_dir.join(segment_file_name(base_offset)));
+        }
+        let mut batches = vec![];
+        for (index, segment) in segments.iter().enumerate() {
+            if !segment.exists() {
+                continue;
+            }
+            let content = fs::read(segment)
+                .with_context(|| format!("failed to read {}", segment.display()))?;
+            let mut bytes = content.as_slice();
+            while bytes.len() >= RecordBatch::LOG_OVERHEAD {
+                let batch = match RecordBatch::deserialize(bytes) {
+                    Ok((batch, _)) if batch.is_valid() => batch,
+                    Ok(_) => {
+                        eprintln!(
+                            "Stopping metadata replay in {}: corrupt batch",
+                            segment.display()
+                        );
+                        break;
+                    }
+                    Err(e) => {
+                        eprintln!("Stopping metadata replay in {}: {e}", segment.display());
+                        break;
+                    }
+                };
+                let size = RecordBatch::LOG_OVERHEAD
+                    + i32::from_be_bytes(bytes[8..12].try_into().unwrap()) as usize;
+                batches.push(BatchEntry {
+                    base_offset: batch.base_offset,
+                    last_offset: batch.last_offset(),
+                    epoch: batch.partition_leader_epoch,
+                    segment: index,
+                    position: (content.len() - bytes.len()) as u64,
+                    size: size as u64,
+                });
+                bytes = &bytes[size..];
+            }
+            // Drop a torn write at the end of the log so new batches follow a valid one
+            if !bytes.is_empty() && index == segments.len() - 1 {
+                let valid_length = (content.len() - bytes.len()) as u64;
+                OpenOptions::new()
+                    .write(true)
+                    .open(segment)
+                    .and_then(|file| file.set_len(valid_length))
+                    .with_context(|| format!("failed to truncate {}", segment.display()))?;
+            }
+        }
+        let mut log = MetadataLog {
+            partition_dir: partition_dir.to_path_buf(),
+            segment_bytes,
+            segments,
+            batches,
+            snapshot,
+        };
+        // A snapshot installed from the leader may be ahead of the log it replaces
+        if let Some(snapshot) = snapshot {
+            if log.end_offset() < snapshot.end_offset {
+                log.reset_to(snapshot.end_offset)?;
+            }
+        }
+        Ok(log)
+    }
+
+    pub fn partition_dir(&self) -> &Path {
+        &self.partition_dir
+    }
+
+    /// First offset held by the log; anything before it is only in the snapshot.
+    pub fn start_offset(&self) -> i64 {
+        self.batches
+            .first()
+            .map_or(self.end_offset(), |batch| batch.base_offset)
+    }
+
+    /// Offset the next batch is written at.
+    pub fn end_offset(&self) -> i64 {
+        match self.batches.last() {
+            Some(batch) => batch.last_offset + 1,
+            None => self.snapshot.map_or(0, |snapshot| snapshot.end_offset),
+        }
+    }
+
+    /// Epoch of the last batch, 0 for an empty log.
+    pub fn last_epoch(&self) -> i32 {
+        match self.batches.last() {
+            Some(batch) => batch.epoch,
+            None => self.snapshot.map_or(0, |snapshot| snapshot.epoch),
+        }
+    }
+
+    /// Latest snapshot of the log.
+    pub fn snapshot(&self) -> Option<SnapshotId> {
+        self.snapshot
+    }
+
+    /// Durably append the batch at the end of the log and return its base offset.
+    pub fn append(&mut self, mut batch: RecordBatch) -> Result<i64> {
+        let base_offset = self.end_offset();
+        batch.base_offset = base_offset;
+        self.write(&batch, &batch.serialize())?;
+        Ok(base_offset)
+    }
+
+    /// Durably append batches copied from the leader. Batches the log already holds are
+    /// skipped, and the rest must follow on from its end.
+    pub fn append_raw(&mut self, mut bytes: &[u8]) -> Result<()> {
+        while bytes.len() >= RecordBatch::LOG_OVERHEAD {
+            let (batch, rest) = RecordBatch::deserialize(bytes)?;
+            let raw = &bytes[..bytes.len() - rest.len()];
+            bytes = rest;
+            if !batch.is_valid() {
+                bail!("corrupt metadata batch at offset {}", batch.base_offset);
+            }
+            if batch.last_offset() < self.end_offset() {
+                continue;
+            }
+            if batch.base_offset != self.end_offset() {
+                bail!(
+                    "metadata batch at offset {} does not follow the log end offset {}",
+                    batch.base_offset,
+                    self.end_offset()
+                );
+            }
+            self.write(&batch, raw)?;
+        }
+        Ok(())
+    }
+
+    fn write(&mut self, batch: &RecordBatch, bytes: &[u8]) -> Result<()> {
+        fs::create_dir_all(&self.partition_dir)
+            .with_context(|| format!("failed to create {}", self.partition_dir.display()))?;
+        let segment_size = self
+            .batches
+            .last()
+            .filter(|last| last.segment == self.segments.len() - 1)
+            .map_or(0, |last| last.position + last.size);
+        if segment_size >= self.segment_bytes {
+            let path = self
+                .partition_dir
+                .join(segment_file_name(batch.base_offset));
+            self.segments.push(path);
+            // The segment rolled may be covered by the latest snapshot already
+            self.delete_covered_segments()?;
+        }
+        let segment = self.segments.len() - 1;
+        let path = &self.segments[segment];
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .with_context(|| format!("failed to open {}", path.display()))?;
+        let position = file.metadata()?.len();
+        file.write_all(bytes)?;
+        file.sync_data()?;
+        self.batches.push(BatchEntry {
+            base_offset: batch.base_offset,
+            last_offset: batch.last_offset(),
+            epoch: batch.partition_leader_epoch,
+            segment,
+            position,
+            size: bytes.len() as u64,
+        });
+        Ok(())
+    }
+
+    /// Remove every batch from the one holding `offset` on, so that the log ends at or
+    /// before it.
+    pub fn truncate_to(&mut self, offset: i64) -> Result<()> {
+        let Some(index) = self
+            .batches
+            .iter()
+            .position(|batch| batch.last_offset >= offset)
+        else {
+            return Ok(());
+        };
+        let first_removed = self.batches[index];
+        for path in self.segments.drain(first_removed.segment + 1..) {
+            fs::remove_file(&path)
+                .with_context(|| format!("failed to delete {}", path.display()))?;
+        }
+        let path = &self.segments[first_removed.segment];
+        let file = OpenOptions::new()
+            .write(true)
+            .open(path)
+            .with_context(|| format!("failed to open {}", path.display()))?;
+        file.set_len(first_removed.position)?;
+        file.sync_data()?;
+        self.batches.truncate(index);
+        Ok(())
+    }
+
+    /// Raw batches from the one holding `offset`, at least one of them and then as many
+    /// as fit in `max_bytes`.
+    pub fn read(&self, offset: i64, max_bytes: usize) -> Result<Vec<u8>> {
+        let mut buf = Vec::new();
+        for batch in self
+            .batches
+            .iter()
+            .filter(|batch| batch.last_offset >= offset)
+        {
+            if !buf.is_empty() && buf.len() + batch.size as usize > max_bytes {
+                break;
+            }
+            buf.extend(self.read_batch(batch)?);
+        }
+        Ok(buf)
+    }
+
+    /// Batches holding offsets from `from` up to, but excluding, `to`.
+    pub fn batches(&self, from: i64, to: i64) -> Result<Vec<RecordBatch>> {
+        self.batches
+            .iter()
+            .filter(|batch| batch.last_offset >= from && batch.base_offset < to)
+            .map(|batch| Ok(RecordBatch::deserialize(&self.read_batch(batch)?)?.0))
+            .collect()
+    }
+
+    fn read_batch(&self, batch: &BatchEntry) -> Result<Vec<u8>> {
+        let path = &self.segments[batch.segment];
+        let mut file =
+            File::open(path).with_context(|| format!("failed to open {}", path.display()))?;
+        file.seek(SeekFrom::Start(batch.position))?;
+        let mut buf = vec![0; batch.size as usize];
+        file.read_exact(&mut buf)?;
+        Ok(buf)
+    }
+
+    /// Largest epoch of the log not above `epoch`, with the offset the log moved past it.
+    /// An epoch older than the whole log ends at its start.
+    pub fn end_offset_for_epoch(&self, epoch: i32) -> EpochEndOffset {
+        match self.batches.iter().rposition(|batch| batch.epoch <= epoch) {
+            Some(index) => EpochEndOffset {
+                epoch: self.batches[index].epoch,
+                end_offset: self.batches[index].last_offset + 1,
+            },
+            // The snapshot may still cover the epoch
+            None => EpochEndOffset {
+                epoch: self
+                    .snapshot
+                    .filter(|snapshot| snapshot.epoch <= epoch)
+                    .map_or(-1, |snapshot| snapshot.epoch),
+                end_offset: self.start_offset(),
+            },
+        }
+    }
+
+    /// Durably write a snapshot of the log up to `snapshot_id` made of the batches, then
+    /// drop older snapshots and the segments it covers.
+    pub fn write_snapshot(
+        &mut self,
+        snapshot_id: SnapshotId,
+        batches: &[RecordBatch],
+    ) -> Result<()> {
+        let bytes: Vec<u8> = batches.iter().flat_map(|batch| batch.serialize()).collect();
+        self.store_snapshot(snapshot_id, &bytes)?;
+        self.delete_covered_segments()
+    }
+
+    /// Durably store a snapshot fetched from the leader. The log is emptied to start at
+    /// the end of the snapshot unless it holds that offset in the same epoch.
+    pub fn install_snapshot(&mut self, snapshot_id: SnapshotId, bytes: &[u8]) -> Result<()> {
+        let mut rest = bytes;
+        while !rest.is_empty() {
+            let (batch, next) = RecordBatch::deserialize(rest)?;
+            if !batch.is_valid() {
+                bail!(
+                    "corrupt batch in snapshot {}",
+                    snapshot_file_name(snapshot_id)
+                );
+            }
+            rest = next;
+        }
+        self.store_snapshot(snapshot_id, bytes)?;
+        let last_offset = snapshot_id.end_offset - 1;
+        let consistent = self.batches.iter().any(|batch| {
+            batch.base_offset <= last_offset
+                && last_offset <= batch.last_offset
+                && batch.epoch == snapshot_id.epoch
+        });
+        if consistent {
+            self.delete_covered_segments()
+        } else {
+            self.reset_to(snapshot_id.end_offset)
+        }
+    }
+
+    fn store_snapshot(&mut self, snapshot_id: SnapshotId, bytes: &[u8]) -> Result<()> {
+        fs::create_dir_all(&self.partition_dir)
+            .with_context(|| format!("failed to create {}", self.partition_dir.display()))?;
+        let path = self.snapshot_path(snapshot_id);
+        let partial = path.with_extension("checkpoint.part");
+        let mut file = File::create(&partial)
+            .with_context(|| format!("failed to create {}", partial.display()))?;
+        file.write_all(bytes)?;
+        file.sync_all()?;
+        fs::rename(&partial, &path)
+            .with_context(|| format!("failed to write {}", path.display()))?;
+        self.snapshot = Some(snapshot_id);
+
+        for older in snapshot_ids(&self.partition_dir)? {
+            if older < snapshot_id {
+                let path = self.snapshot_path(older);
+                fs::remove_file(&path)
+                    .with_context(|| format!("failed to delete {}", path.display()))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Delete the segments holding nothing past the latest snapshot, keeping the one
+    /// being written to.
+    fn delete_covered_segments(&mut self) -> Result<()> {
+        let Some(snapshot) = self.snapshot else {
+            return Ok(());
+        };
+        let covered = (0..self.segments.len() - 1)
+            .take_while(|&segment| {
+                self.batches
+                    .iter()
+                    .filter(|batch| batch.segment == segment)
+                    .all(|batch| batch.last_offset < snapshot.end_offset)
+            })
+            .count();
+        if covered == 0 {
+            return Ok(());
+        }
+        for path in self.segments.drain(..covered) {
+            fs::remove_file(&path)
+                .with_context(|| format!("failed to delete {}", path.display()))?;
+        }
+        self.batches.retain(|batch| batch.segment >= covered);
+        for batch in &mut self.batches {
+            batch.segment -= covered;
+        }
+        Ok(())
+    }
+
+    /// Delete every segment so that the log starts over at `offset`.
+    fn reset_to(&mut self, offset: i64) -> Result<()> {
+        for path in self.segments.drain(..) {
+            if path.exists() {
+                fs::remove_file(&path)
+                    .with_context(|| format!("failed to delete {}", path.display()))?;
+            }
+        }
+        self.segments
+            .push(self.partition_dir.join(segment_file_name(offset)));
+        self.batches.clear();
+        Ok(())
+    }
+
+    pub fn snapshot_path(&self, snapshot_id: SnapshotId) -> PathBuf {
+        self.partition_dir.join(snapshot_file_name(snapshot_id))
+    }
+
+    /// Batches of a snapshot, control batches included.
+    pub fn read_snapshot(&self, snapshot_id: SnapshotId) -> Result<Vec<RecordBatch>> {
+        let path = self.snapshot_path(snapshot_id);
+        let content =
+            fs::read(&path).with_context(|| format!("failed to read {}", path.display()))?;
+        let mut bytes = content.as_slice();
+        let mut batches = vec![];
+        while !bytes.is_empty() {
+            let (batch, rest) = RecordBatch::deserialize(bytes)?;
+            if !batch.is_valid() {
+                bail!("corrupt batch in {}", path.display());
+            }
+            batches.push(batch);
+            bytes = rest;
+        }
+        Ok(batches)
+    }
+}
+
+/// Name of the segment file starting at `base_offset`.
+pub fn segment_file_name(base_offset: i64) -> String {
+    format!("{base_offset:020}.log")
+}
+
+/// Name of the snapshot file holding the metadata log up to `snapshot_id`.
+pub fn snapshot_file_name(snapshot_id: SnapshotId) -> String {
+    format!(
+        "{:020}-{:010}.checkpoint",
+        snapshot_id.end_offset, snapshot_id.epoch
+    )
+}
+
+/// Snapshots of the metadata partition, oldest first. Partially written ones left by a
+/// crash are deleted.
+fn snapshot_ids(partition_dir: &Path) -> Result<Vec<SnapshotId>> {
+    if !partition_dir.exists() {
+        return Ok(vec![]);
+    }
+    let mut snapshots = vec![];
+    for entry in fs::read_dir(partition_dir)
+        .with_context(|| format!("failed to list {}", partition_dir.display()))?
+    {
+        let path = entry?.path();
+        let Some(name) = path.file_name().and_then(|name| name.to_str()) else {
+            continue;
+        };
+        if name.ends_with(".checkpoint.part") {
+            fs::remove_file(&path)
+                .with_context(|| format!("failed to delete {}", path.display()))?;
+            continue;
+        }
+        let parsed = name
+            .strip_suffix(".checkpoint")
+            .and_then(|stem| stem.split_once('-'))
+            .and_then(|(end_offset, epoch)| Some((end_offset.parse().ok()?, epoch.parse().ok()?)));
+        if let Some((end_offset, epoch)) = parsed {
+            snapshots.push(SnapshotId { end_offset, epoch });
+        }
+    }
+    snapshots.sort();
+    Ok(snapshots)
+}
+
+/// Log segments of a partition directory, ordered by base offset.
+pub fn segment_files(partition_dir: &Path) -> Result<Vec<PathBuf>> {
+    if !partition_dir.exists() {
+        return Ok(vec![]);
+    }
+    let mut segments: Vec<PathBuf> = fs::read_dir(partition_dir)
+        .with_context(|| format!("failed to list {}", partition_dir.display()))?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "log"))
+        .collect();
+    // Segment names are zero-padded base offsets, so they sort lexicographically
+    segments.sort();
+    Ok(segments)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn register(broker_id: i32) -> MetadataRecord {
+        MetadataRecord::RegisterBroker(RegisterBrokerRecord {
+            broker_id,
+            is_migrating_zk_broker: false,
+            incarnation_id: Uuid::from_u128(broker_id as u128),
+            broker_epoch: 0,
+            end_points: vec![],
+            features: vec![],
+            rack: None,
+            fenced: false,
+            in_controlled_shutdown: false,
+            log_dirs: vec![],
+        })
+    }
+
+    fn topic(name: &str, id: u128) -> Vec<MetadataRecord> {
+        let topic_id = Uuid::from_u128(id);
+        vec![
+            MetadataRecord::Topic(TopicRecord {
+                name: name.to_string(),
+                topic_id,
+            }),
+            MetadataRecord::Partition(PartitionRecord {
+                partition_id: 0,
+                topic_id,
+                replicas: vec![1, 2],
+                isr: vec![1, 2],
+                removing_replicas: vec![],
+                adding_replicas: vec![],
+                leader: 1,
+                leader_epoch: 0,
+                partition_epoch: 0,
+                directories: vec![],
+            }),
+        ]
+    }
+
+    fn config(name: &str, value: Option<&str>) -> MetadataRecord {
+        MetadataRecord::Config(ConfigRecord {
+            resource_type: ConfigResourceType::Topic.code(),
+            resource_name: "a".to_string(),
+            name: name.to_string(),
+            value: value.map(str::to_string),
+        })
+    }
+
+    /// Batches of records building a cluster of two brokers and two topics, changed
+    /// along the way.
+    fn history() -> Vec<Vec<MetadataRecord>> {
+        let mut shrink_isr = PartitionChangeRecord::new(Uuid::from_u128(1), 0);
+        shrink_isr.isr = Some(vec![1]);
+        vec![
+            vec![
+                MetadataRecord::FeatureLevel(FeatureLevelRecord {
+                    name: "metadata.version".to_string(),
+                    feature_level: 20,
+                }),
+                register(1),
+                register(2),
+            ],
+            topic("a", 1),
+            vec![
+                config("retention.ms", Some("1000")),
+                config("segment.ms", Some("1000")),
+                MetadataRecord::PartitionChange(shrink_isr),
+                MetadataRecord::FenceBroker(BrokerEpochRecord {
+                    broker_id: 2,
+                    broker_epoch: 0,
+                }),
+            ],
+            topic("b", 2),
+            vec![config("segment.ms", None)],
+        ]
+    }
+
+    fn replay(image: &mut MetadataImage, batches: Vec<RecordBatch>) {
+        for batch in batches.into_iter().filter(|batch| !batch.is_control()) {
+            for record in batch.records {
+                let value = record.value.unwrap();
+                image.apply(MetadataRecord::deserialize(&value).unwrap().0);
+            }
+        }
+    }
+
+    /// The records rebuilding the image, in an order independent of its maps.
+    fn content(image: &MetadataImage) -> Vec<Vec<u8>> {
+        let mut records: Vec<Vec<u8>> = image.records().iter().map(|r| r.serialize()).collect();
+        records.sort();
+        records
+    }
+
+    /// A log of one segment per batch holding `history`, in a fresh directory.
+    fn log(name: &str) -> (PathBuf, MetadataLog) {
+        let dir = std::env::temp_dir().join(format!("{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let mut log = MetadataLog::open(&dir, 1).unwrap();
+        for records in history() {
+            let values = records.iter().map(|record| record.serialize()).collect();
+            log.append(RecordBatch::new(0, 1, 0, values)).unwrap();
+        }
+        (dir, log)
+    }
+
+    /// Offset following the batch at `index` of the log.
+    fn end_of_batch(log: &MetadataLog, index: usize) -> i64 {
+        log.batches(0, log.end_offset()).unwrap()[index].last_offset() + 1
+    }
+
+    #[test]
+    fn snapshot_and_remaining_log_replay_to_the_same_image() {
+        let (dir, mut log) = log("metadata-snapshot");
+        let mut full = MetadataImage::default();
+        replay(&mut full, log.batches(0, log.end_offset()).unwrap());
+
+        // A snapshot of the image after the first three batches
+        let snapshot_id = SnapshotId {
+            end_offset: end_of_batch(&log, 2),
+            epoch: 1,
+        };
+        let mut image = MetadataImage::default();
+        replay(&mut image, log.batches(0, snapshot_id.end_offset).unwrap());
+        let values = image.records().iter().map(|r| r.serialize()).collect();
+        log.write_snapshot(snapshot_id, &[RecordBatch::new(0, 1, 0, values)])
+            .unwrap();
+        drop(log);
+
+        let log = MetadataLog::open(&dir, 1).unwrap();
+        assert_eq!(log.snapshot(), Some(snapshot_id));
+        assert_eq!(log.start_offset(), snapshot_id.end_offset);
+        let mut restored = MetadataImage::default();
+        replay(&mut restored, log.read_snapshot(snapshot_id).unwrap());
+        replay(
+            &mut restored,
+            log.batches(snapshot_id.end_offset, log.end_offset())
+                .unwrap(),
+        );
+        assert_eq!(content(&restored), content(&full));
+        assert!(restored.brokers[&2].fenced);
+        assert_eq!(
+            restored.config(
+                &ConfigResource::new(ConfigResourceType::Topic, "a"),
+                "segment.ms"
+            ),
+            None
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn snapshots_delete_the_segments_they_cover() {
+        let (dir, mut log) = log("metadata-snapshot-segments");
+        assert_eq!(segment_files(&dir).unwrap().len(), 5);
+        let end_offset = end_of_batch(&log, 1);
+        let older = SnapshotId {
+            end_offset: 1,
+            epoch: 1,
+        };
+        log.write_snapshot(older, &[]).unwrap();
+        let snapshot_id = SnapshotId {
+            end_offset,
+            epoch: 1,
+        };
+        log.write_snapshot(snapshot_id, &[]).unwrap();
+
+        let segments = segment_files(&dir).unwrap();
+        assert_eq!(segments.len(), 3);
+        assert_eq!(segments[0], dir.join(segment_file_name(end_offset)));
+        assert_eq!(log.start_offset(), end_offset);
+        // Only the latest snapshot is kept
+        assert_eq!(snapshot_ids(&dir).unwrap(), vec![snapshot_id]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn starts_the_log_over_at_a_snapshot_ahead_of_it() {
+        let (dir, mut log) = log("metadata-snapshot-ahead");
+        let snapshot_id = SnapshotId {
+            end_offset: log.end_offset() + 10,
+            epoch: 2,
+        };
+        log.write_snapshot(snapshot_id, &[]).unwrap();
+        drop(log);
+
+        let mut log = MetadataLog::open(&dir, 1).unwrap();
+        assert_eq!(log.start_offset(), snapshot_id.end_offset);
+        assert_eq!(log.end_offset(), snapshot_id.end_offset);
+        assert_eq!(log.last_epoch(), 2);
+        assert!(segment_files(&dir).unwrap().is_empty());
+        let values = vec![register(3).serialize()];
+        let offset = log.append(RecordBatch::new(0, 2, 0, values)).unwrap();
+        assert_eq!(offset, snapshot_id.end_offset);
+        assert_eq!(
+            segment_files(&dir).unwrap(),
+            vec![dir.join(segment_file_name(offset))]
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
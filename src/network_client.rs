@@ -0,0 +1,83 @@
+use std::{
+    io::{Read, Write},
+    net::{TcpStream, ToSocketAddrs},
+    time::Duration,
+};
+
+use anyhow::{bail, Context, Result};
+
+use crate::protocol::{
+    header::RequestHeader,
+    primitive::{Serializable, TagSection},
+};
+
+/// A blocking PLAINTEXT connection to another node, with one request in flight at a
+/// time.
+pub struct NetworkClient {
+    stream: TcpStream,
+    client_id: String,
+    correlation_id: i32,
+}
+
+impl NetworkClient {
+    /// Connect to `host:port`, waiting at most `timeout` for the connection and then for
+    /// each response.
+    pub fn connect(host: &str, port: u16, timeout: Duration, client_id: String) -> Result<Self> {
+        let address = (host, port)
+            .to_socket_addrs()
+            .with_context(|| format!("failed to resolve {host}:{port}"))?
+            .next()
+            .with_context(|| format!("no address for {host}:{port}"))?;
+        let stream = TcpStream::connect_timeout(&address, timeout)
+            .with_context(|| format!("failed to connect to {host}:{port}"))?;
+        stream.set_read_timeout(Some(timeout))?;
+        stream.set_nodelay(true)?;
+        Ok(NetworkClient {
+            stream,
+            client_id,
+            correlation_id: 0,
+        })
+    }
+
+    /// Send the request and wait for its response.
+    pub fn send<T: Serializable>(
+        &mut self,
+        api_key: i16,
+        api_version: i16,
+        request: &impl Serializable,
+    ) -> Result<T> {
+        self.correlation_id += 1;
+        let header = RequestHeader {
+            request_api_key: api_key,
+            request_api_version: api_version,
+            correlation_id: self.correlation_id,
+            client_id: Some(self.client_id.clone()),
+            tag_buffer: TagSection(None),
+        };
+        let mut message = header.serialize();
+        message.extend(request.serialize());
+        self.stream
+            .write_all(&(message.len() as i32).to_be_bytes())?;
+        self.stream.write_all(&message)?;
+
+        let mut size_buf = [0; 4];
+        self.stream.read_exact(&mut size_buf)?;
+        let mut msg_buf = vec![0; i32::from_be_bytes(size_buf).max(0) as usize];
+        self.stream.read_exact(&mut msg_buf)?;
+        let (received_id, bytes) = i32::deserialize(&msg_buf)?;
+        if received_id != self.correlation_id {
+            bail!(
+                "expected correlation id {}, got {received_id}",
+                self.correlation_id
+            );
+        }
+        // Flexible versions answer with response header v1
+        let bytes = if RequestHeader::is_flexible(api_key, api_version) {
+            TagSection::deserialize(bytes)?.1
+        } else {
+            bytes
+        };
+        let (response, _) = T::deserialize(bytes)?;
+        Ok(response)
+    }
+}
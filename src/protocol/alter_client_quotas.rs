@@ -0,0 +1,319 @@
+use std::collections::BTreeMap;
+
+use anyhow::Result;
+
+use super::{
+    api_keys::{RequestContext, RequestHandler},
+    body::ResponseBody,
+    cluster_metadata::{self, ClientQuotaRecord, MetadataRecord},
+    describe_client_quotas::EntityData,
+    error,
+    primitive::{CompactArray, CompactString, Serializable, TagSection},
+    response::Response,
+};
+use crate::{
+    broker::Broker,
+    quota::{self, CLIENT_ID_ENTITY, REQUEST_PERCENTAGE, USER_ENTITY},
+    raft::RaftError,
+    security::acl::{AclOperation, ResourceType, CLUSTER_RESOURCE_NAME},
+    server::ConnectionContext,
+};
+
+#[derive(Debug)]
+pub struct OpData {
+    pub key: CompactString,
+    pub value: f64,
+    /// Whether the quota is removed, in which case `value` is ignored
+    pub remove: bool,
+    pub tag_buffer: TagSection,
+}
+
+impl Serializable for OpData {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(self.key.serialize());
+        buf.extend(self.value.to_be_bytes());
+        buf.push(self.remove as u8);
+        buf.extend(self.tag_buffer.serialize());
+        buf
+    }
+
+    fn deserialize(bytes: &[u8]) -> Result<(Self, &[u8])> {
+        let (key, bytes) = CompactString::deserialize(bytes)?;
+        let (value, bytes) = f64::deserialize(bytes)?;
+        let (remove, bytes) = bool::deserialize(bytes)?;
+        let (tag_buffer, bytes) = TagSection::deserialize(bytes)?;
+        Ok((
+            OpData {
+                key,
+                value,
+                remove,
+                tag_buffer,
+            },
+            bytes,
+        ))
+    }
+}
+
+#[derive(Debug)]
+pub struct EntryData {
+    pub entity: CompactArray<EntityData>,
+    pub ops: CompactArray<OpData>,
+    pub tag_buffer: TagSection,
+}
+
+impl Serializable for EntryData {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(self.entity.serialize());
+        buf.extend(self.ops.serialize());
+        buf.extend(self.tag_buffer.serialize());
+        buf
+    }
+
+    fn deserialize(bytes: &[u8]) -> Result<(Self, &[u8])> {
+        let (entity, bytes) = CompactArray::<EntityData>::deserialize(bytes)?;
+        let (ops, bytes) = CompactArray::<OpData>::deserialize(bytes)?;
+        let (tag_buffer, bytes) = TagSection::deserialize(bytes)?;
+        Ok((
+            EntryData {
+                entity,
+                ops,
+                tag_buffer,
+            },
+            bytes,
+        ))
+    }
+}
+
+impl EntryData {
+    /// Turn the entry into the records applying it, or the reason it is invalid.
+    fn records(&self) -> Result<Vec<MetadataRecord>, String> {
+        let mut entity = BTreeMap::new();
+        for data in self.entity.iter().flatten() {
+            let entity_type = data.entity_type.as_deref().unwrap_or_default();
+            if entity_type != USER_ENTITY && entity_type != CLIENT_ID_ENTITY {
+                return Err(format!("Unhandled client quota entity type: {entity_type}"));
+            }
+            if data.entity_name.as_deref() == Some("") {
+                return Err(format!(
+                    "Empty {entity_type} name, use null for the default"
+                ));
+            }
+            if entity
+                .insert(entity_type.to_string(), data.entity_name.0.clone())
+                .is_some()
+            {
+                return Err(format!("Duplicate {entity_type} entity type"));
+            }
+        }
+        if entity.is_empty() {
+            return Err("Invalid empty client quota entity".to_string());
+        }
+
+        let mut records = vec![];
+        let mut keys = vec![];
+        for op in self.ops.iter().flatten() {
+            let key = op.key.as_deref().unwrap_or_default();
+            if !quota::is_valid_key(key) {
+                return Err(format!("Unknown quota key {key}"));
+            }
+            if keys.contains(&key) {
+                return Err(format!("Duplicate quota key {key}"));
+            }
+            keys.push(key);
+            if !op.remove {
+                if !(op.value.is_finite() && op.value > 0.0) {
+                    return Err(format!("Quota {key} must be positive"));
+                }
+                // Byte rates are whole numbers of bytes per second
+                if key != REQUEST_PERCENTAGE && op.value.fract() != 0.0 {
+                    return Err(format!("Quota {key} must be an integer"));
+                }
+            }
+            records.push(MetadataRecord::ClientQuota(ClientQuotaRecord {
+                entity: entity
+                    .iter()
+                    .map(|(entity_type, entity_name)| cluster_metadata::EntityData {
+                        entity_type: entity_type.clone(),
+                        entity_name: entity_name.clone(),
+                    })
+                    .collect(),
+                key: key.to_string(),
+                value: if op.remove { 0.0 } else { op.value },
+                remove: op.remove,
+            }));
+        }
+        Ok(records)
+    }
+}
+
+#[derive(Debug)]
+pub struct AlterClientQuotasRequest {
+    pub entries: CompactArray<EntryData>,
+    pub validate_only: bool,
+    pub tag_buffer: TagSection,
+}
+
+impl Serializable for AlterClientQuotasRequest {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(self.entries.serialize());
+        buf.push(self.validate_only as u8);
+        buf.extend(self.tag_buffer.serialize());
+        buf
+    }
+
+    fn deserialize(bytes: &[u8]) -> Result<(Self, &[u8])> {
+        let (entries, bytes) = CompactArray::<EntryData>::deserialize(bytes)?;
+        let (validate_only, bytes) = bool::deserialize(bytes)?;
+        let (tag_buffer, bytes) = TagSection::deserialize(bytes)?;
+        Ok((
+            AlterClientQuotasRequest {
+                entries,
+                validate_only,
+                tag_buffer,
+            },
+            bytes,
+        ))
+    }
+}
+
+impl AlterClientQuotasRequest {
+    pub fn handle_request(&self, broker: &Broker, context: &ConnectionContext) -> Option<Response> {
+        let authorized = broker.authorize(
+            context,
+            AclOperation::AlterConfigs,
+            ResourceType::Cluster,
+            CLUSTER_RESOURCE_NAME,
+        );
+
+        let mut outcomes = vec![];
+        let mut records = vec![];
+        for entry in self.entries.iter().flatten() {
+            let outcome = if !authorized {
+                Err((
+                    error::CLUSTER_AUTHORIZATION_FAILED,
+                    "Cluster authorization failed".to_string(),
+                ))
+            } else {
+                entry.records().map_err(|e| (error::INVALID_REQUEST, e))
+            };
+            outcomes.push(match outcome {
+                Ok(entry_records) => {
+                    records.extend(entry_records);
+                    Ok(())
+                }
+                Err(e) => Err(e),
+            });
+        }
+
+        if !self.validate_only {
+            if let Err(e) = broker.commit_metadata(records) {
+                eprintln!("Failed to persist client quotas: {e:#}");
+                for outcome in outcomes.iter_mut().filter(|o| o.is_ok()) {
+                    *outcome = Err((
+                        RaftError::error_code(&e),
+                        "Failed to persist the quota".to_string(),
+                    ));
+                }
+            }
+        }
+
+        let entries = self
+            .entries
+            .iter()
+            .flatten()
+            .zip(outcomes)
+            .map(|(entry, outcome)| {
+                let (error_code, error_message) = match outcome {
+                    Ok(()) => (error::NONE, None),
+                    Err((error_code, message)) => (error_code, Some(message)),
+                };
+                EntryResult {
+                    error_code,
+                    error_message: CompactString(error_message),
+                    entity: CompactArray(Some(
+                        entry
+                            .entity
+                            .iter()
+                            .flatten()
+                            .map(|data| EntityData {
+                                entity_type: data.entity_type.clone(),
+                                entity_name: data.entity_name.clone(),
+                                tag_buffer: TagSection(None),
+                            })
+                            .collect(),
+                    )),
+                    tag_buffer: TagSection(None),
+                }
+            })
+            .collect();
+
+        Some(Box::new(AlterClientQuotasResponse {
+            throttle_time_ms: 0,
+            entries: CompactArray(Some(entries)),
+            tag_buffer: TagSection(None),
+        }))
+    }
+}
+
+impl RequestHandler for AlterClientQuotasRequest {
+    fn read(bytes: &[u8], _version: i16) -> Result<Self> {
+        Ok(AlterClientQuotasRequest::deserialize(bytes)?.0)
+    }
+
+    fn handle(&self, request: &mut RequestContext) -> Option<Response> {
+        self.handle_request(request.broker, request.connection)
+    }
+}
+
+#[derive(Debug)]
+pub struct EntryResult {
+    pub error_code: i16,
+    pub error_message: CompactString,
+    pub entity: CompactArray<EntityData>,
+    pub tag_buffer: TagSection,
+}
+
+impl Serializable for EntryResult {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(self.error_code.to_be_bytes());
+        buf.extend(self.error_message.serialize());
+        buf.extend(self.entity.serialize());
+        buf.extend(self.tag_buffer.serialize());
+        buf
+    }
+
+    fn deserialize(_bytes: &[u8]) -> Result<(Self, &[u8])> {
+        todo!()
+    }
+}
+
+#[derive(Debug)]
+pub struct AlterClientQuotasResponse {
+    pub throttle_time_ms: i32,
+    pub entries: CompactArray<EntryResult>,
+    pub tag_buffer: TagSection,
+}
+
+impl Serializable for AlterClientQuotasResponse {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(self.throttle_time_ms.to_be_bytes());
+        buf.extend(self.entries.serialize());
+        buf.extend(self.tag_buffer.serialize());
+        buf
+    }
+
+    fn deserialize(_bytes: &[u8]) -> Result<(Self, &[u8])> {
+        todo!()
+    }
+}
+
+impl ResponseBody for AlterClientQuotasResponse {
+    fn set_throttle_time_ms(&mut self, throttle_time_ms: i32) {
+        self.throttle_time_ms = throttle_time_ms;
+    }
+}
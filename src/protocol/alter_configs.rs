@@ -0,0 +1,368 @@
+use std::collections::BTreeMap;
+
+use anyhow::Result;
+
+use super::{
+    api_keys::{RequestContext, RequestHandler},
+    body::ResponseBody,
+    cluster_metadata::{ConfigRecord, MetadataRecord},
+    describe_configs::check_resource,
+    error,
+    primitive::{CompactArray, CompactString, Serializable, TagSection},
+    response::Response,
+};
+use crate::{
+    broker::Broker,
+    config_registry::{self, ConfigResource, ConfigResourceType},
+    raft::RaftError,
+    security::acl::AclOperation,
+    server::ConnectionContext,
+};
+
+/// New dynamic value of each altered config, `None` removing it.
+pub type ConfigChanges = BTreeMap<String, Option<String>>;
+
+/// Validate changes to the dynamic configs of a resource and turn them into the records
+/// applying them.
+pub fn config_records(
+    broker: &Broker,
+    resource: &ConfigResource,
+    changes: &ConfigChanges,
+) -> Result<Vec<MetadataRecord>, (i16, String)> {
+    let kind = match resource.resource_type {
+        ConfigResourceType::Topic => "topic",
+        _ => "broker",
+    };
+    let mut read_only = vec![];
+    for (name, value) in changes {
+        let Some(def) = config_registry::find(resource.resource_type, name) else {
+            return Err((
+                error::INVALID_CONFIG,
+                format!("Unknown {kind} config name: {name}"),
+            ));
+        };
+        if !def.dynamic {
+            read_only.push(name.as_str());
+            continue;
+        }
+        if let Some(value) = value {
+            def.validate(value).map_err(|reason| {
+                (
+                    error::INVALID_CONFIG,
+                    format!("Invalid value {value} for configuration {name}: {reason}"),
+                )
+            })?;
+        }
+    }
+    let enables_remote_storage = resource.resource_type == ConfigResourceType::Topic
+        && changes
+            .get("remote.storage.enable")
+            .is_some_and(|value| value.as_deref().map(str::trim) == Some("true"));
+    if enables_remote_storage && broker.config.remote_log.is_none() {
+        return Err((
+            error::INVALID_CONFIG,
+            "Tiered Storage functionality is disabled in the broker. Topic cannot be \
+             configured with remote log storage."
+                .to_string(),
+        ));
+    }
+    if !read_only.is_empty() {
+        return Err((
+            error::INVALID_REQUEST,
+            format!(
+                "Cannot update these configs dynamically: {}",
+                read_only.join(", ")
+            ),
+        ));
+    }
+
+    let metadata = broker.metadata.read().unwrap();
+    Ok(changes
+        .iter()
+        .map(|(name, value)| (name, value.as_deref().map(str::trim)))
+        .filter(|(name, value)| metadata.config(resource, name) != *value)
+        .map(|(name, value)| {
+            MetadataRecord::Config(ConfigRecord {
+                resource_type: resource.resource_type.code(),
+                resource_name: resource.name.clone(),
+                name: name.clone(),
+                value: value.map(str::to_string),
+            })
+        })
+        .collect())
+}
+
+/// Persist the records of every valid resource unless only validating, failing the
+/// resources whose records could not be written.
+pub fn commit_config_records(
+    broker: &Broker,
+    outcomes: &mut [Result<(), (i16, String)>],
+    records: Vec<MetadataRecord>,
+    validate_only: bool,
+) {
+    if validate_only {
+        return;
+    }
+    if let Err(e) = broker.commit_metadata(records) {
+        eprintln!("Failed to persist configs: {e:#}");
+        for outcome in outcomes.iter_mut().filter(|o| o.is_ok()) {
+            *outcome = Err((
+                RaftError::error_code(&e),
+                "Failed to persist the configs".to_string(),
+            ));
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct AlterableConfig {
+    pub name: CompactString,
+    pub value: CompactString,
+    pub tag_buffer: TagSection,
+}
+
+impl Serializable for AlterableConfig {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(self.name.serialize());
+        buf.extend(self.value.serialize());
+        buf.extend(self.tag_buffer.serialize());
+        buf
+    }
+
+    fn deserialize(bytes: &[u8]) -> Result<(Self, &[u8])> {
+        let (name, bytes) = CompactString::deserialize(bytes)?;
+        let (value, bytes) = CompactString::deserialize(bytes)?;
+        let (tag_buffer, bytes) = TagSection::deserialize(bytes)?;
+        Ok((
+            AlterableConfig {
+                name,
+                value,
+                tag_buffer,
+            },
+            bytes,
+        ))
+    }
+}
+
+#[derive(Debug)]
+pub struct AlterConfigsResource {
+    pub resource_type: i8,
+    pub resource_name: CompactString,
+    pub configs: CompactArray<AlterableConfig>,
+    pub tag_buffer: TagSection,
+}
+
+impl Serializable for AlterConfigsResource {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.push(self.resource_type as u8);
+        buf.extend(self.resource_name.serialize());
+        buf.extend(self.configs.serialize());
+        buf.extend(self.tag_buffer.serialize());
+        buf
+    }
+
+    fn deserialize(bytes: &[u8]) -> Result<(Self, &[u8])> {
+        let (resource_type, bytes) = i8::deserialize(bytes)?;
+        let (resource_name, bytes) = CompactString::deserialize(bytes)?;
+        let (configs, bytes) = CompactArray::<AlterableConfig>::deserialize(bytes)?;
+        let (tag_buffer, bytes) = TagSection::deserialize(bytes)?;
+        Ok((
+            AlterConfigsResource {
+                resource_type,
+                resource_name,
+                configs,
+                tag_buffer,
+            },
+            bytes,
+        ))
+    }
+}
+
+impl AlterConfigsResource {
+    fn resource(&self) -> ConfigResource {
+        ConfigResource::new(
+            ConfigResourceType::from_code(self.resource_type),
+            self.resource_name.as_deref().unwrap_or_default(),
+        )
+    }
+
+    /// The requested configs replace every dynamic config of the resource.
+    fn changes(
+        &self,
+        broker: &Broker,
+        resource: &ConfigResource,
+    ) -> Result<ConfigChanges, (i16, String)> {
+        let mut changes = ConfigChanges::new();
+        if let Some(current) = broker.metadata.read().unwrap().configs.get(resource) {
+            for name in current.keys() {
+                changes.insert(name.clone(), None);
+            }
+        }
+        let mut seen = vec![];
+        for config in self.configs.iter().flatten() {
+            let name = config.name.as_deref().unwrap_or_default();
+            if seen.contains(&name) {
+                return Err((error::INVALID_REQUEST, format!("Duplicate config {name}")));
+            }
+            seen.push(name);
+            let Some(value) = config.value.0.clone() else {
+                return Err((
+                    error::INVALID_REQUEST,
+                    format!("Null value not supported for {name}"),
+                ));
+            };
+            changes.insert(name.to_string(), Some(value));
+        }
+        Ok(changes)
+    }
+}
+
+#[derive(Debug)]
+pub struct AlterConfigsRequest {
+    pub resources: CompactArray<AlterConfigsResource>,
+    pub validate_only: bool,
+    pub tag_buffer: TagSection,
+}
+
+impl Serializable for AlterConfigsRequest {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(self.resources.serialize());
+        buf.push(self.validate_only as u8);
+        buf.extend(self.tag_buffer.serialize());
+        buf
+    }
+
+    fn deserialize(bytes: &[u8]) -> Result<(Self, &[u8])> {
+        let (resources, bytes) = CompactArray::<AlterConfigsResource>::deserialize(bytes)?;
+        let (validate_only, bytes) = bool::deserialize(bytes)?;
+        let (tag_buffer, bytes) = TagSection::deserialize(bytes)?;
+        Ok((
+            AlterConfigsRequest {
+                resources,
+                validate_only,
+                tag_buffer,
+            },
+            bytes,
+        ))
+    }
+}
+
+impl AlterConfigsRequest {
+    pub fn handle_request(&self, broker: &Broker, context: &ConnectionContext) -> Option<Response> {
+        let mut outcomes = vec![];
+        let mut records = vec![];
+        for request in self.resources.iter().flatten() {
+            let resource = request.resource();
+            let outcome = check_resource(broker, context, &resource, AclOperation::AlterConfigs)
+                .and_then(|()| request.changes(broker, &resource))
+                .and_then(|changes| config_records(broker, &resource, &changes));
+            outcomes.push(outcome.map(|resource_records| records.extend(resource_records)));
+        }
+        commit_config_records(broker, &mut outcomes, records, self.validate_only);
+
+        let responses = self
+            .resources
+            .iter()
+            .flatten()
+            .zip(outcomes)
+            .map(|(request, outcome)| {
+                AlterConfigsResourceResponse::new(
+                    outcome,
+                    request.resource_type,
+                    &request.resource_name,
+                )
+            })
+            .collect();
+
+        Some(Box::new(AlterConfigsResponse {
+            throttle_time_ms: 0,
+            responses: CompactArray(Some(responses)),
+            tag_buffer: TagSection(None),
+        }))
+    }
+}
+
+impl RequestHandler for AlterConfigsRequest {
+    fn read(bytes: &[u8], _version: i16) -> Result<Self> {
+        Ok(AlterConfigsRequest::deserialize(bytes)?.0)
+    }
+
+    fn handle(&self, request: &mut RequestContext) -> Option<Response> {
+        self.handle_request(request.broker, request.connection)
+    }
+}
+
+#[derive(Debug)]
+pub struct AlterConfigsResourceResponse {
+    pub error_code: i16,
+    pub error_message: CompactString,
+    pub resource_type: i8,
+    pub resource_name: CompactString,
+    pub tag_buffer: TagSection,
+}
+
+impl AlterConfigsResourceResponse {
+    pub fn new(
+        outcome: Result<(), (i16, String)>,
+        resource_type: i8,
+        resource_name: &CompactString,
+    ) -> Self {
+        let (error_code, error_message) = match outcome {
+            Ok(()) => (error::NONE, None),
+            Err((error_code, message)) => (error_code, Some(message)),
+        };
+        AlterConfigsResourceResponse {
+            error_code,
+            error_message: CompactString(error_message),
+            resource_type,
+            resource_name: resource_name.clone(),
+            tag_buffer: TagSection(None),
+        }
+    }
+}
+
+impl Serializable for AlterConfigsResourceResponse {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(self.error_code.to_be_bytes());
+        buf.extend(self.error_message.serialize());
+        buf.push(self.resource_type as u8);
+        buf.extend(self.resource_name.serialize());
+        buf.extend(self.tag_buffer.serialize());
+        buf
+    }
+
+    fn deserialize(_bytes: &[u8]) -> Result<(Self, &[u8])> {
+        todo!()
+    }
+}
+
+#[derive(Debug)]
+pub struct AlterConfigsResponse {
+    pub throttle_time_ms: i32,
+    pub responses: CompactArray<AlterConfigsResourceResponse>,
+    pub tag_buffer: TagSection,
+}
+
+impl Serializable for AlterConfigsResponse {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(self.throttle_time_ms.to_be_bytes());
+        buf.extend(self.responses.serialize());
+        buf.extend(self.tag_buffer.serialize());
+        buf
+    }
+
+    fn deserialize(_bytes: &[u8]) -> Result<(Self, &[u8])> {
+        todo!()
+    }
+}
+
+impl ResponseBody for AlterConfigsResponse {
+    fn set_throttle_time_ms(&mut self, throttle_time_ms: i32) {
+        self.throttle_time_ms = throttle_time_ms;
+    }
+}
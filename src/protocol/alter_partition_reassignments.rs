@@ -0,0 +1,321 @@
+use anyhow::Result;
+
+use super::{
+    api_keys::{RequestContext, RequestHandler},
+    body::ResponseBody,
+    cluster_metadata::MetadataRecord,
+    error,
+    primitive::{CompactArray, CompactString, Serializable, TagSection, UnsignedVarint},
+    response::Response,
+};
+use crate::{
+    broker::Broker,
+    controller,
+    raft::RaftError,
+    security::acl::{AclOperation, ResourceType, CLUSTER_RESOURCE_NAME},
+    server::ConnectionContext,
+};
+
+#[derive(Debug)]
+pub struct ReassignablePartition {
+    pub partition_index: i32,
+    /// Replicas to move the partition to, null to cancel its ongoing reassignment
+    pub replicas: CompactArray<i32>,
+    pub tag_buffer: TagSection,
+}
+
+impl Serializable for ReassignablePartition {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(self.partition_index.to_be_bytes());
+        buf.extend(self.replicas.serialize());
+        buf.extend(self.tag_buffer.serialize());
+        buf
+    }
+
+    fn deserialize(bytes: &[u8]) -> Result<(Self, &[u8])> {
+        let (partition_index, bytes) = i32::deserialize(bytes)?;
+        let (replicas, bytes) = CompactArray::<i32>::deserialize(bytes)?;
+        let (tag_buffer, bytes) = TagSection::deserialize(bytes)?;
+        Ok((
+            ReassignablePartition {
+                partition_index,
+                replicas,
+                tag_buffer,
+            },
+            bytes,
+        ))
+    }
+}
+
+#[derive(Debug)]
+pub struct ReassignableTopic {
+    pub name: CompactString,
+    pub partitions: CompactArray<ReassignablePartition>,
+    pub tag_buffer: TagSection,
+}
+
+impl Serializable for ReassignableTopic {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(self.name.serialize());
+        buf.extend(self.partitions.serialize());
+        buf.extend(self.tag_buffer.serialize());
+        buf
+    }
+
+    fn deserialize(bytes: &[u8]) -> Result<(Self, &[u8])> {
+        let (name, bytes) = CompactString::deserialize(bytes)?;
+        let (partitions, bytes) = CompactArray::<ReassignablePartition>::deserialize(bytes)?;
+        let (tag_buffer, bytes) = TagSection::deserialize(bytes)?;
+        Ok((
+            ReassignableTopic {
+                name,
+                partitions,
+                tag_buffer,
+            },
+            bytes,
+        ))
+    }
+}
+
+/// AlterPartitionReassignments versions 0 and 1. Version 1 lets the client forbid
+/// reassignments changing the replication factor.
+#[derive(Debug)]
+pub struct AlterPartitionReassignmentsRequest {
+    pub timeout_ms: i32,
+    /// Version 1 and above
+    pub allow_replication_factor_change: bool,
+    pub topics: CompactArray<ReassignableTopic>,
+    pub tag_buffer: TagSection,
+}
+
+impl AlterPartitionReassignmentsRequest {
+    pub fn deserialize_versioned(bytes: &[u8], version: i16) -> Result<(Self, &[u8])> {
+        let (timeout_ms, bytes) = i32::deserialize(bytes)?;
+        let (allow_replication_factor_change, bytes) = if version >= 1 {
+            bool::deserialize(bytes)?
+        } else {
+            (true, bytes)
+        };
+        let (topics, bytes) = CompactArray::<ReassignableTopic>::deserialize(bytes)?;
+        let (tag_buffer, bytes) = TagSection::deserialize(bytes)?;
+        Ok((
+            AlterPartitionReassignmentsRequest {
+                timeout_ms,
+                allow_replication_factor_change,
+                topics,
+                tag_buffer,
+            },
+            bytes,
+        ))
+    }
+
+    /// Reassignments are started or cancelled in the metadata log before the response is
+    /// sent; they complete in the background once the new replicas caught up.
+    pub fn handle_request(
+        &self,
+        version: i16,
+        broker: &Broker,
+        context: &ConnectionContext,
+    ) -> Option<Response> {
+        let mut response = AlterPartitionReassignmentsResponse {
+            version,
+            throttle_time_ms: 0,
+            allow_replication_factor_change: self.allow_replication_factor_change,
+            error_code: error::NONE,
+            error_message: None,
+            responses: vec![],
+        };
+
+        let requested: Vec<(String, i32, Option<Vec<i32>>)> = self
+            .topics
+            .0
+            .iter()
+            .flatten()
+            .flat_map(|topic| {
+                let name = topic.name.0.clone().unwrap_or_default();
+                let partitions = topic.partitions.0.iter().flatten();
+                partitions.map(move |p| (name.clone(), p.partition_index, p.replicas.0.clone()))
+            })
+            .collect();
+        let top_level_error = if !broker.authorize(
+            context,
+            AclOperation::Alter,
+            ResourceType::Cluster,
+            CLUSTER_RESOURCE_NAME,
+        ) {
+            Some(error::CLUSTER_AUTHORIZATION_FAILED)
+        } else if !broker.raft.is_leader() {
+            Some(error::NOT_CONTROLLER)
+        } else {
+            None
+        };
+        let outcomes = match top_level_error {
+            Some(error_code) => {
+                response.error_code = error_code;
+                response.error_message = error_message(error_code).map(str::to_string);
+                requested
+                    .into_iter()
+                    .map(|(topic, partition, _)| (topic, partition, error_code))
+                    .collect()
+            }
+            None => self.reassign(broker, requested),
+        };
+
+        for (topic, partition_index, error_code) in outcomes {
+            let result = ReassignablePartitionResponse {
+                partition_index,
+                error_code,
+                error_message: error_message(error_code).map(str::to_string),
+            };
+            match response.responses.iter_mut().find(|r| r.name == topic) {
+                Some(topic_response) => topic_response.partitions.push(result),
+                None => response.responses.push(ReassignableTopicResponse {
+                    name: topic,
+                    partitions: vec![result],
+                }),
+            }
+        }
+
+        Some(Box::new(response))
+    }
+
+    /// Start or cancel the reassignments and commit them, returning the outcome for each
+    /// partition.
+    fn reassign(
+        &self,
+        broker: &Broker,
+        requested: Vec<(String, i32, Option<Vec<i32>>)>,
+    ) -> Vec<(String, i32, i16)> {
+        let mut outcomes = vec![];
+        let committed = broker.append_metadata(|_| {
+            let image = broker.metadata.read().unwrap();
+            let mut records = vec![];
+            for (topic, partition, target) in requested {
+                let error_code = match image.partition(&topic, partition) {
+                    None => error::UNKNOWN_TOPIC_OR_PARTITION,
+                    Some((topic_id, registration)) => match controller::reassign_partition(
+                        &image,
+                        topic_id,
+                        partition,
+                        registration,
+                        target.as_deref(),
+                        self.allow_replication_factor_change,
+                    ) {
+                        Ok(record) => {
+                            match &target {
+                                Some(target) => {
+                                    eprintln!("Reassigning {topic}-{partition} to {target:?}")
+                                }
+                                None => {
+                                    eprintln!("Cancelling the reassignment of {topic}-{partition}")
+                                }
+                            }
+                            records.extend(record.map(MetadataRecord::PartitionChange));
+                            error::NONE
+                        }
+                        Err(error_code) => error_code,
+                    },
+                };
+                outcomes.push((topic, partition, error_code));
+            }
+            records
+        });
+        if let Err(e) = committed {
+            eprintln!("Failed to reassign partitions: {e:#}");
+            let error_code = RaftError::error_code(&e);
+            for outcome in outcomes.iter_mut().filter(|o| o.2 == error::NONE) {
+                outcome.2 = error_code;
+            }
+        }
+        outcomes
+    }
+}
+
+impl RequestHandler for AlterPartitionReassignmentsRequest {
+    fn read(bytes: &[u8], version: i16) -> Result<Self> {
+        Ok(AlterPartitionReassignmentsRequest::deserialize_versioned(bytes, version)?.0)
+    }
+
+    fn handle(&self, request: &mut RequestContext) -> Option<Response> {
+        self.handle_request(request.api_version, request.broker, request.connection)
+    }
+}
+
+fn error_message(error_code: i16) -> Option<&'static str> {
+    match error_code {
+        error::NONE => None,
+        error::UNKNOWN_TOPIC_OR_PARTITION => Some("The partition does not exist"),
+        error::INVALID_REPLICA_ASSIGNMENT => {
+            Some("The replicas are empty, repeated or not registered brokers")
+        }
+        error::INVALID_REPLICATION_FACTOR => {
+            Some("The reassignment would change the replication factor")
+        }
+        error::NO_REASSIGNMENT_IN_PROGRESS => Some("No reassignment of the partition is ongoing"),
+        error::CLUSTER_AUTHORIZATION_FAILED => Some("Not authorized to reassign partitions"),
+        error::NOT_CONTROLLER => Some("This node is not the active controller"),
+        _ => None,
+    }
+}
+
+#[derive(Debug)]
+pub struct ReassignablePartitionResponse {
+    pub partition_index: i32,
+    pub error_code: i16,
+    pub error_message: Option<String>,
+}
+
+#[derive(Debug)]
+pub struct ReassignableTopicResponse {
+    pub name: String,
+    pub partitions: Vec<ReassignablePartitionResponse>,
+}
+
+#[derive(Debug)]
+pub struct AlterPartitionReassignmentsResponse {
+    pub version: i16,
+    pub throttle_time_ms: i32,
+    /// Version 1 and above
+    pub allow_replication_factor_change: bool,
+    pub error_code: i16,
+    pub error_message: Option<String>,
+    pub responses: Vec<ReassignableTopicResponse>,
+}
+
+impl Serializable for AlterPartitionReassignmentsResponse {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(self.throttle_time_ms.to_be_bytes());
+        if self.version >= 1 {
+            buf.extend(self.allow_replication_factor_change.serialize());
+        }
+        buf.extend(self.error_code.to_be_bytes());
+        buf.extend(CompactString(self.error_message.clone()).serialize());
+        buf.extend(UnsignedVarint(self.responses.len() as u32 + 1).serialize());
+        for topic in &self.responses {
+            buf.extend(CompactString(Some(topic.name.clone())).serialize());
+            buf.extend(UnsignedVarint(topic.partitions.len() as u32 + 1).serialize());
+            for partition in &topic.partitions {
+                buf.extend(partition.partition_index.to_be_bytes());
+                buf.extend(partition.error_code.to_be_bytes());
+                buf.extend(CompactString(partition.error_message.clone()).serialize());
+                buf.extend(TagSection(None).serialize());
+            }
+            buf.extend(TagSection(None).serialize());
+        }
+        buf.extend(TagSection(None).serialize());
+        buf
+    }
+
+    fn deserialize(_bytes: &[u8]) -> Result<(Self, &[u8])> {
+        todo!()
+    }
+}
+
+impl ResponseBody for AlterPartitionReassignmentsResponse {
+    fn set_throttle_time_ms(&mut self, throttle_time_ms: i32) {
+        self.throttle_time_ms = throttle_time_ms;
+    }
+}
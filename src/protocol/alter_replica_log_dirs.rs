@@ -0,0 +1,244 @@
+use std::{collections::BTreeMap, path::Path};
+
+use anyhow::Result;
+
+use super::{
+    api_keys::{RequestContext, RequestHandler},
+    body::ResponseBody,
+    error,
+    primitive::{CompactArray, CompactString, Serializable, TagSection, UnsignedVarint},
+    response::Response,
+};
+use crate::{
+    broker::Broker,
+    log_manager::TopicPartition,
+    security::acl::{AclOperation, ResourceType, CLUSTER_RESOURCE_NAME},
+    server::ConnectionContext,
+};
+
+#[derive(Debug)]
+pub struct AlterReplicaLogDirTopic {
+    pub name: CompactString,
+    pub partitions: CompactArray<i32>,
+    pub tag_buffer: TagSection,
+}
+
+impl Serializable for AlterReplicaLogDirTopic {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(self.name.serialize());
+        buf.extend(self.partitions.serialize());
+        buf.extend(self.tag_buffer.serialize());
+        buf
+    }
+
+    fn deserialize(bytes: &[u8]) -> Result<(Self, &[u8])> {
+        let (name, bytes) = CompactString::deserialize(bytes)?;
+        let (partitions, bytes) = CompactArray::<i32>::deserialize(bytes)?;
+        let (tag_buffer, bytes) = TagSection::deserialize(bytes)?;
+        Ok((
+            AlterReplicaLogDirTopic {
+                name,
+                partitions,
+                tag_buffer,
+            },
+            bytes,
+        ))
+    }
+}
+
+#[derive(Debug)]
+pub struct AlterReplicaLogDir {
+    /// Absolute path of the destination log directory
+    pub path: CompactString,
+    pub topics: CompactArray<AlterReplicaLogDirTopic>,
+    pub tag_buffer: TagSection,
+}
+
+impl Serializable for AlterReplicaLogDir {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(self.path.serialize());
+        buf.extend(self.topics.serialize());
+        buf.extend(self.tag_buffer.serialize());
+        buf
+    }
+
+    fn deserialize(bytes: &[u8]) -> Result<(Self, &[u8])> {
+        let (path, bytes) = CompactString::deserialize(bytes)?;
+        let (topics, bytes) = CompactArray::<AlterReplicaLogDirTopic>::deserialize(bytes)?;
+        let (tag_buffer, bytes) = TagSection::deserialize(bytes)?;
+        Ok((
+            AlterReplicaLogDir {
+                path,
+                topics,
+                tag_buffer,
+            },
+            bytes,
+        ))
+    }
+}
+
+#[derive(Debug)]
+pub struct AlterReplicaLogDirsRequest {
+    pub dirs: CompactArray<AlterReplicaLogDir>,
+    pub tag_buffer: TagSection,
+}
+
+impl Serializable for AlterReplicaLogDirsRequest {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(self.dirs.serialize());
+        buf.extend(self.tag_buffer.serialize());
+        buf
+    }
+
+    fn deserialize(bytes: &[u8]) -> Result<(Self, &[u8])> {
+        let (dirs, bytes) = CompactArray::<AlterReplicaLogDir>::deserialize(bytes)?;
+        let (tag_buffer, bytes) = TagSection::deserialize(bytes)?;
+        Ok((AlterReplicaLogDirsRequest { dirs, tag_buffer }, bytes))
+    }
+}
+
+impl AlterReplicaLogDirsRequest {
+    pub fn handle_request(&self, broker: &Broker, context: &ConnectionContext) -> Option<Response> {
+        let authorized = broker.authorize(
+            context,
+            AclOperation::Alter,
+            ResourceType::Cluster,
+            CLUSTER_RESOURCE_NAME,
+        );
+        // Replicas are moved while the broker keeps serving them, one at a time
+        let mut outcomes: BTreeMap<TopicPartition, i16> = BTreeMap::new();
+        for dir in self.dirs.iter().flatten() {
+            let path = Path::new(dir.path.as_deref().unwrap_or_default());
+            for topic in dir.topics.iter().flatten() {
+                let name = topic.name.as_deref().unwrap_or_default();
+                for &partition in topic.partitions.iter().flatten() {
+                    let replica = TopicPartition::new(name, partition);
+                    let error_code = if !authorized {
+                        error::CLUSTER_AUTHORIZATION_FAILED
+                    } else {
+                        match broker.logs.lock().unwrap().move_replica(&replica, path) {
+                            Ok(()) => error::NONE,
+                            Err(e) => {
+                                eprintln!("Cannot move {replica} to {}: {e}", path.display());
+                                e.error_code()
+                            }
+                        }
+                    };
+                    outcomes.insert(replica, error_code);
+                }
+            }
+        }
+        if let Err(e) = broker.sync_replicas() {
+            eprintln!("Failed to record the log directories of moved replicas: {e:#}");
+        }
+
+        let mut results: Vec<AlterReplicaLogDirTopicResult> = vec![];
+        for (replica, error_code) in outcomes {
+            let partition = AlterReplicaLogDirPartitionResult {
+                partition_index: replica.partition,
+                error_code,
+                tag_buffer: TagSection(None),
+            };
+            match results.last_mut() {
+                Some(topic) if topic.topic_name.as_deref() == Some(replica.topic.as_str()) => {
+                    topic.partitions.push(partition)
+                }
+                _ => results.push(AlterReplicaLogDirTopicResult {
+                    topic_name: CompactString(Some(replica.topic)),
+                    partitions: vec![partition],
+                }),
+            }
+        }
+
+        Some(Box::new(AlterReplicaLogDirsResponse {
+            throttle_time_ms: 0,
+            results,
+        }))
+    }
+}
+
+impl RequestHandler for AlterReplicaLogDirsRequest {
+    fn read(bytes: &[u8], _version: i16) -> Result<Self> {
+        Ok(AlterReplicaLogDirsRequest::deserialize(bytes)?.0)
+    }
+
+    fn handle(&self, request: &mut RequestContext) -> Option<Response> {
+        self.handle_request(request.broker, request.connection)
+    }
+}
+
+#[derive(Debug)]
+pub struct AlterReplicaLogDirPartitionResult {
+    pub partition_index: i32,
+    pub error_code: i16,
+    pub tag_buffer: TagSection,
+}
+
+impl Serializable for AlterReplicaLogDirPartitionResult {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(self.partition_index.to_be_bytes());
+        buf.extend(self.error_code.to_be_bytes());
+        buf.extend(self.tag_buffer.serialize());
+        buf
+    }
+
+    fn deserialize(_bytes: &[u8]) -> Result<(Self, &[u8])> {
+        todo!()
+    }
+}
+
+#[derive(Debug)]
+pub struct AlterReplicaLogDirTopicResult {
+    pub topic_name: CompactString,
+    pub partitions: Vec<AlterReplicaLogDirPartitionResult>,
+}
+
+impl Serializable for AlterReplicaLogDirTopicResult {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(self.topic_name.serialize());
+        buf.extend(UnsignedVarint(self.partitions.len() as u32 + 1).serialize());
+        for partition in &self.partitions {
+            buf.extend(partition.serialize());
+        }
+        buf.extend(TagSection(None).serialize());
+        buf
+    }
+
+    fn deserialize(_bytes: &[u8]) -> Result<(Self, &[u8])> {
+        todo!()
+    }
+}
+
+#[derive(Debug)]
+pub struct AlterReplicaLogDirsResponse {
+    pub throttle_time_ms: i32,
+    pub results: Vec<AlterReplicaLogDirTopicResult>,
+}
+
+impl Serializable for AlterReplicaLogDirsResponse {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(self.throttle_time_ms.to_be_bytes());
+        buf.extend(UnsignedVarint(self.results.len() as u32 + 1).serialize());
+        for result in &self.results {
+            buf.extend(result.serialize());
+        }
+        buf.extend(TagSection(None).serialize());
+        buf
+    }
+
+    fn deserialize(_bytes: &[u8]) -> Result<(Self, &[u8])> {
+        todo!()
+    }
+}
+
+impl ResponseBody for AlterReplicaLogDirsResponse {
+    fn set_throttle_time_ms(&mut self, throttle_time_ms: i32) {
+        self.throttle_time_ms = throttle_time_ms;
+    }
+}
@@ -0,0 +1,447 @@
+use std::collections::{BTreeMap, HashSet};
+
+use anyhow::Result;
+
+use super::{
+    api_keys::{RequestContext, RequestHandler},
+    body::ResponseBody,
+    cluster_metadata::{
+        MetadataRecord, RemoveUserScramCredentialRecord, UserScramCredentialRecord,
+    },
+    error,
+    primitive::{CompactArray, CompactBytes, CompactString, Serializable, TagSection},
+    response::Response,
+};
+use crate::{
+    broker::Broker,
+    raft::RaftError,
+    security::{
+        acl::{AclOperation, ResourceType, CLUSTER_RESOURCE_NAME},
+        scram::{ScramCredential, ScramMechanism},
+    },
+    server::ConnectionContext,
+};
+
+#[derive(Debug)]
+pub struct ScramCredentialDeletion {
+    pub name: CompactString,
+    pub mechanism: i8,
+    pub tag_buffer: TagSection,
+}
+
+impl Serializable for ScramCredentialDeletion {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(self.name.serialize());
+        buf.push(self.mechanism as u8);
+        buf.extend(self.tag_buffer.serialize());
+        buf
+    }
+
+    fn deserialize(bytes: &[u8]) -> Result<(Self, &[u8])> {
+        let (name, bytes) = CompactString::deserialize(bytes)?;
+        let (mechanism, bytes) = i8::deserialize(bytes)?;
+        let (tag_buffer, bytes) = TagSection::deserialize(bytes)?;
+        Ok((
+            ScramCredentialDeletion {
+                name,
+                mechanism,
+                tag_buffer,
+            },
+            bytes,
+        ))
+    }
+}
+
+#[derive(Debug)]
+pub struct ScramCredentialUpsertion {
+    pub name: CompactString,
+    pub mechanism: i8,
+    pub iterations: i32,
+    pub salt: CompactBytes,
+    pub salted_password: CompactBytes,
+    pub tag_buffer: TagSection,
+}
+
+impl Serializable for ScramCredentialUpsertion {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(self.name.serialize());
+        buf.push(self.mechanism as u8);
+        buf.extend(self.iterations.to_be_bytes());
+        buf.extend(self.salt.serialize());
+        buf.extend(self.salted_password.serialize());
+        buf.extend(self.tag_buffer.serialize());
+        buf
+    }
+
+    fn deserialize(bytes: &[u8]) -> Result<(Self, &[u8])> {
+        let (name, bytes) = CompactString::deserialize(bytes)?;
+        let (mechanism, bytes) = i8::deserialize(bytes)?;
+        let (iterations, bytes) = i32::deserialize(bytes)?;
+        let (salt, bytes) = CompactBytes::deserialize(bytes)?;
+        let (salted_password, bytes) = CompactBytes::deserialize(bytes)?;
+        let (tag_buffer, bytes) = TagSection::deserialize(bytes)?;
+        Ok((
+            ScramCredentialUpsertion {
+                name,
+                mechanism,
+                iterations,
+                salt,
+                salted_password,
+                tag_buffer,
+            },
+            bytes,
+        ))
+    }
+}
+
+#[derive(Debug)]
+pub struct AlterUserScramCredentialsRequest {
+    pub deletions: CompactArray<ScramCredentialDeletion>,
+    pub upsertions: CompactArray<ScramCredentialUpsertion>,
+    pub tag_buffer: TagSection,
+}
+
+impl Serializable for AlterUserScramCredentialsRequest {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(self.deletions.serialize());
+        buf.extend(self.upsertions.serialize());
+        buf.extend(self.tag_buffer.serialize());
+        buf
+    }
+
+    fn deserialize(bytes: &[u8]) -> Result<(Self, &[u8])> {
+        let (deletions, bytes) = CompactArray::<ScramCredentialDeletion>::deserialize(bytes)?;
+        let (upsertions, bytes) = CompactArray::<ScramCredentialUpsertion>::deserialize(bytes)?;
+        let (tag_buffer, bytes) = TagSection::deserialize(bytes)?;
+        Ok((
+            AlterUserScramCredentialsRequest {
+                deletions,
+                upsertions,
+                tag_buffer,
+            },
+            bytes,
+        ))
+    }
+}
+
+/// A single alteration of the request, once validated.
+enum Alteration {
+    Delete(ScramMechanism),
+    Upsert(ScramMechanism, ScramCredential),
+}
+
+impl AlterUserScramCredentialsRequest {
+    fn validate_deletion(
+        deletion: &ScramCredentialDeletion,
+        broker: &Broker,
+    ) -> Result<Alteration, (i16, &'static str)> {
+        let Some(mechanism) = ScramMechanism::from_type(deletion.mechanism) else {
+            return Err((error::UNSUPPORTED_SASL_MECHANISM, "Unknown SCRAM mechanism"));
+        };
+        let name = deletion.name.as_deref().unwrap_or_default();
+        let metadata = broker.metadata.read().unwrap();
+        if metadata.scram_credential(name, mechanism).is_none() {
+            return Err((
+                error::RESOURCE_NOT_FOUND,
+                "Attempt to delete a user credential that does not exist",
+            ));
+        }
+        Ok(Alteration::Delete(mechanism))
+    }
+
+    fn validate_upsertion(
+        upsertion: &ScramCredentialUpsertion,
+    ) -> Result<Alteration, (i16, &'static str)> {
+        let Some(mechanism) = ScramMechanism::from_type(upsertion.mechanism) else {
+            return Err((error::UNSUPPORTED_SASL_MECHANISM, "Unknown SCRAM mechanism"));
+        };
+        if upsertion.iterations < mechanism.min_iterations() {
+            return Err((error::UNACCEPTABLE_CREDENTIAL, "Too few iterations"));
+        }
+        if upsertion.iterations > mechanism.max_iterations() {
+            return Err((error::UNACCEPTABLE_CREDENTIAL, "Too many iterations"));
+        }
+        let (Some(salt), Some(salted_password)) = (
+            upsertion.salt.as_ref().filter(|s| !s.is_empty()),
+            upsertion.salted_password.as_ref().filter(|p| !p.is_empty()),
+        ) else {
+            return Err((
+                error::UNACCEPTABLE_CREDENTIAL,
+                "Salt and salted password must not be empty",
+            ));
+        };
+        Ok(Alteration::Upsert(
+            mechanism,
+            ScramCredential::from_salted_password(
+                mechanism,
+                salt.clone(),
+                salted_password,
+                upsertion.iterations,
+            ),
+        ))
+    }
+
+    pub fn handle_request(&self, broker: &Broker, context: &ConnectionContext) -> Option<Response> {
+        let authorized = broker.authorize(
+            context,
+            AclOperation::Alter,
+            ResourceType::Cluster,
+            CLUSTER_RESOURCE_NAME,
+        );
+
+        // Validate every alteration, grouped by user in request order
+        let mut users: Vec<String> = vec![];
+        let mut alterations: BTreeMap<String, Result<Vec<Alteration>, (i16, &str)>> =
+            BTreeMap::new();
+        let mut seen: HashSet<(String, i8)> = HashSet::new();
+        let deletions = self.deletions.iter().flatten().map(|d| {
+            (
+                d.name.as_deref().unwrap_or_default(),
+                d.mechanism,
+                Self::validate_deletion(d, broker),
+            )
+        });
+        let upsertions = self.upsertions.iter().flatten().map(|u| {
+            (
+                u.name.as_deref().unwrap_or_default(),
+                u.mechanism,
+                Self::validate_upsertion(u),
+            )
+        });
+        for (name, mechanism, alteration) in deletions.chain(upsertions) {
+            if !users.iter().any(|u| u == name) {
+                users.push(name.to_string());
+            }
+            let alteration = if !authorized {
+                Err((
+                    error::CLUSTER_AUTHORIZATION_FAILED,
+                    "Cluster authorization failed",
+                ))
+            } else if name.is_empty() {
+                Err((error::UNACCEPTABLE_CREDENTIAL, "Username must not be empty"))
+            } else if !seen.insert((name.to_string(), mechanism)) {
+                Err((
+                    error::DUPLICATE_RESOURCE,
+                    "A user credential cannot be altered twice in the same request",
+                ))
+            } else {
+                alteration
+            };
+            let entry = alterations
+                .entry(name.to_string())
+                .or_insert_with(|| Ok(vec![]));
+            // The first error of a user wins and voids their other alterations
+            match (entry.as_mut(), alteration) {
+                (Ok(list), Ok(alteration)) => list.push(alteration),
+                (Ok(_), Err(e)) => *entry = Err(e),
+                (Err(_), _) => {}
+            }
+        }
+
+        let mut records = vec![];
+        for (name, alteration) in &alterations {
+            let Ok(list) = alteration else { continue };
+            for alteration in list {
+                records.push(match alteration {
+                    Alteration::Delete(mechanism) => {
+                        MetadataRecord::RemoveUserScramCredential(RemoveUserScramCredentialRecord {
+                            name: name.clone(),
+                            mechanism: mechanism.type_id(),
+                        })
+                    }
+                    Alteration::Upsert(mechanism, credential) => {
+                        MetadataRecord::UserScramCredential(UserScramCredentialRecord {
+                            name: name.clone(),
+                            mechanism: mechanism.type_id(),
+                            salt: credential.salt.clone(),
+                            stored_key: credential.stored_key.clone(),
+                            server_key: credential.server_key.clone(),
+                            iterations: credential.iterations,
+                        })
+                    }
+                });
+            }
+        }
+        let commit_error = broker.commit_metadata(records).err().map(|e| {
+            eprintln!("Failed to persist SCRAM credentials: {e:#}");
+            (
+                RaftError::error_code(&e),
+                "Failed to persist the credential",
+            )
+        });
+
+        let results = users
+            .into_iter()
+            .map(|user| {
+                let (error_code, message) = match (&alterations[&user], commit_error) {
+                    (Err((code, message)), _) => (*code, Some(message.to_string())),
+                    (Ok(_), Some((code, message))) => (code, Some(message.to_string())),
+                    (Ok(_), None) => (error::NONE, None),
+                };
+                AlterUserScramCredentialsResult {
+                    user: CompactString(Some(user)),
+                    error_code,
+                    error_message: CompactString(message),
+                    tag_buffer: TagSection(None),
+                }
+            })
+            .collect();
+
+        Some(Box::new(AlterUserScramCredentialsResponse {
+            throttle_time_ms: 0,
+            results: CompactArray(Some(results)),
+            tag_buffer: TagSection(None),
+        }))
+    }
+}
+
+impl RequestHandler for AlterUserScramCredentialsRequest {
+    fn read(bytes: &[u8], _version: i16) -> Result<Self> {
+        Ok(AlterUserScramCredentialsRequest::deserialize(bytes)?.0)
+    }
+
+    fn handle(&self, request: &mut RequestContext) -> Option<Response> {
+        self.handle_request(request.broker, request.connection)
+    }
+}
+
+#[derive(Debug)]
+pub struct AlterUserScramCredentialsResult {
+    pub user: CompactString,
+    pub error_code: i16,
+    pub error_message: CompactString,
+    pub tag_buffer: TagSection,
+}
+
+impl Serializable for AlterUserScramCredentialsResult {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(self.user.serialize());
+        buf.extend(self.error_code.to_be_bytes());
+        buf.extend(self.error_message.serialize());
+        buf.extend(self.tag_buffer.serialize());
+        buf
+    }
+
+    fn deserialize(_bytes: &[u8]) -> Result<(Self, &[u8])> {
+        todo!()
+    }
+}
+
+#[derive(Debug)]
+pub struct AlterUserScramCredentialsResponse {
+    pub throttle_time_ms: i32,
+    pub results: CompactArray<AlterUserScramCredentialsResult>,
+    pub tag_buffer: TagSection,
+}
+
+impl Serializable for AlterUserScramCredentialsResponse {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(self.throttle_time_ms.to_be_bytes());
+        buf.extend(self.results.serialize());
+        buf.extend(self.tag_buffer.serialize());
+        buf
+    }
+
+    fn deserialize(_bytes: &[u8]) -> Result<(Self, &[u8])> {
+        todo!()
+    }
+}
+
+impl ResponseBody for AlterUserScramCredentialsResponse {
+    fn set_throttle_time_ms(&mut self, throttle_time_ms: i32) {
+        self.throttle_time_ms = throttle_time_ms;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_deletions_then_upsertions() {
+        let body = [
+            &[2, 6][..],
+            b"alice",
+            &[2, 0],
+            &[2, 4],
+            b"bob",
+            // SCRAM-SHA-256 with 8192 iterations, a salt and a salted password
+            &[1, 0, 0, 0x20, 0, 3, 1, 2, 3, 4, 5, 0],
+            &[0],
+        ]
+        .concat();
+        let (request, rest) = AlterUserScramCredentialsRequest::deserialize(&body).unwrap();
+        assert!(rest.is_empty());
+        let deletions = request.deletions.0.as_ref().unwrap();
+        assert_eq!(deletions[0].name.as_deref(), Some("alice"));
+        assert_eq!(deletions[0].mechanism, 2);
+        let upsertion = &request.upsertions.0.as_ref().unwrap()[0];
+        assert_eq!(upsertion.name.as_deref(), Some("bob"));
+        assert_eq!(upsertion.mechanism, 1);
+        assert_eq!(upsertion.iterations, 8192);
+        assert_eq!(upsertion.salt.0.as_deref(), Some(&[1, 2][..]));
+        assert_eq!(upsertion.salted_password.0.as_deref(), Some(&[4, 5][..]));
+        assert_eq!(request.serialize(), body);
+    }
+
+    #[test]
+    fn validates_upsertions() {
+        let upsertion = |mechanism, iterations, salt: &[u8]| ScramCredentialUpsertion {
+            name: CompactString(Some("bob".to_string())),
+            mechanism,
+            iterations,
+            salt: CompactBytes(Some(salt.to_vec())),
+            salted_password: CompactBytes(Some(vec![4, 5])),
+            tag_buffer: TagSection(None),
+        };
+        let error_code = |upsertion: &ScramCredentialUpsertion| {
+            AlterUserScramCredentialsRequest::validate_upsertion(upsertion)
+                .err()
+                .map(|(error_code, _)| error_code)
+        };
+        assert_eq!(error_code(&upsertion(1, 8192, &[1, 2])), None);
+        assert_eq!(
+            error_code(&upsertion(3, 8192, &[1, 2])),
+            Some(error::UNSUPPORTED_SASL_MECHANISM)
+        );
+        for iterations in [1, i32::MAX] {
+            assert_eq!(
+                error_code(&upsertion(2, iterations, &[1, 2])),
+                Some(error::UNACCEPTABLE_CREDENTIAL)
+            );
+        }
+        assert_eq!(
+            error_code(&upsertion(1, 8192, &[])),
+            Some(error::UNACCEPTABLE_CREDENTIAL)
+        );
+    }
+
+    #[test]
+    fn writes_a_result_per_user() {
+        let response = AlterUserScramCredentialsResponse {
+            throttle_time_ms: 7,
+            results: CompactArray(Some(vec![AlterUserScramCredentialsResult {
+                user: CompactString(Some("bob".to_string())),
+                error_code: error::UNACCEPTABLE_CREDENTIAL,
+                error_message: CompactString(Some("Too few iterations".to_string())),
+                tag_buffer: TagSection(None),
+            }])),
+            tag_buffer: TagSection(None),
+        };
+        let expected = [
+            &[0, 0, 0, 7, 2, 4][..],
+            b"bob",
+            &error::UNACCEPTABLE_CREDENTIAL.to_be_bytes(),
+            &[19],
+            b"Too few iterations",
+            &[0, 0],
+        ]
+        .concat();
+        assert_eq!(response.serialize(), expected);
+    }
+}
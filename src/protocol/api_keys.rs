@@ -0,0 +1,510 @@
+use anyhow::Result;
+
+use super::{
+    alter_client_quotas::AlterClientQuotasRequest, alter_configs::AlterConfigsRequest,
+    alter_partition_reassignments::AlterPartitionReassignmentsRequest,
+    alter_replica_log_dirs::AlterReplicaLogDirsRequest,
+    alter_user_scram_credentials::AlterUserScramCredentialsRequest,
+    api_version::ApiVersionsRequest, begin_quorum_epoch::BeginQuorumEpochRequest,
+    broker_heartbeat::BrokerHeartbeatRequest, broker_registration::BrokerRegistrationRequest,
+    create_acls::CreateAclsRequest, create_partitions::CreatePartitionsRequest,
+    create_topics::CreateTopicsRequest, delete_acls::DeleteAclsRequest,
+    delete_records::DeleteRecordsRequest, describe_acls::DescribeAclsRequest,
+    describe_client_quotas::DescribeClientQuotasRequest, describe_cluster::DescribeClusterRequest,
+    describe_configs::DescribeConfigsRequest, describe_log_dirs::DescribeLogDirsRequest,
+    describe_quorum::DescribeQuorumRequest,
+    describe_topic_partitions::DescribeTopicPartitionsRequest,
+    describe_user_scram_credentials::DescribeUserScramCredentialsRequest,
+    elect_leaders::ElectLeadersRequest, end_quorum_epoch::EndQuorumEpochRequest,
+    fetch::FetchRequest, fetch_snapshot::FetchSnapshotRequest,
+    find_coordinator::FindCoordinatorRequest,
+    incremental_alter_configs::IncrementalAlterConfigsRequest,
+    list_partition_reassignments::ListPartitionReassignmentsRequest, metadata::MetadataRequest,
+    offset_for_leader_epoch::OffsetForLeaderEpochRequest, produce::ProduceRequest,
+    response::Response, sasl_authenticate::SaslAuthenticateRequest,
+    sasl_handshake::SaslHandshakeRequest, share_acknowledge::ShareAcknowledgeRequest,
+    share_fetch::ShareFetchRequest, share_group_heartbeat::ShareGroupHeartbeatRequest,
+    unregister_broker::UnregisterBrokerRequest, vote::VoteRequest,
+};
+use crate::{broker::Broker, security::sasl::SaslSession, server::ConnectionContext};
+
+pub const METADATA_KEY: i16 = 3;
+pub const FIND_COORDINATOR_KEY: i16 = 10;
+/// ApiVersions, which answers even versions the broker does not implement.
+pub const API_VERSIONS_KEY: i16 = 18;
+pub const SASL_AUTHENTICATE_KEY: i16 = 36;
+pub const ELECT_LEADERS_KEY: i16 = 43;
+
+/// What a request is handled with: the broker, and the connection it came in on along
+/// with its SASL session.
+pub struct RequestContext<'a> {
+    pub api_version: i16,
+    pub client_id: &'a str,
+    /// How long a byte-rate quota the handler recorded the request against throttles
+    /// the client for, in milliseconds
+    pub throttle_time_ms: i32,
+    pub broker: &'a Broker,
+    pub connection: &'a mut ConnectionContext,
+    pub sasl: &'a mut Option<SaslSession>,
+}
+
+/// A request of one API, read from the body of a request frame and then handled.
+pub trait RequestHandler: Sized {
+    /// Read the request body, in the version the client sent it in.
+    fn read(bytes: &[u8], version: i16) -> Result<Self>;
+
+    /// The response to send back, none for requests that expect no answer.
+    fn handle(&self, request: &mut RequestContext) -> Option<Response>;
+}
+
+/// Read and handle a request body of the API served by `T`.
+fn handle<T: RequestHandler>(
+    bytes: &[u8],
+    request: &mut RequestContext,
+) -> Result<Option<Response>> {
+    Ok(T::read(bytes, request.api_version)?.handle(request))
+}
+
+/// An API the broker serves, with the range of versions it implements and the handler
+/// of its requests.
+pub struct ApiKey {
+    pub key: i16,
+    pub name: &'static str,
+    pub min_version: i16,
+    pub max_version: i16,
+    /// First version using the flexible encoding, none if no version does
+    pub flexible_version: Option<i16>,
+    /// Whether requests are served on a SASL connection before it has authenticated
+    pub before_authentication: bool,
+    /// Whether requests are exempt from request quotas, as those needed to connect are
+    pub quota_exempt: bool,
+    pub handler: fn(&[u8], &mut RequestContext) -> Result<Option<Response>>,
+}
+
+impl ApiKey {
+    pub fn supports(&self, version: i16) -> bool {
+        (self.min_version..=self.max_version).contains(&version)
+    }
+
+    /// Whether requests and responses of the version use the flexible encoding.
+    pub fn is_flexible(&self, version: i16) -> bool {
+        self.flexible_version.is_some_and(|first| version >= first)
+    }
+
+    /// Version of the header of responses to the version. ApiVersions answers in
+    /// version 0 whatever the request, so that any client can read the versions to
+    /// fall back to.
+    pub fn response_header_version(&self, version: i16) -> i16 {
+        if self.key != API_VERSIONS_KEY && self.is_flexible(version) {
+            1
+        } else {
+            0
+        }
+    }
+}
+
+/// Every API the broker serves, by key. Dispatching, ApiVersions and the header
+/// versions of requests all follow it.
+pub const API_KEYS: &[ApiKey] = &[
+    ApiKey {
+        key: 0,
+        name: "Produce",
+        min_version: 9,
+        max_version: 9,
+        flexible_version: Some(9),
+        before_authentication: false,
+        quota_exempt: false,
+        handler: handle::<ProduceRequest>,
+    },
+    ApiKey {
+        key: 1,
+        name: "Fetch",
+        min_version: 12,
+        max_version: 12,
+        flexible_version: Some(12),
+        before_authentication: false,
+        quota_exempt: false,
+        handler: handle::<FetchRequest>,
+    },
+    ApiKey {
+        key: METADATA_KEY,
+        name: "Metadata",
+        min_version: 4,
+        max_version: 12,
+        flexible_version: Some(9),
+        before_authentication: false,
+        quota_exempt: false,
+        handler: handle::<MetadataRequest>,
+    },
+    ApiKey {
+        key: FIND_COORDINATOR_KEY,
+        name: "FindCoordinator",
+        min_version: 0,
+        max_version: 6,
+        flexible_version: Some(3),
+        before_authentication: false,
+        quota_exempt: false,
+        handler: handle::<FindCoordinatorRequest>,
+    },
+    ApiKey {
+        key: 17,
+        name: "SaslHandshake",
+        min_version: 1,
+        max_version: 1,
+        flexible_version: None,
+        before_authentication: true,
+        quota_exempt: true,
+        handler: handle::<SaslHandshakeRequest>,
+    },
+    ApiKey {
+        key: API_VERSIONS_KEY,
+        name: "ApiVersions",
+        min_version: 0,
+        max_version: 4,
+        flexible_version: Some(3),
+        before_authentication: true,
+        quota_exempt: true,
+        handler: handle::<ApiVersionsRequest>,
+    },
+    ApiKey {
+        key: 19,
+        name: "CreateTopics",
+        min_version: 5,
+        max_version: 7,
+        flexible_version: Some(5),
+        before_authentication: false,
+        quota_exempt: false,
+        handler: handle::<CreateTopicsRequest>,
+    },
+    ApiKey {
+        key: 21,
+        name: "DeleteRecords",
+        min_version: 2,
+        max_version: 2,
+        flexible_version: Some(2),
+        before_authentication: false,
+        quota_exempt: false,
+        handler: handle::<DeleteRecordsRequest>,
+    },
+    ApiKey {
+        key: 23,
+        name: "OffsetForLeaderEpoch",
+        min_version: 4,
+        max_version: 4,
+        flexible_version: Some(4),
+        before_authentication: false,
+        quota_exempt: false,
+        handler: handle::<OffsetForLeaderEpochRequest>,
+    },
+    ApiKey {
+        key: 29,
+        name: "DescribeAcls",
+        min_version: 2,
+        max_version: 3,
+        flexible_version: Some(2),
+        before_authentication: false,
+        quota_exempt: false,
+        handler: handle::<DescribeAclsRequest>,
+    },
+    ApiKey {
+        key: 30,
+        name: "CreateAcls",
+        min_version: 2,
+        max_version: 3,
+        flexible_version: Some(2),
+        before_authentication: false,
+        quota_exempt: false,
+        handler: handle::<CreateAclsRequest>,
+    },
+    ApiKey {
+        key: 31,
+        name: "DeleteAcls",
+        min_version: 2,
+        max_version: 3,
+        flexible_version: Some(2),
+        before_authentication: false,
+        quota_exempt: false,
+        handler: handle::<DeleteAclsRequest>,
+    },
+    ApiKey {
+        key: 32,
+        name: "DescribeConfigs",
+        min_version: 4,
+        max_version: 4,
+        flexible_version: Some(4),
+        before_authentication: false,
+        quota_exempt: false,
+        handler: handle::<DescribeConfigsRequest>,
+    },
+    ApiKey {
+        key: 33,
+        name: "AlterConfigs",
+        min_version: 2,
+        max_version: 2,
+        flexible_version: Some(2),
+        before_authentication: false,
+        quota_exempt: false,
+        handler: handle::<AlterConfigsRequest>,
+    },
+    ApiKey {
+        key: 34,
+        name: "AlterReplicaLogDirs",
+        min_version: 2,
+        max_version: 2,
+        flexible_version: Some(2),
+        before_authentication: false,
+        quota_exempt: false,
+        handler: handle::<AlterReplicaLogDirsRequest>,
+    },
+    ApiKey {
+        key: 35,
+        name: "DescribeLogDirs",
+        min_version: 2,
+        max_version: 4,
+        flexible_version: Some(2),
+        before_authentication: false,
+        quota_exempt: false,
+        handler: handle::<DescribeLogDirsRequest>,
+    },
+    ApiKey {
+        key: SASL_AUTHENTICATE_KEY,
+        name: "SaslAuthenticate",
+        min_version: 0,
+        max_version: 2,
+        flexible_version: Some(2),
+        before_authentication: true,
+        quota_exempt: true,
+        handler: handle::<SaslAuthenticateRequest>,
+    },
+    ApiKey {
+        key: 37,
+        name: "CreatePartitions",
+        min_version: 2,
+        max_version: 3,
+        flexible_version: Some(2),
+        before_authentication: false,
+        quota_exempt: false,
+        handler: handle::<CreatePartitionsRequest>,
+    },
+    ApiKey {
+        key: ELECT_LEADERS_KEY,
+        name: "ElectLeaders",
+        min_version: 0,
+        max_version: 2,
+        flexible_version: Some(2),
+        before_authentication: false,
+        quota_exempt: false,
+        handler: handle::<ElectLeadersRequest>,
+    },
+    ApiKey {
+        key: 44,
+        name: "IncrementalAlterConfigs",
+        min_version: 1,
+        max_version: 1,
+        flexible_version: Some(1),
+        before_authentication: false,
+        quota_exempt: false,
+        handler: handle::<IncrementalAlterConfigsRequest>,
+    },
+    ApiKey {
+        key: 45,
+        name: "AlterPartitionReassignments",
+        min_version: 0,
+        max_version: 1,
+        flexible_version: Some(0),
+        before_authentication: false,
+        quota_exempt: false,
+        handler: handle::<AlterPartitionReassignmentsRequest>,
+    },
+    ApiKey {
+        key: 46,
+        name: "ListPartitionReassignments",
+        min_version: 0,
+        max_version: 0,
+        flexible_version: Some(0),
+        before_authentication: false,
+        quota_exempt: false,
+        handler: handle::<ListPartitionReassignmentsRequest>,
+    },
+    ApiKey {
+        key: 48,
+        name: "DescribeClientQuotas",
+        min_version: 1,
+        max_version: 1,
+        flexible_version: Some(1),
+        before_authentication: false,
+        quota_exempt: false,
+        handler: handle::<DescribeClientQuotasRequest>,
+    },
+    ApiKey {
+        key: 49,
+        name: "AlterClientQuotas",
+        min_version: 1,
+        max_version: 1,
+        flexible_version: Some(1),
+        before_authentication: false,
+        quota_exempt: false,
+        handler: handle::<AlterClientQuotasRequest>,
+    },
+    ApiKey {
+        key: 50,
+        name: "DescribeUserScramCredentials",
+        min_version: 0,
+        max_version: 0,
+        flexible_version: Some(0),
+        before_authentication: false,
+        quota_exempt: false,
+        handler: handle::<DescribeUserScramCredentialsRequest>,
+    },
+    ApiKey {
+        key: 51,
+        name: "AlterUserScramCredentials",
+        min_version: 0,
+        max_version: 0,
+        flexible_version: Some(0),
+        before_authentication: false,
+        quota_exempt: false,
+        handler: handle::<AlterUserScramCredentialsRequest>,
+    },
+    ApiKey {
+        key: 52,
+        name: "Vote",
+        min_version: 0,
+        max_version: 0,
+        flexible_version: Some(0),
+        before_authentication: false,
+        quota_exempt: false,
+        handler: handle::<VoteRequest>,
+    },
+    ApiKey {
+        key: 53,
+        name: "BeginQuorumEpoch",
+        min_version: 0,
+        max_version: 0,
+        flexible_version: Some(1),
+        before_authentication: false,
+        quota_exempt: false,
+        handler: handle::<BeginQuorumEpochRequest>,
+    },
+    ApiKey {
+        key: 54,
+        name: "EndQuorumEpoch",
+        min_version: 0,
+        max_version: 0,
+        flexible_version: Some(1),
+        before_authentication: false,
+        quota_exempt: false,
+        handler: handle::<EndQuorumEpochRequest>,
+    },
+    ApiKey {
+        key: 55,
+        name: "DescribeQuorum",
+        min_version: 0,
+        max_version: 1,
+        flexible_version: Some(0),
+        before_authentication: false,
+        quota_exempt: false,
+        handler: handle::<DescribeQuorumRequest>,
+    },
+    ApiKey {
+        key: 59,
+        name: "FetchSnapshot",
+        min_version: 0,
+        max_version: 0,
+        flexible_version: Some(0),
+        before_authentication: false,
+        quota_exempt: false,
+        handler: handle::<FetchSnapshotRequest>,
+    },
+    ApiKey {
+        key: 60,
+        name: "DescribeCluster",
+        min_version: 0,
+        max_version: 2,
+        flexible_version: Some(0),
+        before_authentication: false,
+        quota_exempt: false,
+        handler: handle::<DescribeClusterRequest>,
+    },
+    ApiKey {
+        key: 62,
+        name: "BrokerRegistration",
+        min_version: 0,
+        max_version: 3,
+        flexible_version: Some(0),
+        before_authentication: false,
+        quota_exempt: false,
+        handler: handle::<BrokerRegistrationRequest>,
+    },
+    ApiKey {
+        key: 63,
+        name: "BrokerHeartbeat",
+        min_version: 0,
+        max_version: 1,
+        flexible_version: Some(0),
+        before_authentication: false,
+        quota_exempt: false,
+        handler: handle::<BrokerHeartbeatRequest>,
+    },
+    ApiKey {
+        key: 64,
+        name: "UnregisterBroker",
+        min_version: 0,
+        max_version: 0,
+        flexible_version: Some(0),
+        before_authentication: false,
+        quota_exempt: false,
+        handler: handle::<UnregisterBrokerRequest>,
+    },
+    ApiKey {
+        key: 75,
+        name: "DescribeTopicPartitions",
+        min_version: 0,
+        max_version: 0,
+        flexible_version: Some(0),
+        before_authentication: false,
+        quota_exempt: false,
+        handler: handle::<DescribeTopicPartitionsRequest>,
+    },
+    ApiKey {
+        key: 76,
+        name: "ShareGroupHeartbeat",
+        min_version: 0,
+        max_version: 1,
+        flexible_version: Some(0),
+        before_authentication: false,
+        quota_exempt: false,
+        handler: handle::<ShareGroupHeartbeatRequest>,
+    },
+    ApiKey {
+        key: 78,
+        name: "ShareFetch",
+        min_version: 0,
+        max_version: 1,
+        flexible_version: Some(0),
+        before_authentication: false,
+        quota_exempt: false,
+        handler: handle::<ShareFetchRequest>,
+    },
+    ApiKey {
+        key: 79,
+        name: "ShareAcknowledge",
+        min_version: 0,
+        max_version: 1,
+        flexible_version: Some(0),
+        before_authentication: false,
+        quota_exempt: false,
+        handler: handle::<ShareAcknowledgeRequest>,
+    },
+];
+
+/// The API of key `key`, if the broker serves it.
+pub fn api_key(key: i16) -> Option<&'static ApiKey> {
+    API_KEYS.iter().find(|api| api.key == key)
+}
+
+/// Whether requests and responses of the version of API `key` use the flexible encoding,
+/// for bodies whose layout changes with it.
+pub fn is_flexible(key: i16, version: i16) -> bool {
+    api_key(key).is_some_and(|api| api.is_flexible(version))
+}
@@ -1,96 +1,363 @@
-use crate::protocol::{
-    body::ResponseBody,
-    header::{ResponseHeader, ResponseHeaderV0},
-    response::Response,
-};
+use anyhow::Result;
 
 use super::{
-    header::RequestHeader,
-    primitive::{Serializable, TagSection, UnsignedVarint},
+    api_keys::{self, ApiKey, RequestContext, RequestHandler, API_KEYS, API_VERSIONS_KEY},
+    body::ResponseBody,
+    cluster_metadata::BrokerFeature,
+    error,
+    primitive::{CompactArray, CompactString, Serializable, TagField, TagSection, UnsignedVarint},
+    response::Response,
 };
+use crate::{broker::Broker, metadata};
 
-pub struct ApiVersionsRequest;
+/// ApiVersions versions 0 to 4, listing the APIs and features the broker supports.
+/// Version 3 and above use the flexible encoding and name the client software.
+#[derive(Debug, Default)]
+pub struct ApiVersionsRequest {
+    /// Version 3 and above
+    pub client_software_name: Option<String>,
+    /// Version 3 and above
+    pub client_software_version: Option<String>,
+    pub tag_buffer: TagSection,
+}
 
 impl ApiVersionsRequest {
-    const SUPPORTED_API: [ApiVersion; 2] = [
-        ApiVersion {
-            api_key: 18,
-            min_version: 0,
-            max_version: 4,
-            tag_buffer: TagSection(None),
-        },
-        ApiVersion {
-            api_key: 75,
-            min_version: 0,
-            max_version: 0,
-            tag_buffer: TagSection(None),
-        },
-    ];
-
-    pub fn handle_request(correlation_id: i32, request_header: RequestHeader) -> Option<Response> {
-        let response_header = ResponseHeader::V0(ResponseHeaderV0 { correlation_id });
-        let (error_code, api_keys): (i16, &[ApiVersion]) = match request_header.request_api_version
-        {
-            4 => (0, &Self::SUPPORTED_API),
-            _ => (35, &[]),
+    /// The body of a version the broker does not know is left unread, as it is answered
+    /// with the versions to retry with anyway.
+    pub fn deserialize_versioned(bytes: &[u8], version: i16) -> Result<(Self, &[u8])> {
+        if version < 3 || !Self::supports(version) {
+            return Ok((ApiVersionsRequest::default(), bytes));
+        }
+        let (client_software_name, bytes) = CompactString::deserialize(bytes)?;
+        let (client_software_version, bytes) = CompactString::deserialize(bytes)?;
+        let (tag_buffer, bytes) = TagSection::deserialize(bytes)?;
+        Ok((
+            ApiVersionsRequest {
+                client_software_name: client_software_name.0,
+                client_software_version: client_software_version.0,
+                tag_buffer,
+            },
+            bytes,
+        ))
+    }
+
+    fn supports(version: i16) -> bool {
+        api_keys::api_key(API_VERSIONS_KEY).is_some_and(|api| api.supports(version))
+    }
+
+    /// Whether the client software name and version are both set and, as Apache Kafka
+    /// requires, made of letters, digits, '-' and '.', starting and ending with a letter
+    /// or a digit.
+    pub fn has_valid_client_software(&self) -> bool {
+        let is_valid = |value: &Option<String>| {
+            let Some(value) = value else {
+                return false;
+            };
+            let alphanumeric = |c: char| c.is_ascii_alphanumeric();
+            value.starts_with(alphanumeric)
+                && value.ends_with(alphanumeric)
+                && value
+                    .chars()
+                    .all(|c| alphanumeric(c) || c == '-' || c == '.')
         };
+        is_valid(&self.client_software_name) && is_valid(&self.client_software_version)
+    }
 
-        let response_body = ResponseBody::ApiVersions(ApiVersionsResponse {
-            error_code,
-            api_keys,
+    pub fn handle_request(&self, version: i16, broker: &Broker) -> Option<Response> {
+        let mut response = ApiVersionsResponse {
+            version,
+            error_code: error::NONE,
+            api_keys: vec![],
             throttle_time_ms: 0,
-            tag_buffer: TagSection(None),
-        });
-        Some(Response {
-            header: response_header,
-            body: response_body,
-        })
+            supported_features: vec![],
+            finalized_features_epoch: -1,
+            finalized_features: vec![],
+        };
+        if !Self::supports(version) {
+            // Answered in version 0, which every client reads, with the versions of
+            // ApiVersions it may retry with
+            response.version = 0;
+            response.error_code = error::UNSUPPORTED_VERSION;
+            response.api_keys = API_KEYS
+                .iter()
+                .filter(|api| api.key == API_VERSIONS_KEY)
+                .map(ApiVersion::from)
+                .collect();
+        } else if version >= 3 && !self.has_valid_client_software() {
+            response.error_code = error::INVALID_REQUEST;
+        } else {
+            response.api_keys = API_KEYS.iter().map(ApiVersion::from).collect();
+            response.supported_features = metadata::supported_features();
+            let image = broker.metadata.read().unwrap();
+            if !image.features.is_empty() {
+                response.finalized_features_epoch = image.offset;
+                response.finalized_features = image
+                    .features
+                    .iter()
+                    .map(|(name, &level)| FinalizedFeature {
+                        name: name.clone(),
+                        level,
+                    })
+                    .collect();
+            }
+        }
+
+        Some(Box::new(response))
     }
 }
 
-#[derive(Debug)]
-pub struct ApiVersionsResponse {
-    pub error_code: i16,
-    pub api_keys: &'static [ApiVersion],
-    pub throttle_time_ms: i32,
-    pub tag_buffer: TagSection,
+impl RequestHandler for ApiVersionsRequest {
+    fn read(bytes: &[u8], version: i16) -> Result<Self> {
+        Ok(ApiVersionsRequest::deserialize_versioned(bytes, version)?.0)
+    }
+
+    fn handle(&self, request: &mut RequestContext) -> Option<Response> {
+        if self.has_valid_client_software() {
+            request.connection.client_software_name = self.client_software_name.clone();
+            request.connection.client_software_version = self.client_software_version.clone();
+        }
+        self.handle_request(request.api_version, request.broker)
+    }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct ApiVersion {
     pub api_key: i16,
     pub min_version: i16,
     pub max_version: i16,
-    pub tag_buffer: TagSection,
 }
 
+impl From<&ApiKey> for ApiVersion {
+    fn from(api: &ApiKey) -> Self {
+        ApiVersion {
+            api_key: api.key,
+            min_version: api.min_version,
+            max_version: api.max_version,
+        }
+    }
+}
+
+/// Entry of the API array, followed by tagged fields in flexible versions.
 impl Serializable for ApiVersion {
     fn serialize(&self) -> Vec<u8> {
         let mut buf = Vec::new();
         buf.extend(self.api_key.to_be_bytes());
         buf.extend(self.min_version.to_be_bytes());
         buf.extend(self.max_version.to_be_bytes());
-        buf.extend(self.tag_buffer.serialize());
         buf
     }
-    fn deserialize(bytes: &[u8]) -> anyhow::Result<(Self, &[u8])> {
+
+    fn deserialize(_bytes: &[u8]) -> Result<(Self, &[u8])> {
         todo!()
     }
 }
 
+/// Feature level the cluster runs at, reported as both the minimum and maximum level.
+#[derive(Debug, Clone)]
+pub struct FinalizedFeature {
+    pub name: String,
+    pub level: i16,
+}
+
+impl Serializable for FinalizedFeature {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(CompactString(Some(self.name.clone())).serialize());
+        buf.extend(self.level.to_be_bytes());
+        buf.extend(self.level.to_be_bytes());
+        buf.extend(TagSection(None).serialize());
+        buf
+    }
+
+    fn deserialize(_bytes: &[u8]) -> Result<(Self, &[u8])> {
+        todo!()
+    }
+}
+
+#[derive(Debug)]
+pub struct ApiVersionsResponse {
+    pub version: i16,
+    pub error_code: i16,
+    pub api_keys: Vec<ApiVersion>,
+    /// Version 1 and above
+    pub throttle_time_ms: i32,
+    /// Tagged field 0, version 3 and above
+    pub supported_features: Vec<BrokerFeature>,
+    /// Tagged field 1, version 3 and above; -1 when no feature is finalized
+    pub finalized_features_epoch: i64,
+    /// Tagged field 2, version 3 and above
+    pub finalized_features: Vec<FinalizedFeature>,
+}
+
+impl ApiVersionsResponse {
+    const SUPPORTED_FEATURES_TAG: u32 = 0;
+    const FINALIZED_FEATURES_EPOCH_TAG: u32 = 1;
+    const FINALIZED_FEATURES_TAG: u32 = 2;
+
+    /// Tagged fields holding the features, those at their default value left out.
+    fn tags(&self) -> TagSection {
+        let mut fields = vec![];
+        if !self.supported_features.is_empty() {
+            fields.push(TagField {
+                tag: Self::SUPPORTED_FEATURES_TAG,
+                data: CompactArray(Some(self.supported_features.clone())).serialize(),
+            });
+        }
+        if self.finalized_features_epoch != -1 {
+            fields.push(TagField {
+                tag: Self::FINALIZED_FEATURES_EPOCH_TAG,
+                data: self.finalized_features_epoch.to_be_bytes().to_vec(),
+            });
+        }
+        if !self.finalized_features.is_empty() {
+            fields.push(TagField {
+                tag: Self::FINALIZED_FEATURES_TAG,
+                data: CompactArray(Some(self.finalized_features.clone())).serialize(),
+            });
+        }
+        TagSection(Some(fields))
+    }
+}
+
 impl Serializable for ApiVersionsResponse {
     fn serialize(&self) -> Vec<u8> {
+        let flexible = api_keys::is_flexible(API_VERSIONS_KEY, self.version);
         let mut buf = Vec::new();
         buf.extend(self.error_code.to_be_bytes());
-        buf.extend(UnsignedVarint(self.api_keys.len() as u32 + 1).serialize());
-        for api_key in self.api_keys {
+        if flexible {
+            buf.extend(UnsignedVarint(self.api_keys.len() as u32 + 1).serialize());
+        } else {
+            buf.extend((self.api_keys.len() as i32).to_be_bytes());
+        }
+        for api_key in &self.api_keys {
             buf.extend(api_key.serialize());
+            if flexible {
+                buf.extend(TagSection(None).serialize());
+            }
+        }
+        if self.version >= 1 {
+            buf.extend(self.throttle_time_ms.to_be_bytes());
+        }
+        if flexible {
+            buf.extend(self.tags().serialize());
         }
-        buf.extend(self.throttle_time_ms.to_be_bytes());
-        buf.extend(self.tag_buffer.serialize());
         buf
     }
-    fn deserialize(bytes: &[u8]) -> anyhow::Result<(Self, &[u8])> {
+
+    fn deserialize(_bytes: &[u8]) -> Result<(Self, &[u8])> {
         todo!()
     }
 }
+
+impl ResponseBody for ApiVersionsResponse {
+    fn set_throttle_time_ms(&mut self, throttle_time_ms: i32) {
+        self.throttle_time_ms = throttle_time_ms;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn response(version: i16) -> ApiVersionsResponse {
+        ApiVersionsResponse {
+            version,
+            error_code: error::NONE,
+            api_keys: vec![ApiVersion {
+                api_key: API_VERSIONS_KEY,
+                min_version: 0,
+                max_version: 4,
+            }],
+            throttle_time_ms: 5,
+            supported_features: vec![BrokerFeature {
+                name: "metadata.version".to_string(),
+                min_supported_version: 7,
+                max_supported_version: 21,
+            }],
+            finalized_features_epoch: 20,
+            finalized_features: vec![FinalizedFeature {
+                name: "metadata.version".to_string(),
+                level: 20,
+            }],
+        }
+    }
+
+    #[test]
+    fn writes_the_api_array_and_throttle_time_of_versions_0_to_2() {
+        let api = [0, 18, 0, 0, 0, 4];
+        let v0 = [&[0, 0, 0, 0, 0, 1][..], &api].concat();
+        assert_eq!(response(0).serialize(), v0);
+        for version in [1, 2] {
+            assert_eq!(
+                response(version).serialize(),
+                [&v0[..], &[0, 0, 0, 5]].concat()
+            );
+        }
+    }
+
+    #[test]
+    fn writes_compact_arrays_and_feature_tags_from_version_3() {
+        let feature = [&[0x11][..], b"metadata.version"].concat();
+        let expected = [
+            &[0, 0, 2][..],
+            &[0, 18, 0, 0, 0, 4, 0],
+            &[0, 0, 0, 5],
+            // Supported features, finalized features epoch and finalized features
+            &[3, 0, 23, 2],
+            &feature,
+            &[0, 7, 0, 21, 0],
+            &[1, 8, 0, 0, 0, 0, 0, 0, 0, 20],
+            &[2, 23, 2],
+            &feature,
+            &[0, 20, 0, 20, 0],
+        ]
+        .concat();
+        for version in [3, 4] {
+            assert_eq!(response(version).serialize(), expected);
+        }
+    }
+
+    #[test]
+    fn leaves_out_features_at_their_default() {
+        let mut response = response(3);
+        response.supported_features.clear();
+        response.finalized_features_epoch = -1;
+        response.finalized_features.clear();
+        assert_eq!(
+            response.serialize(),
+            [0, 0, 2, 0, 18, 0, 0, 0, 4, 0, 0, 0, 0, 5, 0]
+        );
+    }
+
+    #[test]
+    fn reads_the_client_software_from_version_3() {
+        let body = [&[7][..], b"kcat-1", &[6], b"1.7.0", &[0]].concat();
+        let (request, rest) = ApiVersionsRequest::deserialize_versioned(&body, 3).unwrap();
+        assert_eq!(request.client_software_name.as_deref(), Some("kcat-1"));
+        assert_eq!(request.client_software_version.as_deref(), Some("1.7.0"));
+        assert!(rest.is_empty());
+        assert!(request.has_valid_client_software());
+
+        // Older versions have no body, and unknown ones are left unread
+        for version in [0, 2, 5] {
+            let (request, rest) =
+                ApiVersionsRequest::deserialize_versioned(&body, version).unwrap();
+            assert_eq!(request.client_software_name, None);
+            assert_eq!(rest, body);
+        }
+    }
+
+    #[test]
+    fn validates_the_client_software() {
+        let request = |name: &str| ApiVersionsRequest {
+            client_software_name: Some(name.to_string()),
+            client_software_version: Some("1.0".to_string()),
+            tag_buffer: TagSection(None),
+        };
+        assert!(request("apache-kafka.java").has_valid_client_software());
+        for name in ["", "-kafka", "kafka.", "kafka client", "kafka_client"] {
+            assert!(!request(name).has_valid_client_software(), "{name:?}");
+        }
+        assert!(!ApiVersionsRequest::default().has_valid_client_software());
+    }
+}
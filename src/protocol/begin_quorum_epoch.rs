@@ -0,0 +1,270 @@
+use anyhow::Result;
+
+use super::{
+    api_keys::{RequestContext, RequestHandler},
+    body::ResponseBody,
+    error,
+    primitive::{KafkaArray, KafkaString, Serializable},
+    response::Response,
+};
+use crate::{
+    broker::Broker,
+    raft::METADATA_TOPIC,
+    security::acl::{AclOperation, ResourceType, CLUSTER_RESOURCE_NAME},
+    server::ConnectionContext,
+};
+
+#[derive(Debug)]
+pub struct BeginQuorumEpochPartition {
+    pub partition_index: i32,
+    pub leader_id: i32,
+    pub leader_epoch: i32,
+}
+
+impl Serializable for BeginQuorumEpochPartition {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(self.partition_index.to_be_bytes());
+        buf.extend(self.leader_id.to_be_bytes());
+        buf.extend(self.leader_epoch.to_be_bytes());
+        buf
+    }
+
+    fn deserialize(bytes: &[u8]) -> Result<(Self, &[u8])> {
+        let (partition_index, bytes) = i32::deserialize(bytes)?;
+        let (leader_id, bytes) = i32::deserialize(bytes)?;
+        let (leader_epoch, bytes) = i32::deserialize(bytes)?;
+        Ok((
+            BeginQuorumEpochPartition {
+                partition_index,
+                leader_id,
+                leader_epoch,
+            },
+            bytes,
+        ))
+    }
+}
+
+#[derive(Debug)]
+pub struct BeginQuorumEpochTopic {
+    pub topic_name: KafkaString,
+    pub partitions: KafkaArray<BeginQuorumEpochPartition>,
+}
+
+impl Serializable for BeginQuorumEpochTopic {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(self.topic_name.serialize());
+        buf.extend(self.partitions.serialize());
+        buf
+    }
+
+    fn deserialize(bytes: &[u8]) -> Result<(Self, &[u8])> {
+        let (topic_name, bytes) = KafkaString::deserialize(bytes)?;
+        let (partitions, bytes) = KafkaArray::<BeginQuorumEpochPartition>::deserialize(bytes)?;
+        Ok((
+            BeginQuorumEpochTopic {
+                topic_name,
+                partitions,
+            },
+            bytes,
+        ))
+    }
+}
+
+/// BeginQuorumEpoch version 0, sent by a newly elected leader to the other voters.
+#[derive(Debug)]
+pub struct BeginQuorumEpochRequest {
+    pub cluster_id: KafkaString,
+    pub topics: KafkaArray<BeginQuorumEpochTopic>,
+}
+
+impl Serializable for BeginQuorumEpochRequest {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(self.cluster_id.serialize());
+        buf.extend(self.topics.serialize());
+        buf
+    }
+
+    fn deserialize(bytes: &[u8]) -> Result<(Self, &[u8])> {
+        let (cluster_id, bytes) = KafkaString::deserialize(bytes)?;
+        let (topics, bytes) = KafkaArray::<BeginQuorumEpochTopic>::deserialize(bytes)?;
+        Ok((BeginQuorumEpochRequest { cluster_id, topics }, bytes))
+    }
+}
+
+impl BeginQuorumEpochRequest {
+    pub fn handle_request(&self, broker: &Broker, context: &ConnectionContext) -> Option<Response> {
+        let mut response = BeginQuorumEpochResponse {
+            error_code: error::NONE,
+            topics: KafkaArray(Some(vec![])),
+        };
+
+        if !broker.authorize(
+            context,
+            AclOperation::ClusterAction,
+            ResourceType::Cluster,
+            CLUSTER_RESOURCE_NAME,
+        ) {
+            response.error_code = error::CLUSTER_AUTHORIZATION_FAILED;
+        } else if self
+            .cluster_id
+            .as_deref()
+            .is_some_and(|id| id != broker.cluster_id)
+        {
+            response.error_code = error::INCONSISTENT_CLUSTER_ID;
+        } else {
+            let topics = self.topics.iter().flatten().map(|topic| {
+                let is_metadata = topic.topic_name.as_deref() == Some(METADATA_TOPIC);
+                let partitions = topic.partitions.iter().flatten().map(|partition| {
+                    if is_metadata && partition.partition_index == 0 {
+                        broker
+                            .raft
+                            .handle_begin_quorum_epoch(partition.leader_id, partition.leader_epoch)
+                            .into_response(partition.partition_index)
+                    } else {
+                        QuorumEpochPartitionResponse::error(
+                            partition.partition_index,
+                            error::UNKNOWN_TOPIC_OR_PARTITION,
+                        )
+                    }
+                });
+                QuorumEpochTopicResponse {
+                    topic_name: topic.topic_name.clone(),
+                    partitions: KafkaArray(Some(partitions.collect())),
+                }
+            });
+            response.topics = KafkaArray(Some(topics.collect()));
+        }
+
+        Some(Box::new(response))
+    }
+}
+
+impl RequestHandler for BeginQuorumEpochRequest {
+    fn read(bytes: &[u8], _version: i16) -> Result<Self> {
+        Ok(BeginQuorumEpochRequest::deserialize(bytes)?.0)
+    }
+
+    fn handle(&self, request: &mut RequestContext) -> Option<Response> {
+        self.handle_request(request.broker, request.connection)
+    }
+}
+
+/// Outcome of BeginQuorumEpoch or EndQuorumEpoch for the metadata partition, with the
+/// leader the receiver knows of.
+#[derive(Debug)]
+pub struct QuorumEpochOutcome {
+    pub error_code: i16,
+    pub leader_id: i32,
+    pub leader_epoch: i32,
+}
+
+impl QuorumEpochOutcome {
+    pub fn into_response(self, partition_index: i32) -> QuorumEpochPartitionResponse {
+        QuorumEpochPartitionResponse {
+            partition_index,
+            error_code: self.error_code,
+            leader_id: self.leader_id,
+            leader_epoch: self.leader_epoch,
+        }
+    }
+}
+
+/// Partition result shared by BeginQuorumEpoch and EndQuorumEpoch responses.
+#[derive(Debug)]
+pub struct QuorumEpochPartitionResponse {
+    pub partition_index: i32,
+    pub error_code: i16,
+    pub leader_id: i32,
+    pub leader_epoch: i32,
+}
+
+impl QuorumEpochPartitionResponse {
+    pub fn error(partition_index: i32, error_code: i16) -> Self {
+        QuorumEpochPartitionResponse {
+            partition_index,
+            error_code,
+            leader_id: -1,
+            leader_epoch: -1,
+        }
+    }
+}
+
+impl Serializable for QuorumEpochPartitionResponse {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(self.partition_index.to_be_bytes());
+        buf.extend(self.error_code.to_be_bytes());
+        buf.extend(self.leader_id.to_be_bytes());
+        buf.extend(self.leader_epoch.to_be_bytes());
+        buf
+    }
+
+    fn deserialize(bytes: &[u8]) -> Result<(Self, &[u8])> {
+        let (partition_index, bytes) = i32::deserialize(bytes)?;
+        let (error_code, bytes) = i16::deserialize(bytes)?;
+        let (leader_id, bytes) = i32::deserialize(bytes)?;
+        let (leader_epoch, bytes) = i32::deserialize(bytes)?;
+        Ok((
+            QuorumEpochPartitionResponse {
+                partition_index,
+                error_code,
+                leader_id,
+                leader_epoch,
+            },
+            bytes,
+        ))
+    }
+}
+
+#[derive(Debug)]
+pub struct QuorumEpochTopicResponse {
+    pub topic_name: KafkaString,
+    pub partitions: KafkaArray<QuorumEpochPartitionResponse>,
+}
+
+impl Serializable for QuorumEpochTopicResponse {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(self.topic_name.serialize());
+        buf.extend(self.partitions.serialize());
+        buf
+    }
+
+    fn deserialize(bytes: &[u8]) -> Result<(Self, &[u8])> {
+        let (topic_name, bytes) = KafkaString::deserialize(bytes)?;
+        let (partitions, bytes) = KafkaArray::<QuorumEpochPartitionResponse>::deserialize(bytes)?;
+        Ok((
+            QuorumEpochTopicResponse {
+                topic_name,
+                partitions,
+            },
+            bytes,
+        ))
+    }
+}
+
+#[derive(Debug)]
+pub struct BeginQuorumEpochResponse {
+    pub error_code: i16,
+    pub topics: KafkaArray<QuorumEpochTopicResponse>,
+}
+
+impl Serializable for BeginQuorumEpochResponse {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(self.error_code.to_be_bytes());
+        buf.extend(self.topics.serialize());
+        buf
+    }
+
+    fn deserialize(bytes: &[u8]) -> Result<(Self, &[u8])> {
+        let (error_code, bytes) = i16::deserialize(bytes)?;
+        let (topics, bytes) = KafkaArray::<QuorumEpochTopicResponse>::deserialize(bytes)?;
+        Ok((BeginQuorumEpochResponse { error_code, topics }, bytes))
+    }
+}
+
+impl ResponseBody for BeginQuorumEpochResponse {}
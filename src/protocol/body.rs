@@ -1,19 +1,10 @@
-use super::{
-    api_version::ApiVersionsResponse, describe_topic_partitions::DescribeTopicPartitionsResponse,
-    primitive::Serializable,
-};
+use std::fmt::Debug;
 
-#[derive(Debug)]
-pub enum ResponseBody {
-    ApiVersions(ApiVersionsResponse),
-    DescribeTopicPartitions(DescribeTopicPartitionsResponse),
-}
+use super::primitive::Serializable;
 
-impl ResponseBody {
-    pub fn serialize(&self) -> Vec<u8> {
-        match self {
-            ResponseBody::ApiVersions(payload) => payload.serialize(),
-            ResponseBody::DescribeTopicPartitions(payload) => payload.serialize(),
-        }
-    }
+/// Body of a response to a request. The server writes the response header before it,
+/// in the version the API and the version of the request call for.
+pub trait ResponseBody: Serializable + Debug {
+    /// Report quota throttling to the client, for responses that carry a throttle time.
+    fn set_throttle_time_ms(&mut self, _throttle_time_ms: i32) {}
 }
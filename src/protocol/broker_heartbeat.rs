@@ -0,0 +1,176 @@
+use anyhow::Result;
+use uuid::Uuid;
+
+use super::{
+    api_keys::{RequestContext, RequestHandler},
+    body::ResponseBody,
+    error,
+    primitive::{CompactArray, Serializable, TagField, TagSection},
+    response::Response,
+};
+use crate::{
+    broker::Broker,
+    controller,
+    security::acl::{AclOperation, ResourceType, CLUSTER_RESOURCE_NAME},
+    server::ConnectionContext,
+};
+
+/// BrokerHeartbeat versions 0 and 1, sent by a registered broker to the active
+/// controller to keep its session alive. Version 1 adds the offline log directories.
+#[derive(Debug)]
+pub struct BrokerHeartbeatRequest {
+    pub version: i16,
+    pub broker_id: i32,
+    pub broker_epoch: i64,
+    /// Offset up to which the broker applied the metadata log
+    pub current_metadata_offset: i64,
+    pub want_fence: bool,
+    pub want_shut_down: bool,
+    /// Version 1 and above, as a tagged field
+    pub offline_log_dirs: Vec<Uuid>,
+}
+
+impl BrokerHeartbeatRequest {
+    /// Latest version, which brokers heartbeat with
+    pub const LATEST_VERSION: i16 = 1;
+
+    const OFFLINE_LOG_DIRS_TAG: u32 = 0;
+
+    pub fn deserialize_versioned(bytes: &[u8], version: i16) -> Result<(Self, &[u8])> {
+        let (broker_id, bytes) = i32::deserialize(bytes)?;
+        let (broker_epoch, bytes) = i64::deserialize(bytes)?;
+        let (current_metadata_offset, bytes) = i64::deserialize(bytes)?;
+        let (want_fence, bytes) = bool::deserialize(bytes)?;
+        let (want_shut_down, bytes) = bool::deserialize(bytes)?;
+        let (tag_buffer, bytes) = TagSection::deserialize(bytes)?;
+        let offline_log_dirs = match tag_buffer.field(Self::OFFLINE_LOG_DIRS_TAG) {
+            Some(data) if version >= 1 => CompactArray::<Uuid>::deserialize(data)?.0 .0,
+            _ => None,
+        };
+        Ok((
+            BrokerHeartbeatRequest {
+                version,
+                broker_id,
+                broker_epoch,
+                current_metadata_offset,
+                want_fence,
+                want_shut_down,
+                offline_log_dirs: offline_log_dirs.unwrap_or_default(),
+            },
+            bytes,
+        ))
+    }
+
+    pub fn handle_request(&self, broker: &Broker, context: &ConnectionContext) -> Option<Response> {
+        let mut response = BrokerHeartbeatResponse {
+            throttle_time_ms: 0,
+            error_code: error::NONE,
+            is_caught_up: false,
+            is_fenced: true,
+            should_shut_down: false,
+        };
+
+        if !broker.authorize(
+            context,
+            AclOperation::ClusterAction,
+            ResourceType::Cluster,
+            CLUSTER_RESOURCE_NAME,
+        ) {
+            response.error_code = error::CLUSTER_AUTHORIZATION_FAILED;
+        } else {
+            match controller::process_heartbeat(broker, self) {
+                Ok(state) => {
+                    response.is_caught_up = state.is_caught_up;
+                    response.is_fenced = state.is_fenced;
+                    response.should_shut_down = state.should_shut_down;
+                }
+                Err(error_code) => response.error_code = error_code,
+            }
+        }
+
+        Some(Box::new(response))
+    }
+}
+
+impl RequestHandler for BrokerHeartbeatRequest {
+    fn read(bytes: &[u8], version: i16) -> Result<Self> {
+        Ok(BrokerHeartbeatRequest::deserialize_versioned(bytes, version)?.0)
+    }
+
+    fn handle(&self, request: &mut RequestContext) -> Option<Response> {
+        self.handle_request(request.broker, request.connection)
+    }
+}
+
+impl Serializable for BrokerHeartbeatRequest {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(self.broker_id.to_be_bytes());
+        buf.extend(self.broker_epoch.to_be_bytes());
+        buf.extend(self.current_metadata_offset.to_be_bytes());
+        buf.extend(self.want_fence.serialize());
+        buf.extend(self.want_shut_down.serialize());
+        let mut fields = vec![];
+        if self.version >= 1 && !self.offline_log_dirs.is_empty() {
+            fields.push(TagField {
+                tag: Self::OFFLINE_LOG_DIRS_TAG,
+                data: CompactArray(Some(self.offline_log_dirs.clone())).serialize(),
+            });
+        }
+        buf.extend(TagSection(Some(fields)).serialize());
+        buf
+    }
+
+    fn deserialize(bytes: &[u8]) -> Result<(Self, &[u8])> {
+        Self::deserialize_versioned(bytes, Self::LATEST_VERSION)
+    }
+}
+
+#[derive(Debug)]
+pub struct BrokerHeartbeatResponse {
+    pub throttle_time_ms: i32,
+    pub error_code: i16,
+    /// Whether the broker applied the metadata log past its registration
+    pub is_caught_up: bool,
+    pub is_fenced: bool,
+    /// Whether the broker moved its leaderships away and may exit
+    pub should_shut_down: bool,
+}
+
+impl Serializable for BrokerHeartbeatResponse {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(self.throttle_time_ms.to_be_bytes());
+        buf.extend(self.error_code.to_be_bytes());
+        buf.extend(self.is_caught_up.serialize());
+        buf.extend(self.is_fenced.serialize());
+        buf.extend(self.should_shut_down.serialize());
+        buf.extend(TagSection(None).serialize());
+        buf
+    }
+
+    fn deserialize(bytes: &[u8]) -> Result<(Self, &[u8])> {
+        let (throttle_time_ms, bytes) = i32::deserialize(bytes)?;
+        let (error_code, bytes) = i16::deserialize(bytes)?;
+        let (is_caught_up, bytes) = bool::deserialize(bytes)?;
+        let (is_fenced, bytes) = bool::deserialize(bytes)?;
+        let (should_shut_down, bytes) = bool::deserialize(bytes)?;
+        let (_tag_buffer, bytes) = TagSection::deserialize(bytes)?;
+        Ok((
+            BrokerHeartbeatResponse {
+                throttle_time_ms,
+                error_code,
+                is_caught_up,
+                is_fenced,
+                should_shut_down,
+            },
+            bytes,
+        ))
+    }
+}
+
+impl ResponseBody for BrokerHeartbeatResponse {
+    fn set_throttle_time_ms(&mut self, throttle_time_ms: i32) {
+        self.throttle_time_ms = throttle_time_ms;
+    }
+}
@@ -0,0 +1,184 @@
+use anyhow::Result;
+use uuid::Uuid;
+
+use super::{
+    api_keys::{RequestContext, RequestHandler},
+    body::ResponseBody,
+    cluster_metadata::{BrokerEndpoint, BrokerFeature},
+    error,
+    primitive::{CompactArray, CompactString, Serializable, TagSection},
+    response::Response,
+};
+use crate::{
+    broker::Broker,
+    controller,
+    security::acl::{AclOperation, ResourceType, CLUSTER_RESOURCE_NAME},
+    server::ConnectionContext,
+};
+
+/// BrokerRegistration versions 0 to 3, sent by a broker to the active controller when it
+/// starts. Version 1 adds `is_migrating_zk_broker`, version 2 the log directories and
+/// version 3 the epoch of the previous registration.
+#[derive(Debug)]
+pub struct BrokerRegistrationRequest {
+    pub version: i16,
+    pub broker_id: i32,
+    pub cluster_id: String,
+    /// Identifies this run of the broker
+    pub incarnation_id: Uuid,
+    pub listeners: Vec<BrokerEndpoint>,
+    pub features: Vec<BrokerFeature>,
+    pub rack: Option<String>,
+    pub is_migrating_zk_broker: bool,
+    pub log_dirs: Vec<Uuid>,
+    /// Epoch of the registration the broker shut down cleanly with, -1 if none
+    pub previous_broker_epoch: i64,
+}
+
+impl BrokerRegistrationRequest {
+    /// Latest version, which brokers register with
+    pub const LATEST_VERSION: i16 = 3;
+
+    pub fn deserialize_versioned(bytes: &[u8], version: i16) -> Result<(Self, &[u8])> {
+        let (broker_id, bytes) = i32::deserialize(bytes)?;
+        let (cluster_id, bytes) = CompactString::deserialize(bytes)?;
+        let (incarnation_id, bytes) = Uuid::deserialize(bytes)?;
+        let (listeners, bytes) = CompactArray::<BrokerEndpoint>::deserialize(bytes)?;
+        let (features, bytes) = CompactArray::<BrokerFeature>::deserialize(bytes)?;
+        let (rack, bytes) = CompactString::deserialize(bytes)?;
+        let (is_migrating_zk_broker, bytes) = if version >= 1 {
+            bool::deserialize(bytes)?
+        } else {
+            (false, bytes)
+        };
+        let (log_dirs, bytes) = if version >= 2 {
+            CompactArray::<Uuid>::deserialize(bytes)?
+        } else {
+            (CompactArray(None), bytes)
+        };
+        let (previous_broker_epoch, bytes) = if version >= 3 {
+            i64::deserialize(bytes)?
+        } else {
+            (-1, bytes)
+        };
+        let (_tag_buffer, bytes) = TagSection::deserialize(bytes)?;
+        Ok((
+            BrokerRegistrationRequest {
+                version,
+                broker_id,
+                cluster_id: cluster_id.0.unwrap_or_default(),
+                incarnation_id,
+                listeners: listeners.0.unwrap_or_default(),
+                features: features.0.unwrap_or_default(),
+                rack: rack.0,
+                is_migrating_zk_broker,
+                log_dirs: log_dirs.0.unwrap_or_default(),
+                previous_broker_epoch,
+            },
+            bytes,
+        ))
+    }
+
+    pub fn handle_request(&self, broker: &Broker, context: &ConnectionContext) -> Option<Response> {
+        let mut response = BrokerRegistrationResponse {
+            throttle_time_ms: 0,
+            error_code: error::NONE,
+            broker_epoch: -1,
+        };
+
+        if !broker.authorize(
+            context,
+            AclOperation::ClusterAction,
+            ResourceType::Cluster,
+            CLUSTER_RESOURCE_NAME,
+        ) {
+            response.error_code = error::CLUSTER_AUTHORIZATION_FAILED;
+        } else if self.cluster_id != broker.cluster_id {
+            response.error_code = error::INCONSISTENT_CLUSTER_ID;
+        } else {
+            match controller::register_broker(broker, self) {
+                Ok(broker_epoch) => response.broker_epoch = broker_epoch,
+                Err(error_code) => response.error_code = error_code,
+            }
+        }
+
+        Some(Box::new(response))
+    }
+}
+
+impl RequestHandler for BrokerRegistrationRequest {
+    fn read(bytes: &[u8], version: i16) -> Result<Self> {
+        Ok(BrokerRegistrationRequest::deserialize_versioned(bytes, version)?.0)
+    }
+
+    fn handle(&self, request: &mut RequestContext) -> Option<Response> {
+        self.handle_request(request.broker, request.connection)
+    }
+}
+
+impl Serializable for BrokerRegistrationRequest {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(self.broker_id.to_be_bytes());
+        buf.extend(CompactString(Some(self.cluster_id.clone())).serialize());
+        buf.extend(self.incarnation_id.as_bytes());
+        buf.extend(CompactArray(Some(self.listeners.clone())).serialize());
+        buf.extend(CompactArray(Some(self.features.clone())).serialize());
+        buf.extend(CompactString(self.rack.clone()).serialize());
+        if self.version >= 1 {
+            buf.push(self.is_migrating_zk_broker as u8);
+        }
+        if self.version >= 2 {
+            buf.extend(CompactArray(Some(self.log_dirs.clone())).serialize());
+        }
+        if self.version >= 3 {
+            buf.extend(self.previous_broker_epoch.to_be_bytes());
+        }
+        buf.extend(TagSection(None).serialize());
+        buf
+    }
+
+    fn deserialize(bytes: &[u8]) -> Result<(Self, &[u8])> {
+        Self::deserialize_versioned(bytes, Self::LATEST_VERSION)
+    }
+}
+
+#[derive(Debug)]
+pub struct BrokerRegistrationResponse {
+    pub throttle_time_ms: i32,
+    pub error_code: i16,
+    /// Epoch of the new registration, -1 on error
+    pub broker_epoch: i64,
+}
+
+impl Serializable for BrokerRegistrationResponse {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(self.throttle_time_ms.to_be_bytes());
+        buf.extend(self.error_code.to_be_bytes());
+        buf.extend(self.broker_epoch.to_be_bytes());
+        buf.extend(TagSection(None).serialize());
+        buf
+    }
+
+    fn deserialize(bytes: &[u8]) -> Result<(Self, &[u8])> {
+        let (throttle_time_ms, bytes) = i32::deserialize(bytes)?;
+        let (error_code, bytes) = i16::deserialize(bytes)?;
+        let (broker_epoch, bytes) = i64::deserialize(bytes)?;
+        let (_tag_buffer, bytes) = TagSection::deserialize(bytes)?;
+        Ok((
+            BrokerRegistrationResponse {
+                throttle_time_ms,
+                error_code,
+                broker_epoch,
+            },
+            bytes,
+        ))
+    }
+}
+
+impl ResponseBody for BrokerRegistrationResponse {
+    fn set_throttle_time_ms(&mut self, throttle_time_ms: i32) {
+        self.throttle_time_ms = throttle_time_ms;
+    }
+}
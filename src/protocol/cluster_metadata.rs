@@ -1,93 +1,1348 @@
-use std::io::Read;
-
-use anyhow::{bail, Ok};
+use anyhow::{bail, Ok, Result};
 use uuid::Uuid;
 
-use super::primitive::{CompactString, Serializable, TagSection, UnsignedVarint, Varint};
+use super::primitive::{
+    take, CompactArray, CompactBytes, CompactString, Serializable, TagField, TagSection,
+    UnsignedVarint, Varint, Varlong,
+};
 
-#[derive(Debug)]
-struct RecordBatch {
-    partition_leader_epoch: i32,
-    magic_byte: i8,
-    crc: i32,
-    attributes: i16,
-    last_offset_delta: i32,
-    base_timestamp: i64,
-    max_timestamp: i64,
-    producer_id: i64,
-    producer_epoch: i16,
-    base_sequence: i32,
-    records_length: i32,
+/// CRC-32C (Castagnoli) as used by record batches.
+pub fn crc32c(data: &[u8]) -> u32 {
+    const POLY: u32 = 0x82F6_3B78;
+    let mut crc = !0u32;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ POLY
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct RecordBatch {
+    pub base_offset: i64,
+    pub partition_leader_epoch: i32,
+    pub magic_byte: i8,
+    pub crc: u32,
+    pub attributes: i16,
+    pub last_offset_delta: i32,
+    pub base_timestamp: i64,
+    pub max_timestamp: i64,
+    pub producer_id: i64,
+    pub producer_epoch: i16,
+    pub base_sequence: i32,
+    pub records: Vec<Record>,
+}
+
+impl RecordBatch {
+    /// Size of the fields preceding the batch length plus the length itself.
+    pub const LOG_OVERHEAD: usize = 12;
+    /// Size of a v2 batch up to and including the record count.
+    pub const HEADER_SIZE: usize = 61;
+    const CONTROL_FLAG: i16 = 0x20;
+
+    /// Build a batch of data records with consecutive offsets starting at `base_offset`.
+    pub fn new(base_offset: i64, leader_epoch: i32, timestamp: i64, values: Vec<Vec<u8>>) -> Self {
+        let records: Vec<Record> = values
+            .into_iter()
+            .enumerate()
+            .map(|(i, value)| Record {
+                offset_delta: i as i32,
+                value: Some(value),
+                ..Default::default()
+            })
+            .collect();
+        RecordBatch {
+            base_offset,
+            partition_leader_epoch: leader_epoch,
+            magic_byte: 2,
+            last_offset_delta: records.len().saturating_sub(1) as i32,
+            base_timestamp: timestamp,
+            max_timestamp: timestamp,
+            producer_id: -1,
+            producer_epoch: -1,
+            base_sequence: -1,
+            records,
+            ..Default::default()
+        }
+    }
+
+    /// Build a control batch holding one record, such as the LeaderChange record that
+    /// opens each epoch of the metadata log.
+    pub fn control(
+        base_offset: i64,
+        leader_epoch: i32,
+        timestamp: i64,
+        key: ControlRecordKey,
+        value: Vec<u8>,
+    ) -> Self {
+        let mut batch = RecordBatch::new(base_offset, leader_epoch, timestamp, vec![value]);
+        batch.records[0].key = Some(key.serialize());
+        batch.attributes |= Self::CONTROL_FLAG;
+        batch
+    }
+
+    pub fn is_control(&self) -> bool {
+        self.attributes & Self::CONTROL_FLAG != 0
+    }
+
+    pub fn last_offset(&self) -> i64 {
+        self.base_offset + self.last_offset_delta as i64
+    }
+
+    /// Whether the stored CRC matches the batch content.
+    pub fn is_valid(&self) -> bool {
+        self.crc == crc32c(&self.crc_covered_bytes())
+    }
+
+    fn crc_covered_bytes(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(self.attributes.to_be_bytes());
+        buf.extend(self.last_offset_delta.to_be_bytes());
+        buf.extend(self.base_timestamp.to_be_bytes());
+        buf.extend(self.max_timestamp.to_be_bytes());
+        buf.extend(self.producer_id.to_be_bytes());
+        buf.extend(self.producer_epoch.to_be_bytes());
+        buf.extend(self.base_sequence.to_be_bytes());
+        buf.extend((self.records.len() as i32).to_be_bytes());
+        for record in &self.records {
+            buf.extend(record.serialize());
+        }
+        buf
+    }
+}
+
+impl Serializable for RecordBatch {
+    fn serialize(&self) -> Vec<u8> {
+        let covered = self.crc_covered_bytes();
+        let mut body = Vec::new();
+        body.extend(self.partition_leader_epoch.to_be_bytes());
+        body.push(self.magic_byte as u8);
+        body.extend(crc32c(&covered).to_be_bytes());
+        body.extend(covered);
+
+        let mut buf = Vec::new();
+        buf.extend(self.base_offset.to_be_bytes());
+        buf.extend((body.len() as i32).to_be_bytes());
+        buf.extend(body);
+        buf
+    }
+
+    fn deserialize(bytes: &[u8]) -> Result<(Self, &[u8])> {
+        let (base_offset, bytes) = i64::deserialize(bytes)?;
+        let (batch_length, bytes) = i32::deserialize(bytes)?;
+        let Some((batch, rest)) = bytes.split_at_checked(batch_length.max(0) as usize) else {
+            bail!("Truncated record batch at offset {base_offset}");
+        };
+
+        let (partition_leader_epoch, batch) = i32::deserialize(batch)?;
+        let (magic_byte, batch) = i8::deserialize(batch)?;
+        if magic_byte != 2 {
+            bail!("Unsupported record batch magic {magic_byte}");
+        }
+        let (crc, batch) = u32::deserialize(batch)?;
+        let (attributes, batch) = i16::deserialize(batch)?;
+        let (last_offset_delta, batch) = i32::deserialize(batch)?;
+        let (base_timestamp, batch) = i64::deserialize(batch)?;
+        let (max_timestamp, batch) = i64::deserialize(batch)?;
+        let (producer_id, batch) = i64::deserialize(batch)?;
+        let (producer_epoch, batch) = i16::deserialize(batch)?;
+        let (base_sequence, batch) = i32::deserialize(batch)?;
+        let (records_length, mut batch) = i32::deserialize(batch)?;
+
+        let mut records = Vec::with_capacity((records_length.max(0) as usize).min(batch.len()));
+        for _ in 0..records_length {
+            let (record, remaining) = Record::deserialize(batch)?;
+            records.push(record);
+            batch = remaining;
+        }
+
+        Ok((
+            RecordBatch {
+                base_offset,
+                partition_leader_epoch,
+                magic_byte,
+                crc,
+                attributes,
+                last_offset_delta,
+                base_timestamp,
+                max_timestamp,
+                producer_id,
+                producer_epoch,
+                base_sequence,
+                records,
+            },
+            rest,
+        ))
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct RecordHeader {
+    pub key: String,
+    pub value: Option<Vec<u8>>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Record {
+    pub attributes: i8,
+    pub timestamp_delta: i64,
+    pub offset_delta: i32,
+    pub key: Option<Vec<u8>>,
+    pub value: Option<Vec<u8>>,
+    pub headers: Vec<RecordHeader>,
+}
+
+fn serialize_varint_bytes(buf: &mut Vec<u8>, data: Option<&[u8]>) {
+    match data {
+        None => buf.extend(Varint(-1).serialize()),
+        Some(data) => {
+            buf.extend(Varint(data.len() as i32).serialize());
+            buf.extend(data);
+        }
+    }
+}
+
+fn deserialize_varint_bytes(bytes: &[u8]) -> Result<(Option<Vec<u8>>, &[u8])> {
+    let (length, bytes) = Varint::deserialize(bytes)?;
+    if length.0 < 0 {
+        return Ok((None, bytes));
+    }
+    let Some((data, bytes)) = bytes.split_at_checked(length.0 as usize) else {
+        bail!("Not enough bytes left");
+    };
+    Ok((Some(data.to_vec()), bytes))
+}
+
+impl Serializable for Record {
+    fn serialize(&self) -> Vec<u8> {
+        let mut body = Vec::new();
+        body.push(self.attributes as u8);
+        body.extend(Varlong(self.timestamp_delta).serialize());
+        body.extend(Varint(self.offset_delta).serialize());
+        serialize_varint_bytes(&mut body, self.key.as_deref());
+        serialize_varint_bytes(&mut body, self.value.as_deref());
+        body.extend(Varint(self.headers.len() as i32).serialize());
+        for header in &self.headers {
+            serialize_varint_bytes(&mut body, Some(header.key.as_bytes()));
+            serialize_varint_bytes(&mut body, header.value.as_deref());
+        }
+
+        let mut buf = Varint(body.len() as i32).serialize();
+        buf.extend(body);
+        buf
+    }
+
+    fn deserialize(bytes: &[u8]) -> Result<(Self, &[u8])> {
+        let (length, bytes) = Varint::deserialize(bytes)?;
+        let Some((record, rest)) = bytes.split_at_checked(length.0.max(0) as usize) else {
+            bail!("Not enough bytes left");
+        };
+
+        let (attributes, record) = i8::deserialize(record)?;
+        let (timestamp_delta, record) = Varlong::deserialize(record)?;
+        let (offset_delta, record) = Varint::deserialize(record)?;
+        let (key, record) = deserialize_varint_bytes(record)?;
+        let (value, record) = deserialize_varint_bytes(record)?;
+        let (header_count, mut record) = Varint::deserialize(record)?;
+        let mut headers = vec![];
+        for _ in 0..header_count.0 {
+            let (key, remaining) = deserialize_varint_bytes(record)?;
+            let (value, remaining) = deserialize_varint_bytes(remaining)?;
+            headers.push(RecordHeader {
+                key: String::from_utf8_lossy(&key.unwrap_or_default()).into_owned(),
+                value,
+            });
+            record = remaining;
+        }
+
+        Ok((
+            Record {
+                attributes,
+                timestamp_delta: timestamp_delta.0,
+                offset_delta: offset_delta.0,
+                key,
+                value,
+                headers,
+            },
+            rest,
+        ))
+    }
+}
+
+/// Key of the record of a control batch.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ControlRecordKey {
+    pub record_type: i16,
+}
+
+impl ControlRecordKey {
+    pub const LEADER_CHANGE: Self = ControlRecordKey { record_type: 2 };
+    pub const SNAPSHOT_HEADER: Self = ControlRecordKey { record_type: 3 };
+    pub const SNAPSHOT_FOOTER: Self = ControlRecordKey { record_type: 4 };
+    const VERSION: i16 = 0;
+}
+
+impl Serializable for ControlRecordKey {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(Self::VERSION.to_be_bytes());
+        buf.extend(self.record_type.to_be_bytes());
+        buf
+    }
+
+    fn deserialize(bytes: &[u8]) -> Result<(Self, &[u8])> {
+        let (_version, bytes) = i16::deserialize(bytes)?;
+        let (record_type, bytes) = i16::deserialize(bytes)?;
+        Ok((ControlRecordKey { record_type }, bytes))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct LeaderChangeVoter {
+    pub voter_id: i32,
 }
 
+impl Serializable for LeaderChangeVoter {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(self.voter_id.to_be_bytes());
+        buf.extend(TagSection(None).serialize());
+        buf
+    }
+
+    fn deserialize(bytes: &[u8]) -> Result<(Self, &[u8])> {
+        let (voter_id, bytes) = i32::deserialize(bytes)?;
+        let (_, bytes) = TagSection::deserialize(bytes)?;
+        Ok((LeaderChangeVoter { voter_id }, bytes))
+    }
+}
+
+/// Value of the control record a leader writes first in its epoch.
 #[derive(Debug)]
-struct Record<T: Serializable> {
-    attributes: i8,
-    timestamp_delta: Varint,
-    offset_delta: Varint,
-    key: Vec<u8>,
-    value: T,
-    headers: Vec<u8>,
+pub struct LeaderChangeMessage {
+    pub leader_id: i32,
+    pub voters: CompactArray<LeaderChangeVoter>,
+    /// Voters that granted the leader their vote
+    pub granting_voters: CompactArray<LeaderChangeVoter>,
+}
+
+impl LeaderChangeMessage {
+    const VERSION: i16 = 0;
+}
+
+impl Serializable for LeaderChangeMessage {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(Self::VERSION.to_be_bytes());
+        buf.extend(self.leader_id.to_be_bytes());
+        buf.extend(self.voters.serialize());
+        buf.extend(self.granting_voters.serialize());
+        buf.extend(TagSection(None).serialize());
+        buf
+    }
+
+    fn deserialize(bytes: &[u8]) -> Result<(Self, &[u8])> {
+        let (_version, bytes) = i16::deserialize(bytes)?;
+        let (leader_id, bytes) = i32::deserialize(bytes)?;
+        let (voters, bytes) = CompactArray::<LeaderChangeVoter>::deserialize(bytes)?;
+        let (granting_voters, bytes) = CompactArray::<LeaderChangeVoter>::deserialize(bytes)?;
+        let (_, bytes) = TagSection::deserialize(bytes)?;
+        Ok((
+            LeaderChangeMessage {
+                leader_id,
+                voters,
+                granting_voters,
+            },
+            bytes,
+        ))
+    }
 }
 
-impl<T: Serializable> Serializable for Record<T> {
+/// Value of the control record opening a snapshot of the metadata log.
+#[derive(Debug, Clone, Copy)]
+pub struct SnapshotHeaderRecord {
+    /// Timestamp of the last batch the snapshot covers
+    pub last_contained_log_timestamp: i64,
+}
+
+impl SnapshotHeaderRecord {
+    const VERSION: i16 = 0;
+}
+
+impl Serializable for SnapshotHeaderRecord {
     fn serialize(&self) -> Vec<u8> {
-        todo!()
+        let mut buf = Vec::new();
+        buf.extend(Self::VERSION.to_be_bytes());
+        buf.extend(self.last_contained_log_timestamp.to_be_bytes());
+        buf.extend(TagSection(None).serialize());
+        buf
     }
 
-    fn deserialize(bytes: &[u8]) -> anyhow::Result<(Self, &[u8])> {
-        todo!()
+    fn deserialize(bytes: &[u8]) -> Result<(Self, &[u8])> {
+        let (_version, bytes) = i16::deserialize(bytes)?;
+        let (last_contained_log_timestamp, bytes) = i64::deserialize(bytes)?;
+        let (_, bytes) = TagSection::deserialize(bytes)?;
+        Ok((
+            SnapshotHeaderRecord {
+                last_contained_log_timestamp,
+            },
+            bytes,
+        ))
     }
 }
 
-struct TopicRecord {
-    pub frame_version: i8,
-    pub record_type: i8,
-    pub version: i8,
+/// Value of the control record closing a snapshot of the metadata log.
+#[derive(Debug, Clone, Copy)]
+pub struct SnapshotFooterRecord;
+
+impl SnapshotFooterRecord {
+    const VERSION: i16 = 0;
+}
+
+impl Serializable for SnapshotFooterRecord {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(Self::VERSION.to_be_bytes());
+        buf.extend(TagSection(None).serialize());
+        buf
+    }
+
+    fn deserialize(bytes: &[u8]) -> Result<(Self, &[u8])> {
+        let (_version, bytes) = i16::deserialize(bytes)?;
+        let (_, bytes) = TagSection::deserialize(bytes)?;
+        Ok((SnapshotFooterRecord, bytes))
+    }
+}
+
+fn compact_string(bytes: &[u8]) -> Result<(String, &[u8])> {
+    let (value, bytes) = CompactString::deserialize(bytes)?;
+    Ok((value.0.unwrap_or_default(), bytes))
+}
+
+fn compact_bytes(bytes: &[u8]) -> Result<(Vec<u8>, &[u8])> {
+    let (value, bytes) = CompactBytes::deserialize(bytes)?;
+    Ok((value.0.unwrap_or_default(), bytes))
+}
+
+#[derive(Debug, Clone)]
+pub struct TopicRecord {
     pub name: String,
-    pub uuid: Uuid,
-    pub tag_buffer: TagSection,
+    pub topic_id: Uuid,
 }
 
 impl Serializable for TopicRecord {
     fn serialize(&self) -> Vec<u8> {
-        todo!()
+        let mut buf = Vec::new();
+        buf.extend(CompactString(Some(self.name.clone())).serialize());
+        buf.extend(self.topic_id.as_bytes());
+        buf
     }
 
-    fn deserialize(bytes: &[u8]) -> anyhow::Result<(Self, &[u8])> {
-        let Some((&frame_version, bytes)) = bytes.split_first() else {
-            bail!("Not enough bytes left");
+    fn deserialize(bytes: &[u8]) -> Result<(Self, &[u8])> {
+        let (name, bytes) = compact_string(bytes)?;
+        let (uuid, bytes) = take::<16>(bytes)?;
+        let topic_id = Uuid::from_bytes(uuid);
+        Ok((TopicRecord { name, topic_id }, bytes))
+    }
+}
+
+/// Finalized level of a cluster-wide feature, level 0 removing it.
+#[derive(Debug, Clone)]
+pub struct FeatureLevelRecord {
+    pub name: String,
+    pub feature_level: i16,
+}
+
+impl Serializable for FeatureLevelRecord {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(CompactString(Some(self.name.clone())).serialize());
+        buf.extend(self.feature_level.to_be_bytes());
+        buf
+    }
+
+    fn deserialize(bytes: &[u8]) -> Result<(Self, &[u8])> {
+        let (name, bytes) = compact_string(bytes)?;
+        let (feature_level, bytes) = i16::deserialize(bytes)?;
+        Ok((
+            FeatureLevelRecord {
+                name,
+                feature_level,
+            },
+            bytes,
+        ))
+    }
+}
+
+/// Assignment and leadership of a partition when it is created.
+#[derive(Debug, Clone)]
+pub struct PartitionRecord {
+    pub partition_id: i32,
+    pub topic_id: Uuid,
+    pub replicas: Vec<i32>,
+    pub isr: Vec<i32>,
+    pub removing_replicas: Vec<i32>,
+    pub adding_replicas: Vec<i32>,
+    pub leader: i32,
+    pub leader_epoch: i32,
+    pub partition_epoch: i32,
+    /// Version 1 and above: log directory of each replica, in the order of `replicas`
+    pub directories: Vec<Uuid>,
+}
+
+impl PartitionRecord {
+    /// Version records are written with; older ones are still read.
+    pub const VERSION: u32 = 1;
+
+    pub fn deserialize_versioned(bytes: &[u8], version: u32) -> Result<(Self, &[u8])> {
+        let (partition_id, bytes) = i32::deserialize(bytes)?;
+        let (topic_id, bytes) = Uuid::deserialize(bytes)?;
+        let (replicas, bytes) = CompactArray::<i32>::deserialize(bytes)?;
+        let (isr, bytes) = CompactArray::<i32>::deserialize(bytes)?;
+        let (removing_replicas, bytes) = CompactArray::<i32>::deserialize(bytes)?;
+        let (adding_replicas, bytes) = CompactArray::<i32>::deserialize(bytes)?;
+        let (leader, bytes) = i32::deserialize(bytes)?;
+        let (leader_epoch, bytes) = i32::deserialize(bytes)?;
+        let (partition_epoch, bytes) = i32::deserialize(bytes)?;
+        let (directories, bytes) = if version >= 1 {
+            CompactArray::<Uuid>::deserialize(bytes)?
+        } else {
+            (CompactArray(None), bytes)
         };
-        let Some((&record_type, bytes)) = bytes.split_first() else {
-            bail!("Not enough bytes left");
+        Ok((
+            PartitionRecord {
+                partition_id,
+                topic_id,
+                replicas: replicas.0.unwrap_or_default(),
+                isr: isr.0.unwrap_or_default(),
+                removing_replicas: removing_replicas.0.unwrap_or_default(),
+                adding_replicas: adding_replicas.0.unwrap_or_default(),
+                leader,
+                leader_epoch,
+                partition_epoch,
+                directories: directories.0.unwrap_or_default(),
+            },
+            bytes,
+        ))
+    }
+}
+
+impl Serializable for PartitionRecord {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(self.partition_id.to_be_bytes());
+        buf.extend(self.topic_id.as_bytes());
+        buf.extend(CompactArray(Some(self.replicas.clone())).serialize());
+        buf.extend(CompactArray(Some(self.isr.clone())).serialize());
+        buf.extend(CompactArray(Some(self.removing_replicas.clone())).serialize());
+        buf.extend(CompactArray(Some(self.adding_replicas.clone())).serialize());
+        buf.extend(self.leader.to_be_bytes());
+        buf.extend(self.leader_epoch.to_be_bytes());
+        buf.extend(self.partition_epoch.to_be_bytes());
+        buf.extend(CompactArray(Some(self.directories.clone())).serialize());
+        buf
+    }
+
+    fn deserialize(bytes: &[u8]) -> Result<(Self, &[u8])> {
+        Self::deserialize_versioned(bytes, Self::VERSION)
+    }
+}
+
+/// Change to a partition. Only the fields that change are set, each carried as a tagged
+/// field of the record.
+#[derive(Debug, Clone)]
+pub struct PartitionChangeRecord {
+    pub partition_id: i32,
+    pub topic_id: Uuid,
+    pub isr: Option<Vec<i32>>,
+    /// [`PartitionChangeRecord::NO_LEADER_CHANGE`] unless the leader changes, -1 for none
+    pub leader: i32,
+    pub replicas: Option<Vec<i32>>,
+    pub removing_replicas: Option<Vec<i32>>,
+    pub adding_replicas: Option<Vec<i32>>,
+    pub directories: Option<Vec<Uuid>>,
+}
+
+impl PartitionChangeRecord {
+    /// Version records are written with; older ones are still read.
+    pub const VERSION: u32 = 1;
+    pub const NO_LEADER_CHANGE: i32 = -2;
+
+    const ISR_TAG: u32 = 0;
+    const LEADER_TAG: u32 = 1;
+    const REPLICAS_TAG: u32 = 2;
+    const REMOVING_REPLICAS_TAG: u32 = 3;
+    const ADDING_REPLICAS_TAG: u32 = 4;
+    const DIRECTORIES_TAG: u32 = 8;
+
+    pub fn new(topic_id: Uuid, partition_id: i32) -> Self {
+        PartitionChangeRecord {
+            partition_id,
+            topic_id,
+            isr: None,
+            leader: Self::NO_LEADER_CHANGE,
+            replicas: None,
+            removing_replicas: None,
+            adding_replicas: None,
+            directories: None,
+        }
+    }
+
+    /// Tagged fields of the changes, in tag order.
+    fn tags(&self) -> TagSection {
+        let mut fields = vec![];
+        let mut push = |tag, data: Option<Vec<u8>>| {
+            if let Some(data) = data {
+                fields.push(TagField { tag, data });
+            }
         };
-        let Some((&version, bytes)) = bytes.split_first() else {
-            bail!("Not enough bytes left");
+        let array = |values: &Option<Vec<i32>>| {
+            values
+                .as_ref()
+                .map(|values| CompactArray(Some(values.clone())).serialize())
         };
-        let frame_version = frame_version as i8;
-        let record_type = record_type as i8;
-        let version = version as i8;
+        push(Self::ISR_TAG, array(&self.isr));
+        push(
+            Self::LEADER_TAG,
+            (self.leader != Self::NO_LEADER_CHANGE).then(|| self.leader.to_be_bytes().to_vec()),
+        );
+        push(Self::REPLICAS_TAG, array(&self.replicas));
+        push(Self::REMOVING_REPLICAS_TAG, array(&self.removing_replicas));
+        push(Self::ADDING_REPLICAS_TAG, array(&self.adding_replicas));
+        push(
+            Self::DIRECTORIES_TAG,
+            self.directories
+                .as_ref()
+                .map(|directories| CompactArray(Some(directories.clone())).serialize()),
+        );
+        TagSection(Some(fields))
+    }
 
-        let (name_length, bytes) = UnsignedVarint::deserialize(bytes)?;
-        let Some((name, bytes)) = bytes.split_at_checked(*name_length as usize) else {
-            bail!("Not enough bytes left");
+    fn read_tags(&mut self, tags: &TagSection) -> Result<()> {
+        let array = |data: &[u8]| -> Result<Option<Vec<i32>>> {
+            Ok(CompactArray::<i32>::deserialize(data)?.0 .0)
         };
-        let name = String::from_utf8_lossy(name).to_string();
-        let Some((uiid, bytes)) = bytes.split_at_checked(16) else {
-            bail!("Not enough bytes left");
-        };
-        let uuid = Uuid::from_bytes(uiid.try_into()?);
-        let (tag_field_count, bytes) = UnsignedVarint::deserialize(bytes)?;
+        for field in tags.0.iter().flatten() {
+            match field.tag {
+                Self::ISR_TAG => self.isr = array(&field.data)?,
+                Self::LEADER_TAG => self.leader = i32::deserialize(&field.data)?.0,
+                Self::REPLICAS_TAG => self.replicas = array(&field.data)?,
+                Self::REMOVING_REPLICAS_TAG => self.removing_replicas = array(&field.data)?,
+                Self::ADDING_REPLICAS_TAG => self.adding_replicas = array(&field.data)?,
+                Self::DIRECTORIES_TAG => {
+                    self.directories = CompactArray::<Uuid>::deserialize(&field.data)?.0 .0
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Serializable for PartitionChangeRecord {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(self.partition_id.to_be_bytes());
+        buf.extend(self.topic_id.as_bytes());
+        buf
+    }
+
+    fn deserialize(bytes: &[u8]) -> Result<(Self, &[u8])> {
+        let (partition_id, bytes) = i32::deserialize(bytes)?;
+        let (topic_id, bytes) = Uuid::deserialize(bytes)?;
+        Ok((PartitionChangeRecord::new(topic_id, partition_id), bytes))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct UserScramCredentialRecord {
+    pub name: String,
+    pub mechanism: i8,
+    pub salt: Vec<u8>,
+    pub stored_key: Vec<u8>,
+    pub server_key: Vec<u8>,
+    pub iterations: i32,
+}
+
+impl Serializable for UserScramCredentialRecord {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(CompactString(Some(self.name.clone())).serialize());
+        buf.push(self.mechanism as u8);
+        buf.extend(CompactBytes(Some(self.salt.clone())).serialize());
+        buf.extend(CompactBytes(Some(self.stored_key.clone())).serialize());
+        buf.extend(CompactBytes(Some(self.server_key.clone())).serialize());
+        buf.extend(self.iterations.to_be_bytes());
+        buf
+    }
+
+    fn deserialize(bytes: &[u8]) -> Result<(Self, &[u8])> {
+        let (name, bytes) = compact_string(bytes)?;
+        let (mechanism, bytes) = i8::deserialize(bytes)?;
+        let (salt, bytes) = compact_bytes(bytes)?;
+        let (stored_key, bytes) = compact_bytes(bytes)?;
+        let (server_key, bytes) = compact_bytes(bytes)?;
+        let (iterations, bytes) = i32::deserialize(bytes)?;
+        Ok((
+            UserScramCredentialRecord {
+                name,
+                mechanism,
+                salt,
+                stored_key,
+                server_key,
+                iterations,
+            },
+            bytes,
+        ))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RemoveUserScramCredentialRecord {
+    pub name: String,
+    pub mechanism: i8,
+}
+
+impl Serializable for RemoveUserScramCredentialRecord {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(CompactString(Some(self.name.clone())).serialize());
+        buf.push(self.mechanism as u8);
+        buf
+    }
+
+    fn deserialize(bytes: &[u8]) -> Result<(Self, &[u8])> {
+        let (name, bytes) = compact_string(bytes)?;
+        let (mechanism, bytes) = i8::deserialize(bytes)?;
+        Ok((RemoveUserScramCredentialRecord { name, mechanism }, bytes))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct AccessControlEntryRecord {
+    pub id: Uuid,
+    pub resource_type: i8,
+    pub resource_name: String,
+    pub pattern_type: i8,
+    pub principal: String,
+    pub host: String,
+    pub operation: i8,
+    pub permission_type: i8,
+}
+
+impl Serializable for AccessControlEntryRecord {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(self.id.as_bytes());
+        buf.push(self.resource_type as u8);
+        buf.extend(CompactString(Some(self.resource_name.clone())).serialize());
+        buf.push(self.pattern_type as u8);
+        buf.extend(CompactString(Some(self.principal.clone())).serialize());
+        buf.extend(CompactString(Some(self.host.clone())).serialize());
+        buf.push(self.operation as u8);
+        buf.push(self.permission_type as u8);
+        buf
+    }
+
+    fn deserialize(bytes: &[u8]) -> Result<(Self, &[u8])> {
+        let (id, bytes) = Uuid::deserialize(bytes)?;
+        let (resource_type, bytes) = i8::deserialize(bytes)?;
+        let (resource_name, bytes) = compact_string(bytes)?;
+        let (pattern_type, bytes) = i8::deserialize(bytes)?;
+        let (principal, bytes) = compact_string(bytes)?;
+        let (host, bytes) = compact_string(bytes)?;
+        let (operation, bytes) = i8::deserialize(bytes)?;
+        let (permission_type, bytes) = i8::deserialize(bytes)?;
+        Ok((
+            AccessControlEntryRecord {
+                id,
+                resource_type,
+                resource_name,
+                pattern_type,
+                principal,
+                host,
+                operation,
+                permission_type,
+            },
+            bytes,
+        ))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RemoveAccessControlEntryRecord {
+    pub id: Uuid,
+}
+
+impl Serializable for RemoveAccessControlEntryRecord {
+    fn serialize(&self) -> Vec<u8> {
+        self.id.as_bytes().to_vec()
+    }
+
+    fn deserialize(bytes: &[u8]) -> Result<(Self, &[u8])> {
+        let (id, bytes) = Uuid::deserialize(bytes)?;
+        Ok((RemoveAccessControlEntryRecord { id }, bytes))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct EntityData {
+    pub entity_type: String,
+    /// `None` for the default entity of the type
+    pub entity_name: Option<String>,
+}
+
+impl Serializable for EntityData {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(CompactString(Some(self.entity_type.clone())).serialize());
+        buf.extend(CompactString(self.entity_name.clone()).serialize());
+        buf.extend(TagSection(None).serialize());
+        buf
+    }
+
+    fn deserialize(bytes: &[u8]) -> Result<(Self, &[u8])> {
+        let (entity_type, bytes) = compact_string(bytes)?;
+        let (entity_name, bytes) = CompactString::deserialize(bytes)?;
+        let (_tag_buffer, bytes) = TagSection::deserialize(bytes)?;
+        Ok((
+            EntityData {
+                entity_type,
+                entity_name: entity_name.0,
+            },
+            bytes,
+        ))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ClientQuotaRecord {
+    pub entity: Vec<EntityData>,
+    pub key: String,
+    pub value: f64,
+    /// Whether the quota is removed rather than set
+    pub remove: bool,
+}
+
+impl Serializable for ClientQuotaRecord {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(CompactArray(Some(self.entity.clone())).serialize());
+        buf.extend(CompactString(Some(self.key.clone())).serialize());
+        buf.extend(self.value.to_be_bytes());
+        buf.push(self.remove as u8);
+        buf
+    }
+
+    fn deserialize(bytes: &[u8]) -> Result<(Self, &[u8])> {
+        let (entity, bytes) = CompactArray::<EntityData>::deserialize(bytes)?;
+        let (key, bytes) = compact_string(bytes)?;
+        let (value, bytes) = f64::deserialize(bytes)?;
+        let (remove, bytes) = bool::deserialize(bytes)?;
+        Ok((
+            ClientQuotaRecord {
+                entity: entity.0.unwrap_or_default(),
+                key,
+                value,
+                remove,
+            },
+            bytes,
+        ))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ConfigRecord {
+    pub resource_type: i8,
+    pub resource_name: String,
+    pub name: String,
+    /// `None` when the dynamic config is removed
+    pub value: Option<String>,
+}
+
+impl Serializable for ConfigRecord {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.push(self.resource_type as u8);
+        buf.extend(CompactString(Some(self.resource_name.clone())).serialize());
+        buf.extend(CompactString(Some(self.name.clone())).serialize());
+        buf.extend(CompactString(self.value.clone()).serialize());
+        buf
+    }
+
+    fn deserialize(bytes: &[u8]) -> Result<(Self, &[u8])> {
+        let (resource_type, bytes) = i8::deserialize(bytes)?;
+        let (resource_name, bytes) = compact_string(bytes)?;
+        let (name, bytes) = compact_string(bytes)?;
+        let (value, bytes) = CompactString::deserialize(bytes)?;
         Ok((
-            Self {
-                frame_version,
-                record_type,
-                version,
+            ConfigRecord {
+                resource_type,
+                resource_name,
                 name,
-                uuid,
-                tag_buffer: TagSection(None),
+                value: value.0,
             },
             bytes,
         ))
     }
 }
+
+#[derive(Debug, Clone)]
+pub struct BrokerEndpoint {
+    pub name: String,
+    pub host: String,
+    pub port: u16,
+    pub security_protocol: i16,
+}
+
+impl Serializable for BrokerEndpoint {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(CompactString(Some(self.name.clone())).serialize());
+        buf.extend(CompactString(Some(self.host.clone())).serialize());
+        buf.extend(self.port.to_be_bytes());
+        buf.extend(self.security_protocol.to_be_bytes());
+        buf.extend(TagSection(None).serialize());
+        buf
+    }
+
+    fn deserialize(bytes: &[u8]) -> Result<(Self, &[u8])> {
+        let (name, bytes) = compact_string(bytes)?;
+        let (host, bytes) = compact_string(bytes)?;
+        let (port, bytes) = u16::deserialize(bytes)?;
+        let (security_protocol, bytes) = i16::deserialize(bytes)?;
+        let (_tag_buffer, bytes) = TagSection::deserialize(bytes)?;
+        Ok((
+            BrokerEndpoint {
+                name,
+                host,
+                port,
+                security_protocol,
+            },
+            bytes,
+        ))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct BrokerFeature {
+    pub name: String,
+    pub min_supported_version: i16,
+    pub max_supported_version: i16,
+}
+
+impl Serializable for BrokerFeature {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(CompactString(Some(self.name.clone())).serialize());
+        buf.extend(self.min_supported_version.to_be_bytes());
+        buf.extend(self.max_supported_version.to_be_bytes());
+        buf.extend(TagSection(None).serialize());
+        buf
+    }
+
+    fn deserialize(bytes: &[u8]) -> Result<(Self, &[u8])> {
+        let (name, bytes) = compact_string(bytes)?;
+        let (min_supported_version, bytes) = i16::deserialize(bytes)?;
+        let (max_supported_version, bytes) = i16::deserialize(bytes)?;
+        let (_tag_buffer, bytes) = TagSection::deserialize(bytes)?;
+        Ok((
+            BrokerFeature {
+                name,
+                min_supported_version,
+                max_supported_version,
+            },
+            bytes,
+        ))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RegisterBrokerRecord {
+    pub broker_id: i32,
+    pub is_migrating_zk_broker: bool,
+    pub incarnation_id: Uuid,
+    pub broker_epoch: i64,
+    pub end_points: Vec<BrokerEndpoint>,
+    pub features: Vec<BrokerFeature>,
+    pub rack: Option<String>,
+    pub fenced: bool,
+    pub in_controlled_shutdown: bool,
+    pub log_dirs: Vec<Uuid>,
+}
+
+impl RegisterBrokerRecord {
+    /// Version records are written with; older ones are still read.
+    pub const VERSION: u32 = 3;
+
+    pub fn deserialize_versioned(bytes: &[u8], version: u32) -> Result<(Self, &[u8])> {
+        let (broker_id, bytes) = i32::deserialize(bytes)?;
+        let (is_migrating_zk_broker, bytes) = if version >= 2 {
+            bool::deserialize(bytes)?
+        } else {
+            (false, bytes)
+        };
+        let (incarnation_id, bytes) = Uuid::deserialize(bytes)?;
+        let (broker_epoch, bytes) = i64::deserialize(bytes)?;
+        let (end_points, bytes) = CompactArray::<BrokerEndpoint>::deserialize(bytes)?;
+        let (features, bytes) = CompactArray::<BrokerFeature>::deserialize(bytes)?;
+        let (rack, bytes) = CompactString::deserialize(bytes)?;
+        // Brokers registered before fencing was recorded start fenced
+        let (fenced, bytes) = if version >= 1 {
+            bool::deserialize(bytes)?
+        } else {
+            (true, bytes)
+        };
+        let (in_controlled_shutdown, bytes) = if version >= 2 {
+            bool::deserialize(bytes)?
+        } else {
+            (false, bytes)
+        };
+        let (log_dirs, bytes) = if version >= 3 {
+            CompactArray::<Uuid>::deserialize(bytes)?
+        } else {
+            (CompactArray(None), bytes)
+        };
+        Ok((
+            RegisterBrokerRecord {
+                broker_id,
+                is_migrating_zk_broker,
+                incarnation_id,
+                broker_epoch,
+                end_points: end_points.0.unwrap_or_default(),
+                features: features.0.unwrap_or_default(),
+                rack: rack.0,
+                fenced,
+                in_controlled_shutdown,
+                log_dirs: log_dirs.0.unwrap_or_default(),
+            },
+            bytes,
+        ))
+    }
+}
+
+impl Serializable for RegisterBrokerRecord {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(self.broker_id.to_be_bytes());
+        buf.push(self.is_migrating_zk_broker as u8);
+        buf.extend(self.incarnation_id.as_bytes());
+        buf.extend(self.broker_epoch.to_be_bytes());
+        buf.extend(CompactArray(Some(self.end_points.clone())).serialize());
+        buf.extend(CompactArray(Some(self.features.clone())).serialize());
+        buf.extend(CompactString(self.rack.clone()).serialize());
+        buf.push(self.fenced as u8);
+        buf.push(self.in_controlled_shutdown as u8);
+        buf.extend(CompactArray(Some(self.log_dirs.clone())).serialize());
+        buf
+    }
+
+    fn deserialize(bytes: &[u8]) -> Result<(Self, &[u8])> {
+        Self::deserialize_versioned(bytes, Self::VERSION)
+    }
+}
+
+/// Body shared by the records that only name a broker registration: UnregisterBroker,
+/// FenceBroker and UnfenceBroker.
+#[derive(Debug, Clone)]
+pub struct BrokerEpochRecord {
+    pub broker_id: i32,
+    pub broker_epoch: i64,
+}
+
+impl Serializable for BrokerEpochRecord {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(self.broker_id.to_be_bytes());
+        buf.extend(self.broker_epoch.to_be_bytes());
+        buf
+    }
+
+    fn deserialize(bytes: &[u8]) -> Result<(Self, &[u8])> {
+        let (broker_id, bytes) = i32::deserialize(bytes)?;
+        let (broker_epoch, bytes) = i64::deserialize(bytes)?;
+        Ok((
+            BrokerEpochRecord {
+                broker_id,
+                broker_epoch,
+            },
+            bytes,
+        ))
+    }
+}
+
+/// Changes the fencing or controlled shutdown state of a broker registration.
+#[derive(Debug, Clone)]
+pub struct BrokerRegistrationChangeRecord {
+    pub broker_id: i32,
+    pub broker_epoch: i64,
+    /// `Some(true)` to fence the broker, `Some(false)` to unfence it
+    pub fenced: Option<bool>,
+    /// Whether the broker entered controlled shutdown
+    pub in_controlled_shutdown: bool,
+}
+
+impl BrokerRegistrationChangeRecord {
+    pub const VERSION: u32 = 1;
+
+    const FENCED_TAG: u32 = 0;
+    const IN_CONTROLLED_SHUTDOWN_TAG: u32 = 1;
+
+    pub fn new(broker_id: i32, broker_epoch: i64) -> Self {
+        BrokerRegistrationChangeRecord {
+            broker_id,
+            broker_epoch,
+            fenced: None,
+            in_controlled_shutdown: false,
+        }
+    }
+
+    /// Tagged fields of the changes, in tag order.
+    fn tags(&self) -> TagSection {
+        let mut fields = vec![];
+        if let Some(fenced) = self.fenced {
+            let value: i8 = if fenced { 1 } else { -1 };
+            fields.push(TagField {
+                tag: Self::FENCED_TAG,
+                data: value.to_be_bytes().to_vec(),
+            });
+        }
+        if self.in_controlled_shutdown {
+            fields.push(TagField {
+                tag: Self::IN_CONTROLLED_SHUTDOWN_TAG,
+                data: vec![1],
+            });
+        }
+        TagSection(Some(fields))
+    }
+
+    fn read_tags(&mut self, tags: &TagSection) -> Result<()> {
+        if let Some(data) = tags.field(Self::FENCED_TAG) {
+            self.fenced = match i8::deserialize(data)?.0 {
+                1 => Some(true),
+                -1 => Some(false),
+                _ => None,
+            };
+        }
+        if let Some(data) = tags.field(Self::IN_CONTROLLED_SHUTDOWN_TAG) {
+            self.in_controlled_shutdown = i8::deserialize(data)?.0 == 1;
+        }
+        Ok(())
+    }
+}
+
+impl Serializable for BrokerRegistrationChangeRecord {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(self.broker_id.to_be_bytes());
+        buf.extend(self.broker_epoch.to_be_bytes());
+        buf
+    }
+
+    fn deserialize(bytes: &[u8]) -> Result<(Self, &[u8])> {
+        let (broker_id, bytes) = i32::deserialize(bytes)?;
+        let (broker_epoch, bytes) = i64::deserialize(bytes)?;
+        Ok((
+            BrokerRegistrationChangeRecord::new(broker_id, broker_epoch),
+            bytes,
+        ))
+    }
+}
+
+/// A record of the `__cluster_metadata` log, framed by its type and version.
+#[derive(Debug, Clone)]
+pub enum MetadataRecord {
+    RegisterBroker(RegisterBrokerRecord),
+    UnregisterBroker(BrokerEpochRecord),
+    Topic(TopicRecord),
+    Partition(PartitionRecord),
+    Config(ConfigRecord),
+    PartitionChange(PartitionChangeRecord),
+    AccessControlEntry(AccessControlEntryRecord),
+    RemoveAccessControlEntry(RemoveAccessControlEntryRecord),
+    FenceBroker(BrokerEpochRecord),
+    UnfenceBroker(BrokerEpochRecord),
+    UserScramCredential(UserScramCredentialRecord),
+    FeatureLevel(FeatureLevelRecord),
+    ClientQuota(ClientQuotaRecord),
+    BrokerRegistrationChange(BrokerRegistrationChangeRecord),
+    RemoveUserScramCredential(RemoveUserScramCredentialRecord),
+    /// Record types the broker does not interpret yet
+    Unknown {
+        record_type: u32,
+        version: u32,
+    },
+}
+
+impl MetadataRecord {
+    const FRAME_VERSION: u32 = 1;
+
+    pub fn record_type(&self) -> u32 {
+        match self {
+            MetadataRecord::RegisterBroker(_) => 0,
+            MetadataRecord::UnregisterBroker(_) => 1,
+            MetadataRecord::Topic(_) => 2,
+            MetadataRecord::Partition(_) => 3,
+            MetadataRecord::Config(_) => 4,
+            MetadataRecord::PartitionChange(_) => 5,
+            MetadataRecord::AccessControlEntry(_) => 6,
+            MetadataRecord::RemoveAccessControlEntry(_) => 7,
+            MetadataRecord::FenceBroker(_) => 8,
+            MetadataRecord::UnfenceBroker(_) => 9,
+            MetadataRecord::UserScramCredential(_) => 11,
+            MetadataRecord::FeatureLevel(_) => 12,
+            MetadataRecord::ClientQuota(_) => 14,
+            MetadataRecord::BrokerRegistrationChange(_) => 17,
+            MetadataRecord::RemoveUserScramCredential(_) => 22,
+            MetadataRecord::Unknown { record_type, .. } => *record_type,
+        }
+    }
+
+    /// Tagged fields of the record, written after its body.
+    fn tags(&self) -> TagSection {
+        match self {
+            MetadataRecord::PartitionChange(record) => record.tags(),
+            MetadataRecord::BrokerRegistrationChange(record) => record.tags(),
+            _ => TagSection(None),
+        }
+    }
+
+    pub fn version(&self) -> u32 {
+        match self {
+            MetadataRecord::RegisterBroker(_) => RegisterBrokerRecord::VERSION,
+            MetadataRecord::Partition(_) => PartitionRecord::VERSION,
+            MetadataRecord::PartitionChange(_) => PartitionChangeRecord::VERSION,
+            MetadataRecord::BrokerRegistrationChange(_) => BrokerRegistrationChangeRecord::VERSION,
+            MetadataRecord::Unknown { version, .. } => *version,
+            _ => 0,
+        }
+    }
+}
+
+impl Serializable for MetadataRecord {
+    fn serialize(&self) -> Vec<u8> {
+        let body = match self {
+            MetadataRecord::RegisterBroker(record) => record.serialize(),
+            MetadataRecord::UnregisterBroker(record) => record.serialize(),
+            MetadataRecord::Topic(record) => record.serialize(),
+            MetadataRecord::Partition(record) => record.serialize(),
+            MetadataRecord::Config(record) => record.serialize(),
+            MetadataRecord::PartitionChange(record) => record.serialize(),
+            MetadataRecord::AccessControlEntry(record) => record.serialize(),
+            MetadataRecord::RemoveAccessControlEntry(record) => record.serialize(),
+            MetadataRecord::FenceBroker(record) => record.serialize(),
+            MetadataRecord::UnfenceBroker(record) => record.serialize(),
+            MetadataRecord::UserScramCredential(record) => record.serialize(),
+            MetadataRecord::FeatureLevel(record) => record.serialize(),
+            MetadataRecord::ClientQuota(record) => record.serialize(),
+            MetadataRecord::BrokerRegistrationChange(record) => record.serialize(),
+            MetadataRecord::RemoveUserScramCredential(record) => record.serialize(),
+            MetadataRecord::Unknown { .. } => vec![],
+        };
+        let mut buf = Vec::new();
+        buf.extend(UnsignedVarint(Self::FRAME_VERSION).serialize());
+        buf.extend(UnsignedVarint(self.record_type()).serialize());
+        buf.extend(UnsignedVarint(self.version()).serialize());
+        buf.extend(body);
+        buf.extend(self.tags().serialize());
+        buf
+    }
+
+    fn deserialize(bytes: &[u8]) -> Result<(Self, &[u8])> {
+        let (frame_version, bytes) = UnsignedVarint::deserialize(bytes)?;
+        if frame_version.0 != Self::FRAME_VERSION {
+            bail!(
+                "Unsupported metadata record frame version {}",
+                frame_version.0
+            );
+        }
+        let (record_type, bytes) = UnsignedVarint::deserialize(bytes)?;
+        let (version, bytes) = UnsignedVarint::deserialize(bytes)?;
+
+        let (mut record, bytes) = match record_type.0 {
+            0 => {
+                let (record, bytes) =
+                    RegisterBrokerRecord::deserialize_versioned(bytes, version.0)?;
+                (MetadataRecord::RegisterBroker(record), bytes)
+            }
+            1 => {
+                let (record, bytes) = BrokerEpochRecord::deserialize(bytes)?;
+                (MetadataRecord::UnregisterBroker(record), bytes)
+            }
+            2 => {
+                let (record, bytes) = TopicRecord::deserialize(bytes)?;
+                (MetadataRecord::Topic(record), bytes)
+            }
+            3 => {
+                let (record, bytes) = PartitionRecord::deserialize_versioned(bytes, version.0)?;
+                (MetadataRecord::Partition(record), bytes)
+            }
+            4 => {
+                let (record, bytes) = ConfigRecord::deserialize(bytes)?;
+                (MetadataRecord::Config(record), bytes)
+            }
+            5 => {
+                let (record, bytes) = PartitionChangeRecord::deserialize(bytes)?;
+                (MetadataRecord::PartitionChange(record), bytes)
+            }
+            6 => {
+                let (record, bytes) = AccessControlEntryRecord::deserialize(bytes)?;
+                (MetadataRecord::AccessControlEntry(record), bytes)
+            }
+            7 => {
+                let (record, bytes) = RemoveAccessControlEntryRecord::deserialize(bytes)?;
+                (MetadataRecord::RemoveAccessControlEntry(record), bytes)
+            }
+            8 => {
+                let (record, bytes) = BrokerEpochRecord::deserialize(bytes)?;
+                (MetadataRecord::FenceBroker(record), bytes)
+            }
+            9 => {
+                let (record, bytes) = BrokerEpochRecord::deserialize(bytes)?;
+                (MetadataRecord::UnfenceBroker(record), bytes)
+            }
+            11 => {
+                let (record, bytes) = UserScramCredentialRecord::deserialize(bytes)?;
+                (MetadataRecord::UserScramCredential(record), bytes)
+            }
+            12 => {
+                let (record, bytes) = FeatureLevelRecord::deserialize(bytes)?;
+                (MetadataRecord::FeatureLevel(record), bytes)
+            }
+            14 => {
+                let (record, bytes) = ClientQuotaRecord::deserialize(bytes)?;
+                (MetadataRecord::ClientQuota(record), bytes)
+            }
+            17 => {
+                let (record, bytes) = BrokerRegistrationChangeRecord::deserialize(bytes)?;
+                (MetadataRecord::BrokerRegistrationChange(record), bytes)
+            }
+            22 => {
+                let (record, bytes) = RemoveUserScramCredentialRecord::deserialize(bytes)?;
+                (MetadataRecord::RemoveUserScramCredential(record), bytes)
+            }
+            record_type => {
+                return Ok((
+                    MetadataRecord::Unknown {
+                        record_type,
+                        version: version.0,
+                    },
+                    &[],
+                ))
+            }
+        };
+        let (tags, bytes) = TagSection::deserialize(bytes)?;
+        match &mut record {
+            MetadataRecord::PartitionChange(record) => record.read_tags(&tags)?,
+            MetadataRecord::BrokerRegistrationChange(record) => record.read_tags(&tags)?,
+            _ => {}
+        }
+        Ok((record, bytes))
+    }
+}
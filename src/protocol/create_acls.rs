@@ -0,0 +1,260 @@
+use anyhow::Result;
+use uuid::Builder;
+
+use super::{
+    api_keys::{RequestContext, RequestHandler},
+    body::ResponseBody,
+    cluster_metadata::{AccessControlEntryRecord, MetadataRecord},
+    error,
+    primitive::{CompactArray, CompactString, Serializable, TagSection},
+    response::Response,
+};
+use crate::{
+    broker::Broker,
+    raft::RaftError,
+    security::acl::{
+        AclBinding, AclOperation, AclPermissionType, PatternType, ResourceType,
+        CLUSTER_RESOURCE_NAME,
+    },
+    server::ConnectionContext,
+};
+
+#[derive(Debug)]
+pub struct AclCreation {
+    pub resource_type: i8,
+    pub resource_name: CompactString,
+    pub resource_pattern_type: i8,
+    pub principal: CompactString,
+    pub host: CompactString,
+    pub operation: i8,
+    pub permission_type: i8,
+    pub tag_buffer: TagSection,
+}
+
+impl AclCreation {
+    fn binding(&self) -> AclBinding {
+        AclBinding {
+            resource_type: ResourceType::from_code(self.resource_type),
+            resource_name: self.resource_name.0.clone().unwrap_or_default(),
+            pattern_type: PatternType::from_code(self.resource_pattern_type),
+            principal: self.principal.0.clone().unwrap_or_default(),
+            host: self.host.0.clone().unwrap_or_default(),
+            operation: AclOperation::from_code(self.operation),
+            permission_type: AclPermissionType::from_code(self.permission_type),
+        }
+    }
+}
+
+impl Serializable for AclCreation {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.push(self.resource_type as u8);
+        buf.extend(self.resource_name.serialize());
+        buf.push(self.resource_pattern_type as u8);
+        buf.extend(self.principal.serialize());
+        buf.extend(self.host.serialize());
+        buf.push(self.operation as u8);
+        buf.push(self.permission_type as u8);
+        buf.extend(self.tag_buffer.serialize());
+        buf
+    }
+
+    fn deserialize(bytes: &[u8]) -> Result<(Self, &[u8])> {
+        let (resource_type, bytes) = i8::deserialize(bytes)?;
+        let (resource_name, bytes) = CompactString::deserialize(bytes)?;
+        let (resource_pattern_type, bytes) = i8::deserialize(bytes)?;
+        let (principal, bytes) = CompactString::deserialize(bytes)?;
+        let (host, bytes) = CompactString::deserialize(bytes)?;
+        let (operation, bytes) = i8::deserialize(bytes)?;
+        let (permission_type, bytes) = i8::deserialize(bytes)?;
+        let (tag_buffer, bytes) = TagSection::deserialize(bytes)?;
+        Ok((
+            AclCreation {
+                resource_type,
+                resource_name,
+                resource_pattern_type,
+                principal,
+                host,
+                operation,
+                permission_type,
+                tag_buffer,
+            },
+            bytes,
+        ))
+    }
+}
+
+#[derive(Debug)]
+pub struct CreateAclsRequest {
+    pub creations: CompactArray<AclCreation>,
+    pub tag_buffer: TagSection,
+}
+
+impl Serializable for CreateAclsRequest {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(self.creations.serialize());
+        buf.extend(self.tag_buffer.serialize());
+        buf
+    }
+
+    fn deserialize(bytes: &[u8]) -> Result<(Self, &[u8])> {
+        let (creations, bytes) = CompactArray::<AclCreation>::deserialize(bytes)?;
+        let (tag_buffer, bytes) = TagSection::deserialize(bytes)?;
+        Ok((
+            CreateAclsRequest {
+                creations,
+                tag_buffer,
+            },
+            bytes,
+        ))
+    }
+}
+
+impl CreateAclsRequest {
+    pub fn handle_request(&self, broker: &Broker, context: &ConnectionContext) -> Option<Response> {
+        let creations = self.creations.as_deref().unwrap_or_default();
+        let denial = if !broker.has_authorizer() {
+            Some((error::SECURITY_DISABLED, "No Authorizer is configured"))
+        } else if !broker.authorize(
+            context,
+            AclOperation::Alter,
+            ResourceType::Cluster,
+            CLUSTER_RESOURCE_NAME,
+        ) {
+            Some((
+                error::CLUSTER_AUTHORIZATION_FAILED,
+                "Cluster authorization failed",
+            ))
+        } else {
+            None
+        };
+
+        let results = match denial {
+            Some((error_code, message)) => creations
+                .iter()
+                .map(|_| AclCreationResult::error(error_code, message.to_string()))
+                .collect(),
+            None => {
+                let mut results = vec![];
+                let mut records = vec![];
+                let mut created: Vec<AclBinding> = vec![];
+                {
+                    let metadata = broker.metadata.read().unwrap();
+                    for creation in creations {
+                        let binding = creation.binding();
+                        if let Err(message) = binding.validate() {
+                            results.push(AclCreationResult::error(error::INVALID_REQUEST, message));
+                            continue;
+                        }
+                        results.push(AclCreationResult::error(error::NONE, String::new()));
+                        // Creating a binding that already exists succeeds without a new entry
+                        if metadata.acls.values().any(|acl| *acl == binding)
+                            || created.contains(&binding)
+                        {
+                            continue;
+                        }
+                        records.push(MetadataRecord::AccessControlEntry(
+                            AccessControlEntryRecord {
+                                id: Builder::from_random_bytes(rand::random()).into_uuid(),
+                                resource_type: binding.resource_type.code(),
+                                resource_name: binding.resource_name.clone(),
+                                pattern_type: binding.pattern_type.code(),
+                                principal: binding.principal.clone(),
+                                host: binding.host.clone(),
+                                operation: binding.operation.code(),
+                                permission_type: binding.permission_type.code(),
+                            },
+                        ));
+                        created.push(binding);
+                    }
+                }
+                if let Err(e) = broker.commit_metadata(records) {
+                    eprintln!("Failed to persist ACLs: {e:#}");
+                    for result in results.iter_mut().filter(|r| r.error_code == error::NONE) {
+                        *result = AclCreationResult::error(
+                            RaftError::error_code(&e),
+                            "Failed to persist the ACL".to_string(),
+                        );
+                    }
+                }
+                results
+            }
+        };
+
+        Some(Box::new(CreateAclsResponse {
+            throttle_time_ms: 0,
+            results: CompactArray(Some(results)),
+            tag_buffer: TagSection(None),
+        }))
+    }
+}
+
+impl RequestHandler for CreateAclsRequest {
+    fn read(bytes: &[u8], _version: i16) -> Result<Self> {
+        Ok(CreateAclsRequest::deserialize(bytes)?.0)
+    }
+
+    fn handle(&self, request: &mut RequestContext) -> Option<Response> {
+        self.handle_request(request.broker, request.connection)
+    }
+}
+
+#[derive(Debug)]
+pub struct AclCreationResult {
+    pub error_code: i16,
+    pub error_message: CompactString,
+    pub tag_buffer: TagSection,
+}
+
+impl AclCreationResult {
+    /// Result with `error_code`, carrying `message` unless the creation succeeded.
+    fn error(error_code: i16, message: String) -> Self {
+        AclCreationResult {
+            error_code,
+            error_message: CompactString((error_code != error::NONE).then_some(message)),
+            tag_buffer: TagSection(None),
+        }
+    }
+}
+
+impl Serializable for AclCreationResult {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(self.error_code.to_be_bytes());
+        buf.extend(self.error_message.serialize());
+        buf.extend(self.tag_buffer.serialize());
+        buf
+    }
+
+    fn deserialize(_bytes: &[u8]) -> Result<(Self, &[u8])> {
+        todo!()
+    }
+}
+
+#[derive(Debug)]
+pub struct CreateAclsResponse {
+    pub throttle_time_ms: i32,
+    pub results: CompactArray<AclCreationResult>,
+    pub tag_buffer: TagSection,
+}
+
+impl Serializable for CreateAclsResponse {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(self.throttle_time_ms.to_be_bytes());
+        buf.extend(self.results.serialize());
+        buf.extend(self.tag_buffer.serialize());
+        buf
+    }
+
+    fn deserialize(_bytes: &[u8]) -> Result<(Self, &[u8])> {
+        todo!()
+    }
+}
+
+impl ResponseBody for CreateAclsResponse {
+    fn set_throttle_time_ms(&mut self, throttle_time_ms: i32) {
+        self.throttle_time_ms = throttle_time_ms;
+    }
+}
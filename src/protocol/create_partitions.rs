@@ -0,0 +1,378 @@
+use std::collections::BTreeSet;
+
+use anyhow::Result;
+
+use super::{
+    api_keys::{RequestContext, RequestHandler},
+    body::ResponseBody,
+    cluster_metadata::MetadataRecord,
+    error,
+    primitive::{CompactArray, CompactString, Serializable, TagSection, UnsignedVarint},
+    response::Response,
+};
+use crate::{
+    broker::Broker,
+    controller,
+    metadata::MetadataImage,
+    raft::RaftError,
+    security::acl::{AclOperation, ResourceType},
+    server::ConnectionContext,
+};
+
+#[derive(Debug)]
+pub struct CreatePartitionsAssignment {
+    pub broker_ids: CompactArray<i32>,
+    pub tag_buffer: TagSection,
+}
+
+impl Serializable for CreatePartitionsAssignment {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(self.broker_ids.serialize());
+        buf.extend(self.tag_buffer.serialize());
+        buf
+    }
+
+    fn deserialize(bytes: &[u8]) -> Result<(Self, &[u8])> {
+        let (broker_ids, bytes) = CompactArray::<i32>::deserialize(bytes)?;
+        let (tag_buffer, bytes) = TagSection::deserialize(bytes)?;
+        Ok((
+            CreatePartitionsAssignment {
+                broker_ids,
+                tag_buffer,
+            },
+            bytes,
+        ))
+    }
+}
+
+#[derive(Debug)]
+pub struct CreatePartitionsTopic {
+    pub name: CompactString,
+    /// Partition count the topic should end up with
+    pub count: i32,
+    /// Replicas of each new partition, null to let the controller place them
+    pub assignments: CompactArray<CreatePartitionsAssignment>,
+    pub tag_buffer: TagSection,
+}
+
+impl Serializable for CreatePartitionsTopic {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(self.name.serialize());
+        buf.extend(self.count.to_be_bytes());
+        buf.extend(self.assignments.serialize());
+        buf.extend(self.tag_buffer.serialize());
+        buf
+    }
+
+    fn deserialize(bytes: &[u8]) -> Result<(Self, &[u8])> {
+        let (name, bytes) = CompactString::deserialize(bytes)?;
+        let (count, bytes) = i32::deserialize(bytes)?;
+        let (assignments, bytes) = CompactArray::<CreatePartitionsAssignment>::deserialize(bytes)?;
+        let (tag_buffer, bytes) = TagSection::deserialize(bytes)?;
+        Ok((
+            CreatePartitionsTopic {
+                name,
+                count,
+                assignments,
+                tag_buffer,
+            },
+            bytes,
+        ))
+    }
+}
+
+/// CreatePartitions versions 2 and 3, adding partitions to existing topics. New
+/// partitions keep the replication factor of the topic.
+#[derive(Debug)]
+pub struct CreatePartitionsRequest {
+    pub topics: CompactArray<CreatePartitionsTopic>,
+    pub timeout_ms: i32,
+    pub validate_only: bool,
+    pub tag_buffer: TagSection,
+}
+
+impl Serializable for CreatePartitionsRequest {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(self.topics.serialize());
+        buf.extend(self.timeout_ms.to_be_bytes());
+        buf.extend(self.validate_only.serialize());
+        buf.extend(self.tag_buffer.serialize());
+        buf
+    }
+
+    fn deserialize(bytes: &[u8]) -> Result<(Self, &[u8])> {
+        let (topics, bytes) = CompactArray::<CreatePartitionsTopic>::deserialize(bytes)?;
+        let (timeout_ms, bytes) = i32::deserialize(bytes)?;
+        let (validate_only, bytes) = bool::deserialize(bytes)?;
+        let (tag_buffer, bytes) = TagSection::deserialize(bytes)?;
+        Ok((
+            CreatePartitionsRequest {
+                topics,
+                timeout_ms,
+                validate_only,
+                tag_buffer,
+            },
+            bytes,
+        ))
+    }
+}
+
+impl CreatePartitionsRequest {
+    pub fn handle_request(&self, broker: &Broker, context: &ConnectionContext) -> Option<Response> {
+        let topics: Vec<&CreatePartitionsTopic> = self.topics.iter().flatten().collect();
+        let mut names = BTreeSet::new();
+        let duplicates: BTreeSet<&str> = topics
+            .iter()
+            .map(|topic| topic.name.as_deref().unwrap_or_default())
+            .filter(|name| !names.insert(*name))
+            .collect();
+
+        let mut outcomes: Vec<Result<(), (i16, String)>> = topics
+            .iter()
+            .map(|topic| {
+                let name = topic.name.as_deref().unwrap_or_default();
+                if duplicates.contains(name) {
+                    return Err((
+                        error::INVALID_REQUEST,
+                        "Duplicate topic in request.".to_string(),
+                    ));
+                }
+                if !broker.authorize(context, AclOperation::Alter, ResourceType::Topic, name) {
+                    return Err((
+                        error::TOPIC_AUTHORIZATION_FAILED,
+                        "The topic authorization is failed.".to_string(),
+                    ));
+                }
+                if !broker.raft.is_leader() {
+                    return Err((
+                        error::NOT_CONTROLLER,
+                        "This node is not the active controller".to_string(),
+                    ));
+                }
+                Ok(())
+            })
+            .collect();
+
+        let mut create = |image: &MetadataImage| {
+            let mut records = vec![];
+            for (topic, outcome) in topics.iter().zip(outcomes.iter_mut()) {
+                if outcome.is_err() {
+                    continue;
+                }
+                match add_partitions(image, topic) {
+                    Ok(topic_records) => records.extend(topic_records),
+                    Err(e) => *outcome = Err(e),
+                }
+            }
+            records
+        };
+        if self.validate_only {
+            create(&broker.metadata.read().unwrap());
+        } else {
+            let committed = broker.append_metadata(|_| create(&broker.metadata.read().unwrap()));
+            match committed {
+                Ok(()) => {
+                    let created = topics.iter().zip(&outcomes).filter(|(_, o)| o.is_ok());
+                    for (topic, _) in created {
+                        eprintln!(
+                            "Increased the partition count of {} to {}",
+                            topic.name.as_deref().unwrap_or_default(),
+                            topic.count
+                        );
+                    }
+                }
+                Err(e) => {
+                    eprintln!("Failed to create partitions: {e:#}");
+                    for outcome in outcomes.iter_mut().filter(|o| o.is_ok()) {
+                        *outcome = Err((
+                            RaftError::error_code(&e),
+                            "Failed to persist the partitions".to_string(),
+                        ));
+                    }
+                }
+            }
+        }
+
+        let results = topics
+            .iter()
+            .zip(outcomes)
+            .map(|(topic, outcome)| {
+                let (error_code, error_message) = match outcome {
+                    Ok(()) => (error::NONE, None),
+                    Err((error_code, message)) => (error_code, Some(message)),
+                };
+                CreatePartitionsTopicResult {
+                    name: topic.name.0.clone().unwrap_or_default(),
+                    error_code,
+                    error_message,
+                }
+            })
+            .collect();
+
+        Some(Box::new(CreatePartitionsResponse {
+            throttle_time_ms: 0,
+            results,
+        }))
+    }
+}
+
+impl RequestHandler for CreatePartitionsRequest {
+    fn read(bytes: &[u8], _version: i16) -> Result<Self> {
+        Ok(CreatePartitionsRequest::deserialize(bytes)?.0)
+    }
+
+    fn handle(&self, request: &mut RequestContext) -> Option<Response> {
+        self.handle_request(request.broker, request.connection)
+    }
+}
+
+/// Records of the partitions a topic of the request gains.
+fn add_partitions(
+    image: &MetadataImage,
+    topic: &CreatePartitionsTopic,
+) -> Result<Vec<MetadataRecord>, (i16, String)> {
+    let name = topic.name.as_deref().unwrap_or_default();
+    let Some((&topic_id, partitions)) = image
+        .topics
+        .get(name)
+        .and_then(|topic_id| Some((topic_id, image.partitions.get(topic_id)?)))
+    else {
+        return Err((
+            error::UNKNOWN_TOPIC_OR_PARTITION,
+            format!("Topic '{name}' does not exist."),
+        ));
+    };
+    if partitions.values().any(|p| p.is_reassigning()) {
+        return Err((
+            error::REASSIGNMENT_IN_PROGRESS,
+            "A partition reassignment is in progress.".to_string(),
+        ));
+    }
+    let current = partitions.len() as i32;
+    if topic.count < current {
+        return Err((
+            error::INVALID_PARTITIONS,
+            format!(
+                "Topic currently has {current} partitions, which is higher than the requested \
+                 {}.",
+                topic.count
+            ),
+        ));
+    }
+    if topic.count == current {
+        return Err((
+            error::INVALID_PARTITIONS,
+            format!("Topic already has {current} partitions."),
+        ));
+    }
+
+    let replication_factor = partitions
+        .values()
+        .next()
+        .map_or(0, |p| p.replicas.len() as i32);
+    let added = topic.count - current;
+    let replicas = match topic.assignments.as_deref() {
+        None => controller::place_replicas(image, current, added, replication_factor).map_err(
+            |error_code| {
+                (
+                    error_code,
+                    format!(
+                        "Unable to replicate the partition {replication_factor} time(s): there \
+                         are not enough brokers that may host replicas"
+                    ),
+                )
+            },
+        )?,
+        Some(assignments) => {
+            if assignments.len() as i32 != added {
+                return Err((
+                    error::INVALID_REPLICA_ASSIGNMENT,
+                    format!(
+                        "Attempted to add {added} additional partition(s), but only {} \
+                         assignment(s) were specified.",
+                        assignments.len()
+                    ),
+                ));
+            }
+            let mut replicas = vec![];
+            for assignment in assignments {
+                let broker_ids = assignment.broker_ids.as_deref().unwrap_or_default();
+                if broker_ids.len() as i32 != replication_factor {
+                    return Err((
+                        error::INVALID_REPLICA_ASSIGNMENT,
+                        format!(
+                            "The manual partition assignment includes a partition with {} \
+                             replica(s), but this is not consistent with previous partitions, \
+                             which have {replication_factor} replica(s).",
+                            broker_ids.len()
+                        ),
+                    ));
+                }
+                controller::check_assignment(image, broker_ids).map_err(|error_code| {
+                    (error_code, format!("Invalid replicas {broker_ids:?}"))
+                })?;
+                replicas.push(broker_ids.to_vec());
+            }
+            replicas
+        }
+    };
+
+    let mut records = vec![];
+    for (partition, replicas) in (current..).zip(replicas) {
+        let record = controller::new_partition(image, topic_id, partition, replicas).map_err(
+            |error_code| {
+                (
+                    error_code,
+                    format!(
+                        "All brokers specified for partition {partition} are fenced or in \
+                         controlled shutdown"
+                    ),
+                )
+            },
+        )?;
+        records.push(MetadataRecord::Partition(record));
+    }
+    Ok(records)
+}
+
+#[derive(Debug)]
+pub struct CreatePartitionsTopicResult {
+    pub name: String,
+    pub error_code: i16,
+    pub error_message: Option<String>,
+}
+
+#[derive(Debug)]
+pub struct CreatePartitionsResponse {
+    pub throttle_time_ms: i32,
+    pub results: Vec<CreatePartitionsTopicResult>,
+}
+
+impl Serializable for CreatePartitionsResponse {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(self.throttle_time_ms.to_be_bytes());
+        buf.extend(UnsignedVarint(self.results.len() as u32 + 1).serialize());
+        for result in &self.results {
+            buf.extend(CompactString(Some(result.name.clone())).serialize());
+            buf.extend(result.error_code.to_be_bytes());
+            buf.extend(CompactString(result.error_message.clone()).serialize());
+            buf.extend(TagSection(None).serialize());
+        }
+        buf.extend(TagSection(None).serialize());
+        buf
+    }
+
+    fn deserialize(_bytes: &[u8]) -> Result<(Self, &[u8])> {
+        todo!()
+    }
+}
+
+impl ResponseBody for CreatePartitionsResponse {
+    fn set_throttle_time_ms(&mut self, throttle_time_ms: i32) {
+        self.throttle_time_ms = throttle_time_ms;
+    }
+}
@@ -0,0 +1,629 @@
+use std::collections::BTreeSet;
+
+use anyhow::Result;
+use uuid::Uuid;
+
+use super::{
+    alter_configs::{config_records, ConfigChanges},
+    api_keys::{RequestContext, RequestHandler},
+    body::ResponseBody,
+    cluster_metadata::{MetadataRecord, TopicRecord},
+    error,
+    primitive::{CompactArray, CompactString, Serializable, TagField, TagSection, UnsignedVarint},
+    response::Response,
+};
+use crate::{
+    broker::Broker,
+    config_registry::{self, ConfigResolver, ConfigResource, ConfigResourceType, ConfigSource},
+    controller,
+    metadata::{random_uuid, MetadataImage},
+    raft::RaftError,
+    security::acl::{AclOperation, ResourceType, CLUSTER_RESOURCE_NAME},
+    server::ConnectionContext,
+};
+
+/// Longest topic name, leaving room for the partition suffix of directory names.
+const MAX_TOPIC_NAME_LENGTH: usize = 249;
+
+/// Reason `name` cannot name a topic, if any.
+pub fn validate_topic_name(name: &str) -> Result<(), String> {
+    if name.is_empty() {
+        return Err("Topic name is illegal, it can't be empty".to_string());
+    }
+    if name == "." || name == ".." {
+        return Err("Topic name cannot be \".\" or \"..\"".to_string());
+    }
+    if name.len() > MAX_TOPIC_NAME_LENGTH {
+        return Err(format!(
+            "Topic name is illegal, it can't be longer than {MAX_TOPIC_NAME_LENGTH} characters, \
+             topic name: {name}"
+        ));
+    }
+    if !name
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '.' || c == '_' || c == '-')
+    {
+        return Err(format!(
+            "Topic name \"{name}\" is illegal, it contains a character other than ASCII \
+             alphanumerics, '.', '_' and '-'"
+        ));
+    }
+    Ok(())
+}
+
+#[derive(Debug)]
+pub struct CreatableReplicaAssignment {
+    pub partition_index: i32,
+    pub broker_ids: CompactArray<i32>,
+    pub tag_buffer: TagSection,
+}
+
+impl Serializable for CreatableReplicaAssignment {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(self.partition_index.to_be_bytes());
+        buf.extend(self.broker_ids.serialize());
+        buf.extend(self.tag_buffer.serialize());
+        buf
+    }
+
+    fn deserialize(bytes: &[u8]) -> Result<(Self, &[u8])> {
+        let (partition_index, bytes) = i32::deserialize(bytes)?;
+        let (broker_ids, bytes) = CompactArray::<i32>::deserialize(bytes)?;
+        let (tag_buffer, bytes) = TagSection::deserialize(bytes)?;
+        Ok((
+            CreatableReplicaAssignment {
+                partition_index,
+                broker_ids,
+                tag_buffer,
+            },
+            bytes,
+        ))
+    }
+}
+
+#[derive(Debug)]
+pub struct CreatableTopicConfig {
+    pub name: CompactString,
+    pub value: CompactString,
+    pub tag_buffer: TagSection,
+}
+
+impl Serializable for CreatableTopicConfig {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(self.name.serialize());
+        buf.extend(self.value.serialize());
+        buf.extend(self.tag_buffer.serialize());
+        buf
+    }
+
+    fn deserialize(bytes: &[u8]) -> Result<(Self, &[u8])> {
+        let (name, bytes) = CompactString::deserialize(bytes)?;
+        let (value, bytes) = CompactString::deserialize(bytes)?;
+        let (tag_buffer, bytes) = TagSection::deserialize(bytes)?;
+        Ok((
+            CreatableTopicConfig {
+                name,
+                value,
+                tag_buffer,
+            },
+            bytes,
+        ))
+    }
+}
+
+#[derive(Debug)]
+pub struct CreatableTopic {
+    pub name: CompactString,
+    /// -1 for `num.partitions`, or when the assignments are given
+    pub num_partitions: i32,
+    /// -1 for `default.replication.factor`, or when the assignments are given
+    pub replication_factor: i16,
+    /// Replicas of each partition, empty to let the controller place them
+    pub assignments: CompactArray<CreatableReplicaAssignment>,
+    pub configs: CompactArray<CreatableTopicConfig>,
+    pub tag_buffer: TagSection,
+}
+
+impl Serializable for CreatableTopic {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(self.name.serialize());
+        buf.extend(self.num_partitions.to_be_bytes());
+        buf.extend(self.replication_factor.to_be_bytes());
+        buf.extend(self.assignments.serialize());
+        buf.extend(self.configs.serialize());
+        buf.extend(self.tag_buffer.serialize());
+        buf
+    }
+
+    fn deserialize(bytes: &[u8]) -> Result<(Self, &[u8])> {
+        let (name, bytes) = CompactString::deserialize(bytes)?;
+        let (num_partitions, bytes) = i32::deserialize(bytes)?;
+        let (replication_factor, bytes) = i16::deserialize(bytes)?;
+        let (assignments, bytes) = CompactArray::<CreatableReplicaAssignment>::deserialize(bytes)?;
+        let (configs, bytes) = CompactArray::<CreatableTopicConfig>::deserialize(bytes)?;
+        let (tag_buffer, bytes) = TagSection::deserialize(bytes)?;
+        Ok((
+            CreatableTopic {
+                name,
+                num_partitions,
+                replication_factor,
+                assignments,
+                configs,
+                tag_buffer,
+            },
+            bytes,
+        ))
+    }
+}
+
+/// CreateTopics versions 5 to 7. Partitions without explicit assignments are placed
+/// across the brokers that may lead them, racks first. Version 7 reports topic ids.
+#[derive(Debug)]
+pub struct CreateTopicsRequest {
+    pub topics: CompactArray<CreatableTopic>,
+    pub timeout_ms: i32,
+    pub validate_only: bool,
+    pub tag_buffer: TagSection,
+}
+
+impl Serializable for CreateTopicsRequest {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(self.topics.serialize());
+        buf.extend(self.timeout_ms.to_be_bytes());
+        buf.extend(self.validate_only.serialize());
+        buf.extend(self.tag_buffer.serialize());
+        buf
+    }
+
+    fn deserialize(bytes: &[u8]) -> Result<(Self, &[u8])> {
+        let (topics, bytes) = CompactArray::<CreatableTopic>::deserialize(bytes)?;
+        let (timeout_ms, bytes) = i32::deserialize(bytes)?;
+        let (validate_only, bytes) = bool::deserialize(bytes)?;
+        let (tag_buffer, bytes) = TagSection::deserialize(bytes)?;
+        Ok((
+            CreateTopicsRequest {
+                topics,
+                timeout_ms,
+                validate_only,
+                tag_buffer,
+            },
+            bytes,
+        ))
+    }
+}
+
+/// A topic of the request that passed the checks not depending on the metadata image.
+struct TopicCreation<'a> {
+    topic: &'a CreatableTopic,
+    num_partitions: i32,
+    replication_factor: i32,
+    configs: ConfigChanges,
+    config_records: Vec<MetadataRecord>,
+}
+
+impl CreateTopicsRequest {
+    pub fn handle_request(
+        &self,
+        version: i16,
+        broker: &Broker,
+        context: &ConnectionContext,
+    ) -> Option<Response> {
+        let topics: Vec<&CreatableTopic> = self.topics.iter().flatten().collect();
+        let mut names = BTreeSet::new();
+        let duplicates: BTreeSet<&str> = topics
+            .iter()
+            .map(|topic| topic.name.as_deref().unwrap_or_default())
+            .filter(|name| !names.insert(*name))
+            .collect();
+        let cluster_authorized = broker.authorize(
+            context,
+            AclOperation::Create,
+            ResourceType::Cluster,
+            CLUSTER_RESOURCE_NAME,
+        );
+
+        let mut outcomes: Vec<Result<TopicCreation, (i16, String)>> = topics
+            .iter()
+            .map(|topic| {
+                let name = topic.name.as_deref().unwrap_or_default();
+                if duplicates.contains(name) {
+                    return Err((
+                        error::INVALID_REQUEST,
+                        "Found multiple entries for this topic".to_string(),
+                    ));
+                }
+                if !cluster_authorized
+                    && !broker.authorize(context, AclOperation::Create, ResourceType::Topic, name)
+                {
+                    return Err((
+                        error::TOPIC_AUTHORIZATION_FAILED,
+                        "Authorization failed".to_string(),
+                    ));
+                }
+                if !broker.raft.is_leader() {
+                    return Err((
+                        error::NOT_CONTROLLER,
+                        "This node is not the active controller".to_string(),
+                    ));
+                }
+                self.check_topic(broker, topic)
+            })
+            .collect();
+
+        let mut results: Vec<(Uuid, i32, i32)> = vec![(Uuid::nil(), -1, -1); outcomes.len()];
+        let mut create = |image: &MetadataImage| {
+            let mut records = vec![];
+            for (outcome, result) in outcomes.iter_mut().zip(&mut results) {
+                let Ok(creation) = outcome else {
+                    continue;
+                };
+                match create_topic(image, creation) {
+                    Ok((topic_id, topic_records)) => {
+                        *result = (
+                            topic_id,
+                            creation.num_partitions,
+                            creation.replication_factor,
+                        );
+                        records.extend(topic_records);
+                    }
+                    Err(e) => *outcome = Err(e),
+                }
+            }
+            records
+        };
+        if self.validate_only {
+            create(&broker.metadata.read().unwrap());
+        } else {
+            let committed = broker.append_metadata(|_| create(&broker.metadata.read().unwrap()));
+            if let Err(e) = committed {
+                eprintln!("Failed to create topics: {e:#}");
+                for outcome in outcomes.iter_mut().filter(|o| o.is_ok()) {
+                    *outcome = Err((
+                        RaftError::error_code(&e),
+                        "Failed to persist the topic".to_string(),
+                    ));
+                }
+            }
+        }
+
+        let metadata = broker.metadata.read().unwrap();
+        let resolver = ConfigResolver::new(&metadata, &broker.config);
+        let responses = topics
+            .iter()
+            .zip(outcomes)
+            .zip(results)
+            .map(
+                |((topic, outcome), (topic_id, num_partitions, replication_factor))| {
+                    let name = topic.name.0.clone().unwrap_or_default();
+                    let mut result = CreatableTopicResult {
+                        name: name.clone(),
+                        topic_id,
+                        error_code: error::NONE,
+                        error_message: None,
+                        topic_config_error_code: None,
+                        num_partitions,
+                        replication_factor: replication_factor as i16,
+                        configs: None,
+                    };
+                    match outcome {
+                        Ok(creation) => {
+                            if self.validate_only {
+                                result.topic_id = Uuid::nil();
+                            } else {
+                                eprintln!(
+                                    "Created topic {name} with {num_partitions} partitions and \
+                                 replication factor {replication_factor}"
+                                );
+                            }
+                            if broker.authorize(
+                                context,
+                                AclOperation::DescribeConfigs,
+                                ResourceType::Topic,
+                                &name,
+                            ) {
+                                result.configs = Some(topic_configs(&resolver, &name, &creation));
+                            } else {
+                                result.topic_config_error_code =
+                                    Some(error::TOPIC_AUTHORIZATION_FAILED);
+                            }
+                        }
+                        Err((error_code, message)) => {
+                            result.error_code = error_code;
+                            result.error_message = Some(message);
+                        }
+                    }
+                    result
+                },
+            )
+            .collect();
+
+        Some(Box::new(CreateTopicsResponse {
+            version,
+            throttle_time_ms: 0,
+            topics: responses,
+        }))
+    }
+
+    /// Check a topic against the request itself and the config registry, resolving its
+    /// partition count and replication factor.
+    fn check_topic<'a>(
+        &self,
+        broker: &Broker,
+        topic: &'a CreatableTopic,
+    ) -> Result<TopicCreation<'a>, (i16, String)> {
+        let name = topic.name.as_deref().unwrap_or_default();
+        validate_topic_name(name).map_err(|reason| (error::INVALID_TOPIC_EXCEPTION, reason))?;
+
+        let assignments = topic.assignments.as_deref().unwrap_or_default();
+        let (num_partitions, replication_factor) = if assignments.is_empty() {
+            let metadata = broker.metadata.read().unwrap();
+            let resolver = ConfigResolver::new(&metadata, &broker.config);
+            let num_partitions = match topic.num_partitions {
+                -1 => resolver.broker_config("num.partitions"),
+                n if n <= 0 => {
+                    return Err((
+                        error::INVALID_PARTITIONS,
+                        "Number of partitions was set to an invalid non-positive value."
+                            .to_string(),
+                    ))
+                }
+                n => n,
+            };
+            let replication_factor = match topic.replication_factor {
+                -1 => resolver.broker_config("default.replication.factor"),
+                n if n <= 0 => {
+                    return Err((
+                        error::INVALID_REPLICATION_FACTOR,
+                        "Replication factor must be larger than 0, or -1 to use the default \
+                         value."
+                            .to_string(),
+                    ))
+                }
+                n => n as i32,
+            };
+            (num_partitions, replication_factor)
+        } else {
+            if topic.num_partitions != -1 || topic.replication_factor != -1 {
+                return Err((
+                    error::INVALID_REQUEST,
+                    "Both numPartitions or replicationFactor and replicasAssignments were set. \
+                     Both cannot be used at the same time."
+                        .to_string(),
+                ));
+            }
+            let first = assignments[0].broker_ids.as_deref().unwrap_or_default();
+            (assignments.len() as i32, first.len() as i32)
+        };
+
+        let mut configs = ConfigChanges::new();
+        for config in topic.configs.iter().flatten() {
+            let config_name = config.name.as_deref().unwrap_or_default();
+            let Some(value) = config.value.as_deref() else {
+                return Err((
+                    error::INVALID_CONFIG,
+                    format!("Null value not supported for topic configs: {config_name}"),
+                ));
+            };
+            if configs
+                .insert(config_name.to_string(), Some(value.to_string()))
+                .is_some()
+            {
+                return Err((
+                    error::INVALID_REQUEST,
+                    format!("Duplicate config {config_name}"),
+                ));
+            }
+        }
+        let resource = ConfigResource::new(ConfigResourceType::Topic, name);
+        let config_records = config_records(broker, &resource, &configs)?;
+        Ok(TopicCreation {
+            topic,
+            num_partitions,
+            replication_factor,
+            configs,
+            config_records,
+        })
+    }
+}
+
+impl RequestHandler for CreateTopicsRequest {
+    fn read(bytes: &[u8], _version: i16) -> Result<Self> {
+        Ok(CreateTopicsRequest::deserialize(bytes)?.0)
+    }
+
+    fn handle(&self, request: &mut RequestContext) -> Option<Response> {
+        self.handle_request(request.api_version, request.broker, request.connection)
+    }
+}
+
+/// Records creating the topic in the image, with the id they give it.
+fn create_topic(
+    image: &MetadataImage,
+    creation: &TopicCreation,
+) -> Result<(Uuid, Vec<MetadataRecord>), (i16, String)> {
+    let name = creation.topic.name.as_deref().unwrap_or_default();
+    if image.topics.contains_key(name) {
+        return Err((
+            error::TOPIC_ALREADY_EXISTS,
+            format!("Topic '{name}' already exists."),
+        ));
+    }
+
+    let assignments = creation.topic.assignments.as_deref().unwrap_or_default();
+    let replicas = if assignments.is_empty() {
+        controller::place_replicas(
+            image,
+            0,
+            creation.num_partitions,
+            creation.replication_factor,
+        )
+        .map_err(|error_code| {
+            (
+                error_code,
+                format!(
+                    "Unable to replicate the partition {} time(s): there are not enough \
+                     brokers that may host replicas",
+                    creation.replication_factor
+                ),
+            )
+        })?
+    } else {
+        let mut replicas = vec![];
+        for (index, assignment) in assignments.iter().enumerate() {
+            let broker_ids = assignment.broker_ids.as_deref().unwrap_or_default();
+            if assignment.partition_index != index as i32 {
+                return Err((
+                    error::INVALID_REPLICA_ASSIGNMENT,
+                    "Partitions must be numbered consecutively from 0".to_string(),
+                ));
+            }
+            controller::check_assignment(image, broker_ids).map_err(|error_code| {
+                (
+                    error_code,
+                    format!(
+                        "Invalid replicas {broker_ids:?} for partition {}",
+                        assignment.partition_index
+                    ),
+                )
+            })?;
+            replicas.push(broker_ids.to_vec());
+        }
+        replicas
+    };
+
+    let topic_id = random_uuid();
+    let mut records = vec![MetadataRecord::Topic(TopicRecord {
+        name: name.to_string(),
+        topic_id,
+    })];
+    for (partition, replicas) in replicas.into_iter().enumerate() {
+        let record = controller::new_partition(image, topic_id, partition as i32, replicas)
+            .map_err(|error_code| {
+                (
+                    error_code,
+                    format!(
+                        "All brokers specified for partition {partition} are fenced or in \
+                         controlled shutdown"
+                    ),
+                )
+            })?;
+        records.push(MetadataRecord::Partition(record));
+    }
+    records.extend(creation.config_records.iter().cloned());
+    Ok((topic_id, records))
+}
+
+/// Configs of the new topic, those given in the request taking precedence over the
+/// broker defaults.
+fn topic_configs(
+    resolver: &ConfigResolver,
+    name: &str,
+    creation: &TopicCreation,
+) -> Vec<CreatableTopicConfigs> {
+    let resource = ConfigResource::new(ConfigResourceType::Topic, name);
+    config_registry::TOPIC_CONFIGS
+        .iter()
+        .map(|def| {
+            let (value, source) = match creation.configs.get(def.name) {
+                Some(value) => (value.clone(), ConfigSource::DynamicTopic),
+                None => {
+                    let resolved = resolver.resolve(&resource, def);
+                    (resolved.value, resolved.source)
+                }
+            };
+            CreatableTopicConfigs {
+                name: def.name.to_string(),
+                value: if def.is_sensitive() { None } else { value },
+                read_only: false,
+                config_source: source.code(),
+                is_sensitive: def.is_sensitive(),
+            }
+        })
+        .collect()
+}
+
+#[derive(Debug)]
+pub struct CreatableTopicConfigs {
+    pub name: String,
+    pub value: Option<String>,
+    pub read_only: bool,
+    pub config_source: i8,
+    pub is_sensitive: bool,
+}
+
+#[derive(Debug)]
+pub struct CreatableTopicResult {
+    pub name: String,
+    /// Version 7 and above
+    pub topic_id: Uuid,
+    pub error_code: i16,
+    pub error_message: Option<String>,
+    /// Tagged field 0, set when the configs cannot be described to the client
+    pub topic_config_error_code: Option<i16>,
+    pub num_partitions: i32,
+    pub replication_factor: i16,
+    /// Null when the topic was not created or its configs cannot be described
+    pub configs: Option<Vec<CreatableTopicConfigs>>,
+}
+
+#[derive(Debug)]
+pub struct CreateTopicsResponse {
+    pub version: i16,
+    pub throttle_time_ms: i32,
+    pub topics: Vec<CreatableTopicResult>,
+}
+
+impl Serializable for CreateTopicsResponse {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(self.throttle_time_ms.to_be_bytes());
+        buf.extend(UnsignedVarint(self.topics.len() as u32 + 1).serialize());
+        for topic in &self.topics {
+            buf.extend(CompactString(Some(topic.name.clone())).serialize());
+            if self.version >= 7 {
+                buf.extend(topic.topic_id.serialize());
+            }
+            buf.extend(topic.error_code.to_be_bytes());
+            buf.extend(CompactString(topic.error_message.clone()).serialize());
+            buf.extend(topic.num_partitions.to_be_bytes());
+            buf.extend(topic.replication_factor.to_be_bytes());
+            match &topic.configs {
+                Some(configs) => {
+                    buf.extend(UnsignedVarint(configs.len() as u32 + 1).serialize());
+                    for config in configs {
+                        buf.extend(CompactString(Some(config.name.clone())).serialize());
+                        buf.extend(CompactString(config.value.clone()).serialize());
+                        buf.extend(config.read_only.serialize());
+                        buf.push(config.config_source as u8);
+                        buf.extend(config.is_sensitive.serialize());
+                        buf.extend(TagSection(None).serialize());
+                    }
+                }
+                None => buf.extend(UnsignedVarint(0).serialize()),
+            }
+            let tags = topic.topic_config_error_code.map(|error_code| {
+                vec![TagField {
+                    tag: 0,
+                    data: error_code.to_be_bytes().to_vec(),
+                }]
+            });
+            buf.extend(TagSection(tags).serialize());
+        }
+        buf.extend(TagSection(None).serialize());
+        buf
+    }
+
+    fn deserialize(_bytes: &[u8]) -> Result<(Self, &[u8])> {
+        todo!()
+    }
+}
+
+impl ResponseBody for CreateTopicsResponse {
+    fn set_throttle_time_ms(&mut self, throttle_time_ms: i32) {
+        self.throttle_time_ms = throttle_time_ms;
+    }
+}
@@ -0,0 +1,316 @@
+use std::collections::HashSet;
+
+use anyhow::Result;
+
+use super::{
+    api_keys::{RequestContext, RequestHandler},
+    body::ResponseBody,
+    cluster_metadata::{MetadataRecord, RemoveAccessControlEntryRecord},
+    error,
+    primitive::{CompactArray, CompactString, Serializable, TagSection},
+    response::Response,
+};
+use crate::{
+    broker::Broker,
+    raft::RaftError,
+    security::acl::{
+        AclBinding, AclBindingFilter, AclOperation, AclPermissionType, PatternType, ResourceType,
+        CLUSTER_RESOURCE_NAME,
+    },
+    server::ConnectionContext,
+};
+
+#[derive(Debug)]
+pub struct DeleteAclsFilter {
+    pub resource_type_filter: i8,
+    pub resource_name_filter: CompactString,
+    pub pattern_type_filter: i8,
+    pub principal_filter: CompactString,
+    pub host_filter: CompactString,
+    pub operation: i8,
+    pub permission_type: i8,
+    pub tag_buffer: TagSection,
+}
+
+impl DeleteAclsFilter {
+    fn filter(&self) -> AclBindingFilter {
+        AclBindingFilter {
+            resource_type: ResourceType::from_code(self.resource_type_filter),
+            resource_name: self.resource_name_filter.0.clone(),
+            pattern_type: PatternType::from_code(self.pattern_type_filter),
+            principal: self.principal_filter.0.clone(),
+            host: self.host_filter.0.clone(),
+            operation: AclOperation::from_code(self.operation),
+            permission_type: AclPermissionType::from_code(self.permission_type),
+        }
+    }
+}
+
+impl Serializable for DeleteAclsFilter {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.push(self.resource_type_filter as u8);
+        buf.extend(self.resource_name_filter.serialize());
+        buf.push(self.pattern_type_filter as u8);
+        buf.extend(self.principal_filter.serialize());
+        buf.extend(self.host_filter.serialize());
+        buf.push(self.operation as u8);
+        buf.push(self.permission_type as u8);
+        buf.extend(self.tag_buffer.serialize());
+        buf
+    }
+
+    fn deserialize(bytes: &[u8]) -> Result<(Self, &[u8])> {
+        let (resource_type_filter, bytes) = i8::deserialize(bytes)?;
+        let (resource_name_filter, bytes) = CompactString::deserialize(bytes)?;
+        let (pattern_type_filter, bytes) = i8::deserialize(bytes)?;
+        let (principal_filter, bytes) = CompactString::deserialize(bytes)?;
+        let (host_filter, bytes) = CompactString::deserialize(bytes)?;
+        let (operation, bytes) = i8::deserialize(bytes)?;
+        let (permission_type, bytes) = i8::deserialize(bytes)?;
+        let (tag_buffer, bytes) = TagSection::deserialize(bytes)?;
+        Ok((
+            DeleteAclsFilter {
+                resource_type_filter,
+                resource_name_filter,
+                pattern_type_filter,
+                principal_filter,
+                host_filter,
+                operation,
+                permission_type,
+                tag_buffer,
+            },
+            bytes,
+        ))
+    }
+}
+
+#[derive(Debug)]
+pub struct DeleteAclsRequest {
+    pub filters: CompactArray<DeleteAclsFilter>,
+    pub tag_buffer: TagSection,
+}
+
+impl Serializable for DeleteAclsRequest {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(self.filters.serialize());
+        buf.extend(self.tag_buffer.serialize());
+        buf
+    }
+
+    fn deserialize(bytes: &[u8]) -> Result<(Self, &[u8])> {
+        let (filters, bytes) = CompactArray::<DeleteAclsFilter>::deserialize(bytes)?;
+        let (tag_buffer, bytes) = TagSection::deserialize(bytes)?;
+        Ok((
+            DeleteAclsRequest {
+                filters,
+                tag_buffer,
+            },
+            bytes,
+        ))
+    }
+}
+
+impl DeleteAclsRequest {
+    pub fn handle_request(&self, broker: &Broker, context: &ConnectionContext) -> Option<Response> {
+        let filters = self.filters.as_deref().unwrap_or_default();
+        let denial = if !broker.has_authorizer() {
+            Some((error::SECURITY_DISABLED, "No Authorizer is configured"))
+        } else if !broker.authorize(
+            context,
+            AclOperation::Alter,
+            ResourceType::Cluster,
+            CLUSTER_RESOURCE_NAME,
+        ) {
+            Some((
+                error::CLUSTER_AUTHORIZATION_FAILED,
+                "Cluster authorization failed",
+            ))
+        } else {
+            None
+        };
+
+        let filter_results = match denial {
+            Some((error_code, message)) => filters
+                .iter()
+                .map(|_| DeleteAclsFilterResult::error(error_code, message.to_string()))
+                .collect(),
+            None => {
+                let mut results = vec![];
+                let mut records = vec![];
+                let mut removed = HashSet::new();
+                {
+                    let metadata = broker.metadata.read().unwrap();
+                    for filter in filters.iter().map(DeleteAclsFilter::filter) {
+                        if let Err(message) = filter.validate() {
+                            results.push(DeleteAclsFilterResult::error(
+                                error::INVALID_REQUEST,
+                                message,
+                            ));
+                            continue;
+                        }
+                        // A binding matched by several filters is reported by each of them
+                        let mut matching_acls = vec![];
+                        for (id, acl) in &metadata.acls {
+                            if !filter.matches(acl) {
+                                continue;
+                            }
+                            if removed.insert(*id) {
+                                records.push(MetadataRecord::RemoveAccessControlEntry(
+                                    RemoveAccessControlEntryRecord { id: *id },
+                                ));
+                            }
+                            matching_acls.push(DeleteAclsMatchingAcl::from(acl));
+                        }
+                        results.push(DeleteAclsFilterResult {
+                            error_code: error::NONE,
+                            error_message: CompactString(None),
+                            matching_acls: CompactArray(Some(matching_acls)),
+                            tag_buffer: TagSection(None),
+                        });
+                    }
+                }
+                if let Err(e) = broker.commit_metadata(records) {
+                    eprintln!("Failed to delete ACLs: {e:#}");
+                    for result in results.iter_mut().filter(|r| r.error_code == error::NONE) {
+                        *result = DeleteAclsFilterResult::error(
+                            RaftError::error_code(&e),
+                            "Failed to persist the ACL deletion".to_string(),
+                        );
+                    }
+                }
+                results
+            }
+        };
+
+        Some(Box::new(DeleteAclsResponse {
+            throttle_time_ms: 0,
+            filter_results: CompactArray(Some(filter_results)),
+            tag_buffer: TagSection(None),
+        }))
+    }
+}
+
+impl RequestHandler for DeleteAclsRequest {
+    fn read(bytes: &[u8], _version: i16) -> Result<Self> {
+        Ok(DeleteAclsRequest::deserialize(bytes)?.0)
+    }
+
+    fn handle(&self, request: &mut RequestContext) -> Option<Response> {
+        self.handle_request(request.broker, request.connection)
+    }
+}
+
+#[derive(Debug)]
+pub struct DeleteAclsMatchingAcl {
+    pub error_code: i16,
+    pub error_message: CompactString,
+    pub resource_type: i8,
+    pub resource_name: CompactString,
+    pub pattern_type: i8,
+    pub principal: CompactString,
+    pub host: CompactString,
+    pub operation: i8,
+    pub permission_type: i8,
+    pub tag_buffer: TagSection,
+}
+
+impl From<&AclBinding> for DeleteAclsMatchingAcl {
+    fn from(acl: &AclBinding) -> Self {
+        DeleteAclsMatchingAcl {
+            error_code: error::NONE,
+            error_message: CompactString(None),
+            resource_type: acl.resource_type.code(),
+            resource_name: CompactString(Some(acl.resource_name.clone())),
+            pattern_type: acl.pattern_type.code(),
+            principal: CompactString(Some(acl.principal.clone())),
+            host: CompactString(Some(acl.host.clone())),
+            operation: acl.operation.code(),
+            permission_type: acl.permission_type.code(),
+            tag_buffer: TagSection(None),
+        }
+    }
+}
+
+impl Serializable for DeleteAclsMatchingAcl {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(self.error_code.to_be_bytes());
+        buf.extend(self.error_message.serialize());
+        buf.push(self.resource_type as u8);
+        buf.extend(self.resource_name.serialize());
+        buf.push(self.pattern_type as u8);
+        buf.extend(self.principal.serialize());
+        buf.extend(self.host.serialize());
+        buf.push(self.operation as u8);
+        buf.push(self.permission_type as u8);
+        buf.extend(self.tag_buffer.serialize());
+        buf
+    }
+
+    fn deserialize(_bytes: &[u8]) -> Result<(Self, &[u8])> {
+        todo!()
+    }
+}
+
+#[derive(Debug)]
+pub struct DeleteAclsFilterResult {
+    pub error_code: i16,
+    pub error_message: CompactString,
+    pub matching_acls: CompactArray<DeleteAclsMatchingAcl>,
+    pub tag_buffer: TagSection,
+}
+
+impl DeleteAclsFilterResult {
+    fn error(error_code: i16, message: String) -> Self {
+        DeleteAclsFilterResult {
+            error_code,
+            error_message: CompactString(Some(message)),
+            matching_acls: CompactArray(Some(vec![])),
+            tag_buffer: TagSection(None),
+        }
+    }
+}
+
+impl Serializable for DeleteAclsFilterResult {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(self.error_code.to_be_bytes());
+        buf.extend(self.error_message.serialize());
+        buf.extend(self.matching_acls.serialize());
+        buf.extend(self.tag_buffer.serialize());
+        buf
+    }
+
+    fn deserialize(_bytes: &[u8]) -> Result<(Self, &[u8])> {
+        todo!()
+    }
+}
+
+#[derive(Debug)]
+pub struct DeleteAclsResponse {
+    pub throttle_time_ms: i32,
+    pub filter_results: CompactArray<DeleteAclsFilterResult>,
+    pub tag_buffer: TagSection,
+}
+
+impl Serializable for DeleteAclsResponse {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(self.throttle_time_ms.to_be_bytes());
+        buf.extend(self.filter_results.serialize());
+        buf.extend(self.tag_buffer.serialize());
+        buf
+    }
+
+    fn deserialize(_bytes: &[u8]) -> Result<(Self, &[u8])> {
+        todo!()
+    }
+}
+
+impl ResponseBody for DeleteAclsResponse {
+    fn set_throttle_time_ms(&mut self, throttle_time_ms: i32) {
+        self.throttle_time_ms = throttle_time_ms;
+    }
+}
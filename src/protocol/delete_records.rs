@@ -0,0 +1,242 @@
+use anyhow::Result;
+
+use super::{
+    api_keys::{RequestContext, RequestHandler},
+    body::ResponseBody,
+    error,
+    primitive::{CompactArray, CompactString, Serializable, TagSection},
+    response::Response,
+};
+use crate::{
+    broker::Broker,
+    log_manager::TopicPartition,
+    security::acl::{AclOperation, ResourceType},
+    server::ConnectionContext,
+};
+
+#[derive(Debug)]
+pub struct DeleteRecordsPartition {
+    pub partition_index: i32,
+    /// Records before this offset are deleted, -1 for the high watermark
+    pub offset: i64,
+    pub tag_buffer: TagSection,
+}
+
+impl Serializable for DeleteRecordsPartition {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(self.partition_index.to_be_bytes());
+        buf.extend(self.offset.to_be_bytes());
+        buf.extend(self.tag_buffer.serialize());
+        buf
+    }
+
+    fn deserialize(bytes: &[u8]) -> Result<(Self, &[u8])> {
+        let (partition_index, bytes) = i32::deserialize(bytes)?;
+        let (offset, bytes) = i64::deserialize(bytes)?;
+        let (tag_buffer, bytes) = TagSection::deserialize(bytes)?;
+        Ok((
+            DeleteRecordsPartition {
+                partition_index,
+                offset,
+                tag_buffer,
+            },
+            bytes,
+        ))
+    }
+}
+
+#[derive(Debug)]
+pub struct DeleteRecordsTopic {
+    pub name: CompactString,
+    pub partitions: CompactArray<DeleteRecordsPartition>,
+    pub tag_buffer: TagSection,
+}
+
+impl Serializable for DeleteRecordsTopic {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(self.name.serialize());
+        buf.extend(self.partitions.serialize());
+        buf.extend(self.tag_buffer.serialize());
+        buf
+    }
+
+    fn deserialize(bytes: &[u8]) -> Result<(Self, &[u8])> {
+        let (name, bytes) = CompactString::deserialize(bytes)?;
+        let (partitions, bytes) = CompactArray::<DeleteRecordsPartition>::deserialize(bytes)?;
+        let (tag_buffer, bytes) = TagSection::deserialize(bytes)?;
+        Ok((
+            DeleteRecordsTopic {
+                name,
+                partitions,
+                tag_buffer,
+            },
+            bytes,
+        ))
+    }
+}
+
+#[derive(Debug)]
+pub struct DeleteRecordsRequest {
+    pub topics: CompactArray<DeleteRecordsTopic>,
+    pub timeout_ms: i32,
+    pub tag_buffer: TagSection,
+}
+
+impl Serializable for DeleteRecordsRequest {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(self.topics.serialize());
+        buf.extend(self.timeout_ms.to_be_bytes());
+        buf.extend(self.tag_buffer.serialize());
+        buf
+    }
+
+    fn deserialize(bytes: &[u8]) -> Result<(Self, &[u8])> {
+        let (topics, bytes) = CompactArray::<DeleteRecordsTopic>::deserialize(bytes)?;
+        let (timeout_ms, bytes) = i32::deserialize(bytes)?;
+        let (tag_buffer, bytes) = TagSection::deserialize(bytes)?;
+        Ok((
+            DeleteRecordsRequest {
+                topics,
+                timeout_ms,
+                tag_buffer,
+            },
+            bytes,
+        ))
+    }
+}
+
+impl DeleteRecordsRequest {
+    pub fn handle_request(&self, broker: &Broker, context: &ConnectionContext) -> Option<Response> {
+        let topics = self
+            .topics
+            .iter()
+            .flatten()
+            .map(|topic| {
+                let name = topic.name.as_deref().unwrap_or_default();
+                let authorized =
+                    broker.authorize(context, AclOperation::Delete, ResourceType::Topic, name);
+                let partitions = topic
+                    .partitions
+                    .iter()
+                    .flatten()
+                    .map(|partition| {
+                        let outcome = if authorized {
+                            let replica = TopicPartition::new(name, partition.partition_index);
+                            broker
+                                .replicas
+                                .delete_records(broker, &replica, partition.offset)
+                        } else {
+                            Err(error::TOPIC_AUTHORIZATION_FAILED)
+                        };
+                        let (low_watermark, error_code) = match outcome {
+                            Ok(low_watermark) => (low_watermark, error::NONE),
+                            Err(error_code) => (-1, error_code),
+                        };
+                        DeleteRecordsPartitionResult {
+                            partition_index: partition.partition_index,
+                            low_watermark,
+                            error_code,
+                            tag_buffer: TagSection(None),
+                        }
+                    })
+                    .collect();
+                DeleteRecordsTopicResult {
+                    name: topic.name.clone(),
+                    partitions: CompactArray(Some(partitions)),
+                    tag_buffer: TagSection(None),
+                }
+            })
+            .collect();
+
+        Some(Box::new(DeleteRecordsResponse {
+            throttle_time_ms: 0,
+            topics: CompactArray(Some(topics)),
+            tag_buffer: TagSection(None),
+        }))
+    }
+}
+
+impl RequestHandler for DeleteRecordsRequest {
+    fn read(bytes: &[u8], _version: i16) -> Result<Self> {
+        Ok(DeleteRecordsRequest::deserialize(bytes)?.0)
+    }
+
+    fn handle(&self, request: &mut RequestContext) -> Option<Response> {
+        self.handle_request(request.broker, request.connection)
+    }
+}
+
+#[derive(Debug)]
+pub struct DeleteRecordsPartitionResult {
+    pub partition_index: i32,
+    /// Log start offset of the partition after the deletion
+    pub low_watermark: i64,
+    pub error_code: i16,
+    pub tag_buffer: TagSection,
+}
+
+impl Serializable for DeleteRecordsPartitionResult {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(self.partition_index.to_be_bytes());
+        buf.extend(self.low_watermark.to_be_bytes());
+        buf.extend(self.error_code.to_be_bytes());
+        buf.extend(self.tag_buffer.serialize());
+        buf
+    }
+
+    fn deserialize(_bytes: &[u8]) -> Result<(Self, &[u8])> {
+        todo!()
+    }
+}
+
+#[derive(Debug)]
+pub struct DeleteRecordsTopicResult {
+    pub name: CompactString,
+    pub partitions: CompactArray<DeleteRecordsPartitionResult>,
+    pub tag_buffer: TagSection,
+}
+
+impl Serializable for DeleteRecordsTopicResult {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(self.name.serialize());
+        buf.extend(self.partitions.serialize());
+        buf.extend(self.tag_buffer.serialize());
+        buf
+    }
+
+    fn deserialize(_bytes: &[u8]) -> Result<(Self, &[u8])> {
+        todo!()
+    }
+}
+
+#[derive(Debug)]
+pub struct DeleteRecordsResponse {
+    pub throttle_time_ms: i32,
+    pub topics: CompactArray<DeleteRecordsTopicResult>,
+    pub tag_buffer: TagSection,
+}
+
+impl Serializable for DeleteRecordsResponse {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(self.throttle_time_ms.to_be_bytes());
+        buf.extend(self.topics.serialize());
+        buf.extend(self.tag_buffer.serialize());
+        buf
+    }
+
+    fn deserialize(_bytes: &[u8]) -> Result<(Self, &[u8])> {
+        todo!()
+    }
+}
+
+impl ResponseBody for DeleteRecordsResponse {
+    fn set_throttle_time_ms(&mut self, throttle_time_ms: i32) {
+        self.throttle_time_ms = throttle_time_ms;
+    }
+}
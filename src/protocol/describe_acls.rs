@@ -0,0 +1,246 @@
+use std::collections::BTreeMap;
+
+use anyhow::Result;
+
+use super::{
+    api_keys::{RequestContext, RequestHandler},
+    body::ResponseBody,
+    error,
+    primitive::{CompactArray, CompactString, Serializable, TagSection},
+    response::Response,
+};
+use crate::{
+    broker::Broker,
+    security::acl::{
+        AclBindingFilter, AclOperation, AclPermissionType, PatternType, ResourceType,
+        CLUSTER_RESOURCE_NAME,
+    },
+    server::ConnectionContext,
+};
+
+#[derive(Debug)]
+pub struct DescribeAclsRequest {
+    pub resource_type_filter: i8,
+    pub resource_name_filter: CompactString,
+    pub pattern_type_filter: i8,
+    pub principal_filter: CompactString,
+    pub host_filter: CompactString,
+    pub operation: i8,
+    pub permission_type: i8,
+    pub tag_buffer: TagSection,
+}
+
+impl Serializable for DescribeAclsRequest {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.push(self.resource_type_filter as u8);
+        buf.extend(self.resource_name_filter.serialize());
+        buf.push(self.pattern_type_filter as u8);
+        buf.extend(self.principal_filter.serialize());
+        buf.extend(self.host_filter.serialize());
+        buf.push(self.operation as u8);
+        buf.push(self.permission_type as u8);
+        buf.extend(self.tag_buffer.serialize());
+        buf
+    }
+
+    fn deserialize(bytes: &[u8]) -> Result<(Self, &[u8])> {
+        let (resource_type_filter, bytes) = i8::deserialize(bytes)?;
+        let (resource_name_filter, bytes) = CompactString::deserialize(bytes)?;
+        let (pattern_type_filter, bytes) = i8::deserialize(bytes)?;
+        let (principal_filter, bytes) = CompactString::deserialize(bytes)?;
+        let (host_filter, bytes) = CompactString::deserialize(bytes)?;
+        let (operation, bytes) = i8::deserialize(bytes)?;
+        let (permission_type, bytes) = i8::deserialize(bytes)?;
+        let (tag_buffer, bytes) = TagSection::deserialize(bytes)?;
+        Ok((
+            DescribeAclsRequest {
+                resource_type_filter,
+                resource_name_filter,
+                pattern_type_filter,
+                principal_filter,
+                host_filter,
+                operation,
+                permission_type,
+                tag_buffer,
+            },
+            bytes,
+        ))
+    }
+}
+
+impl DescribeAclsRequest {
+    fn filter(&self) -> AclBindingFilter {
+        AclBindingFilter {
+            resource_type: ResourceType::from_code(self.resource_type_filter),
+            resource_name: self.resource_name_filter.0.clone(),
+            pattern_type: PatternType::from_code(self.pattern_type_filter),
+            principal: self.principal_filter.0.clone(),
+            host: self.host_filter.0.clone(),
+            operation: AclOperation::from_code(self.operation),
+            permission_type: AclPermissionType::from_code(self.permission_type),
+        }
+    }
+
+    pub fn handle_request(&self, broker: &Broker, context: &ConnectionContext) -> Option<Response> {
+        let filter = self.filter();
+        let outcome = if !broker.has_authorizer() {
+            Err((
+                error::SECURITY_DISABLED,
+                "No Authorizer is configured".to_string(),
+            ))
+        } else if !broker.authorize(
+            context,
+            AclOperation::Describe,
+            ResourceType::Cluster,
+            CLUSTER_RESOURCE_NAME,
+        ) {
+            Err((
+                error::CLUSTER_AUTHORIZATION_FAILED,
+                "Cluster authorization failed".to_string(),
+            ))
+        } else {
+            filter.validate().map_err(|e| (error::INVALID_REQUEST, e))
+        };
+
+        let (error_code, error_message, resources) = match outcome {
+            Err((error_code, message)) => (error_code, Some(message), vec![]),
+            Ok(()) => {
+                // Group the matching bindings by resource pattern, in a stable order
+                let mut resources: BTreeMap<(i8, String, i8), Vec<AclDescription>> =
+                    BTreeMap::new();
+                for acl in broker.metadata.read().unwrap().acls.values() {
+                    if !filter.matches(acl) {
+                        continue;
+                    }
+                    resources
+                        .entry((
+                            acl.resource_type.code(),
+                            acl.resource_name.clone(),
+                            acl.pattern_type.code(),
+                        ))
+                        .or_default()
+                        .push(AclDescription {
+                            principal: CompactString(Some(acl.principal.clone())),
+                            host: CompactString(Some(acl.host.clone())),
+                            operation: acl.operation.code(),
+                            permission_type: acl.permission_type.code(),
+                            tag_buffer: TagSection(None),
+                        });
+                }
+                let resources = resources
+                    .into_iter()
+                    .map(|((resource_type, resource_name, pattern_type), acls)| {
+                        DescribeAclsResource {
+                            resource_type,
+                            resource_name: CompactString(Some(resource_name)),
+                            pattern_type,
+                            acls: CompactArray(Some(acls)),
+                            tag_buffer: TagSection(None),
+                        }
+                    })
+                    .collect();
+                (error::NONE, None, resources)
+            }
+        };
+
+        Some(Box::new(DescribeAclsResponse {
+            throttle_time_ms: 0,
+            error_code,
+            error_message: CompactString(error_message),
+            resources: CompactArray(Some(resources)),
+            tag_buffer: TagSection(None),
+        }))
+    }
+}
+
+impl RequestHandler for DescribeAclsRequest {
+    fn read(bytes: &[u8], _version: i16) -> Result<Self> {
+        Ok(DescribeAclsRequest::deserialize(bytes)?.0)
+    }
+
+    fn handle(&self, request: &mut RequestContext) -> Option<Response> {
+        self.handle_request(request.broker, request.connection)
+    }
+}
+
+#[derive(Debug)]
+pub struct AclDescription {
+    pub principal: CompactString,
+    pub host: CompactString,
+    pub operation: i8,
+    pub permission_type: i8,
+    pub tag_buffer: TagSection,
+}
+
+impl Serializable for AclDescription {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(self.principal.serialize());
+        buf.extend(self.host.serialize());
+        buf.push(self.operation as u8);
+        buf.push(self.permission_type as u8);
+        buf.extend(self.tag_buffer.serialize());
+        buf
+    }
+
+    fn deserialize(_bytes: &[u8]) -> Result<(Self, &[u8])> {
+        todo!()
+    }
+}
+
+#[derive(Debug)]
+pub struct DescribeAclsResource {
+    pub resource_type: i8,
+    pub resource_name: CompactString,
+    pub pattern_type: i8,
+    pub acls: CompactArray<AclDescription>,
+    pub tag_buffer: TagSection,
+}
+
+impl Serializable for DescribeAclsResource {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.push(self.resource_type as u8);
+        buf.extend(self.resource_name.serialize());
+        buf.push(self.pattern_type as u8);
+        buf.extend(self.acls.serialize());
+        buf.extend(self.tag_buffer.serialize());
+        buf
+    }
+
+    fn deserialize(_bytes: &[u8]) -> Result<(Self, &[u8])> {
+        todo!()
+    }
+}
+
+#[derive(Debug)]
+pub struct DescribeAclsResponse {
+    pub throttle_time_ms: i32,
+    pub error_code: i16,
+    pub error_message: CompactString,
+    pub resources: CompactArray<DescribeAclsResource>,
+    pub tag_buffer: TagSection,
+}
+
+impl Serializable for DescribeAclsResponse {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(self.throttle_time_ms.to_be_bytes());
+        buf.extend(self.error_code.to_be_bytes());
+        buf.extend(self.error_message.serialize());
+        buf.extend(self.resources.serialize());
+        buf.extend(self.tag_buffer.serialize());
+        buf
+    }
+
+    fn deserialize(_bytes: &[u8]) -> Result<(Self, &[u8])> {
+        todo!()
+    }
+}
+
+impl ResponseBody for DescribeAclsResponse {
+    fn set_throttle_time_ms(&mut self, throttle_time_ms: i32) {
+        self.throttle_time_ms = throttle_time_ms;
+    }
+}
@@ -0,0 +1,334 @@
+use anyhow::Result;
+
+use super::{
+    api_keys::{RequestContext, RequestHandler},
+    body::ResponseBody,
+    error,
+    primitive::{CompactArray, CompactString, Serializable, TagSection},
+    response::Response,
+};
+use crate::{
+    broker::Broker,
+    quota::ClientQuotaEntity,
+    security::acl::{AclOperation, ResourceType, CLUSTER_RESOURCE_NAME},
+    server::ConnectionContext,
+};
+
+/// How a component matches the name of its entity type.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MatchType {
+    Exact,
+    Default,
+    Any,
+}
+
+impl MatchType {
+    pub fn from_code(code: i8) -> Option<Self> {
+        match code {
+            0 => Some(MatchType::Exact),
+            1 => Some(MatchType::Default),
+            2 => Some(MatchType::Any),
+            _ => None,
+        }
+    }
+}
+
+/// Parsed components as `(entity type, match type, name)` triples.
+type Filter<'a> = Vec<(&'a str, MatchType, Option<&'a str>)>;
+
+#[derive(Debug)]
+pub struct ComponentData {
+    pub entity_type: CompactString,
+    pub match_type: i8,
+    /// Name to match exactly, for the `Exact` match type
+    pub match_name: CompactString,
+    pub tag_buffer: TagSection,
+}
+
+impl Serializable for ComponentData {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(self.entity_type.serialize());
+        buf.push(self.match_type as u8);
+        buf.extend(self.match_name.serialize());
+        buf.extend(self.tag_buffer.serialize());
+        buf
+    }
+
+    fn deserialize(bytes: &[u8]) -> Result<(Self, &[u8])> {
+        let (entity_type, bytes) = CompactString::deserialize(bytes)?;
+        let (match_type, bytes) = i8::deserialize(bytes)?;
+        let (match_name, bytes) = CompactString::deserialize(bytes)?;
+        let (tag_buffer, bytes) = TagSection::deserialize(bytes)?;
+        Ok((
+            ComponentData {
+                entity_type,
+                match_type,
+                match_name,
+                tag_buffer,
+            },
+            bytes,
+        ))
+    }
+}
+
+#[derive(Debug)]
+pub struct DescribeClientQuotasRequest {
+    pub components: CompactArray<ComponentData>,
+    /// Whether entities with types beyond the components are left out
+    pub strict: bool,
+    pub tag_buffer: TagSection,
+}
+
+impl Serializable for DescribeClientQuotasRequest {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(self.components.serialize());
+        buf.push(self.strict as u8);
+        buf.extend(self.tag_buffer.serialize());
+        buf
+    }
+
+    fn deserialize(bytes: &[u8]) -> Result<(Self, &[u8])> {
+        let (components, bytes) = CompactArray::<ComponentData>::deserialize(bytes)?;
+        let (strict, bytes) = bool::deserialize(bytes)?;
+        let (tag_buffer, bytes) = TagSection::deserialize(bytes)?;
+        Ok((
+            DescribeClientQuotasRequest {
+                components,
+                strict,
+                tag_buffer,
+            },
+            bytes,
+        ))
+    }
+}
+
+impl DescribeClientQuotasRequest {
+    fn filter(&self) -> Result<Filter<'_>, String> {
+        let mut filter: Filter<'_> = vec![];
+        for component in self.components.iter().flatten() {
+            let entity_type = component.entity_type.as_deref().unwrap_or_default();
+            if filter.iter().any(|(t, _, _)| *t == entity_type) {
+                return Err(format!("Duplicate {entity_type} entity type in filter"));
+            }
+            let match_type = match MatchType::from_code(component.match_type) {
+                Some(MatchType::Exact) if component.match_name.is_none() => {
+                    return Err(format!("Missing name to match for {entity_type}"))
+                }
+                Some(match_type) => match_type,
+                None => return Err(format!("Unknown match type {}", component.match_type)),
+            };
+            filter.push((entity_type, match_type, component.match_name.as_deref()));
+        }
+        Ok(filter)
+    }
+
+    fn matches(
+        filter: &[(&str, MatchType, Option<&str>)],
+        strict: bool,
+        entity: &ClientQuotaEntity,
+    ) -> bool {
+        if strict && entity.0.len() != filter.len() {
+            return false;
+        }
+        filter.iter().all(|(entity_type, match_type, name)| {
+            match (entity.0.get(*entity_type), match_type) {
+                (None, _) => false,
+                (Some(entity_name), MatchType::Exact) => entity_name.as_deref() == *name,
+                (Some(entity_name), MatchType::Default) => entity_name.is_none(),
+                (Some(_), MatchType::Any) => true,
+            }
+        })
+    }
+
+    pub fn handle_request(&self, broker: &Broker, context: &ConnectionContext) -> Option<Response> {
+        let outcome = if !broker.authorize(
+            context,
+            AclOperation::DescribeConfigs,
+            ResourceType::Cluster,
+            CLUSTER_RESOURCE_NAME,
+        ) {
+            Err((
+                error::CLUSTER_AUTHORIZATION_FAILED,
+                "Cluster authorization failed".to_string(),
+            ))
+        } else {
+            self.filter().map_err(|e| (error::INVALID_REQUEST, e))
+        };
+
+        Some(Box::new(match outcome {
+            Err((error_code, message)) => DescribeClientQuotasResponse {
+                throttle_time_ms: 0,
+                error_code,
+                error_message: CompactString(Some(message)),
+                entries: CompactArray(None),
+                tag_buffer: TagSection(None),
+            },
+            Ok(filter) => {
+                let metadata = broker.metadata.read().unwrap();
+                let mut matching: Vec<_> = metadata
+                    .client_quotas
+                    .iter()
+                    .filter(|(entity, _)| Self::matches(&filter, self.strict, entity))
+                    .collect();
+                matching.sort_by(|a, b| a.0.cmp(b.0));
+                let entries = matching
+                    .into_iter()
+                    .map(|(entity, values)| {
+                        let mut values: Vec<ValueData> = values
+                            .iter()
+                            .map(|(key, value)| ValueData {
+                                key: CompactString(Some(key.clone())),
+                                value: *value,
+                                tag_buffer: TagSection(None),
+                            })
+                            .collect();
+                        values.sort_by(|a, b| a.key.0.cmp(&b.key.0));
+                        EntryData {
+                            entity: CompactArray(Some(EntityData::from_entity(entity))),
+                            values: CompactArray(Some(values)),
+                            tag_buffer: TagSection(None),
+                        }
+                    })
+                    .collect();
+                DescribeClientQuotasResponse {
+                    throttle_time_ms: 0,
+                    error_code: error::NONE,
+                    error_message: CompactString(None),
+                    entries: CompactArray(Some(entries)),
+                    tag_buffer: TagSection(None),
+                }
+            }
+        }))
+    }
+}
+
+impl RequestHandler for DescribeClientQuotasRequest {
+    fn read(bytes: &[u8], _version: i16) -> Result<Self> {
+        Ok(DescribeClientQuotasRequest::deserialize(bytes)?.0)
+    }
+
+    fn handle(&self, request: &mut RequestContext) -> Option<Response> {
+        self.handle_request(request.broker, request.connection)
+    }
+}
+
+#[derive(Debug)]
+pub struct EntityData {
+    pub entity_type: CompactString,
+    /// Null for the default entity of the type
+    pub entity_name: CompactString,
+    pub tag_buffer: TagSection,
+}
+
+impl EntityData {
+    pub fn from_entity(entity: &ClientQuotaEntity) -> Vec<Self> {
+        entity
+            .0
+            .iter()
+            .map(|(entity_type, entity_name)| EntityData {
+                entity_type: CompactString(Some(entity_type.clone())),
+                entity_name: CompactString(entity_name.clone()),
+                tag_buffer: TagSection(None),
+            })
+            .collect()
+    }
+}
+
+impl Serializable for EntityData {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(self.entity_type.serialize());
+        buf.extend(self.entity_name.serialize());
+        buf.extend(self.tag_buffer.serialize());
+        buf
+    }
+
+    fn deserialize(bytes: &[u8]) -> Result<(Self, &[u8])> {
+        let (entity_type, bytes) = CompactString::deserialize(bytes)?;
+        let (entity_name, bytes) = CompactString::deserialize(bytes)?;
+        let (tag_buffer, bytes) = TagSection::deserialize(bytes)?;
+        Ok((
+            EntityData {
+                entity_type,
+                entity_name,
+                tag_buffer,
+            },
+            bytes,
+        ))
+    }
+}
+
+#[derive(Debug)]
+pub struct ValueData {
+    pub key: CompactString,
+    pub value: f64,
+    pub tag_buffer: TagSection,
+}
+
+impl Serializable for ValueData {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(self.key.serialize());
+        buf.extend(self.value.to_be_bytes());
+        buf.extend(self.tag_buffer.serialize());
+        buf
+    }
+
+    fn deserialize(_bytes: &[u8]) -> Result<(Self, &[u8])> {
+        todo!()
+    }
+}
+
+#[derive(Debug)]
+pub struct EntryData {
+    pub entity: CompactArray<EntityData>,
+    pub values: CompactArray<ValueData>,
+    pub tag_buffer: TagSection,
+}
+
+impl Serializable for EntryData {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(self.entity.serialize());
+        buf.extend(self.values.serialize());
+        buf.extend(self.tag_buffer.serialize());
+        buf
+    }
+
+    fn deserialize(_bytes: &[u8]) -> Result<(Self, &[u8])> {
+        todo!()
+    }
+}
+
+#[derive(Debug)]
+pub struct DescribeClientQuotasResponse {
+    pub throttle_time_ms: i32,
+    pub error_code: i16,
+    pub error_message: CompactString,
+    pub entries: CompactArray<EntryData>,
+    pub tag_buffer: TagSection,
+}
+
+impl Serializable for DescribeClientQuotasResponse {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(self.throttle_time_ms.to_be_bytes());
+        buf.extend(self.error_code.to_be_bytes());
+        buf.extend(self.error_message.serialize());
+        buf.extend(self.entries.serialize());
+        buf.extend(self.tag_buffer.serialize());
+        buf
+    }
+
+    fn deserialize(_bytes: &[u8]) -> Result<(Self, &[u8])> {
+        todo!()
+    }
+}
+
+impl ResponseBody for DescribeClientQuotasResponse {
+    fn set_throttle_time_ms(&mut self, throttle_time_ms: i32) {
+        self.throttle_time_ms = throttle_time_ms;
+    }
+}
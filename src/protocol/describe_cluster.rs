@@ -0,0 +1,271 @@
+use anyhow::Result;
+
+use super::{
+    api_keys::{RequestContext, RequestHandler},
+    body::ResponseBody,
+    error,
+    primitive::{CompactString, Serializable, TagSection, UnsignedVarint},
+    response::Response,
+};
+use crate::{
+    broker::Broker,
+    security::acl::{AclOperation, ResourceType, CLUSTER_RESOURCE_NAME},
+    server::ConnectionContext,
+};
+
+/// Endpoints a DescribeCluster request asks for.
+const BROKER_ENDPOINT_TYPE: i8 = 1;
+const CONTROLLER_ENDPOINT_TYPE: i8 = 2;
+
+#[derive(Debug)]
+pub struct DescribeClusterRequest {
+    pub include_cluster_authorized_operations: bool,
+    /// Version 1 and above
+    pub endpoint_type: i8,
+    /// Version 2 and above
+    pub include_fenced_brokers: bool,
+    pub tag_buffer: TagSection,
+}
+
+impl DescribeClusterRequest {
+    pub fn deserialize_versioned(bytes: &[u8], version: i16) -> Result<(Self, &[u8])> {
+        let (include_cluster_authorized_operations, bytes) = bool::deserialize(bytes)?;
+        let (endpoint_type, bytes) = if version >= 1 {
+            i8::deserialize(bytes)?
+        } else {
+            (BROKER_ENDPOINT_TYPE, bytes)
+        };
+        let (include_fenced_brokers, bytes) = if version >= 2 {
+            bool::deserialize(bytes)?
+        } else {
+            (false, bytes)
+        };
+        let (tag_buffer, bytes) = TagSection::deserialize(bytes)?;
+        Ok((
+            DescribeClusterRequest {
+                include_cluster_authorized_operations,
+                endpoint_type,
+                include_fenced_brokers,
+                tag_buffer,
+            },
+            bytes,
+        ))
+    }
+
+    pub fn handle_request(
+        &self,
+        version: i16,
+        broker: &Broker,
+        context: &ConnectionContext,
+    ) -> Option<Response> {
+        let mut response = DescribeClusterResponse {
+            version,
+            throttle_time_ms: 0,
+            error_code: error::NONE,
+            error_message: CompactString(None),
+            endpoint_type: self.endpoint_type,
+            cluster_id: CompactString(Some(broker.cluster_id.clone())),
+            controller_id: broker.raft.leader().unwrap_or(-1),
+            brokers: vec![],
+            cluster_authorized_operations: i32::MIN,
+        };
+
+        match self.endpoint_type {
+            BROKER_ENDPOINT_TYPE => {}
+            CONTROLLER_ENDPOINT_TYPE => {
+                response.error_code = error::UNSUPPORTED_ENDPOINT_TYPE;
+                response.error_message = CompactString(Some(
+                    "The broker does not expose controller endpoints".to_string(),
+                ));
+            }
+            endpoint_type => {
+                response.error_code = error::INVALID_REQUEST;
+                response.error_message =
+                    CompactString(Some(format!("Unknown endpoint type {endpoint_type}")));
+            }
+        }
+        if response.error_code != error::NONE {
+            return Some(Box::new(response));
+        }
+
+        // Brokers are reported by their endpoint on the listener the client came in through
+        let brokers = broker
+            .metadata
+            .read()
+            .unwrap()
+            .brokers
+            .values()
+            .filter(|registration| self.include_fenced_brokers || !registration.fenced)
+            .filter_map(|registration| {
+                let endpoint = registration
+                    .endpoints
+                    .iter()
+                    .find(|endpoint| endpoint.name == context.listener_name)?;
+                Some(DescribeClusterBroker {
+                    broker_id: registration.id,
+                    host: CompactString(Some(endpoint.host.clone())),
+                    port: endpoint.port as i32,
+                    rack: CompactString(registration.rack.clone()),
+                    is_fenced: registration.fenced,
+                    tag_buffer: TagSection(None),
+                })
+            })
+            .collect();
+        response.brokers = brokers;
+
+        if self.include_cluster_authorized_operations {
+            response.cluster_authorized_operations = if broker.authorize(
+                context,
+                AclOperation::Describe,
+                ResourceType::Cluster,
+                CLUSTER_RESOURCE_NAME,
+            ) {
+                broker.authorized_operations(context, ResourceType::Cluster, CLUSTER_RESOURCE_NAME)
+            } else {
+                0
+            };
+        }
+
+        Some(Box::new(response))
+    }
+}
+
+impl RequestHandler for DescribeClusterRequest {
+    fn read(bytes: &[u8], version: i16) -> Result<Self> {
+        Ok(DescribeClusterRequest::deserialize_versioned(bytes, version)?.0)
+    }
+
+    fn handle(&self, request: &mut RequestContext) -> Option<Response> {
+        self.handle_request(request.api_version, request.broker, request.connection)
+    }
+}
+
+#[derive(Debug)]
+pub struct DescribeClusterBroker {
+    pub broker_id: i32,
+    pub host: CompactString,
+    pub port: i32,
+    pub rack: CompactString,
+    /// Version 2 and above
+    pub is_fenced: bool,
+    pub tag_buffer: TagSection,
+}
+
+impl DescribeClusterBroker {
+    fn serialize_versioned(&self, version: i16) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(self.broker_id.to_be_bytes());
+        buf.extend(self.host.serialize());
+        buf.extend(self.port.to_be_bytes());
+        buf.extend(self.rack.serialize());
+        if version >= 2 {
+            buf.push(self.is_fenced as u8);
+        }
+        buf.extend(self.tag_buffer.serialize());
+        buf
+    }
+}
+
+#[derive(Debug)]
+pub struct DescribeClusterResponse {
+    pub version: i16,
+    pub throttle_time_ms: i32,
+    pub error_code: i16,
+    pub error_message: CompactString,
+    /// Version 1 and above
+    pub endpoint_type: i8,
+    pub cluster_id: CompactString,
+    pub controller_id: i32,
+    pub brokers: Vec<DescribeClusterBroker>,
+    /// `i32::MIN` unless requested
+    pub cluster_authorized_operations: i32,
+}
+
+impl Serializable for DescribeClusterResponse {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(self.throttle_time_ms.to_be_bytes());
+        buf.extend(self.error_code.to_be_bytes());
+        buf.extend(self.error_message.serialize());
+        if self.version >= 1 {
+            buf.push(self.endpoint_type as u8);
+        }
+        buf.extend(self.cluster_id.serialize());
+        buf.extend(self.controller_id.to_be_bytes());
+        buf.extend(UnsignedVarint(self.brokers.len() as u32 + 1).serialize());
+        for broker in &self.brokers {
+            buf.extend(broker.serialize_versioned(self.version));
+        }
+        buf.extend(self.cluster_authorized_operations.to_be_bytes());
+        buf.extend(TagSection(None).serialize());
+        buf
+    }
+
+    fn deserialize(_bytes: &[u8]) -> Result<(Self, &[u8])> {
+        todo!()
+    }
+}
+
+impl ResponseBody for DescribeClusterResponse {
+    fn set_throttle_time_ms(&mut self, throttle_time_ms: i32) {
+        self.throttle_time_ms = throttle_time_ms;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn response(version: i16) -> DescribeClusterResponse {
+        DescribeClusterResponse {
+            version,
+            throttle_time_ms: 0,
+            error_code: error::NONE,
+            error_message: CompactString(None),
+            endpoint_type: BROKER_ENDPOINT_TYPE,
+            cluster_id: CompactString(Some("c".to_string())),
+            controller_id: 1,
+            brokers: vec![DescribeClusterBroker {
+                broker_id: 1,
+                host: CompactString(Some("kafka".to_string())),
+                port: 9092,
+                rack: CompactString(None),
+                is_fenced: true,
+                tag_buffer: TagSection(None),
+            }],
+            cluster_authorized_operations: i32::MIN,
+        }
+    }
+
+    #[test]
+    fn reads_the_endpoint_type_and_fenced_brokers_flag_by_version() {
+        let (request, rest) = DescribeClusterRequest::deserialize_versioned(&[1, 0], 0).unwrap();
+        assert!(request.include_cluster_authorized_operations);
+        assert_eq!(request.endpoint_type, BROKER_ENDPOINT_TYPE);
+        assert!(!request.include_fenced_brokers);
+        assert!(rest.is_empty());
+
+        let (request, rest) =
+            DescribeClusterRequest::deserialize_versioned(&[0, 2, 1, 0], 2).unwrap();
+        assert!(!request.include_cluster_authorized_operations);
+        assert_eq!(request.endpoint_type, CONTROLLER_ENDPOINT_TYPE);
+        assert!(request.include_fenced_brokers);
+        assert!(rest.is_empty());
+    }
+
+    #[test]
+    fn writes_the_endpoint_type_from_version_1_and_fencing_from_version_2() {
+        let broker = [&[0, 0, 0, 1, 6][..], b"kafka", &[0, 0, 0x23, 0x84, 0]].concat();
+        let header = [0, 0, 0, 0, 0, 0, 0];
+        let cluster = [2, b'c', 0, 0, 0, 1, 2];
+        let trailer = [&i32::MIN.to_be_bytes()[..], &[0]].concat();
+
+        let v0 = [&header[..], &cluster, &broker, &[0], &trailer].concat();
+        assert_eq!(response(0).serialize(), v0);
+        let v1 = [&header[..], &[1], &cluster, &broker, &[0], &trailer].concat();
+        assert_eq!(response(1).serialize(), v1);
+        // The fenced flag goes before the tags of the broker
+        let v2 = [&header[..], &[1], &cluster, &broker, &[1, 0], &trailer].concat();
+        assert_eq!(response(2).serialize(), v2);
+    }
+}
@@ -0,0 +1,364 @@
+use anyhow::Result;
+
+use super::{
+    api_keys::{RequestContext, RequestHandler},
+    body::ResponseBody,
+    error,
+    primitive::{CompactArray, CompactString, Serializable, TagSection},
+    response::Response,
+};
+use crate::{
+    broker::Broker,
+    config_registry::{self, ConfigResolver, ConfigResource, ConfigResourceType, ResolvedConfig},
+    security::acl::{AclOperation, ResourceType, CLUSTER_RESOURCE_NAME},
+    server::ConnectionContext,
+};
+
+#[derive(Debug)]
+pub struct DescribeConfigsResource {
+    pub resource_type: i8,
+    pub resource_name: CompactString,
+    /// Configs to describe, null for all of them
+    pub configuration_keys: CompactArray<CompactString>,
+    pub tag_buffer: TagSection,
+}
+
+impl Serializable for DescribeConfigsResource {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.push(self.resource_type as u8);
+        buf.extend(self.resource_name.serialize());
+        buf.extend(self.configuration_keys.serialize());
+        buf.extend(self.tag_buffer.serialize());
+        buf
+    }
+
+    fn deserialize(bytes: &[u8]) -> Result<(Self, &[u8])> {
+        let (resource_type, bytes) = i8::deserialize(bytes)?;
+        let (resource_name, bytes) = CompactString::deserialize(bytes)?;
+        let (configuration_keys, bytes) = CompactArray::<CompactString>::deserialize(bytes)?;
+        let (tag_buffer, bytes) = TagSection::deserialize(bytes)?;
+        Ok((
+            DescribeConfigsResource {
+                resource_type,
+                resource_name,
+                configuration_keys,
+                tag_buffer,
+            },
+            bytes,
+        ))
+    }
+}
+
+/// Check that the resource exists and that the connection may perform `operation` on it.
+pub fn check_resource(
+    broker: &Broker,
+    context: &ConnectionContext,
+    resource: &ConfigResource,
+    operation: AclOperation,
+) -> Result<(), (i16, String)> {
+    match resource.resource_type {
+        ConfigResourceType::Topic => {
+            if !broker.authorize(context, operation, ResourceType::Topic, &resource.name) {
+                return Err((
+                    error::TOPIC_AUTHORIZATION_FAILED,
+                    "Topic authorization failed".to_string(),
+                ));
+            }
+            if !broker
+                .metadata
+                .read()
+                .unwrap()
+                .topics
+                .contains_key(&resource.name)
+            {
+                return Err((
+                    error::UNKNOWN_TOPIC_OR_PARTITION,
+                    format!("Topic {} does not exist", resource.name),
+                ));
+            }
+        }
+        ConfigResourceType::Broker => {
+            if !broker.authorize(
+                context,
+                operation,
+                ResourceType::Cluster,
+                CLUSTER_RESOURCE_NAME,
+            ) {
+                return Err((
+                    error::CLUSTER_AUTHORIZATION_FAILED,
+                    "Cluster authorization failed".to_string(),
+                ));
+            }
+            if !resource.name.is_empty() && resource.name != broker.config.node_id.to_string() {
+                return Err((
+                    error::INVALID_REQUEST,
+                    format!("Unexpected broker id {}", resource.name),
+                ));
+            }
+        }
+        ConfigResourceType::Unknown => {
+            return Err((
+                error::INVALID_REQUEST,
+                "Unsupported resource type".to_string(),
+            ))
+        }
+    }
+    Ok(())
+}
+
+#[derive(Debug)]
+pub struct DescribeConfigsRequest {
+    pub resources: CompactArray<DescribeConfigsResource>,
+    pub include_synonyms: bool,
+    pub include_documentation: bool,
+    pub tag_buffer: TagSection,
+}
+
+impl Serializable for DescribeConfigsRequest {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(self.resources.serialize());
+        buf.push(self.include_synonyms as u8);
+        buf.push(self.include_documentation as u8);
+        buf.extend(self.tag_buffer.serialize());
+        buf
+    }
+
+    fn deserialize(bytes: &[u8]) -> Result<(Self, &[u8])> {
+        let (resources, bytes) = CompactArray::<DescribeConfigsResource>::deserialize(bytes)?;
+        let (include_synonyms, bytes) = bool::deserialize(bytes)?;
+        let (include_documentation, bytes) = bool::deserialize(bytes)?;
+        let (tag_buffer, bytes) = TagSection::deserialize(bytes)?;
+        Ok((
+            DescribeConfigsRequest {
+                resources,
+                include_synonyms,
+                include_documentation,
+                tag_buffer,
+            },
+            bytes,
+        ))
+    }
+}
+
+impl DescribeConfigsRequest {
+    pub fn handle_request(&self, broker: &Broker, context: &ConnectionContext) -> Option<Response> {
+        let results = self
+            .resources
+            .iter()
+            .flatten()
+            .map(|resource| self.describe(broker, context, resource))
+            .collect();
+
+        Some(Box::new(DescribeConfigsResponse {
+            throttle_time_ms: 0,
+            results: CompactArray(Some(results)),
+            tag_buffer: TagSection(None),
+        }))
+    }
+
+    fn describe(
+        &self,
+        broker: &Broker,
+        context: &ConnectionContext,
+        request: &DescribeConfigsResource,
+    ) -> DescribeConfigsResult {
+        let resource = ConfigResource::new(
+            ConfigResourceType::from_code(request.resource_type),
+            request.resource_name.as_deref().unwrap_or_default(),
+        );
+        let mut result = DescribeConfigsResult {
+            error_code: error::NONE,
+            error_message: CompactString(None),
+            resource_type: request.resource_type,
+            resource_name: request.resource_name.clone(),
+            configs: CompactArray(Some(vec![])),
+            tag_buffer: TagSection(None),
+        };
+        if let Err((error_code, message)) =
+            check_resource(broker, context, &resource, AclOperation::DescribeConfigs)
+        {
+            result.error_code = error_code;
+            result.error_message = CompactString(Some(message));
+            return result;
+        }
+
+        let metadata = broker.metadata.read().unwrap();
+        let resolver = ConfigResolver::new(&metadata, &broker.config);
+        let keys = request.configuration_keys.as_deref();
+        let configs = config_registry::configs(resource.resource_type)
+            .iter()
+            .filter(|def| {
+                keys.map_or(true, |keys| {
+                    keys.iter().any(|key| key.as_deref() == Some(def.name))
+                })
+            })
+            .map(|def| {
+                let resolved = resolver.resolve(&resource, def);
+                self.describe_config(resource.resource_type, resolved)
+            })
+            .collect();
+        result.configs = CompactArray(Some(configs));
+        result
+    }
+
+    fn describe_config(
+        &self,
+        resource_type: ConfigResourceType,
+        resolved: ResolvedConfig,
+    ) -> DescribeConfigsResourceResult {
+        let def = resolved.def;
+        // Sensitive values never leave the broker
+        let mask = |value: Option<String>| if def.is_sensitive() { None } else { value };
+        let synonyms = if self.include_synonyms {
+            resolved
+                .synonyms
+                .into_iter()
+                .map(|synonym| DescribeConfigsSynonym {
+                    name: CompactString(Some(synonym.def.name.to_string())),
+                    value: CompactString(mask(Some(synonym.value))),
+                    source: synonym.source.code(),
+                    tag_buffer: TagSection(None),
+                })
+                .collect()
+        } else {
+            vec![]
+        };
+        DescribeConfigsResourceResult {
+            name: CompactString(Some(def.name.to_string())),
+            value: CompactString(mask(resolved.value)),
+            read_only: resource_type == ConfigResourceType::Broker && !def.dynamic,
+            config_source: resolved.source.code(),
+            is_sensitive: def.is_sensitive(),
+            synonyms: CompactArray(Some(synonyms)),
+            config_type: def.config_type.code(),
+            documentation: CompactString(
+                self.include_documentation
+                    .then(|| def.documentation.to_string()),
+            ),
+            tag_buffer: TagSection(None),
+        }
+    }
+}
+
+impl RequestHandler for DescribeConfigsRequest {
+    fn read(bytes: &[u8], _version: i16) -> Result<Self> {
+        Ok(DescribeConfigsRequest::deserialize(bytes)?.0)
+    }
+
+    fn handle(&self, request: &mut RequestContext) -> Option<Response> {
+        self.handle_request(request.broker, request.connection)
+    }
+}
+
+#[derive(Debug)]
+pub struct DescribeConfigsSynonym {
+    pub name: CompactString,
+    pub value: CompactString,
+    pub source: i8,
+    pub tag_buffer: TagSection,
+}
+
+impl Serializable for DescribeConfigsSynonym {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(self.name.serialize());
+        buf.extend(self.value.serialize());
+        buf.push(self.source as u8);
+        buf.extend(self.tag_buffer.serialize());
+        buf
+    }
+
+    fn deserialize(_bytes: &[u8]) -> Result<(Self, &[u8])> {
+        todo!()
+    }
+}
+
+#[derive(Debug)]
+pub struct DescribeConfigsResourceResult {
+    pub name: CompactString,
+    /// Null when unset or sensitive
+    pub value: CompactString,
+    pub read_only: bool,
+    pub config_source: i8,
+    pub is_sensitive: bool,
+    pub synonyms: CompactArray<DescribeConfigsSynonym>,
+    pub config_type: i8,
+    pub documentation: CompactString,
+    pub tag_buffer: TagSection,
+}
+
+impl Serializable for DescribeConfigsResourceResult {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(self.name.serialize());
+        buf.extend(self.value.serialize());
+        buf.push(self.read_only as u8);
+        buf.push(self.config_source as u8);
+        buf.push(self.is_sensitive as u8);
+        buf.extend(self.synonyms.serialize());
+        buf.push(self.config_type as u8);
+        buf.extend(self.documentation.serialize());
+        buf.extend(self.tag_buffer.serialize());
+        buf
+    }
+
+    fn deserialize(_bytes: &[u8]) -> Result<(Self, &[u8])> {
+        todo!()
+    }
+}
+
+#[derive(Debug)]
+pub struct DescribeConfigsResult {
+    pub error_code: i16,
+    pub error_message: CompactString,
+    pub resource_type: i8,
+    pub resource_name: CompactString,
+    pub configs: CompactArray<DescribeConfigsResourceResult>,
+    pub tag_buffer: TagSection,
+}
+
+impl Serializable for DescribeConfigsResult {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(self.error_code.to_be_bytes());
+        buf.extend(self.error_message.serialize());
+        buf.push(self.resource_type as u8);
+        buf.extend(self.resource_name.serialize());
+        buf.extend(self.configs.serialize());
+        buf.extend(self.tag_buffer.serialize());
+        buf
+    }
+
+    fn deserialize(_bytes: &[u8]) -> Result<(Self, &[u8])> {
+        todo!()
+    }
+}
+
+#[derive(Debug)]
+pub struct DescribeConfigsResponse {
+    pub throttle_time_ms: i32,
+    pub results: CompactArray<DescribeConfigsResult>,
+    pub tag_buffer: TagSection,
+}
+
+impl Serializable for DescribeConfigsResponse {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(self.throttle_time_ms.to_be_bytes());
+        buf.extend(self.results.serialize());
+        buf.extend(self.tag_buffer.serialize());
+        buf
+    }
+
+    fn deserialize(_bytes: &[u8]) -> Result<(Self, &[u8])> {
+        todo!()
+    }
+}
+
+impl ResponseBody for DescribeConfigsResponse {
+    fn set_throttle_time_ms(&mut self, throttle_time_ms: i32) {
+        self.throttle_time_ms = throttle_time_ms;
+    }
+}
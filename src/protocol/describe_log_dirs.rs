@@ -0,0 +1,293 @@
+use anyhow::Result;
+
+use super::{
+    api_keys::{RequestContext, RequestHandler},
+    body::ResponseBody,
+    error,
+    primitive::{CompactArray, CompactString, Serializable, TagSection, UnsignedVarint},
+    response::Response,
+};
+use crate::{
+    broker::Broker,
+    log_manager::TopicPartition,
+    security::acl::{AclOperation, ResourceType, CLUSTER_RESOURCE_NAME},
+    server::ConnectionContext,
+};
+
+#[derive(Debug)]
+pub struct DescribableLogDirTopic {
+    pub topic: CompactString,
+    pub partitions: CompactArray<i32>,
+    pub tag_buffer: TagSection,
+}
+
+impl Serializable for DescribableLogDirTopic {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(self.topic.serialize());
+        buf.extend(self.partitions.serialize());
+        buf.extend(self.tag_buffer.serialize());
+        buf
+    }
+
+    fn deserialize(bytes: &[u8]) -> Result<(Self, &[u8])> {
+        let (topic, bytes) = CompactString::deserialize(bytes)?;
+        let (partitions, bytes) = CompactArray::<i32>::deserialize(bytes)?;
+        let (tag_buffer, bytes) = TagSection::deserialize(bytes)?;
+        Ok((
+            DescribableLogDirTopic {
+                topic,
+                partitions,
+                tag_buffer,
+            },
+            bytes,
+        ))
+    }
+}
+
+#[derive(Debug)]
+pub struct DescribeLogDirsRequest {
+    /// Replicas to describe, null for all of them
+    pub topics: CompactArray<DescribableLogDirTopic>,
+    pub tag_buffer: TagSection,
+}
+
+impl Serializable for DescribeLogDirsRequest {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(self.topics.serialize());
+        buf.extend(self.tag_buffer.serialize());
+        buf
+    }
+
+    fn deserialize(bytes: &[u8]) -> Result<(Self, &[u8])> {
+        let (topics, bytes) = CompactArray::<DescribableLogDirTopic>::deserialize(bytes)?;
+        let (tag_buffer, bytes) = TagSection::deserialize(bytes)?;
+        Ok((DescribeLogDirsRequest { topics, tag_buffer }, bytes))
+    }
+}
+
+impl DescribeLogDirsRequest {
+    pub fn handle_request(
+        &self,
+        version: i16,
+        broker: &Broker,
+        context: &ConnectionContext,
+    ) -> Option<Response> {
+        let mut response = DescribeLogDirsResponse {
+            version,
+            throttle_time_ms: 0,
+            error_code: error::NONE,
+            results: vec![],
+        };
+
+        if broker.authorize(
+            context,
+            AclOperation::Describe,
+            ResourceType::Cluster,
+            CLUSTER_RESOURCE_NAME,
+        ) {
+            response.results = self.describe(broker);
+        } else {
+            response.error_code = error::CLUSTER_AUTHORIZATION_FAILED;
+        }
+
+        Some(Box::new(response))
+    }
+
+    fn is_requested(&self, replica: &TopicPartition) -> bool {
+        let Some(topics) = self.topics.as_deref() else {
+            return true;
+        };
+        topics.iter().any(|topic| {
+            topic.topic.as_deref() == Some(replica.topic.as_str())
+                && topic
+                    .partitions
+                    .iter()
+                    .flatten()
+                    .any(|&p| p == replica.partition)
+        })
+    }
+
+    fn describe(&self, broker: &Broker) -> Vec<DescribeLogDirsResult> {
+        let mut logs = broker.logs.lock().unwrap();
+        let mut results = vec![];
+        for index in 0..logs.dirs().len() {
+            let replicas: Vec<TopicPartition> = logs
+                .replicas_in(index)
+                .filter(|replica| self.is_requested(replica))
+                .cloned()
+                .collect();
+
+            // Replicas come sorted by topic, so the partitions of a topic are adjacent
+            let mut topics: Vec<DescribeLogDirsTopic> = vec![];
+            for replica in replicas {
+                let Ok(partition_size) = logs.replica_size(&replica) else {
+                    break;
+                };
+                let partition = DescribeLogDirsPartition {
+                    partition_index: replica.partition,
+                    partition_size,
+                    // Replicas never fall behind without replication
+                    offset_lag: 0,
+                    is_future_key: false,
+                    tag_buffer: TagSection(None),
+                };
+                match topics.last_mut() {
+                    Some(topic) if topic.name.as_deref() == Some(replica.topic.as_str()) => {
+                        topic.partitions.push(partition)
+                    }
+                    _ => topics.push(DescribeLogDirsTopic {
+                        name: CompactString(Some(replica.topic)),
+                        partitions: vec![partition],
+                    }),
+                }
+            }
+
+            let dir = &logs.dirs()[index];
+            let mut result = DescribeLogDirsResult {
+                error_code: error::NONE,
+                log_dir: CompactString(Some(dir.path.display().to_string())),
+                topics,
+                total_bytes: -1,
+                usable_bytes: -1,
+            };
+            if dir.is_online() {
+                if let Some((total_bytes, usable_bytes)) = dir.disk_space() {
+                    result.total_bytes = total_bytes;
+                    result.usable_bytes = usable_bytes;
+                }
+            } else {
+                result.error_code = error::KAFKA_STORAGE_ERROR;
+                result.topics.clear();
+            }
+            results.push(result);
+        }
+        results
+    }
+}
+
+impl RequestHandler for DescribeLogDirsRequest {
+    fn read(bytes: &[u8], _version: i16) -> Result<Self> {
+        Ok(DescribeLogDirsRequest::deserialize(bytes)?.0)
+    }
+
+    fn handle(&self, request: &mut RequestContext) -> Option<Response> {
+        self.handle_request(request.api_version, request.broker, request.connection)
+    }
+}
+
+#[derive(Debug)]
+pub struct DescribeLogDirsPartition {
+    pub partition_index: i32,
+    /// Bytes used by the log segments of the replica
+    pub partition_size: i64,
+    /// How far the replica is behind the high watermark, or the current replica if it
+    /// is a future one
+    pub offset_lag: i64,
+    pub is_future_key: bool,
+    pub tag_buffer: TagSection,
+}
+
+impl Serializable for DescribeLogDirsPartition {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(self.partition_index.to_be_bytes());
+        buf.extend(self.partition_size.to_be_bytes());
+        buf.extend(self.offset_lag.to_be_bytes());
+        buf.push(self.is_future_key as u8);
+        buf.extend(self.tag_buffer.serialize());
+        buf
+    }
+
+    fn deserialize(_bytes: &[u8]) -> Result<(Self, &[u8])> {
+        todo!()
+    }
+}
+
+#[derive(Debug)]
+pub struct DescribeLogDirsTopic {
+    pub name: CompactString,
+    pub partitions: Vec<DescribeLogDirsPartition>,
+}
+
+impl Serializable for DescribeLogDirsTopic {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(self.name.serialize());
+        buf.extend(UnsignedVarint(self.partitions.len() as u32 + 1).serialize());
+        for partition in &self.partitions {
+            buf.extend(partition.serialize());
+        }
+        buf.extend(TagSection(None).serialize());
+        buf
+    }
+
+    fn deserialize(_bytes: &[u8]) -> Result<(Self, &[u8])> {
+        todo!()
+    }
+}
+
+#[derive(Debug)]
+pub struct DescribeLogDirsResult {
+    pub error_code: i16,
+    pub log_dir: CompactString,
+    pub topics: Vec<DescribeLogDirsTopic>,
+    /// Version 4 and above: capacity of the volume, -1 if unknown
+    pub total_bytes: i64,
+    /// Version 4 and above: free space of the volume, -1 if unknown
+    pub usable_bytes: i64,
+}
+
+impl DescribeLogDirsResult {
+    fn serialize_versioned(&self, version: i16) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(self.error_code.to_be_bytes());
+        buf.extend(self.log_dir.serialize());
+        buf.extend(UnsignedVarint(self.topics.len() as u32 + 1).serialize());
+        for topic in &self.topics {
+            buf.extend(topic.serialize());
+        }
+        if version >= 4 {
+            buf.extend(self.total_bytes.to_be_bytes());
+            buf.extend(self.usable_bytes.to_be_bytes());
+        }
+        buf.extend(TagSection(None).serialize());
+        buf
+    }
+}
+
+#[derive(Debug)]
+pub struct DescribeLogDirsResponse {
+    pub version: i16,
+    pub throttle_time_ms: i32,
+    /// Version 3 and above
+    pub error_code: i16,
+    pub results: Vec<DescribeLogDirsResult>,
+}
+
+impl Serializable for DescribeLogDirsResponse {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(self.throttle_time_ms.to_be_bytes());
+        if self.version >= 3 {
+            buf.extend(self.error_code.to_be_bytes());
+        }
+        buf.extend(UnsignedVarint(self.results.len() as u32 + 1).serialize());
+        for result in &self.results {
+            buf.extend(result.serialize_versioned(self.version));
+        }
+        buf.extend(TagSection(None).serialize());
+        buf
+    }
+
+    fn deserialize(_bytes: &[u8]) -> Result<(Self, &[u8])> {
+        todo!()
+    }
+}
+
+impl ResponseBody for DescribeLogDirsResponse {
+    fn set_throttle_time_ms(&mut self, throttle_time_ms: i32) {
+        self.throttle_time_ms = throttle_time_ms;
+    }
+}
@@ -0,0 +1,403 @@
+use std::collections::BTreeMap;
+
+use anyhow::Result;
+
+use super::{
+    api_keys::{RequestContext, RequestHandler},
+    body::ResponseBody,
+    error,
+    primitive::{CompactArray, CompactString, Serializable, TagSection, UnsignedVarint},
+    response::Response,
+};
+use crate::{
+    broker::Broker,
+    raft::{ReplicaState, METADATA_PARTITION, METADATA_TOPIC},
+    security::acl::{AclOperation, ResourceType, CLUSTER_RESOURCE_NAME},
+    server::ConnectionContext,
+};
+
+#[derive(Debug)]
+pub struct DescribeQuorumPartition {
+    pub partition_index: i32,
+    pub tag_buffer: TagSection,
+}
+
+impl Serializable for DescribeQuorumPartition {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(self.partition_index.to_be_bytes());
+        buf.extend(self.tag_buffer.serialize());
+        buf
+    }
+
+    fn deserialize(bytes: &[u8]) -> Result<(Self, &[u8])> {
+        let (partition_index, bytes) = i32::deserialize(bytes)?;
+        let (tag_buffer, bytes) = TagSection::deserialize(bytes)?;
+        Ok((
+            DescribeQuorumPartition {
+                partition_index,
+                tag_buffer,
+            },
+            bytes,
+        ))
+    }
+}
+
+#[derive(Debug)]
+pub struct DescribeQuorumTopic {
+    pub topic_name: CompactString,
+    pub partitions: CompactArray<DescribeQuorumPartition>,
+    pub tag_buffer: TagSection,
+}
+
+impl Serializable for DescribeQuorumTopic {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(self.topic_name.serialize());
+        buf.extend(self.partitions.serialize());
+        buf.extend(self.tag_buffer.serialize());
+        buf
+    }
+
+    fn deserialize(bytes: &[u8]) -> Result<(Self, &[u8])> {
+        let (topic_name, bytes) = CompactString::deserialize(bytes)?;
+        let (partitions, bytes) = CompactArray::<DescribeQuorumPartition>::deserialize(bytes)?;
+        let (tag_buffer, bytes) = TagSection::deserialize(bytes)?;
+        Ok((
+            DescribeQuorumTopic {
+                topic_name,
+                partitions,
+                tag_buffer,
+            },
+            bytes,
+        ))
+    }
+}
+
+#[derive(Debug)]
+pub struct DescribeQuorumRequest {
+    pub topics: CompactArray<DescribeQuorumTopic>,
+    pub tag_buffer: TagSection,
+}
+
+impl Serializable for DescribeQuorumRequest {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(self.topics.serialize());
+        buf.extend(self.tag_buffer.serialize());
+        buf
+    }
+
+    fn deserialize(bytes: &[u8]) -> Result<(Self, &[u8])> {
+        let (topics, bytes) = CompactArray::<DescribeQuorumTopic>::deserialize(bytes)?;
+        let (tag_buffer, bytes) = TagSection::deserialize(bytes)?;
+        Ok((DescribeQuorumRequest { topics, tag_buffer }, bytes))
+    }
+}
+
+impl DescribeQuorumRequest {
+    pub fn handle_request(
+        &self,
+        version: i16,
+        broker: &Broker,
+        context: &ConnectionContext,
+    ) -> Option<Response> {
+        let mut response = DescribeQuorumResponse {
+            version,
+            error_code: error::NONE,
+            topics: vec![],
+        };
+
+        if broker.authorize(
+            context,
+            AclOperation::Describe,
+            ResourceType::Cluster,
+            CLUSTER_RESOURCE_NAME,
+        ) {
+            response.topics = self.describe(broker);
+        } else {
+            response.error_code = error::CLUSTER_AUTHORIZATION_FAILED;
+        }
+
+        Some(Box::new(response))
+    }
+
+    fn describe(&self, broker: &Broker) -> Vec<DescribeQuorumTopicResult> {
+        self.topics
+            .iter()
+            .flatten()
+            .map(|topic| {
+                let is_metadata = topic.topic_name.as_deref() == Some(METADATA_TOPIC);
+                let partitions = topic.partitions.iter().flatten().map(|partition| {
+                    let index = partition.partition_index;
+                    if !is_metadata || index != METADATA_PARTITION {
+                        return DescribeQuorumPartitionResult::error(
+                            index,
+                            error::UNKNOWN_TOPIC_OR_PARTITION,
+                            -1,
+                            -1,
+                        );
+                    }
+                    // Only the leader tracks the progress of the other replicas
+                    let Some(quorum) = broker.raft.describe_quorum() else {
+                        let leader = broker.raft.current_leader();
+                        return DescribeQuorumPartitionResult::error(
+                            index,
+                            error::NOT_LEADER_OR_FOLLOWER,
+                            leader.leader_id,
+                            leader.leader_epoch,
+                        );
+                    };
+                    DescribeQuorumPartitionResult {
+                        partition_index: index,
+                        error_code: error::NONE,
+                        leader_id: broker.config.node_id,
+                        leader_epoch: quorum.leader_epoch,
+                        high_watermark: quorum.high_watermark,
+                        current_voters: replica_states(&quorum.voters),
+                        observers: replica_states(&quorum.observers),
+                    }
+                });
+                DescribeQuorumTopicResult {
+                    topic_name: topic.topic_name.clone(),
+                    partitions: partitions.collect(),
+                }
+            })
+            .collect()
+    }
+}
+
+impl RequestHandler for DescribeQuorumRequest {
+    fn read(bytes: &[u8], _version: i16) -> Result<Self> {
+        Ok(DescribeQuorumRequest::deserialize(bytes)?.0)
+    }
+
+    fn handle(&self, request: &mut RequestContext) -> Option<Response> {
+        self.handle_request(request.api_version, request.broker, request.connection)
+    }
+}
+
+fn replica_states(replicas: &BTreeMap<i32, ReplicaState>) -> Vec<ReplicaStateResult> {
+    replicas
+        .iter()
+        .map(|(&replica_id, replica)| ReplicaStateResult {
+            replica_id,
+            log_end_offset: replica.end_offset,
+            last_fetch_timestamp: replica.last_fetch_timestamp,
+            last_caught_up_timestamp: replica.last_caught_up_timestamp,
+        })
+        .collect()
+}
+
+#[derive(Debug)]
+pub struct ReplicaStateResult {
+    pub replica_id: i32,
+    /// -1 until the replica fetches
+    pub log_end_offset: i64,
+    /// Version 1 and above
+    pub last_fetch_timestamp: i64,
+    /// Version 1 and above
+    pub last_caught_up_timestamp: i64,
+}
+
+impl ReplicaStateResult {
+    fn serialize_versioned(&self, version: i16) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(self.replica_id.to_be_bytes());
+        buf.extend(self.log_end_offset.to_be_bytes());
+        if version >= 1 {
+            buf.extend(self.last_fetch_timestamp.to_be_bytes());
+            buf.extend(self.last_caught_up_timestamp.to_be_bytes());
+        }
+        buf.extend(TagSection(None).serialize());
+        buf
+    }
+}
+
+#[derive(Debug)]
+pub struct DescribeQuorumPartitionResult {
+    pub partition_index: i32,
+    pub error_code: i16,
+    pub leader_id: i32,
+    pub leader_epoch: i32,
+    pub high_watermark: i64,
+    pub current_voters: Vec<ReplicaStateResult>,
+    pub observers: Vec<ReplicaStateResult>,
+}
+
+impl DescribeQuorumPartitionResult {
+    fn error(partition_index: i32, error_code: i16, leader_id: i32, leader_epoch: i32) -> Self {
+        DescribeQuorumPartitionResult {
+            partition_index,
+            error_code,
+            leader_id,
+            leader_epoch,
+            high_watermark: -1,
+            current_voters: vec![],
+            observers: vec![],
+        }
+    }
+
+    fn serialize_versioned(&self, version: i16) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(self.partition_index.to_be_bytes());
+        buf.extend(self.error_code.to_be_bytes());
+        buf.extend(self.leader_id.to_be_bytes());
+        buf.extend(self.leader_epoch.to_be_bytes());
+        buf.extend(self.high_watermark.to_be_bytes());
+        for replicas in [&self.current_voters, &self.observers] {
+            buf.extend(UnsignedVarint(replicas.len() as u32 + 1).serialize());
+            for replica in replicas {
+                buf.extend(replica.serialize_versioned(version));
+            }
+        }
+        buf.extend(TagSection(None).serialize());
+        buf
+    }
+}
+
+#[derive(Debug)]
+pub struct DescribeQuorumTopicResult {
+    pub topic_name: CompactString,
+    pub partitions: Vec<DescribeQuorumPartitionResult>,
+}
+
+impl DescribeQuorumTopicResult {
+    fn serialize_versioned(&self, version: i16) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(self.topic_name.serialize());
+        buf.extend(UnsignedVarint(self.partitions.len() as u32 + 1).serialize());
+        for partition in &self.partitions {
+            buf.extend(partition.serialize_versioned(version));
+        }
+        buf.extend(TagSection(None).serialize());
+        buf
+    }
+}
+
+/// DescribeQuorum versions 0 and 1, which only differ in the replica timestamps.
+#[derive(Debug)]
+pub struct DescribeQuorumResponse {
+    pub version: i16,
+    pub error_code: i16,
+    pub topics: Vec<DescribeQuorumTopicResult>,
+}
+
+impl Serializable for DescribeQuorumResponse {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(self.error_code.to_be_bytes());
+        buf.extend(UnsignedVarint(self.topics.len() as u32 + 1).serialize());
+        for topic in &self.topics {
+            buf.extend(topic.serialize_versioned(self.version));
+        }
+        buf.extend(TagSection(None).serialize());
+        buf
+    }
+
+    fn deserialize(_bytes: &[u8]) -> Result<(Self, &[u8])> {
+        todo!()
+    }
+}
+
+impl ResponseBody for DescribeQuorumResponse {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn response(version: i16) -> DescribeQuorumResponse {
+        DescribeQuorumResponse {
+            version,
+            error_code: error::NONE,
+            topics: vec![DescribeQuorumTopicResult {
+                topic_name: CompactString(Some("m".to_string())),
+                partitions: vec![DescribeQuorumPartitionResult {
+                    partition_index: 0,
+                    error_code: error::NONE,
+                    leader_id: 1,
+                    leader_epoch: 2,
+                    high_watermark: 3,
+                    current_voters: vec![ReplicaStateResult {
+                        replica_id: 1,
+                        log_end_offset: 4,
+                        last_fetch_timestamp: 5,
+                        last_caught_up_timestamp: 6,
+                    }],
+                    observers: vec![],
+                }],
+            }],
+        }
+    }
+
+    #[test]
+    fn reads_the_partitions_to_describe() {
+        let body = [
+            &[2, METADATA_TOPIC.len() as u8 + 1][..],
+            METADATA_TOPIC.as_bytes(),
+            &[2, 0, 0, 0, 0, 0, 0, 0],
+        ]
+        .concat();
+        let (request, rest) = DescribeQuorumRequest::deserialize(&body).unwrap();
+        assert!(rest.is_empty());
+        let topics = request.topics.0.unwrap();
+        assert_eq!(topics[0].topic_name.as_deref(), Some(METADATA_TOPIC));
+        let partitions = topics[0].partitions.0.as_ref().unwrap();
+        assert_eq!(partitions[0].partition_index, METADATA_PARTITION);
+    }
+
+    #[test]
+    fn writes_replica_timestamps_from_version_1() {
+        let partition = [
+            &[0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 2][..],
+            &3i64.to_be_bytes(),
+            &[2, 0, 0, 0, 1],
+            &4i64.to_be_bytes(),
+        ]
+        .concat();
+        let v0 = [
+            &[0, 0, 2, 2, b'm', 2][..],
+            &partition,
+            // Voter tags, no observers, then the partition, topic and response tags
+            &[0, 1, 0, 0, 0],
+        ]
+        .concat();
+        assert_eq!(response(0).serialize(), v0);
+
+        let v1 = [
+            &[0, 0, 2, 2, b'm', 2][..],
+            &partition,
+            &5i64.to_be_bytes(),
+            &6i64.to_be_bytes(),
+            &[0, 1, 0, 0, 0],
+        ]
+        .concat();
+        assert_eq!(response(1).serialize(), v1);
+    }
+
+    #[test]
+    fn writes_errors_without_replicas() {
+        let response = DescribeQuorumResponse {
+            version: 1,
+            error_code: error::NONE,
+            topics: vec![DescribeQuorumTopicResult {
+                topic_name: CompactString(Some("m".to_string())),
+                partitions: vec![DescribeQuorumPartitionResult::error(
+                    1,
+                    error::UNKNOWN_TOPIC_OR_PARTITION,
+                    -1,
+                    -1,
+                )],
+            }],
+        };
+        let expected = [
+            &[0, 0, 2, 2, b'm', 2, 0, 0, 0, 1][..],
+            &error::UNKNOWN_TOPIC_OR_PARTITION.to_be_bytes(),
+            &[0xff; 8],
+            &(-1i64).to_be_bytes(),
+            &[1, 1, 0, 0, 0],
+        ]
+        .concat();
+        assert_eq!(response.serialize(), expected);
+    }
+}
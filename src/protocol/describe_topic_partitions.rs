@@ -1,9 +1,16 @@
 use super::{
+    api_keys::{RequestContext, RequestHandler},
     body::ResponseBody,
-    header::{ResponseHeader, ResponseHeaderV1},
+    error,
+    metadata::{describe_partition, MetadataResponsePartition},
     primitive::{CompactArray, CompactString, Serializable, TagSection},
     response::Response,
 };
+use crate::{
+    broker::Broker,
+    security::acl::{AclOperation, ResourceType},
+    server::ConnectionContext,
+};
 use anyhow::{anyhow, Ok, Result};
 use uuid::Uuid;
 
@@ -51,7 +58,7 @@ impl Serializable for TopicResponse {
         buf.extend(self.tag_buffer.serialize());
         buf
     }
-    fn deserialize(bytes: &[u8]) -> Result<(Self, &[u8])> {
+    fn deserialize(_bytes: &[u8]) -> Result<(Self, &[u8])> {
         todo!()
     }
 }
@@ -59,6 +66,15 @@ impl Serializable for TopicResponse {
 impl TopicResponse {
     pub fn unknown_topic(name: String) -> Self {
         TopicResponse {
+            error_code: error::UNKNOWN_TOPIC_OR_PARTITION,
+            name: CompactString(Some(name)),
+            ..Default::default()
+        }
+    }
+
+    pub fn unauthorized_topic(name: String) -> Self {
+        TopicResponse {
+            error_code: error::TOPIC_AUTHORIZATION_FAILED,
             name: CompactString(Some(name)),
             ..Default::default()
         }
@@ -67,22 +83,54 @@ impl TopicResponse {
 
 #[derive(Debug, Default)]
 pub struct Partition {
-    error_code: i16,
+    pub error_code: i16,
+    pub partition_index: i32,
+    pub leader_id: i32,
+    pub leader_epoch: i32,
+    pub replica_nodes: Vec<i32>,
+    pub isr_nodes: Vec<i32>,
+    /// Null, as eligible leader replicas (KIP-966) are not tracked
+    pub eligible_leader_replicas: Option<Vec<i32>>,
+    pub last_known_elr: Option<Vec<i32>>,
+    pub offline_replicas: Vec<i32>,
+    pub tag_buffer: TagSection,
+}
+
+impl From<MetadataResponsePartition> for Partition {
+    fn from(partition: MetadataResponsePartition) -> Self {
+        Partition {
+            error_code: partition.error_code,
+            partition_index: partition.partition_index,
+            leader_id: partition.leader_id,
+            leader_epoch: partition.leader_epoch,
+            replica_nodes: partition.replica_nodes,
+            isr_nodes: partition.isr_nodes,
+            eligible_leader_replicas: None,
+            last_known_elr: None,
+            offline_replicas: partition.offline_replicas,
+            tag_buffer: TagSection(None),
+        }
+    }
 }
 
 impl Serializable for Partition {
     fn serialize(&self) -> Vec<u8> {
         let mut buf = Vec::new();
         buf.extend(self.error_code.to_be_bytes());
+        buf.extend(self.partition_index.to_be_bytes());
+        buf.extend(self.leader_id.to_be_bytes());
+        buf.extend(self.leader_epoch.to_be_bytes());
+        buf.extend(CompactArray(Some(self.replica_nodes.clone())).serialize());
+        buf.extend(CompactArray(Some(self.isr_nodes.clone())).serialize());
+        buf.extend(CompactArray(self.eligible_leader_replicas.clone()).serialize());
+        buf.extend(CompactArray(self.last_known_elr.clone()).serialize());
+        buf.extend(CompactArray(Some(self.offline_replicas.clone())).serialize());
+        buf.extend(self.tag_buffer.serialize());
         buf
     }
 
-    fn deserialize(bytes: &[u8]) -> Result<(Self, &[u8])> {
-        let (error_code, bytes) = bytes
-            .split_at_checked(2)
-            .ok_or(anyhow!("Error: not enough bytes left"))?;
-        let error_code = i16::from_be_bytes(error_code.try_into()?);
-        Ok((Partition { error_code }, bytes))
+    fn deserialize(_bytes: &[u8]) -> Result<(Self, &[u8])> {
+        todo!()
     }
 }
 
@@ -93,6 +141,16 @@ pub struct Cursor {
     tag_buffer: TagSection,
 }
 
+impl Cursor {
+    fn new(topic_name: &str, partition_index: i32) -> Self {
+        Cursor {
+            topic_name: CompactString(Some(topic_name.to_string())),
+            partition_index,
+            tag_buffer: TagSection(None),
+        }
+    }
+}
+
 impl Serializable for Cursor {
     fn serialize(&self) -> Vec<u8> {
         let mut buf = Vec::new();
@@ -133,7 +191,10 @@ impl Serializable for DescribeTopicPartitionsRequest {
         buf.extend(self.topics.serialize());
         buf.extend(self.response_partition_limit.to_be_bytes());
         match self.cursor {
-            Some(ref cursor) => buf.extend(cursor.serialize()),
+            Some(ref cursor) => {
+                buf.push(1);
+                buf.extend(cursor.serialize());
+            }
             None => buf.push(0xff),
         }
         buf.extend(self.tag_buffer.serialize());
@@ -145,10 +206,12 @@ impl Serializable for DescribeTopicPartitionsRequest {
             .split_at_checked(4)
             .ok_or(anyhow!("Error: not enough bytes left"))?;
         let response_partition_limit = i32::from_be_bytes(response_partition_limit.try_into()?);
-        let (cursor, bytes) = if bytes[0] == 0xff {
-            (None, &bytes[1..])
+        // A nullable struct is preceded by -1 when null and 1 otherwise
+        let (present, bytes) = i8::deserialize(bytes)?;
+        let (cursor, bytes) = if present < 0 {
+            (None, bytes)
         } else {
-            let (cursor, bytes) = Cursor::deserialize(&bytes[1..])?;
+            let (cursor, bytes) = Cursor::deserialize(bytes)?;
             (Some(cursor), bytes)
         };
         let (tag_buffer, bytes) = TagSection::deserialize(bytes)?;
@@ -165,32 +228,100 @@ impl Serializable for DescribeTopicPartitionsRequest {
 }
 
 impl DescribeTopicPartitionsRequest {
-    pub fn handle_request(&self, correlation_id: i32) -> Option<Response> {
-        let response_header = ResponseHeader::V1(ResponseHeaderV1 {
-            correlation_id,
-            tag_buffer: TagSection(None),
-        });
+    /// Topics are described in name order, from the cursor on, with up to
+    /// `response_partition_limit` partitions; the cursor of the response points at the
+    /// first partition left out.
+    pub fn handle_request(&self, broker: &Broker, context: &ConnectionContext) -> Option<Response> {
+        let image = broker.metadata.read().unwrap();
+        let mut names: Vec<&String> = self
+            .topics
+            .iter()
+            .flatten()
+            .filter_map(|topic| topic.name.as_ref())
+            .collect();
+        names.sort();
+        names.dedup();
 
+        let mut remaining = self.response_partition_limit.max(1);
         let mut response_topics = vec![];
-        if let Some(topics) = self.topics.as_ref() {
-            for topic in topics {
-                if let Some(topic_name) = topic.name.as_ref() {
-                    response_topics.push(TopicResponse::unknown_topic(topic_name.clone()))
+        let mut next_cursor = None;
+        for topic_name in names {
+            let first_partition = match &self.cursor {
+                Some(cursor) => match cursor.topic_name.as_deref() {
+                    Some(cursor_topic) if topic_name.as_str() < cursor_topic => continue,
+                    Some(cursor_topic) if topic_name == cursor_topic => cursor.partition_index,
+                    _ => 0,
+                },
+                None => 0,
+            };
+            if !broker.authorize(
+                context,
+                AclOperation::Describe,
+                ResourceType::Topic,
+                topic_name,
+            ) {
+                response_topics.push(TopicResponse::unauthorized_topic(topic_name.clone()));
+                continue;
+            }
+            let Some(&topic_id) = image.topics.get(topic_name) else {
+                response_topics.push(TopicResponse::unknown_topic(topic_name.clone()));
+                continue;
+            };
+            if remaining == 0 {
+                next_cursor = Some(Cursor::new(topic_name, first_partition));
+                break;
+            }
+
+            let mut partitions = vec![];
+            for (&partition_index, registration) in image
+                .partitions
+                .get(&topic_id)
+                .into_iter()
+                .flatten()
+                .filter(|(&partition_index, _)| partition_index >= first_partition)
+            {
+                if remaining == 0 {
+                    next_cursor = Some(Cursor::new(topic_name, partition_index));
+                    break;
                 }
+                remaining -= 1;
+                partitions.push(
+                    describe_partition(&image, context, partition_index, registration).into(),
+                );
             }
-        }
-        let response_body =
-            ResponseBody::DescribeTopicPartitions(DescribeTopicPartitionsResponse {
-                throttle_time: 0,
-                topics: CompactArray(Some(response_topics)),
-                next_cursor: None,
+            response_topics.push(TopicResponse {
+                error_code: error::NONE,
+                name: CompactString(Some(topic_name.clone())),
+                topic_id,
+                is_internal: false,
+                partitions: CompactArray(Some(partitions)),
+                topic_authorized_operations: broker.authorized_operations(
+                    context,
+                    ResourceType::Topic,
+                    topic_name,
+                ),
                 tag_buffer: TagSection(None),
             });
+            if next_cursor.is_some() {
+                break;
+            }
+        }
+        Some(Box::new(DescribeTopicPartitionsResponse {
+            throttle_time: 0,
+            topics: CompactArray(Some(response_topics)),
+            next_cursor,
+            tag_buffer: TagSection(None),
+        }))
+    }
+}
+
+impl RequestHandler for DescribeTopicPartitionsRequest {
+    fn read(bytes: &[u8], _version: i16) -> Result<Self> {
+        Ok(DescribeTopicPartitionsRequest::deserialize(bytes)?.0)
+    }
 
-        Some(Response {
-            header: response_header,
-            body: response_body,
-        })
+    fn handle(&self, request: &mut RequestContext) -> Option<Response> {
+        self.handle_request(request.broker, request.connection)
     }
 }
 
@@ -208,13 +339,71 @@ impl Serializable for DescribeTopicPartitionsResponse {
         buf.extend(self.throttle_time.to_be_bytes());
         buf.extend(self.topics.serialize());
         match self.next_cursor {
-            Some(ref cursor) => buf.extend(cursor.serialize()),
+            Some(ref cursor) => {
+                buf.push(1);
+                buf.extend(cursor.serialize());
+            }
             None => buf.push(0xff),
         }
         buf.extend(self.tag_buffer.serialize());
         buf
     }
-    fn deserialize(bytes: &[u8]) -> Result<(Self, &[u8])> {
+    fn deserialize(_bytes: &[u8]) -> Result<(Self, &[u8])> {
         todo!()
     }
 }
+
+impl ResponseBody for DescribeTopicPartitionsResponse {
+    fn set_throttle_time_ms(&mut self, throttle_time_ms: i32) {
+        self.throttle_time = throttle_time_ms;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips_the_request_cursor() {
+        let request = DescribeTopicPartitionsRequest {
+            topics: CompactArray(Some(vec![])),
+            response_partition_limit: 2,
+            cursor: Some(Cursor::new("foo", 3)),
+            tag_buffer: TagSection(None),
+        };
+        let bytes = request.serialize();
+        assert_eq!(&bytes[5..7], &[1, 4]);
+        let (read, rest) = DescribeTopicPartitionsRequest::deserialize(&bytes).unwrap();
+        assert!(rest.is_empty());
+        let cursor = read.cursor.unwrap();
+        assert_eq!(cursor.topic_name.as_deref(), Some("foo"));
+        assert_eq!(cursor.partition_index, 3);
+    }
+
+    #[test]
+    fn writes_partitions_with_null_eligible_leader_replicas() {
+        let partition = Partition {
+            partition_index: 1,
+            leader_id: 2,
+            leader_epoch: 3,
+            replica_nodes: vec![2],
+            isr_nodes: vec![2],
+            ..Default::default()
+        };
+        let expected = [
+            &[0, 0, 0, 0, 0, 1, 0, 0, 0, 2, 0, 0, 0, 3][..],
+            &[2, 0, 0, 0, 2, 2, 0, 0, 0, 2],
+            // Eligible leader replicas, last known ELR and offline replicas
+            &[0, 0, 1],
+            &[0],
+        ]
+        .concat();
+        assert_eq!(partition.serialize(), expected);
+    }
+
+    #[test]
+    fn reports_unknown_topics_as_an_error() {
+        let topic = TopicResponse::unknown_topic("foo".to_string());
+        assert_eq!(topic.error_code, error::UNKNOWN_TOPIC_OR_PARTITION);
+    }
+}
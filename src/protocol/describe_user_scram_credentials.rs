@@ -0,0 +1,313 @@
+use std::collections::BTreeMap;
+
+use anyhow::Result;
+
+use super::{
+    api_keys::{RequestContext, RequestHandler},
+    body::ResponseBody,
+    error,
+    primitive::{CompactArray, CompactString, Serializable, TagSection},
+    response::Response,
+};
+use crate::{
+    broker::Broker,
+    security::acl::{AclOperation, ResourceType, CLUSTER_RESOURCE_NAME},
+    server::ConnectionContext,
+};
+
+#[derive(Debug)]
+pub struct UserName {
+    pub name: CompactString,
+    pub tag_buffer: TagSection,
+}
+
+impl Serializable for UserName {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(self.name.serialize());
+        buf.extend(self.tag_buffer.serialize());
+        buf
+    }
+
+    fn deserialize(bytes: &[u8]) -> Result<(Self, &[u8])> {
+        let (name, bytes) = CompactString::deserialize(bytes)?;
+        let (tag_buffer, bytes) = TagSection::deserialize(bytes)?;
+        Ok((UserName { name, tag_buffer }, bytes))
+    }
+}
+
+#[derive(Debug)]
+pub struct DescribeUserScramCredentialsRequest {
+    /// Users to describe, or null for every user with a credential
+    pub users: CompactArray<UserName>,
+    pub tag_buffer: TagSection,
+}
+
+impl Serializable for DescribeUserScramCredentialsRequest {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(self.users.serialize());
+        buf.extend(self.tag_buffer.serialize());
+        buf
+    }
+
+    fn deserialize(bytes: &[u8]) -> Result<(Self, &[u8])> {
+        let (users, bytes) = CompactArray::<UserName>::deserialize(bytes)?;
+        let (tag_buffer, bytes) = TagSection::deserialize(bytes)?;
+        Ok((
+            DescribeUserScramCredentialsRequest { users, tag_buffer },
+            bytes,
+        ))
+    }
+}
+
+impl DescribeUserScramCredentialsRequest {
+    pub fn handle_request(&self, broker: &Broker, context: &ConnectionContext) -> Option<Response> {
+        if !broker.authorize(
+            context,
+            AclOperation::Describe,
+            ResourceType::Cluster,
+            CLUSTER_RESOURCE_NAME,
+        ) {
+            return Some(Box::new(DescribeUserScramCredentialsResponse {
+                throttle_time_ms: 0,
+                error_code: error::CLUSTER_AUTHORIZATION_FAILED,
+                error_message: CompactString(Some("Cluster authorization failed".to_string())),
+                results: CompactArray(Some(vec![])),
+                tag_buffer: TagSection(None),
+            }));
+        }
+
+        // Credentials of every user, sorted by name for a stable output
+        let mut credentials: BTreeMap<String, Vec<CredentialInfo>> = BTreeMap::new();
+        for ((user, mechanism), credential) in &broker.metadata.read().unwrap().scram_credentials {
+            credentials
+                .entry(user.clone())
+                .or_default()
+                .push(CredentialInfo {
+                    mechanism: mechanism.type_id(),
+                    iterations: credential.iterations,
+                    tag_buffer: TagSection(None),
+                });
+        }
+        for infos in credentials.values_mut() {
+            infos.sort_by_key(|info| info.mechanism);
+        }
+
+        let results = match self.users.as_ref() {
+            None => credentials
+                .into_iter()
+                .map(|(user, infos)| DescribeUserScramCredentialsResult::found(user, infos))
+                .collect(),
+            Some(users) => {
+                let mut results = vec![];
+                for (i, user) in users.iter().enumerate() {
+                    let name = user.name.as_deref().unwrap_or_default();
+                    let occurrences = users
+                        .iter()
+                        .filter(|u| u.name.as_deref().unwrap_or_default() == name)
+                        .count();
+                    // Report a duplicated user only once
+                    if users[..i]
+                        .iter()
+                        .any(|u| u.name.as_deref().unwrap_or_default() == name)
+                    {
+                        continue;
+                    }
+                    let result = if occurrences > 1 {
+                        DescribeUserScramCredentialsResult::error(
+                            name,
+                            error::DUPLICATE_RESOURCE,
+                            "Cannot describe SCRAM credentials for the same user twice in a single request",
+                        )
+                    } else {
+                        match credentials.get(name) {
+                            Some(infos) => DescribeUserScramCredentialsResult::found(
+                                name.to_string(),
+                                infos.clone(),
+                            ),
+                            None => DescribeUserScramCredentialsResult::error(
+                                name,
+                                error::RESOURCE_NOT_FOUND,
+                                "Attempt to describe a user credential that does not exist",
+                            ),
+                        }
+                    };
+                    results.push(result);
+                }
+                results
+            }
+        };
+
+        Some(Box::new(DescribeUserScramCredentialsResponse {
+            throttle_time_ms: 0,
+            error_code: error::NONE,
+            error_message: CompactString(None),
+            results: CompactArray(Some(results)),
+            tag_buffer: TagSection(None),
+        }))
+    }
+}
+
+impl RequestHandler for DescribeUserScramCredentialsRequest {
+    fn read(bytes: &[u8], _version: i16) -> Result<Self> {
+        Ok(DescribeUserScramCredentialsRequest::deserialize(bytes)?.0)
+    }
+
+    fn handle(&self, request: &mut RequestContext) -> Option<Response> {
+        self.handle_request(request.broker, request.connection)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CredentialInfo {
+    pub mechanism: i8,
+    pub iterations: i32,
+    pub tag_buffer: TagSection,
+}
+
+impl Serializable for CredentialInfo {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.push(self.mechanism as u8);
+        buf.extend(self.iterations.to_be_bytes());
+        buf.extend(self.tag_buffer.serialize());
+        buf
+    }
+
+    fn deserialize(_bytes: &[u8]) -> Result<(Self, &[u8])> {
+        todo!()
+    }
+}
+
+#[derive(Debug)]
+pub struct DescribeUserScramCredentialsResult {
+    pub user: CompactString,
+    pub error_code: i16,
+    pub error_message: CompactString,
+    pub credential_infos: CompactArray<CredentialInfo>,
+    pub tag_buffer: TagSection,
+}
+
+impl DescribeUserScramCredentialsResult {
+    fn found(user: String, infos: Vec<CredentialInfo>) -> Self {
+        DescribeUserScramCredentialsResult {
+            user: CompactString(Some(user)),
+            error_code: error::NONE,
+            error_message: CompactString(None),
+            credential_infos: CompactArray(Some(infos)),
+            tag_buffer: TagSection(None),
+        }
+    }
+
+    fn error(user: &str, error_code: i16, message: &str) -> Self {
+        DescribeUserScramCredentialsResult {
+            user: CompactString(Some(user.to_string())),
+            error_code,
+            error_message: CompactString(Some(message.to_string())),
+            credential_infos: CompactArray(Some(vec![])),
+            tag_buffer: TagSection(None),
+        }
+    }
+}
+
+impl Serializable for DescribeUserScramCredentialsResult {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(self.user.serialize());
+        buf.extend(self.error_code.to_be_bytes());
+        buf.extend(self.error_message.serialize());
+        buf.extend(self.credential_infos.serialize());
+        buf.extend(self.tag_buffer.serialize());
+        buf
+    }
+
+    fn deserialize(_bytes: &[u8]) -> Result<(Self, &[u8])> {
+        todo!()
+    }
+}
+
+#[derive(Debug)]
+pub struct DescribeUserScramCredentialsResponse {
+    pub throttle_time_ms: i32,
+    pub error_code: i16,
+    pub error_message: CompactString,
+    pub results: CompactArray<DescribeUserScramCredentialsResult>,
+    pub tag_buffer: TagSection,
+}
+
+impl Serializable for DescribeUserScramCredentialsResponse {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(self.throttle_time_ms.to_be_bytes());
+        buf.extend(self.error_code.to_be_bytes());
+        buf.extend(self.error_message.serialize());
+        buf.extend(self.results.serialize());
+        buf.extend(self.tag_buffer.serialize());
+        buf
+    }
+
+    fn deserialize(_bytes: &[u8]) -> Result<(Self, &[u8])> {
+        todo!()
+    }
+}
+
+impl ResponseBody for DescribeUserScramCredentialsResponse {
+    fn set_throttle_time_ms(&mut self, throttle_time_ms: i32) {
+        self.throttle_time_ms = throttle_time_ms;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_null_users_as_every_user() {
+        let (request, rest) = DescribeUserScramCredentialsRequest::deserialize(&[0, 0]).unwrap();
+        assert!(request.users.0.is_none());
+        assert!(rest.is_empty());
+
+        let body = [&[2, 6][..], b"alice", &[0, 0]].concat();
+        let (request, rest) = DescribeUserScramCredentialsRequest::deserialize(&body).unwrap();
+        let users = request.users.0.unwrap();
+        assert_eq!(users.len(), 1);
+        assert_eq!(users[0].name.as_deref(), Some("alice"));
+        assert!(rest.is_empty());
+    }
+
+    #[test]
+    fn writes_credentials_and_errors_per_user() {
+        let info = CredentialInfo {
+            mechanism: 1,
+            iterations: 4096,
+            tag_buffer: TagSection(None),
+        };
+        let response = DescribeUserScramCredentialsResponse {
+            throttle_time_ms: 0,
+            error_code: error::NONE,
+            error_message: CompactString(None),
+            results: CompactArray(Some(vec![
+                DescribeUserScramCredentialsResult::found("alice".to_string(), vec![info]),
+                DescribeUserScramCredentialsResult::error("bob", error::RESOURCE_NOT_FOUND, "gone"),
+            ])),
+            tag_buffer: TagSection(None),
+        };
+        let expected = [
+            &[0, 0, 0, 0, 0, 0, 0, 3][..],
+            &[6],
+            b"alice",
+            // No error, then one SCRAM-SHA-256 credential of 4096 iterations
+            &[0, 0, 0, 2, 1, 0, 0, 0x10, 0, 0, 0],
+            &[4],
+            b"bob",
+            &error::RESOURCE_NOT_FOUND.to_be_bytes(),
+            &[5],
+            b"gone",
+            &[1, 0],
+            &[0],
+        ]
+        .concat();
+        assert_eq!(response.serialize(), expected);
+    }
+}
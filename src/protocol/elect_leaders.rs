@@ -0,0 +1,340 @@
+use anyhow::{bail, Result};
+
+use super::{
+    api_keys::{self, RequestContext, RequestHandler, ELECT_LEADERS_KEY},
+    body::ResponseBody,
+    cluster_metadata::MetadataRecord,
+    error,
+    primitive::{array_len, CompactString, KafkaString, Serializable, TagSection, UnsignedVarint},
+    response::Response,
+};
+use crate::{
+    broker::Broker,
+    controller::{self, ElectionType},
+    raft::RaftError,
+    security::acl::{AclOperation, ResourceType, CLUSTER_RESOURCE_NAME},
+    server::ConnectionContext,
+};
+
+#[derive(Debug)]
+pub struct TopicPartitions {
+    pub topic: String,
+    pub partitions: Vec<i32>,
+}
+
+/// ElectLeaders versions 0 to 2. Version 1 adds the election type, and version 2 uses
+/// the compact (flexible) encoding.
+#[derive(Debug)]
+pub struct ElectLeadersRequest {
+    pub election_type: i8,
+    /// Partitions to elect a leader for, null for all of them
+    pub topic_partitions: Option<Vec<TopicPartitions>>,
+    pub timeout_ms: i32,
+}
+
+impl ElectLeadersRequest {
+    pub fn deserialize_versioned(bytes: &[u8], version: i16) -> Result<(Self, &[u8])> {
+        let flexible = api_keys::is_flexible(ELECT_LEADERS_KEY, version);
+        let (election_type, bytes) = if version >= 1 {
+            i8::deserialize(bytes)?
+        } else {
+            (0, bytes)
+        };
+        let (len, mut bytes) = array_len(bytes, flexible)?;
+        let topic_partitions = match len {
+            None => None,
+            Some(len) => {
+                let mut topics = Vec::with_capacity(len.min(bytes.len()));
+                for _ in 0..len {
+                    let (topic, rest) = if flexible {
+                        let (topic, rest) = CompactString::deserialize(bytes)?;
+                        (topic.0, rest)
+                    } else {
+                        let (topic, rest) = KafkaString::deserialize(bytes)?;
+                        (topic.0, rest)
+                    };
+                    let Some(topic) = topic else {
+                        bail!("null topic name in ElectLeaders request");
+                    };
+                    let (count, mut rest) = array_len(rest, flexible)?;
+                    let mut partitions = vec![];
+                    for _ in 0..count.unwrap_or_default() {
+                        let (partition, next) = i32::deserialize(rest)?;
+                        partitions.push(partition);
+                        rest = next;
+                    }
+                    if flexible {
+                        rest = TagSection::deserialize(rest)?.1;
+                    }
+                    topics.push(TopicPartitions { topic, partitions });
+                    bytes = rest;
+                }
+                Some(topics)
+            }
+        };
+        let (timeout_ms, mut bytes) = i32::deserialize(bytes)?;
+        if flexible {
+            bytes = TagSection::deserialize(bytes)?.1;
+        }
+        Ok((
+            ElectLeadersRequest {
+                election_type,
+                topic_partitions,
+                timeout_ms,
+            },
+            bytes,
+        ))
+    }
+
+    /// Elections are committed to the metadata log before the response is sent, so the
+    /// timeout only bounds the wait for the quorum as any other metadata write does.
+    pub fn handle_request(
+        &self,
+        version: i16,
+        broker: &Broker,
+        context: &ConnectionContext,
+    ) -> Option<Response> {
+        let mut response = ElectLeadersResponse {
+            version,
+            throttle_time_ms: 0,
+            error_code: error::NONE,
+            replica_election_results: vec![],
+        };
+
+        let requested = self.requested_partitions(broker);
+        let election_type = ElectionType::from_code(self.election_type);
+        let outcomes = if !broker.authorize(
+            context,
+            AclOperation::Alter,
+            ResourceType::Cluster,
+            CLUSTER_RESOURCE_NAME,
+        ) {
+            response.error_code = error::CLUSTER_AUTHORIZATION_FAILED;
+            let code = error::CLUSTER_AUTHORIZATION_FAILED;
+            requested.into_iter().map(|(t, p)| (t, p, code)).collect()
+        } else if election_type.is_some() && !broker.raft.is_leader() {
+            response.error_code = error::NOT_CONTROLLER;
+            let code = error::NOT_CONTROLLER;
+            requested.into_iter().map(|(t, p)| (t, p, code)).collect()
+        } else if let Some(election_type) = election_type {
+            self.elect(broker, requested, election_type)
+        } else {
+            response.error_code = error::INVALID_REQUEST;
+            let code = error::INVALID_REQUEST;
+            requested.into_iter().map(|(t, p)| (t, p, code)).collect()
+        };
+
+        for (topic, partition_id, error_code) in outcomes {
+            let result = PartitionResult {
+                partition_id,
+                error_code,
+                error_message: error_message(error_code).map(str::to_string),
+            };
+            match response
+                .replica_election_results
+                .iter_mut()
+                .find(|result| result.topic == topic)
+            {
+                Some(topic_result) => topic_result.partition_results.push(result),
+                None => response
+                    .replica_election_results
+                    .push(ReplicaElectionResult {
+                        topic,
+                        partition_results: vec![result],
+                    }),
+            }
+        }
+
+        Some(Box::new(response))
+    }
+
+    /// Partitions the request names, or every partition of the cluster.
+    fn requested_partitions(&self, broker: &Broker) -> Vec<(String, i32)> {
+        match &self.topic_partitions {
+            Some(topics) => topics
+                .iter()
+                .flat_map(|topic| {
+                    let name = &topic.topic;
+                    topic.partitions.iter().map(|&p| (name.clone(), p))
+                })
+                .collect(),
+            None => {
+                let metadata = broker.metadata.read().unwrap();
+                let mut partitions: Vec<(String, i32)> = metadata
+                    .topics
+                    .iter()
+                    .flat_map(|(topic, topic_id)| {
+                        let partitions = metadata.partitions.get(topic_id).into_iter();
+                        partitions.flat_map(|p| p.keys().map(|&p| (topic.clone(), p)))
+                    })
+                    .collect();
+                partitions.sort();
+                partitions
+            }
+        }
+    }
+
+    /// Elect the leaders and commit the changes, returning the outcome for each
+    /// partition. Asking for every partition only reports those an election was needed
+    /// for.
+    fn elect(
+        &self,
+        broker: &Broker,
+        requested: Vec<(String, i32)>,
+        election_type: ElectionType,
+    ) -> Vec<(String, i32, i16)> {
+        let mut outcomes = vec![];
+        let committed = broker.append_metadata(|_| {
+            let image = broker.metadata.read().unwrap();
+            let mut records = vec![];
+            for (topic, partition) in requested {
+                let error_code = match image.partition(&topic, partition) {
+                    None => error::UNKNOWN_TOPIC_OR_PARTITION,
+                    Some((topic_id, registration)) => {
+                        match controller::elect_leader(
+                            &image,
+                            topic_id,
+                            partition,
+                            registration,
+                            election_type,
+                        ) {
+                            Ok(record) => {
+                                eprintln!(
+                                    "Electing broker {} leader of {topic}-{partition}",
+                                    record.leader
+                                );
+                                records.push(MetadataRecord::PartitionChange(record));
+                                error::NONE
+                            }
+                            Err(error_code) => error_code,
+                        }
+                    }
+                };
+                outcomes.push((topic, partition, error_code));
+            }
+            records
+        });
+        if let Err(e) = committed {
+            eprintln!("Failed to elect leaders: {e:#}");
+            let error_code = RaftError::error_code(&e);
+            for outcome in outcomes.iter_mut().filter(|o| o.2 == error::NONE) {
+                outcome.2 = error_code;
+            }
+        }
+        if self.topic_partitions.is_none() {
+            outcomes.retain(|outcome| outcome.2 != error::ELECTION_NOT_NEEDED);
+        }
+        outcomes
+    }
+}
+
+impl RequestHandler for ElectLeadersRequest {
+    fn read(bytes: &[u8], version: i16) -> Result<Self> {
+        Ok(ElectLeadersRequest::deserialize_versioned(bytes, version)?.0)
+    }
+
+    fn handle(&self, request: &mut RequestContext) -> Option<Response> {
+        self.handle_request(request.api_version, request.broker, request.connection)
+    }
+}
+
+fn error_message(error_code: i16) -> Option<&'static str> {
+    match error_code {
+        error::NONE => None,
+        error::UNKNOWN_TOPIC_OR_PARTITION => Some("The partition does not exist"),
+        error::ELECTION_NOT_NEEDED => Some("Leader election not needed for the partition"),
+        error::PREFERRED_LEADER_NOT_AVAILABLE => {
+            Some("The preferred replica is not in sync or not available")
+        }
+        error::ELIGIBLE_LEADERS_NOT_AVAILABLE => Some("No replica of the partition is available"),
+        error::CLUSTER_AUTHORIZATION_FAILED => Some("Not authorized to elect leaders"),
+        error::INVALID_REQUEST => Some("Unknown election type"),
+        error::NOT_CONTROLLER => Some("This node is not the active controller"),
+        _ => None,
+    }
+}
+
+#[derive(Debug)]
+pub struct PartitionResult {
+    pub partition_id: i32,
+    pub error_code: i16,
+    pub error_message: Option<String>,
+}
+
+#[derive(Debug)]
+pub struct ReplicaElectionResult {
+    pub topic: String,
+    pub partition_results: Vec<PartitionResult>,
+}
+
+#[derive(Debug)]
+pub struct ElectLeadersResponse {
+    pub version: i16,
+    pub throttle_time_ms: i32,
+    /// Version 1 and above
+    pub error_code: i16,
+    pub replica_election_results: Vec<ReplicaElectionResult>,
+}
+
+impl ElectLeadersResponse {
+    fn flexible(&self) -> bool {
+        api_keys::is_flexible(ELECT_LEADERS_KEY, self.version)
+    }
+
+    fn serialize_string(&self, value: Option<String>) -> Vec<u8> {
+        if self.flexible() {
+            CompactString(value).serialize()
+        } else {
+            KafkaString(value).serialize()
+        }
+    }
+
+    fn serialize_len(&self, len: usize) -> Vec<u8> {
+        if self.flexible() {
+            UnsignedVarint(len as u32 + 1).serialize()
+        } else {
+            (len as i32).to_be_bytes().to_vec()
+        }
+    }
+}
+
+impl Serializable for ElectLeadersResponse {
+    fn serialize(&self) -> Vec<u8> {
+        let flexible = self.flexible();
+        let mut buf = Vec::new();
+        buf.extend(self.throttle_time_ms.to_be_bytes());
+        if self.version >= 1 {
+            buf.extend(self.error_code.to_be_bytes());
+        }
+        buf.extend(self.serialize_len(self.replica_election_results.len()));
+        for result in &self.replica_election_results {
+            buf.extend(self.serialize_string(Some(result.topic.clone())));
+            buf.extend(self.serialize_len(result.partition_results.len()));
+            for partition in &result.partition_results {
+                buf.extend(partition.partition_id.to_be_bytes());
+                buf.extend(partition.error_code.to_be_bytes());
+                buf.extend(self.serialize_string(partition.error_message.clone()));
+                if flexible {
+                    buf.extend(TagSection(None).serialize());
+                }
+            }
+            if flexible {
+                buf.extend(TagSection(None).serialize());
+            }
+        }
+        if flexible {
+            buf.extend(TagSection(None).serialize());
+        }
+        buf
+    }
+
+    fn deserialize(_bytes: &[u8]) -> Result<(Self, &[u8])> {
+        todo!()
+    }
+}
+
+impl ResponseBody for ElectLeadersResponse {
+    fn set_throttle_time_ms(&mut self, throttle_time_ms: i32) {
+        self.throttle_time_ms = throttle_time_ms;
+    }
+}
@@ -0,0 +1,190 @@
+use anyhow::Result;
+
+use super::{
+    api_keys::{RequestContext, RequestHandler},
+    begin_quorum_epoch::{QuorumEpochPartitionResponse, QuorumEpochTopicResponse},
+    body::ResponseBody,
+    error,
+    primitive::{KafkaArray, KafkaString, Serializable},
+    response::Response,
+};
+use crate::{
+    broker::Broker,
+    raft::METADATA_TOPIC,
+    security::acl::{AclOperation, ResourceType, CLUSTER_RESOURCE_NAME},
+    server::ConnectionContext,
+};
+
+#[derive(Debug)]
+pub struct EndQuorumEpochPartition {
+    pub partition_index: i32,
+    pub leader_id: i32,
+    pub leader_epoch: i32,
+    /// Voters best placed to lead next, most up to date first
+    pub preferred_successors: KafkaArray<i32>,
+}
+
+impl Serializable for EndQuorumEpochPartition {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(self.partition_index.to_be_bytes());
+        buf.extend(self.leader_id.to_be_bytes());
+        buf.extend(self.leader_epoch.to_be_bytes());
+        buf.extend(self.preferred_successors.serialize());
+        buf
+    }
+
+    fn deserialize(bytes: &[u8]) -> Result<(Self, &[u8])> {
+        let (partition_index, bytes) = i32::deserialize(bytes)?;
+        let (leader_id, bytes) = i32::deserialize(bytes)?;
+        let (leader_epoch, bytes) = i32::deserialize(bytes)?;
+        let (preferred_successors, bytes) = KafkaArray::<i32>::deserialize(bytes)?;
+        Ok((
+            EndQuorumEpochPartition {
+                partition_index,
+                leader_id,
+                leader_epoch,
+                preferred_successors,
+            },
+            bytes,
+        ))
+    }
+}
+
+#[derive(Debug)]
+pub struct EndQuorumEpochTopic {
+    pub topic_name: KafkaString,
+    pub partitions: KafkaArray<EndQuorumEpochPartition>,
+}
+
+impl Serializable for EndQuorumEpochTopic {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(self.topic_name.serialize());
+        buf.extend(self.partitions.serialize());
+        buf
+    }
+
+    fn deserialize(bytes: &[u8]) -> Result<(Self, &[u8])> {
+        let (topic_name, bytes) = KafkaString::deserialize(bytes)?;
+        let (partitions, bytes) = KafkaArray::<EndQuorumEpochPartition>::deserialize(bytes)?;
+        Ok((
+            EndQuorumEpochTopic {
+                topic_name,
+                partitions,
+            },
+            bytes,
+        ))
+    }
+}
+
+/// EndQuorumEpoch version 0, sent by a leader stepping down so that the other voters
+/// elect a new one without waiting for their fetch timeout.
+#[derive(Debug)]
+pub struct EndQuorumEpochRequest {
+    pub cluster_id: KafkaString,
+    pub topics: KafkaArray<EndQuorumEpochTopic>,
+}
+
+impl Serializable for EndQuorumEpochRequest {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(self.cluster_id.serialize());
+        buf.extend(self.topics.serialize());
+        buf
+    }
+
+    fn deserialize(bytes: &[u8]) -> Result<(Self, &[u8])> {
+        let (cluster_id, bytes) = KafkaString::deserialize(bytes)?;
+        let (topics, bytes) = KafkaArray::<EndQuorumEpochTopic>::deserialize(bytes)?;
+        Ok((EndQuorumEpochRequest { cluster_id, topics }, bytes))
+    }
+}
+
+impl EndQuorumEpochRequest {
+    pub fn handle_request(&self, broker: &Broker, context: &ConnectionContext) -> Option<Response> {
+        let mut response = EndQuorumEpochResponse {
+            error_code: error::NONE,
+            topics: KafkaArray(Some(vec![])),
+        };
+
+        if !broker.authorize(
+            context,
+            AclOperation::ClusterAction,
+            ResourceType::Cluster,
+            CLUSTER_RESOURCE_NAME,
+        ) {
+            response.error_code = error::CLUSTER_AUTHORIZATION_FAILED;
+        } else if self
+            .cluster_id
+            .as_deref()
+            .is_some_and(|id| id != broker.cluster_id)
+        {
+            response.error_code = error::INCONSISTENT_CLUSTER_ID;
+        } else {
+            let topics = self.topics.iter().flatten().map(|topic| {
+                let is_metadata = topic.topic_name.as_deref() == Some(METADATA_TOPIC);
+                let partitions = topic.partitions.iter().flatten().map(|partition| {
+                    if is_metadata && partition.partition_index == 0 {
+                        broker
+                            .raft
+                            .handle_end_quorum_epoch(
+                                partition.leader_id,
+                                partition.leader_epoch,
+                                partition
+                                    .preferred_successors
+                                    .as_deref()
+                                    .unwrap_or_default(),
+                            )
+                            .into_response(partition.partition_index)
+                    } else {
+                        QuorumEpochPartitionResponse::error(
+                            partition.partition_index,
+                            error::UNKNOWN_TOPIC_OR_PARTITION,
+                        )
+                    }
+                });
+                QuorumEpochTopicResponse {
+                    topic_name: topic.topic_name.clone(),
+                    partitions: KafkaArray(Some(partitions.collect())),
+                }
+            });
+            response.topics = KafkaArray(Some(topics.collect()));
+        }
+
+        Some(Box::new(response))
+    }
+}
+
+impl RequestHandler for EndQuorumEpochRequest {
+    fn read(bytes: &[u8], _version: i16) -> Result<Self> {
+        Ok(EndQuorumEpochRequest::deserialize(bytes)?.0)
+    }
+
+    fn handle(&self, request: &mut RequestContext) -> Option<Response> {
+        self.handle_request(request.broker, request.connection)
+    }
+}
+
+#[derive(Debug)]
+pub struct EndQuorumEpochResponse {
+    pub error_code: i16,
+    pub topics: KafkaArray<QuorumEpochTopicResponse>,
+}
+
+impl Serializable for EndQuorumEpochResponse {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(self.error_code.to_be_bytes());
+        buf.extend(self.topics.serialize());
+        buf
+    }
+
+    fn deserialize(bytes: &[u8]) -> Result<(Self, &[u8])> {
+        let (error_code, bytes) = i16::deserialize(bytes)?;
+        let (topics, bytes) = KafkaArray::<QuorumEpochTopicResponse>::deserialize(bytes)?;
+        Ok((EndQuorumEpochResponse { error_code, topics }, bytes))
+    }
+}
+
+impl ResponseBody for EndQuorumEpochResponse {}
@@ -0,0 +1,668 @@
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+
+use super::{
+    api_keys::{RequestContext, RequestHandler},
+    body::ResponseBody,
+    error,
+    primitive::{CompactArray, CompactBytes, CompactString, Serializable, TagField, TagSection},
+    response::Response,
+};
+use crate::{
+    broker::Broker,
+    log_manager::TopicPartition,
+    quota::QuotaType,
+    raft::METADATA_TOPIC,
+    security::acl::{AclOperation, ResourceType, CLUSTER_RESOURCE_NAME},
+    server::ConnectionContext,
+};
+
+#[derive(Debug)]
+pub struct FetchPartition {
+    pub partition: i32,
+    /// Leader epoch the fetcher knows of, -1 to skip the check
+    pub current_leader_epoch: i32,
+    pub fetch_offset: i64,
+    pub last_fetched_epoch: i32,
+    /// Log start offset of the follower, -1 for consumers
+    pub log_start_offset: i64,
+    pub partition_max_bytes: i32,
+    pub tag_buffer: TagSection,
+}
+
+impl Serializable for FetchPartition {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(self.partition.to_be_bytes());
+        buf.extend(self.current_leader_epoch.to_be_bytes());
+        buf.extend(self.fetch_offset.to_be_bytes());
+        buf.extend(self.last_fetched_epoch.to_be_bytes());
+        buf.extend(self.log_start_offset.to_be_bytes());
+        buf.extend(self.partition_max_bytes.to_be_bytes());
+        buf.extend(self.tag_buffer.serialize());
+        buf
+    }
+
+    fn deserialize(bytes: &[u8]) -> Result<(Self, &[u8])> {
+        let (partition, bytes) = i32::deserialize(bytes)?;
+        let (current_leader_epoch, bytes) = i32::deserialize(bytes)?;
+        let (fetch_offset, bytes) = i64::deserialize(bytes)?;
+        let (last_fetched_epoch, bytes) = i32::deserialize(bytes)?;
+        let (log_start_offset, bytes) = i64::deserialize(bytes)?;
+        let (partition_max_bytes, bytes) = i32::deserialize(bytes)?;
+        let (tag_buffer, bytes) = TagSection::deserialize(bytes)?;
+        Ok((
+            FetchPartition {
+                partition,
+                current_leader_epoch,
+                fetch_offset,
+                last_fetched_epoch,
+                log_start_offset,
+                partition_max_bytes,
+                tag_buffer,
+            },
+            bytes,
+        ))
+    }
+}
+
+#[derive(Debug)]
+pub struct FetchTopic {
+    pub topic: CompactString,
+    pub partitions: CompactArray<FetchPartition>,
+    pub tag_buffer: TagSection,
+}
+
+impl Serializable for FetchTopic {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(self.topic.serialize());
+        buf.extend(self.partitions.serialize());
+        buf.extend(self.tag_buffer.serialize());
+        buf
+    }
+
+    fn deserialize(bytes: &[u8]) -> Result<(Self, &[u8])> {
+        let (topic, bytes) = CompactString::deserialize(bytes)?;
+        let (partitions, bytes) = CompactArray::<FetchPartition>::deserialize(bytes)?;
+        let (tag_buffer, bytes) = TagSection::deserialize(bytes)?;
+        Ok((
+            FetchTopic {
+                topic,
+                partitions,
+                tag_buffer,
+            },
+            bytes,
+        ))
+    }
+}
+
+#[derive(Debug)]
+pub struct ForgottenTopic {
+    pub topic: CompactString,
+    pub partitions: CompactArray<i32>,
+    pub tag_buffer: TagSection,
+}
+
+impl Serializable for ForgottenTopic {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(self.topic.serialize());
+        buf.extend(self.partitions.serialize());
+        buf.extend(self.tag_buffer.serialize());
+        buf
+    }
+
+    fn deserialize(bytes: &[u8]) -> Result<(Self, &[u8])> {
+        let (topic, bytes) = CompactString::deserialize(bytes)?;
+        let (partitions, bytes) = CompactArray::<i32>::deserialize(bytes)?;
+        let (tag_buffer, bytes) = TagSection::deserialize(bytes)?;
+        Ok((
+            ForgottenTopic {
+                topic,
+                partitions,
+                tag_buffer,
+            },
+            bytes,
+        ))
+    }
+}
+
+/// Fetch version 12. Fetch sessions are not supported: every request is a full fetch
+/// and responses carry session id 0.
+#[derive(Debug)]
+pub struct FetchRequest {
+    /// Broker id of the follower sending the fetch, negative for consumers
+    pub replica_id: i32,
+    pub max_wait_ms: i32,
+    pub min_bytes: i32,
+    pub max_bytes: i32,
+    pub isolation_level: i8,
+    pub session_id: i32,
+    pub session_epoch: i32,
+    pub topics: CompactArray<FetchTopic>,
+    pub forgotten_topics_data: CompactArray<ForgottenTopic>,
+    pub rack_id: CompactString,
+    pub tag_buffer: TagSection,
+}
+
+impl Serializable for FetchRequest {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(self.replica_id.to_be_bytes());
+        buf.extend(self.max_wait_ms.to_be_bytes());
+        buf.extend(self.min_bytes.to_be_bytes());
+        buf.extend(self.max_bytes.to_be_bytes());
+        buf.extend(self.isolation_level.to_be_bytes());
+        buf.extend(self.session_id.to_be_bytes());
+        buf.extend(self.session_epoch.to_be_bytes());
+        buf.extend(self.topics.serialize());
+        buf.extend(self.forgotten_topics_data.serialize());
+        buf.extend(self.rack_id.serialize());
+        buf.extend(self.tag_buffer.serialize());
+        buf
+    }
+
+    fn deserialize(bytes: &[u8]) -> Result<(Self, &[u8])> {
+        let (replica_id, bytes) = i32::deserialize(bytes)?;
+        let (max_wait_ms, bytes) = i32::deserialize(bytes)?;
+        let (min_bytes, bytes) = i32::deserialize(bytes)?;
+        let (max_bytes, bytes) = i32::deserialize(bytes)?;
+        let (isolation_level, bytes) = i8::deserialize(bytes)?;
+        let (session_id, bytes) = i32::deserialize(bytes)?;
+        let (session_epoch, bytes) = i32::deserialize(bytes)?;
+        let (topics, bytes) = CompactArray::<FetchTopic>::deserialize(bytes)?;
+        let (forgotten_topics_data, bytes) = CompactArray::<ForgottenTopic>::deserialize(bytes)?;
+        let (rack_id, bytes) = CompactString::deserialize(bytes)?;
+        let (tag_buffer, bytes) = TagSection::deserialize(bytes)?;
+        Ok((
+            FetchRequest {
+                replica_id,
+                max_wait_ms,
+                min_bytes,
+                max_bytes,
+                isolation_level,
+                session_id,
+                session_epoch,
+                topics,
+                forgotten_topics_data,
+                rack_id,
+                tag_buffer,
+            },
+            bytes,
+        ))
+    }
+}
+
+impl FetchRequest {
+    const CLUSTER_ID_TAG: u32 = 0;
+
+    pub fn handle_request(&self, request: &mut RequestContext) -> Option<Response> {
+        let broker = request.broker;
+        let context = &*request.connection;
+        let mut response = FetchResponse {
+            throttle_time_ms: 0,
+            error_code: error::NONE,
+            session_id: 0,
+            responses: CompactArray(Some(vec![])),
+            tag_buffer: TagSection(None),
+        };
+
+        // Only brokers may fetch as followers, since that moves the high watermark
+        if self.is_from_follower()
+            && !broker.authorize(
+                context,
+                AclOperation::ClusterAction,
+                ResourceType::Cluster,
+                CLUSTER_RESOURCE_NAME,
+            )
+        {
+            response.error_code = error::CLUSTER_AUTHORIZATION_FAILED;
+        } else if self.is_metadata_fetch() {
+            response = broker.raft.handle_fetch(self);
+        } else {
+            // Wait for more records until the response holds `min_bytes`, hits an error,
+            // tells a follower to truncate or sends a consumer to another replica
+            let deadline = Instant::now() + Duration::from_millis(self.max_wait_ms.max(0) as u64);
+            loop {
+                let progress = broker.replicas.progress();
+                let responses = self.read(broker, context);
+                let partitions = || {
+                    responses
+                        .iter()
+                        .flat_map(|topic| topic.partitions.iter().flatten())
+                };
+                let bytes: usize = partitions()
+                    .map(|partition| partition.records.as_ref().map_or(0, Vec::len))
+                    .sum();
+                if bytes >= self.min_bytes.max(0) as usize
+                    || partitions().any(|partition| {
+                        partition.error_code != error::NONE
+                            || partition.diverging_epoch().is_some()
+                            || partition.preferred_read_replica >= 0
+                    })
+                    || !broker.replicas.wait_for_progress(progress, deadline)
+                {
+                    response.responses = CompactArray(Some(responses));
+                    // Followers are held to the replication quotas instead
+                    if !self.is_from_follower() {
+                        response.throttle_time_ms = broker.record_quota(
+                            context,
+                            request.client_id,
+                            QuotaType::Fetch,
+                            bytes as f64,
+                        );
+                        request.throttle_time_ms = response.throttle_time_ms;
+                    }
+                    break;
+                }
+            }
+        }
+
+        Some(Box::new(response))
+    }
+
+    fn is_from_follower(&self) -> bool {
+        self.replica_id >= 0
+    }
+
+    /// Cluster id sent by KRaft voters and observers, tagged field 0.
+    pub fn cluster_id(&self) -> Option<String> {
+        let data = self.tag_buffer.field(Self::CLUSTER_ID_TAG)?;
+        CompactString::deserialize(data).ok()?.0 .0
+    }
+
+    /// Tagged fields carrying `cluster_id`.
+    pub fn cluster_id_tags(cluster_id: &str) -> TagSection {
+        TagSection(Some(vec![TagField {
+            tag: Self::CLUSTER_ID_TAG,
+            data: CompactString(Some(cluster_id.to_string())).serialize(),
+        }]))
+    }
+
+    /// Whether a voter or an observer of the metadata quorum is fetching.
+    fn is_metadata_fetch(&self) -> bool {
+        self.is_from_follower()
+            && self
+                .topics
+                .iter()
+                .flatten()
+                .any(|topic| topic.topic.as_deref() == Some(METADATA_TOPIC))
+    }
+
+    fn read(&self, broker: &Broker, context: &ConnectionContext) -> Vec<FetchableTopicResponse> {
+        let mut remaining_bytes = self.max_bytes.max(0) as usize;
+        let mut responses = vec![];
+        for topic in self.topics.iter().flatten() {
+            let name = topic.topic.as_deref().unwrap_or_default();
+            let authorized = self.is_from_follower()
+                || broker.authorize(context, AclOperation::Read, ResourceType::Topic, name);
+            let mut partitions = vec![];
+            for fetch in topic.partitions.iter().flatten() {
+                let mut partition = PartitionData {
+                    partition_index: fetch.partition,
+                    error_code: error::NONE,
+                    high_watermark: -1,
+                    last_stable_offset: -1,
+                    log_start_offset: -1,
+                    aborted_transactions: CompactArray(None),
+                    preferred_read_replica: -1,
+                    records: CompactBytes(None),
+                    tag_buffer: TagSection(None),
+                };
+                let replica = TopicPartition::new(name, fetch.partition);
+                let max_bytes = remaining_bytes.min(fetch.partition_max_bytes.max(0) as usize);
+                let read = if authorized {
+                    broker.replicas.read_records(
+                        broker,
+                        &replica,
+                        self.replica_id,
+                        self.rack_id.as_deref().unwrap_or_default(),
+                        fetch,
+                        max_bytes,
+                    )
+                } else {
+                    Err(error::TOPIC_AUTHORIZATION_FAILED)
+                };
+                match read {
+                    Ok(read) => {
+                        remaining_bytes = remaining_bytes.saturating_sub(read.records.len());
+                        partition.high_watermark = read.high_watermark;
+                        // Without transactions every committed record is stable
+                        partition.last_stable_offset = read.high_watermark;
+                        partition.log_start_offset = read.log_start_offset;
+                        partition.records = CompactBytes(Some(read.records));
+                        if let Some(preferred_read_replica) = read.preferred_read_replica {
+                            partition.preferred_read_replica = preferred_read_replica;
+                        }
+                        if let Some(diverging_epoch) = read.diverging_epoch {
+                            partition.tag_buffer =
+                                PartitionData::raft_tags(Some(diverging_epoch), None, None);
+                        }
+                    }
+                    Err(error_code) => {
+                        partition.error_code = error_code;
+                        // Followers out of range start their log over where the leader's
+                        // starts
+                        if error_code == error::OFFSET_OUT_OF_RANGE {
+                            let mut logs = broker.logs.lock().unwrap();
+                            if let Ok(log_start_offset) = logs.log_start_offset(&replica) {
+                                partition.log_start_offset = log_start_offset;
+                                partition.high_watermark = logs.high_watermark(&replica);
+                            }
+                        }
+                    }
+                }
+                partitions.push(partition);
+            }
+            responses.push(FetchableTopicResponse {
+                topic: topic.topic.clone(),
+                partitions: CompactArray(Some(partitions)),
+                tag_buffer: TagSection(None),
+            });
+        }
+        responses
+    }
+}
+
+impl RequestHandler for FetchRequest {
+    fn read(bytes: &[u8], _version: i16) -> Result<Self> {
+        Ok(FetchRequest::deserialize(bytes)?.0)
+    }
+
+    fn handle(&self, request: &mut RequestContext) -> Option<Response> {
+        self.handle_request(request)
+    }
+}
+
+#[derive(Debug)]
+pub struct AbortedTransaction {
+    pub producer_id: i64,
+    pub first_offset: i64,
+    pub tag_buffer: TagSection,
+}
+
+impl Serializable for AbortedTransaction {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(self.producer_id.to_be_bytes());
+        buf.extend(self.first_offset.to_be_bytes());
+        buf.extend(self.tag_buffer.serialize());
+        buf
+    }
+
+    fn deserialize(bytes: &[u8]) -> Result<(Self, &[u8])> {
+        let (producer_id, bytes) = i64::deserialize(bytes)?;
+        let (first_offset, bytes) = i64::deserialize(bytes)?;
+        let (tag_buffer, bytes) = TagSection::deserialize(bytes)?;
+        Ok((
+            AbortedTransaction {
+                producer_id,
+                first_offset,
+                tag_buffer,
+            },
+            bytes,
+        ))
+    }
+}
+
+#[derive(Debug)]
+pub struct PartitionData {
+    pub partition_index: i32,
+    pub error_code: i16,
+    pub high_watermark: i64,
+    pub last_stable_offset: i64,
+    pub log_start_offset: i64,
+    pub aborted_transactions: CompactArray<AbortedTransaction>,
+    pub preferred_read_replica: i32,
+    pub records: CompactBytes,
+    pub tag_buffer: TagSection,
+}
+
+impl Serializable for PartitionData {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(self.partition_index.to_be_bytes());
+        buf.extend(self.error_code.to_be_bytes());
+        buf.extend(self.high_watermark.to_be_bytes());
+        buf.extend(self.last_stable_offset.to_be_bytes());
+        buf.extend(self.log_start_offset.to_be_bytes());
+        buf.extend(self.aborted_transactions.serialize());
+        buf.extend(self.preferred_read_replica.to_be_bytes());
+        buf.extend(self.records.serialize());
+        buf.extend(self.tag_buffer.serialize());
+        buf
+    }
+
+    fn deserialize(bytes: &[u8]) -> Result<(Self, &[u8])> {
+        let (partition_index, bytes) = i32::deserialize(bytes)?;
+        let (error_code, bytes) = i16::deserialize(bytes)?;
+        let (high_watermark, bytes) = i64::deserialize(bytes)?;
+        let (last_stable_offset, bytes) = i64::deserialize(bytes)?;
+        let (log_start_offset, bytes) = i64::deserialize(bytes)?;
+        let (aborted_transactions, bytes) = CompactArray::<AbortedTransaction>::deserialize(bytes)?;
+        let (preferred_read_replica, bytes) = i32::deserialize(bytes)?;
+        let (records, bytes) = CompactBytes::deserialize(bytes)?;
+        let (tag_buffer, bytes) = TagSection::deserialize(bytes)?;
+        Ok((
+            PartitionData {
+                partition_index,
+                error_code,
+                high_watermark,
+                last_stable_offset,
+                log_start_offset,
+                aborted_transactions,
+                preferred_read_replica,
+                records,
+                tag_buffer,
+            },
+            bytes,
+        ))
+    }
+}
+
+impl PartitionData {
+    const DIVERGING_EPOCH_TAG: u32 = 0;
+    const CURRENT_LEADER_TAG: u32 = 1;
+    const SNAPSHOT_ID_TAG: u32 = 2;
+
+    /// Tagged fields of responses to followers, in tag order. Only KRaft followers get
+    /// the current leader and the snapshot id.
+    pub fn raft_tags(
+        diverging_epoch: Option<EpochEndOffset>,
+        current_leader: Option<LeaderIdAndEpoch>,
+        snapshot_id: Option<SnapshotId>,
+    ) -> TagSection {
+        let fields = [
+            (
+                Self::DIVERGING_EPOCH_TAG,
+                diverging_epoch.map(|e| e.serialize()),
+            ),
+            (
+                Self::CURRENT_LEADER_TAG,
+                current_leader.map(|l| l.serialize()),
+            ),
+            (Self::SNAPSHOT_ID_TAG, snapshot_id.map(|s| s.serialize())),
+        ];
+        TagSection(Some(
+            fields
+                .into_iter()
+                .filter_map(|(tag, data)| Some(TagField { tag, data: data? }))
+                .collect(),
+        ))
+    }
+
+    /// Where the log of the follower diverges from the leader's, tagged field 0.
+    pub fn diverging_epoch(&self) -> Option<EpochEndOffset> {
+        let data = self.tag_buffer.field(Self::DIVERGING_EPOCH_TAG)?;
+        Some(EpochEndOffset::deserialize(data).ok()?.0)
+    }
+
+    /// Leader known to the broker that answered, tagged field 1.
+    pub fn current_leader(&self) -> Option<LeaderIdAndEpoch> {
+        let data = self.tag_buffer.field(Self::CURRENT_LEADER_TAG)?;
+        Some(LeaderIdAndEpoch::deserialize(data).ok()?.0)
+    }
+
+    /// Snapshot to fetch before the log, tagged field 2.
+    pub fn snapshot_id(&self) -> Option<SnapshotId> {
+        let data = self.tag_buffer.field(Self::SNAPSHOT_ID_TAG)?;
+        Some(SnapshotId::deserialize(data).ok()?.0)
+    }
+}
+
+/// Largest epoch of the leader's log not above the one the follower fetched last, with
+/// the offset it ends at.
+#[derive(Debug, Clone, Copy)]
+pub struct EpochEndOffset {
+    pub epoch: i32,
+    pub end_offset: i64,
+}
+
+impl Serializable for EpochEndOffset {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(self.epoch.to_be_bytes());
+        buf.extend(self.end_offset.to_be_bytes());
+        buf.extend(TagSection(None).serialize());
+        buf
+    }
+
+    fn deserialize(bytes: &[u8]) -> Result<(Self, &[u8])> {
+        let (epoch, bytes) = i32::deserialize(bytes)?;
+        let (end_offset, bytes) = i64::deserialize(bytes)?;
+        let (_, bytes) = TagSection::deserialize(bytes)?;
+        Ok((EpochEndOffset { epoch, end_offset }, bytes))
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct LeaderIdAndEpoch {
+    /// -1 when no leader is known
+    pub leader_id: i32,
+    pub leader_epoch: i32,
+}
+
+impl Serializable for LeaderIdAndEpoch {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(self.leader_id.to_be_bytes());
+        buf.extend(self.leader_epoch.to_be_bytes());
+        buf.extend(TagSection(None).serialize());
+        buf
+    }
+
+    fn deserialize(bytes: &[u8]) -> Result<(Self, &[u8])> {
+        let (leader_id, bytes) = i32::deserialize(bytes)?;
+        let (leader_epoch, bytes) = i32::deserialize(bytes)?;
+        let (_, bytes) = TagSection::deserialize(bytes)?;
+        Ok((
+            LeaderIdAndEpoch {
+                leader_id,
+                leader_epoch,
+            },
+            bytes,
+        ))
+    }
+}
+
+/// A snapshot of the metadata log, named after the offset and epoch it ends at.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct SnapshotId {
+    pub end_offset: i64,
+    pub epoch: i32,
+}
+
+impl Serializable for SnapshotId {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(self.end_offset.to_be_bytes());
+        buf.extend(self.epoch.to_be_bytes());
+        buf.extend(TagSection(None).serialize());
+        buf
+    }
+
+    fn deserialize(bytes: &[u8]) -> Result<(Self, &[u8])> {
+        let (end_offset, bytes) = i64::deserialize(bytes)?;
+        let (epoch, bytes) = i32::deserialize(bytes)?;
+        let (_, bytes) = TagSection::deserialize(bytes)?;
+        Ok((SnapshotId { end_offset, epoch }, bytes))
+    }
+}
+
+#[derive(Debug)]
+pub struct FetchableTopicResponse {
+    pub topic: CompactString,
+    pub partitions: CompactArray<PartitionData>,
+    pub tag_buffer: TagSection,
+}
+
+impl Serializable for FetchableTopicResponse {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(self.topic.serialize());
+        buf.extend(self.partitions.serialize());
+        buf.extend(self.tag_buffer.serialize());
+        buf
+    }
+
+    fn deserialize(bytes: &[u8]) -> Result<(Self, &[u8])> {
+        let (topic, bytes) = CompactString::deserialize(bytes)?;
+        let (partitions, bytes) = CompactArray::<PartitionData>::deserialize(bytes)?;
+        let (tag_buffer, bytes) = TagSection::deserialize(bytes)?;
+        Ok((
+            FetchableTopicResponse {
+                topic,
+                partitions,
+                tag_buffer,
+            },
+            bytes,
+        ))
+    }
+}
+
+#[derive(Debug)]
+pub struct FetchResponse {
+    pub throttle_time_ms: i32,
+    pub error_code: i16,
+    pub session_id: i32,
+    pub responses: CompactArray<FetchableTopicResponse>,
+    pub tag_buffer: TagSection,
+}
+
+impl Serializable for FetchResponse {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(self.throttle_time_ms.to_be_bytes());
+        buf.extend(self.error_code.to_be_bytes());
+        buf.extend(self.session_id.to_be_bytes());
+        buf.extend(self.responses.serialize());
+        buf.extend(self.tag_buffer.serialize());
+        buf
+    }
+
+    fn deserialize(bytes: &[u8]) -> Result<(Self, &[u8])> {
+        let (throttle_time_ms, bytes) = i32::deserialize(bytes)?;
+        let (error_code, bytes) = i16::deserialize(bytes)?;
+        let (session_id, bytes) = i32::deserialize(bytes)?;
+        let (responses, bytes) = CompactArray::<FetchableTopicResponse>::deserialize(bytes)?;
+        let (tag_buffer, bytes) = TagSection::deserialize(bytes)?;
+        Ok((
+            FetchResponse {
+                throttle_time_ms,
+                error_code,
+                session_id,
+                responses,
+                tag_buffer,
+            },
+            bytes,
+        ))
+    }
+}
+
+impl ResponseBody for FetchResponse {
+    fn set_throttle_time_ms(&mut self, throttle_time_ms: i32) {
+        self.throttle_time_ms = throttle_time_ms;
+    }
+}
@@ -0,0 +1,353 @@
+use anyhow::Result;
+
+use super::{
+    api_keys::{RequestContext, RequestHandler},
+    body::ResponseBody,
+    error,
+    fetch::{LeaderIdAndEpoch, SnapshotId},
+    primitive::{CompactArray, CompactBytes, CompactString, Serializable, TagField, TagSection},
+    response::Response,
+};
+use crate::{
+    broker::Broker,
+    raft::METADATA_TOPIC,
+    security::acl::{AclOperation, ResourceType, CLUSTER_RESOURCE_NAME},
+    server::ConnectionContext,
+};
+
+#[derive(Debug)]
+pub struct FetchSnapshotPartition {
+    pub partition: i32,
+    pub current_leader_epoch: i32,
+    pub snapshot_id: SnapshotId,
+    /// Byte position in the snapshot to read from
+    pub position: i64,
+    pub tag_buffer: TagSection,
+}
+
+impl Serializable for FetchSnapshotPartition {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(self.partition.to_be_bytes());
+        buf.extend(self.current_leader_epoch.to_be_bytes());
+        buf.extend(self.snapshot_id.serialize());
+        buf.extend(self.position.to_be_bytes());
+        buf.extend(self.tag_buffer.serialize());
+        buf
+    }
+
+    fn deserialize(bytes: &[u8]) -> Result<(Self, &[u8])> {
+        let (partition, bytes) = i32::deserialize(bytes)?;
+        let (current_leader_epoch, bytes) = i32::deserialize(bytes)?;
+        let (snapshot_id, bytes) = SnapshotId::deserialize(bytes)?;
+        let (position, bytes) = i64::deserialize(bytes)?;
+        let (tag_buffer, bytes) = TagSection::deserialize(bytes)?;
+        Ok((
+            FetchSnapshotPartition {
+                partition,
+                current_leader_epoch,
+                snapshot_id,
+                position,
+                tag_buffer,
+            },
+            bytes,
+        ))
+    }
+}
+
+#[derive(Debug)]
+pub struct FetchSnapshotTopic {
+    pub name: CompactString,
+    pub partitions: CompactArray<FetchSnapshotPartition>,
+    pub tag_buffer: TagSection,
+}
+
+impl Serializable for FetchSnapshotTopic {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(self.name.serialize());
+        buf.extend(self.partitions.serialize());
+        buf.extend(self.tag_buffer.serialize());
+        buf
+    }
+
+    fn deserialize(bytes: &[u8]) -> Result<(Self, &[u8])> {
+        let (name, bytes) = CompactString::deserialize(bytes)?;
+        let (partitions, bytes) = CompactArray::<FetchSnapshotPartition>::deserialize(bytes)?;
+        let (tag_buffer, bytes) = TagSection::deserialize(bytes)?;
+        Ok((
+            FetchSnapshotTopic {
+                name,
+                partitions,
+                tag_buffer,
+            },
+            bytes,
+        ))
+    }
+}
+
+/// FetchSnapshot version 0, sent by a replica of the metadata log that fell behind the
+/// log start of the leader.
+#[derive(Debug)]
+pub struct FetchSnapshotRequest {
+    pub replica_id: i32,
+    pub max_bytes: i32,
+    pub topics: CompactArray<FetchSnapshotTopic>,
+    /// Carries the cluster id as tagged field 0
+    pub tag_buffer: TagSection,
+}
+
+impl Serializable for FetchSnapshotRequest {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(self.replica_id.to_be_bytes());
+        buf.extend(self.max_bytes.to_be_bytes());
+        buf.extend(self.topics.serialize());
+        buf.extend(self.tag_buffer.serialize());
+        buf
+    }
+
+    fn deserialize(bytes: &[u8]) -> Result<(Self, &[u8])> {
+        let (replica_id, bytes) = i32::deserialize(bytes)?;
+        let (max_bytes, bytes) = i32::deserialize(bytes)?;
+        let (topics, bytes) = CompactArray::<FetchSnapshotTopic>::deserialize(bytes)?;
+        let (tag_buffer, bytes) = TagSection::deserialize(bytes)?;
+        Ok((
+            FetchSnapshotRequest {
+                replica_id,
+                max_bytes,
+                topics,
+                tag_buffer,
+            },
+            bytes,
+        ))
+    }
+}
+
+impl FetchSnapshotRequest {
+    const CLUSTER_ID_TAG: u32 = 0;
+
+    pub fn cluster_id(&self) -> Option<String> {
+        let data = self.tag_buffer.field(Self::CLUSTER_ID_TAG)?;
+        CompactString::deserialize(data).ok()?.0 .0
+    }
+
+    /// Tagged fields carrying `cluster_id`.
+    pub fn cluster_id_tags(cluster_id: &str) -> TagSection {
+        TagSection(Some(vec![TagField {
+            tag: Self::CLUSTER_ID_TAG,
+            data: CompactString(Some(cluster_id.to_string())).serialize(),
+        }]))
+    }
+
+    pub fn handle_request(&self, broker: &Broker, context: &ConnectionContext) -> Option<Response> {
+        let mut response = FetchSnapshotResponse {
+            throttle_time_ms: 0,
+            error_code: error::NONE,
+            topics: CompactArray(Some(vec![])),
+            tag_buffer: TagSection(None),
+        };
+
+        if !broker.authorize(
+            context,
+            AclOperation::ClusterAction,
+            ResourceType::Cluster,
+            CLUSTER_RESOURCE_NAME,
+        ) {
+            response.error_code = error::CLUSTER_AUTHORIZATION_FAILED;
+        } else if self
+            .cluster_id()
+            .is_some_and(|cluster_id| cluster_id != broker.cluster_id)
+        {
+            response.error_code = error::INCONSISTENT_CLUSTER_ID;
+        } else {
+            let max_bytes = self.max_bytes.max(0) as usize;
+            let topics = self.topics.iter().flatten().map(|topic| {
+                let is_metadata = topic.name.as_deref() == Some(METADATA_TOPIC);
+                let partitions = topic.partitions.iter().flatten().map(|partition| {
+                    if is_metadata && partition.partition == 0 {
+                        broker.raft.handle_fetch_snapshot(partition, max_bytes)
+                    } else {
+                        SnapshotPartitionResponse::error(
+                            partition.partition,
+                            error::UNKNOWN_TOPIC_OR_PARTITION,
+                            None,
+                        )
+                    }
+                });
+                SnapshotTopicResponse {
+                    name: topic.name.clone(),
+                    partitions: CompactArray(Some(partitions.collect())),
+                    tag_buffer: TagSection(None),
+                }
+            });
+            response.topics = CompactArray(Some(topics.collect()));
+        }
+
+        Some(Box::new(response))
+    }
+}
+
+impl RequestHandler for FetchSnapshotRequest {
+    fn read(bytes: &[u8], _version: i16) -> Result<Self> {
+        Ok(FetchSnapshotRequest::deserialize(bytes)?.0)
+    }
+
+    fn handle(&self, request: &mut RequestContext) -> Option<Response> {
+        self.handle_request(request.broker, request.connection)
+    }
+}
+
+#[derive(Debug)]
+pub struct SnapshotPartitionResponse {
+    pub index: i32,
+    pub error_code: i16,
+    pub snapshot_id: SnapshotId,
+    /// Total size of the snapshot in bytes
+    pub size: i64,
+    pub position: i64,
+    pub unaligned_records: CompactBytes,
+    /// Carries the leader known to the broker as tagged field 0
+    pub tag_buffer: TagSection,
+}
+
+impl SnapshotPartitionResponse {
+    const CURRENT_LEADER_TAG: u32 = 0;
+
+    pub fn error(index: i32, error_code: i16, current_leader: Option<LeaderIdAndEpoch>) -> Self {
+        SnapshotPartitionResponse {
+            index,
+            error_code,
+            snapshot_id: SnapshotId {
+                end_offset: -1,
+                epoch: -1,
+            },
+            size: -1,
+            position: -1,
+            unaligned_records: CompactBytes(Some(vec![])),
+            tag_buffer: Self::current_leader_tags(current_leader),
+        }
+    }
+
+    pub fn current_leader_tags(current_leader: Option<LeaderIdAndEpoch>) -> TagSection {
+        TagSection(current_leader.map(|leader| {
+            vec![TagField {
+                tag: Self::CURRENT_LEADER_TAG,
+                data: leader.serialize(),
+            }]
+        }))
+    }
+
+    pub fn current_leader(&self) -> Option<LeaderIdAndEpoch> {
+        let data = self.tag_buffer.field(Self::CURRENT_LEADER_TAG)?;
+        Some(LeaderIdAndEpoch::deserialize(data).ok()?.0)
+    }
+}
+
+impl Serializable for SnapshotPartitionResponse {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(self.index.to_be_bytes());
+        buf.extend(self.error_code.to_be_bytes());
+        buf.extend(self.snapshot_id.serialize());
+        buf.extend(self.size.to_be_bytes());
+        buf.extend(self.position.to_be_bytes());
+        buf.extend(self.unaligned_records.serialize());
+        buf.extend(self.tag_buffer.serialize());
+        buf
+    }
+
+    fn deserialize(bytes: &[u8]) -> Result<(Self, &[u8])> {
+        let (index, bytes) = i32::deserialize(bytes)?;
+        let (error_code, bytes) = i16::deserialize(bytes)?;
+        let (snapshot_id, bytes) = SnapshotId::deserialize(bytes)?;
+        let (size, bytes) = i64::deserialize(bytes)?;
+        let (position, bytes) = i64::deserialize(bytes)?;
+        let (unaligned_records, bytes) = CompactBytes::deserialize(bytes)?;
+        let (tag_buffer, bytes) = TagSection::deserialize(bytes)?;
+        Ok((
+            SnapshotPartitionResponse {
+                index,
+                error_code,
+                snapshot_id,
+                size,
+                position,
+                unaligned_records,
+                tag_buffer,
+            },
+            bytes,
+        ))
+    }
+}
+
+#[derive(Debug)]
+pub struct SnapshotTopicResponse {
+    pub name: CompactString,
+    pub partitions: CompactArray<SnapshotPartitionResponse>,
+    pub tag_buffer: TagSection,
+}
+
+impl Serializable for SnapshotTopicResponse {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(self.name.serialize());
+        buf.extend(self.partitions.serialize());
+        buf.extend(self.tag_buffer.serialize());
+        buf
+    }
+
+    fn deserialize(bytes: &[u8]) -> Result<(Self, &[u8])> {
+        let (name, bytes) = CompactString::deserialize(bytes)?;
+        let (partitions, bytes) = CompactArray::<SnapshotPartitionResponse>::deserialize(bytes)?;
+        let (tag_buffer, bytes) = TagSection::deserialize(bytes)?;
+        Ok((
+            SnapshotTopicResponse {
+                name,
+                partitions,
+                tag_buffer,
+            },
+            bytes,
+        ))
+    }
+}
+
+#[derive(Debug)]
+pub struct FetchSnapshotResponse {
+    pub throttle_time_ms: i32,
+    pub error_code: i16,
+    pub topics: CompactArray<SnapshotTopicResponse>,
+    pub tag_buffer: TagSection,
+}
+
+impl Serializable for FetchSnapshotResponse {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(self.throttle_time_ms.to_be_bytes());
+        buf.extend(self.error_code.to_be_bytes());
+        buf.extend(self.topics.serialize());
+        buf.extend(self.tag_buffer.serialize());
+        buf
+    }
+
+    fn deserialize(bytes: &[u8]) -> Result<(Self, &[u8])> {
+        let (throttle_time_ms, bytes) = i32::deserialize(bytes)?;
+        let (error_code, bytes) = i16::deserialize(bytes)?;
+        let (topics, bytes) = CompactArray::<SnapshotTopicResponse>::deserialize(bytes)?;
+        let (tag_buffer, bytes) = TagSection::deserialize(bytes)?;
+        Ok((
+            FetchSnapshotResponse {
+                throttle_time_ms,
+                error_code,
+                topics,
+                tag_buffer,
+            },
+            bytes,
+        ))
+    }
+}
+
+impl ResponseBody for FetchSnapshotResponse {
+    fn set_throttle_time_ms(&mut self, throttle_time_ms: i32) {
+        self.throttle_time_ms = throttle_time_ms;
+    }
+}
@@ -0,0 +1,293 @@
+use anyhow::Result;
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use uuid::Uuid;
+
+use super::{
+    api_keys::{self, RequestContext, RequestHandler, FIND_COORDINATOR_KEY},
+    body::ResponseBody,
+    error,
+    primitive::{CompactArray, CompactString, KafkaString, Serializable, TagSection},
+    response::Response,
+};
+use crate::{
+    broker::Broker,
+    security::acl::{AclOperation, ResourceType, CLUSTER_RESOURCE_NAME},
+    server::ConnectionContext,
+    share_group,
+};
+
+/// Coordinators a FindCoordinator request looks for.
+const GROUP_KEY_TYPE: i8 = 0;
+const TRANSACTION_KEY_TYPE: i8 = 1;
+/// Version 6 and above, keyed by `groupId:topicId:partition`
+const SHARE_KEY_TYPE: i8 = 2;
+
+#[derive(Debug)]
+pub struct FindCoordinatorRequest {
+    /// Version 1 and above
+    pub key_type: i8,
+    /// A single key below version 4
+    pub coordinator_keys: Vec<String>,
+    pub tag_buffer: TagSection,
+}
+
+impl FindCoordinatorRequest {
+    /// Version 3 and above use the compact (flexible) encoding, and version 4 and above
+    /// look for several coordinators at once.
+    pub fn deserialize_versioned(bytes: &[u8], version: i16) -> Result<(Self, &[u8])> {
+        if version >= 4 {
+            let (key_type, bytes) = i8::deserialize(bytes)?;
+            let (keys, bytes) = CompactArray::<CompactString>::deserialize(bytes)?;
+            let (tag_buffer, bytes) = TagSection::deserialize(bytes)?;
+            let coordinator_keys = keys
+                .0
+                .unwrap_or_default()
+                .into_iter()
+                .map(|key| key.0.unwrap_or_default())
+                .collect();
+            return Ok((
+                FindCoordinatorRequest {
+                    key_type,
+                    coordinator_keys,
+                    tag_buffer,
+                },
+                bytes,
+            ));
+        }
+        let flexible = api_keys::is_flexible(FIND_COORDINATOR_KEY, version);
+        let (key, bytes) = if flexible {
+            let (key, bytes) = CompactString::deserialize(bytes)?;
+            (key.0, bytes)
+        } else {
+            let (key, bytes) = KafkaString::deserialize(bytes)?;
+            (key.0, bytes)
+        };
+        let (key_type, bytes) = if version >= 1 {
+            i8::deserialize(bytes)?
+        } else {
+            (GROUP_KEY_TYPE, bytes)
+        };
+        let (tag_buffer, bytes) = if flexible {
+            TagSection::deserialize(bytes)?
+        } else {
+            (TagSection(None), bytes)
+        };
+        Ok((
+            FindCoordinatorRequest {
+                key_type,
+                coordinator_keys: vec![key.unwrap_or_default()],
+                tag_buffer,
+            },
+            bytes,
+        ))
+    }
+
+    pub fn handle_request(
+        &self,
+        version: i16,
+        broker: &Broker,
+        context: &ConnectionContext,
+    ) -> Option<Response> {
+        let coordinators = self
+            .coordinator_keys
+            .iter()
+            .map(|key| self.find(version, key, broker, context))
+            .collect();
+        Some(Box::new(FindCoordinatorResponse {
+            version,
+            throttle_time_ms: 0,
+            coordinators,
+        }))
+    }
+
+    fn find(
+        &self,
+        version: i16,
+        key: &str,
+        broker: &Broker,
+        context: &ConnectionContext,
+    ) -> Coordinator {
+        let mut coordinator = Coordinator {
+            key: key.to_string(),
+            node_id: -1,
+            host: String::new(),
+            port: -1,
+            error_code: error::NONE,
+            error_message: None,
+        };
+        let mut fail = |error_code, message: &str| {
+            coordinator.error_code = error_code;
+            coordinator.error_message = Some(message.to_string());
+            coordinator.clone()
+        };
+        // Authorization reads the metadata image too, so it is taken afterwards
+        let node_id = match self.key_type {
+            GROUP_KEY_TYPE => {
+                if !broker.authorize(context, AclOperation::Describe, ResourceType::Group, key) {
+                    return fail(error::GROUP_AUTHORIZATION_FAILED, "");
+                }
+                share_group::coordinator_for(&broker.metadata.read().unwrap(), key)
+            }
+            TRANSACTION_KEY_TYPE => {
+                return fail(
+                    error::COORDINATOR_NOT_AVAILABLE,
+                    "The broker has no transaction coordinator.",
+                )
+            }
+            // Only brokers look for the leader keeping the state of a share partition
+            SHARE_KEY_TYPE if version >= 6 => {
+                if !broker.authorize(
+                    context,
+                    AclOperation::ClusterAction,
+                    ResourceType::Cluster,
+                    CLUSTER_RESOURCE_NAME,
+                ) {
+                    return fail(error::CLUSTER_AUTHORIZATION_FAILED, "");
+                }
+                let Some((topic_id, partition)) = parse_share_key(key) else {
+                    return fail(
+                        error::INVALID_REQUEST,
+                        "Share coordinator keys are of the form groupId:topicId:partition.",
+                    );
+                };
+                let image = broker.metadata.read().unwrap();
+                image
+                    .topic_name(topic_id)
+                    .and_then(|name| image.partition(name, partition))
+                    .map(|(_, registration)| registration.leader)
+                    .filter(|&leader| leader >= 0)
+            }
+            key_type => {
+                return fail(
+                    error::INVALID_REQUEST,
+                    &format!("Unknown coordinator key type {key_type}."),
+                )
+            }
+        };
+        let image = broker.metadata.read().unwrap();
+        let endpoint = node_id.and_then(|node_id| {
+            let registration = image.brokers.get(&node_id)?;
+            registration
+                .endpoints
+                .iter()
+                .find(|endpoint| endpoint.name == context.listener_name)
+                .map(|endpoint| (node_id, endpoint))
+        });
+        let Some((node_id, endpoint)) = endpoint else {
+            return fail(
+                error::COORDINATOR_NOT_AVAILABLE,
+                "The coordinator is not available.",
+            );
+        };
+        coordinator.node_id = node_id;
+        coordinator.host = endpoint.host.clone();
+        coordinator.port = endpoint.port as i32;
+        coordinator
+    }
+}
+
+impl RequestHandler for FindCoordinatorRequest {
+    fn read(bytes: &[u8], version: i16) -> Result<Self> {
+        Ok(FindCoordinatorRequest::deserialize_versioned(bytes, version)?.0)
+    }
+
+    fn handle(&self, request: &mut RequestContext) -> Option<Response> {
+        self.handle_request(request.api_version, request.broker, request.connection)
+    }
+}
+
+/// Topic id and partition of a `groupId:topicId:partition` key, the group id possibly
+/// holding colons itself.
+fn parse_share_key(key: &str) -> Option<(Uuid, i32)> {
+    let (rest, partition) = key.rsplit_once(':')?;
+    let (_, topic_id) = rest.rsplit_once(':')?;
+    let topic_id = Uuid::from_slice(&URL_SAFE_NO_PAD.decode(topic_id).ok()?).ok()?;
+    Some((topic_id, partition.parse().ok()?))
+}
+
+#[derive(Debug, Clone)]
+pub struct Coordinator {
+    pub key: String,
+    pub node_id: i32,
+    pub host: String,
+    pub port: i32,
+    pub error_code: i16,
+    pub error_message: Option<String>,
+}
+
+/// Entry of the coordinator array of version 4 and above.
+impl Serializable for Coordinator {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(CompactString(Some(self.key.clone())).serialize());
+        buf.extend(self.node_id.to_be_bytes());
+        buf.extend(CompactString(Some(self.host.clone())).serialize());
+        buf.extend(self.port.to_be_bytes());
+        buf.extend(self.error_code.to_be_bytes());
+        buf.extend(CompactString(self.error_message.clone()).serialize());
+        buf.extend(TagSection(None).serialize());
+        buf
+    }
+
+    fn deserialize(_bytes: &[u8]) -> Result<(Self, &[u8])> {
+        todo!()
+    }
+}
+
+#[derive(Debug)]
+pub struct FindCoordinatorResponse {
+    pub version: i16,
+    pub throttle_time_ms: i32,
+    /// A single coordinator below version 4
+    pub coordinators: Vec<Coordinator>,
+}
+
+impl Serializable for FindCoordinatorResponse {
+    fn serialize(&self) -> Vec<u8> {
+        let flexible = api_keys::is_flexible(FIND_COORDINATOR_KEY, self.version);
+        let mut buf = Vec::new();
+        if self.version >= 1 {
+            buf.extend(self.throttle_time_ms.to_be_bytes());
+        }
+        if self.version >= 4 {
+            buf.extend(CompactArray(Some(self.coordinators.clone())).serialize());
+            buf.extend(TagSection(None).serialize());
+            return buf;
+        }
+        let coordinator = self.coordinators.first().cloned().unwrap_or(Coordinator {
+            key: String::new(),
+            node_id: -1,
+            host: String::new(),
+            port: -1,
+            error_code: error::INVALID_REQUEST,
+            error_message: None,
+        });
+        buf.extend(coordinator.error_code.to_be_bytes());
+        if flexible {
+            buf.extend(CompactString(coordinator.error_message).serialize());
+        } else if self.version >= 1 {
+            buf.extend(KafkaString(coordinator.error_message).serialize());
+        }
+        buf.extend(coordinator.node_id.to_be_bytes());
+        if flexible {
+            buf.extend(CompactString(Some(coordinator.host)).serialize());
+        } else {
+            buf.extend(KafkaString(Some(coordinator.host)).serialize());
+        }
+        buf.extend(coordinator.port.to_be_bytes());
+        if flexible {
+            buf.extend(TagSection(None).serialize());
+        }
+        buf
+    }
+
+    fn deserialize(_bytes: &[u8]) -> Result<(Self, &[u8])> {
+        todo!()
+    }
+}
+
+impl ResponseBody for FindCoordinatorResponse {
+    fn set_throttle_time_ms(&mut self, throttle_time_ms: i32) {
+        self.throttle_time_ms = throttle_time_ms;
+    }
+}
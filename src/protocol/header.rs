@@ -1,7 +1,31 @@
 #![allow(dead_code)]
-use anyhow::Result;
+use thiserror::Error;
 
-use super::primitive::{Serializable, TagSection};
+use super::{
+    api_keys,
+    primitive::{KafkaString, Serializable, TagSection},
+};
+
+/// The only API whose oldest version still uses request header v0.
+const CONTROLLED_SHUTDOWN_KEY: i16 = 7;
+
+/// Why the header of a request frame could not be read.
+#[derive(Debug, Error)]
+pub enum HeaderError {
+    /// Too short to tell which request it is, so nothing can be answered
+    #[error("request frame of {0} bytes is too short for a request header")]
+    Truncated(usize),
+    #[error(
+        "request {correlation_id} (API key {api_key} version {api_version}) has a malformed \
+         or truncated {field}"
+    )]
+    Invalid {
+        api_key: i16,
+        api_version: i16,
+        correlation_id: i32,
+        field: &'static str,
+    },
+}
 
 #[derive(Debug)]
 pub struct RequestHeader {
@@ -19,6 +43,17 @@ pub enum ResponseHeader {
 }
 
 impl ResponseHeader {
+    pub fn new(correlation_id: i32, header_version: i16) -> Self {
+        if header_version >= 1 {
+            ResponseHeader::V1(ResponseHeaderV1 {
+                correlation_id,
+                tag_buffer: TagSection(None),
+            })
+        } else {
+            ResponseHeader::V0(ResponseHeaderV0 { correlation_id })
+        }
+    }
+
     pub fn serialize(&self) -> Vec<u8> {
         match self {
             ResponseHeader::V0(header) => header.serialize(),
@@ -56,44 +91,73 @@ impl ResponseHeaderV1 {
 }
 
 impl RequestHeader {
-    pub fn deserialize(msg_buf: &[u8]) -> Result<(Self, &[u8])> {
-        // Initialize an offset, this will be incremented after the reading of each field
-        let mut offset = 0;
-
-        // Read the API key
-        let request_api_key = i16::from_be_bytes(msg_buf[offset..offset + 2].try_into().unwrap());
-        offset += 2;
-
-        // Read the API version
-        let request_api_version =
-            i16::from_be_bytes(msg_buf[offset..offset + 2].try_into().unwrap());
-        offset += 2;
-
-        // Read the correlation id
-        let correlation_id = i32::from_be_bytes(msg_buf[offset..offset + 4].try_into().unwrap());
-        offset += 4;
-
-        // Initialize the client id to a null string
-        let mut client_id: Option<String> = None;
-        // Then read the first two bytes which indicate the length of the string
-        let len_client_id =
-            i16::from_be_bytes(msg_buf[offset..offset + 2].try_into().unwrap()) as usize;
-        offset += 2;
-        match len_client_id {
-            // If the length N is positive then read the next N bytes as client id
-            n if n > 0 => {
-                client_id = Some(
-                    String::from_utf8_lossy(&msg_buf[offset..offset + len_client_id]).to_string(),
-                );
-                offset += len_client_id;
-            }
-            // Else do nothing (in the documentation, null string is indicated by a -1 in the length
-            // field)
-            _ => {}
+    /// Whether requests of this API version use the flexible encoding.
+    pub fn is_flexible(api_key: i16, api_version: i16) -> bool {
+        api_keys::is_flexible(api_key, api_version)
+    }
+
+    /// Request header in the header version of the API version, as sent to another
+    /// broker.
+    pub fn serialize(&self) -> Vec<u8> {
+        let header_version = Self::header_version(self.request_api_key, self.request_api_version);
+        let mut buf = vec![];
+        buf.extend(self.request_api_key.to_be_bytes());
+        buf.extend(self.request_api_version.to_be_bytes());
+        buf.extend(self.correlation_id.to_be_bytes());
+        if header_version >= 1 {
+            buf.extend(KafkaString(self.client_id.clone()).serialize());
+        }
+        if header_version >= 2 {
+            buf.extend(self.tag_buffer.serialize());
+        }
+        buf
+    }
+
+    /// Request header version of an API version: v0, without a client id, for
+    /// ControlledShutdown version 0 only, v2 with tagged fields for flexible versions
+    /// and v1 otherwise.
+    pub fn header_version(api_key: i16, api_version: i16) -> i16 {
+        if api_key == CONTROLLED_SHUTDOWN_KEY && api_version == 0 {
+            0
+        } else if Self::is_flexible(api_key, api_version) {
+            2
+        } else {
+            1
         }
+    }
 
-        // For now, don't care about parsing the tag buffer
-        let (tag_buffer, body) = TagSection::deserialize(&msg_buf[offset..])?;
+    /// Read the header of a request frame, in the header version of its API version.
+    /// Returns the header along with the request body following it.
+    pub fn deserialize(msg_buf: &[u8]) -> Result<(Self, &[u8]), HeaderError> {
+        let truncated = |_| HeaderError::Truncated(msg_buf.len());
+        let (request_api_key, bytes) = i16::deserialize(msg_buf).map_err(truncated)?;
+        let (request_api_version, bytes) = i16::deserialize(bytes).map_err(truncated)?;
+        let (correlation_id, bytes) = i32::deserialize(bytes).map_err(truncated)?;
+        let header_version = Self::header_version(request_api_key, request_api_version);
+        let invalid = |field| HeaderError::Invalid {
+            api_key: request_api_key,
+            api_version: request_api_version,
+            correlation_id,
+            field,
+        };
+
+        let (client_id, bytes) = if header_version >= 1 {
+            // Only -1 stands for a null client id
+            let (length, _) = i16::deserialize(bytes).map_err(|_| invalid("client_id"))?;
+            if length < -1 {
+                return Err(invalid("client_id"));
+            }
+            let (client_id, bytes) =
+                KafkaString::deserialize(bytes).map_err(|_| invalid("client_id"))?;
+            (client_id.0, bytes)
+        } else {
+            (None, bytes)
+        };
+        let (tag_buffer, body) = if header_version >= 2 {
+            TagSection::deserialize(bytes).map_err(|_| invalid("tagged fields"))?
+        } else {
+            (TagSection(None), bytes)
+        };
 
         Ok((
             RequestHeader {
@@ -107,3 +171,106 @@ impl RequestHeader {
         ))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::api_keys::METADATA_KEY;
+
+    fn frame(api_key: i16, api_version: i16, rest: &[u8]) -> Vec<u8> {
+        let mut frame = vec![];
+        frame.extend(api_key.to_be_bytes());
+        frame.extend(api_version.to_be_bytes());
+        frame.extend(42i32.to_be_bytes());
+        frame.extend(rest);
+        frame
+    }
+
+    #[test]
+    fn reads_header_v0_without_a_client_id() {
+        let bytes = frame(CONTROLLED_SHUTDOWN_KEY, 0, b"body");
+        let (header, body) = RequestHeader::deserialize(&bytes).unwrap();
+        assert_eq!(RequestHeader::header_version(CONTROLLED_SHUTDOWN_KEY, 0), 0);
+        assert_eq!(header.request_api_key, CONTROLLED_SHUTDOWN_KEY);
+        assert_eq!(header.request_api_version, 0);
+        assert_eq!(header.correlation_id, 42);
+        assert_eq!(header.client_id, None);
+        assert_eq!(body, b"body");
+    }
+
+    #[test]
+    fn reads_header_v1_with_a_client_id_and_no_tagged_fields() {
+        let bytes = frame(METADATA_KEY, 4, b"\x00\x06client\x00body");
+        let (header, body) = RequestHeader::deserialize(&bytes).unwrap();
+        assert_eq!(RequestHeader::header_version(METADATA_KEY, 4), 1);
+        assert_eq!(header.client_id.as_deref(), Some("client"));
+        // What follows the client id is left to the body, tag-like as it may look
+        assert_eq!(body, b"\x00body");
+
+        let bytes = frame(METADATA_KEY, 4, b"\xff\xffbody");
+        let (header, body) = RequestHeader::deserialize(&bytes).unwrap();
+        assert_eq!(header.client_id, None);
+        assert_eq!(body, b"body");
+    }
+
+    #[test]
+    fn reads_header_v2_with_tagged_fields() {
+        let bytes = frame(METADATA_KEY, 12, b"\x00\x06client\x01\x00\x02hi\x00body");
+        let (header, body) = RequestHeader::deserialize(&bytes).unwrap();
+        assert_eq!(RequestHeader::header_version(METADATA_KEY, 12), 2);
+        assert_eq!(header.client_id.as_deref(), Some("client"));
+        assert_eq!(header.tag_buffer.field(0), Some(&b"hi"[..]));
+        assert_eq!(body, b"\x00body");
+    }
+
+    #[test]
+    fn writes_headers_it_reads_back() {
+        for version in [4, 12] {
+            let header = RequestHeader {
+                request_api_key: METADATA_KEY,
+                request_api_version: version,
+                correlation_id: 7,
+                client_id: Some("broker-1".to_string()),
+                tag_buffer: TagSection(None),
+            };
+            let mut bytes = header.serialize();
+            bytes.extend(b"body");
+            let (read, body) = RequestHeader::deserialize(&bytes).unwrap();
+            assert_eq!(read.correlation_id, 7);
+            assert_eq!(read.client_id.as_deref(), Some("broker-1"));
+            assert_eq!(body, b"body");
+        }
+    }
+
+    #[test]
+    fn rejects_truncated_and_malformed_headers() {
+        assert!(matches!(
+            RequestHeader::deserialize(&[0, 3, 0, 4, 0, 0]),
+            Err(HeaderError::Truncated(6))
+        ));
+        let cases = [
+            (frame(METADATA_KEY, 4, b"\x00"), "client_id"),
+            (frame(METADATA_KEY, 4, b"\x00\x06clie"), "client_id"),
+            (frame(METADATA_KEY, 4, b"\xff\xfe"), "client_id"),
+            (
+                frame(METADATA_KEY, 12, b"\x00\x00\x01\x00\x05hi"),
+                "tagged fields",
+            ),
+        ];
+        for (bytes, expected) in cases {
+            let Err(HeaderError::Invalid {
+                api_key,
+                correlation_id,
+                field,
+                ..
+            }) = RequestHeader::deserialize(&bytes)
+            else {
+                panic!("expected {expected} to be invalid");
+            };
+            assert_eq!(
+                (api_key, correlation_id, field),
+                (METADATA_KEY, 42, expected)
+            );
+        }
+    }
+}
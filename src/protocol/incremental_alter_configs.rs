@@ -0,0 +1,299 @@
+use anyhow::Result;
+
+use super::{
+    alter_configs::{
+        commit_config_records, config_records, AlterConfigsResourceResponse, ConfigChanges,
+    },
+    api_keys::{RequestContext, RequestHandler},
+    body::ResponseBody,
+    describe_configs::check_resource,
+    error,
+    primitive::{CompactArray, CompactString, Serializable, TagSection},
+    response::Response,
+};
+use crate::{
+    broker::Broker,
+    config_registry::{self, ConfigResolver, ConfigResource, ConfigResourceType, ConfigType},
+    security::acl::AclOperation,
+    server::ConnectionContext,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OpType {
+    Set,
+    Delete,
+    /// List configs only: add the items missing from the current value
+    Append,
+    /// List configs only: remove the items from the current value
+    Subtract,
+}
+
+impl OpType {
+    pub fn from_code(code: i8) -> Option<Self> {
+        match code {
+            0 => Some(OpType::Set),
+            1 => Some(OpType::Delete),
+            2 => Some(OpType::Append),
+            3 => Some(OpType::Subtract),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct AlterableConfig {
+    pub name: CompactString,
+    pub config_operation: i8,
+    pub value: CompactString,
+    pub tag_buffer: TagSection,
+}
+
+impl Serializable for AlterableConfig {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(self.name.serialize());
+        buf.push(self.config_operation as u8);
+        buf.extend(self.value.serialize());
+        buf.extend(self.tag_buffer.serialize());
+        buf
+    }
+
+    fn deserialize(bytes: &[u8]) -> Result<(Self, &[u8])> {
+        let (name, bytes) = CompactString::deserialize(bytes)?;
+        let (config_operation, bytes) = i8::deserialize(bytes)?;
+        let (value, bytes) = CompactString::deserialize(bytes)?;
+        let (tag_buffer, bytes) = TagSection::deserialize(bytes)?;
+        Ok((
+            AlterableConfig {
+                name,
+                config_operation,
+                value,
+                tag_buffer,
+            },
+            bytes,
+        ))
+    }
+}
+
+#[derive(Debug)]
+pub struct AlterConfigsResource {
+    pub resource_type: i8,
+    pub resource_name: CompactString,
+    pub configs: CompactArray<AlterableConfig>,
+    pub tag_buffer: TagSection,
+}
+
+impl Serializable for AlterConfigsResource {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.push(self.resource_type as u8);
+        buf.extend(self.resource_name.serialize());
+        buf.extend(self.configs.serialize());
+        buf.extend(self.tag_buffer.serialize());
+        buf
+    }
+
+    fn deserialize(bytes: &[u8]) -> Result<(Self, &[u8])> {
+        let (resource_type, bytes) = i8::deserialize(bytes)?;
+        let (resource_name, bytes) = CompactString::deserialize(bytes)?;
+        let (configs, bytes) = CompactArray::<AlterableConfig>::deserialize(bytes)?;
+        let (tag_buffer, bytes) = TagSection::deserialize(bytes)?;
+        Ok((
+            AlterConfigsResource {
+                resource_type,
+                resource_name,
+                configs,
+                tag_buffer,
+            },
+            bytes,
+        ))
+    }
+}
+
+impl AlterConfigsResource {
+    fn resource(&self) -> ConfigResource {
+        ConfigResource::new(
+            ConfigResourceType::from_code(self.resource_type),
+            self.resource_name.as_deref().unwrap_or_default(),
+        )
+    }
+
+    /// Apply each operation to the current configs of the resource, leaving the others
+    /// untouched.
+    fn changes(
+        &self,
+        broker: &Broker,
+        resource: &ConfigResource,
+    ) -> Result<ConfigChanges, (i16, String)> {
+        let metadata = broker.metadata.read().unwrap();
+        let resolver = ConfigResolver::new(&metadata, &broker.config);
+        let mut changes = ConfigChanges::new();
+        for config in self.configs.iter().flatten() {
+            let name = config.name.as_deref().unwrap_or_default();
+            if changes.contains_key(name) {
+                return Err((error::INVALID_REQUEST, format!("Duplicate config {name}")));
+            }
+            let value = match OpType::from_code(config.config_operation) {
+                Some(OpType::Set) => match config.value.0.clone() {
+                    Some(value) => Some(value),
+                    None => {
+                        return Err((
+                            error::INVALID_REQUEST,
+                            format!("Null value not supported for {name}"),
+                        ))
+                    }
+                },
+                Some(OpType::Delete) => None,
+                Some(op) => {
+                    let def = config_registry::find(resource.resource_type, name)
+                        .filter(|def| def.config_type == ConfigType::List)
+                        .ok_or_else(|| {
+                            (
+                                error::INVALID_CONFIG,
+                                format!(
+                                    "Config value append or subtract is not allowed for {name}"
+                                ),
+                            )
+                        })?;
+                    let split = |value: &str| -> Vec<String> {
+                        value
+                            .split(',')
+                            .map(str::trim)
+                            .filter(|s| !s.is_empty())
+                            .map(str::to_string)
+                            .collect()
+                    };
+                    let mut items = split(
+                        resolver
+                            .resolve(resource, def)
+                            .value
+                            .as_deref()
+                            .unwrap_or_default(),
+                    );
+                    let operands = split(config.value.as_deref().unwrap_or_default());
+                    if op == OpType::Append {
+                        for operand in operands {
+                            if !items.contains(&operand) {
+                                items.push(operand);
+                            }
+                        }
+                    } else {
+                        items.retain(|item| !operands.contains(item));
+                    }
+                    Some(items.join(","))
+                }
+                None => {
+                    return Err((
+                        error::INVALID_REQUEST,
+                        format!("Unknown config operation {}", config.config_operation),
+                    ))
+                }
+            };
+            changes.insert(name.to_string(), value);
+        }
+        Ok(changes)
+    }
+}
+
+#[derive(Debug)]
+pub struct IncrementalAlterConfigsRequest {
+    pub resources: CompactArray<AlterConfigsResource>,
+    pub validate_only: bool,
+    pub tag_buffer: TagSection,
+}
+
+impl Serializable for IncrementalAlterConfigsRequest {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(self.resources.serialize());
+        buf.push(self.validate_only as u8);
+        buf.extend(self.tag_buffer.serialize());
+        buf
+    }
+
+    fn deserialize(bytes: &[u8]) -> Result<(Self, &[u8])> {
+        let (resources, bytes) = CompactArray::<AlterConfigsResource>::deserialize(bytes)?;
+        let (validate_only, bytes) = bool::deserialize(bytes)?;
+        let (tag_buffer, bytes) = TagSection::deserialize(bytes)?;
+        Ok((
+            IncrementalAlterConfigsRequest {
+                resources,
+                validate_only,
+                tag_buffer,
+            },
+            bytes,
+        ))
+    }
+}
+
+impl IncrementalAlterConfigsRequest {
+    pub fn handle_request(&self, broker: &Broker, context: &ConnectionContext) -> Option<Response> {
+        let mut outcomes = vec![];
+        let mut records = vec![];
+        for request in self.resources.iter().flatten() {
+            let resource = request.resource();
+            let outcome = check_resource(broker, context, &resource, AclOperation::AlterConfigs)
+                .and_then(|()| request.changes(broker, &resource))
+                .and_then(|changes| config_records(broker, &resource, &changes));
+            outcomes.push(outcome.map(|resource_records| records.extend(resource_records)));
+        }
+        commit_config_records(broker, &mut outcomes, records, self.validate_only);
+
+        let responses = self
+            .resources
+            .iter()
+            .flatten()
+            .zip(outcomes)
+            .map(|(request, outcome)| {
+                AlterConfigsResourceResponse::new(
+                    outcome,
+                    request.resource_type,
+                    &request.resource_name,
+                )
+            })
+            .collect();
+
+        Some(Box::new(IncrementalAlterConfigsResponse {
+            throttle_time_ms: 0,
+            responses: CompactArray(Some(responses)),
+            tag_buffer: TagSection(None),
+        }))
+    }
+}
+
+impl RequestHandler for IncrementalAlterConfigsRequest {
+    fn read(bytes: &[u8], _version: i16) -> Result<Self> {
+        Ok(IncrementalAlterConfigsRequest::deserialize(bytes)?.0)
+    }
+
+    fn handle(&self, request: &mut RequestContext) -> Option<Response> {
+        self.handle_request(request.broker, request.connection)
+    }
+}
+
+#[derive(Debug)]
+pub struct IncrementalAlterConfigsResponse {
+    pub throttle_time_ms: i32,
+    pub responses: CompactArray<AlterConfigsResourceResponse>,
+    pub tag_buffer: TagSection,
+}
+
+impl Serializable for IncrementalAlterConfigsResponse {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(self.throttle_time_ms.to_be_bytes());
+        buf.extend(self.responses.serialize());
+        buf.extend(self.tag_buffer.serialize());
+        buf
+    }
+
+    fn deserialize(_bytes: &[u8]) -> Result<(Self, &[u8])> {
+        todo!()
+    }
+}
+
+impl ResponseBody for IncrementalAlterConfigsResponse {
+    fn set_throttle_time_ms(&mut self, throttle_time_ms: i32) {
+        self.throttle_time_ms = throttle_time_ms;
+    }
+}
@@ -0,0 +1,221 @@
+use anyhow::Result;
+
+use super::{
+    api_keys::{RequestContext, RequestHandler},
+    body::ResponseBody,
+    error,
+    primitive::{CompactArray, CompactString, Serializable, TagSection, UnsignedVarint},
+    response::Response,
+};
+use crate::{
+    broker::Broker,
+    security::acl::{AclOperation, ResourceType, CLUSTER_RESOURCE_NAME},
+    server::ConnectionContext,
+};
+
+#[derive(Debug)]
+pub struct ListPartitionReassignmentsTopics {
+    pub name: CompactString,
+    pub partition_indexes: CompactArray<i32>,
+    pub tag_buffer: TagSection,
+}
+
+impl Serializable for ListPartitionReassignmentsTopics {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(self.name.serialize());
+        buf.extend(self.partition_indexes.serialize());
+        buf.extend(self.tag_buffer.serialize());
+        buf
+    }
+
+    fn deserialize(bytes: &[u8]) -> Result<(Self, &[u8])> {
+        let (name, bytes) = CompactString::deserialize(bytes)?;
+        let (partition_indexes, bytes) = CompactArray::<i32>::deserialize(bytes)?;
+        let (tag_buffer, bytes) = TagSection::deserialize(bytes)?;
+        Ok((
+            ListPartitionReassignmentsTopics {
+                name,
+                partition_indexes,
+                tag_buffer,
+            },
+            bytes,
+        ))
+    }
+}
+
+/// ListPartitionReassignments version 0, reporting the ongoing reassignments from the
+/// metadata image of this node.
+#[derive(Debug)]
+pub struct ListPartitionReassignmentsRequest {
+    pub timeout_ms: i32,
+    /// Partitions to report on, null for every reassigning partition
+    pub topics: CompactArray<ListPartitionReassignmentsTopics>,
+    pub tag_buffer: TagSection,
+}
+
+impl Serializable for ListPartitionReassignmentsRequest {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(self.timeout_ms.to_be_bytes());
+        buf.extend(self.topics.serialize());
+        buf.extend(self.tag_buffer.serialize());
+        buf
+    }
+
+    fn deserialize(bytes: &[u8]) -> Result<(Self, &[u8])> {
+        let (timeout_ms, bytes) = i32::deserialize(bytes)?;
+        let (topics, bytes) = CompactArray::<ListPartitionReassignmentsTopics>::deserialize(bytes)?;
+        let (tag_buffer, bytes) = TagSection::deserialize(bytes)?;
+        Ok((
+            ListPartitionReassignmentsRequest {
+                timeout_ms,
+                topics,
+                tag_buffer,
+            },
+            bytes,
+        ))
+    }
+}
+
+impl ListPartitionReassignmentsRequest {
+    /// Partitions named in the request that are not reassigning, or do not exist, are
+    /// left out of the response.
+    pub fn handle_request(&self, broker: &Broker, context: &ConnectionContext) -> Option<Response> {
+        let mut response = ListPartitionReassignmentsResponse {
+            throttle_time_ms: 0,
+            error_code: error::NONE,
+            error_message: None,
+            topics: vec![],
+        };
+
+        if !broker.authorize(
+            context,
+            AclOperation::Describe,
+            ResourceType::Cluster,
+            CLUSTER_RESOURCE_NAME,
+        ) {
+            response.error_code = error::CLUSTER_AUTHORIZATION_FAILED;
+            response.error_message =
+                Some("Not authorized to list partition reassignments".to_string());
+        } else {
+            let metadata = broker.metadata.read().unwrap();
+            let requested: Vec<(String, Option<Vec<i32>>)> = match &self.topics.0 {
+                Some(topics) => topics
+                    .iter()
+                    .filter_map(|topic| {
+                        let name = topic.name.0.clone()?;
+                        Some((
+                            name,
+                            Some(topic.partition_indexes.0.clone().unwrap_or_default()),
+                        ))
+                    })
+                    .collect(),
+                None => {
+                    let mut names: Vec<String> = metadata.topics.keys().cloned().collect();
+                    names.sort();
+                    names.into_iter().map(|name| (name, None)).collect()
+                }
+            };
+            for (name, partitions) in requested {
+                let Some(registrations) = metadata
+                    .topics
+                    .get(&name)
+                    .and_then(|topic_id| metadata.partitions.get(topic_id))
+                else {
+                    continue;
+                };
+                let ongoing: Vec<OngoingPartitionReassignment> = registrations
+                    .iter()
+                    .filter(|(index, registration)| {
+                        registration.is_reassigning()
+                            && partitions.as_ref().map_or(true, |p| p.contains(index))
+                    })
+                    .map(
+                        |(&partition_index, registration)| OngoingPartitionReassignment {
+                            partition_index,
+                            replicas: registration.replicas.clone(),
+                            adding_replicas: registration.adding_replicas.clone(),
+                            removing_replicas: registration.removing_replicas.clone(),
+                        },
+                    )
+                    .collect();
+                if !ongoing.is_empty() {
+                    response.topics.push(OngoingTopicReassignment {
+                        name,
+                        partitions: ongoing,
+                    });
+                }
+            }
+        }
+
+        Some(Box::new(response))
+    }
+}
+
+impl RequestHandler for ListPartitionReassignmentsRequest {
+    fn read(bytes: &[u8], _version: i16) -> Result<Self> {
+        Ok(ListPartitionReassignmentsRequest::deserialize(bytes)?.0)
+    }
+
+    fn handle(&self, request: &mut RequestContext) -> Option<Response> {
+        self.handle_request(request.broker, request.connection)
+    }
+}
+
+#[derive(Debug)]
+pub struct OngoingPartitionReassignment {
+    pub partition_index: i32,
+    /// Current assignment, holding the adding and the removing replicas alike
+    pub replicas: Vec<i32>,
+    pub adding_replicas: Vec<i32>,
+    pub removing_replicas: Vec<i32>,
+}
+
+#[derive(Debug)]
+pub struct OngoingTopicReassignment {
+    pub name: String,
+    pub partitions: Vec<OngoingPartitionReassignment>,
+}
+
+#[derive(Debug)]
+pub struct ListPartitionReassignmentsResponse {
+    pub throttle_time_ms: i32,
+    pub error_code: i16,
+    pub error_message: Option<String>,
+    pub topics: Vec<OngoingTopicReassignment>,
+}
+
+impl Serializable for ListPartitionReassignmentsResponse {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(self.throttle_time_ms.to_be_bytes());
+        buf.extend(self.error_code.to_be_bytes());
+        buf.extend(CompactString(self.error_message.clone()).serialize());
+        buf.extend(UnsignedVarint(self.topics.len() as u32 + 1).serialize());
+        for topic in &self.topics {
+            buf.extend(CompactString(Some(topic.name.clone())).serialize());
+            buf.extend(UnsignedVarint(topic.partitions.len() as u32 + 1).serialize());
+            for partition in &topic.partitions {
+                buf.extend(partition.partition_index.to_be_bytes());
+                buf.extend(CompactArray(Some(partition.replicas.clone())).serialize());
+                buf.extend(CompactArray(Some(partition.adding_replicas.clone())).serialize());
+                buf.extend(CompactArray(Some(partition.removing_replicas.clone())).serialize());
+                buf.extend(TagSection(None).serialize());
+            }
+            buf.extend(TagSection(None).serialize());
+        }
+        buf.extend(TagSection(None).serialize());
+        buf
+    }
+
+    fn deserialize(_bytes: &[u8]) -> Result<(Self, &[u8])> {
+        todo!()
+    }
+}
+
+impl ResponseBody for ListPartitionReassignmentsResponse {
+    fn set_throttle_time_ms(&mut self, throttle_time_ms: i32) {
+        self.throttle_time_ms = throttle_time_ms;
+    }
+}
@@ -0,0 +1,596 @@
+use anyhow::Result;
+use uuid::Uuid;
+
+use super::{
+    api_keys::{self, RequestContext, RequestHandler, METADATA_KEY},
+    body::ResponseBody,
+    error,
+    primitive::{array_len, CompactString, KafkaString, Serializable, TagSection, UnsignedVarint},
+    response::Response,
+};
+use crate::{
+    broker::Broker,
+    metadata::{MetadataImage, PartitionRegistration},
+    security::acl::{AclOperation, ResourceType, CLUSTER_RESOURCE_NAME},
+    server::ConnectionContext,
+};
+
+/// A topic a Metadata request asks about, by name or, from version 10, by id.
+#[derive(Debug)]
+pub struct MetadataRequestTopic {
+    pub topic_id: Uuid,
+    pub name: Option<String>,
+}
+
+/// Metadata versions 4 to 12. Version 8 adds the authorized operations, version 9 the
+/// compact (flexible) encoding, version 10 topic ids, and version 11 drops the cluster
+/// authorized operations. Topics are not created automatically.
+#[derive(Debug)]
+pub struct MetadataRequest {
+    /// Topics to describe, null for all of them
+    pub topics: Option<Vec<MetadataRequestTopic>>,
+    pub allow_auto_topic_creation: bool,
+    /// Versions 8 to 10
+    pub include_cluster_authorized_operations: bool,
+    /// Version 8 and above
+    pub include_topic_authorized_operations: bool,
+}
+
+impl MetadataRequest {
+    pub fn deserialize_versioned(bytes: &[u8], version: i16) -> Result<(Self, &[u8])> {
+        let flexible = api_keys::is_flexible(METADATA_KEY, version);
+        let (len, mut bytes) = array_len(bytes, flexible)?;
+        let topics = match len {
+            None => None,
+            Some(len) => {
+                let mut topics = Vec::with_capacity(len.min(bytes.len()));
+                for _ in 0..len {
+                    let (topic_id, rest) = if version >= 10 {
+                        Uuid::deserialize(bytes)?
+                    } else {
+                        (Uuid::nil(), bytes)
+                    };
+                    let (name, mut rest) = if flexible {
+                        let (name, rest) = CompactString::deserialize(rest)?;
+                        (name.0, rest)
+                    } else {
+                        let (name, rest) = KafkaString::deserialize(rest)?;
+                        (name.0, rest)
+                    };
+                    if flexible {
+                        rest = TagSection::deserialize(rest)?.1;
+                    }
+                    topics.push(MetadataRequestTopic { topic_id, name });
+                    bytes = rest;
+                }
+                Some(topics)
+            }
+        };
+        let (allow_auto_topic_creation, bytes) = bool::deserialize(bytes)?;
+        let (include_cluster_authorized_operations, bytes) = if (8..=10).contains(&version) {
+            bool::deserialize(bytes)?
+        } else {
+            (false, bytes)
+        };
+        let (include_topic_authorized_operations, mut bytes) = if version >= 8 {
+            bool::deserialize(bytes)?
+        } else {
+            (false, bytes)
+        };
+        if flexible {
+            bytes = TagSection::deserialize(bytes)?.1;
+        }
+        Ok((
+            MetadataRequest {
+                topics,
+                allow_auto_topic_creation,
+                include_cluster_authorized_operations,
+                include_topic_authorized_operations,
+            },
+            bytes,
+        ))
+    }
+
+    pub fn handle_request(
+        &self,
+        version: i16,
+        broker: &Broker,
+        context: &ConnectionContext,
+    ) -> Option<Response> {
+        let image = broker.metadata.read().unwrap();
+
+        // Fenced brokers are left out, and brokers are reported by their endpoint on the
+        // listener the client came in through
+        let brokers = image
+            .brokers
+            .values()
+            .filter(|registration| !registration.fenced)
+            .filter_map(|registration| {
+                let endpoint = registration
+                    .endpoints
+                    .iter()
+                    .find(|endpoint| endpoint.name == context.listener_name)?;
+                Some(MetadataResponseBroker {
+                    node_id: registration.id,
+                    host: endpoint.host.clone(),
+                    port: endpoint.port as i32,
+                    rack: registration.rack.clone(),
+                })
+            })
+            .collect();
+
+        let authorized_operations = |name: &str| {
+            if self.include_topic_authorized_operations {
+                broker.authorized_operations(context, ResourceType::Topic, name)
+            } else {
+                i32::MIN
+            }
+        };
+        let mut topics = vec![];
+        match &self.topics {
+            // Listing every topic only shows those the client may describe
+            None => {
+                let mut names: Vec<(&String, &Uuid)> = image.topics.iter().collect();
+                names.sort();
+                for (name, &topic_id) in names {
+                    if broker.authorize(context, AclOperation::Describe, ResourceType::Topic, name)
+                    {
+                        let mut topic = describe_topic(&image, context, name, topic_id);
+                        topic.topic_authorized_operations = authorized_operations(name);
+                        topics.push(topic);
+                    }
+                }
+            }
+            Some(requested) => {
+                for requested in requested {
+                    let name = match &requested.name {
+                        Some(name) => Some(name.clone()),
+                        None => image
+                            .topics
+                            .iter()
+                            .find(|(_, &topic_id)| topic_id == requested.topic_id)
+                            .map(|(name, _)| name.clone()),
+                    };
+                    let Some(name) = name else {
+                        topics.push(MetadataResponseTopic::error(
+                            error::UNKNOWN_TOPIC_ID,
+                            None,
+                            requested.topic_id,
+                        ));
+                        continue;
+                    };
+                    if !broker.authorize(
+                        context,
+                        AclOperation::Describe,
+                        ResourceType::Topic,
+                        &name,
+                    ) {
+                        topics.push(MetadataResponseTopic::error(
+                            error::TOPIC_AUTHORIZATION_FAILED,
+                            Some(name),
+                            requested.topic_id,
+                        ));
+                        continue;
+                    }
+                    match image.topics.get(&name) {
+                        Some(&topic_id) => {
+                            let mut topic = describe_topic(&image, context, &name, topic_id);
+                            topic.topic_authorized_operations = authorized_operations(&name);
+                            topics.push(topic);
+                        }
+                        None => topics.push(MetadataResponseTopic::error(
+                            error::UNKNOWN_TOPIC_OR_PARTITION,
+                            Some(name),
+                            requested.topic_id,
+                        )),
+                    }
+                }
+            }
+        }
+
+        let cluster_authorized_operations = if self.include_cluster_authorized_operations {
+            if broker.authorize(
+                context,
+                AclOperation::Describe,
+                ResourceType::Cluster,
+                CLUSTER_RESOURCE_NAME,
+            ) {
+                broker.authorized_operations(context, ResourceType::Cluster, CLUSTER_RESOURCE_NAME)
+            } else {
+                0
+            }
+        } else {
+            i32::MIN
+        };
+
+        Some(Box::new(MetadataResponse {
+            version,
+            throttle_time_ms: 0,
+            brokers,
+            cluster_id: Some(broker.cluster_id.clone()),
+            controller_id: broker.raft.leader().unwrap_or(-1),
+            topics,
+            cluster_authorized_operations,
+        }))
+    }
+}
+
+impl RequestHandler for MetadataRequest {
+    fn read(bytes: &[u8], version: i16) -> Result<Self> {
+        Ok(MetadataRequest::deserialize_versioned(bytes, version)?.0)
+    }
+
+    fn handle(&self, request: &mut RequestContext) -> Option<Response> {
+        self.handle_request(request.api_version, request.broker, request.connection)
+    }
+}
+
+/// Partitions of a topic as the image records them.
+fn describe_topic(
+    image: &MetadataImage,
+    context: &ConnectionContext,
+    name: &str,
+    topic_id: Uuid,
+) -> MetadataResponseTopic {
+    let partitions = image
+        .partitions
+        .get(&topic_id)
+        .into_iter()
+        .flatten()
+        .map(|(&partition_index, registration)| {
+            describe_partition(image, context, partition_index, registration)
+        })
+        .collect();
+    MetadataResponseTopic {
+        error_code: error::NONE,
+        name: Some(name.to_string()),
+        topic_id,
+        is_internal: false,
+        partitions,
+        topic_authorized_operations: i32::MIN,
+    }
+}
+
+/// A partition whose leader is missing, fenced or unreachable through the listener
+/// reports LEADER_NOT_AVAILABLE. Replicas on such brokers are listed as offline.
+pub(crate) fn describe_partition(
+    image: &MetadataImage,
+    context: &ConnectionContext,
+    partition_index: i32,
+    registration: &PartitionRegistration,
+) -> MetadataResponsePartition {
+    let is_online = |broker_id: i32| {
+        image.brokers.get(&broker_id).is_some_and(|broker| {
+            !broker.fenced
+                && broker
+                    .endpoints
+                    .iter()
+                    .any(|endpoint| endpoint.name == context.listener_name)
+        })
+    };
+    let (error_code, leader_id) = if registration.leader >= 0 && is_online(registration.leader) {
+        (error::NONE, registration.leader)
+    } else {
+        (error::LEADER_NOT_AVAILABLE, -1)
+    };
+    MetadataResponsePartition {
+        error_code,
+        partition_index,
+        leader_id,
+        leader_epoch: registration.leader_epoch,
+        replica_nodes: registration.replicas.clone(),
+        isr_nodes: registration.isr.clone(),
+        offline_replicas: registration
+            .replicas
+            .iter()
+            .copied()
+            .filter(|&id| !is_online(id))
+            .collect(),
+    }
+}
+
+#[derive(Debug)]
+pub struct MetadataResponseBroker {
+    pub node_id: i32,
+    pub host: String,
+    pub port: i32,
+    pub rack: Option<String>,
+}
+
+#[derive(Debug)]
+pub struct MetadataResponsePartition {
+    pub error_code: i16,
+    pub partition_index: i32,
+    pub leader_id: i32,
+    /// Version 7 and above
+    pub leader_epoch: i32,
+    pub replica_nodes: Vec<i32>,
+    pub isr_nodes: Vec<i32>,
+    /// Version 5 and above
+    pub offline_replicas: Vec<i32>,
+}
+
+#[derive(Debug)]
+pub struct MetadataResponseTopic {
+    pub error_code: i16,
+    pub name: Option<String>,
+    /// Version 10 and above
+    pub topic_id: Uuid,
+    pub is_internal: bool,
+    pub partitions: Vec<MetadataResponsePartition>,
+    /// Version 8 and above, `i32::MIN` unless requested
+    pub topic_authorized_operations: i32,
+}
+
+impl MetadataResponseTopic {
+    fn error(error_code: i16, name: Option<String>, topic_id: Uuid) -> Self {
+        MetadataResponseTopic {
+            error_code,
+            name,
+            topic_id,
+            is_internal: false,
+            partitions: vec![],
+            topic_authorized_operations: i32::MIN,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct MetadataResponse {
+    pub version: i16,
+    pub throttle_time_ms: i32,
+    pub brokers: Vec<MetadataResponseBroker>,
+    pub cluster_id: Option<String>,
+    pub controller_id: i32,
+    pub topics: Vec<MetadataResponseTopic>,
+    /// Versions 8 to 10, `i32::MIN` unless requested
+    pub cluster_authorized_operations: i32,
+}
+
+impl MetadataResponse {
+    fn flexible(&self) -> bool {
+        api_keys::is_flexible(METADATA_KEY, self.version)
+    }
+
+    fn serialize_string(&self, value: Option<String>) -> Vec<u8> {
+        if self.flexible() {
+            CompactString(value).serialize()
+        } else {
+            KafkaString(value).serialize()
+        }
+    }
+
+    fn serialize_len(&self, len: usize) -> Vec<u8> {
+        if self.flexible() {
+            UnsignedVarint(len as u32 + 1).serialize()
+        } else {
+            (len as i32).to_be_bytes().to_vec()
+        }
+    }
+
+    fn serialize_ids(&self, ids: &[i32]) -> Vec<u8> {
+        let mut buf = self.serialize_len(ids.len());
+        for id in ids {
+            buf.extend(id.to_be_bytes());
+        }
+        buf
+    }
+
+    fn serialize_tags(&self, buf: &mut Vec<u8>) {
+        if self.flexible() {
+            buf.extend(TagSection(None).serialize());
+        }
+    }
+}
+
+impl Serializable for MetadataResponse {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(self.throttle_time_ms.to_be_bytes());
+        buf.extend(self.serialize_len(self.brokers.len()));
+        for broker in &self.brokers {
+            buf.extend(broker.node_id.to_be_bytes());
+            buf.extend(self.serialize_string(Some(broker.host.clone())));
+            buf.extend(broker.port.to_be_bytes());
+            buf.extend(self.serialize_string(broker.rack.clone()));
+            self.serialize_tags(&mut buf);
+        }
+        buf.extend(self.serialize_string(self.cluster_id.clone()));
+        buf.extend(self.controller_id.to_be_bytes());
+        buf.extend(self.serialize_len(self.topics.len()));
+        for topic in &self.topics {
+            buf.extend(topic.error_code.to_be_bytes());
+            // Names are only nullable from version 12
+            let name = topic.name.clone();
+            buf.extend(
+                self.serialize_string(name.or_else(|| (self.version < 12).then(String::new))),
+            );
+            if self.version >= 10 {
+                buf.extend(topic.topic_id.as_bytes());
+            }
+            buf.push(topic.is_internal as u8);
+            buf.extend(self.serialize_len(topic.partitions.len()));
+            for partition in &topic.partitions {
+                buf.extend(partition.error_code.to_be_bytes());
+                buf.extend(partition.partition_index.to_be_bytes());
+                buf.extend(partition.leader_id.to_be_bytes());
+                if self.version >= 7 {
+                    buf.extend(partition.leader_epoch.to_be_bytes());
+                }
+                buf.extend(self.serialize_ids(&partition.replica_nodes));
+                buf.extend(self.serialize_ids(&partition.isr_nodes));
+                if self.version >= 5 {
+                    buf.extend(self.serialize_ids(&partition.offline_replicas));
+                }
+                self.serialize_tags(&mut buf);
+            }
+            if self.version >= 8 {
+                buf.extend(topic.topic_authorized_operations.to_be_bytes());
+            }
+            self.serialize_tags(&mut buf);
+        }
+        if (8..=10).contains(&self.version) {
+            buf.extend(self.cluster_authorized_operations.to_be_bytes());
+        }
+        self.serialize_tags(&mut buf);
+        buf
+    }
+
+    fn deserialize(_bytes: &[u8]) -> Result<(Self, &[u8])> {
+        todo!()
+    }
+}
+
+impl ResponseBody for MetadataResponse {
+    fn set_throttle_time_ms(&mut self, throttle_time_ms: i32) {
+        self.throttle_time_ms = throttle_time_ms;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::{
+        config::{BrokerConfig, Properties, SecurityProtocol},
+        security::KafkaPrincipal,
+    };
+
+    fn response(version: i16) -> MetadataResponse {
+        MetadataResponse {
+            version,
+            throttle_time_ms: 0,
+            brokers: vec![MetadataResponseBroker {
+                node_id: 1,
+                host: "kafka".to_string(),
+                port: 9092,
+                rack: None,
+            }],
+            cluster_id: Some("c".to_string()),
+            controller_id: 1,
+            topics: vec![],
+            cluster_authorized_operations: i32::MIN,
+        }
+    }
+
+    fn context(listener_name: &str) -> ConnectionContext {
+        ConnectionContext {
+            listener_name: listener_name.to_string(),
+            security_protocol: SecurityProtocol::Plaintext,
+            peer_addr: "127.0.0.1:50000".parse().unwrap(),
+            principal: KafkaPrincipal::anonymous(),
+            client_software_name: None,
+            client_software_version: None,
+        }
+    }
+
+    #[test]
+    fn writes_brokers_in_the_encoding_of_the_version() {
+        let broker = [&[0, 0, 0, 1, 0, 5][..], b"kafka", &[0, 0, 0x23, 0x84]].concat();
+        let expected = [
+            &[0, 0, 0, 0, 0, 0, 0, 1][..],
+            &broker,
+            // Null rack, cluster id, controller id and no topics
+            &[0xff, 0xff, 0, 1, b'c', 0, 0, 0, 1, 0, 0, 0, 0],
+        ]
+        .concat();
+        assert_eq!(response(7).serialize(), expected);
+
+        let broker = [&[0, 0, 0, 1, 6][..], b"kafka", &[0, 0, 0x23, 0x84]].concat();
+        let expected = [
+            &[0, 0, 0, 0, 2][..],
+            &broker,
+            &[0, 0, 2, b'c', 0, 0, 0, 1, 1],
+            // Cluster authorized operations, then the response tags
+            &(i32::MIN).to_be_bytes(),
+            &[0],
+        ]
+        .concat();
+        assert_eq!(response(10).serialize(), expected);
+    }
+
+    #[test]
+    fn reads_null_topics_as_a_request_for_every_topic() {
+        let (request, rest) = MetadataRequest::deserialize_versioned(&[0, 1, 1, 0], 12).unwrap();
+        assert!(request.topics.is_none());
+        assert!(request.allow_auto_topic_creation);
+        assert!(request.include_topic_authorized_operations);
+        assert!(rest.is_empty());
+
+        let body = [&[0, 0, 0, 1, 0, 3][..], b"foo", &[0, 1, 0]].concat();
+        let (request, rest) = MetadataRequest::deserialize_versioned(&body, 8).unwrap();
+        let topics = request.topics.unwrap();
+        assert_eq!(topics[0].name.as_deref(), Some("foo"));
+        assert!(request.include_cluster_authorized_operations);
+        assert!(!request.include_topic_authorized_operations);
+        assert!(rest.is_empty());
+    }
+
+    #[test]
+    fn describes_brokers_by_their_endpoint_on_the_client_listener() {
+        let dir = std::env::temp_dir().join(format!("metadata-listeners-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let mut properties = Properties::default();
+        for (key, value) in [
+            ("node.id", "1"),
+            ("process.roles", "broker,controller"),
+            ("controller.quorum.voters", "1@127.0.0.1:9093"),
+            (
+                "listeners",
+                "INTERNAL://127.0.0.1:9092,EXTERNAL://127.0.0.1:9094,CONTROLLER://127.0.0.1:9093",
+            ),
+            (
+                "advertised.listeners",
+                "INTERNAL://broker-1.internal:9092,EXTERNAL://kafka.example.com:19094",
+            ),
+            (
+                "listener.security.protocol.map",
+                "INTERNAL:PLAINTEXT,EXTERNAL:PLAINTEXT,CONTROLLER:PLAINTEXT",
+            ),
+            ("inter.broker.listener.name", "INTERNAL"),
+            ("controller.listener.names", "CONTROLLER"),
+            ("log.dirs", &dir.join("logs").display().to_string()),
+        ] {
+            properties.set(key, value);
+        }
+        let broker = Broker::new(BrokerConfig::from_properties(&properties).unwrap()).unwrap();
+
+        let request = MetadataRequest {
+            topics: Some(vec![]),
+            allow_auto_topic_creation: false,
+            include_cluster_authorized_operations: false,
+            include_topic_authorized_operations: false,
+        };
+        for (listener, host, port) in [
+            ("INTERNAL", "broker-1.internal", 9092),
+            ("EXTERNAL", "kafka.example.com", 19094),
+        ] {
+            let described = request
+                .handle_request(12, &broker, &context(listener))
+                .unwrap();
+            let expected = MetadataResponse {
+                brokers: vec![MetadataResponseBroker {
+                    node_id: 1,
+                    host: host.to_string(),
+                    port,
+                    rack: None,
+                }],
+                cluster_id: Some(broker.cluster_id.clone()),
+                ..response(12)
+            };
+            assert_eq!(described.serialize(), expected.serialize(), "{listener}");
+        }
+        // Brokers without an endpoint on the listener are left out
+        let described = request
+            .handle_request(12, &broker, &context("OTHER"))
+            .unwrap();
+        let expected = MetadataResponse {
+            brokers: vec![],
+            cluster_id: Some(broker.cluster_id.clone()),
+            ..response(12)
+        };
+        assert_eq!(described.serialize(), expected.serialize());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,7 +1,46 @@
+pub mod alter_client_quotas;
+pub mod alter_configs;
+pub mod alter_partition_reassignments;
+pub mod alter_replica_log_dirs;
+pub mod alter_user_scram_credentials;
+pub mod api_keys;
 pub mod api_version;
+pub mod begin_quorum_epoch;
 pub mod body;
+pub mod broker_heartbeat;
+pub mod broker_registration;
 pub mod cluster_metadata;
+pub mod create_acls;
+pub mod create_partitions;
+pub mod create_topics;
+pub mod delete_acls;
+pub mod delete_records;
+pub mod describe_acls;
+pub mod describe_client_quotas;
+pub mod describe_cluster;
+pub mod describe_configs;
+pub mod describe_log_dirs;
+pub mod describe_quorum;
 pub mod describe_topic_partitions;
+pub mod describe_user_scram_credentials;
+pub mod elect_leaders;
+pub mod end_quorum_epoch;
+pub mod error;
+pub mod fetch;
+pub mod fetch_snapshot;
+pub mod find_coordinator;
 pub mod header;
+pub mod incremental_alter_configs;
+pub mod list_partition_reassignments;
+pub mod metadata;
+pub mod offset_for_leader_epoch;
 pub mod primitive;
+pub mod produce;
 pub mod response;
+pub mod sasl_authenticate;
+pub mod sasl_handshake;
+pub mod share_acknowledge;
+pub mod share_fetch;
+pub mod share_group_heartbeat;
+pub mod unregister_broker;
+pub mod vote;
@@ -0,0 +1,278 @@
+use anyhow::Result;
+
+use super::{
+    api_keys::{RequestContext, RequestHandler},
+    body::ResponseBody,
+    error,
+    primitive::{CompactArray, CompactString, Serializable, TagSection},
+    response::Response,
+};
+use crate::{
+    broker::Broker,
+    log_manager::TopicPartition,
+    security::acl::{AclOperation, ResourceType, CLUSTER_RESOURCE_NAME},
+    server::ConnectionContext,
+};
+
+#[derive(Debug)]
+pub struct OffsetForLeaderPartition {
+    pub partition: i32,
+    /// Leader epoch the client knows, -1 to skip fencing
+    pub current_leader_epoch: i32,
+    /// Epoch to find the end offset of
+    pub leader_epoch: i32,
+    pub tag_buffer: TagSection,
+}
+
+impl Serializable for OffsetForLeaderPartition {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(self.partition.to_be_bytes());
+        buf.extend(self.current_leader_epoch.to_be_bytes());
+        buf.extend(self.leader_epoch.to_be_bytes());
+        buf.extend(self.tag_buffer.serialize());
+        buf
+    }
+
+    fn deserialize(bytes: &[u8]) -> Result<(Self, &[u8])> {
+        let (partition, bytes) = i32::deserialize(bytes)?;
+        let (current_leader_epoch, bytes) = i32::deserialize(bytes)?;
+        let (leader_epoch, bytes) = i32::deserialize(bytes)?;
+        let (tag_buffer, bytes) = TagSection::deserialize(bytes)?;
+        Ok((
+            OffsetForLeaderPartition {
+                partition,
+                current_leader_epoch,
+                leader_epoch,
+                tag_buffer,
+            },
+            bytes,
+        ))
+    }
+}
+
+#[derive(Debug)]
+pub struct OffsetForLeaderTopic {
+    pub topic: CompactString,
+    pub partitions: CompactArray<OffsetForLeaderPartition>,
+    pub tag_buffer: TagSection,
+}
+
+impl Serializable for OffsetForLeaderTopic {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(self.topic.serialize());
+        buf.extend(self.partitions.serialize());
+        buf.extend(self.tag_buffer.serialize());
+        buf
+    }
+
+    fn deserialize(bytes: &[u8]) -> Result<(Self, &[u8])> {
+        let (topic, bytes) = CompactString::deserialize(bytes)?;
+        let (partitions, bytes) = CompactArray::<OffsetForLeaderPartition>::deserialize(bytes)?;
+        let (tag_buffer, bytes) = TagSection::deserialize(bytes)?;
+        Ok((
+            OffsetForLeaderTopic {
+                topic,
+                partitions,
+                tag_buffer,
+            },
+            bytes,
+        ))
+    }
+}
+
+/// OffsetForLeaderEpoch version 4, with which consumers find where their log diverged
+/// after a leader change.
+#[derive(Debug)]
+pub struct OffsetForLeaderEpochRequest {
+    /// Id of the follower asking, -1 for consumers and -2 for debugging consumers
+    pub replica_id: i32,
+    pub topics: CompactArray<OffsetForLeaderTopic>,
+    pub tag_buffer: TagSection,
+}
+
+impl Serializable for OffsetForLeaderEpochRequest {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(self.replica_id.to_be_bytes());
+        buf.extend(self.topics.serialize());
+        buf.extend(self.tag_buffer.serialize());
+        buf
+    }
+
+    fn deserialize(bytes: &[u8]) -> Result<(Self, &[u8])> {
+        let (replica_id, bytes) = i32::deserialize(bytes)?;
+        let (topics, bytes) = CompactArray::<OffsetForLeaderTopic>::deserialize(bytes)?;
+        let (tag_buffer, bytes) = TagSection::deserialize(bytes)?;
+        Ok((
+            OffsetForLeaderEpochRequest {
+                replica_id,
+                topics,
+                tag_buffer,
+            },
+            bytes,
+        ))
+    }
+}
+
+impl OffsetForLeaderEpochRequest {
+    pub fn handle_request(&self, broker: &Broker, context: &ConnectionContext) -> Option<Response> {
+        // Followers act for the cluster, consumers only need to know of the topic
+        let is_follower = self.replica_id >= 0;
+        let follower_authorized = is_follower
+            && broker.authorize(
+                context,
+                AclOperation::ClusterAction,
+                ResourceType::Cluster,
+                CLUSTER_RESOURCE_NAME,
+            );
+        let topics = self
+            .topics
+            .iter()
+            .flatten()
+            .map(|topic| {
+                let name = topic.topic.as_deref().unwrap_or_default();
+                let authorized = if is_follower {
+                    follower_authorized
+                } else {
+                    broker.authorize(context, AclOperation::Describe, ResourceType::Topic, name)
+                };
+                let partitions = topic
+                    .partitions
+                    .iter()
+                    .flatten()
+                    .map(|partition| {
+                        let outcome = if !authorized {
+                            Err(if is_follower {
+                                error::CLUSTER_AUTHORIZATION_FAILED
+                            } else {
+                                error::TOPIC_AUTHORIZATION_FAILED
+                            })
+                        } else {
+                            let replica = TopicPartition::new(name, partition.partition);
+                            broker.replicas.end_offset_for_epoch(
+                                broker,
+                                &replica,
+                                self.replica_id,
+                                partition.current_leader_epoch,
+                                partition.leader_epoch,
+                            )
+                        };
+                        let mut result = EpochEndOffset {
+                            error_code: error::NONE,
+                            partition: partition.partition,
+                            leader_epoch: -1,
+                            end_offset: -1,
+                            tag_buffer: TagSection(None),
+                        };
+                        match outcome {
+                            Ok(end) => {
+                                result.leader_epoch = end.epoch;
+                                result.end_offset = end.end_offset;
+                            }
+                            Err(error_code) => result.error_code = error_code,
+                        }
+                        result
+                    })
+                    .collect();
+                OffsetForLeaderTopicResult {
+                    topic: topic.topic.clone(),
+                    partitions: CompactArray(Some(partitions)),
+                    tag_buffer: TagSection(None),
+                }
+            })
+            .collect();
+
+        Some(Box::new(OffsetForLeaderEpochResponse {
+            throttle_time_ms: 0,
+            topics: CompactArray(Some(topics)),
+            tag_buffer: TagSection(None),
+        }))
+    }
+}
+
+impl RequestHandler for OffsetForLeaderEpochRequest {
+    fn read(bytes: &[u8], _version: i16) -> Result<Self> {
+        Ok(OffsetForLeaderEpochRequest::deserialize(bytes)?.0)
+    }
+
+    fn handle(&self, request: &mut RequestContext) -> Option<Response> {
+        self.handle_request(request.broker, request.connection)
+    }
+}
+
+#[derive(Debug)]
+pub struct EpochEndOffset {
+    pub error_code: i16,
+    pub partition: i32,
+    /// Largest epoch not above the requested one, -1 if unknown
+    pub leader_epoch: i32,
+    /// Offset the epoch ends at, -1 if unknown
+    pub end_offset: i64,
+    pub tag_buffer: TagSection,
+}
+
+impl Serializable for EpochEndOffset {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(self.error_code.to_be_bytes());
+        buf.extend(self.partition.to_be_bytes());
+        buf.extend(self.leader_epoch.to_be_bytes());
+        buf.extend(self.end_offset.to_be_bytes());
+        buf.extend(self.tag_buffer.serialize());
+        buf
+    }
+
+    fn deserialize(_bytes: &[u8]) -> Result<(Self, &[u8])> {
+        todo!()
+    }
+}
+
+#[derive(Debug)]
+pub struct OffsetForLeaderTopicResult {
+    pub topic: CompactString,
+    pub partitions: CompactArray<EpochEndOffset>,
+    pub tag_buffer: TagSection,
+}
+
+impl Serializable for OffsetForLeaderTopicResult {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(self.topic.serialize());
+        buf.extend(self.partitions.serialize());
+        buf.extend(self.tag_buffer.serialize());
+        buf
+    }
+
+    fn deserialize(_bytes: &[u8]) -> Result<(Self, &[u8])> {
+        todo!()
+    }
+}
+
+#[derive(Debug)]
+pub struct OffsetForLeaderEpochResponse {
+    pub throttle_time_ms: i32,
+    pub topics: CompactArray<OffsetForLeaderTopicResult>,
+    pub tag_buffer: TagSection,
+}
+
+impl Serializable for OffsetForLeaderEpochResponse {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(self.throttle_time_ms.to_be_bytes());
+        buf.extend(self.topics.serialize());
+        buf.extend(self.tag_buffer.serialize());
+        buf
+    }
+
+    fn deserialize(_bytes: &[u8]) -> Result<(Self, &[u8])> {
+        todo!()
+    }
+}
+
+impl ResponseBody for OffsetForLeaderEpochResponse {
+    fn set_throttle_time_ms(&mut self, throttle_time_ms: i32) {
+        self.throttle_time_ms = throttle_time_ms;
+    }
+}
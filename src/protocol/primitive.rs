@@ -1,9 +1,11 @@
 use anyhow::{Ok, Result};
 use derive_more::Deref;
 
-pub trait Serializable: Sized {
+pub trait Serializable {
     fn serialize(&self) -> Vec<u8>;
-    fn deserialize(bytes: &[u8]) -> Result<(Self, &[u8])>;
+    fn deserialize(bytes: &[u8]) -> Result<(Self, &[u8])>
+    where
+        Self: Sized;
 }
 
 #[derive(Debug, Deref)]
@@ -24,6 +26,9 @@ impl Serializable for UnsignedVarint {
         let mut result = 0;
         let mut shift = 0;
         for (i, byte) in bytes.iter().enumerate() {
+            if shift > 28 {
+                break;
+            }
             let value = (byte & 0x7F) as u32;
             result |= value << shift;
             if byte & 0x80 == 0 {
@@ -53,6 +58,9 @@ impl Serializable for Varint {
         let mut result = 0;
         let mut shift = 0;
         for (i, byte) in bytes.iter().enumerate() {
+            if shift > 28 {
+                break;
+            }
             let value = (byte & 0x7F) as i32;
             result |= value << shift;
             if byte & 0x80 == 0 {
@@ -65,7 +73,7 @@ impl Serializable for Varint {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct TagField {
     pub tag: u32,
     pub data: Vec<u8>,
@@ -85,19 +93,30 @@ impl Serializable for TagField {
         let tag = tag.0;
         let (length, bytes) = UnsignedVarint::deserialize(bytes)?;
         let length = length.0;
-        let (data, bytes) = bytes.split_at(length as usize);
+        let Some((data, bytes)) = bytes.split_at_checked(length as usize) else {
+            anyhow::bail!("Error: not enough bytes left");
+        };
         let data = data.to_vec();
         Ok((TagField { tag, data }, bytes))
     }
 }
 
-#[derive(Debug, Default)]
+#[derive(Debug, Clone, Default)]
 pub struct TagSection(pub Option<Vec<TagField>>);
 
 impl TagSection {
     pub fn new() -> Self {
         TagSection(Some(vec![]))
     }
+
+    /// Data of the tagged field `tag`, if present.
+    pub fn field(&self, tag: u32) -> Option<&[u8]> {
+        self.0
+            .iter()
+            .flatten()
+            .find(|field| field.tag == tag)
+            .map(|field| field.data.as_slice())
+    }
 }
 
 impl Serializable for TagSection {
@@ -131,7 +150,7 @@ impl Serializable for TagSection {
     }
 }
 
-#[derive(Debug, Deref, Default)]
+#[derive(Debug, Clone, Deref, Default, PartialEq, Eq)]
 pub struct CompactString(pub Option<String>);
 
 impl Serializable for CompactString {
@@ -153,7 +172,9 @@ impl Serializable for CompactString {
             return Ok((CompactString(None), bytes));
         }
 
-        let (str, bytes) = bytes.split_at(length as usize - 1);
+        let Some((str, bytes)) = bytes.split_at_checked(length as usize - 1) else {
+            anyhow::bail!("Error: not enough bytes left");
+        };
         let str = String::from_utf8_lossy(str).into_owned();
         Ok((CompactString(Some(str)), bytes))
     }
@@ -184,7 +205,7 @@ impl<T: Serializable> Serializable for CompactArray<T> {
         }
 
         let mut bytes = bytes;
-        let mut array = Vec::with_capacity(length as usize - 1);
+        let mut array = Vec::with_capacity((length as usize - 1).min(bytes.len()));
         for _ in 0..(length - 1) {
             let (item, rest) = T::deserialize(bytes)?;
             array.push(item);
@@ -193,3 +214,213 @@ impl<T: Serializable> Serializable for CompactArray<T> {
         Ok((CompactArray(Some(array)), bytes))
     }
 }
+
+#[derive(Debug)]
+pub struct Varlong(pub i64);
+
+impl Serializable for Varlong {
+    fn serialize(&self) -> Vec<u8> {
+        let mut result = Vec::new();
+        let mut value = ((self.0 << 1) ^ (self.0 >> 63)) as u64;
+        while value >= 0x80 {
+            result.push((value & 0x7F) as u8 | 0x80);
+            value >>= 7;
+        }
+        result.push(value as u8);
+        result
+    }
+    fn deserialize(bytes: &[u8]) -> Result<(Self, &[u8])> {
+        let mut result: i64 = 0;
+        let mut shift = 0;
+        for (i, byte) in bytes.iter().enumerate() {
+            if shift > 63 {
+                break;
+            }
+            let value = (byte & 0x7F) as i64;
+            result |= value << shift;
+            if byte & 0x80 == 0 {
+                let decode = ((result as u64) >> 1) as i64 ^ -(result & 1);
+                return Ok((Varlong(decode), &bytes[i + 1..]));
+            }
+            shift += 7;
+        }
+        anyhow::bail!("Failed to deserialize")
+    }
+}
+
+/// Split off exactly `N` bytes from the front of the buffer.
+pub fn take<const N: usize>(bytes: &[u8]) -> Result<([u8; N], &[u8])> {
+    let Some((head, rest)) = bytes.split_at_checked(N) else {
+        anyhow::bail!("Error: not enough bytes left");
+    };
+    Ok((head.try_into()?, rest))
+}
+
+/// Length of an array, compact in the flexible encoding and `None` for a null array.
+pub fn array_len(bytes: &[u8], flexible: bool) -> Result<(Option<usize>, &[u8])> {
+    if flexible {
+        let (len, bytes) = UnsignedVarint::deserialize(bytes)?;
+        Ok((len.0.checked_sub(1).map(|len| len as usize), bytes))
+    } else {
+        let (len, bytes) = i32::deserialize(bytes)?;
+        Ok(((len >= 0).then_some(len as usize), bytes))
+    }
+}
+
+macro_rules! impl_serializable_int {
+    ($($t:ty),*) => {
+        $(
+            impl Serializable for $t {
+                fn serialize(&self) -> Vec<u8> {
+                    self.to_be_bytes().to_vec()
+                }
+                fn deserialize(bytes: &[u8]) -> Result<(Self, &[u8])> {
+                    let (value, bytes) = take(bytes)?;
+                    Ok((<$t>::from_be_bytes(value), bytes))
+                }
+            }
+        )*
+    };
+}
+
+impl_serializable_int!(i8, i16, i32, i64, u16, u32, f64);
+
+impl Serializable for bool {
+    fn serialize(&self) -> Vec<u8> {
+        vec![*self as u8]
+    }
+    fn deserialize(bytes: &[u8]) -> Result<(Self, &[u8])> {
+        let ([value], bytes) = take(bytes)?;
+        Ok((value != 0, bytes))
+    }
+}
+
+impl Serializable for uuid::Uuid {
+    fn serialize(&self) -> Vec<u8> {
+        self.as_bytes().to_vec()
+    }
+    fn deserialize(bytes: &[u8]) -> Result<(Self, &[u8])> {
+        let (value, bytes) = take::<16>(bytes)?;
+        Ok((uuid::Uuid::from_bytes(value), bytes))
+    }
+}
+
+/// A string prefixed with its length as an INT16, where -1 stands for null.
+#[derive(Debug, Clone, Deref, Default, PartialEq, Eq)]
+pub struct KafkaString(pub Option<String>);
+
+impl Serializable for KafkaString {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        match &self.0 {
+            None => buf.extend((-1i16).to_be_bytes()),
+            Some(str) => {
+                buf.extend((str.len() as i16).to_be_bytes());
+                buf.extend(str.as_bytes());
+            }
+        }
+        buf
+    }
+    fn deserialize(bytes: &[u8]) -> Result<(Self, &[u8])> {
+        let (length, bytes) = i16::deserialize(bytes)?;
+        if length < 0 {
+            return Ok((KafkaString(None), bytes));
+        }
+        let Some((str, bytes)) = bytes.split_at_checked(length as usize) else {
+            anyhow::bail!("Error: not enough bytes left");
+        };
+        let str = String::from_utf8_lossy(str).into_owned();
+        Ok((KafkaString(Some(str)), bytes))
+    }
+}
+
+/// A byte buffer prefixed with its length as an INT32, where -1 stands for null.
+#[derive(Debug, Clone, Deref, Default, PartialEq, Eq)]
+pub struct KafkaBytes(pub Option<Vec<u8>>);
+
+impl Serializable for KafkaBytes {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        match &self.0 {
+            None => buf.extend((-1i32).to_be_bytes()),
+            Some(data) => {
+                buf.extend((data.len() as i32).to_be_bytes());
+                buf.extend(data);
+            }
+        }
+        buf
+    }
+    fn deserialize(bytes: &[u8]) -> Result<(Self, &[u8])> {
+        let (length, bytes) = i32::deserialize(bytes)?;
+        if length < 0 {
+            return Ok((KafkaBytes(None), bytes));
+        }
+        let Some((data, bytes)) = bytes.split_at_checked(length as usize) else {
+            anyhow::bail!("Error: not enough bytes left");
+        };
+        Ok((KafkaBytes(Some(data.to_vec())), bytes))
+    }
+}
+
+#[derive(Debug, Clone, Deref, Default, PartialEq, Eq)]
+pub struct CompactBytes(pub Option<Vec<u8>>);
+
+impl Serializable for CompactBytes {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        match &self.0 {
+            None => buf.extend(UnsignedVarint(0).serialize()),
+            Some(data) => {
+                buf.extend(UnsignedVarint(data.len() as u32 + 1).serialize());
+                buf.extend(data);
+            }
+        }
+        buf
+    }
+    fn deserialize(bytes: &[u8]) -> Result<(Self, &[u8])> {
+        let (length, bytes) = UnsignedVarint::deserialize(bytes)?;
+        let length = length.0;
+        if length == 0 {
+            return Ok((CompactBytes(None), bytes));
+        }
+        let Some((data, bytes)) = bytes.split_at_checked(length as usize - 1) else {
+            anyhow::bail!("Error: not enough bytes left");
+        };
+        Ok((CompactBytes(Some(data.to_vec())), bytes))
+    }
+}
+
+/// An array prefixed with its length as an INT32, where -1 stands for null.
+#[derive(Debug, Default, Deref)]
+pub struct KafkaArray<T: Serializable>(pub Option<Vec<T>>);
+
+impl<T: Serializable> Serializable for KafkaArray<T> {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        match &self.0 {
+            None => buf.extend((-1i32).to_be_bytes()),
+            Some(array) => {
+                buf.extend((array.len() as i32).to_be_bytes());
+                for element in array {
+                    buf.extend(element.serialize());
+                }
+            }
+        }
+        buf
+    }
+    fn deserialize(bytes: &[u8]) -> Result<(Self, &[u8])> {
+        let (length, bytes) = i32::deserialize(bytes)?;
+        if length < 0 {
+            return Ok((KafkaArray(None), bytes));
+        }
+
+        let mut bytes = bytes;
+        let mut array = Vec::with_capacity((length as usize).min(bytes.len()));
+        for _ in 0..length {
+            let (item, rest) = T::deserialize(bytes)?;
+            array.push(item);
+            bytes = rest;
+        }
+        Ok((KafkaArray(Some(array)), bytes))
+    }
+}
@@ -0,0 +1,461 @@
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+
+use super::{
+    api_keys::{RequestContext, RequestHandler},
+    body::ResponseBody,
+    cluster_metadata::{crc32c, RecordBatch},
+    error,
+    primitive::{
+        CompactArray, CompactBytes, CompactString, Serializable, TagSection, UnsignedVarint,
+    },
+    response::Response,
+};
+use crate::{
+    log_manager::TopicPartition,
+    quota::QuotaType,
+    security::acl::{AclOperation, ResourceType},
+};
+
+/// Acknowledgement from every in-sync replica.
+pub const ACKS_ALL: i16 = -1;
+/// No response at all.
+pub const ACKS_NONE: i16 = 0;
+/// Acknowledgement once the leader appended the records.
+pub const ACKS_LEADER: i16 = 1;
+
+#[derive(Debug)]
+pub struct PartitionProduceData {
+    pub index: i32,
+    pub records: CompactBytes,
+    pub tag_buffer: TagSection,
+}
+
+impl Serializable for PartitionProduceData {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(self.index.to_be_bytes());
+        buf.extend(self.records.serialize());
+        buf.extend(self.tag_buffer.serialize());
+        buf
+    }
+
+    fn deserialize(bytes: &[u8]) -> Result<(Self, &[u8])> {
+        let (index, bytes) = i32::deserialize(bytes)?;
+        let (records, bytes) = CompactBytes::deserialize(bytes)?;
+        let (tag_buffer, bytes) = TagSection::deserialize(bytes)?;
+        Ok((
+            PartitionProduceData {
+                index,
+                records,
+                tag_buffer,
+            },
+            bytes,
+        ))
+    }
+}
+
+#[derive(Debug)]
+pub struct TopicProduceData {
+    pub name: CompactString,
+    pub partition_data: CompactArray<PartitionProduceData>,
+    pub tag_buffer: TagSection,
+}
+
+impl Serializable for TopicProduceData {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(self.name.serialize());
+        buf.extend(self.partition_data.serialize());
+        buf.extend(self.tag_buffer.serialize());
+        buf
+    }
+
+    fn deserialize(bytes: &[u8]) -> Result<(Self, &[u8])> {
+        let (name, bytes) = CompactString::deserialize(bytes)?;
+        let (partition_data, bytes) = CompactArray::<PartitionProduceData>::deserialize(bytes)?;
+        let (tag_buffer, bytes) = TagSection::deserialize(bytes)?;
+        Ok((
+            TopicProduceData {
+                name,
+                partition_data,
+                tag_buffer,
+            },
+            bytes,
+        ))
+    }
+}
+
+/// Produce version 9. Transactions are not supported, the transactional id is ignored.
+#[derive(Debug)]
+pub struct ProduceRequest {
+    pub transactional_id: CompactString,
+    pub acks: i16,
+    pub timeout_ms: i32,
+    pub topic_data: CompactArray<TopicProduceData>,
+    pub tag_buffer: TagSection,
+}
+
+impl Serializable for ProduceRequest {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(self.transactional_id.serialize());
+        buf.extend(self.acks.to_be_bytes());
+        buf.extend(self.timeout_ms.to_be_bytes());
+        buf.extend(self.topic_data.serialize());
+        buf.extend(self.tag_buffer.serialize());
+        buf
+    }
+
+    fn deserialize(bytes: &[u8]) -> Result<(Self, &[u8])> {
+        let (transactional_id, bytes) = CompactString::deserialize(bytes)?;
+        let (acks, bytes) = i16::deserialize(bytes)?;
+        let (timeout_ms, bytes) = i32::deserialize(bytes)?;
+        let (topic_data, bytes) = CompactArray::<TopicProduceData>::deserialize(bytes)?;
+        let (tag_buffer, bytes) = TagSection::deserialize(bytes)?;
+        Ok((
+            ProduceRequest {
+                transactional_id,
+                acks,
+                timeout_ms,
+                topic_data,
+                tag_buffer,
+            },
+            bytes,
+        ))
+    }
+}
+
+impl ProduceRequest {
+    pub fn handle_request(&self, request: &mut RequestContext) -> Option<Response> {
+        let broker = request.broker;
+        let context = &*request.connection;
+        let deadline = Instant::now() + Duration::from_millis(self.timeout_ms.max(0) as u64);
+
+        // Every partition is appended before waiting on any of them for acks=all
+        let mut responses = vec![];
+        let mut pending = vec![];
+        let mut appended_bytes = 0;
+        for topic in self.topic_data.iter().flatten() {
+            let name = topic.name.as_deref().unwrap_or_default();
+            let authorized =
+                broker.authorize(context, AclOperation::Write, ResourceType::Topic, name);
+            let mut partition_responses = vec![];
+            for partition in topic.partition_data.iter().flatten() {
+                let mut response = PartitionProduceResponse {
+                    index: partition.index,
+                    error_code: error::NONE,
+                    base_offset: -1,
+                    log_append_time_ms: -1,
+                    log_start_offset: -1,
+                    record_errors: CompactArray(Some(vec![])),
+                    error_message: CompactString(None),
+                    tag_buffer: TagSection(None),
+                };
+                let replica = TopicPartition::new(name, partition.index);
+                let appended = if !matches!(self.acks, ACKS_ALL | ACKS_NONE | ACKS_LEADER) {
+                    Err(error::INVALID_REQUIRED_ACKS)
+                } else if !authorized {
+                    Err(error::TOPIC_AUTHORIZATION_FAILED)
+                } else {
+                    let max_message_bytes = broker.log_config(name).max_message_bytes;
+                    validate_records(partition.records.as_deref(), max_message_bytes).and_then(
+                        |records| {
+                            broker.replicas.append_records(
+                                broker,
+                                &replica,
+                                records.to_vec(),
+                                self.acks,
+                            )
+                        },
+                    )
+                };
+                if appended.is_ok() {
+                    appended_bytes += partition.records.as_deref().map_or(0, <[u8]>::len);
+                }
+                match appended {
+                    Ok(append) => {
+                        response.base_offset = append.base_offset;
+                        response.log_start_offset = append.log_start_offset;
+                        if self.acks == ACKS_ALL {
+                            pending.push((
+                                responses.len(),
+                                partition_responses.len(),
+                                replica,
+                                append.log_end_offset,
+                            ));
+                        }
+                    }
+                    Err(error_code) => response.error_code = error_code,
+                }
+                partition_responses.push(response);
+            }
+            responses.push(TopicProduceResponse {
+                name: topic.name.clone(),
+                partition_responses,
+            });
+        }
+
+        request.throttle_time_ms = broker.record_quota(
+            context,
+            request.client_id,
+            QuotaType::Produce,
+            appended_bytes as f64,
+        );
+        if self.acks == ACKS_NONE {
+            return None;
+        }
+        for (topic, partition, replica, log_end_offset) in pending {
+            if let Err(error_code) =
+                broker
+                    .replicas
+                    .wait_for_replication(broker, &replica, log_end_offset, deadline)
+            {
+                let response = &mut responses[topic].partition_responses[partition];
+                response.error_code = error_code;
+                response.base_offset = -1;
+            }
+        }
+
+        Some(Box::new(ProduceResponse {
+            responses,
+            throttle_time_ms: request.throttle_time_ms,
+        }))
+    }
+}
+
+impl RequestHandler for ProduceRequest {
+    fn read(bytes: &[u8], _version: i16) -> Result<Self> {
+        Ok(ProduceRequest::deserialize(bytes)?.0)
+    }
+
+    fn handle(&self, request: &mut RequestContext) -> Option<Response> {
+        self.handle_request(request)
+    }
+}
+
+/// Check that the records are well-formed version 2 batches, each within the size
+/// limit of the topic.
+fn validate_records(records: Option<&[u8]>, max_message_bytes: i32) -> Result<&[u8], i16> {
+    let records = records.ok_or(error::CORRUPT_MESSAGE)?;
+    // Partition leader epoch, magic and CRC precede the CRC-covered part of a batch
+    const CRC_POSITION: usize = RecordBatch::LOG_OVERHEAD + 5;
+    const MAGIC_POSITION: usize = RecordBatch::LOG_OVERHEAD + 4;
+    const CRC_COVERED_POSITION: usize = CRC_POSITION + 4;
+    let mut bytes = records;
+    if bytes.is_empty() {
+        return Err(error::CORRUPT_MESSAGE);
+    }
+    while !bytes.is_empty() {
+        let header = bytes
+            .get(..RecordBatch::HEADER_SIZE)
+            .ok_or(error::CORRUPT_MESSAGE)?;
+        let length = i32::from_be_bytes(header[8..12].try_into().unwrap());
+        let size = RecordBatch::LOG_OVERHEAD + length.max(0) as usize;
+        if size > max_message_bytes.max(0) as usize {
+            return Err(error::MESSAGE_TOO_LARGE);
+        }
+        let batch = bytes
+            .get(..size)
+            .filter(|_| size >= RecordBatch::HEADER_SIZE)
+            .ok_or(error::CORRUPT_MESSAGE)?;
+        let crc = u32::from_be_bytes(
+            batch[CRC_POSITION..CRC_COVERED_POSITION]
+                .try_into()
+                .unwrap(),
+        );
+        if batch[MAGIC_POSITION] != 2 || crc != crc32c(&batch[CRC_COVERED_POSITION..]) {
+            return Err(error::CORRUPT_MESSAGE);
+        }
+        bytes = &bytes[size..];
+    }
+    Ok(records)
+}
+
+#[derive(Debug)]
+pub struct BatchIndexAndErrorMessage {
+    pub batch_index: i32,
+    pub batch_index_error_message: CompactString,
+    pub tag_buffer: TagSection,
+}
+
+impl Serializable for BatchIndexAndErrorMessage {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(self.batch_index.to_be_bytes());
+        buf.extend(self.batch_index_error_message.serialize());
+        buf.extend(self.tag_buffer.serialize());
+        buf
+    }
+
+    fn deserialize(_bytes: &[u8]) -> Result<(Self, &[u8])> {
+        todo!()
+    }
+}
+
+#[derive(Debug)]
+pub struct PartitionProduceResponse {
+    pub index: i32,
+    pub error_code: i16,
+    pub base_offset: i64,
+    /// Append time given to the records, -1 when they keep their create time
+    pub log_append_time_ms: i64,
+    pub log_start_offset: i64,
+    pub record_errors: CompactArray<BatchIndexAndErrorMessage>,
+    pub error_message: CompactString,
+    pub tag_buffer: TagSection,
+}
+
+impl Serializable for PartitionProduceResponse {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(self.index.to_be_bytes());
+        buf.extend(self.error_code.to_be_bytes());
+        buf.extend(self.base_offset.to_be_bytes());
+        buf.extend(self.log_append_time_ms.to_be_bytes());
+        buf.extend(self.log_start_offset.to_be_bytes());
+        buf.extend(self.record_errors.serialize());
+        buf.extend(self.error_message.serialize());
+        buf.extend(self.tag_buffer.serialize());
+        buf
+    }
+
+    fn deserialize(_bytes: &[u8]) -> Result<(Self, &[u8])> {
+        todo!()
+    }
+}
+
+#[derive(Debug)]
+pub struct TopicProduceResponse {
+    pub name: CompactString,
+    pub partition_responses: Vec<PartitionProduceResponse>,
+}
+
+impl Serializable for TopicProduceResponse {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(self.name.serialize());
+        buf.extend(UnsignedVarint(self.partition_responses.len() as u32 + 1).serialize());
+        for partition in &self.partition_responses {
+            buf.extend(partition.serialize());
+        }
+        buf.extend(TagSection(None).serialize());
+        buf
+    }
+
+    fn deserialize(_bytes: &[u8]) -> Result<(Self, &[u8])> {
+        todo!()
+    }
+}
+
+#[derive(Debug)]
+pub struct ProduceResponse {
+    pub responses: Vec<TopicProduceResponse>,
+    pub throttle_time_ms: i32,
+}
+
+impl Serializable for ProduceResponse {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(UnsignedVarint(self.responses.len() as u32 + 1).serialize());
+        for response in &self.responses {
+            buf.extend(response.serialize());
+        }
+        buf.extend(self.throttle_time_ms.to_be_bytes());
+        buf.extend(TagSection(None).serialize());
+        buf
+    }
+
+    fn deserialize(_bytes: &[u8]) -> Result<(Self, &[u8])> {
+        todo!()
+    }
+}
+
+impl ResponseBody for ProduceResponse {
+    fn set_throttle_time_ms(&mut self, throttle_time_ms: i32) {
+        self.throttle_time_ms = throttle_time_ms;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn batch(values: Vec<Vec<u8>>) -> Vec<u8> {
+        RecordBatch::new(0, 0, 0, values).serialize()
+    }
+
+    #[test]
+    fn accepts_consecutive_valid_batches() {
+        let mut records = batch(vec![b"a".to_vec()]);
+        records.extend(batch(vec![b"b".to_vec(), b"c".to_vec()]));
+        assert_eq!(validate_records(Some(&records), i32::MAX), Ok(&records[..]));
+    }
+
+    #[test]
+    fn rejects_missing_or_empty_records() {
+        assert_eq!(
+            validate_records(None, i32::MAX),
+            Err(error::CORRUPT_MESSAGE)
+        );
+        assert_eq!(
+            validate_records(Some(&[]), i32::MAX),
+            Err(error::CORRUPT_MESSAGE)
+        );
+    }
+
+    #[test]
+    fn rejects_batches_shorter_than_the_header() {
+        // A batch whose length and CRC agree but that stops before the record count
+        let mut records = batch(vec![b"a".to_vec()]);
+        records.truncate(RecordBatch::HEADER_SIZE - 1);
+        let length = (records.len() - RecordBatch::LOG_OVERHEAD) as i32;
+        records[8..12].copy_from_slice(&length.to_be_bytes());
+        let crc = crc32c(&records[21..]);
+        records[17..21].copy_from_slice(&crc.to_be_bytes());
+        assert_eq!(
+            validate_records(Some(&records), i32::MAX),
+            Err(error::CORRUPT_MESSAGE)
+        );
+    }
+
+    #[test]
+    fn rejects_truncated_batches() {
+        let records = batch(vec![b"a".to_vec()]);
+        let truncated = &records[..records.len() - 1];
+        assert_eq!(
+            validate_records(Some(truncated), i32::MAX),
+            Err(error::CORRUPT_MESSAGE)
+        );
+    }
+
+    #[test]
+    fn rejects_bad_crc_and_magic() {
+        let mut records = batch(vec![b"a".to_vec()]);
+        let last = records.len() - 1;
+        records[last] ^= 0xff;
+        assert_eq!(
+            validate_records(Some(&records), i32::MAX),
+            Err(error::CORRUPT_MESSAGE)
+        );
+
+        let mut records = batch(vec![b"a".to_vec()]);
+        records[RecordBatch::LOG_OVERHEAD + 4] = 1;
+        assert_eq!(
+            validate_records(Some(&records), i32::MAX),
+            Err(error::CORRUPT_MESSAGE)
+        );
+    }
+
+    #[test]
+    fn rejects_batches_above_max_message_bytes() {
+        let records = batch(vec![vec![0; 100]]);
+        let max = records.len() as i32 - 1;
+        assert_eq!(
+            validate_records(Some(&records), max),
+            Err(error::MESSAGE_TOO_LARGE)
+        );
+        assert!(validate_records(Some(&records), max + 1).is_ok());
+    }
+}
@@ -0,0 +1,126 @@
+use anyhow::Result;
+
+use super::{
+    api_keys::{self, RequestContext, RequestHandler, SASL_AUTHENTICATE_KEY},
+    body::ResponseBody,
+    error,
+    primitive::{CompactBytes, CompactString, KafkaBytes, KafkaString, Serializable, TagSection},
+    response::Response,
+};
+use crate::security::sasl::SaslSession;
+
+#[derive(Debug)]
+pub struct SaslAuthenticateRequest {
+    pub auth_bytes: Vec<u8>,
+    pub tag_buffer: TagSection,
+}
+
+impl SaslAuthenticateRequest {
+    /// Version 2 and above use the compact (flexible) encoding.
+    pub fn deserialize_versioned(bytes: &[u8], version: i16) -> Result<(Self, &[u8])> {
+        if api_keys::is_flexible(SASL_AUTHENTICATE_KEY, version) {
+            let (auth_bytes, bytes) = CompactBytes::deserialize(bytes)?;
+            let (tag_buffer, bytes) = TagSection::deserialize(bytes)?;
+            Ok((
+                SaslAuthenticateRequest {
+                    auth_bytes: auth_bytes.0.unwrap_or_default(),
+                    tag_buffer,
+                },
+                bytes,
+            ))
+        } else {
+            let (auth_bytes, bytes) = KafkaBytes::deserialize(bytes)?;
+            Ok((
+                SaslAuthenticateRequest {
+                    auth_bytes: auth_bytes.0.unwrap_or_default(),
+                    tag_buffer: TagSection(None),
+                },
+                bytes,
+            ))
+        }
+    }
+
+    pub fn handle_request(
+        &self,
+        version: i16,
+        session: Option<&mut SaslSession>,
+    ) -> Option<Response> {
+        let mut response = SaslAuthenticateResponse {
+            version,
+            error_code: error::NONE,
+            error_message: None,
+            auth_bytes: vec![],
+            session_lifetime_ms: 0,
+        };
+        match session {
+            None => {
+                response.error_code = error::ILLEGAL_SASL_STATE;
+                response.error_message = Some("SASL is not enabled on this listener".to_string());
+            }
+            Some(session) => match session.authenticate(&self.auth_bytes) {
+                Ok(auth_bytes) => {
+                    response.auth_bytes = auth_bytes;
+                    response.session_lifetime_ms = session.session_lifetime_ms();
+                }
+                Err(failure) => {
+                    response.error_code = failure.error_code;
+                    response.error_message = Some(failure.message);
+                }
+            },
+        }
+
+        Some(Box::new(response))
+    }
+}
+
+impl RequestHandler for SaslAuthenticateRequest {
+    fn read(bytes: &[u8], version: i16) -> Result<Self> {
+        Ok(SaslAuthenticateRequest::deserialize_versioned(bytes, version)?.0)
+    }
+
+    fn handle(&self, request: &mut RequestContext) -> Option<Response> {
+        let response = self.handle_request(request.api_version, request.sasl.as_mut());
+        // The connection takes on the principal the exchange authenticated
+        if let Some(principal) = request.sasl.as_ref().and_then(SaslSession::principal) {
+            request.connection.principal = principal.clone();
+        }
+        response
+    }
+}
+
+#[derive(Debug)]
+pub struct SaslAuthenticateResponse {
+    pub version: i16,
+    pub error_code: i16,
+    pub error_message: Option<String>,
+    pub auth_bytes: Vec<u8>,
+    pub session_lifetime_ms: i64,
+}
+
+impl Serializable for SaslAuthenticateResponse {
+    fn serialize(&self) -> Vec<u8> {
+        let flexible = api_keys::is_flexible(SASL_AUTHENTICATE_KEY, self.version);
+        let mut buf = Vec::new();
+        buf.extend(self.error_code.to_be_bytes());
+        if flexible {
+            buf.extend(CompactString(self.error_message.clone()).serialize());
+            buf.extend(CompactBytes(Some(self.auth_bytes.clone())).serialize());
+        } else {
+            buf.extend(KafkaString(self.error_message.clone()).serialize());
+            buf.extend(KafkaBytes(Some(self.auth_bytes.clone())).serialize());
+        }
+        if self.version >= 1 {
+            buf.extend(self.session_lifetime_ms.to_be_bytes());
+        }
+        if flexible {
+            buf.extend(TagSection(None).serialize());
+        }
+        buf
+    }
+
+    fn deserialize(_bytes: &[u8]) -> Result<(Self, &[u8])> {
+        todo!()
+    }
+}
+
+impl ResponseBody for SaslAuthenticateResponse {}
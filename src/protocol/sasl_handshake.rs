@@ -0,0 +1,88 @@
+use anyhow::Result;
+
+use super::{
+    api_keys::{RequestContext, RequestHandler},
+    body::ResponseBody,
+    error,
+    primitive::{KafkaArray, KafkaString, Serializable},
+    response::Response,
+};
+use crate::security::sasl::SaslSession;
+
+#[derive(Debug)]
+pub struct SaslHandshakeRequest {
+    pub mechanism: KafkaString,
+}
+
+impl Serializable for SaslHandshakeRequest {
+    fn serialize(&self) -> Vec<u8> {
+        self.mechanism.serialize()
+    }
+
+    fn deserialize(bytes: &[u8]) -> Result<(Self, &[u8])> {
+        let (mechanism, bytes) = KafkaString::deserialize(bytes)?;
+        Ok((SaslHandshakeRequest { mechanism }, bytes))
+    }
+}
+
+impl SaslHandshakeRequest {
+    /// Select the mechanism of the connection. Listeners without SASL reject the request.
+    pub fn handle_request(&self, session: Option<&mut SaslSession>) -> Option<Response> {
+        let (error_code, mechanisms) = match session {
+            Some(session) => (
+                session.handshake(self.mechanism.as_deref().unwrap_or_default()),
+                session.enabled_mechanisms(),
+            ),
+            None => (error::ILLEGAL_SASL_STATE, vec![]),
+        };
+
+        Some(Box::new(SaslHandshakeResponse {
+            error_code,
+            mechanisms: KafkaArray(Some(
+                mechanisms
+                    .into_iter()
+                    .map(|m| KafkaString(Some(m)))
+                    .collect(),
+            )),
+        }))
+    }
+}
+
+impl RequestHandler for SaslHandshakeRequest {
+    fn read(bytes: &[u8], _version: i16) -> Result<Self> {
+        Ok(SaslHandshakeRequest::deserialize(bytes)?.0)
+    }
+
+    fn handle(&self, request: &mut RequestContext) -> Option<Response> {
+        self.handle_request(request.sasl.as_mut())
+    }
+}
+
+#[derive(Debug)]
+pub struct SaslHandshakeResponse {
+    pub error_code: i16,
+    pub mechanisms: KafkaArray<KafkaString>,
+}
+
+impl Serializable for SaslHandshakeResponse {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(self.error_code.to_be_bytes());
+        buf.extend(self.mechanisms.serialize());
+        buf
+    }
+
+    fn deserialize(bytes: &[u8]) -> Result<(Self, &[u8])> {
+        let (error_code, bytes) = i16::deserialize(bytes)?;
+        let (mechanisms, bytes) = KafkaArray::<KafkaString>::deserialize(bytes)?;
+        Ok((
+            SaslHandshakeResponse {
+                error_code,
+                mechanisms,
+            },
+            bytes,
+        ))
+    }
+}
+
+impl ResponseBody for SaslHandshakeResponse {}
@@ -0,0 +1,299 @@
+use std::collections::BTreeMap;
+
+use anyhow::Result;
+use uuid::Uuid;
+
+use super::{
+    api_keys::{RequestContext, RequestHandler},
+    body::ResponseBody,
+    error,
+    fetch::LeaderIdAndEpoch,
+    primitive::{CompactArray, CompactString, Serializable, TagSection, UnsignedVarint},
+    response::Response,
+    share_fetch::{self, AcknowledgementBatch, NodeEndpoint},
+};
+use crate::{
+    broker::Broker,
+    security::acl::{AclOperation, ResourceType},
+    server::ConnectionContext,
+    share_partition::Acknowledgement,
+};
+
+#[derive(Debug)]
+pub struct AcknowledgePartition {
+    pub partition_index: i32,
+    pub acknowledgement_batches: CompactArray<AcknowledgementBatch>,
+    pub tag_buffer: TagSection,
+}
+
+impl Serializable for AcknowledgePartition {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(self.partition_index.to_be_bytes());
+        buf.extend(self.acknowledgement_batches.serialize());
+        buf.extend(self.tag_buffer.serialize());
+        buf
+    }
+
+    fn deserialize(bytes: &[u8]) -> Result<(Self, &[u8])> {
+        let (partition_index, bytes) = i32::deserialize(bytes)?;
+        let (acknowledgement_batches, bytes) = CompactArray::deserialize(bytes)?;
+        let (tag_buffer, bytes) = TagSection::deserialize(bytes)?;
+        Ok((
+            AcknowledgePartition {
+                partition_index,
+                acknowledgement_batches,
+                tag_buffer,
+            },
+            bytes,
+        ))
+    }
+}
+
+#[derive(Debug)]
+pub struct AcknowledgeTopic {
+    pub topic_id: Uuid,
+    pub partitions: CompactArray<AcknowledgePartition>,
+    pub tag_buffer: TagSection,
+}
+
+impl Serializable for AcknowledgeTopic {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(self.topic_id.serialize());
+        buf.extend(self.partitions.serialize());
+        buf.extend(self.tag_buffer.serialize());
+        buf
+    }
+
+    fn deserialize(bytes: &[u8]) -> Result<(Self, &[u8])> {
+        let (topic_id, bytes) = Uuid::deserialize(bytes)?;
+        let (partitions, bytes) = CompactArray::deserialize(bytes)?;
+        let (tag_buffer, bytes) = TagSection::deserialize(bytes)?;
+        Ok((
+            AcknowledgeTopic {
+                topic_id,
+                partitions,
+                tag_buffer,
+            },
+            bytes,
+        ))
+    }
+}
+
+/// ShareAcknowledge versions 0 and 1, acknowledging records a member of a share group
+/// acquired without fetching more. Epoch -1 closes the share session afterwards.
+#[derive(Debug)]
+pub struct ShareAcknowledgeRequest {
+    pub group_id: CompactString,
+    pub member_id: CompactString,
+    pub share_session_epoch: i32,
+    pub topics: CompactArray<AcknowledgeTopic>,
+    pub tag_buffer: TagSection,
+}
+
+impl Serializable for ShareAcknowledgeRequest {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(self.group_id.serialize());
+        buf.extend(self.member_id.serialize());
+        buf.extend(self.share_session_epoch.to_be_bytes());
+        buf.extend(self.topics.serialize());
+        buf.extend(self.tag_buffer.serialize());
+        buf
+    }
+
+    fn deserialize(bytes: &[u8]) -> Result<(Self, &[u8])> {
+        let (group_id, bytes) = CompactString::deserialize(bytes)?;
+        let (member_id, bytes) = CompactString::deserialize(bytes)?;
+        let (share_session_epoch, bytes) = i32::deserialize(bytes)?;
+        let (topics, bytes) = CompactArray::deserialize(bytes)?;
+        let (tag_buffer, bytes) = TagSection::deserialize(bytes)?;
+        Ok((
+            ShareAcknowledgeRequest {
+                group_id,
+                member_id,
+                share_session_epoch,
+                topics,
+                tag_buffer,
+            },
+            bytes,
+        ))
+    }
+}
+
+impl ShareAcknowledgeRequest {
+    pub fn handle_request(&self, broker: &Broker, context: &ConnectionContext) -> Option<Response> {
+        let mut response = ShareAcknowledgeResponse {
+            throttle_time_ms: 0,
+            error_code: error::NONE,
+            error_message: CompactString(None),
+            responses: vec![],
+            node_endpoints: vec![],
+        };
+        let group_id = self.group_id.0.clone().unwrap_or_default();
+        let member_id = self.member_id.0.clone().unwrap_or_default();
+
+        let error =
+            if !broker.authorize(context, AclOperation::Read, ResourceType::Group, &group_id) {
+                Some((error::GROUP_AUTHORIZATION_FAILED, None))
+            } else if group_id.is_empty() || member_id.is_empty() {
+                Some((
+                    error::INVALID_REQUEST,
+                    Some("GroupId and MemberId can't be empty.".to_string()),
+                ))
+            } else if self.share_session_epoch == 0 {
+                // There is nothing to acknowledge before the session fetched
+                Some((error::INVALID_SHARE_SESSION_EPOCH, None))
+            } else {
+                None
+            };
+        // Acknowledging moves the session to its next epoch, or closes it
+        let session = match error {
+            Some(error) => Err(error),
+            None => broker
+                .share_partitions
+                .update_session(&group_id, &member_id, self.share_session_epoch, &[], &[])
+                .map_err(|error_code| (error_code, None)),
+        };
+        let session = match session {
+            Ok(session) => session,
+            Err((error_code, error_message)) => {
+                response.error_code = error_code;
+                response.error_message = CompactString(error_message);
+                return Some(Box::new(response));
+            }
+        };
+
+        let mut responses: BTreeMap<Uuid, Vec<AcknowledgePartitionResponse>> = BTreeMap::new();
+        for topic in self.topics.iter().flatten() {
+            let topic_error = share_fetch::topic_error(broker, context, topic.topic_id);
+            for partition in topic.partitions.iter().flatten() {
+                let batches: Vec<Acknowledgement> = partition
+                    .acknowledgement_batches
+                    .iter()
+                    .flatten()
+                    .map(AcknowledgementBatch::acknowledgement)
+                    .collect();
+                let error_code = match topic_error {
+                    Some(error_code) => error_code,
+                    None => share_fetch::acknowledge(
+                        broker,
+                        &group_id,
+                        &member_id,
+                        topic.topic_id,
+                        partition.partition_index,
+                        &batches,
+                    ),
+                };
+                let current_leader = if error_code == error::NOT_LEADER_OR_FOLLOWER {
+                    share_fetch::current_leader(broker, topic.topic_id, partition.partition_index)
+                } else {
+                    LeaderIdAndEpoch {
+                        leader_id: -1,
+                        leader_epoch: -1,
+                    }
+                };
+                responses
+                    .entry(topic.topic_id)
+                    .or_default()
+                    .push(AcknowledgePartitionResponse {
+                        partition_index: partition.partition_index,
+                        error_code,
+                        current_leader,
+                    });
+            }
+        }
+        if self.share_session_epoch == -1 {
+            broker
+                .share_partitions
+                .release_member(broker, &group_id, &member_id, &session);
+        }
+
+        let leaders = responses
+            .values()
+            .flatten()
+            .map(|partition| partition.current_leader.leader_id);
+        response.node_endpoints = share_fetch::node_endpoints(broker, context, leaders);
+        response.responses = responses.into_iter().collect();
+
+        Some(Box::new(response))
+    }
+}
+
+impl RequestHandler for ShareAcknowledgeRequest {
+    fn read(bytes: &[u8], _version: i16) -> Result<Self> {
+        Ok(ShareAcknowledgeRequest::deserialize(bytes)?.0)
+    }
+
+    fn handle(&self, request: &mut RequestContext) -> Option<Response> {
+        self.handle_request(request.broker, request.connection)
+    }
+}
+
+#[derive(Debug)]
+pub struct AcknowledgePartitionResponse {
+    pub partition_index: i32,
+    pub error_code: i16,
+    pub current_leader: LeaderIdAndEpoch,
+}
+
+impl Serializable for AcknowledgePartitionResponse {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(self.partition_index.to_be_bytes());
+        buf.extend(self.error_code.to_be_bytes());
+        buf.extend(CompactString(None).serialize());
+        buf.extend(self.current_leader.serialize());
+        buf.extend(TagSection(None).serialize());
+        buf
+    }
+
+    fn deserialize(_bytes: &[u8]) -> Result<(Self, &[u8])> {
+        todo!()
+    }
+}
+
+#[derive(Debug)]
+pub struct ShareAcknowledgeResponse {
+    pub throttle_time_ms: i32,
+    pub error_code: i16,
+    pub error_message: CompactString,
+    /// Partitions of each topic, by topic id
+    pub responses: Vec<(Uuid, Vec<AcknowledgePartitionResponse>)>,
+    pub node_endpoints: Vec<NodeEndpoint>,
+}
+
+impl Serializable for ShareAcknowledgeResponse {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(self.throttle_time_ms.to_be_bytes());
+        buf.extend(self.error_code.to_be_bytes());
+        buf.extend(self.error_message.serialize());
+        buf.extend(UnsignedVarint(self.responses.len() as u32 + 1).serialize());
+        for (topic_id, partitions) in &self.responses {
+            buf.extend(topic_id.serialize());
+            buf.extend(UnsignedVarint(partitions.len() as u32 + 1).serialize());
+            for partition in partitions {
+                buf.extend(partition.serialize());
+            }
+            buf.extend(TagSection(None).serialize());
+        }
+        buf.extend(UnsignedVarint(self.node_endpoints.len() as u32 + 1).serialize());
+        for endpoint in &self.node_endpoints {
+            buf.extend(endpoint.serialize());
+        }
+        buf.extend(TagSection(None).serialize());
+        buf
+    }
+
+    fn deserialize(_bytes: &[u8]) -> Result<(Self, &[u8])> {
+        todo!()
+    }
+}
+
+impl ResponseBody for ShareAcknowledgeResponse {
+    fn set_throttle_time_ms(&mut self, throttle_time_ms: i32) {
+        self.throttle_time_ms = throttle_time_ms;
+    }
+}
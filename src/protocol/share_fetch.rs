@@ -0,0 +1,723 @@
+use std::{
+    collections::BTreeMap,
+    time::{Duration, Instant},
+};
+
+use anyhow::Result;
+use uuid::Uuid;
+
+use super::{
+    api_keys::{RequestContext, RequestHandler},
+    body::ResponseBody,
+    error,
+    fetch::LeaderIdAndEpoch,
+    primitive::{
+        CompactArray, CompactBytes, CompactString, Serializable, TagSection, UnsignedVarint,
+    },
+    response::Response,
+};
+use crate::{
+    broker::Broker,
+    security::acl::{AclOperation, ResourceType},
+    server::ConnectionContext,
+    share_partition::{Acknowledgement, SharePartitionKey},
+};
+
+#[derive(Debug)]
+pub struct AcknowledgementBatch {
+    pub first_offset: i64,
+    pub last_offset: i64,
+    /// One type for the whole batch, or one per offset
+    pub acknowledge_types: CompactArray<i8>,
+    pub tag_buffer: TagSection,
+}
+
+impl Serializable for AcknowledgementBatch {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(self.first_offset.to_be_bytes());
+        buf.extend(self.last_offset.to_be_bytes());
+        buf.extend(self.acknowledge_types.serialize());
+        buf.extend(self.tag_buffer.serialize());
+        buf
+    }
+
+    fn deserialize(bytes: &[u8]) -> Result<(Self, &[u8])> {
+        let (first_offset, bytes) = i64::deserialize(bytes)?;
+        let (last_offset, bytes) = i64::deserialize(bytes)?;
+        let (acknowledge_types, bytes) = CompactArray::<i8>::deserialize(bytes)?;
+        let (tag_buffer, bytes) = TagSection::deserialize(bytes)?;
+        Ok((
+            AcknowledgementBatch {
+                first_offset,
+                last_offset,
+                acknowledge_types,
+                tag_buffer,
+            },
+            bytes,
+        ))
+    }
+}
+
+impl AcknowledgementBatch {
+    pub fn acknowledgement(&self) -> Acknowledgement {
+        Acknowledgement {
+            first_offset: self.first_offset,
+            last_offset: self.last_offset,
+            acknowledge_types: self.acknowledge_types.0.clone().unwrap_or_default(),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct ShareFetchPartition {
+    pub partition_index: i32,
+    /// Version 0 only
+    pub partition_max_bytes: i32,
+    pub acknowledgement_batches: CompactArray<AcknowledgementBatch>,
+    pub tag_buffer: TagSection,
+}
+
+#[derive(Debug)]
+pub struct ShareFetchTopic {
+    pub topic_id: Uuid,
+    pub partitions: Vec<ShareFetchPartition>,
+    pub tag_buffer: TagSection,
+}
+
+#[derive(Debug)]
+pub struct ForgottenTopic {
+    pub topic_id: Uuid,
+    pub partitions: CompactArray<i32>,
+    pub tag_buffer: TagSection,
+}
+
+impl Serializable for ForgottenTopic {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(self.topic_id.serialize());
+        buf.extend(self.partitions.serialize());
+        buf.extend(self.tag_buffer.serialize());
+        buf
+    }
+
+    fn deserialize(bytes: &[u8]) -> Result<(Self, &[u8])> {
+        let (topic_id, bytes) = Uuid::deserialize(bytes)?;
+        let (partitions, bytes) = CompactArray::<i32>::deserialize(bytes)?;
+        let (tag_buffer, bytes) = TagSection::deserialize(bytes)?;
+        Ok((
+            ForgottenTopic {
+                topic_id,
+                partitions,
+                tag_buffer,
+            },
+            bytes,
+        ))
+    }
+}
+
+/// ShareFetch versions 0 and 1, acquiring records of share partitions for a member of a
+/// share group and acknowledging those it acquired before. Version 1 bounds the
+/// records acquired instead of the bytes of each partition.
+#[derive(Debug)]
+pub struct ShareFetchRequest {
+    pub group_id: CompactString,
+    pub member_id: CompactString,
+    /// 0 opens a share session, -1 closes it
+    pub share_session_epoch: i32,
+    pub max_wait_ms: i32,
+    pub min_bytes: i32,
+    pub max_bytes: i32,
+    /// Version 1 and above
+    pub max_records: i32,
+    /// Version 1 and above, a hint the broker does not use
+    pub batch_size: i32,
+    pub topics: Vec<ShareFetchTopic>,
+    pub forgotten_topics_data: CompactArray<ForgottenTopic>,
+    pub tag_buffer: TagSection,
+}
+
+impl ShareFetchRequest {
+    pub fn deserialize_versioned(bytes: &[u8], version: i16) -> Result<(Self, &[u8])> {
+        let (group_id, bytes) = CompactString::deserialize(bytes)?;
+        let (member_id, bytes) = CompactString::deserialize(bytes)?;
+        let (share_session_epoch, bytes) = i32::deserialize(bytes)?;
+        let (max_wait_ms, bytes) = i32::deserialize(bytes)?;
+        let (min_bytes, bytes) = i32::deserialize(bytes)?;
+        let (max_bytes, bytes) = i32::deserialize(bytes)?;
+        let (max_records, bytes) = if version >= 1 {
+            i32::deserialize(bytes)?
+        } else {
+            (i32::MAX, bytes)
+        };
+        let (batch_size, bytes) = if version >= 1 {
+            i32::deserialize(bytes)?
+        } else {
+            (i32::MAX, bytes)
+        };
+        let (len, mut bytes) = UnsignedVarint::deserialize(bytes)?;
+        let mut topics = vec![];
+        for _ in 1..len.0 {
+            let (topic_id, rest) = Uuid::deserialize(bytes)?;
+            let (len, mut rest) = UnsignedVarint::deserialize(rest)?;
+            let mut partitions = vec![];
+            for _ in 1..len.0 {
+                let (partition_index, more) = i32::deserialize(rest)?;
+                let (partition_max_bytes, more) = if version == 0 {
+                    i32::deserialize(more)?
+                } else {
+                    (i32::MAX, more)
+                };
+                let (acknowledgement_batches, more) = CompactArray::deserialize(more)?;
+                let (tag_buffer, more) = TagSection::deserialize(more)?;
+                partitions.push(ShareFetchPartition {
+                    partition_index,
+                    partition_max_bytes,
+                    acknowledgement_batches,
+                    tag_buffer,
+                });
+                rest = more;
+            }
+            let (tag_buffer, rest) = TagSection::deserialize(rest)?;
+            topics.push(ShareFetchTopic {
+                topic_id,
+                partitions,
+                tag_buffer,
+            });
+            bytes = rest;
+        }
+        let (forgotten_topics_data, bytes) = CompactArray::deserialize(bytes)?;
+        let (tag_buffer, bytes) = TagSection::deserialize(bytes)?;
+        Ok((
+            ShareFetchRequest {
+                group_id,
+                member_id,
+                share_session_epoch,
+                max_wait_ms,
+                min_bytes,
+                max_bytes,
+                max_records,
+                batch_size,
+                topics,
+                forgotten_topics_data,
+                tag_buffer,
+            },
+            bytes,
+        ))
+    }
+
+    /// Acknowledgements are applied first, then records are acquired from every
+    /// partition of the share session until the response holds `min_bytes` or the wait
+    /// times out. A full fetch (epoch 0) answers for each partition requested, while an
+    /// incremental one only for those with records, errors or acknowledgements.
+    pub fn handle_request(
+        &self,
+        version: i16,
+        broker: &Broker,
+        context: &ConnectionContext,
+    ) -> Option<Response> {
+        let mut response = ShareFetchResponse {
+            version,
+            throttle_time_ms: 0,
+            error_code: error::NONE,
+            error_message: CompactString(None),
+            acquisition_lock_timeout_ms: broker.config.share_group.record_lock_duration_ms as i32,
+            responses: vec![],
+            node_endpoints: vec![],
+        };
+        let group_id = self.group_id.0.clone().unwrap_or_default();
+        let member_id = self.member_id.0.clone().unwrap_or_default();
+        let acknowledging =
+            self.topics
+                .iter()
+                .flat_map(|topic| &topic.partitions)
+                .any(|partition| {
+                    partition
+                        .acknowledgement_batches
+                        .iter()
+                        .flatten()
+                        .next()
+                        .is_some()
+                });
+
+        let error =
+            if !broker.authorize(context, AclOperation::Read, ResourceType::Group, &group_id) {
+                Some((error::GROUP_AUTHORIZATION_FAILED, None))
+            } else if group_id.is_empty() || member_id.is_empty() {
+                Some((
+                    error::INVALID_REQUEST,
+                    Some("GroupId and MemberId can't be empty.".to_string()),
+                ))
+            } else if self.share_session_epoch == 0 && acknowledging {
+                Some((
+                    error::INVALID_REQUEST,
+                    Some(
+                        "Acknowledgements are not allowed when opening a share session."
+                            .to_string(),
+                    ),
+                ))
+            } else {
+                None
+            };
+        if let Some((error_code, error_message)) = error {
+            response.error_code = error_code;
+            response.error_message = CompactString(error_message);
+            return Some(Box::new(response));
+        }
+
+        let requested: Vec<(Uuid, i32)> = self
+            .topics
+            .iter()
+            .flat_map(|topic| {
+                topic
+                    .partitions
+                    .iter()
+                    .map(|partition| (topic.topic_id, partition.partition_index))
+            })
+            .collect();
+        let forgotten: Vec<(Uuid, i32)> = self
+            .forgotten_topics_data
+            .iter()
+            .flatten()
+            .flat_map(|topic| {
+                topic
+                    .partitions
+                    .iter()
+                    .flatten()
+                    .map(|&partition| (topic.topic_id, partition))
+            })
+            .collect();
+        let session = match broker.share_partitions.update_session(
+            &group_id,
+            &member_id,
+            self.share_session_epoch,
+            &requested,
+            &forgotten,
+        ) {
+            Ok(session) => session,
+            Err(error_code) => {
+                response.error_code = error_code;
+                return Some(Box::new(response));
+            }
+        };
+
+        let mut partitions: BTreeMap<(Uuid, i32), ShareFetchPartitionData> = BTreeMap::new();
+        for topic in &self.topics {
+            let topic_error = topic_error(broker, context, topic.topic_id);
+            for partition in &topic.partitions {
+                let mut data = ShareFetchPartitionData::new(partition.partition_index);
+                let batches: Vec<Acknowledgement> = partition
+                    .acknowledgement_batches
+                    .iter()
+                    .flatten()
+                    .map(AcknowledgementBatch::acknowledgement)
+                    .collect();
+                if !batches.is_empty() {
+                    data.acknowledge_error_code = match topic_error {
+                        Some(error_code) => error_code,
+                        None => acknowledge(
+                            broker,
+                            &group_id,
+                            &member_id,
+                            topic.topic_id,
+                            partition.partition_index,
+                            &batches,
+                        ),
+                    };
+                }
+                partitions.insert((topic.topic_id, partition.partition_index), data);
+            }
+        }
+
+        if self.share_session_epoch == -1 {
+            // Records the member still holds go to other members
+            broker
+                .share_partitions
+                .release_member(broker, &group_id, &member_id, &session);
+        } else {
+            let deadline = Instant::now() + Duration::from_millis(self.max_wait_ms.max(0) as u64);
+            self.acquire(
+                broker,
+                context,
+                &group_id,
+                &member_id,
+                &session,
+                &mut partitions,
+                deadline,
+            );
+        }
+
+        let full = self.share_session_epoch == 0;
+        let mut responses: BTreeMap<Uuid, Vec<ShareFetchPartitionData>> = BTreeMap::new();
+        for ((topic_id, _), mut data) in partitions {
+            let report = full
+                || requested.contains(&(topic_id, data.partition_index))
+                || data.error_code != error::NONE
+                || !data.acquired_records.is_empty();
+            if !report {
+                continue;
+            }
+            if data.error_code == error::NOT_LEADER_OR_FOLLOWER
+                || data.acknowledge_error_code == error::NOT_LEADER_OR_FOLLOWER
+            {
+                data.current_leader = current_leader(broker, topic_id, data.partition_index);
+            }
+            responses.entry(topic_id).or_default().push(data);
+        }
+        let leaders = responses
+            .values()
+            .flatten()
+            .map(|data| data.current_leader.leader_id);
+        response.node_endpoints = node_endpoints(broker, context, leaders);
+        response.responses = responses
+            .into_iter()
+            .map(|(topic_id, partitions)| ShareFetchTopicResponse {
+                topic_id,
+                partitions,
+            })
+            .collect();
+
+        Some(Box::new(response))
+    }
+
+    /// Acquire records from the partitions of the share session, waiting for more until
+    /// `min_bytes` are acquired, a partition fails or the deadline passes.
+    #[allow(clippy::too_many_arguments)]
+    fn acquire(
+        &self,
+        broker: &Broker,
+        context: &ConnectionContext,
+        group_id: &str,
+        member_id: &str,
+        session: &[(Uuid, i32)],
+        partitions: &mut BTreeMap<(Uuid, i32), ShareFetchPartitionData>,
+        deadline: Instant,
+    ) {
+        let partition_max_bytes: BTreeMap<(Uuid, i32), usize> = self
+            .topics
+            .iter()
+            .flat_map(|topic| {
+                topic.partitions.iter().map(|partition| {
+                    let key = (topic.topic_id, partition.partition_index);
+                    (key, partition.partition_max_bytes.max(0) as usize)
+                })
+            })
+            .collect();
+        let mut remaining_bytes = self.max_bytes.max(0) as usize;
+        let mut remaining_records = self.max_records.max(0) as usize;
+        let min_bytes = self.min_bytes.max(1) as usize;
+        let mut acquired_bytes = 0;
+        loop {
+            let progress = broker.replicas.progress();
+            let mut failed = false;
+            let mut topic_errors = BTreeMap::new();
+            for &(topic_id, partition_index) in session {
+                if remaining_bytes == 0 || remaining_records == 0 {
+                    break;
+                }
+                let data = partitions
+                    .entry((topic_id, partition_index))
+                    .or_insert_with(|| ShareFetchPartitionData::new(partition_index));
+                let topic_error = *topic_errors
+                    .entry(topic_id)
+                    .or_insert_with(|| topic_error(broker, context, topic_id));
+                let key = SharePartitionKey {
+                    group_id: group_id.to_string(),
+                    topic_id,
+                    partition: partition_index,
+                };
+                let max_bytes = partition_max_bytes
+                    .get(&(topic_id, partition_index))
+                    .map_or(remaining_bytes, |&max| max.min(remaining_bytes));
+                let acquisition = match topic_error {
+                    Some(error_code) => Err(error_code),
+                    None => broker.share_partitions.acquire(
+                        broker,
+                        &key,
+                        member_id,
+                        remaining_records,
+                        max_bytes,
+                    ),
+                };
+                match acquisition {
+                    Ok(acquisition) => {
+                        let records: i64 = acquisition
+                            .acquired
+                            .iter()
+                            .map(|range| range.last_offset - range.first_offset + 1)
+                            .sum();
+                        remaining_records = remaining_records.saturating_sub(records as usize);
+                        remaining_bytes = remaining_bytes.saturating_sub(acquisition.records.len());
+                        acquired_bytes += acquisition.records.len();
+                        data.records.extend(acquisition.records);
+                        data.acquired_records
+                            .extend(acquisition.acquired.into_iter().map(|range| {
+                                AcquiredRecords {
+                                    first_offset: range.first_offset,
+                                    last_offset: range.last_offset,
+                                    delivery_count: range.delivery_count,
+                                }
+                            }));
+                    }
+                    Err(error_code) => {
+                        data.error_code = error_code;
+                        failed = true;
+                    }
+                }
+            }
+            if acquired_bytes >= min_bytes
+                || failed
+                || remaining_bytes == 0
+                || remaining_records == 0
+                || !broker.replicas.wait_for_progress(progress, deadline)
+            {
+                break;
+            }
+        }
+    }
+}
+
+impl RequestHandler for ShareFetchRequest {
+    fn read(bytes: &[u8], version: i16) -> Result<Self> {
+        Ok(ShareFetchRequest::deserialize_versioned(bytes, version)?.0)
+    }
+
+    fn handle(&self, request: &mut RequestContext) -> Option<Response> {
+        self.handle_request(request.api_version, request.broker, request.connection)
+    }
+}
+
+/// Error of the partitions of a topic the connection may not read or that is unknown.
+pub fn topic_error(broker: &Broker, context: &ConnectionContext, topic_id: Uuid) -> Option<i16> {
+    let name = broker
+        .metadata
+        .read()
+        .unwrap()
+        .topic_name(topic_id)
+        .map(str::to_string);
+    match name {
+        None => Some(error::UNKNOWN_TOPIC_ID),
+        Some(name)
+            if !broker.authorize(context, AclOperation::Read, ResourceType::Topic, &name) =>
+        {
+            Some(error::TOPIC_AUTHORIZATION_FAILED)
+        }
+        Some(_) => None,
+    }
+}
+
+/// Apply the acknowledgements of a member to a share partition.
+pub fn acknowledge(
+    broker: &Broker,
+    group_id: &str,
+    member_id: &str,
+    topic_id: Uuid,
+    partition: i32,
+    acknowledgements: &[Acknowledgement],
+) -> i16 {
+    let key = SharePartitionKey {
+        group_id: group_id.to_string(),
+        topic_id,
+        partition,
+    };
+    match broker
+        .share_partitions
+        .acknowledge(broker, &key, member_id, acknowledgements)
+    {
+        Ok(()) => error::NONE,
+        Err(error_code) => error_code,
+    }
+}
+
+/// Leader a client sent away with NOT_LEADER_OR_FOLLOWER should turn to.
+pub fn current_leader(broker: &Broker, topic_id: Uuid, partition: i32) -> LeaderIdAndEpoch {
+    let image = broker.metadata.read().unwrap();
+    image
+        .topic_name(topic_id)
+        .and_then(|name| image.partition(name, partition))
+        .map_or(
+            LeaderIdAndEpoch {
+                leader_id: -1,
+                leader_epoch: -1,
+            },
+            |(_, registration)| LeaderIdAndEpoch {
+                leader_id: registration.leader,
+                leader_epoch: registration.leader_epoch,
+            },
+        )
+}
+
+/// Endpoints of the leaders, on the listener the client came in through.
+pub fn node_endpoints(
+    broker: &Broker,
+    context: &ConnectionContext,
+    leaders: impl Iterator<Item = i32>,
+) -> Vec<NodeEndpoint> {
+    let image = broker.metadata.read().unwrap();
+    let mut leaders: Vec<i32> = leaders.filter(|&leader| leader >= 0).collect();
+    leaders.sort();
+    leaders.dedup();
+    leaders
+        .into_iter()
+        .filter_map(|leader| {
+            let registration = image.brokers.get(&leader)?;
+            let endpoint = registration
+                .endpoints
+                .iter()
+                .find(|endpoint| endpoint.name == context.listener_name)?;
+            Some(NodeEndpoint {
+                node_id: leader,
+                host: endpoint.host.clone(),
+                port: endpoint.port as i32,
+                rack: registration.rack.clone(),
+            })
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone)]
+pub struct AcquiredRecords {
+    pub first_offset: i64,
+    pub last_offset: i64,
+    pub delivery_count: i16,
+}
+
+impl Serializable for AcquiredRecords {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(self.first_offset.to_be_bytes());
+        buf.extend(self.last_offset.to_be_bytes());
+        buf.extend(self.delivery_count.to_be_bytes());
+        buf.extend(TagSection(None).serialize());
+        buf
+    }
+
+    fn deserialize(_bytes: &[u8]) -> Result<(Self, &[u8])> {
+        todo!()
+    }
+}
+
+#[derive(Debug)]
+pub struct ShareFetchPartitionData {
+    pub partition_index: i32,
+    pub error_code: i16,
+    pub acknowledge_error_code: i16,
+    pub current_leader: LeaderIdAndEpoch,
+    pub records: Vec<u8>,
+    pub acquired_records: Vec<AcquiredRecords>,
+}
+
+impl ShareFetchPartitionData {
+    fn new(partition_index: i32) -> Self {
+        ShareFetchPartitionData {
+            partition_index,
+            error_code: error::NONE,
+            acknowledge_error_code: error::NONE,
+            current_leader: LeaderIdAndEpoch {
+                leader_id: -1,
+                leader_epoch: -1,
+            },
+            records: vec![],
+            acquired_records: vec![],
+        }
+    }
+}
+
+impl Serializable for ShareFetchPartitionData {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(self.partition_index.to_be_bytes());
+        buf.extend(self.error_code.to_be_bytes());
+        buf.extend(CompactString(None).serialize());
+        buf.extend(self.acknowledge_error_code.to_be_bytes());
+        buf.extend(CompactString(None).serialize());
+        buf.extend(self.current_leader.serialize());
+        buf.extend(CompactBytes(Some(self.records.clone())).serialize());
+        buf.extend(CompactArray(Some(self.acquired_records.clone())).serialize());
+        buf.extend(TagSection(None).serialize());
+        buf
+    }
+
+    fn deserialize(_bytes: &[u8]) -> Result<(Self, &[u8])> {
+        todo!()
+    }
+}
+
+#[derive(Debug)]
+pub struct ShareFetchTopicResponse {
+    pub topic_id: Uuid,
+    pub partitions: Vec<ShareFetchPartitionData>,
+}
+
+#[derive(Debug)]
+pub struct NodeEndpoint {
+    pub node_id: i32,
+    pub host: String,
+    pub port: i32,
+    pub rack: Option<String>,
+}
+
+impl Serializable for NodeEndpoint {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(self.node_id.to_be_bytes());
+        buf.extend(CompactString(Some(self.host.clone())).serialize());
+        buf.extend(self.port.to_be_bytes());
+        buf.extend(CompactString(self.rack.clone()).serialize());
+        buf.extend(TagSection(None).serialize());
+        buf
+    }
+
+    fn deserialize(_bytes: &[u8]) -> Result<(Self, &[u8])> {
+        todo!()
+    }
+}
+
+#[derive(Debug)]
+pub struct ShareFetchResponse {
+    pub version: i16,
+    pub throttle_time_ms: i32,
+    pub error_code: i16,
+    pub error_message: CompactString,
+    /// Version 1 and above
+    pub acquisition_lock_timeout_ms: i32,
+    pub responses: Vec<ShareFetchTopicResponse>,
+    pub node_endpoints: Vec<NodeEndpoint>,
+}
+
+impl Serializable for ShareFetchResponse {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(self.throttle_time_ms.to_be_bytes());
+        buf.extend(self.error_code.to_be_bytes());
+        buf.extend(self.error_message.serialize());
+        if self.version >= 1 {
+            buf.extend(self.acquisition_lock_timeout_ms.to_be_bytes());
+        }
+        buf.extend(UnsignedVarint(self.responses.len() as u32 + 1).serialize());
+        for topic in &self.responses {
+            buf.extend(topic.topic_id.serialize());
+            buf.extend(UnsignedVarint(topic.partitions.len() as u32 + 1).serialize());
+            for partition in &topic.partitions {
+                buf.extend(partition.serialize());
+            }
+            buf.extend(TagSection(None).serialize());
+        }
+        buf.extend(UnsignedVarint(self.node_endpoints.len() as u32 + 1).serialize());
+        for endpoint in &self.node_endpoints {
+            buf.extend(endpoint.serialize());
+        }
+        buf.extend(TagSection(None).serialize());
+        buf
+    }
+
+    fn deserialize(_bytes: &[u8]) -> Result<(Self, &[u8])> {
+        todo!()
+    }
+}
+
+impl ResponseBody for ShareFetchResponse {
+    fn set_throttle_time_ms(&mut self, throttle_time_ms: i32) {
+        self.throttle_time_ms = throttle_time_ms;
+    }
+}
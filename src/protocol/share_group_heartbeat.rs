@@ -0,0 +1,246 @@
+use anyhow::Result;
+use uuid::Uuid;
+
+use super::{
+    api_keys::{RequestContext, RequestHandler},
+    body::ResponseBody,
+    error,
+    primitive::{CompactArray, CompactString, Serializable, TagSection},
+    response::Response,
+};
+use crate::{
+    broker::Broker,
+    security::acl::{AclOperation, ResourceType},
+    server::ConnectionContext,
+    share_group::{self, Assignment},
+};
+
+/// ShareGroupHeartbeat versions 0 and 1, joining, staying in or leaving a share group
+/// and getting the partitions to consume.
+#[derive(Debug)]
+pub struct ShareGroupHeartbeatRequest {
+    pub group_id: CompactString,
+    pub member_id: CompactString,
+    /// 0 to join the group, -1 to leave it
+    pub member_epoch: i32,
+    /// Not used for assignment
+    pub rack_id: CompactString,
+    /// Null when unchanged since the previous heartbeat
+    pub subscribed_topic_names: CompactArray<CompactString>,
+    pub tag_buffer: TagSection,
+}
+
+impl Serializable for ShareGroupHeartbeatRequest {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(self.group_id.serialize());
+        buf.extend(self.member_id.serialize());
+        buf.extend(self.member_epoch.to_be_bytes());
+        buf.extend(self.rack_id.serialize());
+        buf.extend(self.subscribed_topic_names.serialize());
+        buf.extend(self.tag_buffer.serialize());
+        buf
+    }
+
+    fn deserialize(bytes: &[u8]) -> Result<(Self, &[u8])> {
+        let (group_id, bytes) = CompactString::deserialize(bytes)?;
+        let (member_id, bytes) = CompactString::deserialize(bytes)?;
+        let (member_epoch, bytes) = i32::deserialize(bytes)?;
+        let (rack_id, bytes) = CompactString::deserialize(bytes)?;
+        let (subscribed_topic_names, bytes) = CompactArray::deserialize(bytes)?;
+        let (tag_buffer, bytes) = TagSection::deserialize(bytes)?;
+        Ok((
+            ShareGroupHeartbeatRequest {
+                group_id,
+                member_id,
+                member_epoch,
+                rack_id,
+                subscribed_topic_names,
+                tag_buffer,
+            },
+            bytes,
+        ))
+    }
+}
+
+impl ShareGroupHeartbeatRequest {
+    pub fn handle_request(&self, broker: &Broker, context: &ConnectionContext) -> Option<Response> {
+        let group_id = self.group_id.0.clone().unwrap_or_default();
+        let member_id = self.member_id.0.clone().unwrap_or_default();
+        let mut response = ShareGroupHeartbeatResponse {
+            throttle_time_ms: 0,
+            error_code: error::NONE,
+            error_message: CompactString(None),
+            member_id: CompactString(Some(member_id.clone())),
+            member_epoch: self.member_epoch,
+            heartbeat_interval_ms: broker.config.share_group.heartbeat_interval_ms as i32,
+            assignment: None,
+            tag_buffer: TagSection(None),
+        };
+        let topics = self.subscribed_topic_names.0.as_ref().map(|names| {
+            names
+                .iter()
+                .map(|name| name.0.clone().unwrap_or_default())
+                .collect::<Vec<_>>()
+        });
+
+        let result =
+            if !broker.authorize(context, AclOperation::Read, ResourceType::Group, &group_id) {
+                Err((error::GROUP_AUTHORIZATION_FAILED, String::new()))
+            } else if group_id.is_empty() {
+                Err((
+                    error::INVALID_GROUP_ID,
+                    "GroupId can't be empty.".to_string(),
+                ))
+            } else if member_id.is_empty() {
+                Err((
+                    error::INVALID_REQUEST,
+                    "MemberId can't be empty.".to_string(),
+                ))
+            } else if self.member_epoch == 0 && topics.as_ref().map_or(true, Vec::is_empty) {
+                Err((
+                    error::INVALID_REQUEST,
+                    "SubscribedTopicNames must be set in first request.".to_string(),
+                ))
+            } else if self.member_epoch < -1 {
+                Err((
+                    error::INVALID_REQUEST,
+                    "MemberEpoch must be greater than or equal to -1.".to_string(),
+                ))
+            } else {
+                let image = broker.metadata.read().unwrap();
+                if share_group::coordinator_for(&image, &group_id) != Some(broker.config.node_id) {
+                    Err((
+                        error::NOT_COORDINATOR,
+                        "This is not the coordinator of the share group.".to_string(),
+                    ))
+                } else {
+                    broker.share_groups.heartbeat(
+                        &image,
+                        &group_id,
+                        &member_id,
+                        self.member_epoch,
+                        topics,
+                    )
+                }
+            };
+        match result {
+            Ok(result) => {
+                response.member_epoch = result.member_epoch;
+                response.assignment = result.assignment.map(ShareGroupAssignment::from);
+            }
+            Err((error_code, message)) => {
+                response.error_code = error_code;
+                response.error_message = CompactString((!message.is_empty()).then_some(message));
+            }
+        }
+        if self.member_epoch == -1 {
+            // Departed members get no further heartbeats
+            response.heartbeat_interval_ms = 0;
+        }
+
+        Some(Box::new(response))
+    }
+}
+
+impl RequestHandler for ShareGroupHeartbeatRequest {
+    fn read(bytes: &[u8], _version: i16) -> Result<Self> {
+        Ok(ShareGroupHeartbeatRequest::deserialize(bytes)?.0)
+    }
+
+    fn handle(&self, request: &mut RequestContext) -> Option<Response> {
+        self.handle_request(request.broker, request.connection)
+    }
+}
+
+#[derive(Debug)]
+pub struct TopicPartitions {
+    pub topic_id: Uuid,
+    pub partitions: CompactArray<i32>,
+    pub tag_buffer: TagSection,
+}
+
+impl Serializable for TopicPartitions {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(self.topic_id.serialize());
+        buf.extend(self.partitions.serialize());
+        buf.extend(self.tag_buffer.serialize());
+        buf
+    }
+
+    fn deserialize(_bytes: &[u8]) -> Result<(Self, &[u8])> {
+        todo!()
+    }
+}
+
+/// Partitions assigned to the member, sent when they changed.
+#[derive(Debug)]
+pub struct ShareGroupAssignment {
+    pub topic_partitions: CompactArray<TopicPartitions>,
+    pub tag_buffer: TagSection,
+}
+
+impl From<Assignment> for ShareGroupAssignment {
+    fn from(assignment: Assignment) -> Self {
+        ShareGroupAssignment {
+            topic_partitions: CompactArray(Some(
+                assignment
+                    .into_iter()
+                    .map(|(topic_id, partitions)| TopicPartitions {
+                        topic_id,
+                        partitions: CompactArray(Some(partitions)),
+                        tag_buffer: TagSection(None),
+                    })
+                    .collect(),
+            )),
+            tag_buffer: TagSection(None),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct ShareGroupHeartbeatResponse {
+    pub throttle_time_ms: i32,
+    pub error_code: i16,
+    pub error_message: CompactString,
+    pub member_id: CompactString,
+    pub member_epoch: i32,
+    pub heartbeat_interval_ms: i32,
+    /// Null when the assignment is unchanged
+    pub assignment: Option<ShareGroupAssignment>,
+    pub tag_buffer: TagSection,
+}
+
+impl Serializable for ShareGroupHeartbeatResponse {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(self.throttle_time_ms.to_be_bytes());
+        buf.extend(self.error_code.to_be_bytes());
+        buf.extend(self.error_message.serialize());
+        buf.extend(self.member_id.serialize());
+        buf.extend(self.member_epoch.to_be_bytes());
+        buf.extend(self.heartbeat_interval_ms.to_be_bytes());
+        // A nullable struct is preceded by -1 when null and 1 otherwise
+        match &self.assignment {
+            Some(assignment) => {
+                buf.extend(1i8.to_be_bytes());
+                buf.extend(assignment.topic_partitions.serialize());
+                buf.extend(assignment.tag_buffer.serialize());
+            }
+            None => buf.extend((-1i8).to_be_bytes()),
+        }
+        buf.extend(self.tag_buffer.serialize());
+        buf
+    }
+
+    fn deserialize(_bytes: &[u8]) -> Result<(Self, &[u8])> {
+        todo!()
+    }
+}
+
+impl ResponseBody for ShareGroupHeartbeatResponse {
+    fn set_throttle_time_ms(&mut self, throttle_time_ms: i32) {
+        self.throttle_time_ms = throttle_time_ms;
+    }
+}
@@ -0,0 +1,116 @@
+use anyhow::Result;
+
+use super::{
+    api_keys::{RequestContext, RequestHandler},
+    body::ResponseBody,
+    error,
+    primitive::{CompactString, Serializable, TagSection},
+    response::Response,
+};
+use crate::{
+    broker::Broker,
+    controller,
+    security::acl::{AclOperation, ResourceType, CLUSTER_RESOURCE_NAME},
+    server::ConnectionContext,
+};
+
+/// UnregisterBroker version 0, removing the registration of a broker that left the
+/// cluster for good.
+#[derive(Debug)]
+pub struct UnregisterBrokerRequest {
+    pub broker_id: i32,
+    pub tag_buffer: TagSection,
+}
+
+impl Serializable for UnregisterBrokerRequest {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(self.broker_id.to_be_bytes());
+        buf.extend(self.tag_buffer.serialize());
+        buf
+    }
+
+    fn deserialize(bytes: &[u8]) -> Result<(Self, &[u8])> {
+        let (broker_id, bytes) = i32::deserialize(bytes)?;
+        let (tag_buffer, bytes) = TagSection::deserialize(bytes)?;
+        Ok((
+            UnregisterBrokerRequest {
+                broker_id,
+                tag_buffer,
+            },
+            bytes,
+        ))
+    }
+}
+
+impl UnregisterBrokerRequest {
+    pub fn handle_request(&self, broker: &Broker, context: &ConnectionContext) -> Option<Response> {
+        let error_code = if !broker.authorize(
+            context,
+            AclOperation::Alter,
+            ResourceType::Cluster,
+            CLUSTER_RESOURCE_NAME,
+        ) {
+            error::CLUSTER_AUTHORIZATION_FAILED
+        } else {
+            match controller::unregister_broker(broker, self.broker_id) {
+                Ok(()) => error::NONE,
+                Err(error_code) => error_code,
+            }
+        };
+        let error_message = match error_code {
+            error::NONE => None,
+            error::BROKER_ID_NOT_REGISTERED => {
+                Some(format!("Broker {} is not registered", self.broker_id))
+            }
+            error::NOT_CONTROLLER => Some("This node is not the active controller".to_string()),
+            _ => None,
+        };
+
+        Some(Box::new(UnregisterBrokerResponse {
+            throttle_time_ms: 0,
+            error_code,
+            error_message: CompactString(error_message),
+            tag_buffer: TagSection(None),
+        }))
+    }
+}
+
+impl RequestHandler for UnregisterBrokerRequest {
+    fn read(bytes: &[u8], _version: i16) -> Result<Self> {
+        Ok(UnregisterBrokerRequest::deserialize(bytes)?.0)
+    }
+
+    fn handle(&self, request: &mut RequestContext) -> Option<Response> {
+        self.handle_request(request.broker, request.connection)
+    }
+}
+
+#[derive(Debug)]
+pub struct UnregisterBrokerResponse {
+    pub throttle_time_ms: i32,
+    pub error_code: i16,
+    pub error_message: CompactString,
+    pub tag_buffer: TagSection,
+}
+
+impl Serializable for UnregisterBrokerResponse {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(self.throttle_time_ms.to_be_bytes());
+        buf.extend(self.error_code.to_be_bytes());
+        buf.extend(self.error_message.serialize());
+        buf.extend(self.tag_buffer.serialize());
+        buf
+    }
+
+    fn deserialize(_bytes: &[u8]) -> Result<(Self, &[u8])> {
+        todo!()
+    }
+}
+
+impl ResponseBody for UnregisterBrokerResponse {
+    fn set_throttle_time_ms(&mut self, throttle_time_ms: i32) {
+        self.throttle_time_ms = throttle_time_ms;
+    }
+}
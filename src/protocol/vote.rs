@@ -0,0 +1,301 @@
+use anyhow::Result;
+
+use super::{
+    api_keys::{RequestContext, RequestHandler},
+    body::ResponseBody,
+    error,
+    primitive::{CompactArray, CompactString, Serializable, TagSection},
+    response::Response,
+};
+use crate::{
+    broker::Broker,
+    raft::METADATA_TOPIC,
+    security::acl::{AclOperation, ResourceType, CLUSTER_RESOURCE_NAME},
+    server::ConnectionContext,
+};
+
+#[derive(Debug)]
+pub struct VotePartition {
+    pub partition_index: i32,
+    pub candidate_epoch: i32,
+    pub candidate_id: i32,
+    /// Epoch of the last batch in the log of the candidate
+    pub last_offset_epoch: i32,
+    /// End offset of the log of the candidate
+    pub last_offset: i64,
+    pub tag_buffer: TagSection,
+}
+
+impl Serializable for VotePartition {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(self.partition_index.to_be_bytes());
+        buf.extend(self.candidate_epoch.to_be_bytes());
+        buf.extend(self.candidate_id.to_be_bytes());
+        buf.extend(self.last_offset_epoch.to_be_bytes());
+        buf.extend(self.last_offset.to_be_bytes());
+        buf.extend(self.tag_buffer.serialize());
+        buf
+    }
+
+    fn deserialize(bytes: &[u8]) -> Result<(Self, &[u8])> {
+        let (partition_index, bytes) = i32::deserialize(bytes)?;
+        let (candidate_epoch, bytes) = i32::deserialize(bytes)?;
+        let (candidate_id, bytes) = i32::deserialize(bytes)?;
+        let (last_offset_epoch, bytes) = i32::deserialize(bytes)?;
+        let (last_offset, bytes) = i64::deserialize(bytes)?;
+        let (tag_buffer, bytes) = TagSection::deserialize(bytes)?;
+        Ok((
+            VotePartition {
+                partition_index,
+                candidate_epoch,
+                candidate_id,
+                last_offset_epoch,
+                last_offset,
+                tag_buffer,
+            },
+            bytes,
+        ))
+    }
+}
+
+#[derive(Debug)]
+pub struct VoteTopic {
+    pub topic_name: CompactString,
+    pub partitions: CompactArray<VotePartition>,
+    pub tag_buffer: TagSection,
+}
+
+impl Serializable for VoteTopic {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(self.topic_name.serialize());
+        buf.extend(self.partitions.serialize());
+        buf.extend(self.tag_buffer.serialize());
+        buf
+    }
+
+    fn deserialize(bytes: &[u8]) -> Result<(Self, &[u8])> {
+        let (topic_name, bytes) = CompactString::deserialize(bytes)?;
+        let (partitions, bytes) = CompactArray::<VotePartition>::deserialize(bytes)?;
+        let (tag_buffer, bytes) = TagSection::deserialize(bytes)?;
+        Ok((
+            VoteTopic {
+                topic_name,
+                partitions,
+                tag_buffer,
+            },
+            bytes,
+        ))
+    }
+}
+
+/// Vote version 0, sent by a candidate to every voter of the metadata quorum.
+#[derive(Debug)]
+pub struct VoteRequest {
+    pub cluster_id: CompactString,
+    pub topics: CompactArray<VoteTopic>,
+    pub tag_buffer: TagSection,
+}
+
+impl Serializable for VoteRequest {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(self.cluster_id.serialize());
+        buf.extend(self.topics.serialize());
+        buf.extend(self.tag_buffer.serialize());
+        buf
+    }
+
+    fn deserialize(bytes: &[u8]) -> Result<(Self, &[u8])> {
+        let (cluster_id, bytes) = CompactString::deserialize(bytes)?;
+        let (topics, bytes) = CompactArray::<VoteTopic>::deserialize(bytes)?;
+        let (tag_buffer, bytes) = TagSection::deserialize(bytes)?;
+        Ok((
+            VoteRequest {
+                cluster_id,
+                topics,
+                tag_buffer,
+            },
+            bytes,
+        ))
+    }
+}
+
+impl VoteRequest {
+    pub fn handle_request(&self, broker: &Broker, context: &ConnectionContext) -> Option<Response> {
+        let mut response = VoteResponse {
+            error_code: error::NONE,
+            topics: CompactArray(Some(vec![])),
+            tag_buffer: TagSection(None),
+        };
+
+        if !broker.authorize(
+            context,
+            AclOperation::ClusterAction,
+            ResourceType::Cluster,
+            CLUSTER_RESOURCE_NAME,
+        ) {
+            response.error_code = error::CLUSTER_AUTHORIZATION_FAILED;
+        } else if self
+            .cluster_id
+            .as_deref()
+            .is_some_and(|id| id != broker.cluster_id)
+        {
+            response.error_code = error::INCONSISTENT_CLUSTER_ID;
+        } else {
+            let topics = self.topics.iter().flatten().map(|topic| {
+                let is_metadata = topic.topic_name.as_deref() == Some(METADATA_TOPIC);
+                let partitions = topic.partitions.iter().flatten().map(|partition| {
+                    if is_metadata && partition.partition_index == 0 {
+                        broker.raft.handle_vote(partition)
+                    } else {
+                        VotePartitionResponse::error(
+                            partition.partition_index,
+                            error::UNKNOWN_TOPIC_OR_PARTITION,
+                        )
+                    }
+                });
+                VoteTopicResponse {
+                    topic_name: topic.topic_name.clone(),
+                    partitions: CompactArray(Some(partitions.collect())),
+                    tag_buffer: TagSection(None),
+                }
+            });
+            response.topics = CompactArray(Some(topics.collect()));
+        }
+
+        Some(Box::new(response))
+    }
+}
+
+impl RequestHandler for VoteRequest {
+    fn read(bytes: &[u8], _version: i16) -> Result<Self> {
+        Ok(VoteRequest::deserialize(bytes)?.0)
+    }
+
+    fn handle(&self, request: &mut RequestContext) -> Option<Response> {
+        self.handle_request(request.broker, request.connection)
+    }
+}
+
+#[derive(Debug)]
+pub struct VotePartitionResponse {
+    pub partition_index: i32,
+    pub error_code: i16,
+    /// Leader of the latest epoch known to the voter, -1 if unknown
+    pub leader_id: i32,
+    pub leader_epoch: i32,
+    pub vote_granted: bool,
+    pub tag_buffer: TagSection,
+}
+
+impl VotePartitionResponse {
+    pub fn error(partition_index: i32, error_code: i16) -> Self {
+        VotePartitionResponse {
+            partition_index,
+            error_code,
+            leader_id: -1,
+            leader_epoch: -1,
+            vote_granted: false,
+            tag_buffer: TagSection(None),
+        }
+    }
+}
+
+impl Serializable for VotePartitionResponse {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(self.partition_index.to_be_bytes());
+        buf.extend(self.error_code.to_be_bytes());
+        buf.extend(self.leader_id.to_be_bytes());
+        buf.extend(self.leader_epoch.to_be_bytes());
+        buf.extend(self.vote_granted.serialize());
+        buf.extend(self.tag_buffer.serialize());
+        buf
+    }
+
+    fn deserialize(bytes: &[u8]) -> Result<(Self, &[u8])> {
+        let (partition_index, bytes) = i32::deserialize(bytes)?;
+        let (error_code, bytes) = i16::deserialize(bytes)?;
+        let (leader_id, bytes) = i32::deserialize(bytes)?;
+        let (leader_epoch, bytes) = i32::deserialize(bytes)?;
+        let (vote_granted, bytes) = bool::deserialize(bytes)?;
+        let (tag_buffer, bytes) = TagSection::deserialize(bytes)?;
+        Ok((
+            VotePartitionResponse {
+                partition_index,
+                error_code,
+                leader_id,
+                leader_epoch,
+                vote_granted,
+                tag_buffer,
+            },
+            bytes,
+        ))
+    }
+}
+
+#[derive(Debug)]
+pub struct VoteTopicResponse {
+    pub topic_name: CompactString,
+    pub partitions: CompactArray<VotePartitionResponse>,
+    pub tag_buffer: TagSection,
+}
+
+impl Serializable for VoteTopicResponse {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(self.topic_name.serialize());
+        buf.extend(self.partitions.serialize());
+        buf.extend(self.tag_buffer.serialize());
+        buf
+    }
+
+    fn deserialize(bytes: &[u8]) -> Result<(Self, &[u8])> {
+        let (topic_name, bytes) = CompactString::deserialize(bytes)?;
+        let (partitions, bytes) = CompactArray::<VotePartitionResponse>::deserialize(bytes)?;
+        let (tag_buffer, bytes) = TagSection::deserialize(bytes)?;
+        Ok((
+            VoteTopicResponse {
+                topic_name,
+                partitions,
+                tag_buffer,
+            },
+            bytes,
+        ))
+    }
+}
+
+#[derive(Debug)]
+pub struct VoteResponse {
+    pub error_code: i16,
+    pub topics: CompactArray<VoteTopicResponse>,
+    pub tag_buffer: TagSection,
+}
+
+impl Serializable for VoteResponse {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(self.error_code.to_be_bytes());
+        buf.extend(self.topics.serialize());
+        buf.extend(self.tag_buffer.serialize());
+        buf
+    }
+
+    fn deserialize(bytes: &[u8]) -> Result<(Self, &[u8])> {
+        let (error_code, bytes) = i16::deserialize(bytes)?;
+        let (topics, bytes) = CompactArray::<VoteTopicResponse>::deserialize(bytes)?;
+        let (tag_buffer, bytes) = TagSection::deserialize(bytes)?;
+        Ok((
+            VoteResponse {
+                error_code,
+                topics,
+                tag_buffer,
+            },
+            bytes,
+        ))
+    }
+}
+
+impl ResponseBody for VoteResponse {}
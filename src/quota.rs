@@ -0,0 +1,376 @@
+use std::{
+    collections::{BTreeMap, HashMap, VecDeque},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+    time::Instant,
+};
+
+use crate::{config::QuotaConfig, metadata::MetadataImage};
+
+pub const USER_ENTITY: &str = "user";
+pub const CLIENT_ID_ENTITY: &str = "client-id";
+
+pub const PRODUCER_BYTE_RATE: &str = "producer_byte_rate";
+pub const CONSUMER_BYTE_RATE: &str = "consumer_byte_rate";
+pub const REQUEST_PERCENTAGE: &str = "request_percentage";
+
+/// Entity a quota is configured for, mapping each entity type to its name; a `None`
+/// name stands for the default entity of that type.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ClientQuotaEntity(pub BTreeMap<String, Option<String>>);
+
+impl ClientQuotaEntity {
+    fn of(user: Option<Option<&str>>, client_id: Option<Option<&str>>) -> Self {
+        let mut entity = BTreeMap::new();
+        if let Some(user) = user {
+            entity.insert(USER_ENTITY.to_string(), user.map(str::to_string));
+        }
+        if let Some(client_id) = client_id {
+            entity.insert(CLIENT_ID_ENTITY.to_string(), client_id.map(str::to_string));
+        }
+        ClientQuotaEntity(entity)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum QuotaType {
+    Produce,
+    Fetch,
+    Request,
+}
+
+impl QuotaType {
+    pub fn key(&self) -> &'static str {
+        match self {
+            QuotaType::Produce => PRODUCER_BYTE_RATE,
+            QuotaType::Fetch => CONSUMER_BYTE_RATE,
+            QuotaType::Request => REQUEST_PERCENTAGE,
+        }
+    }
+}
+
+/// Side of the replication a throttle applies to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ReplicationQuotaType {
+    /// Leaders sending records to followers
+    Leader,
+    /// Followers fetching records from leaders
+    Follower,
+}
+
+impl ReplicationQuotaType {
+    /// Broker config of the throttled rate, in bytes per second.
+    pub fn rate_config(&self) -> &'static str {
+        match self {
+            ReplicationQuotaType::Leader => "leader.replication.throttled.rate",
+            ReplicationQuotaType::Follower => "follower.replication.throttled.rate",
+        }
+    }
+}
+
+/// Whether a list of throttled replicas, `partition:broker` entries or `*` for every
+/// replica, names the replica of `partition` on `broker_id`.
+pub fn is_throttled_replica(throttled: &[String], partition: i32, broker_id: i32) -> bool {
+    throttled.iter().any(|entry| {
+        entry == "*"
+            || entry.split_once(':').is_some_and(|(p, b)| {
+                p.trim().parse() == Ok(partition) && b.trim().parse() == Ok(broker_id)
+            })
+    })
+}
+
+/// Whether `key` is a quota the broker knows how to enforce.
+pub fn is_valid_key(key: &str) -> bool {
+    matches!(
+        key,
+        PRODUCER_BYTE_RATE | CONSUMER_BYTE_RATE | REQUEST_PERCENTAGE
+    )
+}
+
+/// Find the most specific quota of `key` that applies to the client, along with the
+/// entity it is configured for.
+pub fn resolve_quota<'a>(
+    metadata: &'a MetadataImage,
+    key: &str,
+    user: &str,
+    client_id: &str,
+) -> Option<(f64, &'a ClientQuotaEntity)> {
+    // Same precedence as Apache Kafka, from the most to the least specific entity
+    let candidates = [
+        ClientQuotaEntity::of(Some(Some(user)), Some(Some(client_id))),
+        ClientQuotaEntity::of(Some(Some(user)), Some(None)),
+        ClientQuotaEntity::of(Some(Some(user)), None),
+        ClientQuotaEntity::of(Some(None), Some(Some(client_id))),
+        ClientQuotaEntity::of(Some(None), Some(None)),
+        ClientQuotaEntity::of(Some(None), None),
+        ClientQuotaEntity::of(None, Some(Some(client_id))),
+        ClientQuotaEntity::of(None, Some(None)),
+    ];
+    candidates.iter().find_map(|candidate| {
+        let (entity, values) = metadata.client_quotas.get_key_value(candidate)?;
+        values.get(key).map(|value| (*value, entity))
+    })
+}
+
+/// Rate of a metric over a sliding window made of a fixed number of samples.
+#[derive(Debug, Default)]
+struct Rate {
+    /// Start of each sample in milliseconds along with the total recorded in it
+    samples: VecDeque<(u64, f64)>,
+}
+
+impl Rate {
+    fn record(&mut self, value: f64, now_ms: u64, config: &QuotaConfig) {
+        match self.samples.back_mut() {
+            Some((start_ms, total)) if now_ms - *start_ms < config.window_size_ms => {
+                *total += value
+            }
+            _ => self.samples.push_back((now_ms, value)),
+        }
+        let max_age_ms = config.max_age_ms();
+        while let Some(&(start_ms, _)) = self.samples.front() {
+            if now_ms - start_ms < max_age_ms {
+                break;
+            }
+            self.samples.pop_front();
+        }
+    }
+
+    /// Length of the window the rate is measured over, in milliseconds.
+    fn window_ms(&self, now_ms: u64, config: &QuotaConfig) -> u64 {
+        let elapsed_ms = self.samples.front().map_or(0, |&(start, _)| now_ms - start);
+        // Measure over at least all but one full sample so that a burst right after the
+        // first request does not read as a huge rate
+        let min_ms = config
+            .window_size_ms
+            .saturating_mul((config.window_num as u64).saturating_sub(1));
+        elapsed_ms.max(min_ms).max(1)
+    }
+
+    /// Whether nothing was recorded over the whole window, for the rate to be dropped.
+    fn is_idle(&self, now_ms: u64, config: &QuotaConfig) -> bool {
+        let max_age_ms = config.max_age_ms();
+        self.samples
+            .back()
+            .map_or(true, |&(start_ms, _)| now_ms - start_ms >= max_age_ms)
+    }
+
+    /// Value per second over the window.
+    fn measure(&self, now_ms: u64, config: &QuotaConfig) -> f64 {
+        let total: f64 = self.samples.iter().map(|(_, value)| value).sum();
+        total * 1000.0 / self.window_ms(now_ms, config) as f64
+    }
+}
+
+/// Quota type along with the user and client id of the quota entity; a dimension the
+/// entity does not constrain is left empty so that its clients share the usage.
+type RateKey = (QuotaType, Option<String>, Option<String>);
+
+/// Tracks per-client usage against the quotas of the metadata image.
+#[derive(Debug)]
+pub struct QuotaManager {
+    config: QuotaConfig,
+    started: Instant,
+    rates: Mutex<HashMap<RateKey, Rate>>,
+    /// When the idle rates were last dropped, in milliseconds since `started`
+    rates_expired_ms: AtomicU64,
+    /// Bytes of throttled replicas replicated by this broker
+    replication_rates: Mutex<HashMap<ReplicationQuotaType, Rate>>,
+}
+
+impl QuotaManager {
+    /// Longest a client is ever throttled for, as in Apache Kafka.
+    const MAX_THROTTLE_TIME_MS: u64 = 1_000 * 60 * 60;
+
+    pub fn new(config: QuotaConfig) -> Self {
+        QuotaManager {
+            config,
+            started: Instant::now(),
+            rates: Mutex::new(HashMap::new()),
+            rates_expired_ms: AtomicU64::new(0),
+            replication_rates: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Record usage of the client and return how long it must be throttled for, in
+    /// milliseconds. Byte-rate quotas record bytes, the request quota records the
+    /// percentage of a thread's second spent handling the request.
+    pub fn record(
+        &self,
+        metadata: &MetadataImage,
+        quota_type: QuotaType,
+        user: &str,
+        client_id: &str,
+        value: f64,
+    ) -> i32 {
+        let Some((quota, entity)) = resolve_quota(metadata, quota_type.key(), user, client_id)
+        else {
+            return 0;
+        };
+        let key = (
+            entity.0.contains_key(USER_ENTITY).then(|| user.to_string()),
+            entity
+                .0
+                .contains_key(CLIENT_ID_ENTITY)
+                .then(|| client_id.to_string()),
+        );
+
+        let now_ms = self.started.elapsed().as_millis() as u64;
+        let mut rates = self.rates.lock().unwrap();
+        // Clients that went away leave their rates behind, dropped once per window
+        let max_age_ms = self.config.max_age_ms();
+        if now_ms - self.rates_expired_ms.load(Ordering::Relaxed) >= max_age_ms {
+            rates.retain(|_, rate| !rate.is_idle(now_ms, &self.config));
+            self.rates_expired_ms.store(now_ms, Ordering::Relaxed);
+        }
+        let rate = rates.entry((quota_type, key.0, key.1)).or_default();
+        rate.record(value, now_ms, &self.config);
+
+        let measured = rate.measure(now_ms, &self.config);
+        if measured <= quota || quota <= 0.0 {
+            return 0;
+        }
+        let window_ms = rate.window_ms(now_ms, &self.config) as f64;
+        let throttle_ms = ((measured - quota) / quota * window_ms) as u64;
+        throttle_ms.min(Self::MAX_THROTTLE_TIME_MS) as i32
+    }
+
+    /// Record bytes of throttled replicas sent or fetched by this broker.
+    pub fn record_replication(&self, quota_type: ReplicationQuotaType, bytes: usize) {
+        let now_ms = self.started.elapsed().as_millis() as u64;
+        let mut rates = self.replication_rates.lock().unwrap();
+        let rate = rates.entry(quota_type).or_default();
+        rate.record(bytes as f64, now_ms, &self.config);
+    }
+
+    /// Whether throttled replicas were replicated faster than `rate` bytes per second,
+    /// in which case they wait for the rate to come down.
+    pub fn is_replication_quota_exceeded(
+        &self,
+        quota_type: ReplicationQuotaType,
+        rate: i64,
+    ) -> bool {
+        if rate == i64::MAX {
+            return false;
+        }
+        let now_ms = self.started.elapsed().as_millis() as u64;
+        let rates = self.replication_rates.lock().unwrap();
+        rates
+            .get(&quota_type)
+            .is_some_and(|measured| measured.measure(now_ms, &self.config) > rate as f64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: QuotaConfig = QuotaConfig {
+        window_num: 11,
+        window_size_ms: 1000,
+    };
+
+    /// Every entity a quota can be set for, from the most to the least specific, each
+    /// given a producer byte rate telling it apart.
+    fn entities() -> Vec<(ClientQuotaEntity, f64)> {
+        [
+            (Some(Some("alice")), Some(Some("app"))),
+            (Some(Some("alice")), Some(None)),
+            (Some(Some("alice")), None),
+            (Some(None), Some(Some("app"))),
+            (Some(None), Some(None)),
+            (Some(None), None),
+            (None, Some(Some("app"))),
+            (None, Some(None)),
+        ]
+        .into_iter()
+        .enumerate()
+        .map(|(i, (user, client_id))| (ClientQuotaEntity::of(user, client_id), i as f64 + 1.0))
+        .collect()
+    }
+
+    fn image(quotas: &[(ClientQuotaEntity, f64)]) -> MetadataImage {
+        let mut image = MetadataImage::default();
+        for (entity, value) in quotas {
+            image.client_quotas.insert(
+                entity.clone(),
+                HashMap::from([(PRODUCER_BYTE_RATE.to_string(), *value)]),
+            );
+        }
+        image
+    }
+
+    #[test]
+    fn resolves_the_most_specific_quota() {
+        let mut quotas = entities();
+        while !quotas.is_empty() {
+            let image = image(&quotas);
+            let (value, entity) =
+                resolve_quota(&image, PRODUCER_BYTE_RATE, "alice", "app").unwrap();
+            assert_eq!((value, entity), (quotas[0].1, &quotas[0].0));
+            quotas.remove(0);
+        }
+        assert!(resolve_quota(&image(&[]), PRODUCER_BYTE_RATE, "alice", "app").is_none());
+    }
+
+    #[test]
+    fn ignores_quotas_of_other_clients_and_keys() {
+        let image = image(&entities());
+        // Bob falls back to the default user with client id app
+        let (value, _) = resolve_quota(&image, PRODUCER_BYTE_RATE, "bob", "app").unwrap();
+        assert_eq!(value, 4.0);
+        let (value, _) = resolve_quota(&image, PRODUCER_BYTE_RATE, "bob", "other").unwrap();
+        assert_eq!(value, 5.0);
+        assert!(resolve_quota(&image, CONSUMER_BYTE_RATE, "alice", "app").is_none());
+    }
+
+    #[test]
+    fn expires_samples_older_than_the_window() {
+        let mut rate = Rate::default();
+        rate.record(100.0, 0, &CONFIG);
+        rate.record(100.0, 999, &CONFIG);
+        rate.record(100.0, 1000, &CONFIG);
+        assert_eq!(rate.samples, [(0, 200.0), (1000, 100.0)]);
+        // Measured over all but one sample until the window fills up
+        assert_eq!(rate.window_ms(1000, &CONFIG), 10_000);
+        assert_eq!(rate.measure(1000, &CONFIG), 30.0);
+        assert_eq!(rate.window_ms(10_500, &CONFIG), 10_500);
+
+        rate.record(100.0, 11_000, &CONFIG);
+        assert_eq!(rate.samples, [(1000, 100.0), (11_000, 100.0)]);
+        assert!(!rate.is_idle(21_999, &CONFIG));
+        assert!(rate.is_idle(22_000, &CONFIG));
+    }
+
+    #[test]
+    fn throttles_by_how_far_the_rate_is_over_the_quota() {
+        let quotas = QuotaManager::new(CONFIG);
+        let image = image(&[(ClientQuotaEntity::of(None, Some(Some("app"))), 1000.0)]);
+        assert_eq!(
+            quotas.record(&image, QuotaType::Produce, "alice", "app", 10_000.0),
+            0
+        );
+        // 30000 bytes over a 10 second window is 3000 bytes per second, twice the quota
+        // over it, so the client waits twice the window for the rate to come down
+        assert_eq!(
+            quotas.record(&image, QuotaType::Produce, "alice", "app", 20_000.0),
+            20_000
+        );
+        // Other clients, and quota types without a quota, are not throttled
+        assert_eq!(
+            quotas.record(&image, QuotaType::Produce, "alice", "other", 1e9),
+            0
+        );
+        assert_eq!(
+            quotas.record(&image, QuotaType::Fetch, "alice", "app", 1e9),
+            0
+        );
+        // Nor is anyone ever throttled for more than an hour
+        assert_eq!(
+            quotas.record(&image, QuotaType::Produce, "bob", "app", 1e12),
+            QuotaManager::MAX_THROTTLE_TIME_MS as i32
+        );
+    }
+}
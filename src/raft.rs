@@ -0,0 +1,1653 @@
+use std::{
+    cmp::Reverse,
+    collections::{BTreeMap, BTreeSet},
+    fmt, fs,
+    path::Path,
+    sync::{Arc, Condvar, Mutex},
+    thread,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{bail, Context, Result};
+use rand::Rng;
+use thiserror::Error;
+
+use crate::{
+    broker::Broker,
+    config::{BrokerConfig, QuorumConfig},
+    metadata::{snapshot_file_name, MetadataLog},
+    network_client::NetworkClient,
+    protocol::{
+        begin_quorum_epoch::{
+            BeginQuorumEpochPartition, BeginQuorumEpochRequest, BeginQuorumEpochResponse,
+            BeginQuorumEpochTopic, QuorumEpochOutcome, QuorumEpochTopicResponse,
+        },
+        cluster_metadata::{
+            ControlRecordKey, LeaderChangeMessage, LeaderChangeVoter, MetadataRecord, RecordBatch,
+            SnapshotFooterRecord, SnapshotHeaderRecord,
+        },
+        end_quorum_epoch::{
+            EndQuorumEpochPartition, EndQuorumEpochRequest, EndQuorumEpochResponse,
+            EndQuorumEpochTopic,
+        },
+        error,
+        fetch::{
+            FetchPartition, FetchRequest, FetchResponse, FetchTopic, FetchableTopicResponse,
+            LeaderIdAndEpoch, PartitionData, SnapshotId,
+        },
+        fetch_snapshot::{
+            FetchSnapshotPartition, FetchSnapshotRequest, FetchSnapshotResponse,
+            FetchSnapshotTopic, SnapshotPartitionResponse,
+        },
+        primitive::{
+            CompactArray, CompactBytes, CompactString, KafkaArray, KafkaString, Serializable,
+            TagSection,
+        },
+        vote::{VotePartition, VotePartitionResponse, VoteRequest, VoteResponse, VoteTopic},
+    },
+};
+
+/// Name of the topic holding the metadata log, with a single partition.
+pub const METADATA_TOPIC: &str = "__cluster_metadata";
+pub const METADATA_PARTITION: i32 = 0;
+
+const FETCH_API_KEY: i16 = 1;
+const FETCH_VERSION: i16 = 12;
+const VOTE_API_KEY: i16 = 52;
+const BEGIN_QUORUM_EPOCH_API_KEY: i16 = 53;
+const END_QUORUM_EPOCH_API_KEY: i16 = 54;
+const FETCH_SNAPSHOT_API_KEY: i16 = 59;
+
+/// How long the leader holds a fetch that is caught up, as in Apache Kafka
+const FETCH_MAX_WAIT_MS: i32 = 500;
+const FETCH_MAX_BYTES: i32 = 8 * 1024 * 1024;
+/// Apache Kafka's default for `controller.quorum.retry.backoff.ms`
+const RETRY_BACKOFF: Duration = Duration::from_millis(20);
+/// Records per data batch of a snapshot
+const SNAPSHOT_BATCH_RECORDS: usize = 1000;
+/// Observers that stopped fetching for this long are no longer described, as in Apache
+/// Kafka
+const OBSERVER_SESSION_TIMEOUT_MS: i64 = 5 * 60 * 1000;
+
+#[derive(Debug, Error)]
+pub enum RaftError {
+    #[error("node {0} is not the active controller")]
+    NotController(i32),
+    #[error("timed out waiting for the metadata quorum to commit offset {0}")]
+    CommitTimeout(i64),
+}
+
+impl RaftError {
+    /// Error code reported to clients for a failed metadata write.
+    pub fn error_code(error: &anyhow::Error) -> i16 {
+        match error.downcast_ref::<RaftError>() {
+            Some(RaftError::NotController(_)) => error::NOT_CONTROLLER,
+            Some(RaftError::CommitTimeout(_)) => error::REQUEST_TIMED_OUT,
+            None => error::UNKNOWN_SERVER_ERROR,
+        }
+    }
+}
+
+/// Progress of a replica of the metadata log, as seen by the leader.
+#[derive(Debug, Clone, Copy)]
+pub struct ReplicaState {
+    /// Offset the replica fetched from last, -1 until it fetches
+    pub end_offset: i64,
+    /// Milliseconds since the Unix epoch, -1 until the replica fetches
+    pub last_fetch_timestamp: i64,
+    /// Last time the replica fetched from the end of the log, -1 until it does
+    pub last_caught_up_timestamp: i64,
+}
+
+impl Default for ReplicaState {
+    fn default() -> Self {
+        ReplicaState {
+            end_offset: -1,
+            last_fetch_timestamp: -1,
+            last_caught_up_timestamp: -1,
+        }
+    }
+}
+
+/// State of the metadata partition as the leader sees it, for DescribeQuorum.
+#[derive(Debug)]
+pub struct QuorumInfo {
+    pub leader_epoch: i32,
+    pub high_watermark: i64,
+    pub voters: BTreeMap<i32, ReplicaState>,
+    pub observers: BTreeMap<i32, ReplicaState>,
+}
+
+#[derive(Debug)]
+struct LeaderState {
+    /// Offset of the LeaderChange record opening the epoch, which must commit before
+    /// anything else does
+    epoch_start_offset: i64,
+    elected: Instant,
+    voters: BTreeMap<i32, ReplicaState>,
+    observers: BTreeMap<i32, ReplicaState>,
+    /// Voters yet to acknowledge BeginQuorumEpoch
+    unacknowledged: BTreeSet<i32>,
+    next_begin_quorum_epoch: Instant,
+}
+
+impl LeaderState {
+    /// End offset a majority of voters have reached, once it takes in the epoch's
+    /// LeaderChange record.
+    fn committed_offset(&self) -> Option<i64> {
+        let mut offsets: Vec<i64> = self.voters.values().map(|r| r.end_offset).collect();
+        offsets.sort_unstable_by(|a, b| b.cmp(a));
+        let majority_offset = offsets[offsets.len() / 2];
+        (majority_offset > self.epoch_start_offset).then_some(majority_offset)
+    }
+}
+
+#[derive(Debug)]
+enum Role {
+    /// Neither a leader nor a vote in the epoch
+    Unattached,
+    Voted(i32),
+    Candidate {
+        granted: BTreeSet<i32>,
+        votes_requested: bool,
+    },
+    Leader(LeaderState),
+    /// A leader that stepped down and asked the other voters to elect a successor
+    Resigned {
+        preferred_successors: Vec<i32>,
+        notified: bool,
+    },
+    Follower(i32),
+}
+
+impl fmt::Display for Role {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Role::Unattached => write!(f, "unattached"),
+            Role::Voted(candidate) => write!(f, "voter for {candidate}"),
+            Role::Candidate { .. } => write!(f, "candidate"),
+            Role::Leader(_) => write!(f, "leader"),
+            Role::Resigned { .. } => write!(f, "resigned leader"),
+            Role::Follower(leader) => write!(f, "follower of {leader}"),
+        }
+    }
+}
+
+struct RaftState {
+    epoch: i32,
+    role: Role,
+    log: MetadataLog,
+    high_watermark: i64,
+    /// Snapshot the leader told the follower to fetch, as its log starts after the
+    /// follower's end
+    pending_snapshot: Option<SnapshotId>,
+    /// When a voter that hears from no leader stands for election, or when an observer
+    /// looks for a new leader
+    deadline: Instant,
+}
+
+/// What the driver thread does next.
+enum Action {
+    Wait(Duration),
+    RequestVotes(VoteRequest),
+    BeginQuorumEpoch(Vec<i32>, BeginQuorumEpochRequest),
+    EndQuorumEpoch(Vec<i32>, EndQuorumEpochRequest),
+    Fetch(i32, FetchRequest),
+    FetchSnapshot(i32, SnapshotId),
+}
+
+/// Election and replication of the metadata log among the controllers of
+/// `controller.quorum.voters`, following KIP-595. Brokers that do not vote replicate
+/// the log as observers.
+pub struct RaftManager {
+    node_id: i32,
+    cluster_id: String,
+    config: QuorumConfig,
+    state: Mutex<RaftState>,
+    /// Signalled whenever the log, the high watermark or the role changes
+    changed: Condvar,
+}
+
+impl RaftManager {
+    /// Open the metadata log and resume the election state it was left in. A single
+    /// voter elects itself at once.
+    pub fn open(config: &BrokerConfig, cluster_id: &str) -> Result<Self> {
+        let log = MetadataLog::open(
+            &config.metadata_partition_dir(),
+            config.metadata_log.segment_bytes,
+        )?;
+        let election = ElectionState::load(log.partition_dir())?;
+        let node_id = config.node_id;
+        let role = match election {
+            ElectionState { leader_id, .. } if leader_id >= 0 && leader_id != node_id => {
+                Role::Follower(leader_id)
+            }
+            ElectionState { voted_id, .. } if voted_id >= 0 => Role::Voted(voted_id),
+            _ => Role::Unattached,
+        };
+        let state = RaftState {
+            epoch: election.epoch.max(log.last_epoch()),
+            role,
+            // A snapshot only covers committed records
+            high_watermark: log.snapshot().map_or(0, |snapshot| snapshot.end_offset),
+            log,
+            pending_snapshot: None,
+            deadline: Instant::now(),
+        };
+        let raft = RaftManager {
+            node_id,
+            cluster_id: cluster_id.to_string(),
+            config: config.quorum.clone(),
+            state: Mutex::new(state),
+            changed: Condvar::new(),
+        };
+        {
+            let mut state = raft.state.lock().unwrap();
+            state.deadline = raft.deadline_for(&state.role);
+            if raft.config.voters.len() == 1 && raft.is_voter(node_id) {
+                raft.become_candidate(&mut state)?;
+            }
+        }
+        Ok(raft)
+    }
+
+    /// Leader of the current epoch, if known.
+    pub fn leader(&self) -> Option<i32> {
+        self.leader_of(&self.state.lock().unwrap())
+    }
+
+    pub fn is_leader(&self) -> bool {
+        matches!(self.state.lock().unwrap().role, Role::Leader(_))
+    }
+
+    pub fn end_offset(&self) -> i64 {
+        self.state.lock().unwrap().log.end_offset()
+    }
+
+    pub fn high_watermark(&self) -> i64 {
+        self.state.lock().unwrap().high_watermark
+    }
+
+    /// Batches of the committed log from the one holding `from`.
+    pub fn committed_batches(&self, from: i64) -> Result<Vec<RecordBatch>> {
+        let state = self.state.lock().unwrap();
+        state.log.batches(from, state.high_watermark)
+    }
+
+    /// Latest snapshot of the metadata log.
+    pub fn latest_snapshot(&self) -> Option<SnapshotId> {
+        self.state.lock().unwrap().log.snapshot()
+    }
+
+    /// Records of a snapshot, without its header and footer.
+    pub fn read_snapshot(&self, snapshot_id: SnapshotId) -> Result<Vec<MetadataRecord>> {
+        let batches = self.state.lock().unwrap().log.read_snapshot(snapshot_id)?;
+        let mut records = vec![];
+        for batch in batches.iter().filter(|batch| !batch.is_control()) {
+            for record in &batch.records {
+                let Some(value) = record.value.as_deref() else {
+                    continue;
+                };
+                records.push(MetadataRecord::deserialize(value)?.0);
+            }
+        }
+        Ok(records)
+    }
+
+    /// Write a snapshot of the committed log up to `snapshot_id` holding the records,
+    /// framed by SnapshotHeader and SnapshotFooter control records, and delete the log
+    /// segments it covers.
+    pub fn create_snapshot(
+        &self,
+        snapshot_id: SnapshotId,
+        records: &[MetadataRecord],
+        last_contained_log_timestamp: i64,
+    ) -> Result<()> {
+        let mut state = self.state.lock().unwrap();
+        if snapshot_id.end_offset > state.high_watermark {
+            bail!(
+                "snapshot end offset {} is past the high watermark {}",
+                snapshot_id.end_offset,
+                state.high_watermark
+            );
+        }
+        if state
+            .log
+            .snapshot()
+            .is_some_and(|snapshot| snapshot >= snapshot_id)
+        {
+            return Ok(());
+        }
+
+        let timestamp = now_ms();
+        let header = SnapshotHeaderRecord {
+            last_contained_log_timestamp,
+        };
+        let mut batches = vec![RecordBatch::control(
+            0,
+            snapshot_id.epoch,
+            timestamp,
+            ControlRecordKey::SNAPSHOT_HEADER,
+            header.serialize(),
+        )];
+        let mut offset = 1;
+        for chunk in records.chunks(SNAPSHOT_BATCH_RECORDS) {
+            let values = chunk.iter().map(Serializable::serialize).collect();
+            batches.push(RecordBatch::new(
+                offset,
+                snapshot_id.epoch,
+                timestamp,
+                values,
+            ));
+            offset += chunk.len() as i64;
+        }
+        batches.push(RecordBatch::control(
+            offset,
+            snapshot_id.epoch,
+            timestamp,
+            ControlRecordKey::SNAPSHOT_FOOTER,
+            SnapshotFooterRecord.serialize(),
+        ));
+        state.log.write_snapshot(snapshot_id, &batches)?;
+        eprintln!(
+            "Wrote metadata snapshot {}",
+            snapshot_file_name(snapshot_id)
+        );
+        Ok(())
+    }
+
+    /// Append the records as one batch of the current epoch and return the offset the
+    /// log ends at after it. Only the leader writes to the log.
+    pub fn append(&self, records: &[MetadataRecord]) -> Result<i64> {
+        let mut state = self.state.lock().unwrap();
+        if !matches!(state.role, Role::Leader(_)) {
+            return Err(RaftError::NotController(self.node_id).into());
+        }
+        let values = records.iter().map(Serializable::serialize).collect();
+        let batch = RecordBatch::new(0, state.epoch, now_ms(), values);
+        state.log.append(batch)?;
+        self.update_high_watermark(&mut state);
+        self.changed.notify_all();
+        Ok(state.log.end_offset())
+    }
+
+    /// Wait until the log is committed up to `offset`, failing if this node loses the
+    /// leadership first.
+    pub fn wait_for_commit(&self, offset: i64) -> Result<()> {
+        let deadline = Instant::now() + Duration::from_millis(self.config.fetch_timeout_ms);
+        let mut state = self.state.lock().unwrap();
+        let epoch = state.epoch;
+        while state.high_watermark < offset {
+            if state.epoch != epoch || !matches!(state.role, Role::Leader(_)) {
+                return Err(RaftError::NotController(self.node_id).into());
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                return Err(RaftError::CommitTimeout(offset).into());
+            }
+            state = self.changed.wait_timeout(state, deadline - now).unwrap().0;
+        }
+        Ok(())
+    }
+
+    /// Wait until the high watermark moves past `offset`, or the timeout passes.
+    pub fn wait_for_high_watermark(&self, offset: i64, timeout: Duration) {
+        let state = self.state.lock().unwrap();
+        let _ = self
+            .changed
+            .wait_timeout_while(state, timeout, |state| state.high_watermark <= offset)
+            .unwrap();
+    }
+
+    fn is_voter(&self, node_id: i32) -> bool {
+        self.config.voter(node_id).is_some()
+    }
+
+    /// Leader of the current epoch, -1 if unknown, with the epoch.
+    pub fn current_leader(&self) -> LeaderIdAndEpoch {
+        self.leader_id_and_epoch(&self.state.lock().unwrap())
+    }
+
+    /// Progress of the voters and recent observers, `None` unless this node leads the
+    /// quorum.
+    pub fn describe_quorum(&self) -> Option<QuorumInfo> {
+        let mut guard = self.state.lock().unwrap();
+        self.update_high_watermark(&mut guard);
+        let Role::Leader(leader) = &guard.role else {
+            return None;
+        };
+        let since_ms = now_ms() - OBSERVER_SESSION_TIMEOUT_MS;
+        Some(QuorumInfo {
+            leader_epoch: guard.epoch,
+            high_watermark: guard.high_watermark,
+            voters: leader.voters.clone(),
+            observers: leader
+                .observers
+                .iter()
+                .filter(|(_, replica)| replica.last_fetch_timestamp >= since_ms)
+                .map(|(&id, &replica)| (id, replica))
+                .collect(),
+        })
+    }
+
+    fn leader_of(&self, state: &RaftState) -> Option<i32> {
+        match state.role {
+            Role::Leader(_) => Some(self.node_id),
+            Role::Follower(leader) => Some(leader),
+            _ => None,
+        }
+    }
+
+    fn leader_id_and_epoch(&self, state: &RaftState) -> LeaderIdAndEpoch {
+        LeaderIdAndEpoch {
+            leader_id: self.leader_of(state).unwrap_or(-1),
+            leader_epoch: state.epoch,
+        }
+    }
+
+    /// A random election timeout, so that voters rarely stand at the same time.
+    fn election_timeout(&self) -> Duration {
+        let timeout = self.config.election_timeout_ms;
+        Duration::from_millis(timeout + rand::thread_rng().gen_range(0..timeout))
+    }
+
+    fn deadline_for(&self, role: &Role) -> Instant {
+        let timeout = match role {
+            Role::Follower(_) => Duration::from_millis(self.config.fetch_timeout_ms),
+            _ => self.election_timeout(),
+        };
+        Instant::now() + timeout
+    }
+
+    /// Move to `role` in `epoch`, persisting the election state first.
+    fn transition(&self, state: &mut RaftState, epoch: i32, role: Role) -> Result<()> {
+        let (leader_id, voted_id) = match &role {
+            Role::Leader(_) | Role::Resigned { .. } => (self.node_id, -1),
+            Role::Follower(leader) => (*leader, -1),
+            Role::Voted(candidate) => (-1, *candidate),
+            Role::Candidate { .. } => (-1, self.node_id),
+            Role::Unattached => (-1, -1),
+        };
+        let election = ElectionState {
+            epoch,
+            leader_id,
+            voted_id,
+        };
+        election.store(state.log.partition_dir(), &self.cluster_id, &self.config)?;
+        eprintln!(
+            "Metadata quorum epoch {epoch}: node {} is {role}",
+            self.node_id
+        );
+        state.deadline = self.deadline_for(&role);
+        state.pending_snapshot = None;
+        state.epoch = epoch;
+        state.role = role;
+        self.changed.notify_all();
+        Ok(())
+    }
+
+    /// Stand for election in the next epoch, voting for this node.
+    fn become_candidate(&self, state: &mut RaftState) -> Result<()> {
+        let role = Role::Candidate {
+            granted: BTreeSet::from([self.node_id]),
+            votes_requested: false,
+        };
+        self.transition(state, state.epoch + 1, role)?;
+        self.maybe_become_leader(state)
+    }
+
+    fn maybe_become_leader(&self, state: &mut RaftState) -> Result<()> {
+        let Role::Candidate { granted, .. } = &state.role else {
+            return Ok(());
+        };
+        if granted.len() * 2 <= self.config.voters.len() {
+            return Ok(());
+        }
+        let granted = granted.clone();
+        let now = Instant::now();
+        let leader = LeaderState {
+            epoch_start_offset: state.log.end_offset(),
+            elected: now,
+            voters: self
+                .config
+                .voters
+                .iter()
+                .map(|voter| (voter.id, ReplicaState::default()))
+                .collect(),
+            observers: BTreeMap::new(),
+            unacknowledged: self
+                .config
+                .voters
+                .iter()
+                .map(|voter| voter.id)
+                .filter(|&id| id != self.node_id)
+                .collect(),
+            next_begin_quorum_epoch: now,
+        };
+        self.transition(state, state.epoch, Role::Leader(leader))?;
+
+        let voters = |ids: &mut dyn Iterator<Item = i32>| {
+            CompactArray(Some(
+                ids.map(|voter_id| LeaderChangeVoter { voter_id }).collect(),
+            ))
+        };
+        let message = LeaderChangeMessage {
+            leader_id: self.node_id,
+            voters: voters(&mut self.config.voters.iter().map(|voter| voter.id)),
+            granting_voters: voters(&mut granted.into_iter()),
+        };
+        let batch = RecordBatch::control(
+            0,
+            state.epoch,
+            now_ms(),
+            ControlRecordKey::LEADER_CHANGE,
+            message.serialize(),
+        );
+        state.log.append(batch)?;
+        self.update_high_watermark(state);
+        Ok(())
+    }
+
+    /// Commit what a majority of voters hold, once the epoch's LeaderChange record is
+    /// among it.
+    fn update_high_watermark(&self, state: &mut RaftState) {
+        let end_offset = state.log.end_offset();
+        let Role::Leader(leader) = &mut state.role else {
+            return;
+        };
+        let now = now_ms();
+        if let Some(own) = leader.voters.get_mut(&self.node_id) {
+            own.end_offset = end_offset;
+            own.last_fetch_timestamp = now;
+            own.last_caught_up_timestamp = now;
+        }
+        if let Some(committed) = leader.committed_offset() {
+            if committed > state.high_watermark {
+                state.high_watermark = committed;
+                self.changed.notify_all();
+            }
+        }
+    }
+
+    /// Follow a leader learnt of from another node, if it is newer than what this node
+    /// knows.
+    fn observe_leader(&self, state: &mut RaftState, leader: LeaderIdAndEpoch) -> Result<()> {
+        let known = leader.leader_id >= 0 && leader.leader_id != self.node_id;
+        if leader.leader_epoch > state.epoch {
+            let role = if known {
+                Role::Follower(leader.leader_id)
+            } else {
+                Role::Unattached
+            };
+            self.transition(state, leader.leader_epoch, role)
+        } else if leader.leader_epoch == state.epoch
+            && known
+            && !matches!(state.role, Role::Follower(_) | Role::Leader(_))
+        {
+            self.transition(state, state.epoch, Role::Follower(leader.leader_id))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Step down after losing touch with a majority, asking the most caught up voters
+    /// to stand first.
+    fn resign(&self, state: &mut RaftState) -> Result<()> {
+        let Role::Leader(leader) = &state.role else {
+            return Ok(());
+        };
+        let mut successors: Vec<(i32, i64)> = leader
+            .voters
+            .iter()
+            .filter(|(&id, _)| id != self.node_id)
+            .map(|(&id, replica)| (id, replica.end_offset))
+            .collect();
+        successors.sort_by_key(|&(_, end_offset)| Reverse(end_offset));
+        let role = Role::Resigned {
+            preferred_successors: successors.into_iter().map(|(id, _)| id).collect(),
+            notified: false,
+        };
+        eprintln!(
+            "Resigning as leader of metadata quorum epoch {}: a majority of voters stopped fetching",
+            state.epoch
+        );
+        self.transition(state, state.epoch, role)
+    }
+
+    /// Answer a candidate asking for this node's vote.
+    pub fn handle_vote(&self, request: &VotePartition) -> VotePartitionResponse {
+        let mut state = self.state.lock().unwrap();
+        let response = |state: &RaftState, error_code, vote_granted| {
+            let leader = self.leader_id_and_epoch(state);
+            VotePartitionResponse {
+                partition_index: request.partition_index,
+                error_code,
+                leader_id: leader.leader_id,
+                leader_epoch: leader.leader_epoch,
+                vote_granted,
+                tag_buffer: TagSection(None),
+            }
+        };
+        if request.candidate_epoch < state.epoch {
+            return response(&state, error::FENCED_LEADER_EPOCH, false);
+        }
+        if !self.is_voter(request.candidate_id) || !self.is_voter(self.node_id) {
+            return response(&state, error::INCONSISTENT_VOTER_SET, false);
+        }
+        if request.candidate_epoch > state.epoch {
+            if let Err(e) = self.transition(&mut state, request.candidate_epoch, Role::Unattached) {
+                eprintln!("Failed to persist the metadata quorum state: {e:#}");
+                return response(&state, error::UNKNOWN_SERVER_ERROR, false);
+            }
+        }
+
+        // Only a log at least as up to date as this one may lead, so that no committed
+        // record is lost
+        let up_to_date = (request.last_offset_epoch, request.last_offset)
+            >= (state.log.last_epoch(), state.log.end_offset());
+        let granted = match state.role {
+            Role::Unattached => up_to_date,
+            Role::Voted(candidate) => candidate == request.candidate_id,
+            _ => false,
+        };
+        if granted && matches!(state.role, Role::Unattached) {
+            let epoch = state.epoch;
+            if let Err(e) = self.transition(&mut state, epoch, Role::Voted(request.candidate_id)) {
+                eprintln!("Failed to persist the metadata quorum state: {e:#}");
+                return response(&state, error::UNKNOWN_SERVER_ERROR, false);
+            }
+        }
+        response(&state, error::NONE, granted)
+    }
+
+    /// Accept the leader of an epoch announcing itself.
+    pub fn handle_begin_quorum_epoch(
+        &self,
+        leader_id: i32,
+        leader_epoch: i32,
+    ) -> QuorumEpochOutcome {
+        let mut state = self.state.lock().unwrap();
+        let error_code = if leader_epoch < state.epoch {
+            error::FENCED_LEADER_EPOCH
+        } else if !self.is_voter(leader_id) {
+            error::INCONSISTENT_VOTER_SET
+        } else if leader_id == self.node_id
+            || matches!(state.role, Role::Follower(leader) if leader == leader_id && leader_epoch == state.epoch)
+        {
+            error::NONE
+        } else {
+            match self.transition(&mut state, leader_epoch, Role::Follower(leader_id)) {
+                Ok(()) => error::NONE,
+                Err(e) => {
+                    eprintln!("Failed to persist the metadata quorum state: {e:#}");
+                    error::UNKNOWN_SERVER_ERROR
+                }
+            }
+        };
+        let leader = self.leader_id_and_epoch(&state);
+        QuorumEpochOutcome {
+            error_code,
+            leader_id: leader.leader_id,
+            leader_epoch: leader.leader_epoch,
+        }
+    }
+
+    /// Prepare to succeed a leader that stepped down. Voters it prefers stand for
+    /// election sooner.
+    pub fn handle_end_quorum_epoch(
+        &self,
+        leader_id: i32,
+        leader_epoch: i32,
+        preferred_successors: &[i32],
+    ) -> QuorumEpochOutcome {
+        let mut state = self.state.lock().unwrap();
+        let error_code = if leader_epoch < state.epoch {
+            error::FENCED_LEADER_EPOCH
+        } else if !self.is_voter(leader_id) {
+            error::INCONSISTENT_VOTER_SET
+        } else {
+            let moved = if leader_epoch > state.epoch {
+                self.transition(&mut state, leader_epoch, Role::Unattached)
+            } else {
+                Ok(())
+            };
+            match moved {
+                Ok(()) => {
+                    let following =
+                        matches!(state.role, Role::Follower(leader) if leader == leader_id);
+                    if self.is_voter(self.node_id)
+                        && (following || matches!(state.role, Role::Unattached))
+                    {
+                        let max_backoff = self.config.election_backoff_max_ms;
+                        let backoff_ms = match preferred_successors
+                            .iter()
+                            .position(|&id| id == self.node_id)
+                        {
+                            Some(0) => 0,
+                            Some(position) => {
+                                max_backoff * position as u64 / preferred_successors.len() as u64
+                                    + rand::thread_rng().gen_range(0..max_backoff / 10 + 1)
+                            }
+                            None => max_backoff,
+                        };
+                        state.deadline = Instant::now() + Duration::from_millis(backoff_ms);
+                        self.changed.notify_all();
+                    }
+                    error::NONE
+                }
+                Err(e) => {
+                    eprintln!("Failed to persist the metadata quorum state: {e:#}");
+                    error::UNKNOWN_SERVER_ERROR
+                }
+            }
+        };
+        let leader = self.leader_id_and_epoch(&state);
+        QuorumEpochOutcome {
+            error_code,
+            leader_id: leader.leader_id,
+            leader_epoch: leader.leader_epoch,
+        }
+    }
+
+    /// Serve a voter or an observer fetching the metadata log, holding the fetch for up
+    /// to `max_wait_ms` while it is caught up.
+    pub fn handle_fetch(&self, request: &FetchRequest) -> FetchResponse {
+        let mut response = FetchResponse {
+            throttle_time_ms: 0,
+            error_code: error::NONE,
+            session_id: 0,
+            responses: CompactArray(Some(vec![])),
+            tag_buffer: TagSection(None),
+        };
+        if request
+            .cluster_id()
+            .is_some_and(|cluster_id| cluster_id != self.cluster_id)
+        {
+            response.error_code = error::INCONSISTENT_CLUSTER_ID;
+            return response;
+        }
+        let topics = request.topics.iter().flatten().map(|topic| {
+            let is_metadata = topic.topic.as_deref() == Some(METADATA_TOPIC);
+            let partitions = topic.partitions.iter().flatten().map(|partition| {
+                if is_metadata && partition.partition == METADATA_PARTITION {
+                    self.fetch_partition(request, partition)
+                } else {
+                    partition_data(
+                        partition.partition,
+                        error::UNKNOWN_TOPIC_OR_PARTITION,
+                        TagSection(None),
+                    )
+                }
+            });
+            FetchableTopicResponse {
+                topic: topic.topic.clone(),
+                partitions: CompactArray(Some(partitions.collect())),
+                tag_buffer: TagSection(None),
+            }
+        });
+        response.responses = CompactArray(Some(topics.collect()));
+        response
+    }
+
+    fn fetch_partition(&self, request: &FetchRequest, partition: &FetchPartition) -> PartitionData {
+        let deadline = Instant::now() + Duration::from_millis(request.max_wait_ms.max(0) as u64);
+        let mut state = self.state.lock().unwrap();
+        loop {
+            let leader = self.leader_id_and_epoch(&state);
+            let error = |error_code| {
+                let tags = PartitionData::raft_tags(None, Some(leader), None);
+                partition_data(partition.partition, error_code, tags)
+            };
+            if !matches!(state.role, Role::Leader(_)) {
+                return error(error::NOT_LEADER_OR_FOLLOWER);
+            }
+            if partition.current_leader_epoch < state.epoch {
+                return error(error::FENCED_LEADER_EPOCH);
+            }
+            if partition.current_leader_epoch > state.epoch {
+                return error(error::UNKNOWN_LEADER_EPOCH);
+            }
+
+            let mut data = partition_data(partition.partition, error::NONE, TagSection(None));
+            data.high_watermark = state.high_watermark;
+            data.last_stable_offset = state.high_watermark;
+            data.log_start_offset = state.log.start_offset();
+
+            // A follower behind the start of the log loads the snapshot first
+            if partition.fetch_offset < state.log.start_offset() {
+                if let Some(snapshot) = state.log.snapshot() {
+                    self.record_fetch(&mut state, request.replica_id, partition.fetch_offset);
+                    data.tag_buffer = PartitionData::raft_tags(None, None, Some(snapshot));
+                    return data;
+                }
+            }
+
+            // A follower whose log went further in an epoch than the leader's truncates
+            // to where the leader's log left that epoch
+            if partition.fetch_offset > 0 {
+                let end = state.log.end_offset_for_epoch(partition.last_fetched_epoch);
+                if end.epoch != partition.last_fetched_epoch
+                    || end.end_offset < partition.fetch_offset
+                {
+                    data.tag_buffer = PartitionData::raft_tags(Some(end), None, None);
+                    return data;
+                }
+            }
+
+            self.record_fetch(&mut state, request.replica_id, partition.fetch_offset);
+            if partition.fetch_offset < state.log.end_offset() {
+                let max_bytes = partition.partition_max_bytes.max(0) as usize;
+                match state.log.read(partition.fetch_offset, max_bytes) {
+                    Ok(records) => data.records = CompactBytes(Some(records)),
+                    Err(e) => {
+                        eprintln!("Failed to read the metadata log: {e:#}");
+                        return error(error::UNKNOWN_SERVER_ERROR);
+                    }
+                }
+                return data;
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                return data;
+            }
+            state = self.changed.wait_timeout(state, deadline - now).unwrap().0;
+        }
+    }
+
+    /// Serve a chunk of the snapshot `partition` asks for, read from `position` on.
+    pub fn handle_fetch_snapshot(
+        &self,
+        partition: &FetchSnapshotPartition,
+        max_bytes: usize,
+    ) -> SnapshotPartitionResponse {
+        let (leader, path) = {
+            let state = self.state.lock().unwrap();
+            let leader = self.leader_id_and_epoch(&state);
+            let error_code = if !matches!(state.role, Role::Leader(_)) {
+                error::NOT_LEADER_OR_FOLLOWER
+            } else if partition.current_leader_epoch < state.epoch {
+                error::FENCED_LEADER_EPOCH
+            } else if partition.current_leader_epoch > state.epoch {
+                error::UNKNOWN_LEADER_EPOCH
+            } else {
+                error::NONE
+            };
+            if error_code != error::NONE {
+                return SnapshotPartitionResponse::error(
+                    partition.partition,
+                    error_code,
+                    Some(leader),
+                );
+            }
+            let path = state
+                .log
+                .partition_dir()
+                .join(snapshot_file_name(partition.snapshot_id));
+            (leader, path)
+        };
+        let error = |error_code| {
+            SnapshotPartitionResponse::error(partition.partition, error_code, Some(leader))
+        };
+
+        let Ok(snapshot) = fs::read(&path) else {
+            return error(error::SNAPSHOT_NOT_FOUND);
+        };
+        let size = snapshot.len() as i64;
+        if partition.position < 0 || partition.position >= size {
+            return error(error::POSITION_OUT_OF_RANGE);
+        }
+        let start = partition.position as usize;
+        let end = snapshot.len().min(start.saturating_add(max_bytes.max(1)));
+        SnapshotPartitionResponse {
+            index: partition.partition,
+            error_code: error::NONE,
+            snapshot_id: partition.snapshot_id,
+            size,
+            position: partition.position,
+            unaligned_records: CompactBytes(Some(snapshot[start..end].to_vec())),
+            tag_buffer: SnapshotPartitionResponse::current_leader_tags(Some(leader)),
+        }
+    }
+
+    /// Note how far a replica has fetched, which may commit more of the log.
+    fn record_fetch(&self, state: &mut RaftState, replica_id: i32, fetch_offset: i64) {
+        let end_offset = state.log.end_offset();
+        let Role::Leader(leader) = &mut state.role else {
+            return;
+        };
+        let replica = match leader.voters.get_mut(&replica_id) {
+            Some(replica) => replica,
+            None => leader.observers.entry(replica_id).or_default(),
+        };
+        let now = now_ms();
+        replica.end_offset = fetch_offset;
+        replica.last_fetch_timestamp = now;
+        if fetch_offset >= end_offset {
+            replica.last_caught_up_timestamp = now;
+        }
+        // Fetching proves the voter knows the epoch
+        leader.unacknowledged.remove(&replica_id);
+        self.update_high_watermark(state);
+    }
+
+    /// Decide what the driver does next, moving on from any deadline that passed.
+    fn next_action(&self) -> Result<Action> {
+        let mut guard = self.state.lock().unwrap();
+        let state = &mut *guard;
+        loop {
+            let now = Instant::now();
+            let is_voter = self.is_voter(self.node_id);
+            match &mut state.role {
+                Role::Leader(leader) => {
+                    // Check quorum: step down when a majority stopped fetching, so that
+                    // a partitioned leader does not hold on to the epoch
+                    let check_quorum_ms = self.config.fetch_timeout_ms * 3 / 2;
+                    let since_ms = now_ms() - check_quorum_ms as i64;
+                    let fetching = leader
+                        .voters
+                        .iter()
+                        .filter(|(&id, replica)| {
+                            id == self.node_id || replica.last_fetch_timestamp >= since_ms
+                        })
+                        .count();
+                    if now.duration_since(leader.elected).as_millis() as u64 >= check_quorum_ms
+                        && fetching * 2 <= leader.voters.len()
+                    {
+                        self.resign(state)?;
+                        continue;
+                    }
+                    if !leader.unacknowledged.is_empty() && now >= leader.next_begin_quorum_epoch {
+                        leader.next_begin_quorum_epoch = now + self.election_timeout();
+                        let voters = leader.unacknowledged.iter().copied().collect();
+                        return Ok(Action::BeginQuorumEpoch(
+                            voters,
+                            self.begin_quorum_epoch_request(state.epoch),
+                        ));
+                    }
+                    return Ok(Action::Wait(Duration::from_millis(
+                        self.config.fetch_timeout_ms / 2,
+                    )));
+                }
+                Role::Resigned {
+                    preferred_successors,
+                    notified,
+                } => {
+                    if !*notified {
+                        *notified = true;
+                        let successors = preferred_successors.clone();
+                        let request = self.end_quorum_epoch_request(state.epoch, &successors);
+                        return Ok(Action::EndQuorumEpoch(successors, request));
+                    }
+                }
+                Role::Candidate {
+                    votes_requested, ..
+                } => {
+                    if !*votes_requested && now < state.deadline {
+                        *votes_requested = true;
+                        return Ok(Action::RequestVotes(self.vote_request(state)));
+                    }
+                }
+                Role::Follower(leader) => {
+                    if now < state.deadline {
+                        let leader = *leader;
+                        if let Some(snapshot) = state.pending_snapshot {
+                            return Ok(Action::FetchSnapshot(leader, snapshot));
+                        }
+                        return Ok(Action::Fetch(leader, self.fetch_request(state)));
+                    }
+                    if !is_voter {
+                        // Look for the new leader
+                        let epoch = state.epoch;
+                        self.transition(state, epoch, Role::Unattached)?;
+                        continue;
+                    }
+                }
+                Role::Unattached | Role::Voted(_) => {
+                    if !is_voter {
+                        let voters = &self.config.voters;
+                        let voter = voters[rand::thread_rng().gen_range(0..voters.len())].id;
+                        return Ok(Action::Fetch(voter, self.fetch_request(state)));
+                    }
+                }
+            }
+            if now >= state.deadline {
+                self.become_candidate(state)?;
+                continue;
+            }
+            return Ok(Action::Wait(state.deadline - now));
+        }
+    }
+
+    fn wait(&self, timeout: Duration) {
+        let state = self.state.lock().unwrap();
+        let _ = self.changed.wait_timeout(state, timeout).unwrap();
+    }
+
+    fn vote_request(&self, state: &RaftState) -> VoteRequest {
+        let partition = VotePartition {
+            partition_index: METADATA_PARTITION,
+            candidate_epoch: state.epoch,
+            candidate_id: self.node_id,
+            last_offset_epoch: state.log.last_epoch(),
+            last_offset: state.log.end_offset(),
+            tag_buffer: TagSection(None),
+        };
+        VoteRequest {
+            cluster_id: CompactString(Some(self.cluster_id.clone())),
+            topics: CompactArray(Some(vec![VoteTopic {
+                topic_name: CompactString(Some(METADATA_TOPIC.to_string())),
+                partitions: CompactArray(Some(vec![partition])),
+                tag_buffer: TagSection(None),
+            }])),
+            tag_buffer: TagSection(None),
+        }
+    }
+
+    fn begin_quorum_epoch_request(&self, epoch: i32) -> BeginQuorumEpochRequest {
+        BeginQuorumEpochRequest {
+            cluster_id: KafkaString(Some(self.cluster_id.clone())),
+            topics: KafkaArray(Some(vec![BeginQuorumEpochTopic {
+                topic_name: KafkaString(Some(METADATA_TOPIC.to_string())),
+                partitions: KafkaArray(Some(vec![BeginQuorumEpochPartition {
+                    partition_index: METADATA_PARTITION,
+                    leader_id: self.node_id,
+                    leader_epoch: epoch,
+                }])),
+            }])),
+        }
+    }
+
+    fn end_quorum_epoch_request(&self, epoch: i32, successors: &[i32]) -> EndQuorumEpochRequest {
+        EndQuorumEpochRequest {
+            cluster_id: KafkaString(Some(self.cluster_id.clone())),
+            topics: KafkaArray(Some(vec![EndQuorumEpochTopic {
+                topic_name: KafkaString(Some(METADATA_TOPIC.to_string())),
+                partitions: KafkaArray(Some(vec![EndQuorumEpochPartition {
+                    partition_index: METADATA_PARTITION,
+                    leader_id: self.node_id,
+                    leader_epoch: epoch,
+                    preferred_successors: KafkaArray(Some(successors.to_vec())),
+                }])),
+            }])),
+        }
+    }
+
+    fn fetch_request(&self, state: &RaftState) -> FetchRequest {
+        let partition = FetchPartition {
+            partition: METADATA_PARTITION,
+            current_leader_epoch: state.epoch,
+            fetch_offset: state.log.end_offset(),
+            last_fetched_epoch: state.log.last_epoch(),
+            log_start_offset: state.log.start_offset(),
+            partition_max_bytes: FETCH_MAX_BYTES,
+            tag_buffer: TagSection(None),
+        };
+        FetchRequest {
+            replica_id: self.node_id,
+            max_wait_ms: FETCH_MAX_WAIT_MS,
+            min_bytes: 1,
+            max_bytes: FETCH_MAX_BYTES,
+            isolation_level: 0,
+            session_id: 0,
+            session_epoch: -1,
+            topics: CompactArray(Some(vec![FetchTopic {
+                topic: CompactString(Some(METADATA_TOPIC.to_string())),
+                partitions: CompactArray(Some(vec![partition])),
+                tag_buffer: TagSection(None),
+            }])),
+            forgotten_topics_data: CompactArray(Some(vec![])),
+            rack_id: CompactString(Some(String::new())),
+            tag_buffer: FetchRequest::cluster_id_tags(&self.cluster_id),
+        }
+    }
+
+    fn handle_vote_response(&self, voter: i32, epoch: i32, response: VoteResponse) -> Result<()> {
+        if response.error_code != error::NONE {
+            eprintln!(
+                "Vote request to node {voter} failed with error code {}",
+                response.error_code
+            );
+            return Ok(());
+        }
+        let Some(partition) = response
+            .topics
+            .iter()
+            .flatten()
+            .flat_map(|topic| topic.partitions.iter().flatten())
+            .next()
+        else {
+            return Ok(());
+        };
+        let mut state = self.state.lock().unwrap();
+        if partition.leader_epoch > state.epoch {
+            let leader = LeaderIdAndEpoch {
+                leader_id: partition.leader_id,
+                leader_epoch: partition.leader_epoch,
+            };
+            return self.observe_leader(&mut state, leader);
+        }
+        if state.epoch != epoch || partition.error_code != error::NONE || !partition.vote_granted {
+            return Ok(());
+        }
+        if let Role::Candidate { granted, .. } = &mut state.role {
+            granted.insert(voter);
+        }
+        self.maybe_become_leader(&mut state)
+    }
+
+    /// Handle the answer to BeginQuorumEpoch or EndQuorumEpoch.
+    fn handle_quorum_epoch_response(
+        &self,
+        voter: i32,
+        error_code: i16,
+        leader: Option<LeaderIdAndEpoch>,
+    ) -> Result<()> {
+        let mut state = self.state.lock().unwrap();
+        if let Some(leader) = leader.filter(|leader| leader.leader_epoch > state.epoch) {
+            return self.observe_leader(&mut state, leader);
+        }
+        if let Role::Leader(leader) = &mut state.role {
+            if error_code == error::NONE {
+                leader.unacknowledged.remove(&voter);
+            }
+        }
+        Ok(())
+    }
+
+    /// Append what the leader sent. Returns whether the fetch succeeded, so that the
+    /// driver backs off otherwise.
+    fn handle_fetch_response(&self, source: i32, response: FetchResponse) -> Result<bool> {
+        if response.error_code != error::NONE {
+            eprintln!(
+                "Metadata fetch from node {source} failed with error code {}",
+                response.error_code
+            );
+            return Ok(false);
+        }
+        let Some(partition) = response
+            .responses
+            .iter()
+            .flatten()
+            .filter(|topic| topic.topic.as_deref() == Some(METADATA_TOPIC))
+            .flat_map(|topic| topic.partitions.iter().flatten())
+            .find(|partition| partition.partition_index == METADATA_PARTITION)
+        else {
+            return Ok(false);
+        };
+
+        let mut state = self.state.lock().unwrap();
+        match partition.error_code {
+            error::NONE => {}
+            error::FENCED_LEADER_EPOCH
+            | error::NOT_LEADER_OR_FOLLOWER
+            | error::UNKNOWN_LEADER_EPOCH => {
+                if let Some(leader) = partition.current_leader() {
+                    self.observe_leader(&mut state, leader)?;
+                }
+                return Ok(false);
+            }
+            code => {
+                eprintln!("Metadata fetch from node {source} failed with error code {code}");
+                return Ok(false);
+            }
+        }
+
+        // Only the leader of the epoch answers without error
+        if !matches!(state.role, Role::Follower(leader) if leader == source) {
+            let epoch = state.epoch;
+            self.transition(&mut state, epoch, Role::Follower(source))?;
+        }
+        if let Some(diverging) = partition.diverging_epoch() {
+            let own = state.log.end_offset_for_epoch(diverging.epoch);
+            let offset = diverging.end_offset.min(own.end_offset);
+            eprintln!("Truncating the metadata log to offset {offset} to match the leader's");
+            state.log.truncate_to(offset)?;
+        } else if let Some(snapshot) = partition.snapshot_id() {
+            state.pending_snapshot = Some(snapshot);
+        } else if let Some(records) = partition.records.as_deref() {
+            state.log.append_raw(records)?;
+        }
+        // A follower cannot have more committed records than it holds
+        let high_watermark = partition.high_watermark.min(state.log.end_offset());
+        if high_watermark > state.high_watermark {
+            state.high_watermark = high_watermark;
+        }
+        state.deadline = self.deadline_for(&state.role);
+        self.changed.notify_all();
+        Ok(true)
+    }
+
+    /// Fetch the snapshot from the leader chunk by chunk and install it in place of the
+    /// log it covers.
+    fn fetch_snapshot(
+        &self,
+        client: &mut NetworkClient,
+        source: i32,
+        snapshot_id: SnapshotId,
+    ) -> Result<bool> {
+        let epoch = self.state.lock().unwrap().epoch;
+        let mut snapshot = vec![];
+        loop {
+            let request = FetchSnapshotRequest {
+                replica_id: self.node_id,
+                max_bytes: FETCH_MAX_BYTES,
+                topics: CompactArray(Some(vec![FetchSnapshotTopic {
+                    name: CompactString(Some(METADATA_TOPIC.to_string())),
+                    partitions: CompactArray(Some(vec![FetchSnapshotPartition {
+                        partition: METADATA_PARTITION,
+                        current_leader_epoch: epoch,
+                        snapshot_id,
+                        position: snapshot.len() as i64,
+                        tag_buffer: TagSection(None),
+                    }])),
+                    tag_buffer: TagSection(None),
+                }])),
+                tag_buffer: FetchSnapshotRequest::cluster_id_tags(&self.cluster_id),
+            };
+            let response: FetchSnapshotResponse =
+                client.send(FETCH_SNAPSHOT_API_KEY, 0, &request)?;
+            if response.error_code != error::NONE {
+                bail!(
+                    "snapshot fetch failed with error code {}",
+                    response.error_code
+                );
+            }
+            let Some(partition) = response
+                .topics
+                .iter()
+                .flatten()
+                .flat_map(|topic| topic.partitions.iter().flatten())
+                .find(|partition| partition.index == METADATA_PARTITION)
+            else {
+                return Ok(false);
+            };
+            if partition.error_code != error::NONE {
+                eprintln!(
+                    "Snapshot fetch from node {source} failed with error code {}",
+                    partition.error_code
+                );
+                // Fetch again to learn where the leader's log starts now
+                let mut state = self.state.lock().unwrap();
+                state.pending_snapshot = None;
+                if let Some(leader) = partition.current_leader() {
+                    self.observe_leader(&mut state, leader)?;
+                }
+                return Ok(false);
+            }
+            snapshot.extend(partition.unaligned_records.as_deref().unwrap_or_default());
+            if snapshot.len() as i64 >= partition.size {
+                break;
+            }
+        }
+
+        let mut state = self.state.lock().unwrap();
+        if state.epoch != epoch || state.pending_snapshot != Some(snapshot_id) {
+            return Ok(false);
+        }
+        state.log.install_snapshot(snapshot_id, &snapshot)?;
+        eprintln!(
+            "Loaded metadata snapshot {} from node {source}",
+            snapshot_file_name(snapshot_id)
+        );
+        state.pending_snapshot = None;
+        state.high_watermark = state.high_watermark.max(snapshot_id.end_offset);
+        state.deadline = self.deadline_for(&state.role);
+        self.changed.notify_all();
+        Ok(true)
+    }
+
+    /// Connect to a voter's listener for the quorum.
+    fn connect(&self, voter: i32, timeout: Duration) -> Result<NetworkClient> {
+        let address = self
+            .config
+            .voter(voter)
+            .with_context(|| format!("node {voter} is not a voter"))?;
+        NetworkClient::connect(
+            &address.host,
+            address.port,
+            timeout,
+            format!("raft-client-{}", self.node_id),
+        )
+    }
+}
+
+/// Start electing and replicating the metadata log, and applying what the quorum
+/// commits to the image of the broker.
+pub fn start(broker: &Arc<Broker>) {
+    let driver = Arc::clone(broker);
+    thread::spawn(move || drive(&driver));
+    let publisher = Arc::clone(broker);
+    thread::spawn(move || loop {
+        publisher
+            .raft
+            .wait_for_high_watermark(publisher.applied_offset(), Duration::from_secs(1));
+        publisher.publish_metadata();
+    });
+}
+
+/// Run the driver: stand for election, announce leadership and fetch from the leader
+/// as the state of the node requires.
+fn drive(broker: &Broker) {
+    let raft = &broker.raft;
+    let request_timeout = Duration::from_millis(raft.config.election_timeout_ms);
+    let fetch_timeout =
+        Duration::from_millis(FETCH_MAX_WAIT_MS as u64 + raft.config.fetch_timeout_ms);
+    let mut fetcher: Option<(i32, NetworkClient)> = None;
+    let mut last_error: Option<String> = None;
+    loop {
+        let action = match raft.next_action() {
+            Ok(action) => action,
+            Err(e) => {
+                eprintln!("Metadata quorum state change failed: {e:#}");
+                thread::sleep(RETRY_BACKOFF);
+                continue;
+            }
+        };
+        let result = match action {
+            Action::Wait(timeout) => {
+                raft.wait(timeout);
+                Ok(())
+            }
+            Action::RequestVotes(request) => {
+                let epoch = request
+                    .topics
+                    .iter()
+                    .flatten()
+                    .flat_map(|t| t.partitions.iter().flatten())
+                    .map(|p| p.candidate_epoch)
+                    .next()
+                    .unwrap_or_default();
+                let voters = raft
+                    .config
+                    .voters
+                    .iter()
+                    .map(|voter| voter.id)
+                    .filter(|&id| id != raft.node_id);
+                thread::scope(|scope| {
+                    for voter in voters {
+                        let request = &request;
+                        scope.spawn(move || {
+                            let response = raft
+                                .connect(voter, request_timeout)
+                                .and_then(|mut client| client.send(VOTE_API_KEY, 0, request))
+                                .and_then(|response| {
+                                    raft.handle_vote_response(voter, epoch, response)
+                                });
+                            if let Err(e) = response {
+                                eprintln!("Vote request to node {voter} failed: {e:#}");
+                            }
+                        });
+                    }
+                });
+                Ok(())
+            }
+            Action::BeginQuorumEpoch(voters, request) => {
+                thread::scope(|scope| {
+                    for voter in voters {
+                        let request = &request;
+                        scope.spawn(move || {
+                            let response = raft
+                                .connect(voter, request_timeout)
+                                .and_then(|mut client| {
+                                    client.send::<BeginQuorumEpochResponse>(
+                                        BEGIN_QUORUM_EPOCH_API_KEY,
+                                        0,
+                                        request,
+                                    )
+                                })
+                                .and_then(|response| {
+                                    let (error_code, leader) =
+                                        quorum_epoch_outcome(response.error_code, &response.topics);
+                                    raft.handle_quorum_epoch_response(voter, error_code, leader)
+                                });
+                            if let Err(e) = response {
+                                eprintln!("BeginQuorumEpoch to node {voter} failed: {e:#}");
+                            }
+                        });
+                    }
+                });
+                Ok(())
+            }
+            Action::EndQuorumEpoch(voters, request) => {
+                thread::scope(|scope| {
+                    for voter in voters {
+                        let request = &request;
+                        scope.spawn(move || {
+                            let response = raft
+                                .connect(voter, request_timeout)
+                                .and_then(|mut client| {
+                                    client.send::<EndQuorumEpochResponse>(
+                                        END_QUORUM_EPOCH_API_KEY,
+                                        0,
+                                        request,
+                                    )
+                                })
+                                .and_then(|response| {
+                                    let (error_code, leader) =
+                                        quorum_epoch_outcome(response.error_code, &response.topics);
+                                    raft.handle_quorum_epoch_response(voter, error_code, leader)
+                                });
+                            if let Err(e) = response {
+                                eprintln!("EndQuorumEpoch to node {voter} failed: {e:#}");
+                            }
+                        });
+                    }
+                });
+                Ok(())
+            }
+            Action::Fetch(source, request) => {
+                let client = match fetcher.take() {
+                    Some((node, client)) if node == source => Ok(client),
+                    _ => raft.connect(source, fetch_timeout),
+                };
+                client
+                    .and_then(|mut client| {
+                        let response = client.send(FETCH_API_KEY, FETCH_VERSION, &request)?;
+                        fetcher = Some((source, client));
+                        raft.handle_fetch_response(source, response)
+                    })
+                    .map(|fetched| {
+                        if !fetched {
+                            thread::sleep(RETRY_BACKOFF);
+                        }
+                    })
+                    .map_err(|e| e.context(format!("metadata fetch from node {source} failed")))
+            }
+            Action::FetchSnapshot(source, snapshot_id) => {
+                let client = match fetcher.take() {
+                    Some((node, client)) if node == source => Ok(client),
+                    _ => raft.connect(source, fetch_timeout),
+                };
+                client
+                    .and_then(|mut client| {
+                        let fetched = raft.fetch_snapshot(&mut client, source, snapshot_id)?;
+                        fetcher = Some((source, client));
+                        Ok(fetched)
+                    })
+                    .map(|fetched| {
+                        if !fetched {
+                            thread::sleep(RETRY_BACKOFF);
+                        }
+                    })
+                    .map_err(|e| e.context(format!("snapshot fetch from node {source} failed")))
+            }
+        };
+        match result {
+            Ok(()) => last_error = None,
+            Err(e) => {
+                // An unreachable node fails every retry, so only report a new error
+                let message = format!("{e:#}");
+                if last_error.as_ref() != Some(&message) {
+                    eprintln!("{message}");
+                    last_error = Some(message);
+                }
+                thread::sleep(RETRY_BACKOFF);
+            }
+        }
+    }
+}
+
+/// Error and known leader of the metadata partition in a quorum epoch response.
+fn quorum_epoch_outcome(
+    error_code: i16,
+    topics: &KafkaArray<QuorumEpochTopicResponse>,
+) -> (i16, Option<LeaderIdAndEpoch>) {
+    let partition = topics
+        .iter()
+        .flatten()
+        .flat_map(|topic| topic.partitions.iter().flatten())
+        .next();
+    match partition {
+        Some(partition) if error_code == error::NONE => (
+            partition.error_code,
+            Some(LeaderIdAndEpoch {
+                leader_id: partition.leader_id,
+                leader_epoch: partition.leader_epoch,
+            }),
+        ),
+        _ => (error_code, None),
+    }
+}
+
+fn partition_data(partition_index: i32, error_code: i16, tag_buffer: TagSection) -> PartitionData {
+    PartitionData {
+        partition_index,
+        error_code,
+        high_watermark: -1,
+        last_stable_offset: -1,
+        log_start_offset: -1,
+        aborted_transactions: CompactArray(Some(vec![])),
+        preferred_read_replica: -1,
+        records: CompactBytes(None),
+        tag_buffer,
+    }
+}
+
+fn now_ms() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as i64)
+        .unwrap_or_default()
+}
+
+/// Election state that must survive restarts, kept in `quorum-state` next to the
+/// metadata log in the JSON format of Apache Kafka.
+#[derive(Debug, Clone, Copy)]
+struct ElectionState {
+    epoch: i32,
+    leader_id: i32,
+    voted_id: i32,
+}
+
+impl ElectionState {
+    const FILE_NAME: &'static str = "quorum-state";
+
+    fn load(partition_dir: &Path) -> Result<Self> {
+        let path = partition_dir.join(Self::FILE_NAME);
+        let mut state = ElectionState {
+            epoch: 0,
+            leader_id: -1,
+            voted_id: -1,
+        };
+        if !path.exists() {
+            return Ok(state);
+        }
+        let content = fs::read_to_string(&path)
+            .with_context(|| format!("failed to read {}", path.display()))?;
+        let field = |name: &str| -> Result<i32> {
+            let key = format!("\"{name}\":");
+            let start = content
+                .find(&key)
+                .with_context(|| format!("{} has no {name}", path.display()))?
+                + key.len();
+            let value = content[start..]
+                .trim_start()
+                .split(|c: char| c != '-' && !c.is_ascii_digit())
+                .next()
+                .unwrap_or_default();
+            value
+                .parse()
+                .with_context(|| format!("invalid {name} in {}", path.display()))
+        };
+        state.epoch = field("leaderEpoch")?;
+        state.leader_id = field("leaderId")?;
+        state.voted_id = field("votedId")?;
+        Ok(state)
+    }
+
+    /// Durably replace the stored state.
+    fn store(&self, partition_dir: &Path, cluster_id: &str, config: &QuorumConfig) -> Result<()> {
+        let voters = config
+            .voters
+            .iter()
+            .map(|voter| format!("{{\"voterId\":{}}}", voter.id))
+            .collect::<Vec<_>>()
+            .join(",");
+        let content = format!(
+            "{{\"clusterId\":\"{cluster_id}\",\"leaderId\":{},\"leaderEpoch\":{},\"votedId\":{},\"appliedOffset\":0,\"currentVoters\":[{voters}],\"data_version\":0}}",
+            self.leader_id, self.epoch, self.voted_id
+        );
+        fs::create_dir_all(partition_dir)
+            .with_context(|| format!("failed to create {}", partition_dir.display()))?;
+        let path = partition_dir.join(Self::FILE_NAME);
+        let temporary = partition_dir.join(format!("{}.tmp", Self::FILE_NAME));
+        let write = || -> std::io::Result<()> {
+            let file = fs::File::create(&temporary)?;
+            std::io::Write::write_all(&mut &file, content.as_bytes())?;
+            file.sync_all()?;
+            fs::rename(&temporary, &path)
+        };
+        write().with_context(|| format!("failed to write {}", path.display()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn leader(epoch_start_offset: i64, end_offsets: &[i64]) -> LeaderState {
+        let voters = end_offsets
+            .iter()
+            .enumerate()
+            .map(|(id, &end_offset)| {
+                let replica = ReplicaState {
+                    end_offset,
+                    ..ReplicaState::default()
+                };
+                (id as i32 + 1, replica)
+            })
+            .collect();
+        LeaderState {
+            epoch_start_offset,
+            elected: Instant::now(),
+            voters,
+            observers: BTreeMap::new(),
+            unacknowledged: BTreeSet::new(),
+            next_begin_quorum_epoch: Instant::now(),
+        }
+    }
+
+    #[test]
+    fn commits_what_a_majority_of_voters_hold() {
+        assert_eq!(leader(0, &[12]).committed_offset(), Some(12));
+        assert_eq!(leader(0, &[12, 7, 3]).committed_offset(), Some(7));
+        assert_eq!(leader(0, &[3, 12, 7]).committed_offset(), Some(7));
+        // An even number of voters needs more than half of them
+        assert_eq!(leader(0, &[12, 10, 7, 3]).committed_offset(), Some(7));
+        assert_eq!(leader(0, &[12, 10, 7, 3, 1]).committed_offset(), Some(7));
+    }
+
+    #[test]
+    fn waits_for_voters_that_have_not_fetched() {
+        assert_eq!(leader(0, &[12, -1, 5]).committed_offset(), Some(5));
+        assert_eq!(leader(0, &[12, -1, -1]).committed_offset(), None);
+        assert_eq!(leader(0, &[12, 12, -1, -1, -1]).committed_offset(), None);
+    }
+
+    #[test]
+    fn commits_nothing_before_the_leader_change_record() {
+        // The record opening the epoch is at offset 10, so a majority must be past it
+        assert_eq!(leader(10, &[11, 10, 10]).committed_offset(), None);
+        assert_eq!(leader(10, &[11, 11, 10]).committed_offset(), Some(11));
+    }
+}
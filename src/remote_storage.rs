@@ -0,0 +1,1022 @@
+use std::{
+    fmt::{self, Display},
+    fs,
+    io::{ErrorKind, Read, Seek, SeekFrom},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    thread,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{Context, Result};
+use uuid::Uuid;
+
+use crate::{
+    broker::Broker,
+    config::RemoteLogConfig,
+    config_registry::{ConfigResolver, LogConfig},
+    log_manager::{self, SegmentInfo, TopicPartition},
+    metadata::random_uuid,
+};
+
+/// Directory of the remote storage holding the remote log metadata of every partition.
+const REMOTE_LOG_METADATA_DIR: &str = "__remote_log_metadata";
+/// File of the remote log metadata of a partition listing its remote segments.
+const SEGMENTS_FILE: &str = "remote-log-segment-metadata";
+/// File of the remote log metadata of a partition holding its log start offset.
+const LOG_START_OFFSET_FILE: &str = "remote-log-start-offset";
+/// Bytes of batches between two entries of the offset and time indexes, Apache Kafka's
+/// default `index.interval.bytes`.
+const INDEX_INTERVAL_BYTES: usize = 4096;
+
+/// A partition, told apart from the partitions of an earlier topic of the same name.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct TopicIdPartition {
+    pub topic_id: Uuid,
+    pub partition: TopicPartition,
+}
+
+impl TopicIdPartition {
+    pub fn new(topic_id: Uuid, partition: TopicPartition) -> Self {
+        TopicIdPartition {
+            topic_id,
+            partition,
+        }
+    }
+}
+
+impl Display for TopicIdPartition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}-{}", self.partition, self.topic_id.simple())
+    }
+}
+
+/// Where a remote segment stands in its lifecycle. Only copied segments are read.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RemoteLogSegmentState {
+    CopySegmentStarted,
+    CopySegmentFinished,
+    DeleteSegmentStarted,
+}
+
+impl RemoteLogSegmentState {
+    pub fn code(&self) -> i8 {
+        match self {
+            RemoteLogSegmentState::CopySegmentStarted => 0,
+            RemoteLogSegmentState::CopySegmentFinished => 1,
+            RemoteLogSegmentState::DeleteSegmentStarted => 2,
+        }
+    }
+
+    pub fn from_code(code: i8) -> Option<Self> {
+        match code {
+            0 => Some(RemoteLogSegmentState::CopySegmentStarted),
+            1 => Some(RemoteLogSegmentState::CopySegmentFinished),
+            2 => Some(RemoteLogSegmentState::DeleteSegmentStarted),
+            _ => None,
+        }
+    }
+}
+
+/// A segment of a partition in remote storage.
+#[derive(Debug, Clone)]
+pub struct RemoteLogSegmentMetadata {
+    pub segment_id: Uuid,
+    pub start_offset: i64,
+    pub end_offset: i64,
+    pub max_timestamp: i64,
+    pub size: u64,
+    /// Broker that copied the segment
+    pub broker_id: i32,
+    /// Leader epochs of its records with the offset each starts at
+    pub leader_epochs: Vec<(i32, i64)>,
+    pub state: RemoteLogSegmentState,
+}
+
+impl RemoteLogSegmentMetadata {
+    /// `<id> <state> <start> <end> <max timestamp> <size> <broker> <epoch>:<offset>,...`
+    fn to_line(&self) -> String {
+        let epochs: Vec<String> = self
+            .leader_epochs
+            .iter()
+            .map(|(epoch, offset)| format!("{epoch}:{offset}"))
+            .collect();
+        format!(
+            "{} {} {} {} {} {} {} {}",
+            self.segment_id.simple(),
+            self.state.code(),
+            self.start_offset,
+            self.end_offset,
+            self.max_timestamp,
+            self.size,
+            self.broker_id,
+            if epochs.is_empty() {
+                "-".to_string()
+            } else {
+                epochs.join(",")
+            }
+        )
+    }
+
+    fn from_line(line: &str) -> Option<Self> {
+        let [id, state, start, end, max_timestamp, size, broker_id, epochs] =
+            line.split(' ').collect::<Vec<_>>()[..]
+        else {
+            return None;
+        };
+        let leader_epochs = match epochs {
+            "-" => vec![],
+            epochs => epochs
+                .split(',')
+                .map(|entry| {
+                    let (epoch, offset) = entry.split_once(':')?;
+                    Some((epoch.parse().ok()?, offset.parse().ok()?))
+                })
+                .collect::<Option<_>>()?,
+        };
+        Some(RemoteLogSegmentMetadata {
+            segment_id: Uuid::parse_str(id).ok()?,
+            start_offset: start.parse().ok()?,
+            end_offset: end.parse().ok()?,
+            max_timestamp: max_timestamp.parse().ok()?,
+            size: size.parse().ok()?,
+            broker_id: broker_id.parse().ok()?,
+            leader_epochs,
+            state: RemoteLogSegmentState::from_code(state.parse().ok()?)?,
+        })
+    }
+}
+
+/// Indexes copied along with a segment.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IndexType {
+    /// Position of the batches ending at some offsets, as pairs of 4-byte relative
+    /// offsets and positions
+    Offset,
+    /// Offset of the largest timestamp so far at some batches, `(timestamp, relative
+    /// offset)` entries of 8 and 4 bytes
+    Timestamp,
+    /// Leader epochs of the records, as a leader epoch checkpoint
+    LeaderEpoch,
+}
+
+impl IndexType {
+    fn extension(&self) -> &'static str {
+        match self {
+            IndexType::Offset => "index",
+            IndexType::Timestamp => "timeindex",
+            IndexType::LeaderEpoch => "leader-epoch-checkpoint",
+        }
+    }
+}
+
+/// What is copied to remote storage for a closed segment.
+#[derive(Debug)]
+pub struct LogSegmentData {
+    pub log_segment: PathBuf,
+    pub offset_index: Vec<u8>,
+    pub time_index: Vec<u8>,
+    pub leader_epoch_index: Vec<u8>,
+}
+
+impl LogSegmentData {
+    fn index(&self, index_type: IndexType) -> &[u8] {
+        match index_type {
+            IndexType::Offset => &self.offset_index,
+            IndexType::Timestamp => &self.time_index,
+            IndexType::LeaderEpoch => &self.leader_epoch_index,
+        }
+    }
+}
+
+/// Storage segments are tiered to, after Apache Kafka's `RemoteStorageManager`.
+pub trait RemoteStorageManager: Send + Sync {
+    /// Copy a segment along with its indexes. A copy cut short may leave some of them
+    /// behind, which deleting the segment cleans up.
+    fn copy_log_segment_data(
+        &self,
+        partition: &TopicIdPartition,
+        metadata: &RemoteLogSegmentMetadata,
+        data: &LogSegmentData,
+    ) -> Result<()>;
+
+    /// Bytes of a segment from `start_position` on.
+    fn fetch_log_segment(
+        &self,
+        partition: &TopicIdPartition,
+        metadata: &RemoteLogSegmentMetadata,
+        start_position: usize,
+    ) -> Result<Vec<u8>>;
+
+    fn fetch_index(
+        &self,
+        partition: &TopicIdPartition,
+        metadata: &RemoteLogSegmentMetadata,
+        index_type: IndexType,
+    ) -> Result<Vec<u8>>;
+
+    /// Delete a segment and its indexes, succeeding if they are gone already.
+    fn delete_log_segment_data(
+        &self,
+        partition: &TopicIdPartition,
+        metadata: &RemoteLogSegmentMetadata,
+    ) -> Result<()>;
+}
+
+/// Remote storage in a local directory, standing for an object store the way Apache
+/// Kafka's `LocalTieredStorage` does: a directory per partition holding the files of
+/// each segment, named after its start offset and id.
+pub struct LocalTieredStorage {
+    dir: PathBuf,
+}
+
+impl LocalTieredStorage {
+    pub fn new(dir: PathBuf) -> Self {
+        LocalTieredStorage { dir }
+    }
+
+    fn file(
+        &self,
+        partition: &TopicIdPartition,
+        metadata: &RemoteLogSegmentMetadata,
+        extension: &str,
+    ) -> PathBuf {
+        self.dir.join(partition.to_string()).join(format!(
+            "{:020}-{}.{extension}",
+            metadata.start_offset,
+            metadata.segment_id.simple()
+        ))
+    }
+}
+
+impl RemoteStorageManager for LocalTieredStorage {
+    fn copy_log_segment_data(
+        &self,
+        partition: &TopicIdPartition,
+        metadata: &RemoteLogSegmentMetadata,
+        data: &LogSegmentData,
+    ) -> Result<()> {
+        let dir = self.dir.join(partition.to_string());
+        fs::create_dir_all(&dir).with_context(|| format!("failed to create {}", dir.display()))?;
+        for index_type in [
+            IndexType::Offset,
+            IndexType::Timestamp,
+            IndexType::LeaderEpoch,
+        ] {
+            let path = self.file(partition, metadata, index_type.extension());
+            write_file(&path, data.index(index_type))?;
+        }
+        // The segment goes last, so that its presence tells the copy is complete
+        let content = fs::read(&data.log_segment)
+            .with_context(|| format!("failed to read {}", data.log_segment.display()))?;
+        write_file(&self.file(partition, metadata, "log"), &content)
+    }
+
+    fn fetch_log_segment(
+        &self,
+        partition: &TopicIdPartition,
+        metadata: &RemoteLogSegmentMetadata,
+        start_position: usize,
+    ) -> Result<Vec<u8>> {
+        let path = self.file(partition, metadata, "log");
+        let mut content = vec![];
+        fs::File::open(&path)
+            .and_then(|mut file| {
+                file.seek(SeekFrom::Start(start_position as u64))?;
+                file.read_to_end(&mut content)
+            })
+            .with_context(|| format!("failed to read {}", path.display()))?;
+        Ok(content)
+    }
+
+    fn fetch_index(
+        &self,
+        partition: &TopicIdPartition,
+        metadata: &RemoteLogSegmentMetadata,
+        index_type: IndexType,
+    ) -> Result<Vec<u8>> {
+        let path = self.file(partition, metadata, index_type.extension());
+        fs::read(&path).with_context(|| format!("failed to read {}", path.display()))
+    }
+
+    fn delete_log_segment_data(
+        &self,
+        partition: &TopicIdPartition,
+        metadata: &RemoteLogSegmentMetadata,
+    ) -> Result<()> {
+        let extensions = [
+            "log",
+            IndexType::Offset.extension(),
+            IndexType::Timestamp.extension(),
+            IndexType::LeaderEpoch.extension(),
+        ];
+        for extension in extensions {
+            let path = self.file(partition, metadata, extension);
+            match fs::remove_file(&path) {
+                Err(e) if e.kind() != ErrorKind::NotFound => {
+                    return Err(e).with_context(|| format!("failed to delete {}", path.display()))
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Write a file of the remote storage, replacing any previous one atomically.
+fn write_file(path: &Path, content: &[u8]) -> Result<()> {
+    let temp = path.with_extension("tmp");
+    fs::write(&temp, content)
+        .and_then(|()| fs::rename(&temp, path))
+        .with_context(|| format!("failed to write {}", path.display()))
+}
+
+/// Remote segments of each partition and the offset its log starts at. Apache Kafka
+/// shares them among brokers through the `__remote_log_metadata` topic; here they are
+/// files next to the remote storage, which every broker reaches.
+struct RemoteLogMetadataManager {
+    dir: PathBuf,
+}
+
+impl RemoteLogMetadataManager {
+    fn partition_dir(&self, partition: &TopicIdPartition) -> PathBuf {
+        self.dir.join(partition.to_string())
+    }
+
+    /// Segments of a partition in every state, by start offset.
+    fn segments(&self, partition: &TopicIdPartition) -> Result<Vec<RemoteLogSegmentMetadata>> {
+        let path = self.partition_dir(partition).join(SEGMENTS_FILE);
+        let mut segments =
+            log_manager::read_checkpoint_lines(&path, RemoteLogSegmentMetadata::from_line)?
+                .unwrap_or_default();
+        segments.sort_by_key(|segment| segment.start_offset);
+        Ok(segments)
+    }
+
+    fn write_segments(
+        &self,
+        partition: &TopicIdPartition,
+        segments: &[RemoteLogSegmentMetadata],
+    ) -> Result<()> {
+        let dir = self.partition_dir(partition);
+        fs::create_dir_all(&dir).with_context(|| format!("failed to create {}", dir.display()))?;
+        let lines: Vec<String> = segments.iter().map(|segment| segment.to_line()).collect();
+        let path = dir.join(SEGMENTS_FILE);
+        log_manager::write_checkpoint_lines(&path, &lines)
+            .with_context(|| format!("failed to write {}", path.display()))
+    }
+
+    /// Add a segment, or replace the one of the same id.
+    fn put_segment(
+        &self,
+        partition: &TopicIdPartition,
+        metadata: &RemoteLogSegmentMetadata,
+    ) -> Result<()> {
+        let mut segments = self.segments(partition)?;
+        segments.retain(|segment| segment.segment_id != metadata.segment_id);
+        segments.push(metadata.clone());
+        self.write_segments(partition, &segments)
+    }
+
+    fn remove_segment(&self, partition: &TopicIdPartition, segment_id: Uuid) -> Result<()> {
+        let mut segments = self.segments(partition)?;
+        segments.retain(|segment| segment.segment_id != segment_id);
+        self.write_segments(partition, &segments)
+    }
+
+    /// Offset below which records of the partition were deleted, 0 if none were.
+    fn log_start_offset(&self, partition: &TopicIdPartition) -> Result<i64> {
+        let path = self.partition_dir(partition).join(LOG_START_OFFSET_FILE);
+        let entries = log_manager::read_checkpoint_lines(&path, |line| line.parse().ok())?;
+        Ok(entries
+            .and_then(|entries| entries.first().copied())
+            .unwrap_or_default())
+    }
+
+    fn set_log_start_offset(&self, partition: &TopicIdPartition, offset: i64) -> Result<()> {
+        let dir = self.partition_dir(partition);
+        fs::create_dir_all(&dir).with_context(|| format!("failed to create {}", dir.display()))?;
+        let path = dir.join(LOG_START_OFFSET_FILE);
+        log_manager::write_checkpoint_lines(&path, &[offset.to_string()])
+            .with_context(|| format!("failed to write {}", path.display()))
+    }
+}
+
+/// Tiering of the partitions of topics with `remote.storage.enable`. Leaders copy their
+/// closed segments holding committed records to remote storage, after which local
+/// retention may delete them; records below the local log start offset are then
+/// served from remote storage until `retention.ms` or `retention.bytes` expire them.
+pub struct RemoteLogManager {
+    storage: Box<dyn RemoteStorageManager>,
+    metadata: RemoteLogMetadataManager,
+    /// Held while the remote log metadata is rewritten
+    writer: Mutex<()>,
+}
+
+impl RemoteLogManager {
+    pub fn new(config: &RemoteLogConfig) -> Self {
+        RemoteLogManager {
+            storage: Box::new(LocalTieredStorage::new(config.storage_dir.clone())),
+            metadata: RemoteLogMetadataManager {
+                dir: config.storage_dir.join(REMOTE_LOG_METADATA_DIR),
+            },
+            writer: Mutex::new(()),
+        }
+    }
+
+    /// Segments of a partition copied to remote storage and not deleted since, oldest
+    /// first.
+    pub fn copied_segments(
+        &self,
+        partition: &TopicIdPartition,
+    ) -> Result<Vec<RemoteLogSegmentMetadata>> {
+        let log_start_offset = self.metadata.log_start_offset(partition)?;
+        let mut segments = self.metadata.segments(partition)?;
+        segments.retain(|segment| {
+            segment.state == RemoteLogSegmentState::CopySegmentFinished
+                && segment.end_offset >= log_start_offset
+        });
+        Ok(segments)
+    }
+
+    /// Offset of the last record of a partition in remote storage, -1 if it holds none.
+    pub fn highest_offset(&self, partition: &TopicIdPartition) -> Result<i64> {
+        // Deleting expired segments leaves the later ones, so any segment counts
+        Ok(self
+            .metadata
+            .segments(partition)?
+            .iter()
+            .filter(|segment| segment.state != RemoteLogSegmentState::CopySegmentStarted)
+            .map(|segment| segment.end_offset)
+            .max()
+            .unwrap_or(-1))
+    }
+
+    /// First offset of a partition remote storage serves, `None` if it holds none.
+    pub fn log_start_offset(&self, partition: &TopicIdPartition) -> Result<Option<i64>> {
+        let log_start_offset = self.metadata.log_start_offset(partition)?;
+        Ok(self
+            .copied_segments(partition)?
+            .first()
+            .map(|first| first.start_offset.max(log_start_offset)))
+    }
+
+    /// Leader epochs of the records of a partition in remote storage, with the offset
+    /// each starts at.
+    pub fn leader_epochs(&self, partition: &TopicIdPartition) -> Result<Vec<(i32, i64)>> {
+        let mut epochs: Vec<(i32, i64)> = vec![];
+        for segment in self.copied_segments(partition)? {
+            for &(epoch, start_offset) in &segment.leader_epochs {
+                if epochs.last().map_or(true, |&(last, _)| last < epoch) {
+                    epochs.push((epoch, start_offset));
+                }
+            }
+        }
+        Ok(epochs)
+    }
+
+    /// Batches of a partition from the one holding `offset` on, read from the remote
+    /// segment holding it and adding up to at most `max_bytes` unless the first batch
+    /// alone is larger. `None` if no remote segment holds the offset.
+    pub fn read(
+        &self,
+        partition: &TopicIdPartition,
+        offset: i64,
+        max_bytes: usize,
+    ) -> Result<Option<Vec<u8>>> {
+        if offset < self.metadata.log_start_offset(partition)? {
+            return Ok(None);
+        }
+        let segments = self.copied_segments(partition)?;
+        let Some(segment) = segments
+            .iter()
+            .find(|segment| (segment.start_offset..=segment.end_offset).contains(&offset))
+        else {
+            return Ok(None);
+        };
+        let index = self
+            .storage
+            .fetch_index(partition, segment, IndexType::Offset)?;
+        let position = lookup_position(&index, offset - segment.start_offset);
+        let content = self
+            .storage
+            .fetch_log_segment(partition, segment, position)?;
+        let mut records = vec![];
+        log_manager::read_batches(&content, offset, i64::MAX, max_bytes, &mut records);
+        Ok(Some(records))
+    }
+
+    /// Advance the log start offset of a partition in remote storage to `offset`,
+    /// deleting the segments wholly below it.
+    pub fn delete_records(&self, partition: &TopicIdPartition, offset: i64) -> Result<()> {
+        {
+            let _writer = self.writer.lock().unwrap();
+            if offset <= self.metadata.log_start_offset(partition)? {
+                return Ok(());
+            }
+            self.metadata.set_log_start_offset(partition, offset)?;
+        }
+        for segment in self.metadata.segments(partition)? {
+            if segment.end_offset < offset {
+                self.delete_segment(partition, segment)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Copy a closed local segment of a partition with its indexes, recording it in
+    /// the remote log metadata once it is complete.
+    fn copy_segment(
+        &self,
+        broker_id: i32,
+        partition: &TopicIdPartition,
+        segment: &SegmentInfo,
+        leader_epochs: &[(i32, i64)],
+    ) -> Result<()> {
+        let content = fs::read(&segment.path)
+            .with_context(|| format!("failed to read {}", segment.path.display()))?;
+        let (offset_index, time_index) = build_indexes(&content, segment.base_offset);
+        let leader_epochs = segment_leader_epochs(leader_epochs, segment);
+        let mut leader_epoch_index = format!("0\n{}\n", leader_epochs.len());
+        for (epoch, start_offset) in &leader_epochs {
+            leader_epoch_index.push_str(&format!("{epoch} {start_offset}\n"));
+        }
+        let mut metadata = RemoteLogSegmentMetadata {
+            segment_id: random_uuid(),
+            start_offset: segment.base_offset,
+            end_offset: segment.end_offset,
+            max_timestamp: segment.max_timestamp,
+            size: segment.size,
+            broker_id,
+            leader_epochs,
+            state: RemoteLogSegmentState::CopySegmentStarted,
+        };
+        let data = LogSegmentData {
+            log_segment: segment.path.clone(),
+            offset_index,
+            time_index,
+            leader_epoch_index: leader_epoch_index.into_bytes(),
+        };
+        let _writer = self.writer.lock().unwrap();
+        self.metadata.put_segment(partition, &metadata)?;
+        self.storage
+            .copy_log_segment_data(partition, &metadata, &data)?;
+        metadata.state = RemoteLogSegmentState::CopySegmentFinished;
+        self.metadata.put_segment(partition, &metadata)
+    }
+
+    /// Delete a remote segment, which readers stop seeing first.
+    fn delete_segment(
+        &self,
+        partition: &TopicIdPartition,
+        mut metadata: RemoteLogSegmentMetadata,
+    ) -> Result<()> {
+        let _writer = self.writer.lock().unwrap();
+        metadata.state = RemoteLogSegmentState::DeleteSegmentStarted;
+        self.metadata.put_segment(partition, &metadata)?;
+        self.storage.delete_log_segment_data(partition, &metadata)?;
+        self.metadata.remove_segment(partition, metadata.segment_id)
+    }
+}
+
+/// Position in a segment to read from for `relative_offset`, from its offset index.
+fn lookup_position(index: &[u8], relative_offset: i64) -> usize {
+    index
+        .chunks_exact(8)
+        .map(|entry| {
+            let offset = i32::from_be_bytes(entry[..4].try_into().unwrap());
+            let position = i32::from_be_bytes(entry[4..].try_into().unwrap());
+            (offset as i64, position.max(0) as usize)
+        })
+        .take_while(|&(offset, _)| offset <= relative_offset)
+        .last()
+        .map_or(0, |(_, position)| position)
+}
+
+/// Offset and time indexes of a segment, with an entry every [`INDEX_INTERVAL_BYTES`]
+/// of batches as Apache Kafka writes them while appending.
+fn build_indexes(content: &[u8], base_offset: i64) -> (Vec<u8>, Vec<u8>) {
+    let mut offset_index = vec![];
+    let mut time_index = vec![];
+    let mut bytes_since_entry = 0;
+    let mut max_timestamp = (-1, base_offset);
+    let mut last_indexed_timestamp = -1;
+    let mut add_time_entry = |(timestamp, offset): (i64, i64), time_index: &mut Vec<u8>| {
+        if timestamp > last_indexed_timestamp {
+            time_index.extend(timestamp.to_be_bytes());
+            time_index.extend(((offset - base_offset) as i32).to_be_bytes());
+            last_indexed_timestamp = timestamp;
+        }
+    };
+    let batches = log_manager::batch_headers(content);
+    for (i, batch) in batches.iter().enumerate() {
+        if batch.max_timestamp > max_timestamp.0 {
+            max_timestamp = (batch.max_timestamp, batch.last_offset);
+        }
+        if bytes_since_entry > INDEX_INTERVAL_BYTES {
+            offset_index.extend(((batch.last_offset - base_offset) as i32).to_be_bytes());
+            offset_index.extend((batch.position as i32).to_be_bytes());
+            add_time_entry(max_timestamp, &mut time_index);
+            bytes_since_entry = 0;
+        }
+        let end = batches
+            .get(i + 1)
+            .map_or(content.len(), |next| next.position);
+        bytes_since_entry += end - batch.position;
+    }
+    // A closed segment's time index ends with its largest timestamp
+    add_time_entry(max_timestamp, &mut time_index);
+    (offset_index, time_index)
+}
+
+/// Leader epochs of the records of a segment, out of those of its replica.
+fn segment_leader_epochs(epochs: &[(i32, i64)], segment: &SegmentInfo) -> Vec<(i32, i64)> {
+    let first = epochs
+        .iter()
+        .rposition(|&(_, start_offset)| start_offset <= segment.base_offset)
+        .unwrap_or_default();
+    epochs[first..]
+        .iter()
+        .filter(|&&(_, start_offset)| start_offset <= segment.end_offset)
+        .map(|&(epoch, start_offset)| (epoch, start_offset.max(segment.base_offset)))
+        .collect()
+}
+
+/// Start copying segments to remote storage and expiring them there in the background,
+/// every `remote.log.manager.task.interval.ms`, when tiered storage is enabled.
+pub fn start(broker: &Arc<Broker>) {
+    let Some(config) = broker.config.remote_log.clone() else {
+        return;
+    };
+    let broker = Arc::clone(broker);
+    thread::spawn(move || loop {
+        thread::sleep(Duration::from_millis(config.task_interval_ms.max(1)));
+        let Some(remote) = broker.remote_logs.as_ref() else {
+            return;
+        };
+        for (partition, log_config) in led_tiered_partitions(&broker) {
+            if let Err(e) = copy_segments(&broker, remote, &partition) {
+                eprintln!(
+                    "Failed to copy segments of {} to remote storage: {e:#}",
+                    partition.partition
+                );
+                continue;
+            }
+            if let Err(e) = expire_segments(&broker, remote, &partition, &log_config) {
+                eprintln!(
+                    "Failed to expire remote segments of {}: {e:#}",
+                    partition.partition
+                );
+            }
+        }
+    });
+}
+
+/// Partitions this broker leads whose topic has `remote.storage.enable`, with their log
+/// configs.
+fn led_tiered_partitions(broker: &Broker) -> Vec<(TopicIdPartition, LogConfig)> {
+    let metadata = broker.metadata.read().unwrap();
+    let resolver = ConfigResolver::new(&metadata, &broker.config);
+    let mut led = vec![];
+    for (topic, topic_id) in &metadata.topics {
+        let log_config = resolver.log_config(topic);
+        if !log_config.remote_storage_enable {
+            continue;
+        }
+        for (&partition, registration) in metadata.partitions.get(topic_id).into_iter().flatten() {
+            if registration.leader == broker.config.node_id {
+                let partition = TopicPartition::new(topic.as_str(), partition);
+                led.push((
+                    TopicIdPartition::new(*topic_id, partition),
+                    log_config.clone(),
+                ));
+            }
+        }
+    }
+    led
+}
+
+/// Copy the closed segments of a partition holding only committed records that remote
+/// storage does not have yet, oldest first.
+fn copy_segments(
+    broker: &Broker,
+    remote: &RemoteLogManager,
+    partition: &TopicIdPartition,
+) -> Result<()> {
+    let replica = &partition.partition;
+    let (segments, high_watermark, leader_epochs) = {
+        let mut logs = broker.logs.lock().unwrap();
+        (
+            logs.segment_infos(replica)?,
+            logs.high_watermark(replica),
+            logs.leader_epochs(replica)?,
+        )
+    };
+    let highest_offset = remote.highest_offset(partition)?;
+    let Some((_, closed)) = segments.split_last() else {
+        return Ok(());
+    };
+    for segment in closed {
+        if segment.base_offset <= highest_offset || segment.end_offset < segment.base_offset {
+            continue;
+        }
+        if segment.end_offset >= high_watermark {
+            break;
+        }
+        remote.copy_segment(broker.config.node_id, partition, segment, &leader_epochs)?;
+        eprintln!(
+            "Copied segment {} of {replica} to remote storage",
+            segment.path.display()
+        );
+    }
+    Ok(())
+}
+
+/// Delete the oldest remote segments of a partition past `retention.ms`, or while the
+/// whole log is larger than `retention.bytes`, then move its log start offset past
+/// them.
+fn expire_segments(
+    broker: &Broker,
+    remote: &RemoteLogManager,
+    partition: &TopicIdPartition,
+    config: &LogConfig,
+) -> Result<()> {
+    let replica = &partition.partition;
+    let segments = remote.copied_segments(partition)?;
+    let highest_offset = remote.highest_offset(partition)?;
+    // Local segments count once they are not in remote storage as well
+    let local_size: u64 = broker
+        .logs
+        .lock()
+        .unwrap()
+        .segment_infos(replica)?
+        .iter()
+        .filter(|segment| segment.base_offset > highest_offset)
+        .map(|segment| segment.size)
+        .sum();
+    let mut size = (segments.iter().map(|segment| segment.size).sum::<u64>() + local_size) as i64;
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since| since.as_millis() as i64);
+    let mut log_start_offset = None;
+    for segment in segments {
+        let expired = config.retention_ms >= 0 && now - segment.max_timestamp > config.retention_ms;
+        let oversized =
+            config.retention_bytes >= 0 && size - segment.size as i64 >= config.retention_bytes;
+        if !expired && !oversized {
+            break;
+        }
+        size -= segment.size as i64;
+        log_start_offset = Some(segment.end_offset + 1);
+        let start_offset = segment.start_offset;
+        remote.delete_segment(partition, segment)?;
+        eprintln!(
+            "Deleted the remote segment of {replica} starting at offset {start_offset}, past its \
+             retention"
+        );
+    }
+    if let Some(offset) = log_start_offset {
+        remote.delete_records(partition, offset)?;
+        // Local segments still holding the expired records go with them
+        broker
+            .logs
+            .lock()
+            .unwrap()
+            .delete_records(replica, offset)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        config::{BrokerConfig, Properties},
+        config_registry::ConfigResourceType,
+        controller,
+        protocol::{
+            cluster_metadata::{ConfigRecord, MetadataRecord, RecordBatch, TopicRecord},
+            error,
+            fetch::FetchPartition,
+            primitive::{Serializable, TagSection},
+        },
+    };
+
+    const TOPIC: &str = "tiered";
+
+    /// A single-node cluster with tiered storage, whose topic `tiered` keeps one batch
+    /// per segment and has the given topic configs.
+    fn broker(name: &str, configs: &[(&str, &str)]) -> (PathBuf, Broker) {
+        let dir = std::env::temp_dir().join(format!("{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let mut properties = Properties::default();
+        for (key, value) in [
+            ("node.id", "1"),
+            ("process.roles", "broker,controller"),
+            ("controller.quorum.voters", "1@127.0.0.1:9093"),
+            (
+                "listeners",
+                "PLAINTEXT://127.0.0.1:9092,CONTROLLER://127.0.0.1:9093",
+            ),
+            (
+                "listener.security.protocol.map",
+                "PLAINTEXT:PLAINTEXT,CONTROLLER:PLAINTEXT",
+            ),
+            ("controller.listener.names", "CONTROLLER"),
+            ("log.dirs", &dir.join("logs").display().to_string()),
+            ("remote.log.storage.system.enable", "true"),
+            ("rsm.config.dir", &dir.join("remote").display().to_string()),
+        ] {
+            properties.set(key, value);
+        }
+        let broker = Broker::new(BrokerConfig::from_properties(&properties).unwrap()).unwrap();
+
+        let topic_id = Uuid::from_u128(1);
+        let partition = {
+            let image = broker.metadata.read().unwrap();
+            controller::new_partition(&image, topic_id, 0, vec![1]).unwrap()
+        };
+        let mut records = vec![
+            MetadataRecord::Topic(TopicRecord {
+                name: TOPIC.to_string(),
+                topic_id,
+            }),
+            MetadataRecord::Partition(partition),
+        ];
+        let tiering = [("remote.storage.enable", "true"), ("segment.bytes", "14")];
+        for (name, value) in tiering.iter().chain(configs) {
+            records.push(MetadataRecord::Config(ConfigRecord {
+                resource_type: ConfigResourceType::Topic.code(),
+                resource_name: TOPIC.to_string(),
+                name: name.to_string(),
+                value: Some(value.to_string()),
+            }));
+        }
+        broker.commit_metadata(records).unwrap();
+        broker.sync_replicas().unwrap();
+        (dir, broker)
+    }
+
+    fn partition() -> TopicIdPartition {
+        TopicIdPartition::new(Uuid::from_u128(1), TopicPartition::new(TOPIC, 0))
+    }
+
+    /// Append one batch per timestamp, each rolling a new segment, and commit them all.
+    fn append(broker: &Broker, timestamps: &[i64]) {
+        let replica = TopicPartition::new(TOPIC, 0);
+        let config = broker.log_config(TOPIC);
+        let mut logs = broker.logs.lock().unwrap();
+        for &timestamp in timestamps {
+            let batch = RecordBatch::new(0, 0, timestamp, vec![b"value".to_vec()]);
+            logs.append(&replica, batch.serialize(), 0, &config)
+                .unwrap();
+        }
+        let log_end_offset = logs.log_end_offset(&replica).unwrap();
+        logs.set_high_watermark(&replica, log_end_offset);
+    }
+
+    fn local_log_start_offset(broker: &Broker) -> i64 {
+        let replica = TopicPartition::new(TOPIC, 0);
+        broker
+            .logs
+            .lock()
+            .unwrap()
+            .log_start_offset(&replica)
+            .unwrap()
+    }
+
+    fn copy(broker: &Broker) {
+        copy_segments(broker, broker.remote_logs.as_ref().unwrap(), &partition()).unwrap();
+    }
+
+    fn fetch(broker: &Broker, fetch_offset: i64) -> Result<Vec<u8>, i16> {
+        let fetch = FetchPartition {
+            partition: 0,
+            current_leader_epoch: -1,
+            fetch_offset,
+            last_fetched_epoch: -1,
+            log_start_offset: -1,
+            partition_max_bytes: 1 << 20,
+            tag_buffer: TagSection(None),
+        };
+        let replica = TopicPartition::new(TOPIC, 0);
+        broker
+            .replicas
+            .read_records(broker, &replica, -1, "", &fetch, 1 << 20)
+            .map(|read| read.records)
+    }
+
+    fn now_ms() -> i64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_millis() as i64
+    }
+
+    #[test]
+    fn copies_closed_committed_segments_with_their_indexes() {
+        let (dir, broker) = broker("remote-copy", &[]);
+        let now = now_ms();
+        append(&broker, &[now, now, now]);
+        let remote = broker.remote_logs.as_ref().unwrap();
+        let replica = TopicPartition::new(TOPIC, 0);
+        broker.logs.lock().unwrap().set_high_watermark(&replica, 1);
+        copy(&broker);
+        // Records above the high watermark may still be truncated
+        assert_eq!(remote.highest_offset(&partition()).unwrap(), 0);
+
+        broker.logs.lock().unwrap().set_high_watermark(&replica, 3);
+        copy(&broker);
+
+        // The active segment stays local only
+        let segments = remote.copied_segments(&partition()).unwrap();
+        let offsets: Vec<_> = segments
+            .iter()
+            .map(|segment| (segment.start_offset, segment.end_offset))
+            .collect();
+        assert_eq!(offsets, vec![(0, 0), (1, 1)]);
+        assert_eq!(remote.highest_offset(&partition()).unwrap(), 1);
+        assert_eq!(segments[0].leader_epochs, vec![(0, 0)]);
+        assert_eq!(segments[0].max_timestamp, now);
+
+        let partition_dir = dir.join("remote").join(partition().to_string());
+        let mut extensions: Vec<String> = fs::read_dir(&partition_dir)
+            .unwrap()
+            .map(|entry| {
+                let path = entry.unwrap().path();
+                path.extension().unwrap().to_string_lossy().into_owned()
+            })
+            .collect();
+        extensions.sort();
+        let expected = ["index", "leader-epoch-checkpoint", "log", "timeindex"];
+        let expected: Vec<_> = expected.iter().flat_map(|e| [*e, *e]).collect();
+        assert_eq!(extensions, expected);
+        let storage = LocalTieredStorage::new(dir.join("remote"));
+        let time_index = storage
+            .fetch_index(&partition(), &segments[1], IndexType::Timestamp)
+            .unwrap();
+        assert_eq!(
+            time_index,
+            [&now.to_be_bytes()[..], &0i32.to_be_bytes()].concat()
+        );
+
+        // Nothing is copied twice
+        copy(&broker);
+        assert_eq!(remote.copied_segments(&partition()).unwrap().len(), 2);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn local_retention_deletes_only_segments_in_remote_storage() {
+        let (dir, broker) = broker("remote-local-retention", &[("local.retention.ms", "0")]);
+        append(&broker, &[1, 1, 1]);
+        broker.replicas.delete_old_segments(&broker);
+        assert_eq!(local_log_start_offset(&broker), 0);
+
+        copy(&broker);
+        broker.replicas.delete_old_segments(&broker);
+        assert_eq!(local_log_start_offset(&broker), 2);
+        // Remote storage keeps them, `retention.ms` being unlimited by default
+        let remote = broker.remote_logs.as_ref().unwrap();
+        assert_eq!(remote.log_start_offset(&partition()).unwrap(), Some(0));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn serves_fetches_below_the_local_log_start_from_remote_storage() {
+        let (dir, broker) = broker("remote-fetch", &[("local.retention.ms", "0")]);
+        append(&broker, &[1, 1, 1]);
+        copy(&broker);
+        broker.replicas.delete_old_segments(&broker);
+        assert_eq!(local_log_start_offset(&broker), 2);
+
+        let records = fetch(&broker, 1).unwrap();
+        let offsets: Vec<_> = log_manager::batch_headers(&records)
+            .iter()
+            .map(|batch| batch.base_offset)
+            .collect();
+        assert_eq!(offsets, vec![1]);
+        let records = fetch(&broker, 2).unwrap();
+        assert_eq!(log_manager::batch_headers(&records)[0].base_offset, 2);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn retention_deletes_remote_segments_past_retention_ms() {
+        let now = now_ms();
+        let (dir, broker) = broker("remote-retention", &[("retention.ms", "60000")]);
+        append(&broker, &[1, now, now]);
+        copy(&broker);
+        let remote = broker.remote_logs.as_ref().unwrap();
+        let config = broker.log_config(TOPIC);
+        expire_segments(&broker, remote, &partition(), &config).unwrap();
+
+        let segments = remote.copied_segments(&partition()).unwrap();
+        assert_eq!(segments.len(), 1);
+        assert_eq!(segments[0].start_offset, 1);
+        assert_eq!(remote.log_start_offset(&partition()).unwrap(), Some(1));
+        assert_eq!(local_log_start_offset(&broker), 1);
+        assert_eq!(fetch(&broker, 0), Err(error::OFFSET_OUT_OF_RANGE));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,379 @@
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    sync::{Arc, Mutex},
+    thread,
+    time::{Duration, Instant},
+};
+
+use anyhow::{bail, Context, Result};
+
+use crate::{
+    broker::Broker,
+    config::SecurityProtocol,
+    log_manager::{LogDirError, LogManager, TopicPartition},
+    network_client::NetworkClient,
+    protocol::{
+        error,
+        fetch::{EpochEndOffset, FetchPartition, FetchRequest, FetchResponse, FetchTopic},
+        primitive::{CompactArray, CompactString, TagSection},
+    },
+    quota::ReplicationQuotaType,
+    remote_storage::TopicIdPartition,
+};
+
+const FETCH_API_KEY: i16 = 1;
+const FETCH_VERSION: i16 = 12;
+/// Apache Kafka's defaults for `replica.fetch.max.bytes` and
+/// `replica.fetch.response.max.bytes`
+const PARTITION_MAX_BYTES: i32 = 1024 * 1024;
+const RESPONSE_MAX_BYTES: i32 = 10 * 1024 * 1024;
+
+/// Start copying the partitions this broker follows from their leaders, with one
+/// fetcher thread per leader.
+pub fn start(broker: &Arc<Broker>) {
+    let broker = Arc::clone(broker);
+    thread::spawn(move || {
+        let backoff = Duration::from_millis(broker.config.replication.fetch_backoff_ms.max(1));
+        let fetchers = Arc::new(Mutex::new(HashSet::new()));
+        loop {
+            for leader in followed_leaders(&broker) {
+                if fetchers.lock().unwrap().insert(leader) {
+                    let broker = Arc::clone(&broker);
+                    let fetchers = Arc::clone(&fetchers);
+                    thread::spawn(move || {
+                        fetch_from(&broker, leader);
+                        fetchers.lock().unwrap().remove(&leader);
+                    });
+                }
+            }
+            thread::sleep(backoff);
+        }
+    });
+}
+
+/// Leaders of the partitions this broker follows.
+fn followed_leaders(broker: &Broker) -> BTreeSet<i32> {
+    let node_id = broker.config.node_id;
+    let metadata = broker.metadata.read().unwrap();
+    metadata
+        .partitions
+        .values()
+        .flat_map(BTreeMap::values)
+        .filter(|registration| {
+            registration.leader >= 0
+                && registration.leader != node_id
+                && registration.replicas.contains(&node_id)
+        })
+        .map(|registration| registration.leader)
+        .collect()
+}
+
+/// Partitions this broker follows from `leader`, with their leader epoch and whether
+/// this broker is in their ISR.
+fn followed_partitions(broker: &Broker, leader: i32) -> Vec<(TopicPartition, i32, bool)> {
+    let node_id = broker.config.node_id;
+    let metadata = broker.metadata.read().unwrap();
+    metadata
+        .topics
+        .iter()
+        .flat_map(|(topic, topic_id)| {
+            let partitions = metadata.partitions.get(topic_id).into_iter().flatten();
+            partitions
+                .filter(|(_, registration)| {
+                    registration.leader == leader && registration.replicas.contains(&node_id)
+                })
+                .map(|(&partition, registration)| {
+                    let replica = TopicPartition::new(topic.as_str(), partition);
+                    let in_sync = registration.isr.contains(&node_id);
+                    (replica, registration.leader_epoch, in_sync)
+                })
+        })
+        .collect()
+}
+
+/// Fetch from `leader` until this broker no longer follows any partition it leads.
+fn fetch_from(broker: &Broker, leader: i32) {
+    let backoff = Duration::from_millis(broker.config.replication.fetch_backoff_ms);
+    let mut client = None;
+    // Partitions that failed sit out fetches until their backoff is over
+    let mut delayed: HashMap<TopicPartition, Instant> = HashMap::new();
+    loop {
+        let partitions = followed_partitions(broker, leader);
+        if partitions.is_empty() {
+            return;
+        }
+        let now = Instant::now();
+        delayed.retain(|replica, until| {
+            *until > now && partitions.iter().any(|(followed, ..)| followed == replica)
+        });
+        let fetched = match client.as_mut() {
+            Some(client) => fetch(broker, client, &partitions, &mut delayed),
+            None => connect(broker, leader).map(|connected| {
+                client = Some(connected);
+                true
+            }),
+        };
+        match fetched {
+            Ok(true) => {}
+            // Rounds where every partition sat out are retried later
+            Ok(false) => thread::sleep(backoff),
+            Err(e) => {
+                eprintln!("Failed to fetch from broker {leader}: {e:#}");
+                client = None;
+                thread::sleep(backoff);
+            }
+        }
+    }
+}
+
+/// Connect to the inter-broker listener of `leader`.
+fn connect(broker: &Broker, leader: i32) -> Result<NetworkClient> {
+    let listener = &broker.config.replication.inter_broker_listener_name;
+    if broker.config.security_protocol(listener) != Some(SecurityProtocol::Plaintext) {
+        bail!("inter-broker listener {listener} is not PLAINTEXT, which is all followers support");
+    }
+    let (host, port) = {
+        let metadata = broker.metadata.read().unwrap();
+        metadata
+            .brokers
+            .get(&leader)
+            .and_then(|registration| {
+                let endpoint = registration
+                    .endpoints
+                    .iter()
+                    .find(|e| &e.name == listener)?;
+                Some((endpoint.host.clone(), endpoint.port))
+            })
+            .with_context(|| format!("broker {leader} has no {listener} endpoint"))?
+    };
+    // The leader holds fetches for up to replica.fetch.wait.max.ms
+    let wait = Duration::from_millis(broker.config.replication.fetch_wait_max_ms);
+    NetworkClient::connect(
+        &host,
+        port,
+        wait + Duration::from_secs(30),
+        format!("broker-{}-fetcher", broker.config.node_id),
+    )
+}
+
+/// Fetch once from the leader and append what it sent. Partitions failing with an error
+/// are delayed for `replica.fetch.backoff.ms`. Returns whether any partition was
+/// fetched.
+fn fetch(
+    broker: &Broker,
+    client: &mut NetworkClient,
+    partitions: &[(TopicPartition, i32, bool)],
+    delayed: &mut HashMap<TopicPartition, Instant>,
+) -> Result<bool> {
+    let backoff = Duration::from_millis(broker.config.replication.fetch_backoff_ms);
+    // Replicas catching up on a throttled replica sit out fetches while over the rate
+    let throttled: HashSet<&TopicPartition> = partitions
+        .iter()
+        .filter(|(replica, _, in_sync)| {
+            !in_sync && broker.is_throttled_replica(ReplicationQuotaType::Follower, replica)
+        })
+        .map(|(replica, ..)| replica)
+        .collect();
+    let held_back = !throttled.is_empty()
+        && broker.is_replication_quota_exceeded(ReplicationQuotaType::Follower);
+    let mut topics: BTreeMap<&str, Vec<FetchPartition>> = BTreeMap::new();
+    {
+        let mut logs = broker.logs.lock().unwrap();
+        for (replica, leader_epoch, _) in partitions {
+            if (held_back && throttled.contains(replica)) || delayed.contains_key(replica) {
+                continue;
+            }
+            // Replicas in an offline log directory are not fetched
+            let Ok((fetch_offset, last_fetched_epoch, log_start_offset)) =
+                logs.log_end_offset(replica).and_then(|offset| {
+                    Ok((
+                        offset,
+                        logs.latest_epoch(replica)?,
+                        logs.log_start_offset(replica)?,
+                    ))
+                })
+            else {
+                continue;
+            };
+            topics
+                .entry(&replica.topic)
+                .or_default()
+                .push(FetchPartition {
+                    partition: replica.partition,
+                    current_leader_epoch: *leader_epoch,
+                    fetch_offset,
+                    last_fetched_epoch,
+                    log_start_offset,
+                    partition_max_bytes: PARTITION_MAX_BYTES,
+                    tag_buffer: TagSection(None),
+                });
+        }
+    }
+    // Nothing to fetch this round, the fetcher backs off
+    if topics.is_empty() {
+        return Ok(false);
+    }
+    let request = FetchRequest {
+        replica_id: broker.config.node_id,
+        max_wait_ms: broker.config.replication.fetch_wait_max_ms as i32,
+        min_bytes: 1,
+        max_bytes: RESPONSE_MAX_BYTES,
+        isolation_level: 0,
+        // Every fetch is a full one, outside of any fetch session
+        session_id: 0,
+        session_epoch: -1,
+        topics: CompactArray(Some(
+            topics
+                .into_iter()
+                .map(|(topic, partitions)| FetchTopic {
+                    topic: CompactString(Some(topic.to_string())),
+                    partitions: CompactArray(Some(partitions)),
+                    tag_buffer: TagSection(None),
+                })
+                .collect(),
+        )),
+        forgotten_topics_data: CompactArray(Some(vec![])),
+        rack_id: CompactString(Some(String::new())),
+        tag_buffer: TagSection(None),
+    };
+    let response: FetchResponse = client.send(FETCH_API_KEY, FETCH_VERSION, &request)?;
+    if response.error_code != error::NONE {
+        bail!("fetch failed with error code {}", response.error_code);
+    }
+
+    let mut configs = BTreeMap::new();
+    for (replica, ..) in partitions {
+        configs
+            .entry(replica.topic.as_str())
+            .or_insert_with(|| broker.log_config(&replica.topic));
+    }
+    let mut failed = vec![];
+    let mut tiered = vec![];
+    let mut logs = broker.logs.lock().unwrap();
+    for topic in response.responses.iter().flatten() {
+        let name = topic.topic.as_deref().unwrap_or_default();
+        let Some(config) = configs.get(name) else {
+            continue;
+        };
+        for partition in topic.partitions.iter().flatten() {
+            let replica = TopicPartition::new(name, partition.partition_index);
+            // The leader only has the records from the fetch offset on in remote storage
+            if partition.error_code == error::OFFSET_MOVED_TO_TIERED_STORAGE {
+                tiered.push(replica);
+                continue;
+            }
+            // The leader no longer has the records from the fetch offset on, the log
+            // starts over where the leader's starts
+            if partition.error_code == error::OFFSET_OUT_OF_RANGE
+                && partition.log_start_offset >= 0
+                && logs
+                    .log_end_offset(&replica)
+                    .is_ok_and(|offset| offset < partition.log_start_offset)
+            {
+                let offset = partition.log_start_offset;
+                eprintln!(
+                    "Starting the log of {replica} over at offset {offset}, the leader's log start"
+                );
+                if let Err(e) = logs.truncate_fully_and_start_at(&replica, offset, &[]) {
+                    eprintln!("Cannot truncate {replica}: {e}");
+                    failed.push(replica);
+                }
+                continue;
+            }
+            if partition.error_code != error::NONE {
+                eprintln!(
+                    "Failed to fetch {replica} with error code {}",
+                    partition.error_code
+                );
+                failed.push(replica);
+                continue;
+            }
+            // The next fetch goes on from where the logs agree
+            if let Some(diverging) = partition.diverging_epoch() {
+                if let Err(e) = truncate(&mut logs, &replica, diverging) {
+                    eprintln!("Cannot truncate {replica}: {e}");
+                    failed.push(replica);
+                }
+                continue;
+            }
+            let records = partition.records.as_deref().unwrap_or_default();
+            if throttled.contains(&replica) {
+                broker.record_replication(ReplicationQuotaType::Follower, records.len());
+            }
+            let appended = if records.is_empty() {
+                logs.log_end_offset(&replica)
+            } else {
+                logs.append_as_follower(&replica, records, config)
+            };
+            // The follower cannot have more committed records than it holds, and drops
+            // the records the leader deleted
+            let updated = appended.and_then(|log_end_offset| {
+                let high_watermark = partition.high_watermark.min(log_end_offset);
+                logs.set_high_watermark(&replica, high_watermark);
+                if partition.log_start_offset > logs.log_start_offset(&replica)? {
+                    logs.delete_records(&replica, partition.log_start_offset.min(high_watermark))?;
+                }
+                Ok(())
+            });
+            if let Err(e) = updated {
+                eprintln!("Cannot append to {replica}: {e}");
+                failed.push(replica);
+            }
+        }
+    }
+    drop(logs);
+    for replica in tiered {
+        if let Err(e) = build_remote_log_aux_state(broker, &replica) {
+            eprintln!("Cannot rebuild {replica} from remote storage: {e:#}");
+            failed.push(replica);
+        }
+    }
+    let retry_at = Instant::now() + backoff;
+    delayed.extend(failed.into_iter().map(|replica| (replica, retry_at)));
+    Ok(true)
+}
+
+/// Start the log of a replica where the remote storage of its partition ends, with the
+/// leader epochs of the remote records, as a follower does when the records it lacks are
+/// only in remote storage. The leader still has the records from there on locally.
+fn build_remote_log_aux_state(broker: &Broker, replica: &TopicPartition) -> Result<()> {
+    let Some(remote) = broker.remote_log_manager(&replica.topic) else {
+        bail!("tiered storage is disabled for {}", replica.topic);
+    };
+    let topic_id = broker
+        .metadata
+        .read()
+        .unwrap()
+        .topics
+        .get(&replica.topic)
+        .copied()
+        .with_context(|| format!("topic {} is unknown", replica.topic))?;
+    let partition = TopicIdPartition::new(topic_id, replica.clone());
+    let start_offset = remote.highest_offset(&partition)? + 1;
+    let epochs = remote.leader_epochs(&partition)?;
+    eprintln!("Starting the log of {replica} at offset {start_offset}, after its remote segments");
+    broker
+        .logs
+        .lock()
+        .unwrap()
+        .truncate_fully_and_start_at(replica, start_offset, &epochs)?;
+    Ok(())
+}
+
+/// Truncate a replica whose log diverged from the leader's to where the leader's log
+/// left the last epoch they share, or to the high watermark if the leader knows none.
+fn truncate(
+    logs: &mut LogManager,
+    replica: &TopicPartition,
+    diverging: EpochEndOffset,
+) -> Result<(), LogDirError> {
+    let own = logs.end_offset_for_epoch(replica, diverging.epoch)?;
+    let offset = if diverging.end_offset < 0 || own.end_offset < 0 {
+        logs.high_watermark(replica)
+    } else {
+        diverging.end_offset.min(own.end_offset)
+    };
+    eprintln!("Truncating {replica} to offset {offset} to match the leader's log");
+    logs.truncate_to(replica, offset)?;
+    Ok(())
+}
@@ -0,0 +1,790 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Condvar, Mutex},
+    thread,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use uuid::Uuid;
+
+use crate::{
+    broker::Broker,
+    config::ReplicaSelector,
+    log_manager::{self, LogDirError, TopicPartition, HIGH_WATERMARK},
+    metadata::PartitionRegistration,
+    protocol::{
+        error,
+        fetch::{EpochEndOffset, FetchPartition},
+        produce::ACKS_ALL,
+    },
+    quota::ReplicationQuotaType,
+    remote_storage::TopicIdPartition,
+};
+
+/// Where a follower stands, as the leader sees it from its fetches.
+#[derive(Debug)]
+struct FollowerState {
+    log_end_offset: i64,
+    /// Last time the follower was known to hold every record of the leader
+    last_caught_up: Instant,
+    /// When the follower last fetched, and the log end offset of the leader back then
+    last_fetch: Instant,
+    last_fetch_leader_log_end_offset: i64,
+}
+
+impl FollowerState {
+    /// A follower has `replica.lag.time.max.ms` from the moment it is first seen to
+    /// catch up.
+    fn new(now: Instant) -> Self {
+        FollowerState {
+            log_end_offset: 0,
+            last_caught_up: now,
+            last_fetch: now,
+            last_fetch_leader_log_end_offset: 0,
+        }
+    }
+}
+
+/// Where records appended as the leader landed.
+#[derive(Debug)]
+pub struct AppendInfo {
+    pub base_offset: i64,
+    pub log_start_offset: i64,
+    pub log_end_offset: i64,
+}
+
+/// Records read from a partition, with its offsets at the time.
+#[derive(Debug)]
+pub struct ReadInfo {
+    pub high_watermark: i64,
+    pub log_start_offset: i64,
+    pub records: Vec<u8>,
+    /// Where the log of the fetching follower diverged from the leader's, in which case
+    /// no records are read
+    pub diverging_epoch: Option<EpochEndOffset>,
+    /// Replica the consumer should fetch from instead, in which case no records are read
+    pub preferred_read_replica: Option<i32>,
+}
+
+/// Replica id of consumers allowed to query followers, as tools do for debugging.
+pub const DEBUGGING_REPLICA_ID: i32 = -2;
+
+/// Replication of the partitions this broker leads. Followers report how far they got
+/// through their fetches, from which the leader grows and shrinks the ISR and moves the
+/// high watermark up to the records every in-sync replica holds.
+///
+/// Its locks are never held while taking the metadata image, and come before the log
+/// manager.
+#[derive(Default)]
+pub struct ReplicaManager {
+    followers: Mutex<HashMap<TopicPartition, HashMap<i32, FollowerState>>>,
+    /// Bumped whenever a log grows or a high watermark advances
+    progress: Mutex<u64>,
+    progressed: Condvar,
+}
+
+impl ReplicaManager {
+    /// Start shrinking the ISRs, checkpointing the high watermarks and deleting the
+    /// segments past their retention in the background.
+    pub fn start(broker: &Arc<Broker>) {
+        let broker = Arc::clone(broker);
+        thread::spawn(move || {
+            let config = &broker.config.replication;
+            // As in Apache Kafka, lagging followers are looked for twice per allowed lag
+            let isr_interval = Duration::from_millis((config.lag_time_max_ms / 2).max(1));
+            let checkpoint_interval =
+                Duration::from_millis(config.high_watermark_checkpoint_interval_ms.max(1));
+            let retention_interval =
+                Duration::from_millis(broker.config.log_retention_check_interval_ms.max(1));
+            let mut next_isr_check = Instant::now() + isr_interval;
+            let mut next_checkpoint = Instant::now() + checkpoint_interval;
+            let mut next_retention_check = Instant::now() + retention_interval;
+            loop {
+                let next = next_isr_check
+                    .min(next_checkpoint)
+                    .min(next_retention_check);
+                thread::sleep(next.saturating_duration_since(Instant::now()));
+                let now = Instant::now();
+                if now >= next_isr_check {
+                    broker.replicas.shrink_isrs(&broker);
+                    next_isr_check = now + isr_interval;
+                }
+                if now >= next_checkpoint {
+                    broker.logs.lock().unwrap().checkpoint_high_watermarks();
+                    next_checkpoint = now + checkpoint_interval;
+                }
+                if now >= next_retention_check {
+                    broker.replicas.delete_old_segments(&broker);
+                    next_retention_check = now + retention_interval;
+                }
+            }
+        });
+    }
+
+    /// Append records to a partition this broker leads. With acks=all the ISR must have
+    /// at least `min.insync.replicas` members for the append to happen at all.
+    pub fn append_records(
+        &self,
+        broker: &Broker,
+        replica: &TopicPartition,
+        records: Vec<u8>,
+        acks: i16,
+    ) -> Result<AppendInfo, i16> {
+        let (_, registration) = led_partition(broker, replica)?;
+        // Batches stamped with a leader epoch come from a producer that knows the leader
+        for epoch in log_manager::partition_leader_epochs(&records) {
+            check_leader_epoch(epoch, &registration)?;
+        }
+        if acks == ACKS_ALL && !has_min_isr(broker, replica, &registration) {
+            return Err(error::NOT_ENOUGH_REPLICAS);
+        }
+        let config = broker.log_config(&replica.topic);
+        let append = {
+            let mut logs = broker.logs.lock().unwrap();
+            logs.append(replica, records, registration.leader_epoch, &config)
+                .and_then(|base_offset| {
+                    Ok(AppendInfo {
+                        base_offset,
+                        log_start_offset: logs.log_start_offset(replica)?,
+                        log_end_offset: logs.log_end_offset(replica)?,
+                    })
+                })
+                .map_err(|e| {
+                    eprintln!("Cannot append to {replica}: {e}");
+                    e.error_code()
+                })?
+        };
+        // Followers waiting for new records fetch them right away
+        self.notify_progress();
+        self.advance_high_watermark(broker, replica, &registration.isr);
+        Ok(append)
+    }
+
+    /// Wait until the high watermark of a partition reaches `log_end_offset`, as an
+    /// acks=all append must before it is acknowledged.
+    pub fn wait_for_replication(
+        &self,
+        broker: &Broker,
+        replica: &TopicPartition,
+        log_end_offset: i64,
+        deadline: Instant,
+    ) -> Result<(), i16> {
+        loop {
+            let progress = self.progress();
+            if broker.logs.lock().unwrap().high_watermark(replica) >= log_end_offset {
+                break;
+            }
+            if Instant::now() >= deadline {
+                return Err(error::REQUEST_TIMED_OUT);
+            }
+            self.wait_for_progress(progress, deadline);
+        }
+        // The ISR may have shrunk below the minimum while the records were replicated
+        let (_, registration) = led_partition(broker, replica)?;
+        if !has_min_isr(broker, replica, &registration) {
+            return Err(error::NOT_ENOUGH_REPLICAS_AFTER_APPEND);
+        }
+        Ok(())
+    }
+
+    /// Read records of a partition for a consumer, or for the follower `replica_id`.
+    /// Consumers only see the records below the high watermark and may read from any
+    /// replica, while followers read from the leader and their fetch offset tells how far
+    /// they got. A leader picking a closer replica for a consumer in `client_rack` sends
+    /// it there instead.
+    pub fn read_records(
+        &self,
+        broker: &Broker,
+        replica: &TopicPartition,
+        replica_id: i32,
+        client_rack: &str,
+        fetch: &FetchPartition,
+        max_bytes: usize,
+    ) -> Result<ReadInfo, i16> {
+        let fetch_offset = fetch.fetch_offset;
+        let is_follower = replica_id >= 0;
+        let (topic_id, registration) = if is_follower {
+            led_partition(broker, replica)?
+        } else {
+            hosted_partition(broker, replica)?
+        };
+        check_leader_epoch(fetch.current_leader_epoch, &registration)?;
+        if is_follower && !registration.replicas.contains(&replica_id) {
+            return Err(error::NOT_LEADER_OR_FOLLOWER);
+        }
+        let is_leader = registration.leader == broker.config.node_id;
+
+        // A fetcher whose log went further in an epoch than the leader's truncates to
+        // where the leader's log left that epoch, before reading on
+        if fetch.last_fetched_epoch >= 0 {
+            let mut logs = broker.logs.lock().unwrap();
+            let end = if is_leader {
+                logs.assign_leader_epoch(replica, registration.leader_epoch)
+            } else {
+                Ok(())
+            }
+            .and_then(|()| logs.end_offset_for_epoch(replica, fetch.last_fetched_epoch))
+            .map_err(|e| e.error_code())?;
+            if end.epoch != fetch.last_fetched_epoch || end.end_offset < fetch_offset {
+                return Ok(ReadInfo {
+                    high_watermark: logs.high_watermark(replica),
+                    log_start_offset: logs.log_start_offset(replica).map_err(|e| e.error_code())?,
+                    records: vec![],
+                    diverging_epoch: Some(end),
+                    preferred_read_replica: None,
+                });
+            }
+        }
+
+        if !is_leader {
+            // Followers serve consumers the records they know to be committed
+            let high_watermark = broker.logs.lock().unwrap().high_watermark(replica);
+            let (records, log_start_offset) = read_log(
+                broker,
+                topic_id,
+                replica,
+                false,
+                fetch_offset,
+                high_watermark,
+                max_bytes,
+            )?;
+            return Ok(ReadInfo {
+                high_watermark,
+                log_start_offset,
+                records,
+                diverging_epoch: None,
+                preferred_read_replica: None,
+            });
+        }
+        self.advance_high_watermark(broker, replica, &registration.isr);
+        if !is_follower && broker.config.replication.replica_selector == ReplicaSelector::RackAware
+        {
+            let preferred = self.preferred_read_replica(
+                broker,
+                replica,
+                &registration,
+                client_rack,
+                fetch_offset,
+            );
+            if let Some(preferred) = preferred.filter(|&id| id != broker.config.node_id) {
+                let mut logs = broker.logs.lock().unwrap();
+                return Ok(ReadInfo {
+                    high_watermark: logs.high_watermark(replica),
+                    log_start_offset: logs.log_start_offset(replica).map_err(|e| e.error_code())?,
+                    records: vec![],
+                    diverging_epoch: None,
+                    preferred_read_replica: Some(preferred),
+                });
+            }
+        }
+        // Followers catching up on a throttled replica get nothing while over the rate
+        let throttled = is_follower
+            && !registration.isr.contains(&replica_id)
+            && broker.is_throttled_replica(ReplicationQuotaType::Leader, replica);
+        let held_back =
+            throttled && broker.is_replication_quota_exceeded(ReplicationQuotaType::Leader);
+        let max_offset = {
+            let mut logs = broker.logs.lock().unwrap();
+            if held_back {
+                fetch_offset
+            } else if is_follower {
+                logs.log_end_offset(replica).map_err(|e| e.error_code())?
+            } else {
+                logs.high_watermark(replica)
+            }
+        };
+        let (records, log_start_offset) = read_log(
+            broker,
+            topic_id,
+            replica,
+            is_follower,
+            fetch_offset,
+            max_offset,
+            max_bytes,
+        )?;
+        if throttled {
+            broker.record_replication(ReplicationQuotaType::Leader, records.len());
+        }
+        if is_follower {
+            self.record_follower_fetch(
+                broker,
+                replica,
+                topic_id,
+                &registration,
+                replica_id,
+                fetch_offset,
+            );
+        }
+        Ok(ReadInfo {
+            high_watermark: broker.logs.lock().unwrap().high_watermark(replica),
+            log_start_offset,
+            records,
+            diverging_epoch: None,
+            preferred_read_replica: None,
+        })
+    }
+
+    /// Replica of a partition this broker leads that a consumer in `client_rack` should
+    /// fetch from at `fetch_offset`, as `RackAwareReplicaSelector` picks it: the leader
+    /// if it is in that rack, else the in-sync follower in that rack holding the most
+    /// records, provided it holds the offset. `None` leaves the consumer on the leader.
+    fn preferred_read_replica(
+        &self,
+        broker: &Broker,
+        replica: &TopicPartition,
+        registration: &PartitionRegistration,
+        client_rack: &str,
+        fetch_offset: i64,
+    ) -> Option<i32> {
+        if client_rack.is_empty() || broker.config.rack.as_deref() == Some(client_rack) {
+            return None;
+        }
+        let in_rack: Vec<i32> = {
+            let metadata = broker.metadata.read().unwrap();
+            registration
+                .isr
+                .iter()
+                .copied()
+                .filter(|id| {
+                    metadata.brokers.get(id).is_some_and(|registration| {
+                        !registration.fenced
+                            && !registration.in_controlled_shutdown
+                            && registration.rack.as_deref() == Some(client_rack)
+                    })
+                })
+                .collect()
+        };
+        let followers = self.followers.lock().unwrap();
+        let states = followers.get(replica)?;
+        in_rack
+            .into_iter()
+            .filter_map(|id| Some((id, states.get(&id)?.log_end_offset)))
+            .filter(|&(_, log_end_offset)| log_end_offset >= fetch_offset)
+            .max_by_key(|&(_, log_end_offset)| log_end_offset)
+            .map(|(id, _)| id)
+    }
+
+    /// Largest leader epoch of a partition not above `leader_epoch`, with the offset its
+    /// records end at. Only the leader answers, save for debugging consumers, which any
+    /// replica answers.
+    pub fn end_offset_for_epoch(
+        &self,
+        broker: &Broker,
+        replica: &TopicPartition,
+        replica_id: i32,
+        current_leader_epoch: i32,
+        leader_epoch: i32,
+    ) -> Result<EpochEndOffset, i16> {
+        let (_, registration) = if replica_id == DEBUGGING_REPLICA_ID {
+            hosted_partition(broker, replica)?
+        } else {
+            led_partition(broker, replica)?
+        };
+        check_leader_epoch(current_leader_epoch, &registration)?;
+        let mut logs = broker.logs.lock().unwrap();
+        if registration.leader == broker.config.node_id {
+            logs.assign_leader_epoch(replica, registration.leader_epoch)
+                .map_err(|e| e.error_code())?;
+        }
+        logs.end_offset_for_epoch(replica, leader_epoch)
+            .map_err(|e| e.error_code())
+    }
+
+    /// Delete the records of a partition this broker leads up to `offset`, returning its
+    /// new log start offset.
+    pub fn delete_records(
+        &self,
+        broker: &Broker,
+        replica: &TopicPartition,
+        offset: i64,
+    ) -> Result<i64, i16> {
+        let (topic_id, registration) = led_partition(broker, replica)?;
+        self.advance_high_watermark(broker, replica, &registration.isr);
+        let remote = broker.remote_log_manager(&replica.topic);
+        let (log_start_offset, offset) = {
+            let mut logs = broker.logs.lock().unwrap();
+            let offset = if offset == HIGH_WATERMARK {
+                logs.high_watermark(replica)
+            } else {
+                offset
+            };
+            let log_start_offset = logs.delete_records(replica, offset).map_err(|e| {
+                eprintln!("Cannot delete records of {replica}: {e}");
+                e.error_code()
+            })?;
+            (log_start_offset, offset)
+        };
+        let Some(remote) = remote else {
+            return Ok(log_start_offset);
+        };
+        // Records below the local log may still be in remote storage
+        let partition = TopicIdPartition::new(topic_id, replica.clone());
+        remote
+            .delete_records(&partition, offset)
+            .and_then(|()| remote.log_start_offset(&partition))
+            .map(|remote_start| remote_start.map_or(log_start_offset, |r| r.min(log_start_offset)))
+            .map_err(|e| {
+                eprintln!("Cannot delete remote records of {replica}: {e:#}");
+                error::KAFKA_STORAGE_ERROR
+            })
+    }
+
+    /// Delete the oldest closed segments of the local replicas while past `retention.ms`
+    /// or `retention.bytes`, keeping those holding records above the high watermark.
+    /// Tiered topics follow `local.retention.ms` and `local.retention.bytes` instead, and
+    /// only delete segments already in remote storage.
+    pub fn delete_old_segments(&self, broker: &Broker) {
+        let replicas: Vec<TopicPartition> = {
+            let logs = broker.logs.lock().unwrap();
+            (0..logs.dirs().len())
+                .filter(|&index| logs.dirs()[index].is_online())
+                .flat_map(|index| logs.replicas_in(index).cloned().collect::<Vec<_>>())
+                .collect()
+        };
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |since| since.as_millis() as i64);
+        for replica in replicas {
+            let config = broker.log_config(&replica.topic);
+            let topic_id = broker
+                .metadata
+                .read()
+                .unwrap()
+                .topics
+                .get(&replica.topic)
+                .copied();
+            let (retention_ms, retention_bytes, copied_end) =
+                match (broker.remote_log_manager(&replica.topic), topic_id) {
+                    (Some(remote), Some(topic_id)) => {
+                        let partition = TopicIdPartition::new(topic_id, replica.clone());
+                        let Ok(highest_offset) = remote.highest_offset(&partition) else {
+                            continue;
+                        };
+                        (
+                            config.local_retention_ms,
+                            config.local_retention_bytes,
+                            highest_offset + 1,
+                        )
+                    }
+                    _ => (config.retention_ms, config.retention_bytes, i64::MAX),
+                };
+            let mut logs = broker.logs.lock().unwrap();
+            let Ok(segments) = logs.segment_infos(&replica) else {
+                continue;
+            };
+            let high_watermark = logs.high_watermark(&replica);
+            let mut size: i64 = segments.iter().map(|segment| segment.size as i64).sum();
+            let mut log_start_offset = None;
+            let closed = segments.split_last().map_or(&[][..], |(_, closed)| closed);
+            for segment in closed {
+                if segment.end_offset >= high_watermark || segment.end_offset >= copied_end {
+                    break;
+                }
+                let expired = retention_ms >= 0 && now - segment.max_timestamp > retention_ms;
+                let oversized =
+                    retention_bytes >= 0 && size - segment.size as i64 >= retention_bytes;
+                if !expired && !oversized {
+                    break;
+                }
+                size -= segment.size as i64;
+                log_start_offset = Some(segment.end_offset + 1);
+            }
+            let Some(offset) = log_start_offset else {
+                continue;
+            };
+            match logs.delete_records(&replica, offset) {
+                Ok(_) => eprintln!(
+                    "Deleted the segments of {replica} below offset {offset}, past their \
+                     retention"
+                ),
+                Err(e) => eprintln!("Cannot delete old segments of {replica}: {e}"),
+            }
+        }
+    }
+
+    /// Remove from the ISR of the partitions this broker leads the followers that have
+    /// not caught up for `replica.lag.time.max.ms`.
+    pub fn shrink_isrs(&self, broker: &Broker) {
+        let node_id = broker.config.node_id;
+        let led: Vec<(TopicPartition, Uuid, PartitionRegistration)> = {
+            let metadata = broker.metadata.read().unwrap();
+            metadata
+                .topics
+                .iter()
+                .flat_map(|(topic, topic_id)| {
+                    let partitions = metadata.partitions.get(topic_id).into_iter().flatten();
+                    partitions
+                        .filter(|(_, registration)| registration.leader == node_id)
+                        .map(|(&partition, registration)| {
+                            let replica = TopicPartition::new(topic.as_str(), partition);
+                            (replica, *topic_id, registration.clone())
+                        })
+                })
+                .collect()
+        };
+
+        let max_lag = Duration::from_millis(broker.config.replication.lag_time_max_ms);
+        let now = Instant::now();
+        let mut followers = self.followers.lock().unwrap();
+        // Should the leadership come back, its followers start over
+        followers.retain(|replica, _| led.iter().any(|(led, ..)| led == replica));
+        let isrs: Vec<Vec<i32>> = led
+            .iter()
+            .map(|(replica, _, registration)| {
+                let states = followers.entry(replica.clone()).or_default();
+                registration
+                    .isr
+                    .iter()
+                    .copied()
+                    .filter(|&id| {
+                        id == node_id || {
+                            let state = states.entry(id).or_insert_with(|| FollowerState::new(now));
+                            now.duration_since(state.last_caught_up) <= max_lag
+                        }
+                    })
+                    .collect()
+            })
+            .collect();
+        drop(followers);
+
+        for ((replica, topic_id, registration), isr) in led.iter().zip(isrs) {
+            if isr.len() < registration.isr.len() {
+                eprintln!(
+                    "Shrinking the ISR of {replica} from {:?} to {isr:?}",
+                    registration.isr
+                );
+                let altered = broker.alter_isr(
+                    *topic_id,
+                    replica.partition,
+                    registration.partition_epoch,
+                    isr.clone(),
+                );
+                if let Err(e) = altered {
+                    eprintln!("Failed to shrink the ISR of {replica}: {e:#}");
+                    continue;
+                }
+            }
+            // Without the laggards the high watermark may move on
+            self.advance_high_watermark(broker, replica, &isr);
+        }
+    }
+
+    /// Current progress, to wait for more of with [`ReplicaManager::wait_for_progress`].
+    pub fn progress(&self) -> u64 {
+        *self.progress.lock().unwrap()
+    }
+
+    /// Wait until a log grew or a high watermark advanced since `progress`, or until the
+    /// deadline. Returns whether anything did.
+    pub fn wait_for_progress(&self, progress: u64, deadline: Instant) -> bool {
+        let mut current = self.progress.lock().unwrap();
+        while *current == progress {
+            let now = Instant::now();
+            if now >= deadline {
+                return false;
+            }
+            current = self
+                .progressed
+                .wait_timeout(current, deadline - now)
+                .unwrap()
+                .0;
+        }
+        true
+    }
+
+    fn notify_progress(&self) {
+        *self.progress.lock().unwrap() += 1;
+        self.progressed.notify_all();
+    }
+
+    /// Record how far a follower got, and bring it into the ISR once it reached the high
+    /// watermark, unless it is fenced or shutting down.
+    fn record_follower_fetch(
+        &self,
+        broker: &Broker,
+        replica: &TopicPartition,
+        topic_id: Uuid,
+        registration: &PartitionRegistration,
+        follower_id: i32,
+        fetch_offset: i64,
+    ) {
+        let now = Instant::now();
+        let Ok(leader_log_end_offset) = broker.logs.lock().unwrap().log_end_offset(replica) else {
+            return;
+        };
+        {
+            let mut followers = self.followers.lock().unwrap();
+            let state = followers
+                .entry(replica.clone())
+                .or_default()
+                .entry(follower_id)
+                .or_insert_with(|| FollowerState::new(now));
+            // Reaching what the leader had at the previous fetch counts as caught up then
+            if fetch_offset >= leader_log_end_offset {
+                state.last_caught_up = now;
+            } else if fetch_offset >= state.last_fetch_leader_log_end_offset {
+                state.last_caught_up = state.last_fetch;
+            }
+            state.log_end_offset = fetch_offset;
+            state.last_fetch = now;
+            state.last_fetch_leader_log_end_offset = leader_log_end_offset;
+        }
+
+        let mut isr = registration.isr.clone();
+        let high_watermark = broker.logs.lock().unwrap().high_watermark(replica);
+        // A fenced or shutting down broker stays out of the ISR
+        let eligible = broker
+            .metadata
+            .read()
+            .unwrap()
+            .brokers
+            .get(&follower_id)
+            .is_some_and(|registration| {
+                !registration.fenced && !registration.in_controlled_shutdown
+            });
+        if !isr.contains(&follower_id) && eligible && fetch_offset >= high_watermark {
+            isr.push(follower_id);
+            eprintln!("Expanding the ISR of {replica} to {isr:?}");
+            let altered = broker.alter_isr(
+                topic_id,
+                replica.partition,
+                registration.partition_epoch,
+                isr.clone(),
+            );
+            if let Err(e) = altered {
+                eprintln!("Failed to expand the ISR of {replica}: {e:#}");
+                return;
+            }
+        }
+        self.advance_high_watermark(broker, replica, &isr);
+    }
+
+    /// Move the high watermark of a partition this broker leads up to the smallest log
+    /// end offset in its ISR. It never moves backwards.
+    fn advance_high_watermark(&self, broker: &Broker, replica: &TopicPartition, isr: &[i32]) {
+        let followers = self.followers.lock().unwrap();
+        let mut logs = broker.logs.lock().unwrap();
+        let Ok(log_end_offset) = logs.log_end_offset(replica) else {
+            return;
+        };
+        let states = followers.get(replica);
+        let high_watermark = isr
+            .iter()
+            .filter(|&&id| id != broker.config.node_id)
+            .map(|id| {
+                states
+                    .and_then(|states| states.get(id))
+                    .map_or(0, |state| state.log_end_offset)
+            })
+            .fold(log_end_offset, i64::min);
+        if high_watermark > logs.high_watermark(replica) {
+            logs.set_high_watermark(replica, high_watermark);
+            drop(logs);
+            drop(followers);
+            self.notify_progress();
+        }
+    }
+}
+
+/// Records of a partition from `fetch_offset` up to `max_offset`, along with its log
+/// start offset. Consumers read the records of tiered topics that are no longer local
+/// from remote storage, while followers are told to rebuild their log from there.
+fn read_log(
+    broker: &Broker,
+    topic_id: Uuid,
+    replica: &TopicPartition,
+    is_follower: bool,
+    fetch_offset: i64,
+    max_offset: i64,
+    max_bytes: usize,
+) -> Result<(Vec<u8>, i64), i16> {
+    let remote = broker
+        .remote_log_manager(&replica.topic)
+        .map(|remote| (remote, TopicIdPartition::new(topic_id, replica.clone())));
+    let (read, local_start) = {
+        let mut logs = broker.logs.lock().unwrap();
+        let read = logs.read(replica, fetch_offset, max_offset, max_bytes);
+        (
+            read,
+            logs.log_start_offset(replica).map_err(|e| e.error_code())?,
+        )
+    };
+    let Some((remote, partition)) = remote else {
+        return Ok((read.map_err(|e| e.error_code())?, local_start));
+    };
+    let remote_start = remote.log_start_offset(&partition).map_err(|e| {
+        eprintln!("Cannot read the remote log metadata of {replica}: {e:#}");
+        error::KAFKA_STORAGE_ERROR
+    })?;
+    let log_start_offset = remote_start.map_or(local_start, |start| start.min(local_start));
+    match read {
+        Ok(records) => Ok((records, log_start_offset)),
+        Err(LogDirError::OffsetOutOfRange { offset, start, .. })
+            if offset < start && offset >= log_start_offset =>
+        {
+            if is_follower {
+                return Err(error::OFFSET_MOVED_TO_TIERED_STORAGE);
+            }
+            match remote.read(&partition, offset, max_bytes) {
+                Ok(Some(records)) => Ok((records, log_start_offset)),
+                Ok(None) => Err(error::OFFSET_OUT_OF_RANGE),
+                Err(e) => {
+                    eprintln!("Cannot read {replica} from remote storage: {e:#}");
+                    Err(error::KAFKA_STORAGE_ERROR)
+                }
+            }
+        }
+        Err(e) => Err(e.error_code()),
+    }
+}
+
+/// Topic id and registration of a partition this broker leads.
+fn led_partition(
+    broker: &Broker,
+    replica: &TopicPartition,
+) -> Result<(Uuid, PartitionRegistration), i16> {
+    let metadata = broker.metadata.read().unwrap();
+    let (topic_id, registration) = metadata
+        .partition(&replica.topic, replica.partition)
+        .ok_or(error::UNKNOWN_TOPIC_OR_PARTITION)?;
+    if registration.leader != broker.config.node_id {
+        return Err(error::NOT_LEADER_OR_FOLLOWER);
+    }
+    Ok((topic_id, registration.clone()))
+}
+
+/// Topic id and registration of a partition this broker hosts a replica of.
+fn hosted_partition(
+    broker: &Broker,
+    replica: &TopicPartition,
+) -> Result<(Uuid, PartitionRegistration), i16> {
+    let metadata = broker.metadata.read().unwrap();
+    let (topic_id, registration) = metadata
+        .partition(&replica.topic, replica.partition)
+        .ok_or(error::UNKNOWN_TOPIC_OR_PARTITION)?;
+    if !registration.replicas.contains(&broker.config.node_id) {
+        return Err(error::NOT_LEADER_OR_FOLLOWER);
+    }
+    Ok((topic_id, registration.clone()))
+}
+
+/// Check the leader epoch a client knows against the current one, -1 standing for
+/// any.
+fn check_leader_epoch(leader_epoch: i32, registration: &PartitionRegistration) -> Result<(), i16> {
+    if leader_epoch >= 0 && leader_epoch < registration.leader_epoch {
+        return Err(error::FENCED_LEADER_EPOCH);
+    }
+    if leader_epoch > registration.leader_epoch {
+        return Err(error::UNKNOWN_LEADER_EPOCH);
+    }
+    Ok(())
+}
+
+/// Whether the ISR of the partition is large enough for acks=all writes.
+fn has_min_isr(
+    broker: &Broker,
+    replica: &TopicPartition,
+    registration: &PartitionRegistration,
+) -> bool {
+    registration.isr.len() as i32 >= broker.log_config(&replica.topic).min_insync_replicas
+}
@@ -0,0 +1,485 @@
+use super::KafkaPrincipal;
+
+/// Name of the single CLUSTER resource.
+pub const CLUSTER_RESOURCE_NAME: &str = "kafka-cluster";
+/// Resource name, principal or host matching everything in a literal ACL.
+pub const WILDCARD: &str = "*";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum ResourceType {
+    Unknown,
+    Any,
+    Topic,
+    Group,
+    Cluster,
+    TransactionalId,
+    DelegationToken,
+    User,
+}
+
+impl ResourceType {
+    pub fn from_code(code: i8) -> Self {
+        match code {
+            1 => ResourceType::Any,
+            2 => ResourceType::Topic,
+            3 => ResourceType::Group,
+            4 => ResourceType::Cluster,
+            5 => ResourceType::TransactionalId,
+            6 => ResourceType::DelegationToken,
+            7 => ResourceType::User,
+            _ => ResourceType::Unknown,
+        }
+    }
+
+    pub fn code(&self) -> i8 {
+        match self {
+            ResourceType::Unknown => 0,
+            ResourceType::Any => 1,
+            ResourceType::Topic => 2,
+            ResourceType::Group => 3,
+            ResourceType::Cluster => 4,
+            ResourceType::TransactionalId => 5,
+            ResourceType::DelegationToken => 6,
+            ResourceType::User => 7,
+        }
+    }
+
+    /// Operations that can be granted on resources of this type, as listed in
+    /// authorized-operations bitfields.
+    pub fn operations(&self) -> &'static [AclOperation] {
+        use AclOperation::*;
+        match self {
+            ResourceType::Topic => &[
+                Read,
+                Write,
+                Create,
+                Delete,
+                Alter,
+                Describe,
+                DescribeConfigs,
+                AlterConfigs,
+            ],
+            ResourceType::Group => &[Read, Describe, Delete, DescribeConfigs, AlterConfigs],
+            ResourceType::Cluster => &[
+                Create,
+                ClusterAction,
+                DescribeConfigs,
+                AlterConfigs,
+                IdempotentWrite,
+                Alter,
+                Describe,
+            ],
+            ResourceType::TransactionalId => &[Describe, Write],
+            ResourceType::DelegationToken => &[Describe],
+            ResourceType::User => &[CreateTokens, DescribeTokens],
+            ResourceType::Unknown | ResourceType::Any => &[],
+        }
+    }
+
+    /// Bitfield of the operations for which `authorized` holds, with bit `n` set for the
+    /// operation of code `n`.
+    pub fn authorized_operations(&self, authorized: impl Fn(AclOperation) -> bool) -> i32 {
+        self.operations()
+            .iter()
+            .filter(|&&operation| authorized(operation))
+            .fold(0, |bits, operation| bits | 1 << operation.code())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum PatternType {
+    Unknown,
+    /// Filter only: any pattern type
+    Any,
+    /// Filter only: every binding that applies to the filter's resource name
+    Match,
+    Literal,
+    Prefixed,
+}
+
+impl PatternType {
+    pub fn from_code(code: i8) -> Self {
+        match code {
+            1 => PatternType::Any,
+            2 => PatternType::Match,
+            3 => PatternType::Literal,
+            4 => PatternType::Prefixed,
+            _ => PatternType::Unknown,
+        }
+    }
+
+    pub fn code(&self) -> i8 {
+        match self {
+            PatternType::Unknown => 0,
+            PatternType::Any => 1,
+            PatternType::Match => 2,
+            PatternType::Literal => 3,
+            PatternType::Prefixed => 4,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum AclOperation {
+    Unknown,
+    Any,
+    All,
+    Read,
+    Write,
+    Create,
+    Delete,
+    Alter,
+    Describe,
+    ClusterAction,
+    DescribeConfigs,
+    AlterConfigs,
+    IdempotentWrite,
+    CreateTokens,
+    DescribeTokens,
+}
+
+impl AclOperation {
+    pub fn from_code(code: i8) -> Self {
+        use AclOperation::*;
+        match code {
+            1 => Any,
+            2 => All,
+            3 => Read,
+            4 => Write,
+            5 => Create,
+            6 => Delete,
+            7 => Alter,
+            8 => Describe,
+            9 => ClusterAction,
+            10 => DescribeConfigs,
+            11 => AlterConfigs,
+            12 => IdempotentWrite,
+            13 => CreateTokens,
+            14 => DescribeTokens,
+            _ => Unknown,
+        }
+    }
+
+    pub fn code(&self) -> i8 {
+        use AclOperation::*;
+        match self {
+            Unknown => 0,
+            Any => 1,
+            All => 2,
+            Read => 3,
+            Write => 4,
+            Create => 5,
+            Delete => 6,
+            Alter => 7,
+            Describe => 8,
+            ClusterAction => 9,
+            DescribeConfigs => 10,
+            AlterConfigs => 11,
+            IdempotentWrite => 12,
+            CreateTokens => 13,
+            DescribeTokens => 14,
+        }
+    }
+
+    /// Whether an ALLOW entry for this operation also grants `operation`.
+    pub fn implies(&self, operation: AclOperation) -> bool {
+        use AclOperation::*;
+        if *self == All || *self == operation {
+            return true;
+        }
+        match operation {
+            Describe => matches!(self, Read | Write | Delete | Alter),
+            DescribeConfigs => *self == AlterConfigs,
+            _ => false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum AclPermissionType {
+    Unknown,
+    Any,
+    Deny,
+    Allow,
+}
+
+impl AclPermissionType {
+    pub fn from_code(code: i8) -> Self {
+        match code {
+            1 => AclPermissionType::Any,
+            2 => AclPermissionType::Deny,
+            3 => AclPermissionType::Allow,
+            _ => AclPermissionType::Unknown,
+        }
+    }
+
+    pub fn code(&self) -> i8 {
+        match self {
+            AclPermissionType::Unknown => 0,
+            AclPermissionType::Any => 1,
+            AclPermissionType::Deny => 2,
+            AclPermissionType::Allow => 3,
+        }
+    }
+}
+
+/// A single access control entry bound to a resource pattern.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct AclBinding {
+    pub resource_type: ResourceType,
+    pub resource_name: String,
+    pub pattern_type: PatternType,
+    /// Principal in its `Type:name` form, `User:*` matching every user
+    pub principal: String,
+    pub host: String,
+    pub operation: AclOperation,
+    pub permission_type: AclPermissionType,
+}
+
+impl AclBinding {
+    /// Reason the binding cannot be stored, if any.
+    pub fn validate(&self) -> Result<(), String> {
+        if matches!(
+            self.resource_type,
+            ResourceType::Unknown | ResourceType::Any
+        ) {
+            return Err("Invalid resource type".to_string());
+        }
+        if !matches!(
+            self.pattern_type,
+            PatternType::Literal | PatternType::Prefixed
+        ) {
+            return Err("Invalid resource pattern type".to_string());
+        }
+        if self.resource_name.is_empty() {
+            return Err("Resource name must not be empty".to_string());
+        }
+        if self.resource_type == ResourceType::Cluster
+            && self.resource_name != CLUSTER_RESOURCE_NAME
+        {
+            return Err(format!(
+                "The only valid name for the CLUSTER resource is {CLUSTER_RESOURCE_NAME}"
+            ));
+        }
+        if KafkaPrincipal::parse(&self.principal).is_none() {
+            return Err(format!("Invalid principal {}", self.principal));
+        }
+        if self.host.is_empty() {
+            return Err("Host must not be empty".to_string());
+        }
+        if matches!(self.operation, AclOperation::Unknown | AclOperation::Any) {
+            return Err("Invalid operation".to_string());
+        }
+        if matches!(
+            self.permission_type,
+            AclPermissionType::Unknown | AclPermissionType::Any
+        ) {
+            return Err("Invalid permission type".to_string());
+        }
+        Ok(())
+    }
+
+    /// Whether the binding's resource pattern covers the named resource.
+    pub fn covers(&self, resource_type: ResourceType, resource_name: &str) -> bool {
+        if self.resource_type != resource_type {
+            return false;
+        }
+        match self.pattern_type {
+            PatternType::Literal => {
+                self.resource_name == resource_name || self.resource_name == WILDCARD
+            }
+            PatternType::Prefixed => resource_name.starts_with(&self.resource_name),
+            _ => false,
+        }
+    }
+}
+
+/// Selects bindings in DescribeAcls and DeleteAcls; `None` and `Any` match everything.
+#[derive(Debug, Clone)]
+pub struct AclBindingFilter {
+    pub resource_type: ResourceType,
+    pub resource_name: Option<String>,
+    pub pattern_type: PatternType,
+    pub principal: Option<String>,
+    pub host: Option<String>,
+    pub operation: AclOperation,
+    pub permission_type: AclPermissionType,
+}
+
+impl AclBindingFilter {
+    /// Reason the filter can never match, if any.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.resource_type == ResourceType::Unknown {
+            return Err("Invalid resource type filter".to_string());
+        }
+        if self.pattern_type == PatternType::Unknown {
+            return Err("Invalid resource pattern type filter".to_string());
+        }
+        if self.operation == AclOperation::Unknown {
+            return Err("Invalid operation filter".to_string());
+        }
+        if self.permission_type == AclPermissionType::Unknown {
+            return Err("Invalid permission type filter".to_string());
+        }
+        Ok(())
+    }
+
+    pub fn matches(&self, binding: &AclBinding) -> bool {
+        if self.resource_type != ResourceType::Any && self.resource_type != binding.resource_type {
+            return false;
+        }
+        let resource_matches = match (self.pattern_type, self.resource_name.as_deref()) {
+            (PatternType::Any, None) | (PatternType::Match, None) => true,
+            (PatternType::Any, Some(name)) => binding.resource_name == name,
+            (PatternType::Match, Some(name)) => match binding.pattern_type {
+                PatternType::Literal => {
+                    binding.resource_name == name || binding.resource_name == WILDCARD
+                }
+                PatternType::Prefixed => name.starts_with(&binding.resource_name),
+                _ => false,
+            },
+            (pattern_type, name) => {
+                binding.pattern_type == pattern_type
+                    && name.map_or(true, |name| binding.resource_name == name)
+            }
+        };
+        resource_matches
+            && self
+                .principal
+                .as_ref()
+                .map_or(true, |principal| *principal == binding.principal)
+            && self
+                .host
+                .as_ref()
+                .map_or(true, |host| *host == binding.host)
+            && (self.operation == AclOperation::Any || self.operation == binding.operation)
+            && (self.permission_type == AclPermissionType::Any
+                || self.permission_type == binding.permission_type)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn binding(pattern_type: PatternType, resource_name: &str) -> AclBinding {
+        AclBinding {
+            resource_type: ResourceType::Topic,
+            resource_name: resource_name.to_string(),
+            pattern_type,
+            principal: "User:alice".to_string(),
+            host: WILDCARD.to_string(),
+            operation: AclOperation::Read,
+            permission_type: AclPermissionType::Allow,
+        }
+    }
+
+    fn filter(pattern_type: PatternType, resource_name: Option<&str>) -> AclBindingFilter {
+        AclBindingFilter {
+            resource_type: ResourceType::Any,
+            resource_name: resource_name.map(str::to_string),
+            pattern_type,
+            principal: None,
+            host: None,
+            operation: AclOperation::Any,
+            permission_type: AclPermissionType::Any,
+        }
+    }
+
+    #[test]
+    fn literal_patterns_cover_their_own_name_or_everything_with_the_wildcard() {
+        let literal = binding(PatternType::Literal, "orders");
+        assert!(literal.covers(ResourceType::Topic, "orders"));
+        assert!(!literal.covers(ResourceType::Topic, "orders-eu"));
+        assert!(!literal.covers(ResourceType::Group, "orders"));
+
+        let wildcard = binding(PatternType::Literal, WILDCARD);
+        assert!(wildcard.covers(ResourceType::Topic, "anything"));
+    }
+
+    #[test]
+    fn prefixed_patterns_cover_names_starting_with_the_prefix() {
+        let prefixed = binding(PatternType::Prefixed, "orders");
+        assert!(prefixed.covers(ResourceType::Topic, "orders"));
+        assert!(prefixed.covers(ResourceType::Topic, "orders-eu"));
+        assert!(!prefixed.covers(ResourceType::Topic, "eu-orders"));
+        // A prefixed "*" is a plain prefix, not the wildcard
+        assert!(!binding(PatternType::Prefixed, WILDCARD).covers(ResourceType::Topic, "orders"));
+    }
+
+    #[test]
+    fn filters_select_bindings_by_pattern_type() {
+        let literal = binding(PatternType::Literal, "orders");
+        let prefixed = binding(PatternType::Prefixed, "ord");
+        let wildcard = binding(PatternType::Literal, WILDCARD);
+
+        let any = filter(PatternType::Any, Some("orders"));
+        assert!(any.matches(&literal));
+        assert!(!any.matches(&prefixed));
+
+        let exact = filter(PatternType::Prefixed, Some("ord"));
+        assert!(exact.matches(&prefixed));
+        assert!(!exact.matches(&literal));
+
+        // MATCH selects every binding that applies to the name
+        let applying = filter(PatternType::Match, Some("orders"));
+        assert!(applying.matches(&literal));
+        assert!(applying.matches(&prefixed));
+        assert!(applying.matches(&wildcard));
+        assert!(!filter(PatternType::Match, Some("payments")).matches(&prefixed));
+
+        assert!(filter(PatternType::Any, None).matches(&prefixed));
+    }
+
+    #[test]
+    fn filters_compare_principal_host_operation_and_permission() {
+        let acl = binding(PatternType::Literal, "orders");
+        let mut by_principal = filter(PatternType::Any, None);
+        by_principal.principal = Some("User:alice".to_string());
+        assert!(by_principal.matches(&acl));
+        by_principal.principal = Some("User:bob".to_string());
+        assert!(!by_principal.matches(&acl));
+
+        let mut by_operation = filter(PatternType::Any, None);
+        by_operation.operation = AclOperation::Write;
+        assert!(!by_operation.matches(&acl));
+
+        let mut by_permission = filter(PatternType::Any, None);
+        by_permission.permission_type = AclPermissionType::Deny;
+        assert!(!by_permission.matches(&acl));
+
+        let mut by_resource_type = filter(PatternType::Any, None);
+        by_resource_type.resource_type = ResourceType::Group;
+        assert!(!by_resource_type.matches(&acl));
+    }
+
+    #[test]
+    fn read_write_alter_and_delete_imply_describe() {
+        use AclOperation::*;
+        for operation in [Read, Write, Alter, Delete, All] {
+            assert!(operation.implies(Describe), "{operation:?}");
+        }
+        for operation in [Create, DescribeConfigs, ClusterAction] {
+            assert!(!operation.implies(Describe), "{operation:?}");
+        }
+        assert!(AlterConfigs.implies(DescribeConfigs));
+        assert!(!Read.implies(Write));
+        assert!(All.implies(IdempotentWrite));
+    }
+
+    #[test]
+    fn sets_one_bit_per_authorized_operation() {
+        let bits = ResourceType::Topic
+            .authorized_operations(|operation| operation == AclOperation::Describe);
+        assert_eq!(bits, 1 << AclOperation::Describe.code());
+
+        let bits = ResourceType::Topic.authorized_operations(|operation| {
+            matches!(operation, AclOperation::Read | AclOperation::Write)
+        });
+        assert_eq!(bits, 1 << 3 | 1 << 4);
+
+        // Operations not applicable to the resource type are never reported
+        let bits = ResourceType::Group.authorized_operations(|_| true);
+        assert_eq!(bits, 1 << 3 | 1 << 8 | 1 << 6 | 1 << 10 | 1 << 11);
+    }
+}
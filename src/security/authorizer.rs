@@ -0,0 +1,310 @@
+use super::{
+    acl::{AclOperation, AclPermissionType, ResourceType, WILDCARD},
+    KafkaPrincipal,
+};
+use crate::{config::AuthorizerConfig, metadata::MetadataImage};
+
+/// An operation a principal attempts on a resource.
+#[derive(Debug, Clone, Copy)]
+pub struct Action<'a> {
+    pub principal: &'a KafkaPrincipal,
+    pub host: &'a str,
+    pub operation: AclOperation,
+    pub resource_type: ResourceType,
+    pub resource_name: &'a str,
+}
+
+/// Decides whether an action is allowed, given the ACLs of the metadata image.
+pub trait Authorizer: Send + Sync {
+    fn authorize(&self, metadata: &MetadataImage, action: &Action<'_>) -> bool;
+}
+
+/// Built-in authorizer evaluating the `AccessControlEntryRecord`s of the metadata log.
+#[derive(Debug)]
+pub struct AclAuthorizer {
+    super_users: Vec<KafkaPrincipal>,
+    allow_everyone_if_no_acl_found: bool,
+}
+
+impl AclAuthorizer {
+    pub fn new(config: &AuthorizerConfig) -> Self {
+        AclAuthorizer {
+            super_users: config.super_users.clone(),
+            allow_everyone_if_no_acl_found: config.allow_everyone_if_no_acl_found,
+        }
+    }
+}
+
+impl Authorizer for AclAuthorizer {
+    fn authorize(&self, metadata: &MetadataImage, action: &Action<'_>) -> bool {
+        if self.super_users.contains(action.principal) {
+            return true;
+        }
+
+        let principal = action.principal.to_string();
+        let wildcard_principal = format!("{}:{WILDCARD}", action.principal.principal_type);
+        let mut resource_has_acls = false;
+        let mut allowed = false;
+        for acl in metadata.acls.values() {
+            if !acl.covers(action.resource_type, action.resource_name) {
+                continue;
+            }
+            resource_has_acls = true;
+            if acl.principal != principal && acl.principal != wildcard_principal {
+                continue;
+            }
+            if acl.host != action.host && acl.host != WILDCARD {
+                continue;
+            }
+            match acl.permission_type {
+                // A matching DENY always wins, and does not extend to implied operations
+                AclPermissionType::Deny
+                    if acl.operation == AclOperation::All || acl.operation == action.operation =>
+                {
+                    return false;
+                }
+                AclPermissionType::Allow if acl.operation.implies(action.operation) => {
+                    allowed = true;
+                }
+                _ => {}
+            }
+        }
+        allowed || (!resource_has_acls && self.allow_everyone_if_no_acl_found)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::security::acl::{AclBinding, PatternType};
+    use uuid::Uuid;
+
+    fn allow(principal: &str, operation: AclOperation) -> AclBinding {
+        AclBinding {
+            resource_type: ResourceType::Topic,
+            resource_name: "orders".to_string(),
+            pattern_type: PatternType::Literal,
+            principal: principal.to_string(),
+            host: WILDCARD.to_string(),
+            operation,
+            permission_type: AclPermissionType::Allow,
+        }
+    }
+
+    fn deny(principal: &str, operation: AclOperation) -> AclBinding {
+        AclBinding {
+            permission_type: AclPermissionType::Deny,
+            ..allow(principal, operation)
+        }
+    }
+
+    fn image(acls: Vec<AclBinding>) -> MetadataImage {
+        MetadataImage {
+            acls: acls
+                .into_iter()
+                .enumerate()
+                .map(|(i, acl)| (Uuid::from_u128(i as u128 + 1), acl))
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    fn authorizer(allow_everyone_if_no_acl_found: bool) -> AclAuthorizer {
+        AclAuthorizer::new(&AuthorizerConfig {
+            super_users: vec![KafkaPrincipal::user("admin")],
+            allow_everyone_if_no_acl_found,
+        })
+    }
+
+    fn is_allowed(
+        metadata: &MetadataImage,
+        user: &str,
+        host: &str,
+        operation: AclOperation,
+        topic: &str,
+    ) -> bool {
+        let principal = KafkaPrincipal::user(user);
+        authorizer(false).authorize(
+            metadata,
+            &Action {
+                principal: &principal,
+                host,
+                operation,
+                resource_type: ResourceType::Topic,
+                resource_name: topic,
+            },
+        )
+    }
+
+    #[test]
+    fn allows_only_the_operations_granted_to_the_principal() {
+        let metadata = image(vec![allow("User:alice", AclOperation::Read)]);
+        assert!(is_allowed(
+            &metadata,
+            "alice",
+            "10.0.0.1",
+            AclOperation::Read,
+            "orders"
+        ));
+        assert!(!is_allowed(
+            &metadata,
+            "alice",
+            "10.0.0.1",
+            AclOperation::Write,
+            "orders"
+        ));
+        assert!(!is_allowed(
+            &metadata,
+            "bob",
+            "10.0.0.1",
+            AclOperation::Read,
+            "orders"
+        ));
+        assert!(!is_allowed(
+            &metadata,
+            "alice",
+            "10.0.0.1",
+            AclOperation::Read,
+            "payments"
+        ));
+    }
+
+    #[test]
+    fn matches_the_wildcard_principal_and_host() {
+        let metadata = image(vec![allow("User:*", AclOperation::Read)]);
+        assert!(is_allowed(
+            &metadata,
+            "bob",
+            "10.0.0.1",
+            AclOperation::Read,
+            "orders"
+        ));
+
+        let metadata = image(vec![AclBinding {
+            host: "10.0.0.1".to_string(),
+            ..allow("User:alice", AclOperation::Read)
+        }]);
+        assert!(is_allowed(
+            &metadata,
+            "alice",
+            "10.0.0.1",
+            AclOperation::Read,
+            "orders"
+        ));
+        assert!(!is_allowed(
+            &metadata,
+            "alice",
+            "10.0.0.2",
+            AclOperation::Read,
+            "orders"
+        ));
+    }
+
+    #[test]
+    fn deny_takes_priority_over_allow() {
+        let metadata = image(vec![
+            allow("User:*", AclOperation::All),
+            deny("User:alice", AclOperation::Write),
+        ]);
+        assert!(!is_allowed(
+            &metadata,
+            "alice",
+            "10.0.0.1",
+            AclOperation::Write,
+            "orders"
+        ));
+        assert!(is_allowed(
+            &metadata,
+            "alice",
+            "10.0.0.1",
+            AclOperation::Read,
+            "orders"
+        ));
+        assert!(is_allowed(
+            &metadata,
+            "bob",
+            "10.0.0.1",
+            AclOperation::Write,
+            "orders"
+        ));
+
+        let metadata = image(vec![
+            allow("User:alice", AclOperation::Read),
+            deny("User:*", AclOperation::All),
+        ]);
+        assert!(!is_allowed(
+            &metadata,
+            "alice",
+            "10.0.0.1",
+            AclOperation::Read,
+            "orders"
+        ));
+    }
+
+    #[test]
+    fn grants_describe_through_implying_operations_but_denies_only_exact_ones() {
+        let metadata = image(vec![allow("User:alice", AclOperation::Write)]);
+        assert!(is_allowed(
+            &metadata,
+            "alice",
+            "10.0.0.1",
+            AclOperation::Describe,
+            "orders"
+        ));
+
+        let metadata = image(vec![
+            allow("User:alice", AclOperation::Describe),
+            deny("User:alice", AclOperation::Write),
+        ]);
+        assert!(is_allowed(
+            &metadata,
+            "alice",
+            "10.0.0.1",
+            AclOperation::Describe,
+            "orders"
+        ));
+    }
+
+    #[test]
+    fn falls_back_to_super_users_and_allow_everyone_if_no_acl_found() {
+        let metadata = image(vec![deny("User:*", AclOperation::All)]);
+        let admin = KafkaPrincipal::user("admin");
+        let action = Action {
+            principal: &admin,
+            host: "10.0.0.1",
+            operation: AclOperation::Alter,
+            resource_type: ResourceType::Topic,
+            resource_name: "orders",
+        };
+        assert!(authorizer(false).authorize(&metadata, &action));
+
+        let alice = KafkaPrincipal::user("alice");
+        let action = Action {
+            principal: &alice,
+            ..action
+        };
+        assert!(!authorizer(true).authorize(&metadata, &action));
+        assert!(!authorizer(false).authorize(&image(vec![]), &action));
+        assert!(authorizer(true).authorize(&image(vec![]), &action));
+    }
+
+    #[test]
+    fn builds_authorized_operations_from_the_acls() {
+        let metadata = image(vec![
+            allow("User:alice", AclOperation::Read),
+            allow("User:alice", AclOperation::AlterConfigs),
+        ]);
+        let bits = ResourceType::Topic.authorized_operations(|operation| {
+            is_allowed(&metadata, "alice", "10.0.0.1", operation, "orders")
+        });
+        let expected = [
+            AclOperation::Read,
+            AclOperation::Describe,
+            AclOperation::DescribeConfigs,
+            AclOperation::AlterConfigs,
+        ]
+        .iter()
+        .fold(0, |bits, operation| bits | 1 << operation.code());
+        assert_eq!(bits, expected);
+    }
+}
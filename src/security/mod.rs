@@ -0,0 +1,48 @@
+pub mod acl;
+pub mod authorizer;
+pub mod sasl;
+pub mod scram;
+pub mod tls;
+
+use std::fmt;
+
+/// Identity a connection acts as once it has been authenticated.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct KafkaPrincipal {
+    pub principal_type: String,
+    pub name: String,
+}
+
+impl KafkaPrincipal {
+    pub const USER_TYPE: &'static str = "User";
+
+    pub fn user(name: impl Into<String>) -> Self {
+        KafkaPrincipal {
+            principal_type: Self::USER_TYPE.to_string(),
+            name: name.into(),
+        }
+    }
+
+    /// Parse the `Type:name` form used by ACLs and `super.users`.
+    pub fn parse(value: &str) -> Option<Self> {
+        let (principal_type, name) = value.split_once(':')?;
+        if principal_type.is_empty() || name.is_empty() {
+            return None;
+        }
+        Some(KafkaPrincipal {
+            principal_type: principal_type.to_string(),
+            name: name.to_string(),
+        })
+    }
+
+    /// Principal of connections that have not authenticated.
+    pub fn anonymous() -> Self {
+        Self::user("ANONYMOUS")
+    }
+}
+
+impl fmt::Display for KafkaPrincipal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.principal_type, self.name)
+    }
+}
@@ -0,0 +1,238 @@
+use std::{
+    collections::HashMap,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use super::{
+    scram::{ScramAuthenticator, ScramMechanism},
+    KafkaPrincipal,
+};
+use crate::{
+    broker::Broker,
+    config::{SaslConfig, SaslMechanism},
+    protocol::{api_keys::ApiKey, error},
+};
+
+/// Outcome of feeding one client token to a mechanism.
+pub enum SaslStep {
+    /// The exchange continues and the token must be sent back to the client
+    Challenge(Vec<u8>),
+    /// The client is authenticated; the token is the final server message
+    Complete {
+        principal: KafkaPrincipal,
+        response: Vec<u8>,
+    },
+}
+
+#[derive(Debug)]
+pub struct SaslFailure {
+    pub error_code: i16,
+    pub message: String,
+}
+
+impl SaslFailure {
+    pub fn invalid(message: &str) -> Self {
+        SaslFailure {
+            error_code: error::SASL_AUTHENTICATION_FAILED,
+            message: format!("Authentication failed: {message}"),
+        }
+    }
+
+    /// Failure due to an unknown user or a wrong password; the two are not told apart.
+    pub fn credentials() -> Self {
+        SaslFailure {
+            error_code: error::SASL_AUTHENTICATION_FAILED,
+            message: "Authentication failed: Invalid username or password".to_string(),
+        }
+    }
+
+    fn illegal_state(message: &str) -> Self {
+        SaslFailure {
+            error_code: error::ILLEGAL_SASL_STATE,
+            message: message.to_string(),
+        }
+    }
+}
+
+/// Server side of one SASL mechanism for a single authentication exchange.
+pub trait SaslAuthenticator: Send {
+    fn evaluate(&mut self, token: &[u8]) -> Result<SaslStep, SaslFailure>;
+}
+
+/// The PLAIN mechanism (RFC 4616) checked against the users of the JAAS config.
+pub struct PlainAuthenticator {
+    users: HashMap<String, String>,
+}
+
+impl SaslAuthenticator for PlainAuthenticator {
+    fn evaluate(&mut self, token: &[u8]) -> Result<SaslStep, SaslFailure> {
+        let Ok(message) = std::str::from_utf8(token) else {
+            return Err(SaslFailure::invalid("PLAIN messages must be UTF-8"));
+        };
+        let mut parts = message.split('\0');
+        let (Some(authzid), Some(username), Some(password), None) =
+            (parts.next(), parts.next(), parts.next(), parts.next())
+        else {
+            return Err(SaslFailure::invalid("invalid PLAIN message"));
+        };
+        if username.is_empty() {
+            return Err(SaslFailure::invalid("user name must not be empty"));
+        }
+        if !authzid.is_empty() && authzid != username {
+            return Err(SaslFailure::invalid(
+                "authorization id must match the authenticated user",
+            ));
+        }
+        if self.users.get(username).map(String::as_str) != Some(password) {
+            return Err(SaslFailure::credentials());
+        }
+        Ok(SaslStep::Complete {
+            principal: KafkaPrincipal::user(username),
+            response: vec![],
+        })
+    }
+}
+
+enum SaslState {
+    /// Waiting for SaslHandshake, either initially or to start a re-authentication
+    Handshake,
+    Authenticate(Box<dyn SaslAuthenticator>),
+    Authenticated,
+    /// The connection must be closed once the pending response is sent
+    Failed,
+}
+
+/// SASL state of one connection on a SASL_PLAINTEXT or SASL_SSL listener.
+pub struct SaslSession {
+    config: SaslConfig,
+    broker: Arc<Broker>,
+    state: SaslState,
+    mechanism: Option<SaslMechanism>,
+    principal: Option<KafkaPrincipal>,
+    session_expiry: Option<Instant>,
+}
+
+impl SaslSession {
+    pub fn new(config: SaslConfig, broker: Arc<Broker>) -> Self {
+        SaslSession {
+            config,
+            broker,
+            state: SaslState::Handshake,
+            mechanism: None,
+            principal: None,
+            session_expiry: None,
+        }
+    }
+
+    pub fn enabled_mechanisms(&self) -> Vec<String> {
+        self.config
+            .enabled_mechanisms
+            .iter()
+            .map(|m| m.name().to_string())
+            .collect()
+    }
+
+    pub fn principal(&self) -> Option<&KafkaPrincipal> {
+        self.principal.as_ref()
+    }
+
+    pub fn has_failed(&self) -> bool {
+        matches!(self.state, SaslState::Failed)
+    }
+
+    fn is_expired(&self) -> bool {
+        self.session_expiry
+            .is_some_and(|expiry| Instant::now() >= expiry)
+    }
+
+    /// Whether a request of this API may be processed in the current state. Only the
+    /// APIs served before authentication, the SASL ones and ApiVersions, are served
+    /// before it completes or once the session has expired.
+    pub fn permits(&self, api: &ApiKey) -> bool {
+        api.before_authentication
+            || (matches!(self.state, SaslState::Authenticated) && !self.is_expired())
+    }
+
+    /// Select the mechanism for the next authentication exchange.
+    pub fn handshake(&mut self, mechanism: &str) -> i16 {
+        if !matches!(self.state, SaslState::Handshake | SaslState::Authenticated) {
+            self.state = SaslState::Failed;
+            return error::ILLEGAL_SASL_STATE;
+        }
+        let Some(mechanism) = SaslMechanism::from_name(mechanism)
+            .filter(|m| self.config.enabled_mechanisms.contains(m))
+        else {
+            self.state = SaslState::Failed;
+            return error::UNSUPPORTED_SASL_MECHANISM;
+        };
+        // Re-authentication has to stick to the mechanism of the session
+        if self.mechanism.is_some_and(|previous| previous != mechanism) {
+            self.state = SaslState::Failed;
+            return error::ILLEGAL_SASL_STATE;
+        }
+
+        let authenticator: Box<dyn SaslAuthenticator> = match ScramMechanism::from_sasl(mechanism) {
+            None => Box::new(PlainAuthenticator {
+                users: self.config.plain_users.clone(),
+            }),
+            Some(scram) => {
+                let broker = Arc::clone(&self.broker);
+                Box::new(ScramAuthenticator::new(scram, move |user, mechanism| {
+                    broker
+                        .metadata
+                        .read()
+                        .unwrap()
+                        .scram_credential(user, mechanism)
+                        .cloned()
+                }))
+            }
+        };
+        self.mechanism = Some(mechanism);
+        self.state = SaslState::Authenticate(authenticator);
+        error::NONE
+    }
+
+    /// Feed one SaslAuthenticate token to the selected mechanism and return the token
+    /// to send back.
+    pub fn authenticate(&mut self, token: &[u8]) -> Result<Vec<u8>, SaslFailure> {
+        let SaslState::Authenticate(authenticator) = &mut self.state else {
+            self.state = SaslState::Failed;
+            return Err(SaslFailure::illegal_state(
+                "SaslAuthenticate received before SaslHandshake",
+            ));
+        };
+        match authenticator.evaluate(token) {
+            Ok(SaslStep::Challenge(challenge)) => Ok(challenge),
+            Ok(SaslStep::Complete {
+                principal,
+                response,
+            }) => {
+                if self.principal.as_ref().is_some_and(|p| *p != principal) {
+                    self.state = SaslState::Failed;
+                    return Err(SaslFailure::invalid(
+                        "cannot change principals during re-authentication",
+                    ));
+                }
+                self.principal = Some(principal);
+                self.state = SaslState::Authenticated;
+                self.session_expiry = (self.config.max_reauth_ms > 0).then(|| {
+                    Instant::now() + Duration::from_millis(self.config.max_reauth_ms as u64)
+                });
+                Ok(response)
+            }
+            Err(failure) => {
+                self.state = SaslState::Failed;
+                Err(failure)
+            }
+        }
+    }
+
+    /// Lifetime of the session that just authenticated, 0 when re-authentication is disabled.
+    pub fn session_lifetime_ms(&self) -> i64 {
+        match (&self.state, self.session_expiry) {
+            (SaslState::Authenticated, Some(_)) => self.config.max_reauth_ms,
+            _ => 0,
+        }
+    }
+}
@@ -0,0 +1,431 @@
+use base64::{engine::general_purpose::STANDARD, Engine};
+use hmac::{Hmac, Mac};
+use rand::{distributions::Alphanumeric, Rng};
+use sha2::{Digest, Sha256, Sha512};
+use subtle::ConstantTimeEq;
+
+use super::{
+    sasl::{SaslAuthenticator, SaslFailure, SaslStep},
+    KafkaPrincipal,
+};
+use crate::config::SaslMechanism;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ScramMechanism {
+    Sha256,
+    Sha512,
+}
+
+impl ScramMechanism {
+    /// Value of the `mechanism` field in records and admin APIs.
+    pub fn from_type(mechanism: i8) -> Option<Self> {
+        match mechanism {
+            1 => Some(ScramMechanism::Sha256),
+            2 => Some(ScramMechanism::Sha512),
+            _ => None,
+        }
+    }
+
+    pub fn type_id(&self) -> i8 {
+        match self {
+            ScramMechanism::Sha256 => 1,
+            ScramMechanism::Sha512 => 2,
+        }
+    }
+
+    pub fn from_sasl(mechanism: SaslMechanism) -> Option<Self> {
+        match mechanism {
+            SaslMechanism::ScramSha256 => Some(ScramMechanism::Sha256),
+            SaslMechanism::ScramSha512 => Some(ScramMechanism::Sha512),
+            SaslMechanism::Plain => None,
+        }
+    }
+
+    pub fn min_iterations(&self) -> i32 {
+        4096
+    }
+
+    pub fn max_iterations(&self) -> i32 {
+        16384
+    }
+
+    fn hash(&self, data: &[u8]) -> Vec<u8> {
+        match self {
+            ScramMechanism::Sha256 => Sha256::digest(data).to_vec(),
+            ScramMechanism::Sha512 => Sha512::digest(data).to_vec(),
+        }
+    }
+
+    fn hmac(&self, key: &[u8], data: &[u8]) -> Vec<u8> {
+        match self {
+            ScramMechanism::Sha256 => {
+                let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts any key");
+                mac.update(data);
+                mac.finalize().into_bytes().to_vec()
+            }
+            ScramMechanism::Sha512 => {
+                let mut mac = Hmac::<Sha512>::new_from_slice(key).expect("HMAC accepts any key");
+                mac.update(data);
+                mac.finalize().into_bytes().to_vec()
+            }
+        }
+    }
+}
+
+/// What the broker stores for a SCRAM user; the password itself is never kept.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScramCredential {
+    pub salt: Vec<u8>,
+    pub stored_key: Vec<u8>,
+    pub server_key: Vec<u8>,
+    pub iterations: i32,
+}
+
+impl ScramCredential {
+    pub fn from_salted_password(
+        mechanism: ScramMechanism,
+        salt: Vec<u8>,
+        salted_password: &[u8],
+        iterations: i32,
+    ) -> Self {
+        let client_key = mechanism.hmac(salted_password, b"Client Key");
+        ScramCredential {
+            salt,
+            stored_key: mechanism.hash(&client_key),
+            server_key: mechanism.hmac(salted_password, b"Server Key"),
+            iterations,
+        }
+    }
+}
+
+/// Parse the `key=value` attributes of a SCRAM message.
+fn attributes(message: &str) -> Vec<(char, &str)> {
+    message
+        .split(',')
+        .filter_map(|part| {
+            let mut chars = part.chars();
+            match (chars.next(), chars.next()) {
+                (Some(key), Some('=')) => Some((key, &part[2..])),
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+fn attribute<'a>(attributes: &[(char, &'a str)], key: char) -> Option<&'a str> {
+    attributes.iter().find(|(k, _)| *k == key).map(|(_, v)| *v)
+}
+
+/// Reverse the `=2C` / `=3D` escaping SCRAM applies to user names.
+fn unescape_username(name: &str) -> Option<String> {
+    let unescaped = name.replace("=2C", ",").replace("=3D", "=");
+    (!unescaped.contains('=')).then_some(unescaped)
+}
+
+enum ScramState {
+    ClientFirst,
+    ClientFinal {
+        username: String,
+        credential: ScramCredential,
+        /// Channel binding flag and authorization id, echoed back base64 encoded in `c=`
+        gs2_header: String,
+        client_first_bare: String,
+        server_first: String,
+        nonce: String,
+    },
+    Done,
+}
+
+/// Server side of a SCRAM exchange (RFC 5802) for a single authentication.
+pub struct ScramAuthenticator<F> {
+    mechanism: ScramMechanism,
+    lookup: F,
+    state: ScramState,
+}
+
+impl<F> ScramAuthenticator<F>
+where
+    F: Fn(&str, ScramMechanism) -> Option<ScramCredential>,
+{
+    pub fn new(mechanism: ScramMechanism, lookup: F) -> Self {
+        ScramAuthenticator {
+            mechanism,
+            lookup,
+            state: ScramState::ClientFirst,
+        }
+    }
+
+    fn client_first(&mut self, message: &str) -> Result<SaslStep, SaslFailure> {
+        // gs2-header: channel binding flag, optional authzid, then the bare message
+        let mut parts = message.splitn(3, ',');
+        let (Some(cbind), Some(authzid), Some(bare)) = (parts.next(), parts.next(), parts.next())
+        else {
+            return Err(SaslFailure::invalid("invalid SCRAM client-first message"));
+        };
+        if cbind.starts_with('p') {
+            return Err(SaslFailure::invalid("channel binding is not supported"));
+        }
+        let attrs = attributes(bare);
+        let (Some(username), Some(client_nonce)) = (attribute(&attrs, 'n'), attribute(&attrs, 'r'))
+        else {
+            return Err(SaslFailure::invalid("invalid SCRAM client-first message"));
+        };
+        let Some(username) = unescape_username(username) else {
+            return Err(SaslFailure::invalid("invalid SCRAM user name"));
+        };
+        if let Some(authzid) = authzid.strip_prefix("a=") {
+            if unescape_username(authzid).as_deref() != Some(&username) {
+                return Err(SaslFailure::invalid(
+                    "authorization id must match the authenticated user",
+                ));
+            }
+        }
+        let Some(credential) = (self.lookup)(&username, self.mechanism) else {
+            return Err(SaslFailure::credentials());
+        };
+
+        let server_nonce: String = rand::thread_rng()
+            .sample_iter(&Alphanumeric)
+            .take(32)
+            .map(char::from)
+            .collect();
+        let nonce = format!("{client_nonce}{server_nonce}");
+        let server_first = format!(
+            "r={nonce},s={},i={}",
+            STANDARD.encode(&credential.salt),
+            credential.iterations
+        );
+        let challenge = server_first.clone().into_bytes();
+        self.state = ScramState::ClientFinal {
+            username,
+            credential,
+            gs2_header: format!("{cbind},{authzid},"),
+            client_first_bare: bare.to_string(),
+            server_first,
+            nonce,
+        };
+        Ok(SaslStep::Challenge(challenge))
+    }
+
+    fn client_final(&mut self, message: &str) -> Result<SaslStep, SaslFailure> {
+        let ScramState::ClientFinal {
+            username,
+            credential,
+            gs2_header,
+            client_first_bare,
+            server_first,
+            nonce,
+        } = std::mem::replace(&mut self.state, ScramState::Done)
+        else {
+            return Err(SaslFailure::invalid("unexpected SCRAM message"));
+        };
+
+        let Some((without_proof, proof)) = message.rsplit_once(",p=") else {
+            return Err(SaslFailure::invalid("invalid SCRAM client-final message"));
+        };
+        let attrs = attributes(without_proof);
+        if attribute(&attrs, 'r') != Some(nonce.as_str()) {
+            return Err(SaslFailure::invalid("SCRAM nonce mismatch"));
+        }
+        if attribute(&attrs, 'c') != Some(STANDARD.encode(&gs2_header).as_str()) {
+            return Err(SaslFailure::invalid("SCRAM channel binding mismatch"));
+        }
+        let Ok(proof) = STANDARD.decode(proof) else {
+            return Err(SaslFailure::invalid("invalid SCRAM client proof"));
+        };
+
+        let auth_message = format!("{client_first_bare},{server_first},{without_proof}");
+        let client_signature = self
+            .mechanism
+            .hmac(&credential.stored_key, auth_message.as_bytes());
+        if proof.len() != client_signature.len() {
+            return Err(SaslFailure::credentials());
+        }
+        let client_key: Vec<u8> = proof
+            .iter()
+            .zip(&client_signature)
+            .map(|(a, b)| a ^ b)
+            .collect();
+        // Compared in constant time so that timing does not leak how much of a forged
+        // proof was right
+        let stored_key = self.mechanism.hash(&client_key);
+        if !bool::from(stored_key.ct_eq(&credential.stored_key)) {
+            return Err(SaslFailure::credentials());
+        }
+
+        let server_signature = self
+            .mechanism
+            .hmac(&credential.server_key, auth_message.as_bytes());
+        Ok(SaslStep::Complete {
+            principal: KafkaPrincipal::user(username),
+            response: format!("v={}", STANDARD.encode(server_signature)).into_bytes(),
+        })
+    }
+}
+
+impl<F> SaslAuthenticator for ScramAuthenticator<F>
+where
+    F: Fn(&str, ScramMechanism) -> Option<ScramCredential> + Send,
+{
+    fn evaluate(&mut self, token: &[u8]) -> Result<SaslStep, SaslFailure> {
+        let Ok(message) = std::str::from_utf8(token) else {
+            return Err(SaslFailure::invalid("SCRAM messages must be UTF-8"));
+        };
+        match self.state {
+            ScramState::ClientFirst => self.client_first(message),
+            ScramState::ClientFinal { .. } => self.client_final(message),
+            ScramState::Done => Err(SaslFailure::invalid("SCRAM exchange already completed")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SALT: &[u8] = b"saltsaltsalt";
+    const ITERATIONS: i32 = 4096;
+
+    /// `Hi` of RFC 5802, PBKDF2 with the HMAC of the mechanism.
+    fn salted_password(mechanism: ScramMechanism, password: &str) -> Vec<u8> {
+        let mut block = mechanism.hmac(password.as_bytes(), &[SALT, &1u32.to_be_bytes()].concat());
+        let mut salted = block.clone();
+        for _ in 1..ITERATIONS {
+            block = mechanism.hmac(password.as_bytes(), &block);
+            salted.iter_mut().zip(&block).for_each(|(s, b)| *s ^= b);
+        }
+        salted
+    }
+
+    fn authenticator(
+        mechanism: ScramMechanism,
+    ) -> ScramAuthenticator<impl Fn(&str, ScramMechanism) -> Option<ScramCredential>> {
+        let salted = salted_password(mechanism, "alice-pw");
+        ScramAuthenticator::new(mechanism, move |user, requested| {
+            (user == "alice" && requested == mechanism).then(|| {
+                ScramCredential::from_salted_password(mechanism, SALT.to_vec(), &salted, ITERATIONS)
+            })
+        })
+    }
+
+    /// Client-final message of an exchange with `server_first`, with the proof of
+    /// `password` and channel binding `binding`, along with the expected server signature.
+    fn client_final(
+        mechanism: ScramMechanism,
+        password: &str,
+        client_first_bare: &str,
+        server_first: &str,
+        binding: &str,
+    ) -> (String, String) {
+        let nonce = attribute(&attributes(server_first), 'r').unwrap();
+        let without_proof = format!("c={},r={nonce}", STANDARD.encode(binding));
+        let auth_message = format!("{client_first_bare},{server_first},{without_proof}");
+        let salted = salted_password(mechanism, password);
+        let client_key = mechanism.hmac(&salted, b"Client Key");
+        let signature = mechanism.hmac(&mechanism.hash(&client_key), auth_message.as_bytes());
+        let proof: Vec<u8> = client_key
+            .iter()
+            .zip(&signature)
+            .map(|(a, b)| a ^ b)
+            .collect();
+        let server_key = mechanism.hmac(&salted, b"Server Key");
+        let server_signature = mechanism.hmac(&server_key, auth_message.as_bytes());
+        (
+            format!("{without_proof},p={}", STANDARD.encode(proof)),
+            format!("v={}", STANDARD.encode(server_signature)),
+        )
+    }
+
+    /// Run an exchange as user alice with `password`, answering with channel binding
+    /// `binding` for the gs2 header `gs2_header`.
+    fn exchange(
+        mechanism: ScramMechanism,
+        password: &str,
+        gs2_header: &str,
+        binding: &str,
+    ) -> (Result<SaslStep, SaslFailure>, String) {
+        let mut scram = authenticator(mechanism);
+        let bare = "n=alice,r=clientnonce";
+        let Ok(SaslStep::Challenge(server_first)) =
+            scram.evaluate(format!("{gs2_header}{bare}").as_bytes())
+        else {
+            panic!("expected a server-first message");
+        };
+        let server_first = String::from_utf8(server_first).unwrap();
+        assert!(server_first.starts_with("r=clientnonce"));
+        let (message, server_signature) =
+            client_final(mechanism, password, bare, &server_first, binding);
+        (scram.evaluate(message.as_bytes()), server_signature)
+    }
+
+    #[test]
+    fn authenticates_with_the_right_password() {
+        for mechanism in [ScramMechanism::Sha256, ScramMechanism::Sha512] {
+            let (step, server_signature) = exchange(mechanism, "alice-pw", "n,,", "n,,");
+            let Ok(SaslStep::Complete {
+                principal,
+                response,
+            }) = step
+            else {
+                panic!("expected the exchange to complete");
+            };
+            assert_eq!(principal, KafkaPrincipal::user("alice"));
+            assert_eq!(response, server_signature.into_bytes());
+        }
+    }
+
+    #[test]
+    fn authenticates_with_a_matching_authorization_id() {
+        let (step, _) = exchange(
+            ScramMechanism::Sha256,
+            "alice-pw",
+            "n,a=alice,",
+            "n,a=alice,",
+        );
+        assert!(matches!(step, Ok(SaslStep::Complete { .. })));
+    }
+
+    #[test]
+    fn rejects_a_wrong_password() {
+        let (step, _) = exchange(ScramMechanism::Sha256, "bob-pw", "n,,", "n,,");
+        let Err(failure) = step else {
+            panic!("expected the exchange to fail");
+        };
+        assert_eq!(failure.message, SaslFailure::credentials().message);
+    }
+
+    #[test]
+    fn rejects_channel_binding_that_does_not_echo_the_gs2_header() {
+        for binding in ["y,,", "n,a=alice,", ""] {
+            let (step, _) = exchange(ScramMechanism::Sha256, "alice-pw", "n,,", binding);
+            let Err(failure) = step else {
+                panic!("expected channel binding {binding:?} to be rejected");
+            };
+            assert!(failure.message.contains("channel binding"));
+        }
+    }
+
+    #[test]
+    fn rejects_a_nonce_the_server_did_not_issue() {
+        let mut scram = authenticator(ScramMechanism::Sha256);
+        assert!(matches!(
+            scram.evaluate(b"n,,n=alice,r=clientnonce"),
+            Ok(SaslStep::Challenge(_))
+        ));
+        let message = format!("c={},r=clientnonceforged,p=AAAA", STANDARD.encode("n,,"));
+        let Err(failure) = scram.evaluate(message.as_bytes()) else {
+            panic!("expected the nonce to be rejected");
+        };
+        assert!(failure.message.contains("nonce"));
+    }
+
+    #[test]
+    fn rejects_unknown_users_and_channel_binding_requests() {
+        let mut scram = authenticator(ScramMechanism::Sha256);
+        assert!(scram.evaluate(b"n,,n=mallory,r=clientnonce").is_err());
+        let mut scram = authenticator(ScramMechanism::Sha256);
+        assert!(scram
+            .evaluate(b"p=tls-unique,,n=alice,r=clientnonce")
+            .is_err());
+    }
+}
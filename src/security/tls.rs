@@ -0,0 +1,392 @@
+use std::{
+    fs::{self, File},
+    io::BufReader,
+    net::TcpStream,
+    path::Path,
+    sync::{Arc, RwLock},
+    time::SystemTime,
+};
+
+use anyhow::{anyhow, bail, Context, Result};
+use rustls::{
+    crypto::ring,
+    pki_types::{CertificateDer, PrivateKeyDer},
+    server::WebPkiClientVerifier,
+    RootCertStore, ServerConfig, ServerConnection, StreamOwned,
+};
+use x509_parser::{oid_registry::Oid, parse_x509_certificate};
+
+use super::KafkaPrincipal;
+use crate::config::{SslClientAuth, SslConfig};
+
+pub type TlsStream = StreamOwned<ServerConnection, TcpStream>;
+
+struct LoadedConfig {
+    server_config: Arc<ServerConfig>,
+    /// Modification times of the keystore and truststore when they were loaded
+    stamps: Vec<Option<SystemTime>>,
+}
+
+/// Terminates TLS for one listener, picking up new certificates when the PEM files change.
+pub struct TlsAcceptor {
+    ssl: SslConfig,
+    loaded: RwLock<LoadedConfig>,
+}
+
+impl TlsAcceptor {
+    pub fn new(ssl: SslConfig) -> Result<Self> {
+        let stamps = file_stamps(&ssl);
+        let server_config = build_server_config(&ssl)?;
+        Ok(TlsAcceptor {
+            ssl,
+            loaded: RwLock::new(LoadedConfig {
+                server_config,
+                stamps,
+            }),
+        })
+    }
+
+    /// Reload the certificates if the files were modified since they were last read.
+    /// A broken replacement is reported and the previous certificates stay in use.
+    pub fn reload_if_changed(&self) {
+        let stamps = file_stamps(&self.ssl);
+        if self.loaded.read().unwrap().stamps == stamps {
+            return;
+        }
+        let mut loaded = self.loaded.write().unwrap();
+        match build_server_config(&self.ssl) {
+            Ok(server_config) => {
+                eprintln!(
+                    "Reloaded TLS certificates from {}",
+                    self.ssl.keystore_location.display()
+                );
+                loaded.server_config = server_config;
+            }
+            Err(e) => eprintln!("Keeping previous TLS certificates: {e:#}"),
+        }
+        // Remember the stamps either way so a broken file is not retried on every accept
+        loaded.stamps = stamps;
+    }
+
+    /// Run the TLS handshake and return the stream along with the principal taken from
+    /// the client certificate, if one was presented.
+    pub fn accept(&self, mut stream: TcpStream) -> Result<(TlsStream, Option<KafkaPrincipal>)> {
+        self.reload_if_changed();
+        let server_config = Arc::clone(&self.loaded.read().unwrap().server_config);
+        let mut connection = ServerConnection::new(server_config)?;
+        while connection.is_handshaking() {
+            connection
+                .complete_io(&mut stream)
+                .context("TLS handshake failed")?;
+        }
+        let principal = match connection.peer_certificates() {
+            Some([leaf, ..]) => Some(KafkaPrincipal::user(subject_name(leaf)?)),
+            _ => None,
+        };
+        Ok((StreamOwned::new(connection, stream), principal))
+    }
+}
+
+fn file_stamps(ssl: &SslConfig) -> Vec<Option<SystemTime>> {
+    [
+        Some(&ssl.keystore_location),
+        ssl.truststore_location.as_ref(),
+    ]
+    .into_iter()
+    .flatten()
+    .map(|path| fs::metadata(path).and_then(|m| m.modified()).ok())
+    .collect()
+}
+
+fn read_certificates(path: &Path) -> Result<Vec<CertificateDer<'static>>> {
+    let file = File::open(path).with_context(|| format!("cannot open {}", path.display()))?;
+    let certs = rustls_pemfile::certs(&mut BufReader::new(file))
+        .collect::<Result<Vec<_>, _>>()
+        .with_context(|| format!("invalid PEM in {}", path.display()))?;
+    if certs.is_empty() {
+        bail!("no certificate found in {}", path.display());
+    }
+    Ok(certs)
+}
+
+fn read_private_key(path: &Path) -> Result<PrivateKeyDer<'static>> {
+    let file = File::open(path).with_context(|| format!("cannot open {}", path.display()))?;
+    rustls_pemfile::private_key(&mut BufReader::new(file))
+        .with_context(|| format!("invalid PEM in {}", path.display()))?
+        .ok_or_else(|| anyhow!("no private key found in {}", path.display()))
+}
+
+fn build_server_config(ssl: &SslConfig) -> Result<Arc<ServerConfig>> {
+    let provider = Arc::new(ring::default_provider());
+    let certs = read_certificates(&ssl.keystore_location)?;
+    let key = read_private_key(&ssl.keystore_location)?;
+
+    let builder = ServerConfig::builder_with_provider(Arc::clone(&provider))
+        .with_safe_default_protocol_versions()?;
+    let builder = match (ssl.client_auth, &ssl.truststore_location) {
+        (SslClientAuth::None, _) | (_, None) => builder.with_no_client_auth(),
+        (client_auth, Some(truststore)) => {
+            let mut roots = RootCertStore::empty();
+            for cert in read_certificates(truststore)? {
+                roots.add(cert)?;
+            }
+            let verifier = WebPkiClientVerifier::builder_with_provider(Arc::new(roots), provider);
+            let verifier = match client_auth {
+                SslClientAuth::Requested => verifier.allow_unauthenticated().build()?,
+                _ => verifier.build()?,
+            };
+            builder.with_client_cert_verifier(verifier)
+        }
+    };
+    let server_config = builder
+        .with_single_cert(certs, key)
+        .with_context(|| format!("invalid keystore {}", ssl.keystore_location.display()))?;
+    Ok(Arc::new(server_config))
+}
+
+/// Keyword of an attribute type in RFC 2253, dotted form for the types it has none for.
+fn attribute_keyword(oid: &Oid) -> String {
+    let dotted = oid.to_id_string();
+    let keyword = match dotted.as_str() {
+        "2.5.4.3" => "CN",
+        "2.5.4.6" => "C",
+        "2.5.4.7" => "L",
+        "2.5.4.8" => "ST",
+        "2.5.4.10" => "O",
+        "2.5.4.11" => "OU",
+        "0.9.2342.19200300.100.1.25" => "DC",
+        "0.9.2342.19200300.100.1.1" => "UID",
+        _ => return dotted,
+    };
+    keyword.to_string()
+}
+
+fn escape_rdn_value(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for (i, c) in value.chars().enumerate() {
+        let needs_escape = matches!(c, ',' | '+' | '"' | '\\' | '<' | '>' | ';')
+            || (i == 0 && matches!(c, ' ' | '#'))
+            || (i == value.chars().count() - 1 && c == ' ');
+        if needs_escape {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Distinguished name of the certificate subject in RFC 2253 form (e.g. `CN=client,O=Acme`),
+/// which is what Kafka uses as the principal name of SSL clients.
+pub fn subject_name(cert: &CertificateDer<'_>) -> Result<String> {
+    let (_, certificate) = parse_x509_certificate(cert.as_ref())
+        .map_err(|e| anyhow!("malformed client certificate: {e}"))?;
+    let mut components = vec![];
+    for rdn in certificate.subject().iter() {
+        let attributes = rdn
+            .iter()
+            .map(|attribute| {
+                let value = attribute
+                    .as_str()
+                    .map_err(|e| anyhow!("unsupported value in client certificate subject: {e}"))?;
+                Ok(format!(
+                    "{}={}",
+                    attribute_keyword(attribute.attr_type()),
+                    escape_rdn_value(value)
+                ))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        components.push(attributes.join("+"));
+    }
+    // RFC 2253 lists the most specific component first
+    components.reverse();
+    Ok(components.join(","))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{Read, Write},
+        net::TcpListener,
+        path::PathBuf,
+        thread,
+        time::Duration,
+    };
+
+    use rcgen::{
+        BasicConstraints, Certificate, CertificateParams, DistinguishedName, DnType, IsCa, KeyPair,
+    };
+    use rustls::{pki_types::ServerName, ClientConfig, ClientConnection};
+
+    use super::*;
+
+    struct Authority {
+        certificate: Certificate,
+        key: KeyPair,
+    }
+
+    impl Authority {
+        fn new() -> Self {
+            let key = KeyPair::generate().unwrap();
+            let mut params = CertificateParams::new(vec![]).unwrap();
+            params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+            params
+                .distinguished_name
+                .push(DnType::CommonName, "test-ca");
+            Authority {
+                certificate: params.self_signed(&key).unwrap(),
+                key,
+            }
+        }
+
+        /// A certificate for `localhost` with the subject `names`, most general first,
+        /// and its private key.
+        fn issue(&self, names: &[(DnType, &str)]) -> (Certificate, KeyPair) {
+            let key = KeyPair::generate().unwrap();
+            let mut params = CertificateParams::new(vec!["localhost".to_string()]).unwrap();
+            params.distinguished_name = DistinguishedName::new();
+            for (dn_type, value) in names {
+                params.distinguished_name.push(dn_type.clone(), *value);
+            }
+            let certificate = params
+                .signed_by(&key, &self.certificate, &self.key)
+                .unwrap();
+            (certificate, key)
+        }
+    }
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("tls-{name}-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    /// Replace the file, setting its modification time explicitly as a rewrite within
+    /// the resolution of the file system would otherwise go unnoticed.
+    fn replace(path: &Path, contents: &str, modified: u64) {
+        fs::write(path, contents).unwrap();
+        File::options()
+            .write(true)
+            .open(path)
+            .unwrap()
+            .set_modified(SystemTime::UNIX_EPOCH + Duration::from_secs(modified))
+            .unwrap();
+    }
+
+    fn write_keystore(path: &Path, certificate: &Certificate, key: &KeyPair, modified: u64) {
+        replace(path, &(certificate.pem() + &key.serialize_pem()), modified);
+    }
+
+    /// Handshake with the acceptor as a client trusting `ca`, presenting `client` if
+    /// given, and return the principal the broker took from it along with the
+    /// certificate the broker presented.
+    fn handshake(
+        acceptor: Arc<TlsAcceptor>,
+        ca: &Authority,
+        client: Option<&(Certificate, KeyPair)>,
+    ) -> (Result<Option<KafkaPrincipal>>, Vec<u8>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let server = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let (mut stream, principal) = acceptor.accept(stream)?;
+            let mut byte = [0];
+            stream.read_exact(&mut byte)?;
+            stream.write_all(&byte)?;
+            stream.flush()?;
+            Ok(principal)
+        });
+
+        let mut roots = RootCertStore::empty();
+        roots.add(ca.certificate.der().clone()).unwrap();
+        let builder = ClientConfig::builder_with_provider(Arc::new(ring::default_provider()))
+            .with_safe_default_protocol_versions()
+            .unwrap()
+            .with_root_certificates(roots);
+        let config = match client {
+            Some((certificate, key)) => builder
+                .with_client_auth_cert(
+                    vec![certificate.der().clone()],
+                    PrivateKeyDer::try_from(key.serialize_der()).unwrap(),
+                )
+                .unwrap(),
+            None => builder.with_no_client_auth(),
+        };
+        let connection =
+            ClientConnection::new(Arc::new(config), ServerName::try_from("localhost").unwrap())
+                .unwrap();
+        let mut stream = StreamOwned::new(connection, TcpStream::connect(address).unwrap());
+        let mut byte = [0];
+        let echoed = stream
+            .write_all(b"x")
+            .and_then(|_| stream.read_exact(&mut byte));
+        let presented = stream
+            .conn
+            .peer_certificates()
+            .and_then(|certificates| certificates.first())
+            .map(|certificate| certificate.to_vec())
+            .unwrap_or_default();
+        drop(stream);
+        let principal = server.join().unwrap();
+        if principal.is_ok() {
+            echoed.unwrap();
+            assert_eq!(&byte, b"x");
+        }
+        (principal, presented)
+    }
+
+    #[test]
+    fn maps_client_certificates_to_principals_and_reloads_the_keystore() {
+        let dir = temp_dir("acceptor");
+        let ca = Authority::new();
+        let keystore = dir.join("keystore.pem");
+        let truststore = dir.join("truststore.pem");
+        let (server, server_key) = ca.issue(&[(DnType::CommonName, "broker")]);
+        write_keystore(&keystore, &server, &server_key, 1_000);
+        fs::write(&truststore, ca.certificate.pem()).unwrap();
+        let acceptor = Arc::new(
+            TlsAcceptor::new(SslConfig {
+                keystore_location: keystore.clone(),
+                truststore_location: Some(truststore),
+                client_auth: SslClientAuth::Required,
+            })
+            .unwrap(),
+        );
+
+        let client = ca.issue(&[
+            (DnType::OrganizationName, "Acme, Inc"),
+            (DnType::CommonName, "client"),
+        ]);
+        let (principal, presented) = handshake(Arc::clone(&acceptor), &ca, Some(&client));
+        assert_eq!(
+            principal.unwrap(),
+            Some(KafkaPrincipal::user(r"CN=client,O=Acme\, Inc"))
+        );
+        assert_eq!(presented, server.der().to_vec());
+
+        // Client authentication is required
+        let (principal, _) = handshake(Arc::clone(&acceptor), &ca, None);
+        assert!(principal.is_err());
+
+        // A new keystore is picked up by the next handshake
+        let (renewed, renewed_key) = ca.issue(&[(DnType::CommonName, "renewed")]);
+        write_keystore(&keystore, &renewed, &renewed_key, 2_000);
+        let (principal, presented) = handshake(Arc::clone(&acceptor), &ca, Some(&client));
+        assert!(principal.unwrap().is_some());
+        assert_eq!(presented, renewed.der().to_vec());
+
+        // A broken one is not, and the renewed certificate stays in use
+        replace(&keystore, "not a keystore", 3_000);
+        let (principal, presented) = handshake(acceptor, &ca, Some(&client));
+        assert!(principal.unwrap().is_some());
+        assert_eq!(presented, renewed.der().to_vec());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn rejects_malformed_client_certificates() {
+        let truncated = CertificateDer::from(vec![0x30, 0x82, 0x01, 0x00, 0x30]);
+        assert!(subject_name(&truncated).is_err());
+        assert!(subject_name(&CertificateDer::from(vec![])).is_err());
+    }
+}
@@ -0,0 +1,331 @@
+use std::{
+    io::{ErrorKind, Read, Write},
+    net::{SocketAddr, TcpListener, TcpStream},
+    process,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread,
+    time::Duration,
+};
+
+use anyhow::{bail, Context, Result};
+
+use crate::{
+    broker::Broker,
+    broker_lifecycle,
+    config::{BrokerConfig, Endpoint, SecurityProtocol},
+    controller,
+    protocol::{
+        api_keys::{self, RequestContext, API_VERSIONS_KEY},
+        header::{RequestHeader, ResponseHeader},
+    },
+    quota::QuotaType,
+    raft, remote_storage, replica_fetcher,
+    replica_manager::ReplicaManager,
+    security::{sasl::SaslSession, tls::TlsAcceptor, KafkaPrincipal},
+};
+
+/// Per-connection information about where the client came in.
+#[derive(Debug, Clone)]
+pub struct ConnectionContext {
+    pub listener_name: String,
+    pub security_protocol: SecurityProtocol,
+    pub peer_addr: SocketAddr,
+    pub principal: KafkaPrincipal,
+    /// Client software as named in ApiVersions version 3 and above
+    pub client_software_name: Option<String>,
+    pub client_software_version: Option<String>,
+}
+
+struct BoundListener {
+    endpoint: Endpoint,
+    security_protocol: SecurityProtocol,
+    tls: Option<Arc<TlsAcceptor>>,
+    listener: TcpListener,
+}
+
+pub struct Server {
+    broker: Arc<Broker>,
+    listeners: Vec<BoundListener>,
+}
+
+impl Server {
+    /// Bind every configured listener up front so that a port conflict is a startup error.
+    pub fn bind(config: BrokerConfig) -> Result<Self> {
+        let mut listeners = vec![];
+        for endpoint in &config.listeners {
+            let Some(protocol) = config.security_protocol(&endpoint.name) else {
+                bail!(
+                    "no security protocol defined for listener {}",
+                    endpoint.name
+                );
+            };
+            let tls = match protocol {
+                SecurityProtocol::Plaintext | SecurityProtocol::SaslPlaintext => None,
+                SecurityProtocol::Ssl | SecurityProtocol::SaslSsl => {
+                    let Some(ssl) = config.ssl.get(&endpoint.name) else {
+                        bail!("no SSL settings for listener {}", endpoint.name);
+                    };
+                    let acceptor = TlsAcceptor::new(ssl.clone()).with_context(|| {
+                        format!("failed to load TLS settings of listener {}", endpoint.name)
+                    })?;
+                    Some(Arc::new(acceptor))
+                }
+            };
+            let listener = TcpListener::bind(endpoint.bind_address())
+                .with_context(|| format!("failed to bind listener {endpoint}"))?;
+            listeners.push(BoundListener {
+                endpoint: endpoint.clone(),
+                security_protocol: protocol,
+                tls,
+                listener,
+            });
+        }
+        let broker = Broker::new(config).context("failed to load cluster metadata")?;
+        Ok(Server {
+            broker: Arc::new(broker),
+            listeners,
+        })
+    }
+
+    /// Accept connections on every listener until the process exits.
+    pub fn run(self) {
+        ReplicaManager::start(&self.broker);
+        replica_fetcher::start(&self.broker);
+        remote_storage::start(&self.broker);
+        raft::start(&self.broker);
+        controller::start(&self.broker);
+        broker_lifecycle::start(&self.broker);
+        shut_down_on_signal(&self.broker);
+        let mut acceptors = vec![];
+        for listener in self.listeners {
+            let broker = Arc::clone(&self.broker);
+            acceptors.push(thread::spawn(move || accept_loop(broker, listener)));
+        }
+        for acceptor in acceptors {
+            let _ = acceptor.join();
+        }
+    }
+}
+
+static SHUTDOWN_REQUESTED: AtomicBool = AtomicBool::new(false);
+
+extern "C" fn request_shutdown(_signal: libc::c_int) {
+    SHUTDOWN_REQUESTED.store(true, Ordering::SeqCst);
+}
+
+/// Exit on SIGTERM or SIGINT once the leaderships of the broker moved to other replicas.
+fn shut_down_on_signal(broker: &Arc<Broker>) {
+    let handler = request_shutdown as extern "C" fn(libc::c_int) as libc::sighandler_t;
+    // SAFETY: the handler only stores to an atomic, which is async-signal-safe
+    unsafe {
+        libc::signal(libc::SIGTERM, handler);
+        libc::signal(libc::SIGINT, handler);
+    }
+    let broker = Arc::clone(broker);
+    thread::spawn(move || {
+        while !SHUTDOWN_REQUESTED.load(Ordering::SeqCst) {
+            thread::sleep(Duration::from_millis(100));
+        }
+        eprintln!("Shutting down node {}", broker.config.node_id);
+        broker.lifecycle.controlled_shutdown(&broker);
+        process::exit(0);
+    });
+}
+
+fn accept_loop(broker: Arc<Broker>, bound: BoundListener) {
+    let BoundListener {
+        endpoint,
+        security_protocol,
+        tls,
+        listener,
+    } = bound;
+    for stream in listener.incoming() {
+        match stream {
+            Ok(stream) => {
+                let peer_addr = match stream.peer_addr() {
+                    Ok(addr) => addr,
+                    Err(e) => {
+                        eprintln!("Dropping connection on {}: {e}", endpoint.name);
+                        continue;
+                    }
+                };
+                let context = ConnectionContext {
+                    listener_name: endpoint.name.clone(),
+                    security_protocol,
+                    peer_addr,
+                    principal: KafkaPrincipal::anonymous(),
+                    client_software_name: None,
+                    client_software_version: None,
+                };
+                let broker = Arc::clone(&broker);
+                let tls = tls.clone();
+                // Spawn a new thread for each individual connection
+                thread::spawn(move || handle_connection(broker, context, tls, stream));
+            }
+            Err(e) => {
+                eprintln!("error accepting on {}: {e}", endpoint.name);
+            }
+        }
+    }
+}
+
+fn handle_connection(
+    broker: Arc<Broker>,
+    mut context: ConnectionContext,
+    tls: Option<Arc<TlsAcceptor>>,
+    mut stream: TcpStream,
+) {
+    let result = match tls {
+        None => process_connection(&broker, &mut context, &mut stream),
+        Some(acceptor) => acceptor.accept(stream).and_then(|(mut stream, principal)| {
+            // The client certificate subject becomes the principal of SSL connections,
+            // SASL_SSL connections take the principal of the SASL exchange instead
+            if let Some(principal) = principal {
+                context.principal = principal;
+            }
+            process_connection(&broker, &mut context, &mut stream)
+        }),
+    };
+    if let Err(e) = result {
+        let software = match (
+            &context.client_software_name,
+            &context.client_software_version,
+        ) {
+            (Some(name), Some(version)) => format!(" ({name} {version})"),
+            _ => String::new(),
+        };
+        eprintln!(
+            "Connection from {}{software} closed or error: {e:#}",
+            context.peer_addr
+        );
+    }
+}
+
+fn process_connection<S: Read + Write>(
+    broker: &Arc<Broker>,
+    context: &mut ConnectionContext,
+    stream: &mut S,
+) -> Result<()> {
+    let mut size_buf = [0; 4];
+    let mut sasl = broker
+        .config
+        .sasl
+        .get(&context.listener_name)
+        .map(|config| SaslSession::new(config.clone(), Arc::clone(broker)));
+
+    loop {
+        // The client closing the connection between two requests is no error
+        match stream.read_exact(&mut size_buf) {
+            Ok(()) => {}
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof => return Ok(()),
+            Err(e) => return Err(e.into()),
+        }
+
+        // Frames of a negative or excessive size are refused before allocating them
+        let message_size = i32::from_be_bytes(size_buf);
+        if message_size < 0 || message_size as usize > broker.config.socket_request_max_bytes {
+            bail!(
+                "request frame of {message_size} bytes is outside of [0, {}] \
+                 (socket.request.max.bytes)",
+                broker.config.socket_request_max_bytes
+            );
+        }
+        let mut msg_buf = vec![0; message_size as usize];
+        stream.read_exact(&mut msg_buf)?;
+
+        // A header that cannot be read leaves no way to answer, so the connection is
+        // closed as in Apache Kafka
+        let (request_header, request_body) = RequestHeader::deserialize(&msg_buf)?;
+        let correlation_id: i32 = request_header.correlation_id;
+        let api_version = request_header.request_api_version;
+        let api_key = request_header.request_api_key;
+        let client_id = request_header.client_id.clone().unwrap_or_default();
+        let started = thread_cpu_time();
+
+        let Some(api) = api_keys::api_key(api_key) else {
+            bail!(
+                "unknown API key {api_key} in request {correlation_id} from client {client_id:?}"
+            );
+        };
+        // Anything but the SASL exchange is refused until the client has authenticated
+        if let Some(session) = sasl.as_ref() {
+            if !session.permits(api) {
+                bail!(
+                    "{} (key {api_key}) is not allowed without an authenticated SASL session",
+                    api.name
+                );
+            }
+        }
+        // Versions the broker does not implement close the connection as in Apache
+        // Kafka, but for ApiVersions which answers with the versions to fall back to
+        if !api.supports(api_version) && api_key != API_VERSIONS_KEY {
+            bail!(
+                "unsupported version {api_version} of {} (key {api_key}, versions {} to {}) \
+                 in request {correlation_id} from client {client_id:?}",
+                api.name,
+                api.min_version,
+                api.max_version
+            );
+        }
+        let mut request = RequestContext {
+            api_version,
+            client_id: &client_id,
+            throttle_time_ms: 0,
+            broker,
+            connection: context,
+            sasl: &mut sasl,
+        };
+        let response = (api.handler)(request_body, &mut request)?;
+        let byte_throttle_time_ms = request.throttle_time_ms;
+
+        // Like Apache Kafka, the APIs needed to connect are never subject to the request quota,
+        // which is charged the thread time of the handler and not its waits for acks or
+        // for records to fetch
+        let request_throttle_time_ms = if api.quota_exempt {
+            0
+        } else {
+            broker.record_quota(
+                context,
+                &client_id,
+                QuotaType::Request,
+                thread_cpu_time().saturating_sub(started).as_secs_f64() * 100.0,
+            )
+        };
+        let throttle_time_ms = byte_throttle_time_ms.max(request_throttle_time_ms);
+        if let Some(mut body) = response {
+            body.set_throttle_time_ms(throttle_time_ms);
+
+            let header_version = api.response_header_version(api_version);
+            let mut payload = ResponseHeader::new(correlation_id, header_version).serialize();
+            payload.extend(body.serialize());
+            let message_size: i32 = payload.len() as i32;
+            stream.write_all(&message_size.to_be_bytes())?;
+            stream.write_all(&payload)?;
+        }
+        // Mute the connection until the client is back within its quota, which is all
+        // producers sending acks=0 learn of it
+        if throttle_time_ms > 0 {
+            thread::sleep(Duration::from_millis(throttle_time_ms as u64));
+        }
+
+        // A failed SASL exchange closes the connection once the error has been sent
+        if sasl.as_ref().is_some_and(SaslSession::has_failed) {
+            bail!("SASL authentication failed for {}", context.peer_addr);
+        }
+    }
+}
+
+/// CPU time the calling thread has used. Each connection has a thread of its own, so
+/// this leaves out the time its requests spend blocked.
+fn thread_cpu_time() -> Duration {
+    let mut time = libc::timespec {
+        tv_sec: 0,
+        tv_nsec: 0,
+    };
+    // SAFETY: `time` is a valid timespec for the call to write to
+    unsafe { libc::clock_gettime(libc::CLOCK_THREAD_CPUTIME_ID, &mut time) };
+    Duration::new(time.tv_sec as u64, time.tv_nsec as u32)
+}
@@ -0,0 +1,428 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use uuid::Uuid;
+
+use crate::{config::ShareGroupConfig, metadata::MetadataImage, protocol::error};
+
+/// Broker coordinating the group: one of the unfenced brokers picked by a hash of the
+/// group id, as Apache Kafka picks a partition of `__consumer_offsets`.
+pub fn coordinator_for(image: &MetadataImage, group_id: &str) -> Option<i32> {
+    let brokers: Vec<i32> = image
+        .brokers
+        .values()
+        .filter(|registration| !registration.fenced && !registration.in_controlled_shutdown)
+        .map(|registration| registration.id)
+        .collect();
+    if brokers.is_empty() {
+        return None;
+    }
+    // `String.hashCode` of Java, made non-negative like `Utils.abs`
+    let hash = group_id
+        .encode_utf16()
+        .fold(0i32, |hash, c| hash.wrapping_mul(31).wrapping_add(c as i32));
+    let hash = if hash == i32::MIN { 0 } else { hash.abs() };
+    Some(brokers[hash as usize % brokers.len()])
+}
+
+/// Partitions of each topic, by topic id.
+pub type Assignment = BTreeMap<Uuid, Vec<i32>>;
+
+#[derive(Debug)]
+struct ShareGroupMember {
+    member_epoch: i32,
+    subscribed_topic_names: Vec<String>,
+    /// Partitions the member consumes as of the group epoch
+    target_assignment: Assignment,
+    /// Removed from the group once this passes without a heartbeat
+    session_deadline: Instant,
+}
+
+#[derive(Debug, Default)]
+struct ShareGroup {
+    /// Bumped whenever the members, their subscriptions or the subscribed topics change
+    group_epoch: i32,
+    /// Group epoch the target assignment was computed for
+    assignment_epoch: i32,
+    members: BTreeMap<String, ShareGroupMember>,
+    /// Partition count of each subscribed topic, as of the group epoch
+    subscribed_topics: BTreeMap<Uuid, usize>,
+}
+
+impl ShareGroup {
+    /// Partition counts of the topics members subscribe to that exist.
+    fn subscribed_topics(&self, image: &MetadataImage) -> BTreeMap<Uuid, usize> {
+        self.members
+            .values()
+            .flat_map(|member| &member.subscribed_topic_names)
+            .filter_map(|name| {
+                let topic_id = *image.topics.get(name)?;
+                let partitions = image.partitions.get(&topic_id).map_or(0, BTreeMap::len);
+                Some((topic_id, partitions))
+            })
+            .collect()
+    }
+
+    /// Spread the partitions of each topic over the members subscribing to it. Unlike
+    /// consumer groups, a partition may go to several members, which happens when a
+    /// topic has fewer partitions than subscribers.
+    fn assign(&mut self, image: &MetadataImage) {
+        for member in self.members.values_mut() {
+            member.target_assignment.clear();
+        }
+        for (&topic_id, &partitions) in &self.subscribed_topics {
+            let Some(name) = image.topic_name(topic_id) else {
+                continue;
+            };
+            let subscribers: Vec<&String> = self
+                .members
+                .iter()
+                .filter(|(_, member)| member.subscribed_topic_names.iter().any(|n| n == name))
+                .map(|(member_id, _)| member_id)
+                .collect();
+            if subscribers.is_empty() || partitions == 0 {
+                continue;
+            }
+            let mut assignment: BTreeMap<String, Vec<i32>> = BTreeMap::new();
+            for index in 0..partitions.max(subscribers.len()) {
+                let member_id = subscribers[index % subscribers.len()];
+                let partition = (index % partitions) as i32;
+                let assigned = assignment.entry(member_id.clone()).or_default();
+                if !assigned.contains(&partition) {
+                    assigned.push(partition);
+                }
+            }
+            for (member_id, mut partitions) in assignment {
+                partitions.sort();
+                if let Some(member) = self.members.get_mut(&member_id) {
+                    member.target_assignment.insert(topic_id, partitions);
+                }
+            }
+        }
+        self.assignment_epoch = self.group_epoch;
+    }
+}
+
+/// What a heartbeat tells the member.
+#[derive(Debug)]
+pub struct ShareGroupHeartbeatResult {
+    pub member_epoch: i32,
+    /// Set when the assignment of the member changed since its previous heartbeat
+    pub assignment: Option<Assignment>,
+}
+
+/// Membership of the share groups this broker coordinates. Groups live in memory only:
+/// members of a group whose coordinator moved join again, and the delivery state of
+/// the records they consume is kept by the partition leaders.
+pub struct ShareGroupCoordinator {
+    config: ShareGroupConfig,
+    groups: Mutex<HashMap<String, ShareGroup>>,
+}
+
+impl ShareGroupCoordinator {
+    pub fn new(config: &ShareGroupConfig) -> Self {
+        ShareGroupCoordinator {
+            config: config.clone(),
+            groups: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Handle a heartbeat of a member. Epoch 0 joins the group, -1 leaves it, and any
+    /// other epoch must be the member's current one. Topic names are only required to
+    /// join, and otherwise replace the subscription when set.
+    pub fn heartbeat(
+        &self,
+        image: &MetadataImage,
+        group_id: &str,
+        member_id: &str,
+        member_epoch: i32,
+        subscribed_topic_names: Option<Vec<String>>,
+    ) -> Result<ShareGroupHeartbeatResult, (i16, String)> {
+        let mut groups = self.groups.lock().unwrap();
+        let group = groups.entry(group_id.to_string()).or_default();
+        let now = Instant::now();
+        let expired: Vec<String> = group
+            .members
+            .iter()
+            .filter(|(_, member)| member.session_deadline <= now)
+            .map(|(member_id, _)| member_id.clone())
+            .collect();
+        for member_id in expired {
+            eprintln!("Removing member {member_id} of share group {group_id}: session timed out");
+            group.members.remove(&member_id);
+            group.group_epoch += 1;
+        }
+
+        let session_deadline = now + Duration::from_millis(self.config.session_timeout_ms);
+        match member_epoch {
+            -1 => {
+                if group.members.remove(member_id).is_some() {
+                    group.group_epoch += 1;
+                }
+                if group.members.is_empty() {
+                    groups.remove(group_id);
+                }
+                return Ok(ShareGroupHeartbeatResult {
+                    member_epoch: -1,
+                    assignment: None,
+                });
+            }
+            0 => {
+                let topics = subscribed_topic_names.unwrap_or_default();
+                if !group.members.contains_key(member_id)
+                    && group.members.len() >= self.config.max_size
+                {
+                    return Err((
+                        error::GROUP_MAX_SIZE_REACHED,
+                        format!(
+                            "The share group has reached its maximum size of {}.",
+                            self.config.max_size
+                        ),
+                    ));
+                }
+                group.members.insert(
+                    member_id.to_string(),
+                    ShareGroupMember {
+                        member_epoch: 0,
+                        subscribed_topic_names: topics,
+                        target_assignment: Assignment::new(),
+                        session_deadline,
+                    },
+                );
+                group.group_epoch += 1;
+            }
+            _ => {
+                let Some(member) = group.members.get_mut(member_id) else {
+                    return Err((
+                        error::UNKNOWN_MEMBER_ID,
+                        format!("Member {member_id} is not a member of group {group_id}."),
+                    ));
+                };
+                if member_epoch != member.member_epoch {
+                    return Err((
+                        error::FENCED_MEMBER_EPOCH,
+                        format!(
+                            "The share group member has a epoch {member_epoch} which does not \
+                             match the expected epoch {}.",
+                            member.member_epoch
+                        ),
+                    ));
+                }
+                member.session_deadline = session_deadline;
+                if let Some(topics) = subscribed_topic_names {
+                    if topics != member.subscribed_topic_names {
+                        member.subscribed_topic_names = topics;
+                        group.group_epoch += 1;
+                    }
+                }
+            }
+        }
+
+        // Topics created, deleted or grown since change the assignment too
+        let subscribed_topics = group.subscribed_topics(image);
+        if subscribed_topics != group.subscribed_topics {
+            group.subscribed_topics = subscribed_topics;
+            if group.group_epoch == group.assignment_epoch {
+                group.group_epoch += 1;
+            }
+        }
+        if group.assignment_epoch != group.group_epoch {
+            group.assign(image);
+        }
+
+        // The member moves to the group epoch with the heartbeat that hands it its
+        // assignment
+        let assignment_epoch = group.assignment_epoch;
+        let member = group
+            .members
+            .get_mut(member_id)
+            .expect("member of the group");
+        if member.member_epoch == assignment_epoch {
+            return Ok(ShareGroupHeartbeatResult {
+                member_epoch,
+                assignment: None,
+            });
+        }
+        member.member_epoch = assignment_epoch;
+        Ok(ShareGroupHeartbeatResult {
+            member_epoch: assignment_epoch,
+            assignment: Some(member.target_assignment.clone()),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metadata::{BrokerRegistration, PartitionRegistration};
+
+    fn config(max_size: usize) -> ShareGroupConfig {
+        ShareGroupConfig {
+            heartbeat_interval_ms: 5_000,
+            session_timeout_ms: 45_000,
+            max_size,
+            record_lock_duration_ms: 30_000,
+            delivery_count_limit: 5,
+            partition_max_record_locks: 200,
+            updates_per_snapshot: 0,
+        }
+    }
+
+    /// An image with brokers 1 to 3 and topic `orders` of `partitions` partitions.
+    fn image(partitions: i32) -> MetadataImage {
+        let mut image = MetadataImage::default();
+        for id in 1..=3 {
+            image.brokers.insert(
+                id,
+                BrokerRegistration {
+                    id,
+                    epoch: 0,
+                    incarnation_id: Uuid::nil(),
+                    endpoints: vec![],
+                    rack: None,
+                    fenced: id == 2,
+                    in_controlled_shutdown: false,
+                    log_dirs: vec![],
+                },
+            );
+        }
+        let topic_id = Uuid::from_u128(1);
+        image.topics.insert("orders".to_string(), topic_id);
+        let registrations = (0..partitions)
+            .map(|partition| {
+                let registration = PartitionRegistration {
+                    replicas: vec![1],
+                    isr: vec![1],
+                    leader: 1,
+                    leader_epoch: 0,
+                    partition_epoch: 0,
+                    directories: vec![],
+                    adding_replicas: vec![],
+                    removing_replicas: vec![],
+                };
+                (partition, registration)
+            })
+            .collect();
+        image.partitions.insert(topic_id, registrations);
+        image
+    }
+
+    fn orders() -> Option<Vec<String>> {
+        Some(vec!["orders".to_string()])
+    }
+
+    #[test]
+    fn picks_an_unfenced_coordinator_by_the_hash_of_the_group_id() {
+        let image = image(1);
+        // "group".hashCode() is 98629247, odd, so the second of brokers 1 and 3
+        assert_eq!(coordinator_for(&image, "group"), Some(3));
+        assert_eq!(coordinator_for(&MetadataImage::default(), "group"), None);
+    }
+
+    #[test]
+    fn spreads_partitions_over_the_members() {
+        let image = image(3);
+        let coordinator = ShareGroupCoordinator::new(&config(10));
+        let joined = coordinator
+            .heartbeat(&image, "group", "a", 0, orders())
+            .unwrap();
+        assert_eq!(joined.member_epoch, 1);
+        assert_eq!(
+            joined.assignment,
+            Some(Assignment::from([(Uuid::from_u128(1), vec![0, 1, 2])]))
+        );
+
+        let joined = coordinator
+            .heartbeat(&image, "group", "b", 0, orders())
+            .unwrap();
+        assert_eq!(joined.member_epoch, 2);
+        assert_eq!(
+            joined.assignment,
+            Some(Assignment::from([(Uuid::from_u128(1), vec![1])]))
+        );
+
+        let result = coordinator
+            .heartbeat(&image, "group", "a", 1, None)
+            .unwrap();
+        assert_eq!(result.member_epoch, 2);
+        assert_eq!(
+            result.assignment,
+            Some(Assignment::from([(Uuid::from_u128(1), vec![0, 2])]))
+        );
+        // Nothing changed since
+        let result = coordinator
+            .heartbeat(&image, "group", "a", 2, None)
+            .unwrap();
+        assert_eq!(result.member_epoch, 2);
+        assert_eq!(result.assignment, None);
+    }
+
+    #[test]
+    fn shares_partitions_between_more_members_than_partitions() {
+        let image = image(1);
+        let coordinator = ShareGroupCoordinator::new(&config(10));
+        coordinator
+            .heartbeat(&image, "group", "a", 0, orders())
+            .unwrap();
+        let joined = coordinator
+            .heartbeat(&image, "group", "b", 0, orders())
+            .unwrap();
+        assert_eq!(
+            joined.assignment,
+            Some(Assignment::from([(Uuid::from_u128(1), vec![0])]))
+        );
+    }
+
+    #[test]
+    fn reassigns_when_the_subscribed_topics_grow() {
+        let coordinator = ShareGroupCoordinator::new(&config(10));
+        let joined = coordinator
+            .heartbeat(&image(1), "group", "a", 0, orders())
+            .unwrap();
+        let result = coordinator
+            .heartbeat(&image(2), "group", "a", joined.member_epoch, None)
+            .unwrap();
+        assert_eq!(result.member_epoch, 2);
+        assert_eq!(
+            result.assignment,
+            Some(Assignment::from([(Uuid::from_u128(1), vec![0, 1])]))
+        );
+    }
+
+    #[test]
+    fn rejects_unknown_members_stale_epochs_and_full_groups() {
+        let image = image(1);
+        let coordinator = ShareGroupCoordinator::new(&config(1));
+        coordinator
+            .heartbeat(&image, "group", "a", 0, orders())
+            .unwrap();
+
+        let error_code = |result: Result<ShareGroupHeartbeatResult, (i16, String)>| {
+            result.map(|_| ()).unwrap_err().0
+        };
+        assert_eq!(
+            error_code(coordinator.heartbeat(&image, "group", "b", 0, orders())),
+            error::GROUP_MAX_SIZE_REACHED
+        );
+        assert_eq!(
+            error_code(coordinator.heartbeat(&image, "group", "b", 1, None)),
+            error::UNKNOWN_MEMBER_ID
+        );
+        assert_eq!(
+            error_code(coordinator.heartbeat(&image, "group", "a", 7, None)),
+            error::FENCED_MEMBER_EPOCH
+        );
+
+        // A member leaving makes room for another
+        let left = coordinator
+            .heartbeat(&image, "group", "a", -1, None)
+            .unwrap();
+        assert_eq!(left.member_epoch, -1);
+        coordinator
+            .heartbeat(&image, "group", "b", 0, orders())
+            .unwrap();
+    }
+}
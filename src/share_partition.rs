@@ -0,0 +1,1097 @@
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    path::Path,
+    sync::Mutex,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{bail, Result};
+use uuid::Uuid;
+
+use crate::{
+    broker::Broker,
+    config::{BrokerConfig, ShareGroupConfig},
+    log_manager::{self, TopicPartition},
+    metadata::MetadataLog,
+    protocol::{
+        cluster_metadata::RecordBatch,
+        error,
+        fetch::{FetchPartition, SnapshotId},
+        primitive::{CompactArray, CompactString, Serializable, TagSection},
+    },
+};
+
+/// Directory of the first log directory holding the share-group state log.
+pub const SHARE_GROUP_STATE_DIR: &str = "__share_group_state";
+/// Segment size of the share-group state log, whose snapshots let older segments go.
+const STATE_LOG_SEGMENT_BYTES: u64 = 1 << 20;
+
+/// Delivery state of a record of a share partition.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecordState {
+    Available,
+    /// Locked by the member it was delivered to until it is acknowledged or released
+    Acquired,
+    Acknowledged,
+    /// Rejected, or delivered as many times as allowed
+    Archived,
+}
+
+impl RecordState {
+    pub fn code(&self) -> i8 {
+        match self {
+            RecordState::Available => 0,
+            RecordState::Acquired => 1,
+            RecordState::Acknowledged => 2,
+            RecordState::Archived => 4,
+        }
+    }
+
+    pub fn from_code(code: i8) -> Option<Self> {
+        match code {
+            0 => Some(RecordState::Available),
+            1 => Some(RecordState::Acquired),
+            2 => Some(RecordState::Acknowledged),
+            4 => Some(RecordState::Archived),
+            _ => None,
+        }
+    }
+}
+
+/// How a member acknowledges a record it acquired.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AcknowledgeType {
+    /// The offset holds no record, as after compaction
+    Gap,
+    Accept,
+    /// Deliver the record again, to any member
+    Release,
+    Reject,
+}
+
+impl AcknowledgeType {
+    pub fn from_code(code: i8) -> Option<Self> {
+        match code {
+            0 => Some(AcknowledgeType::Gap),
+            1 => Some(AcknowledgeType::Accept),
+            2 => Some(AcknowledgeType::Release),
+            3 => Some(AcknowledgeType::Reject),
+            _ => None,
+        }
+    }
+}
+
+/// Records from `first_offset` to `last_offset` acknowledged the same way, or one type
+/// per offset.
+#[derive(Debug, Clone)]
+pub struct Acknowledgement {
+    pub first_offset: i64,
+    pub last_offset: i64,
+    pub acknowledge_types: Vec<i8>,
+}
+
+/// Consecutive records a fetch acquired, delivered the same number of times.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AcquiredRecords {
+    pub first_offset: i64,
+    pub last_offset: i64,
+    pub delivery_count: i16,
+}
+
+/// Records a member acquired from a share partition, as the batches holding them.
+#[derive(Debug, Default)]
+pub struct ShareAcquisition {
+    pub records: Vec<u8>,
+    pub acquired: Vec<AcquiredRecords>,
+}
+
+/// A partition as consumed by one share group.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct SharePartitionKey {
+    pub group_id: String,
+    pub topic_id: Uuid,
+    pub partition: i32,
+}
+
+#[derive(Debug)]
+struct InFlightRecord {
+    state: RecordState,
+    delivery_count: i16,
+    /// Member holding the acquisition lock, empty unless acquired
+    member_id: String,
+    lock_deadline: Instant,
+}
+
+impl InFlightRecord {
+    fn new(state: RecordState, delivery_count: i16) -> Self {
+        InFlightRecord {
+            state,
+            delivery_count,
+            member_id: String::new(),
+            lock_deadline: Instant::now(),
+        }
+    }
+
+    /// Make the record available again, unless it was delivered as many times as
+    /// allowed.
+    fn release(&mut self, delivery_count_limit: i16) {
+        self.state = if self.delivery_count >= delivery_count_limit {
+            RecordState::Archived
+        } else {
+            RecordState::Available
+        };
+        self.member_id.clear();
+    }
+}
+
+/// Delivery state of the records of a share partition, from its start offset (SPSO),
+/// below which every record is acknowledged or archived, up to its end offset (SPEO),
+/// past which no record has been delivered yet.
+#[derive(Debug)]
+struct SharePartition {
+    /// Leader epoch of the partition the state was loaded in
+    leader_epoch: i32,
+    /// Bumped each time a leader loads the state
+    state_epoch: i32,
+    start_offset: i64,
+    end_offset: i64,
+    records: BTreeMap<i64, InFlightRecord>,
+}
+
+impl SharePartition {
+    fn new(start_offset: i64, leader_epoch: i32) -> Self {
+        SharePartition {
+            leader_epoch,
+            state_epoch: 0,
+            start_offset,
+            end_offset: start_offset,
+            records: BTreeMap::new(),
+        }
+    }
+
+    /// Load the persisted state. Records acquired back then were persisted as available.
+    fn from_snapshot(snapshot: &ShareSnapshotValue, leader_epoch: i32) -> Self {
+        let mut partition = SharePartition::new(snapshot.start_offset, leader_epoch);
+        partition.state_epoch = snapshot.state_epoch + 1;
+        for batch in &snapshot.state_batches {
+            let state = RecordState::from_code(batch.delivery_state)
+                .filter(|&state| state != RecordState::Acquired)
+                .unwrap_or(RecordState::Available);
+            for offset in batch.first_offset.max(snapshot.start_offset)..=batch.last_offset {
+                partition
+                    .records
+                    .insert(offset, InFlightRecord::new(state, batch.delivery_count));
+            }
+            partition.end_offset = partition.end_offset.max(batch.last_offset + 1);
+        }
+        partition.advance_start();
+        partition
+    }
+
+    fn snapshot(&self, snapshot_epoch: i32) -> ShareSnapshotValue {
+        let mut state_batches: Vec<StateBatch> = vec![];
+        for (&offset, record) in &self.records {
+            let state = match record.state {
+                RecordState::Acquired => RecordState::Available,
+                state => state,
+            };
+            match state_batches.last_mut() {
+                Some(last)
+                    if last.last_offset + 1 == offset
+                        && last.delivery_state == state.code()
+                        && last.delivery_count == record.delivery_count =>
+                {
+                    last.last_offset = offset
+                }
+                _ => state_batches.push(StateBatch {
+                    first_offset: offset,
+                    last_offset: offset,
+                    delivery_state: state.code(),
+                    delivery_count: record.delivery_count,
+                }),
+            }
+        }
+        ShareSnapshotValue {
+            snapshot_epoch,
+            state_epoch: self.state_epoch,
+            leader_epoch: self.leader_epoch,
+            start_offset: self.start_offset,
+            state_batches,
+        }
+    }
+
+    /// Release the records whose acquisition lock timed out.
+    fn release_expired(&mut self, now: Instant, delivery_count_limit: i16) -> bool {
+        let mut released = false;
+        for record in self.records.values_mut() {
+            if record.state == RecordState::Acquired && record.lock_deadline <= now {
+                record.release(delivery_count_limit);
+                released = true;
+            }
+        }
+        if released {
+            self.advance_start();
+        }
+        released
+    }
+
+    /// Release every record the member acquired.
+    fn release_member(&mut self, member_id: &str, delivery_count_limit: i16) -> bool {
+        let mut released = false;
+        for record in self.records.values_mut() {
+            if record.state == RecordState::Acquired && record.member_id == member_id {
+                record.release(delivery_count_limit);
+                released = true;
+            }
+        }
+        if released {
+            self.advance_start();
+        }
+        released
+    }
+
+    /// Move the start offset past the records that are done with.
+    fn advance_start(&mut self) {
+        while let Some(entry) = self.records.first_entry() {
+            if *entry.key() != self.start_offset
+                || !matches!(
+                    entry.get().state,
+                    RecordState::Acknowledged | RecordState::Archived
+                )
+            {
+                break;
+            }
+            entry.remove();
+            self.start_offset += 1;
+        }
+        self.end_offset = self.end_offset.max(self.start_offset);
+    }
+
+    /// Skip the records the log no longer holds.
+    fn move_start_to(&mut self, log_start_offset: i64) -> bool {
+        if log_start_offset <= self.start_offset {
+            return false;
+        }
+        self.records = self.records.split_off(&log_start_offset);
+        self.start_offset = log_start_offset;
+        self.end_offset = self.end_offset.max(log_start_offset);
+        self.advance_start();
+        true
+    }
+
+    /// Offset to read from for more records to deliver: the first available record,
+    /// else the end offset.
+    fn next_fetch_offset(&self) -> i64 {
+        self.records
+            .iter()
+            .find(|(_, record)| record.state == RecordState::Available)
+            .map_or(self.end_offset, |(&offset, _)| offset)
+    }
+
+    /// Acquire for the member up to `max_records` available records of the batches,
+    /// never letting the partition hold more than `max_in_flight` records. Returns the
+    /// batches holding records acquired, with the records they hold.
+    fn acquire(
+        &mut self,
+        member_id: &str,
+        records: &[u8],
+        max_records: usize,
+        max_in_flight: i64,
+        lock_deadline: Instant,
+    ) -> ShareAcquisition {
+        let mut acquisition = ShareAcquisition::default();
+        let mut count = 0;
+        'batches: for batch in log_manager::batch_headers(records) {
+            if count >= max_records {
+                break;
+            }
+            if batch.last_offset < self.start_offset {
+                continue;
+            }
+            // Offsets the log skips hold no record to deliver
+            if batch.base_offset > self.end_offset {
+                for offset in self.end_offset..batch.base_offset {
+                    let gap = InFlightRecord::new(RecordState::Archived, 0);
+                    self.records.insert(offset, gap);
+                }
+                self.end_offset = batch.base_offset;
+            }
+            let mut acquired_any = false;
+            for offset in batch.base_offset.max(self.start_offset)..=batch.last_offset {
+                if count >= max_records {
+                    break;
+                }
+                if offset >= self.end_offset {
+                    if offset - self.start_offset >= max_in_flight {
+                        if acquired_any {
+                            let range = batch.position..batch.position + batch.size;
+                            acquisition.records.extend(&records[range]);
+                        }
+                        break 'batches;
+                    }
+                    self.end_offset = offset + 1;
+                    self.records
+                        .insert(offset, InFlightRecord::new(RecordState::Available, 0));
+                }
+                let Some(record) = self.records.get_mut(&offset) else {
+                    continue;
+                };
+                if record.state != RecordState::Available {
+                    continue;
+                }
+                record.state = RecordState::Acquired;
+                record.delivery_count += 1;
+                record.member_id = member_id.to_string();
+                record.lock_deadline = lock_deadline;
+                match acquisition.acquired.last_mut() {
+                    Some(last)
+                        if last.last_offset + 1 == offset
+                            && last.delivery_count == record.delivery_count =>
+                    {
+                        last.last_offset = offset
+                    }
+                    _ => acquisition.acquired.push(AcquiredRecords {
+                        first_offset: offset,
+                        last_offset: offset,
+                        delivery_count: record.delivery_count,
+                    }),
+                }
+                count += 1;
+                acquired_any = true;
+            }
+            if acquired_any {
+                acquisition
+                    .records
+                    .extend(&records[batch.position..batch.position + batch.size]);
+            }
+        }
+        self.advance_start();
+        acquisition
+    }
+
+    /// Apply an acknowledgement of the member. Every record it covers must be acquired
+    /// by the member, otherwise none is acknowledged.
+    fn acknowledge(
+        &mut self,
+        member_id: &str,
+        acknowledgement: &Acknowledgement,
+        delivery_count_limit: i16,
+    ) -> Result<(), i16> {
+        let Acknowledgement {
+            first_offset,
+            last_offset,
+            ref acknowledge_types,
+        } = *acknowledgement;
+        let count = last_offset - first_offset + 1;
+        if count <= 0 || (acknowledge_types.len() != 1 && acknowledge_types.len() as i64 != count) {
+            return Err(error::INVALID_REQUEST);
+        }
+        let types = acknowledge_types
+            .iter()
+            .map(|&code| AcknowledgeType::from_code(code))
+            .collect::<Option<Vec<_>>>()
+            .ok_or(error::INVALID_REQUEST)?;
+        for offset in first_offset..=last_offset {
+            let acquired = self.records.get(&offset).is_some_and(|record| {
+                record.state == RecordState::Acquired && record.member_id == member_id
+            });
+            if !acquired {
+                return Err(error::INVALID_RECORD_STATE);
+            }
+        }
+        for (i, offset) in (first_offset..=last_offset).enumerate() {
+            let record = self.records.get_mut(&offset).expect("checked above");
+            match types[if types.len() == 1 { 0 } else { i }] {
+                AcknowledgeType::Accept => {
+                    record.state = RecordState::Acknowledged;
+                    record.member_id.clear();
+                }
+                AcknowledgeType::Release => record.release(delivery_count_limit),
+                AcknowledgeType::Gap | AcknowledgeType::Reject => {
+                    record.state = RecordState::Archived;
+                    record.member_id.clear();
+                }
+            }
+        }
+        self.advance_start();
+        Ok(())
+    }
+}
+
+/// Partitions a member of a share group fetches from, whose epoch orders its requests.
+#[derive(Debug)]
+struct ShareSession {
+    /// Epoch the next request of the member must carry
+    next_epoch: i32,
+    partitions: BTreeSet<(Uuid, i32)>,
+}
+
+/// Delivery state of the share partitions this broker leads. The state of each is kept
+/// by its leader, which persists it to the share-group state log of its first log
+/// directory so that a restart does not deliver acknowledged records again.
+///
+/// Its partitions lock comes before the state log, and is never held while reading
+/// records or taking the metadata image.
+pub struct SharePartitionManager {
+    config: ShareGroupConfig,
+    partitions: Mutex<HashMap<SharePartitionKey, SharePartition>>,
+    /// Share sessions keyed by group and member id
+    sessions: Mutex<HashMap<(String, String), ShareSession>>,
+    state_log: Mutex<ShareGroupStateLog>,
+}
+
+impl SharePartitionManager {
+    pub fn open(config: &BrokerConfig) -> Result<Self> {
+        let state_log = ShareGroupStateLog::open(
+            &config.metadata_log_dir.join(SHARE_GROUP_STATE_DIR),
+            config.share_group.updates_per_snapshot,
+        )?;
+        Ok(SharePartitionManager {
+            config: config.share_group.clone(),
+            partitions: Mutex::new(HashMap::new()),
+            sessions: Mutex::new(HashMap::new()),
+            state_log: Mutex::new(state_log),
+        })
+    }
+
+    /// Move the share session of the member to the next request. Epoch 0 opens a new
+    /// session, -1 closes it, and any other epoch must be the one the session expects.
+    /// Returns the partitions of the session, those of the closed session for -1.
+    pub fn update_session(
+        &self,
+        group_id: &str,
+        member_id: &str,
+        epoch: i32,
+        added: &[(Uuid, i32)],
+        forgotten: &[(Uuid, i32)],
+    ) -> Result<Vec<(Uuid, i32)>, i16> {
+        let mut sessions = self.sessions.lock().unwrap();
+        let key = (group_id.to_string(), member_id.to_string());
+        if epoch == 0 {
+            let session = ShareSession {
+                next_epoch: 1,
+                partitions: added.iter().copied().collect(),
+            };
+            let partitions = session.partitions.iter().copied().collect();
+            sessions.insert(key, session);
+            return Ok(partitions);
+        }
+        let Some(session) = sessions.get_mut(&key) else {
+            return Err(error::SHARE_SESSION_NOT_FOUND);
+        };
+        if epoch == -1 {
+            let session = sessions.remove(&key).expect("looked up above");
+            return Ok(session.partitions.into_iter().collect());
+        }
+        if epoch != session.next_epoch {
+            return Err(error::INVALID_SHARE_SESSION_EPOCH);
+        }
+        session.next_epoch = if epoch == i32::MAX { 1 } else { epoch + 1 };
+        session.partitions.extend(added.iter().copied());
+        for partition in forgotten {
+            session.partitions.remove(partition);
+        }
+        Ok(session.partitions.iter().copied().collect())
+    }
+
+    /// Acquire for the member records of a partition this broker leads, reading from
+    /// the first record available for delivery.
+    pub fn acquire(
+        &self,
+        broker: &Broker,
+        key: &SharePartitionKey,
+        member_id: &str,
+        max_records: usize,
+        max_bytes: usize,
+    ) -> Result<ShareAcquisition, i16> {
+        let (replica, leader_epoch) = led_partition(broker, key)?;
+        let fetch_offset = self.with_partition(broker, key, &replica, leader_epoch, |p| {
+            Ok((false, p.next_fetch_offset()))
+        })?;
+        let fetch = FetchPartition {
+            partition: key.partition,
+            current_leader_epoch: -1,
+            fetch_offset,
+            last_fetched_epoch: -1,
+            log_start_offset: -1,
+            partition_max_bytes: max_bytes as i32,
+            tag_buffer: TagSection(None),
+        };
+        let records = match broker
+            .replicas
+            .read_records(broker, &replica, -1, "", &fetch, max_bytes)
+        {
+            Ok(read) => read.records,
+            // Retention removed records not delivered yet
+            Err(error::OFFSET_OUT_OF_RANGE) => {
+                let log_start_offset = broker
+                    .logs
+                    .lock()
+                    .unwrap()
+                    .log_start_offset(&replica)
+                    .map_err(|e| e.error_code())?;
+                self.with_partition(broker, key, &replica, leader_epoch, |p| {
+                    Ok((p.move_start_to(log_start_offset), ()))
+                })?;
+                return Ok(ShareAcquisition::default());
+            }
+            Err(error_code) => return Err(error_code),
+        };
+        if records.is_empty() {
+            return Ok(ShareAcquisition::default());
+        }
+        let lock_deadline =
+            Instant::now() + Duration::from_millis(self.config.record_lock_duration_ms);
+        let max_in_flight = self.config.partition_max_record_locks;
+        self.with_partition(broker, key, &replica, leader_epoch, |p| {
+            let acquisition = p.acquire(
+                member_id,
+                &records,
+                max_records,
+                max_in_flight,
+                lock_deadline,
+            );
+            Ok((!acquisition.acquired.is_empty(), acquisition))
+        })
+    }
+
+    /// Acknowledge records the member acquired from a partition this broker leads.
+    pub fn acknowledge(
+        &self,
+        broker: &Broker,
+        key: &SharePartitionKey,
+        member_id: &str,
+        acknowledgements: &[Acknowledgement],
+    ) -> Result<(), i16> {
+        let (replica, leader_epoch) = led_partition(broker, key)?;
+        let limit = self.config.delivery_count_limit;
+        self.with_partition(broker, key, &replica, leader_epoch, |p| {
+            let mut result = Ok(());
+            let mut changed = false;
+            for acknowledgement in acknowledgements {
+                result = p.acknowledge(member_id, acknowledgement, limit);
+                if result.is_err() {
+                    break;
+                }
+                changed = true;
+            }
+            Ok((changed, result))
+        })?
+    }
+
+    /// Release the records the member holds in the partitions, as its session closed.
+    pub fn release_member(
+        &self,
+        broker: &Broker,
+        group_id: &str,
+        member_id: &str,
+        partitions: &[(Uuid, i32)],
+    ) {
+        let limit = self.config.delivery_count_limit;
+        for &(topic_id, partition) in partitions {
+            let key = SharePartitionKey {
+                group_id: group_id.to_string(),
+                topic_id,
+                partition,
+            };
+            let Ok((replica, leader_epoch)) = led_partition(broker, &key) else {
+                continue;
+            };
+            let _ = self.with_partition(broker, &key, &replica, leader_epoch, |p| {
+                Ok((p.release_member(member_id, limit), ()))
+            });
+        }
+    }
+
+    /// Run `f` on the state of the share partition, loading it first if needed, and
+    /// persist it when `f` reports a change. Acquisition locks that timed out are
+    /// released beforehand.
+    fn with_partition<T>(
+        &self,
+        broker: &Broker,
+        key: &SharePartitionKey,
+        replica: &TopicPartition,
+        leader_epoch: i32,
+        f: impl FnOnce(&mut SharePartition) -> Result<(bool, T), i16>,
+    ) -> Result<T, i16> {
+        // A partition consumed for the first time starts at its high watermark, as
+        // with the default `share.auto.offset.reset` of latest
+        let high_watermark = broker.logs.lock().unwrap().high_watermark(replica);
+        let mut partitions = self.partitions.lock().unwrap();
+        let stale = partitions
+            .get(key)
+            .is_some_and(|partition| partition.leader_epoch != leader_epoch);
+        if stale || !partitions.contains_key(key) {
+            // Locks taken under an earlier leadership no longer hold
+            let state_log = self.state_log.lock().unwrap();
+            let partition = match state_log.states.get(key) {
+                Some(snapshot) => SharePartition::from_snapshot(snapshot, leader_epoch),
+                None => SharePartition::new(high_watermark.max(0), leader_epoch),
+            };
+            partitions.insert(key.clone(), partition);
+        }
+        let partition = partitions.get_mut(key).expect("inserted above");
+        let expired = partition.release_expired(Instant::now(), self.config.delivery_count_limit);
+        let (changed, value) = f(partition)?;
+        if changed || expired {
+            let mut state_log = self.state_log.lock().unwrap();
+            let snapshot = partition.snapshot(state_log.snapshot_epoch);
+            if let Err(e) = state_log.write(key, snapshot) {
+                eprintln!(
+                    "Failed to persist the share partition {}:{}:{}: {e:#}",
+                    key.group_id, key.topic_id, key.partition
+                );
+                return Err(error::KAFKA_STORAGE_ERROR);
+            }
+        }
+        Ok(value)
+    }
+}
+
+/// Name and leader epoch of a partition this broker leads.
+fn led_partition(broker: &Broker, key: &SharePartitionKey) -> Result<(TopicPartition, i32), i16> {
+    let metadata = broker.metadata.read().unwrap();
+    let name = metadata
+        .topic_name(key.topic_id)
+        .ok_or(error::UNKNOWN_TOPIC_ID)?;
+    let (_, registration) = metadata
+        .partition(name, key.partition)
+        .ok_or(error::UNKNOWN_TOPIC_OR_PARTITION)?;
+    if registration.leader != broker.config.node_id {
+        return Err(error::NOT_LEADER_OR_FOLLOWER);
+    }
+    Ok((
+        TopicPartition::new(name, key.partition),
+        registration.leader_epoch,
+    ))
+}
+
+/// Key of the ShareSnapshot records of the share-group state log.
+#[derive(Debug)]
+struct ShareSnapshotKey(SharePartitionKey);
+
+impl ShareSnapshotKey {
+    const RECORD_TYPE: i16 = 0;
+}
+
+impl Serializable for ShareSnapshotKey {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(Self::RECORD_TYPE.to_be_bytes());
+        buf.extend(CompactString(Some(self.0.group_id.clone())).serialize());
+        buf.extend(self.0.topic_id.serialize());
+        buf.extend(self.0.partition.to_be_bytes());
+        buf.extend(TagSection(None).serialize());
+        buf
+    }
+
+    fn deserialize(bytes: &[u8]) -> Result<(Self, &[u8])> {
+        let (record_type, bytes) = i16::deserialize(bytes)?;
+        if record_type != Self::RECORD_TYPE {
+            bail!("Unknown share-group state record type {record_type}");
+        }
+        let (group_id, bytes) = CompactString::deserialize(bytes)?;
+        let (topic_id, bytes) = Uuid::deserialize(bytes)?;
+        let (partition, bytes) = i32::deserialize(bytes)?;
+        let (_, bytes) = TagSection::deserialize(bytes)?;
+        Ok((
+            ShareSnapshotKey(SharePartitionKey {
+                group_id: group_id.0.unwrap_or_default(),
+                topic_id,
+                partition,
+            }),
+            bytes,
+        ))
+    }
+}
+
+/// Records from `first_offset` to `last_offset` in the same state, delivered as many
+/// times.
+#[derive(Debug, Clone)]
+struct StateBatch {
+    first_offset: i64,
+    last_offset: i64,
+    delivery_state: i8,
+    delivery_count: i16,
+}
+
+impl Serializable for StateBatch {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(self.first_offset.to_be_bytes());
+        buf.extend(self.last_offset.to_be_bytes());
+        buf.extend(self.delivery_state.to_be_bytes());
+        buf.extend(self.delivery_count.to_be_bytes());
+        buf.extend(TagSection(None).serialize());
+        buf
+    }
+
+    fn deserialize(bytes: &[u8]) -> Result<(Self, &[u8])> {
+        let (first_offset, bytes) = i64::deserialize(bytes)?;
+        let (last_offset, bytes) = i64::deserialize(bytes)?;
+        let (delivery_state, bytes) = i8::deserialize(bytes)?;
+        let (delivery_count, bytes) = i16::deserialize(bytes)?;
+        let (_, bytes) = TagSection::deserialize(bytes)?;
+        Ok((
+            StateBatch {
+                first_offset,
+                last_offset,
+                delivery_state,
+                delivery_count,
+            },
+            bytes,
+        ))
+    }
+}
+
+/// State of a share partition as persisted, version 0 of the ShareSnapshot value.
+#[derive(Debug, Clone)]
+struct ShareSnapshotValue {
+    /// Snapshot of the state log the value was written after
+    snapshot_epoch: i32,
+    state_epoch: i32,
+    leader_epoch: i32,
+    start_offset: i64,
+    /// Records past the start offset, acknowledged, archived or delivered before
+    state_batches: Vec<StateBatch>,
+}
+
+impl ShareSnapshotValue {
+    const VERSION: i16 = 0;
+}
+
+impl Serializable for ShareSnapshotValue {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(Self::VERSION.to_be_bytes());
+        buf.extend(self.snapshot_epoch.to_be_bytes());
+        buf.extend(self.state_epoch.to_be_bytes());
+        buf.extend(self.leader_epoch.to_be_bytes());
+        buf.extend(self.start_offset.to_be_bytes());
+        buf.extend(CompactArray(Some(self.state_batches.clone())).serialize());
+        buf.extend(TagSection(None).serialize());
+        buf
+    }
+
+    fn deserialize(bytes: &[u8]) -> Result<(Self, &[u8])> {
+        let (version, bytes) = i16::deserialize(bytes)?;
+        if version != Self::VERSION {
+            bail!("Unsupported ShareSnapshot value version {version}");
+        }
+        let (snapshot_epoch, bytes) = i32::deserialize(bytes)?;
+        let (state_epoch, bytes) = i32::deserialize(bytes)?;
+        let (leader_epoch, bytes) = i32::deserialize(bytes)?;
+        let (start_offset, bytes) = i64::deserialize(bytes)?;
+        let (state_batches, bytes) = CompactArray::<StateBatch>::deserialize(bytes)?;
+        let (_, bytes) = TagSection::deserialize(bytes)?;
+        Ok((
+            ShareSnapshotValue {
+                snapshot_epoch,
+                state_epoch,
+                leader_epoch,
+                start_offset,
+                state_batches: state_batches.0.unwrap_or_default(),
+            },
+            bytes,
+        ))
+    }
+}
+
+/// Log of the states of the share partitions this broker led, each record holding the
+/// whole state of one. Once enough were written since the last snapshot, the latest
+/// state of every share partition is written to a new snapshot and the segments it
+/// covers are deleted, as the metadata log does.
+struct ShareGroupStateLog {
+    log: MetadataLog,
+    /// Latest state of every share partition of the log
+    states: HashMap<SharePartitionKey, ShareSnapshotValue>,
+    /// Number of snapshots taken, recorded in the values written after the latest
+    snapshot_epoch: i32,
+    updates_since_snapshot: usize,
+    /// 0 to never snapshot
+    updates_per_snapshot: usize,
+}
+
+impl ShareGroupStateLog {
+    /// Open the state log and replay its latest snapshot, then the records after it.
+    fn open(dir: &Path, updates_per_snapshot: usize) -> Result<Self> {
+        let log = MetadataLog::open(dir, STATE_LOG_SEGMENT_BYTES)?;
+        let snapshot_batches = match log.snapshot() {
+            Some(snapshot) => log.read_snapshot(snapshot)?,
+            None => vec![],
+        };
+        let snapshot_epoch = log.snapshot().map_or(0, |snapshot| snapshot.epoch);
+        let start_offset = log.snapshot().map_or(0, |snapshot| snapshot.end_offset);
+        let batches = log.batches(start_offset, log.end_offset())?;
+        let updates_since_snapshot = batches.iter().map(|batch| batch.records.len()).sum();
+        let mut states = HashMap::new();
+        for batch in snapshot_batches.into_iter().chain(batches) {
+            for record in batch.records {
+                let Some(key) = record.key else { continue };
+                let key = match ShareSnapshotKey::deserialize(&key) {
+                    Ok((key, _)) => key.0,
+                    Err(e) => {
+                        eprintln!("Skipping undecodable share-group state record: {e}");
+                        continue;
+                    }
+                };
+                match record.value.as_deref().map(ShareSnapshotValue::deserialize) {
+                    Some(Ok((value, _))) => {
+                        states.insert(key, value);
+                    }
+                    // A tombstone removes the state
+                    None => {
+                        states.remove(&key);
+                    }
+                    Some(Err(e)) => eprintln!("Skipping undecodable share-group state: {e}"),
+                }
+            }
+        }
+        Ok(ShareGroupStateLog {
+            log,
+            states,
+            snapshot_epoch,
+            updates_since_snapshot,
+            updates_per_snapshot,
+        })
+    }
+
+    /// Durably append the state of a share partition.
+    fn write(&mut self, key: &SharePartitionKey, value: ShareSnapshotValue) -> Result<()> {
+        self.log.append(state_batch(0, vec![(key, &value)]))?;
+        self.states.insert(key.clone(), value);
+        self.updates_since_snapshot += 1;
+        if self.updates_per_snapshot > 0 && self.updates_since_snapshot >= self.updates_per_snapshot
+        {
+            self.take_snapshot()?;
+        }
+        Ok(())
+    }
+
+    fn take_snapshot(&mut self) -> Result<()> {
+        self.snapshot_epoch += 1;
+        let snapshot_id = SnapshotId {
+            end_offset: self.log.end_offset(),
+            epoch: self.snapshot_epoch,
+        };
+        let mut states: Vec<_> = self.states.iter().collect();
+        states.sort_by(|a, b| a.0.cmp(b.0));
+        let batch = state_batch(0, states);
+        self.log.write_snapshot(snapshot_id, &[batch])?;
+        self.updates_since_snapshot = 0;
+        Ok(())
+    }
+}
+
+/// A batch of ShareSnapshot records.
+fn state_batch(
+    base_offset: i64,
+    states: Vec<(&SharePartitionKey, &ShareSnapshotValue)>,
+) -> RecordBatch {
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_millis() as i64);
+    let values = states.iter().map(|(_, value)| value.serialize()).collect();
+    let mut batch = RecordBatch::new(base_offset, 0, timestamp, values);
+    for (record, (key, _)) in batch.records.iter_mut().zip(&states) {
+        record.key = Some(ShareSnapshotKey((*key).clone()).serialize());
+    }
+    batch
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LIMIT: i16 = 3;
+
+    /// A batch of `count` records starting at `base_offset`, as the log stores it.
+    fn records(base_offset: i64, count: usize) -> Vec<u8> {
+        let values = (0..count).map(|i| vec![i as u8]).collect();
+        RecordBatch::new(base_offset, 0, 0, values).serialize()
+    }
+
+    fn acknowledgement(
+        first_offset: i64,
+        last_offset: i64,
+        kind: AcknowledgeType,
+    ) -> Acknowledgement {
+        let code = match kind {
+            AcknowledgeType::Gap => 0,
+            AcknowledgeType::Accept => 1,
+            AcknowledgeType::Release => 2,
+            AcknowledgeType::Reject => 3,
+        };
+        Acknowledgement {
+            first_offset,
+            last_offset,
+            acknowledge_types: vec![code],
+        }
+    }
+
+    fn acquire(partition: &mut SharePartition, member_id: &str) -> Vec<AcquiredRecords> {
+        let deadline = Instant::now() + Duration::from_secs(30);
+        partition
+            .acquire(member_id, &records(0, 3), 10, 100, deadline)
+            .acquired
+    }
+
+    fn state(partition: &SharePartition, offset: i64) -> (RecordState, i16) {
+        let record = &partition.records[&offset];
+        (record.state, record.delivery_count)
+    }
+
+    #[test]
+    fn accepting_acquired_records_moves_the_start_offset_past_them() {
+        let mut partition = SharePartition::new(0, 0);
+        let acquisition = partition.acquire(
+            "a",
+            &records(0, 3),
+            10,
+            100,
+            Instant::now() + Duration::from_secs(30),
+        );
+        assert_eq!(
+            acquisition.acquired,
+            vec![AcquiredRecords {
+                first_offset: 0,
+                last_offset: 2,
+                delivery_count: 1,
+            }]
+        );
+        assert_eq!(acquisition.records, records(0, 3));
+        assert_eq!(partition.end_offset, 3);
+        // Nothing is left to deliver until the log grows
+        assert!(acquire(&mut partition, "b").is_empty());
+        assert_eq!(
+            partition.acknowledge("b", &acknowledgement(0, 2, AcknowledgeType::Accept), LIMIT),
+            Err(error::INVALID_RECORD_STATE)
+        );
+
+        partition
+            .acknowledge("a", &acknowledgement(0, 1, AcknowledgeType::Accept), LIMIT)
+            .unwrap();
+        assert_eq!(partition.start_offset, 2);
+        assert_eq!(state(&partition, 2), (RecordState::Acquired, 1));
+        assert_eq!(partition.next_fetch_offset(), 3);
+    }
+
+    #[test]
+    fn released_records_are_delivered_again_with_a_higher_delivery_count() {
+        let mut partition = SharePartition::new(0, 0);
+        acquire(&mut partition, "a");
+        partition
+            .acknowledge("a", &acknowledgement(1, 1, AcknowledgeType::Release), LIMIT)
+            .unwrap();
+        assert_eq!(state(&partition, 1), (RecordState::Available, 1));
+        assert_eq!(partition.next_fetch_offset(), 1);
+
+        assert_eq!(
+            acquire(&mut partition, "b"),
+            vec![AcquiredRecords {
+                first_offset: 1,
+                last_offset: 1,
+                delivery_count: 2,
+            }]
+        );
+        assert_eq!(partition.records[&1].member_id, "b");
+    }
+
+    #[test]
+    fn archives_records_delivered_as_many_times_as_the_limit() {
+        let mut partition = SharePartition::new(0, 0);
+        for delivery in 1..=LIMIT {
+            let acquired = acquire(&mut partition, "a");
+            assert_eq!(acquired[0].first_offset, 0);
+            assert_eq!(acquired[0].delivery_count, delivery);
+            partition
+                .acknowledge("a", &acknowledgement(0, 2, AcknowledgeType::Release), LIMIT)
+                .unwrap();
+        }
+        // Archived records are done with, like acknowledged ones
+        assert!(partition.records.is_empty());
+        assert_eq!(partition.start_offset, 3);
+        assert!(acquire(&mut partition, "a").is_empty());
+    }
+
+    #[test]
+    fn rejected_records_are_archived() {
+        let mut partition = SharePartition::new(0, 0);
+        acquire(&mut partition, "a");
+        partition
+            .acknowledge("a", &acknowledgement(1, 1, AcknowledgeType::Reject), LIMIT)
+            .unwrap();
+        assert_eq!(state(&partition, 1), (RecordState::Archived, 1));
+        assert_eq!(partition.start_offset, 0);
+    }
+
+    #[test]
+    fn records_become_available_again_when_their_lock_times_out() {
+        let mut partition = SharePartition::new(0, 0);
+        let deadline = Instant::now() + Duration::from_millis(100);
+        partition.acquire("a", &records(0, 3), 2, 100, deadline);
+        assert_eq!(partition.end_offset, 2);
+
+        assert!(!partition.release_expired(deadline - Duration::from_millis(1), LIMIT));
+        assert!(partition.release_expired(deadline, LIMIT));
+        assert_eq!(state(&partition, 0), (RecordState::Available, 1));
+        assert_eq!(partition.records[&0].member_id, "");
+        assert_eq!(
+            partition.acknowledge("a", &acknowledgement(0, 0, AcknowledgeType::Accept), LIMIT),
+            Err(error::INVALID_RECORD_STATE)
+        );
+    }
+
+    #[test]
+    fn never_holds_more_than_the_record_lock_limit() {
+        let mut partition = SharePartition::new(0, 0);
+        let deadline = Instant::now() + Duration::from_secs(30);
+        let acquisition = partition.acquire("a", &records(0, 3), 10, 2, deadline);
+        assert_eq!(acquisition.acquired[0].last_offset, 1);
+        assert_eq!(partition.end_offset, 2);
+    }
+
+    #[test]
+    fn rebuilds_the_state_from_the_state_log_after_a_restart() {
+        let dir = std::env::temp_dir().join(format!("share-state-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let key = SharePartitionKey {
+            group_id: "group".to_string(),
+            topic_id: Uuid::from_u128(1),
+            partition: 0,
+        };
+        let mut partition = SharePartition::new(0, 5);
+        acquire(&mut partition, "a");
+        partition
+            .acknowledge("a", &acknowledgement(0, 0, AcknowledgeType::Accept), LIMIT)
+            .unwrap();
+        partition
+            .acknowledge("a", &acknowledgement(2, 2, AcknowledgeType::Reject), LIMIT)
+            .unwrap();
+
+        {
+            // A snapshot every second update, so the state is rebuilt from both a
+            // snapshot and the records after it
+            let mut state_log = ShareGroupStateLog::open(&dir, 2).unwrap();
+            for _ in 0..3 {
+                let snapshot = partition.snapshot(state_log.snapshot_epoch);
+                state_log.write(&key, snapshot).unwrap();
+            }
+            assert_eq!(state_log.snapshot_epoch, 1);
+        }
+
+        let state_log = ShareGroupStateLog::open(&dir, 2).unwrap();
+        assert_eq!(state_log.snapshot_epoch, 1);
+        assert_eq!(state_log.updates_since_snapshot, 1);
+        let restored = SharePartition::from_snapshot(&state_log.states[&key], 6);
+        assert_eq!(restored.state_epoch, 1);
+        assert_eq!(restored.leader_epoch, 6);
+        assert_eq!(restored.start_offset, 1);
+        assert_eq!(restored.end_offset, 3);
+        // The record still acquired is delivered again, the others are not
+        assert_eq!(state(&restored, 1), (RecordState::Available, 1));
+        assert_eq!(state(&restored, 2), (RecordState::Archived, 1));
+        assert_eq!(restored.next_fetch_offset(), 1);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}